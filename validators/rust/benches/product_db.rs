@@ -0,0 +1,62 @@
+// Product snapshot lookups
+//
+// Loads a generated snapshot of a million products, then times barcode
+// lookups through the `Resolver` interface, hits and misses mixed. Run with
+// `cargo bench --bench product_db --features product-db`; the snapshot size
+// can be set with RCIP_BENCH_PRODUCTS. Exits 1 when the slowest round
+// averages a millisecond or more per lookup.
+
+use rcip_validator::resolve::{ProductDb, Resolver};
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 5;
+const LOOKUPS: usize = 100_000;
+const BUDGET: Duration = Duration::from_millis(1);
+
+/// Barcode of the i-th generated product
+fn gtin(i: usize) -> String {
+    format!("{:013}", 4_000_000_000_000u64 + i as u64 * 7)
+}
+
+fn main() {
+    let count = std::env::var("RCIP_BENCH_PRODUCTS").ok().and_then(|n| n.parse().ok()).unwrap_or(1_000_000);
+    let allergens = ["milk", "eggs", "gluten", "tree-nuts", "soybeans"];
+    let mut snapshot = String::from("{\"format\":\"rcip-product-db\",\"version\":1,\"source\":\"bench\"}\n");
+    for i in 0..count {
+        snapshot.push_str(&format!(
+            "{{\"gtin\":\"{}\",\"name\":\"Product {}\",\"allergens\":[\"{}\"],\"categories\":[\"category-{}\"]}}\n",
+            gtin(i),
+            i,
+            allergens[i % allergens.len()],
+            i % 500
+        ));
+    }
+
+    let start = Instant::now();
+    let db = ProductDb::from_reader(snapshot.as_bytes()).expect("snapshot");
+    let loaded = start.elapsed();
+    drop(snapshot);
+
+    // A fixed stride through the snapshot, every fourth id unknown
+    let ids: Vec<String> = (0..LOOKUPS)
+        .map(|n| if n % 4 == 3 { format!("{:013}", 9_000_000_000_000u64 + n as u64) } else { gtin(n * 7919 % count) })
+        .collect();
+    let slowest = (0..ROUNDS)
+        .map(|_| {
+            let start = Instant::now();
+            let found = ids.iter().filter(|id| db.resolve("GTIN", id).unwrap().is_some()).count();
+            std::hint::black_box(found);
+            start.elapsed()
+        })
+        .max()
+        .unwrap();
+    let per_lookup = slowest / LOOKUPS as u32;
+
+    println!("products:   {}", db.len());
+    println!("load:       {:.2?}", loaded);
+    println!("per lookup: {:.2?} (slowest of {} rounds of {})", per_lookup, ROUNDS, LOOKUPS);
+    if per_lookup >= BUDGET {
+        eprintln!("lookups average {:.2?}, over the {:?} budget", per_lookup, BUDGET);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,44 @@
+// A Lambda-style handler. The validator is compiled once per process from the
+// bundled schema and shared by every invocation; nothing is read from disk.
+//
+//     cargo run --example lambda_handler --features no-fs < recipe.rcip
+//
+// A real deployment registers `handle` with its runtime; here stdin stands in
+// for the request body, answered from four threads at once to show that the
+// shared validator needs no locking.
+
+use rcip_validator::warm::global_validator;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::thread;
+
+/// Validate one request body and build the response
+fn handle(body: &[u8]) -> Value {
+    let validator = match global_validator("0.1") {
+        Ok(validator) => validator,
+        Err(e) => return json!({"statusCode": 500, "body": e.to_string()}),
+    };
+    let result = validator.validate_bytes(body);
+    json!({
+        "statusCode": if result.valid { 200 } else { 422 },
+        "body": {
+            "valid": result.valid,
+            "errors": result.errors,
+            "warnings": result.warnings,
+            "notices": result.notices,
+        },
+    })
+}
+
+fn main() {
+    let mut body = Vec::new();
+    std::io::stdin().read_to_end(&mut body).expect("failed to read the request body");
+
+    let responses: Vec<Value> = thread::scope(|s| {
+        let handles: Vec<_> = (0..4).map(|_| s.spawn(|| handle(&body))).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    for response in responses {
+        println!("{}", response);
+    }
+}
@@ -0,0 +1,54 @@
+// Example rule pack: a house policy that forbids pork
+//
+// Build with
+//
+//     cargo build --release --target wasm32-unknown-unknown
+//
+// and run with `rcip-validator --rule-pack acme.wasm recipe.rcip` after
+// renaming `acme_rule_pack.wasm`; the file stem becomes the code namespace.
+
+use serde_json::{json, Value};
+
+const PORK: &[&str] = &["pork", "bacon", "ham", "lard", "prosciutto", "pancetta", "chorizo"];
+
+/// Hand the host a buffer of `len` bytes for the recipe
+#[no_mangle]
+pub extern "C" fn rcip_alloc(len: i32) -> i32 {
+    let mut buffer = Vec::<u8>::with_capacity(len as usize);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr as i32
+}
+
+/// Check the recipe in `ptr..ptr + len`; returns the issue list as `ptr << 32 | len`
+#[no_mangle]
+pub extern "C" fn rcip_rule_run(ptr: i32, len: i32) -> i64 {
+    let input = unsafe { Vec::from_raw_parts(ptr as *mut u8, len as usize, len as usize) };
+    let issues = match serde_json::from_slice::<Value>(&input) {
+        Ok(recipe) => check(&recipe),
+        Err(e) => vec![json!({"code": "BAD-INPUT", "severity": "error", "message": e.to_string()})],
+    };
+
+    let output = Value::Array(issues).to_string().into_bytes().into_boxed_slice();
+    let (out_ptr, out_len) = (output.as_ptr() as i64, output.len() as i64);
+    std::mem::forget(output);
+    (out_ptr << 32) | out_len
+}
+
+fn check(recipe: &Value) -> Vec<Value> {
+    let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+    ingredients
+        .iter()
+        .enumerate()
+        .filter_map(|(index, ingredient)| {
+            let name = ingredient.get("name")?.as_str()?.to_lowercase();
+            let word = PORK.iter().find(|w| name.split(|c: char| !c.is_alphanumeric()).any(|part| part == **w))?;
+            Some(json!({
+                "code": "NO-PORK",
+                "severity": "error",
+                "message": format!("'{}' is {}; pork is not allowed", name, word),
+                "path": format!("/ingredients/{}", index),
+            }))
+        })
+        .collect()
+}
@@ -0,0 +1,587 @@
+// Contains vs. may contain
+//
+// `allergens` on an ingredient lists what it contains. Cross-contact risk is
+// declared separately: `may_contain_allergens` on an ingredient for traces
+// from its supply chain, and `meta.facility_allergens` for everything handled
+// in the kitchen or plant. The two sets are kept apart: traces never count as
+// contained, and a trace of something already contained is not repeated.
+// Diet labels that exclude an allergen are an error when the recipe contains
+// it and a warning when it only may contain it, or when only optional
+// ingredients contain it. Names are compared in their canonical spelling
+// (see `terms`), so "Milk" and "milk" are one allergen.
+//
+// Every contained allergen also carries where it came from. It is declared
+// when an ingredient lists it in `allergens`, whether its author wrote it or
+// a resolver filled it in from product data (`x-enriched-from`, see
+// `resolve::enrich`; the source names the resolver), inferred when only a
+// keyword in an ingredient's name suggests it ("almond" suggests tree-nuts,
+// see `suggest`), and inherited when a component recipe contributed it. Rules that act on
+// contained allergens take a `ProvenancePolicy` naming the kinds they
+// consider; the default considers declared and inherited allergens, so name
+// keywords alone never fail a diet label unless an operator asks for that.
+// A name keyword the policy leaves out still warns about a label it would
+// break, as do names of meat and other animal products under `vegetarian` and
+// `vegan`; names can mislead ("coconut milk"), so these are never errors.
+
+use crate::terms::{self, Vocabulary};
+use crate::{codes, is_optional, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+mod suggest;
+
+pub(crate) use suggest::{check as check_suggestions, name_suggestions, undeclared};
+pub use suggest::suggest_allergens;
+
+/// Allergen identifiers accepted by the schema
+pub const VOCABULARY: &[&str] = &[
+    "milk", "eggs", "fish", "shellfish", "tree-nuts", "peanuts",
+    "wheat", "gluten", "soybeans", "sesame", "celery", "mustard",
+    "molluscs", "lupins", "sulphites", "lactose",
+];
+
+/// Diet labels and the allergens they exclude
+pub(crate) const DIET_EXCLUSIONS: &[(&str, &[&str])] = &[
+    ("gluten-free", &["wheat", "gluten"]),
+    ("dairy-free", &["milk", "lactose"]),
+    ("nut-free", &["tree-nuts", "peanuts"]),
+    ("egg-free", &["eggs"]),
+    ("soy-free", &["soybeans"]),
+    ("fish-free", &["fish"]),
+    ("shellfish-free", &["shellfish", "molluscs"]),
+    ("vegetarian", &["fish", "shellfish", "molluscs"]),
+    ("vegan", &["milk", "lactose", "eggs", "fish", "shellfish", "molluscs"]),
+];
+
+/// Name keywords of animal products that are not allergens, by the diet
+/// labels they break; a keyword matches as in `NAME_KEYWORDS`
+const DIET_NAME_KEYWORDS: &[(&str, &[&str])] = &[
+    ("vegetarian", MEAT_KEYWORDS),
+    ("vegan", MEAT_KEYWORDS),
+    ("vegan", &["honey", "butter", "ghee", "whey", "casein"]),
+];
+
+const MEAT_KEYWORDS: &[&str] = &[
+    "meat", "beef", "veal", "pork", "ham", "bacon", "lamb", "mutton", "chicken", "turkey", "duck", "goose",
+    "venison", "sausage", "salami", "prosciutto", "pancetta", "chorizo", "gelatin", "gelatine", "lard",
+    "suet", "anchovy", "anchovies",
+];
+
+/// Why an allergen is in the aggregate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provenance {
+    Declared,
+    Inferred,
+    Inherited,
+}
+
+impl Provenance {
+    pub const ALL: [Provenance; 3] = [Provenance::Declared, Provenance::Inferred, Provenance::Inherited];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Provenance::Declared => "declared",
+            Provenance::Inferred => "inferred",
+            Provenance::Inherited => "inherited",
+        }
+    }
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Provenance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Provenance::ALL.into_iter().find(|p| p.name() == s).ok_or_else(|| {
+            format!("unknown allergen provenance '{}'; expected declared, inferred or inherited", s)
+        })
+    }
+}
+
+/// One reason an allergen is in the aggregate
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "provenance", rename_all = "kebab-case")]
+pub enum AllergenSource {
+    /// Listed in the ingredient's `allergens`; `resolver` filled them in
+    /// when the author did not
+    Declared {
+        ingredient: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        resolver: Option<String>,
+    },
+    /// Suggested by `basis`, a keyword in the ingredient's name
+    Inferred { ingredient: String, basis: String },
+    /// Contributed by a component recipe
+    Inherited { recipe: String },
+}
+
+impl AllergenSource {
+    pub fn provenance(&self) -> Provenance {
+        match self {
+            AllergenSource::Declared { .. } => Provenance::Declared,
+            AllergenSource::Inferred { .. } => Provenance::Inferred,
+            AllergenSource::Inherited { .. } => Provenance::Inherited,
+        }
+    }
+}
+
+impl fmt::Display for AllergenSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AllergenSource::Declared { ingredient, resolver: None } => f.write_str(ingredient),
+            AllergenSource::Declared { ingredient, resolver: Some(resolver) } => {
+                write!(f, "{} via {}", ingredient, resolver)
+            }
+            AllergenSource::Inferred { ingredient, basis } => write!(f, "{} inferred from \"{}\"", ingredient, basis),
+            AllergenSource::Inherited { recipe } => write!(f, "from {}", recipe),
+        }
+    }
+}
+
+/// An allergen in the aggregate and every reason it is there
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AllergenEntry {
+    pub allergen: String,
+    pub sources: Vec<AllergenSource>,
+}
+
+impl AllergenEntry {
+    /// Whether any of its sources is of a kind the policy considers
+    pub fn considered(&self, policy: &ProvenancePolicy) -> bool {
+        self.sources.iter().any(|s| policy.considers(s.provenance()))
+    }
+}
+
+impl fmt::Display for AllergenEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sources: Vec<String> = self.sources.iter().map(|s| s.to_string()).collect();
+        write!(f, "{} [{}]", self.allergen, sources.join(", "))
+    }
+}
+
+/// The kinds of allergen provenance a rule acts on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenancePolicy {
+    pub considered: BTreeSet<Provenance>,
+}
+
+impl ProvenancePolicy {
+    /// Every kind, for operators who treat inferred allergens as real
+    pub fn all() -> Self {
+        ProvenancePolicy { considered: Provenance::ALL.into_iter().collect() }
+    }
+
+    pub fn considers(&self, provenance: Provenance) -> bool {
+        self.considered.contains(&provenance)
+    }
+}
+
+impl Default for ProvenancePolicy {
+    fn default() -> Self {
+        ProvenancePolicy { considered: [Provenance::Declared, Provenance::Inherited].into_iter().collect() }
+    }
+}
+
+impl fmt::Display for ProvenancePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.considered.iter().map(|p| p.name()).collect();
+        f.write_str(&names.join(","))
+    }
+}
+
+impl FromStr for ProvenancePolicy {
+    type Err = String;
+
+    /// A comma-separated list such as `declared,inferred`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let considered = s.split(',').map(|p| p.trim().parse()).collect::<Result<BTreeSet<_>, _>>()?;
+        Ok(ProvenancePolicy { considered })
+    }
+}
+
+/// Whether `keyword` is a word of `name`, alone or plural
+fn names_keyword(name: &str, keyword: &str) -> bool {
+    name.split(|c: char| !c.is_alphabetic()).any(|word| {
+        word.strip_prefix(keyword).map(|rest| rest.is_empty() || rest == "s" || rest == "es").unwrap_or(false)
+    })
+}
+
+/// The allergens one ingredient contributes, with their sources. `enriched_by`
+/// is the resolver that filled in its `allergens`, if one did.
+pub(crate) fn ingredient_sources<'a>(
+    id: &str,
+    name: &str,
+    listed: impl Iterator<Item = &'a str>,
+    enriched_by: Option<&str>,
+) -> Vec<(String, AllergenSource)> {
+    let mut found: Vec<(String, AllergenSource)> = Vec::new();
+    for allergen in listed.map(|a| terms::ALLERGENS.canonical(a)) {
+        if found.iter().any(|(a, _)| a == allergen) {
+            continue;
+        }
+        let source =
+            AllergenSource::Declared { ingredient: id.to_string(), resolver: enriched_by.map(String::from) };
+        found.push((allergen.to_string(), source));
+    }
+
+    for (keyword, allergen) in name_suggestions(name) {
+        let allergen = allergen.name();
+        if !found.iter().any(|(a, _)| a == allergen) {
+            let source = AllergenSource::Inferred { ingredient: id.to_string(), basis: keyword.to_string() };
+            found.push((allergen.to_string(), source));
+        }
+    }
+    found
+}
+
+/// Group sources by allergen, in allergen order and each source once
+pub(crate) fn aggregate(sources: impl IntoIterator<Item = (String, AllergenSource)>) -> Vec<AllergenEntry> {
+    let mut by_allergen: BTreeMap<String, Vec<AllergenSource>> = BTreeMap::new();
+    for (allergen, source) in sources {
+        let entry = by_allergen.entry(allergen).or_default();
+        if !entry.contains(&source) {
+            entry.push(source);
+        }
+    }
+    by_allergen.into_iter().map(|(allergen, sources)| AllergenEntry { allergen, sources }).collect()
+}
+
+/// The resolver that filled in an ingredient's `allergens`, if one did
+fn enriched_by(ingredient: &Value) -> Option<&str> {
+    let enriched = ingredient.get("x-enriched-from")?;
+    let filled = strings(enriched.get("fields")).contains(&"allergens");
+    filled.then(|| enriched.get("source").and_then(|v| v.as_str()).unwrap_or("resolver"))
+}
+
+/// The allergens one ingredient contributes, with their sources
+fn sources_of(ingredient: &Value) -> Vec<(String, AllergenSource)> {
+    ingredient_sources(
+        ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?"),
+        ingredient.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+        strings(ingredient.get("allergens")).into_iter(),
+        enriched_by(ingredient),
+    )
+}
+
+/// Every allergen the ingredients contain or suggest, with where each comes
+/// from
+pub fn provenance(recipe: &Value) -> Vec<AllergenEntry> {
+    aggregate(ingredients(recipe).flat_map(sources_of))
+}
+
+/// Allergens contained by the ingredients, counting only the sources the
+/// policy considers
+pub fn contained_with(recipe: &Value, policy: &ProvenancePolicy) -> BTreeSet<String> {
+    provenance(recipe).into_iter().filter(|e| e.considered(policy)).map(|e| e.allergen).collect()
+}
+
+fn strings(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Names in a list in their canonical spelling, each once
+fn names<'a>(vocabulary: &Vocabulary, value: Option<&'a Value>) -> Vec<&'a str> {
+    let mut names = Vec::new();
+    for name in strings(value).into_iter().map(|v| vocabulary.canonical(v)) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+fn ingredients(recipe: &Value) -> impl Iterator<Item = &Value> {
+    recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+/// Allergens contained by any ingredient
+pub fn contained(recipe: &Value) -> BTreeSet<String> {
+    ingredients(recipe)
+        .flat_map(|ing| names(&terms::ALLERGENS, ing.get("allergens")))
+        .map(|a| a.to_string())
+        .collect()
+}
+
+/// Allergens the recipe may contain traces of, excluding contained ones
+pub fn traces(recipe: &Value) -> BTreeSet<String> {
+    let contained = contained(recipe);
+    ingredients(recipe)
+        .flat_map(|ing| names(&terms::ALLERGENS, ing.get("may_contain_allergens")))
+        .chain(names(&terms::ALLERGENS, recipe.pointer("/meta/facility_allergens")))
+        .filter(|a| !contained.contains(*a))
+        .map(|a| a.to_string())
+        .collect()
+}
+
+/// Validate may-contain declarations and diet labels. Diet conflicts count
+/// the contained allergens whose provenance the policy considers.
+pub fn check(recipe: &Value, policy: &ProvenancePolicy, result: &mut ValidationResult) {
+    for (index, ingredient) in ingredients(recipe).enumerate() {
+        if let Some(traces) = ingredient.get("may_contain_allergens") {
+            let pointer = format!("/ingredients/{}/may_contain_allergens", index);
+            terms::ALLERGENS.check(traces, &pointer, result, |result, allergen| {
+                result.error(codes::BAD_TRACE_ALLERGEN, &[("index", &index), ("allergen", &allergen)]);
+            });
+        }
+        let declared = names(&terms::ALLERGENS, ingredient.get("allergens"));
+        for allergen in names(&terms::ALLERGENS, ingredient.get("may_contain_allergens")) {
+            if VOCABULARY.contains(&allergen) && declared.contains(&allergen) {
+                result.warning(codes::REDUNDANT_TRACE, &[("index", &index), ("allergen", &allergen)]);
+            }
+        }
+    }
+
+    if let Some(facility) = recipe.pointer("/meta/facility_allergens") {
+        terms::ALLERGENS.check(facility, "/meta/facility_allergens", result, |result, allergen| {
+            result.error(codes::BAD_FACILITY_ALLERGEN, &[("allergen", &allergen)]);
+        });
+    }
+    if let Some(labels) = recipe.pointer("/meta/diet_labels") {
+        terms::DIET_LABELS.check(labels, "/meta/diet_labels", result, |result, label| {
+            result.error(codes::BAD_DIET_LABEL, &[("label", &label)]);
+        });
+    }
+
+    // Contained allergens and the ids of the ingredients containing them
+    let mut contained: BTreeSet<String> = BTreeSet::new();
+    let mut required: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for ingredient in ingredients(recipe) {
+        let considered = sources_of(ingredient).into_iter().filter(|(_, s)| policy.considers(s.provenance()));
+        for (allergen, _) in considered {
+            if !is_optional(ingredient) {
+                let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?");
+                required.entry(allergen.clone()).or_default().push(id);
+            }
+            contained.insert(allergen);
+        }
+    }
+    let traces = traces(recipe);
+    let labels = names(&terms::DIET_LABELS, recipe.pointer("/meta/diet_labels"));
+    for label in &labels {
+        let excluded = match DIET_EXCLUSIONS.iter().find(|(name, _)| name == label) {
+            Some((_, excluded)) => excluded,
+            None => continue,
+        };
+        for allergen in excluded.iter() {
+            if let Some(ids) = required.get(*allergen) {
+                result.error(
+                    codes::DIET_CONFLICT,
+                    &[("label", label), ("allergen", allergen), ("ingredients", &ids.join(", "))],
+                );
+            } else if contained.contains(*allergen) {
+                result.warning(codes::DIET_OPTIONAL_CONFLICT, &[("label", &label), ("allergen", allergen)]);
+            } else if traces.contains(*allergen) {
+                result.warning(codes::DIET_TRACE_CONFLICT, &[("label", label), ("allergen", allergen)]);
+            }
+        }
+    }
+    check_names(recipe, &labels, policy, &contained, &traces, result);
+}
+
+/// Warn about ingredient names that suggest something a diet label excludes,
+/// unless the allergen they suggest is already contained or a trace, or the
+/// ingredient already conflicts with the label
+fn check_names(
+    recipe: &Value,
+    labels: &[&str],
+    policy: &ProvenancePolicy,
+    contained: &BTreeSet<String>,
+    traces: &BTreeSet<String>,
+    result: &mut ValidationResult,
+) {
+    for (index, ingredient) in ingredients(recipe).enumerate() {
+        let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let lowercase = name.to_lowercase();
+        let considered: Vec<_> =
+            sources_of(ingredient).into_iter().filter(|(_, source)| policy.considers(source.provenance())).collect();
+        let suggested: Vec<&str> = suggest_allergens(name)
+            .iter()
+            .map(|allergen| allergen.name())
+            .filter(|allergen| !contained.contains(*allergen) && !traces.contains(*allergen))
+            .collect();
+
+        for label in labels {
+            let excluded = DIET_EXCLUSIONS.iter().find(|(name, _)| name == label).map_or(&[][..], |(_, e)| *e);
+            if considered.iter().any(|(allergen, _)| excluded.contains(&allergen.as_str())) {
+                continue;
+            }
+            let allergens = suggested.iter().filter(|allergen| excluded.contains(allergen));
+            let products = DIET_NAME_KEYWORDS
+                .iter()
+                .filter(|(diet, _)| diet == label)
+                .flat_map(|(_, keywords)| keywords.iter())
+                .filter(|keyword| names_keyword(&lowercase, keyword));
+            let suggestions: BTreeSet<&str> =
+                allergens.copied().chain(products.copied()).collect();
+            for suggestion in suggestions {
+                result.within(&format!("/ingredients/{}/name", index), |result| {
+                    result.warning(codes::DIET_NAME_CONFLICT, &[
+                        ("label", label),
+                        ("index", &index),
+                        ("name", &name),
+                        ("suggestion", &suggestion),
+                    ]);
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "meta": {"diet_labels": ["nut-free", "gluten-free"], "facility_allergens": ["peanuts", "sesame"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "oats", "allergens": [], "may_contain_allergens": ["gluten"]},
+                {"id": "ing-0002", "name": "tahini", "allergens": ["sesame"], "may_contain_allergens": ["sesame"]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_traces_are_kept_apart() {
+        let recipe = recipe();
+        assert_eq!(contained(&recipe).into_iter().collect::<Vec<_>>(), vec!["sesame"]);
+        assert_eq!(traces(&recipe).into_iter().collect::<Vec<_>>(), vec!["gluten", "peanuts"]);
+    }
+
+    #[test]
+    fn test_diet_labels_and_redundancy() {
+        let mut result = ValidationResult::new();
+        check(&recipe(), &ProvenancePolicy::default(), &mut result);
+        assert!(result.valid);
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::REDUNDANT_TRACE, codes::DIET_TRACE_CONFLICT, codes::DIET_TRACE_CONFLICT]);
+
+        let mut recipe = recipe();
+        recipe["ingredients"][0]["allergens"] = json!(["wheat"]);
+        recipe["meta"]["facility_allergens"] = json!(["nuts"]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert!(!result.valid);
+        assert!(result.errors.contains(&"Invalid facility allergen 'nuts'".to_string()));
+        assert!(result
+            .errors
+            .contains(&"Diet label 'gluten-free' conflicts with allergen 'wheat' contained in ing-0001".to_string()));
+    }
+
+    #[test]
+    fn test_optional_garnish_downgrades_diet_conflict() {
+        let recipe = json!({
+            "meta": {"diet_labels": ["dairy-free"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "pasta", "allergens": ["wheat"]},
+                {"id": "ing-0002", "name": "parmesan", "allergens": ["milk"], "optional": true}
+            ]
+        });
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert!(result.valid);
+        assert_eq!(result.warnings, vec!["Diet label 'dairy-free' holds only without optional ingredients containing 'milk'"]);
+    }
+
+    #[test]
+    fn test_vegan_label_against_allergens_and_names() {
+        let recipe = json!({
+            "meta": {"diet_labels": ["vegan", "dairy-free"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "Oat drink", "allergens": ["milk"]},
+                {"id": "ing-0002", "name": "Whole eggs", "allergens": ["eggs"]},
+                {"id": "ing-0003", "name": "Cashew cream", "allergens": []},
+                {"id": "ing-0004", "name": "Chicken stock", "allergens": []},
+                {"id": "ing-0005", "name": "Hamburger buns", "allergens": []}
+            ]
+        });
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert_eq!(result.errors, vec![
+            "Diet label 'vegan' conflicts with allergen 'milk' contained in ing-0001",
+            "Diet label 'vegan' conflicts with allergen 'eggs' contained in ing-0002",
+            "Diet label 'dairy-free' conflicts with allergen 'milk' contained in ing-0001",
+        ]);
+        // Cashew cream suggests tree nuts, which neither label excludes
+        assert_eq!(result.warnings, vec!["Diet label 'vegan' may not hold: ingredient 3 'Chicken stock' suggests chicken"]);
+        assert_eq!(result.issues.last().unwrap().pointer, "/ingredients/3/name");
+
+        let mut recipe = recipe;
+        recipe["ingredients"] = json!([{"id": "ing-0001", "name": "Single cream", "allergens": []}]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert!(result.valid);
+        assert_eq!(result.warnings, vec![
+            "Diet label 'vegan' may not hold: ingredient 0 'Single cream' suggests milk",
+            "Diet label 'dairy-free' may not hold: ingredient 0 'Single cream' suggests milk",
+        ]);
+
+        recipe["ingredients"] = json!([{"id": "ing-0001", "name": "Cashew cream", "allergens": ["tree-nuts"]}]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert!(result.valid);
+        assert!(result.warnings.is_empty(), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_provenance_of_each_allergen() {
+        let recipe = json!({
+            "meta": {"diet_labels": ["nut-free"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "Butter", "allergens": ["Milk"]},
+                {"id": "ing-0002", "name": "Toasted almonds", "allergens": []},
+                {"id": "ing-0003", "name": "Cream cheese", "allergens": ["milk"],
+                 "x-enriched-from": {"source": "open-food-facts", "fields": ["allergens"]}},
+                {"id": "ing-0004", "name": "Eggplant", "allergens": []}
+            ]
+        });
+        let entries = provenance(&recipe);
+        let rendered: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        assert_eq!(rendered, [
+            "milk [ing-0001, ing-0003 via open-food-facts]",
+            "tree-nuts [ing-0002 inferred from \"almond\"]",
+        ]);
+        assert_eq!(
+            serde_json::to_value(&entries[1]).unwrap(),
+            json!({"allergen": "tree-nuts", "sources": [{"provenance": "inferred", "ingredient": "ing-0002", "basis": "almond"}]})
+        );
+
+        // Only a cautious operator fails the nut-free label on a name
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert!(result.valid);
+        let mut result = ValidationResult::new();
+        check(&recipe, &"declared,inferred".parse().unwrap(), &mut result);
+        assert_eq!(result.errors, vec!["Diet label 'nut-free' conflicts with allergen 'tree-nuts' contained in ing-0002"]);
+        assert_eq!(contained_with(&recipe, &ProvenancePolicy::default()).into_iter().collect::<Vec<_>>(), ["milk"]);
+        assert!("declared,guessed".parse::<ProvenancePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_resolver_allergens_count_as_declared() {
+        let recipe = json!({
+            "meta": {"diet_labels": ["dairy-free"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "Chocolate", "allergens": ["milk"],
+                 "x-enriched-from": {"source": "open-food-facts", "fields": ["allergens"]}}
+            ]
+        });
+        let mut result = ValidationResult::new();
+        check(&recipe, &ProvenancePolicy::default(), &mut result);
+        assert_eq!(result.errors, vec!["Diet label 'dairy-free' conflicts with allergen 'milk' contained in ing-0001"]);
+        assert_eq!(contained_with(&recipe, &ProvenancePolicy::default()).into_iter().collect::<Vec<_>>(), ["milk"]);
+    }
+}
@@ -0,0 +1,219 @@
+// Allergen suggestions from ingredient names
+//
+// The keyword table lives in `assets/allergen_keywords.json` so it can grow
+// without touching this module. `keywords` maps a word or phrase to the
+// allergens it suggests; `not` lists phrases that suggest nothing, so "water
+// chestnut" is not a tree nut and "coconut milk" not milk. A name is read word
+// by word and the longest phrase starting at each word wins, so a `not`
+// phrase or a more specific keyword ("peanut butter") hides the shorter
+// keywords inside it. The last word of a phrase also matches its plural.
+
+use crate::{codes, labels, terms, Allergen, ValidationResult};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+const BUILTIN_KEYWORDS: &str = include_str!("../../assets/allergen_keywords.json");
+
+#[derive(Deserialize)]
+struct Table {
+    keywords: BTreeMap<String, Vec<Allergen>>,
+    not: Vec<String>,
+}
+
+/// Keywords and `not` phrases, the latter suggesting nothing
+struct Keywords {
+    phrases: HashMap<String, Vec<Allergen>>,
+    longest: usize,
+}
+
+impl Keywords {
+    fn builtin() -> Self {
+        let table: Table =
+            serde_json::from_str(BUILTIN_KEYWORDS).expect("built-in allergen keyword asset is valid JSON");
+        let not = table.not.into_iter().map(|phrase| (phrase, Vec::new()));
+        let phrases: HashMap<String, Vec<Allergen>> = table.keywords.into_iter().chain(not).collect();
+        let longest = phrases.keys().map(|p| p.split(' ').count()).max().unwrap_or(1);
+        Keywords { phrases, longest }
+    }
+
+    /// The entry for `words`, read as a phrase with its last word possibly
+    /// plural
+    fn lookup(&self, words: &[String]) -> Option<(&str, &[Allergen])> {
+        let (last, init) = words.split_last()?;
+        let singulars = [Some(last.as_str()), last.strip_suffix('s'), last.strip_suffix("es")];
+        singulars.into_iter().flatten().find_map(|singular| {
+            let phrase = init.iter().map(String::as_str).chain([singular]).collect::<Vec<_>>().join(" ");
+            self.phrases.get_key_value(&phrase).map(|(phrase, allergens)| (phrase.as_str(), allergens.as_slice()))
+        })
+    }
+}
+
+lazy_static! {
+    static ref BUILTIN: Keywords = Keywords::builtin();
+}
+
+impl Allergen {
+    /// The allergen as documents spell it, such as "tree-nuts"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Allergen::Milk => "milk",
+            Allergen::Eggs => "eggs",
+            Allergen::Fish => "fish",
+            Allergen::Shellfish => "shellfish",
+            Allergen::TreeNuts => "tree-nuts",
+            Allergen::Peanuts => "peanuts",
+            Allergen::Wheat => "wheat",
+            Allergen::Gluten => "gluten",
+            Allergen::Soybeans => "soybeans",
+            Allergen::Sesame => "sesame",
+            Allergen::Celery => "celery",
+            Allergen::Mustard => "mustard",
+            Allergen::Molluscs => "molluscs",
+            Allergen::Lupins => "lupins",
+            Allergen::Sulphites => "sulphites",
+            Allergen::Lactose => "lactose",
+        }
+    }
+
+    /// The allergen a document spells `name`
+    pub fn parse(name: &str) -> Option<Allergen> {
+        serde_json::from_value(Value::from(name)).ok()
+    }
+}
+
+/// Each keyword found in `name` with an allergen it suggests, in name order
+pub(crate) fn name_suggestions(name: &str) -> Vec<(&'static str, Allergen)> {
+    let keywords: &'static Keywords = &BUILTIN;
+    let words: Vec<String> =
+        name.to_lowercase().split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()).map(String::from).collect();
+
+    let mut found = Vec::new();
+    let mut start = 0;
+    while start < words.len() {
+        let end = (start + keywords.longest).min(words.len());
+        let matched = (start + 1..=end).rev().find_map(|end| Some((end, keywords.lookup(&words[start..end])?)));
+        match matched {
+            Some((end, (phrase, allergens))) => {
+                found.extend(allergens.iter().map(|allergen| (phrase, *allergen)));
+                start = end;
+            }
+            None => start += 1,
+        }
+    }
+    found
+}
+
+/// Allergens an ingredient's name suggests, each once, such as wheat and
+/// gluten for "whole wheat flour"
+pub fn suggest_allergens(name: &str) -> Vec<Allergen> {
+    let mut allergens = Vec::new();
+    for (_, allergen) in name_suggestions(name) {
+        if !allergens.contains(&allergen) {
+            allergens.push(allergen);
+        }
+    }
+    allergens
+}
+
+/// The regulated allergen an allergen falls under, so declaring gluten
+/// covers a suggested wheat and lactose a suggested milk
+fn group(allergen: &str) -> &str {
+    labels::eu_fic_name(allergen).unwrap_or(allergen)
+}
+
+/// Warn about allergens an ingredient's name suggests that it neither
+/// declares nor may contain
+pub(crate) fn check(ingredient: &Value, index: usize, result: &mut ValidationResult) {
+    for (keyword, allergen) in undeclared(ingredient) {
+        result.warning(codes::LIKELY_ALLERGEN, &[("index", &index), ("keyword", &keyword), ("allergen", &allergen.name())]);
+    }
+}
+
+/// Allergens an ingredient's name suggests that it neither declares nor may
+/// contain, each regulated group once, with the keyword suggesting it
+pub(crate) fn undeclared(ingredient: &Value) -> Vec<(&'static str, Allergen)> {
+    let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let mut covered: Vec<&str> = ["allergens", "may_contain_allergens"]
+        .iter()
+        .filter_map(|field| ingredient.get(*field).and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .map(|allergen| group(terms::ALLERGENS.canonical(allergen)))
+        .collect();
+
+    let mut found = Vec::new();
+    for (keyword, allergen) in name_suggestions(name) {
+        if covered.contains(&group(allergen.name())) {
+            continue;
+        }
+        covered.push(group(allergen.name()));
+        found.push((keyword, allergen));
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_suggest_allergens() {
+        let names = |name: &str| suggest_allergens(name).iter().map(Allergen::name).collect::<Vec<_>>().join(",");
+        let cases = [
+            ("Whole wheat flour", "wheat,gluten"),
+            ("Unsalted butter", "milk"),
+            ("Heavy Cream", "milk"),
+            ("Roasted peanuts", "peanuts"),
+            ("Peanut butter", "peanuts"),
+            ("Tiger prawns", "shellfish"),
+            ("Light soy sauce", "soybeans,gluten"),
+            ("Eggs", "eggs"),
+            ("Toasted almonds", "tree-nuts"),
+            ("Cashew cream", "tree-nuts"),
+            ("Water chestnuts", ""),
+            ("Coconut milk", ""),
+            ("Gluten-free flour", ""),
+            ("Rice flour", ""),
+            ("Eggplant", ""),
+            ("Nutmeg", ""),
+            ("Cream of tartar", ""),
+            ("Salt", ""),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(names(name), expected, "{}", name);
+        }
+
+        assert_eq!(name_suggestions("Salmon and dill"), vec![("salmon", Allergen::Fish)]);
+        assert_eq!(Allergen::parse("tree-nuts"), Some(Allergen::TreeNuts));
+        for name in crate::allergens::VOCABULARY {
+            assert_eq!(Allergen::parse(name).map(|a| a.name()), Some(*name));
+        }
+    }
+
+    #[test]
+    fn test_undeclared_suggestions_warn() {
+        let ingredient = serde_json::json!({
+            "name": "Whole wheat flour and butter", "allergens": ["Gluten"], "may_contain_allergens": ["sesame"]
+        });
+        let mut result = ValidationResult::new();
+        check(&ingredient, 2, &mut result);
+        assert_eq!(result.warnings, vec!["Ingredient 2: 'butter' suggests 'milk', which is not declared"]);
+
+        let mut result = ValidationResult::new();
+        check(&serde_json::json!({"name": "Tahini", "allergens": []}), 0, &mut result);
+        assert_eq!(result.issues[0].code, codes::LIKELY_ALLERGEN);
+    }
+
+    #[test]
+    fn test_keyword_table_is_consistent() {
+        let table: Table = serde_json::from_str(BUILTIN_KEYWORDS).unwrap();
+        for phrase in &table.not {
+            assert!(!table.keywords.contains_key(phrase), "'{}' is both a keyword and a not phrase", phrase);
+        }
+        for phrase in table.keywords.keys().chain(&table.not) {
+            assert_eq!(phrase, &phrase.to_lowercase(), "keywords are lowercase");
+        }
+    }
+}
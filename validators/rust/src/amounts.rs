@@ -0,0 +1,408 @@
+// Human amount parsing
+//
+// `parse_human_amount` reads the quantity and unit at the start of a
+// `human_amount` such as "1 1/2 cups, sifted": integers, decimals, fractions,
+// unicode fractions ("½", "1½"), mixed numbers and ranges ("2-3", "2 to 3"),
+// followed by a unit spelled as the importers accept it ("grams", "Tbsp.",
+// "fl oz"). What follows is kept as the descriptor. "A" or "an" counts as
+// one before a unit ("a pinch"), and "to taste" on its own is one
+// `to-taste`. A quantity followed by a word that is not a unit ("2 medium")
+// parses without a unit.
+//
+// Anything that would need a guess is `None`: text without a leading
+// quantity, decimal commas ("1,5", which is also how thousands are written),
+// alternatives ("2 or 3"), multiplied packs ("2 x 400 g"), the one-letter
+// abbreviations whose case decides between teaspoon and tablespoon ("1 t",
+// "1 T") and a bare "c".
+// This is the one free-text amount parser: scaling and the recipe manager
+// importers read amounts through `leading_amount`, which also says where the
+// quantity and the unit end in the text, so scaling can replace the quantity
+// in place and an importer can split an ingredient line into amount and name.
+//
+// `check_consistency` compares a readable human amount with the machine
+// amount after converting both to the machine unit, and warns when they
+// differ by more than the tolerance. Amounts it cannot compare (no unit in
+// the text, special units, mass against volume) are never reported.
+
+use crate::import::unit_alias;
+use crate::precision::number_value;
+use crate::units::{convert, Dimension};
+use crate::{codes, Unit, ValidationResult};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Default allowed difference between human and machine amount, as a fraction
+pub const DEFAULT_TOLERANCE: f64 = 0.05;
+
+const UNICODE_FRACTIONS: &[(char, &str)] = &[
+    ('½', "1/2"), ('⅓', "1/3"), ('⅔', "2/3"), ('¼', "1/4"), ('¾', "3/4"),
+    ('⅛', "1/8"), ('⅜', "3/8"), ('⅝', "5/8"), ('⅞', "7/8"),
+];
+
+/// Unit abbreviations too ambiguous to read
+const AMBIGUOUS_UNITS: &[&str] = &["t", "c"];
+
+/// Words after a quantity that make the amount ambiguous
+const AMBIGUOUS_WORDS: &[&str] = &["or", "x", "×"];
+
+/// A `human_amount` read as a quantity and unit
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParsedAmount {
+    /// The quantity, or the lower end of a range
+    pub value: f64,
+    /// The upper end of a range
+    pub max: Option<f64>,
+    pub unit: Option<Unit>,
+    /// The text after the quantity and unit, e.g. "sifted"
+    pub descriptor: String,
+}
+
+impl ParsedAmount {
+    /// The quantity, or the middle of a range
+    pub fn midpoint(&self) -> f64 {
+        self.max.map(|max| (self.value + max) / 2.0).unwrap_or(self.value)
+    }
+}
+
+/// An amount read from the start of a text, with the byte offsets where its
+/// quantity (or range) and its unit end; the quantity ends at 0 when it is
+/// only implied, as in "a pinch" or "to taste"
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct LeadingAmount {
+    pub amount: ParsedAmount,
+    pub quantity_end: usize,
+    pub amount_end: usize,
+}
+
+/// A single quantity token: "2", "1.5" or "3/4"
+fn number(token: &str) -> Option<f64> {
+    if let Some((numerator, denominator)) = token.split_once('/') {
+        let numerator: u32 = numerator.parse().ok()?;
+        let denominator: u32 = denominator.parse().ok()?;
+        return (denominator != 0).then(|| numerator as f64 / denominator as f64);
+    }
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    match token.split_once('.') {
+        Some((whole, fraction)) if digits(whole) && digits(fraction) => token.parse().ok(),
+        None if digits(token) => token.parse().ok(),
+        _ => None,
+    }
+}
+
+/// A quantity at the start of `tokens`, with the number of tokens it used:
+/// a number, or a whole number followed by a fraction ("1 1/2")
+fn quantity(tokens: &[String]) -> Option<(f64, usize)> {
+    let first = number(tokens.first()?)?;
+    match tokens.get(1) {
+        Some(next) if next.contains('/') && !tokens[0].contains(['/', '.']) => Some((first + number(next)?, 2)),
+        _ => Some((first, 1)),
+    }
+}
+
+/// Split text into tokens, separating unicode fractions, range dashes and
+/// units attached to numbers ("250g"), each with the byte offset in `text`
+/// where it ends
+fn tokens(text: &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let flush = |current: &mut String, end: usize, tokens: &mut Vec<(String, usize)>| {
+        if !current.is_empty() {
+            tokens.push((std::mem::take(current), end));
+        }
+    };
+    let mut previous = ' ';
+    for (at, ch) in text.char_indices() {
+        let end = at + ch.len_utf8();
+        match UNICODE_FRACTIONS.iter().find(|(c, _)| *c == ch) {
+            Some((_, fraction)) => {
+                flush(&mut current, at, &mut tokens);
+                tokens.push((fraction.to_string(), end));
+            }
+            None if matches!(ch, '-' | '–' | '—') && (previous.is_ascii_digit() || previous == ' ') => {
+                flush(&mut current, at, &mut tokens);
+                tokens.push(("-".to_string(), end));
+            }
+            None if ch == ',' => {
+                flush(&mut current, at, &mut tokens);
+                tokens.push((",".to_string(), end));
+            }
+            None if ch.is_whitespace() => flush(&mut current, at, &mut tokens),
+            None if ch.is_alphabetic() && previous.is_ascii_digit() => {
+                flush(&mut current, at, &mut tokens);
+                current.push(ch);
+            }
+            None => current.push(ch),
+        }
+        previous = ch;
+    }
+    flush(&mut current, text.len(), &mut tokens);
+    tokens
+}
+
+/// The unit at the start of `tokens`, with the number of tokens it used
+fn unit(tokens: &[String]) -> Option<(Unit, usize)> {
+    let word = |i: usize| tokens.get(i).map(|t| t.trim_end_matches('.').to_lowercase());
+    let first = word(0)?;
+    if first == "fl" && word(1).as_deref() == Some("oz") {
+        return Some((Unit::FlOz, 2));
+    }
+    if first == "to" && word(1).as_deref() == Some("taste") {
+        return Some((Unit::ToTaste, 2));
+    }
+    unit_alias(&first).and_then(Unit::parse).map(|unit| (unit, 1))
+}
+
+/// Read a `human_amount`, or `None` when it is not an amount or would need
+/// a guess
+pub fn parse_human_amount(text: &str) -> Option<ParsedAmount> {
+    leading_amount(text.trim()).map(|leading| leading.amount)
+}
+
+/// Read an amount as `parse_human_amount` does, noting where its parts end
+pub(crate) fn leading_amount(text: &str) -> Option<LeadingAmount> {
+    let (tokens, ends): (Vec<String>, Vec<usize>) = tokens(text).into_iter().unzip();
+    let lower = |i: usize| tokens.get(i).map(|t| t.to_lowercase()).unwrap_or_default();
+
+    let (value, mut used) = match quantity(&tokens) {
+        Some(found) => found,
+        None if lower(0) == "to" && lower(1) == "taste" => (1.0, 0),
+        None if matches!(lower(0).as_str(), "a" | "an") && unit(&tokens[1..]).is_some() => (1.0, 1),
+        None if unit(&tokens).is_some_and(|(unit, _)| unit.dimension() == Dimension::Special) => (1.0, 0),
+        None => return None,
+    };
+
+    let mut max = None;
+    if lower(used) == "-" || lower(used) == "to" && lower(used + 1) != "taste" {
+        let (upper, len) = quantity(&tokens[used + 1..])?;
+        if upper < value {
+            return None;
+        }
+        max = Some(upper);
+        used += 1 + len;
+    }
+    let quantity_end = if used == 0 || matches!(lower(0).as_str(), "a" | "an") { 0 } else { ends[used - 1] };
+
+    let next = lower(used);
+    if next == "," && lower(used + 1).starts_with(|c: char| c.is_ascii_digit())
+        || AMBIGUOUS_WORDS.contains(&next.as_str())
+        || AMBIGUOUS_UNITS.contains(&tokens.get(used).map(|t| t.trim_end_matches('.').to_lowercase()).unwrap_or_default().as_str())
+    {
+        return None;
+    }
+
+    let unit = match unit(&tokens[used..]) {
+        Some((unit, len)) => {
+            used += len;
+            Some(unit)
+        }
+        None => None,
+    };
+    let amount_end = if used == 0 { 0 } else { ends[used - 1] };
+    if unit.is_some() && lower(used) == "of" {
+        used += 1;
+    }
+
+    let descriptor = tokens[used..].join(" ").replace(" , ", ", ");
+    let descriptor = descriptor.trim_start_matches([',', ' ']).to_string();
+    let amount = ParsedAmount { value, max, unit, descriptor };
+    Some(LeadingAmount { amount, quantity_end, amount_end })
+}
+
+/// Give every ingredient without a `machine_amount` one parsed from its
+/// `human_amount`, when that names a unit. A range becomes its midpoint
+/// (rounded up for pieces), marked approximate. Returns the ids of the
+/// ingredients filled in
+pub fn fill_machine_amounts(recipe: &mut Value) -> Vec<String> {
+    let mut filled = Vec::new();
+    for ingredient in recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()).into_iter().flatten() {
+        if ingredient.get("machine_amount").is_some() {
+            continue;
+        }
+        let parsed = ingredient.get("human_amount").and_then(|v| v.as_str()).and_then(parse_human_amount);
+        let (parsed, unit) = match parsed.and_then(|p| p.unit.map(|unit| (p, unit))) {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let mut value = parsed.midpoint();
+        if unit.dimension() == Dimension::Count {
+            value = value.ceil();
+        }
+        let mut amount = json!({"value": number_value(value), "unit": unit.name()});
+        if parsed.max.is_some() {
+            amount["approximate"] = json!(true);
+        }
+        ingredient["machine_amount"] = amount;
+        filled.push(ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string());
+    }
+    filled
+}
+
+/// Warn when an ingredient's human amount converts to a quantity more than
+/// `tolerance` (0.05 for 5%) away from its machine amount
+pub fn check_consistency(ingredient: &Value, index: usize, tolerance: f64, result: &mut ValidationResult) {
+    let human = match ingredient.get("human_amount").and_then(|v| v.as_str()) {
+        Some(human) => human,
+        None => return,
+    };
+    let machine = ingredient.get("machine_amount");
+    let value = machine.and_then(|m| m.get("value")).and_then(|v| v.as_f64());
+    let unit = machine.and_then(|m| m.get("unit")).and_then(|v| v.as_str()).and_then(Unit::parse);
+    let (value, unit) = match (value, unit) {
+        (Some(value), Some(unit)) if unit.dimension() != Dimension::Special => (value, unit),
+        _ => return,
+    };
+    let parsed = match parse_human_amount(human) {
+        Some(parsed) => parsed,
+        None => return,
+    };
+    let stated = match parsed.unit {
+        Some(stated) => stated,
+        None => return,
+    };
+
+    let low = match convert(parsed.value, stated, unit) {
+        Ok(low) => low,
+        Err(_) => return,
+    };
+    let high = convert(parsed.max.unwrap_or(parsed.value), stated, unit).unwrap_or(low);
+    if value >= low * (1.0 - tolerance) && value <= high * (1.0 + tolerance) {
+        return;
+    }
+
+    let round = |v: f64| number_value(crate::precision::round_half_even(v, 2)).to_string();
+    let expected = if parsed.max.is_some() { format!("{}-{}", round(low), round(high)) } else { round(low) };
+    result.within(&format!("/ingredients/{}/machine_amount/value", index), |result| {
+        result.warning(codes::AMOUNT_MISMATCH, &[
+            ("index", &index),
+            ("human", &human),
+            ("expected", &expected),
+            ("value", &round(value)),
+            ("unit", &unit.name()),
+        ])
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: f64, max: Option<f64>, unit: Option<Unit>, descriptor: &str) -> Option<ParsedAmount> {
+        Some(ParsedAmount { value, max, unit, descriptor: descriptor.to_string() })
+    }
+
+    #[test]
+    fn test_real_world_amounts() {
+        let corpus = [
+            ("250 g", amount(250.0, None, Some(Unit::G), "")),
+            ("250g", amount(250.0, None, Some(Unit::G), "")),
+            ("500 grams", amount(500.0, None, Some(Unit::G), "")),
+            ("1.5 kg", amount(1.5, None, Some(Unit::Kg), "")),
+            ("325ml", amount(325.0, None, Some(Unit::Ml), "")),
+            ("3 liters", amount(3.0, None, Some(Unit::L), "")),
+            ("1 1/2 cups, sifted", amount(1.5, None, Some(Unit::Cup), "sifted")),
+            ("1½ cups flour", amount(1.5, None, Some(Unit::Cup), "flour")),
+            ("½ teaspoon", amount(0.5, None, Some(Unit::Tsp), "")),
+            ("¼ tsp salt", amount(0.25, None, Some(Unit::Tsp), "salt")),
+            ("1/2 teaspoon", amount(0.5, None, Some(Unit::Tsp), "")),
+            ("2 Tbsp. olive oil", amount(2.0, None, Some(Unit::Tbsp), "olive oil")),
+            ("3 tablespoons", amount(3.0, None, Some(Unit::Tbsp), "")),
+            ("8 fl oz milk", amount(8.0, None, Some(Unit::FlOz), "milk")),
+            ("2 lbs potatoes", amount(2.0, None, Some(Unit::Lb), "potatoes")),
+            ("16 ounces", amount(16.0, None, Some(Unit::Oz), "")),
+            ("1 pint", amount(1.0, None, Some(Unit::Pt), "")),
+            ("2-3 cloves", amount(2.0, Some(3.0), Some(Unit::Pcs), "")),
+            ("2 - 3 cups of stock", amount(2.0, Some(3.0), Some(Unit::Cup), "stock")),
+            ("2 to 3 tbsp", amount(2.0, Some(3.0), Some(Unit::Tbsp), "")),
+            ("1–1½ cups", amount(1.0, Some(1.5), Some(Unit::Cup), "")),
+            ("1 dozen eggs", amount(1.0, None, Some(Unit::Dozen), "eggs")),
+            ("15 leaves", amount(15.0, None, None, "leaves")),
+            ("2 medium", amount(2.0, None, None, "medium")),
+            ("3 cloves garlic, minced", amount(3.0, None, Some(Unit::Pcs), "garlic, minced")),
+            ("200 g, room temperature", amount(200.0, None, Some(Unit::G), "room temperature")),
+            ("1 large egg", amount(1.0, None, None, "large egg")),
+            ("1 egg", amount(1.0, None, None, "egg")),
+            ("pinch", amount(1.0, None, Some(Unit::Pinch), "")),
+            ("pinch (optional)", amount(1.0, None, Some(Unit::Pinch), "(optional)")),
+            ("a pinch of salt", amount(1.0, None, Some(Unit::Pinch), "salt")),
+            ("handful", amount(1.0, None, Some(Unit::Handful), "")),
+            ("a cup of water", amount(1.0, None, Some(Unit::Cup), "water")),
+            ("to taste", amount(1.0, None, Some(Unit::ToTaste), "")),
+            ("salt to taste", None),
+            ("enough to cover egg", None),
+            ("for serving", None),
+            ("some", None),
+            ("a few sprigs", None),
+            ("", None),
+            ("1,5 kg", None),
+            ("1,500 g", None),
+            ("2 or 3 carrots", None),
+            ("2 x 400 g cans", None),
+            ("1 t salt", None),
+            ("1 T butter", None),
+            ("1 c flour", None),
+            ("3-2 cups", None),
+            ("1/0 cup", None),
+            ("1.2.3 g", None),
+        ];
+        for (text, expected) in corpus {
+            assert_eq!(parse_human_amount(text), expected, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_unreadable_amounts_warn_when_enabled() {
+        let recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let mut validator = crate::RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let unreadable = |result: &crate::ValidationResult| {
+            result.issues.iter().filter(|i| i.code == crate::codes::UNPARSED_HUMAN_AMOUNT).map(|i| i.pointer.clone()).collect::<Vec<_>>()
+        };
+        assert!(unreadable(&validator.validate(&recipe)).is_empty());
+
+        validator.set_readable_amounts(true);
+        assert_eq!(unreadable(&validator.validate(&recipe)), vec!["/ingredients/1/human_amount"]);
+    }
+
+    #[test]
+    fn test_human_and_machine_amounts_agree() {
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        recipe["ingredients"][1]["human_amount"] = json!("2 cups");
+        recipe["ingredients"][1]["machine_amount"] = json!({"value": 2, "unit": "tbsp"});
+        recipe["ingredients"][2]["human_amount"] = json!("to taste");
+        recipe["ingredients"][3]["human_amount"] = json!("1 tbsp");
+        let mut validator = crate::RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let mismatches = |validator: &mut crate::RCIPValidator, recipe: &Value| {
+            let result = validator.validate(recipe);
+            result.issues.iter().filter(|i| i.code == codes::AMOUNT_MISMATCH).map(|i| i.pointer.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(mismatches(&mut validator, &recipe), vec!["/ingredients/1/machine_amount/value"]);
+        let result = validator.validate(&recipe);
+        assert!(result.warnings.iter().any(|w| w.contains("'2 cups' is 32 tbsp, but machine_amount.value is 2 tbsp")), "{:?}", result.warnings);
+
+        recipe["ingredients"][1]["machine_amount"] = json!({"value": 480, "unit": "ml"});
+        assert!(mismatches(&mut validator, &recipe).is_empty());
+        validator.set_amount_tolerance(0.01);
+        assert_eq!(mismatches(&mut validator, &recipe).len(), 1);
+
+        recipe["ingredients"][1]["human_amount"] = json!("2-2 1/2 cups");
+        assert!(mismatches(&mut validator, &recipe).is_empty());
+        recipe["ingredients"][1]["human_amount"] = json!("about two cups");
+        assert!(mismatches(&mut validator, &recipe).is_empty());
+    }
+
+    #[test]
+    fn test_fill_machine_amounts() {
+        let mut recipe = json!({"ingredients": [
+            {"id": "ing-0001", "human_amount": "1 1/2 cups"},
+            {"id": "ing-0002", "human_amount": "2-3 cloves"},
+            {"id": "ing-0003", "human_amount": "2 medium"},
+            {"id": "ing-0004", "human_amount": "100 g", "machine_amount": {"value": 90, "unit": "g"}},
+            {"id": "ing-0005", "human_amount": "enough to cover"}
+        ]});
+        assert_eq!(fill_machine_amounts(&mut recipe), vec!["ing-0001", "ing-0002"]);
+        assert_eq!(recipe["ingredients"][0]["machine_amount"], json!({"value": 1.5, "unit": "cup"}));
+        assert_eq!(recipe["ingredients"][1]["machine_amount"], json!({"value": 3, "unit": "pcs", "approximate": true}));
+        assert!(recipe["ingredients"][2].get("machine_amount").is_none());
+        assert_eq!(recipe["ingredients"][3]["machine_amount"]["value"], 90);
+    }
+}
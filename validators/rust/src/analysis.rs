@@ -0,0 +1,611 @@
+// Recipe analysis
+//
+// Baker's percentages: every ingredient that can be classified as flour,
+// liquid, fat, sugar, salt or leavening is weighed and expressed relative to
+// the total flour mass. Categories come from the taxonomy, narrowed by name
+// (only grains called flour, semolina or meal count as flour; milk counts as
+// a liquid and butter as a fat). Mass units are used as given; volumes are
+// only converted for liquids, at 1 g/ml. Ingredients measured in counts or
+// in volumes of dry goods are left out rather than guessed.
+//
+// Quality score: a 0-100 rating of how publishable a recipe is, for ranking
+// a catalog. It is the weighted mean of up to four components, each itself
+// 0-100:
+//
+// - completeness: description, images, nutrition and external ids, 25 each
+// - cleanliness: 100 less a penalty per error and warning, by rule code
+// - time consistency: declared `total_time_minutes` against the critical
+//   path of the steps; full marks within 10%, none at 60% off
+// - difficulty agreement: declared difficulty against `estimate_difficulty`;
+//   full marks when they match, half one level apart
+//
+// The total time check warns when `total_time_minutes` is off from that same
+// critical path by more than the `total_time_tolerance` setting, measured
+// against the critical path. It is the longest chain through the step graph
+// (`graph::critical_path_minutes`): a step starts once every step whose
+// result it uses or that it `depends_on` has finished, and steps with no path
+// between them overlap. Steps without time params count as taking no time:
+// while some are untimed, only a declared total below the critical path is
+// reported, as the untimed steps could make up a longer one. `prep_time_minutes`
+// plus `cook_time_minutes` must not exceed the total either.
+//
+// Components without the data to judge (no declared time, no step
+// durations, no declared difficulty) are left out of the mean rather than
+// scored zero. Weights and penalties are data in `QualityWeights`, set
+// through the `quality_weights` setting; the score depends only on the
+// recipe, the result and the weights, never on when or where it is computed.
+
+mod energy;
+mod language;
+
+pub use energy::{energy_estimate, Appliance, Band, EnergyProfile, EnergyReport, PowerModel, StepEnergy};
+pub use language::{check as check_language, detect_language, detect_text, is_language_tag, languages};
+
+use crate::graph::critical_path_minutes;
+use crate::scaling::measured;
+use crate::taxonomy::{self, Category};
+use crate::{codes, numeric, step_duration_minutes, Severity, ValidationResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Hydration outside this range (percent of flour) suggests a unit error
+pub const HYDRATION_RANGE: (f64, f64) = (40.0, 120.0);
+
+/// Salt above this percentage of flour suggests a unit error
+pub const MAX_SALT: f64 = 3.0;
+
+const LEAVENING: &[&str] = &["yeast", "baking powder", "baking soda", "bicarbonate", "sourdough starter", "levain"];
+
+/// Baking role of an ingredient
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BakingCategory {
+    Flour,
+    Liquid,
+    Fat,
+    Sugar,
+    Salt,
+    Leavening,
+}
+
+/// Baker's percentages, relative to total flour mass
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BakingRatios {
+    /// Total flour mass in grams, the 100% reference
+    pub flour_g: f64,
+    /// Liquids as a percentage of flour
+    pub hydration: f64,
+    pub fat: f64,
+    pub sugar: f64,
+    pub salt: f64,
+    pub leavening: f64,
+}
+
+/// Baking role of an ingredient name
+pub fn category(name: &str) -> Option<BakingCategory> {
+    let lower = name.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| lower.contains(w));
+
+    if has(LEAVENING) {
+        return Some(BakingCategory::Leavening);
+    }
+    match taxonomy::classify(name)? {
+        Category::Grains if has(&["flour", "semolina", "meal"]) => Some(BakingCategory::Flour),
+        Category::Beverages => Some(BakingCategory::Liquid),
+        Category::Dairy if has(&["milk", "kefir"]) => Some(BakingCategory::Liquid),
+        Category::Dairy if has(&["butter", "ghee"]) => Some(BakingCategory::Fat),
+        Category::Fats => Some(BakingCategory::Fat),
+        Category::Sweeteners => Some(BakingCategory::Sugar),
+        Category::Spices if has(&["salt"]) => Some(BakingCategory::Salt),
+        _ => None,
+    }
+}
+
+fn grams(value: Option<f64>, unit: Option<&str>, category: BakingCategory) -> Option<f64> {
+    let contents = measured(value?, unit?)?;
+    if contents.ml == 0.0 {
+        Some(contents.grams)
+    } else if category == BakingCategory::Liquid {
+        Some(contents.ml)
+    } else {
+        None
+    }
+}
+
+/// Whether a recipe is bread or baking: tagged so in its cuisine or keywords,
+/// or using dough actions
+pub fn is_baking(recipe: &Value) -> bool {
+    let tags = recipe
+        .pointer("/meta/origin/cuisine_type")
+        .into_iter()
+        .chain(recipe.pointer("/meta/keywords").and_then(|v| v.as_array()).into_iter().flatten())
+        .filter_map(|v| v.as_str());
+    let actions = recipe
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("action").and_then(|v| v.as_str()));
+    baking_signals(tags, actions)
+}
+
+/// `is_baking` on the cuisine and keyword tags and the step actions
+pub(crate) fn baking_signals<'a>(
+    tags: impl IntoIterator<Item = &'a str>,
+    actions: impl IntoIterator<Item = &'a str>,
+) -> bool {
+    let tagged = tags
+        .into_iter()
+        .map(|s| s.to_lowercase())
+        .any(|t| ["bread", "baking", "bakery", "pastry"].iter().any(|w| t.contains(w)));
+    tagged || actions.into_iter().any(|a| matches!(a, "knead" | "proof" | "ferment"))
+}
+
+/// Baker's percentages of a recipe, or None when it has no weighable flour
+pub fn baking_ratios(recipe: &Value) -> Option<BakingRatios> {
+    let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten().map(|ingredient| {
+        let amount = ingredient.get("machine_amount");
+        (
+            ingredient.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+            amount.and_then(|a| a.get("value")).and_then(|v| v.as_f64()),
+            amount.and_then(|a| a.get("unit")).and_then(|v| v.as_str()),
+        )
+    });
+    baker_percentages(ingredients, category)
+}
+
+/// `baking_ratios` on each ingredient's name, amount value and amount unit,
+/// with the function that gives a name its `category`
+pub(crate) fn baker_percentages<'a>(
+    ingredients: impl IntoIterator<Item = (&'a str, Option<f64>, Option<&'a str>)>,
+    mut category: impl FnMut(&str) -> Option<BakingCategory>,
+) -> Option<BakingRatios> {
+    let mut totals = [0.0; 6];
+    for (name, value, unit) in ingredients {
+        if let Some(category) = category(name) {
+            if let Some(g) = grams(value, unit, category) {
+                totals[category as usize] += g;
+            }
+        }
+    }
+
+    let flour = totals[BakingCategory::Flour as usize];
+    if flour <= 0.0 {
+        return None;
+    }
+    let percent = |category: BakingCategory| totals[category as usize] * 100.0 / flour;
+
+    Some(BakingRatios {
+        flour_g: flour,
+        hydration: percent(BakingCategory::Liquid),
+        fat: percent(BakingCategory::Fat),
+        sugar: percent(BakingCategory::Sugar),
+        salt: percent(BakingCategory::Salt),
+        leavening: percent(BakingCategory::Leavening),
+    })
+}
+
+/// Warn about baker's percentages that almost always mean a unit error
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    if !is_baking(recipe) {
+        return;
+    }
+    let ratios = match baking_ratios(recipe) {
+        Some(ratios) => ratios,
+        None => return,
+    };
+
+    let (min, max) = HYDRATION_RANGE;
+    if ratios.hydration < min || ratios.hydration > max {
+        let hydration = format!("{:.0}", ratios.hydration);
+        result.warning(codes::IMPLAUSIBLE_HYDRATION, &[("hydration", &hydration), ("min", &min), ("max", &max)]);
+    }
+    if ratios.salt > MAX_SALT {
+        let salt = format!("{:.1}", ratios.salt);
+        result.warning(codes::IMPLAUSIBLE_SALT, &[("salt", &salt), ("max", &MAX_SALT)]);
+    }
+}
+
+/// Difficulty levels, easiest first
+pub const DIFFICULTIES: &[&str] = &["beginner", "intermediate", "advanced", "professional"];
+
+/// Actions that take practice to get right
+const TECHNIQUE_ACTIONS: &[&str] = &["knead", "fold", "roll", "shape", "ferment", "proof", "separate", "strain"];
+
+/// Time consistency is full within this share of the longer time...
+const TIME_TOLERANCE: f64 = 0.1;
+
+/// ...and zero from this share on
+const TIME_LIMIT: f64 = 0.6;
+
+/// How far the declared total time may be from the critical path, as a
+/// share of the critical path, before `RCIP-W-TOTAL-TIME-MISMATCH`
+pub const DEFAULT_TOTAL_TIME_TOLERANCE: f64 = 0.25;
+
+/// Weights of the quality components and penalties of the cleanliness
+/// component; missing fields keep their defaults
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QualityWeights {
+    pub completeness: f64,
+    pub cleanliness: f64,
+    pub time_consistency: f64,
+    pub difficulty_agreement: f64,
+    /// Points each error takes off cleanliness
+    pub error_penalty: f64,
+    /// Points each warning takes off cleanliness, unless its code is in
+    /// `rule_penalties`
+    pub warning_penalty: f64,
+    /// Penalty per warning by rule code
+    pub rule_penalties: BTreeMap<String, f64>,
+}
+
+impl Default for QualityWeights {
+    fn default() -> Self {
+        // Warnings about what completeness already measures cost nothing
+        let rule_penalties = [
+            (codes::NO_IMAGES, 0.0),
+            (codes::NO_NUTRITION, 0.0),
+            (codes::NO_EXTERNAL_IDS, 0.0),
+            (codes::MISSING_FIELD, 5.0),
+        ];
+        QualityWeights {
+            completeness: 0.35,
+            cleanliness: 0.35,
+            time_consistency: 0.15,
+            difficulty_agreement: 0.15,
+            error_penalty: 50.0,
+            warning_penalty: 10.0,
+            rule_penalties: rule_penalties.iter().map(|(code, points)| (code.to_string(), *points)).collect(),
+        }
+    }
+}
+
+/// One part of a quality score
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QualityComponent {
+    pub name: &'static str,
+    /// 0 to 100
+    pub score: f64,
+    pub weight: f64,
+    /// What the component found
+    pub detail: String,
+}
+
+/// A recipe's quality, 0 to 100, and the components it was made of
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QualityScore {
+    pub score: f64,
+    pub components: Vec<QualityComponent>,
+}
+
+impl fmt::Display for QualityScore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self.components.iter().map(|c| format!("{} {:.0}", c.name, c.score)).collect();
+        write!(f, "{:.1}/100 ({})", self.score, parts.join(", "))
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn steps(recipe: &Value) -> impl Iterator<Item = &Value> {
+    recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+/// Minutes the steps take along their critical path
+pub fn computed_minutes(recipe: &Value) -> f64 {
+    critical_path_minutes(recipe)
+}
+
+/// Check `total_time_minutes` against the steps' critical path and against
+/// `prep_time_minutes` plus `cook_time_minutes`
+pub fn check_total_time(recipe: &Value, tolerance: f64, result: &mut ValidationResult) {
+    let minutes = |field: &str| recipe.pointer(&format!("/meta/{}", field)).and_then(numeric::finite);
+    let declared = match minutes("total_time_minutes") {
+        Some(declared) => declared,
+        None => return,
+    };
+
+    if let (Some(prep), Some(cook)) = (minutes("prep_time_minutes"), minutes("cook_time_minutes")) {
+        if prep + cook > declared {
+            result.within("/meta/total_time_minutes", |result| {
+                result.warning(codes::TIME_PARTS_EXCEED_TOTAL, &[("prep", &prep), ("cook", &cook), ("total", &declared)])
+            });
+        }
+    }
+
+    let total = steps(recipe).count();
+    let timed = steps(recipe).filter(|s| step_duration_minutes(s).is_some()).count();
+    let computed = computed_minutes(recipe);
+    if timed == 0 || computed <= 0.0 {
+        return;
+    }
+    let off = (declared - computed) / computed;
+    if off.abs() > tolerance && (timed == total || off < 0.0) {
+        result.within("/meta/total_time_minutes", |result| {
+            result.warning(codes::TOTAL_TIME_MISMATCH, &[
+                ("declared", &declared),
+                ("computed", &round(computed)),
+                ("percent", &format!("{:.0}", off.abs() * 100.0)),
+                ("timed", &timed),
+                ("steps", &total),
+            ])
+        });
+    }
+}
+
+/// Difficulty a recipe's steps suggest, from their number, the techniques
+/// they need and how long they take
+pub fn estimate_difficulty(recipe: &Value) -> &'static str {
+    let count = steps(recipe).count();
+    let techniques: BTreeSet<&str> = steps(recipe)
+        .filter_map(|s| s.get("action").and_then(|v| v.as_str()))
+        .filter(|a| TECHNIQUE_ACTIONS.contains(a))
+        .collect();
+
+    let mut points = match count {
+        0..=5 => 0,
+        6..=10 => 1,
+        _ => 2,
+    };
+    points += techniques.len().min(2);
+    if computed_minutes(recipe) > 180.0 {
+        points += 1;
+    }
+    DIFFICULTIES[points.saturating_sub(1).min(DIFFICULTIES.len() - 1)]
+}
+
+fn completeness(recipe: &Value) -> (f64, String) {
+    let ingredients: Vec<&Value> = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let non_empty = |value: Option<&Value>| match value {
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(Value::Array(a)) => !a.is_empty(),
+        Some(Value::Object(o)) => !o.is_empty(),
+        _ => false,
+    };
+    let present = [
+        ("description", non_empty(recipe.pointer("/meta/description"))),
+        ("images", non_empty(recipe.get("images"))),
+        ("nutrition", ingredients.iter().any(|i| non_empty(i.get("nutritional")))),
+        ("external ids", ingredients.iter().any(|i| non_empty(i.get("external_ids")))),
+    ];
+    let missing: Vec<&str> = present.iter().filter(|(_, has)| !has).map(|(name, _)| *name).collect();
+    let score = 100.0 * (present.len() - missing.len()) as f64 / present.len() as f64;
+    let detail = if missing.is_empty() { "complete".to_string() } else { format!("missing {}", missing.join(", ")) };
+    (score, detail)
+}
+
+fn cleanliness(result: &ValidationResult, weights: &QualityWeights) -> (f64, String) {
+    let mut penalty = 0.0;
+    for issue in &result.issues {
+        penalty += match issue.severity {
+            Severity::Error => weights.error_penalty,
+            Severity::Warning => weights.rule_penalties.get(issue.code).copied().unwrap_or(weights.warning_penalty),
+            Severity::Info => 0.0,
+        };
+    }
+    let detail = format!("{} errors, {} warnings", result.errors.len(), result.warnings.len());
+    ((100.0 - penalty).max(0.0), detail)
+}
+
+fn time_consistency(recipe: &Value) -> Option<(f64, String)> {
+    let declared = recipe.pointer("/meta/total_time_minutes").and_then(|v| v.as_f64()).filter(|m| *m > 0.0)?;
+    let computed = Some(computed_minutes(recipe)).filter(|m| *m > 0.0)?;
+    let off = (declared - computed).abs() / declared.max(computed);
+    let score = 100.0 * ((TIME_LIMIT - off) / (TIME_LIMIT - TIME_TOLERANCE)).clamp(0.0, 1.0);
+    Some((score, format!("declared {} min, steps take {} min", declared, round(computed))))
+}
+
+fn difficulty_agreement(recipe: &Value) -> Option<(f64, String)> {
+    let declared = recipe.pointer("/meta/difficulty").and_then(|v| v.as_str())?;
+    let level = DIFFICULTIES.iter().position(|d| *d == declared)?;
+    let estimated = estimate_difficulty(recipe);
+    let estimated_level = DIFFICULTIES.iter().position(|d| *d == estimated).unwrap_or(0);
+    let score = match level.abs_diff(estimated_level) {
+        0 => 100.0,
+        1 => 50.0,
+        _ => 0.0,
+    };
+    Some((score, format!("declared {}, estimated {}", declared, estimated)))
+}
+
+/// Quality score of a validated recipe with the default weights
+pub fn quality_score(recipe: &Value, result: &ValidationResult) -> QualityScore {
+    quality_score_with(recipe, result, &QualityWeights::default())
+}
+
+/// Quality score of a validated recipe
+pub fn quality_score_with(recipe: &Value, result: &ValidationResult, weights: &QualityWeights) -> QualityScore {
+    let judged = [
+        ("completeness", weights.completeness, Some(completeness(recipe))),
+        ("cleanliness", weights.cleanliness, Some(cleanliness(result, weights))),
+        ("time consistency", weights.time_consistency, time_consistency(recipe)),
+        ("difficulty agreement", weights.difficulty_agreement, difficulty_agreement(recipe)),
+    ];
+    let components: Vec<QualityComponent> = judged
+        .into_iter()
+        .filter_map(|(name, weight, judged)| {
+            let (score, detail) = judged?;
+            Some(QualityComponent { name, score: round(score), weight, detail })
+        })
+        .collect();
+
+    let total_weight: f64 = components.iter().map(|c| c.weight.max(0.0)).sum();
+    let score = if total_weight > 0.0 {
+        components.iter().map(|c| c.score * c.weight.max(0.0)).sum::<f64>() / total_weight
+    } else {
+        0.0
+    };
+    QualityScore { score: round(score), components }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn dough(salt: (f64, &str)) -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "00 flour", "machine_amount": {"value": 400, "unit": "g"}},
+                {"id": "ing-0002", "name": "Whole wheat flour", "machine_amount": {"value": 100, "unit": "g"}},
+                {"id": "ing-0003", "name": "Water", "machine_amount": {"value": 300, "unit": "ml"}},
+                {"id": "ing-0004", "name": "Milk", "machine_amount": {"value": 25, "unit": "g"}},
+                {"id": "ing-0005", "name": "Sea salt", "machine_amount": {"value": salt.0, "unit": salt.1}},
+                {"id": "ing-0006", "name": "Active dry yeast", "machine_amount": {"value": 7, "unit": "g"}},
+                {"id": "ing-0007", "name": "Olive oil", "machine_amount": {"value": 1, "unit": "tbsp"}},
+                {"id": "ing-0008", "name": "Butter", "machine_amount": {"value": 15, "unit": "g"}},
+                {"id": "ing-0009", "name": "Basil", "machine_amount": {"value": 5, "unit": "pcs"}}
+            ],
+            "steps": [{"step_id": "s-01", "action": "knead", "human_text": "Knead"}]
+        })
+    }
+
+    #[test]
+    fn test_bakers_percentages() {
+        let ratios = baking_ratios(&dough((10.0, "g"))).unwrap();
+        assert_eq!(ratios.flour_g, 500.0);
+        assert_eq!(ratios.hydration, 65.0);
+        assert_eq!(ratios.salt, 2.0);
+        assert_eq!(ratios.fat, 3.0);
+        assert_eq!(ratios.leavening, 1.4);
+        assert_eq!(ratios.sugar, 0.0);
+
+        let mut result = ValidationResult::new();
+        check(&dough((10.0, "g")), &mut result);
+        assert!(result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_unit_errors() {
+        let mut recipe = dough((10.0, "kg"));
+        recipe["ingredients"][2]["machine_amount"]["unit"] = json!("l");
+        let mut result = ValidationResult::new();
+        check(&recipe, &mut result);
+
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::IMPLAUSIBLE_HYDRATION, codes::IMPLAUSIBLE_SALT]);
+        assert_eq!(result.warnings[1], "Salt is 2000.0% of the flour weight; above 3% usually means a unit error");
+    }
+
+    fn validated(recipe: &Value) -> ValidationResult {
+        let mut validator = crate::RCIPValidator::new("0.1");
+        validator.init_from_str(crate::warm::bundled_schema("0.1").unwrap()).unwrap();
+        validator.validate(recipe)
+    }
+
+    fn example() -> Value {
+        serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap()
+    }
+
+    #[test]
+    fn test_quality_score_components() {
+        let recipe = example();
+        let score = quality_score(&recipe, &validated(&recipe));
+        let names: Vec<&str> = score.components.iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["completeness", "cleanliness", "time consistency", "difficulty agreement"]);
+        assert_eq!(score.components[0].detail, "missing images");
+        assert_eq!(score.components[3].detail, "declared beginner, estimated beginner");
+        assert!(score.score > 50.0 && score.score < 100.0, "{}", score);
+
+        // Without a declared time or difficulty those components drop out
+        let mut bare = recipe.clone();
+        bare["meta"].as_object_mut().unwrap().remove("total_time_minutes");
+        bare["meta"]["difficulty"] = json!("professional");
+        let bare_score = quality_score(&bare, &validated(&bare));
+        assert_eq!(bare_score.components.len(), 3);
+        assert_eq!(bare_score.components[2].score, 0.0);
+        assert!(bare_score.score < score.score);
+    }
+
+    #[test]
+    fn test_quality_weights_are_data() {
+        let recipe = example();
+        let result = validated(&recipe);
+        let only_completeness = QualityWeights {
+            cleanliness: 0.0,
+            time_consistency: 0.0,
+            difficulty_agreement: 0.0,
+            ..QualityWeights::default()
+        };
+        assert_eq!(quality_score_with(&recipe, &result, &only_completeness).score, 75.0);
+
+        let strict: QualityWeights = serde_json::from_value(json!({"rule_penalties": {"RCIP-W-NO-IMAGES": 40}})).unwrap();
+        assert_eq!(strict.completeness, 0.35);
+        let cleanliness = |weights: &QualityWeights| quality_score_with(&recipe, &result, weights).components[1].score;
+        assert_eq!(cleanliness(&QualityWeights::default()), 100.0);
+        assert_eq!(cleanliness(&strict), 60.0);
+    }
+
+    #[test]
+    fn test_total_time_against_steps() {
+        let recipe = |total: f64, second: Value| {
+            json!({
+                "meta": {"total_time_minutes": total, "prep_time_minutes": 10, "cook_time_minutes": 25},
+                "steps": [
+                    {"step_id": "s-01", "action": "chop", "target": ["ing-0001"], "params": {"time_minutes": 10}},
+                    {"step_id": "s-02", "action": "simmer", "target": ["s-01:result"], "params": second},
+                    {"step_id": "s-03", "action": "bake", "target": ["ing-0002"], "params": {"time_hours": 1}}
+                ]
+            })
+        };
+        let codes_of = |recipe: &Value| {
+            let mut result = ValidationResult::new();
+            check_total_time(recipe, DEFAULT_TOTAL_TIME_TOLERANCE, &mut result);
+            result.issues.iter().map(|i| i.code).collect::<Vec<_>>()
+        };
+
+        // Chopping and simmering (10 + 20 minutes) overlap the hour of baking
+        assert!(codes_of(&recipe(60.0, json!({"time_minutes": 20}))).is_empty());
+        assert_eq!(codes_of(&recipe(90.0, json!({"time_minutes": 20}))), [codes::TOTAL_TIME_MISMATCH]);
+        let mut result = ValidationResult::new();
+        check_total_time(&recipe(30.0, json!({"time_minutes": 20})), 0.25, &mut result);
+        assert_eq!(result.warnings, vec![
+            "Prep time (10 min) and cook time (25 min) add up to more than the total time (30 min)",
+            "Declared total time of 30 minutes is 50% off the 60 minutes the steps take (3 of 3 steps timed)",
+        ]);
+
+        // An untimed step may explain a longer total, not a shorter one
+        assert!(codes_of(&recipe(150.0, json!({}))).is_empty());
+        assert_eq!(codes_of(&recipe(150.0, json!({"time_minutes": 20}))), [codes::TOTAL_TIME_MISMATCH]);
+        assert_eq!(codes_of(&recipe(40.0, json!({}))), [codes::TOTAL_TIME_MISMATCH]);
+
+        // Preheating and boiling both finish before the bake that waits on them
+        let parallel = json!({
+            "meta": {"total_time_minutes": 31},
+            "steps": [
+                {"step_id": "s-01", "action": "preheat", "target": [], "params": {"time_minutes": 30}},
+                {"step_id": "s-02", "action": "boil", "target": ["ing-0001"], "params": {"time_minutes": 30}},
+                {"step_id": "s-03", "action": "bake", "target": ["s-02:result"], "depends_on": ["s-01"], "params": {"time_minutes": 1}}
+            ]
+        });
+        assert_eq!(computed_minutes(&parallel), 31.0);
+        assert!(codes_of(&parallel).is_empty());
+    }
+
+    #[test]
+    fn test_quality_score_is_deterministic() {
+        let recipe: Value = serde_json::from_str(include_str!("../../../examples/margherita-pizza.rcip")).unwrap();
+        let first = quality_score(&recipe, &validated(&recipe));
+        for _ in 0..5 {
+            assert_eq!(quality_score(&recipe, &validated(&recipe)), first);
+        }
+        assert_eq!(serde_json::to_string(&first).unwrap(), serde_json::to_string(&quality_score(&recipe, &validated(&recipe))).unwrap());
+    }
+
+    #[test]
+    fn test_no_flour_or_not_baking() {
+        let soup = json!({"ingredients": [{"name": "Water", "machine_amount": {"value": 1, "unit": "l"}}]});
+        assert_eq!(baking_ratios(&soup), None);
+
+        let mut recipe = dough((10.0, "kg"));
+        recipe["steps"][0]["action"] = json!("mix");
+        assert!(!is_baking(&recipe));
+        let mut result = ValidationResult::new();
+        check(&recipe, &mut result);
+        assert!(result.issues.is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+// Energy estimation
+//
+// Each step that runs on a powered appliance gets a power draw model from an
+// `EnergyProfile`: the `type` of the device profile the step refers to, or
+// else the step's action, names an appliance. Steps on neither, like mixing
+// by hand, draw nothing and are left out.
+//
+// - oven: heats at `heat_watts` and `c_per_minute` from ambient, or from the
+//   temperature an earlier step left it at, then holds at `hold_watts_per_c`
+//   per degree above ambient for the step's duration
+// - hob: a fixed draw per temperature band
+// - speeds: a fixed draw per speed setting, for blenders and mixers
+// - constant: a fixed draw
+//
+// Durations are the step spans of the simulator's schedule. A step without a
+// duration, temperature or speed the model needs is estimated with the
+// appliance's default and flagged, and its confidence band widens from
+// `uncertainty` to `assumed_uncertainty`.
+
+use crate::simulate::schedule;
+use crate::{step_duration_minutes, step_temperature_c, RCIPError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Upper end of a hob temperature band and the draw within it
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Band {
+    pub up_to_c: f64,
+    pub watts: f64,
+}
+
+/// How an appliance draws power
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "model", rename_all = "kebab-case")]
+pub enum PowerModel {
+    Oven { heat_watts: f64, c_per_minute: f64, hold_watts_per_c: f64 },
+    /// Bands in rising order; hotter than the last uses the last
+    Hob { bands: Vec<Band> },
+    /// Draw at speed setting 1, 2, ...
+    Speeds { watts: Vec<f64> },
+    Constant { watts: f64 },
+}
+
+fn default_minutes() -> f64 {
+    10.0
+}
+
+fn default_temperature_c() -> f64 {
+    180.0
+}
+
+/// A power model with the values assumed when a step lacks them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Appliance {
+    #[serde(flatten)]
+    pub model: PowerModel,
+    #[serde(default = "default_minutes")]
+    pub default_minutes: f64,
+    #[serde(default = "default_temperature_c")]
+    pub default_temperature_c: f64,
+}
+
+/// Power models for appliances and how steps are matched to them, e.g.
+/// `{"equipment": {"oven": {"model": "oven", "heat_watts": 3000, ...}}, "actions": {"bake": "oven"}}`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EnergyProfile {
+    /// Temperature appliances heat up from, °C
+    pub ambient_c: f64,
+    /// Relative uncertainty of an estimate from complete step data
+    pub uncertainty: f64,
+    /// Relative uncertainty of an estimate that assumed a value
+    pub assumed_uncertainty: f64,
+    /// Appliances by name
+    pub equipment: BTreeMap<String, Appliance>,
+    /// Appliance for a device profile type, when not named the same
+    pub device_types: BTreeMap<String, String>,
+    /// Appliance for steps without a typed device profile, by action
+    pub actions: BTreeMap<String, String>,
+}
+
+impl Default for EnergyProfile {
+    fn default() -> Self {
+        let appliance = |model: PowerModel, default_minutes: f64, default_temperature_c: f64| Appliance {
+            model,
+            default_minutes,
+            default_temperature_c,
+        };
+        let band = |up_to_c: f64, watts: f64| Band { up_to_c, watts };
+        let equipment = BTreeMap::from([
+            ("oven".to_string(), appliance(PowerModel::Oven { heat_watts: 2500.0, c_per_minute: 8.0, hold_watts_per_c: 4.0 }, 30.0, 180.0)),
+            ("hob".to_string(), appliance(PowerModel::Hob { bands: vec![band(100.0, 1200.0), band(160.0, 1800.0), band(250.0, 2400.0)] }, 15.0, 100.0)),
+            ("blender".to_string(), appliance(PowerModel::Speeds { watts: vec![300.0, 600.0, 1000.0] }, 2.0, 0.0)),
+            ("mixer".to_string(), appliance(PowerModel::Speeds { watts: vec![150.0, 300.0, 575.0] }, 8.0, 0.0)),
+            ("microwave".to_string(), appliance(PowerModel::Constant { watts: 1000.0 }, 5.0, 0.0)),
+        ]);
+        let pairs = |pairs: &[(&str, &str)]| pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        EnergyProfile {
+            ambient_c: 20.0,
+            uncertainty: 0.15,
+            assumed_uncertainty: 0.5,
+            equipment,
+            device_types: pairs(&[("stove", "hob"), ("cooktop", "hob"), ("induction", "hob"), ("food-processor", "blender")]),
+            actions: pairs(&[
+                ("bake", "oven"), ("roast", "oven"), ("grill", "oven"),
+                ("heat", "hob"), ("boil", "hob"), ("simmer", "hob"), ("steam", "hob"), ("fry", "hob"), ("saute", "hob"),
+                ("blend", "blender"), ("knead", "mixer"),
+            ]),
+        }
+    }
+}
+
+impl EnergyProfile {
+    /// Load a profile file
+    pub fn from_file(path: &Path) -> Result<Self, RCIPError> {
+        let content = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&content)?;
+        Self::from_value(&value)
+    }
+
+    /// Parse and check a profile document. Appliances and mappings it
+    /// names replace the built-in ones; the rest are kept
+    pub fn from_value(value: &Value) -> Result<Self, RCIPError> {
+        let mut profile = EnergyProfile::deserialize(value)?;
+        let defaults = EnergyProfile::default();
+        for (name, appliance) in defaults.equipment {
+            profile.equipment.entry(name).or_insert(appliance);
+        }
+        for (from, to) in defaults.device_types {
+            profile.device_types.entry(from).or_insert(to);
+        }
+        for (from, to) in defaults.actions {
+            profile.actions.entry(from).or_insert(to);
+        }
+        for (name, appliance) in &profile.equipment {
+            let empty = match &appliance.model {
+                PowerModel::Hob { bands } => bands.is_empty(),
+                PowerModel::Speeds { watts } => watts.is_empty(),
+                _ => false,
+            };
+            if empty {
+                return Err(RCIPError::ValidationError(format!("appliance '{}' has no power levels", name)));
+            }
+            if let PowerModel::Oven { c_per_minute, .. } = appliance.model {
+                if c_per_minute <= 0.0 {
+                    return Err(RCIPError::ValidationError(format!("oven '{}' must heat faster than 0 °C per minute", name)));
+                }
+            }
+        }
+        for (from, to) in profile.device_types.iter().chain(&profile.actions) {
+            if !profile.equipment.contains_key(to) {
+                return Err(RCIPError::ValidationError(format!("'{}' maps to unknown appliance '{}'", from, to)));
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Name and model of the appliance a step runs on, if any
+    fn appliance(&self, recipe: &Value, step: &Value) -> Option<(&str, &Appliance)> {
+        let device_type = step
+            .get("device_profile_ref")
+            .and_then(|v| v.as_str())
+            .and_then(|id| device_profile(recipe, id))
+            .and_then(|profile| profile.get("type"))
+            .and_then(|v| v.as_str())
+            .map(|t| t.to_lowercase().replace('_', "-"));
+        let by_type = device_type.and_then(|t| match self.device_types.get(&t) {
+            Some(name) => Some(name.clone()),
+            None => self.equipment.contains_key(&t).then_some(t),
+        });
+        let name = by_type.or_else(|| {
+            let action = step.get("action").and_then(|v| v.as_str())?;
+            self.actions.get(action).cloned()
+        })?;
+        self.equipment.get_key_value(&name).map(|(name, appliance)| (name.as_str(), appliance))
+    }
+}
+
+fn device_profile<'a>(recipe: &'a Value, id: &str) -> Option<&'a Value> {
+    recipe
+        .get("device_profiles")?
+        .as_array()?
+        .iter()
+        .find(|p| p.get("id").and_then(|v| v.as_str()) == Some(id))
+}
+
+/// Speed setting of a step, 1-based, from `speed`, `speed_setting` or
+/// `mixing_speed`
+fn speed(step: &Value, levels: usize) -> Option<usize> {
+    let params = step.get("params")?;
+    let value = ["speed", "speed_setting", "mixing_speed"].iter().find_map(|key| params.get(*key))?;
+    let level = match value {
+        Value::Number(n) => n.as_f64()?.round().max(1.0) as usize,
+        Value::String(s) => match s.to_lowercase().as_str() {
+            "slow" | "low" => 1,
+            "medium" => levels.div_ceil(2),
+            "fast" | "high" => levels,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(level.min(levels))
+}
+
+/// Estimated energy of one step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepEnergy {
+    pub step_id: String,
+    pub appliance: String,
+    pub minutes: f64,
+    pub kwh: f64,
+    pub low_kwh: f64,
+    pub high_kwh: f64,
+    /// Values assumed for lack of step data
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub assumed: Vec<String>,
+}
+
+/// Estimated energy of a recipe, step by step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EnergyReport {
+    pub steps: Vec<StepEnergy>,
+    pub total_kwh: f64,
+    pub low_kwh: f64,
+    pub high_kwh: f64,
+}
+
+impl EnergyReport {
+    /// Whether any step's estimate rests on an assumed value
+    pub fn has_assumptions(&self) -> bool {
+        self.steps.iter().any(|s| !s.assumed.is_empty())
+    }
+}
+
+/// Estimate the energy each powered step of a recipe draws
+pub fn energy_estimate(recipe: &Value, profile: &EnergyProfile) -> EnergyReport {
+    let spans: HashMap<String, f64> =
+        schedule(recipe).into_iter().map(|s| (s.step_id, s.end_minutes - s.start_minutes)).collect();
+    // Temperature each oven was left at
+    let mut ovens: HashMap<String, f64> = HashMap::new();
+    let mut steps = Vec::new();
+
+    for step in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten() {
+        let Some((name, appliance)) = profile.appliance(recipe, step) else { continue };
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        let mut assumed = Vec::new();
+
+        let minutes = match step_duration_minutes(step) {
+            Some(_) => spans.get(&step_id).copied().unwrap_or(0.0),
+            None => {
+                assumed.push(format!("{} min", appliance.default_minutes));
+                appliance.default_minutes
+            }
+        };
+        let mut temperature = || {
+            step_temperature_c(step).unwrap_or_else(|| {
+                assumed.push(format!("{} °C", appliance.default_temperature_c));
+                appliance.default_temperature_c
+            })
+        };
+
+        let watt_hours = match &appliance.model {
+            PowerModel::Oven { heat_watts, c_per_minute, hold_watts_per_c } => {
+                let target = temperature();
+                let oven = step.get("device_profile_ref").and_then(|v| v.as_str()).unwrap_or(name);
+                let current = ovens.get(oven).copied().unwrap_or(profile.ambient_c);
+                let heating = (target - current).max(0.0) / c_per_minute;
+                ovens.insert(oven.to_string(), target);
+                heat_watts * heating / 60.0 + hold_watts_per_c * (target - profile.ambient_c).max(0.0) * minutes / 60.0
+            }
+            PowerModel::Hob { bands } => {
+                let target = temperature();
+                let band = bands.iter().find(|b| target <= b.up_to_c).or(bands.last());
+                band.map(|b| b.watts).unwrap_or(0.0) * minutes / 60.0
+            }
+            PowerModel::Speeds { watts } => {
+                let level = speed(step, watts.len()).unwrap_or_else(|| {
+                    let middle = watts.len().div_ceil(2);
+                    assumed.push(format!("speed {}", middle));
+                    middle
+                });
+                watts.get(level.saturating_sub(1)).copied().unwrap_or(0.0) * minutes / 60.0
+            }
+            PowerModel::Constant { watts } => watts * minutes / 60.0,
+        };
+
+        let kwh = watt_hours / 1000.0;
+        let uncertainty = if assumed.is_empty() { profile.uncertainty } else { profile.assumed_uncertainty };
+        steps.push(StepEnergy {
+            step_id,
+            appliance: name.to_string(),
+            minutes,
+            kwh,
+            low_kwh: kwh * (1.0 - uncertainty).max(0.0),
+            high_kwh: kwh * (1.0 + uncertainty),
+            assumed,
+        });
+    }
+
+    EnergyReport {
+        total_kwh: steps.iter().map(|s| s.kwh).sum(),
+        low_kwh: steps.iter().map(|s| s.low_kwh).sum(),
+        high_kwh: steps.iter().map(|s| s.high_kwh).sum(),
+        steps,
+    }
+}
+
+impl fmt::Display for EnergyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<8} {:<10} {:>8} {:>8} {:>17}  assumed", "step", "appliance", "min", "kWh", "range")?;
+        for step in &self.steps {
+            writeln!(
+                f,
+                "{:<8} {:<10} {:>8.1} {:>8.3} {:>8.3}-{:<8.3}  {}",
+                step.step_id,
+                step.appliance,
+                step.minutes,
+                step.kwh,
+                step.low_kwh,
+                step.high_kwh,
+                step.assumed.join(", ")
+            )?;
+        }
+        writeln!(f, "Total: {:.3} kWh ({:.3}-{:.3})", self.total_kwh, self.low_kwh, self.high_kwh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture() -> Value {
+        json!({
+            "steps": [
+                {"step_id": "s-01", "action": "bake", "params": {"temperature_c": 200, "time_minutes": 30}},
+                {"step_id": "s-02", "action": "bake", "params": {"temperature_c": 220, "time_minutes": 15}},
+                {"step_id": "s-03", "action": "boil", "params": {"temperature_c": 100, "time_minutes": 20}},
+                {"step_id": "s-04", "action": "mix", "params": {"time_minutes": 5}},
+                {"step_id": "s-05", "action": "blend", "params": {"speed_setting": 2, "time_seconds": 90},
+                 "device_profile_ref": "jug"},
+                {"step_id": "s-06", "action": "simmer"}
+            ],
+            "device_profiles": [{"id": "jug", "type": "food_processor"}]
+        })
+    }
+
+    fn close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_hand_computed_fixture() {
+        let report = energy_estimate(&fixture(), &EnergyProfile::default());
+        let ids: Vec<&str> = report.steps.iter().map(|s| s.step_id.as_str()).collect();
+        assert_eq!(ids, ["s-01", "s-02", "s-03", "s-05", "s-06"]);
+
+        // Heat 20 -> 200 °C at 8 °C/min: 22.5 min at 2500 W = 937.5 Wh;
+        // hold 180 °C x 4 W/°C = 720 W for 30 min = 360 Wh
+        close(report.steps[0].kwh, 1.2975);
+        // Heat 200 -> 220 °C: 2.5 min at 2500 W = 104.17 Wh; hold 800 W for 15 min = 200 Wh
+        close(report.steps[1].kwh, (2500.0 * 2.5 / 60.0 + 200.0) / 1000.0);
+        // 1200 W band for 20 min
+        close(report.steps[2].kwh, 0.4);
+        // Food processor runs as a blender: 600 W at speed 2 for 1.5 min
+        close(report.steps[3].kwh, 0.015);
+        assert_eq!(report.steps[3].appliance, "blender");
+        // No duration or temperature: 15 min at 100 °C assumed, 1200 W
+        close(report.steps[4].kwh, 0.3);
+        assert_eq!(report.steps[4].assumed, ["15 min", "100 °C"]);
+
+        close(report.total_kwh, 1.2975 + 2500.0 * 2.5 / 60.0 / 1000.0 + 0.2 + 0.4 + 0.015 + 0.3);
+        close(report.steps[2].low_kwh, 0.34);
+        close(report.steps[4].high_kwh, 0.45);
+        assert!(report.has_assumptions());
+    }
+
+    #[test]
+    fn test_profile_overrides_and_checks() {
+        let profile = EnergyProfile::from_value(&json!({
+            "equipment": {"oven": {"model": "constant", "watts": 3000, "default_minutes": 40}},
+            "actions": {"bake": "oven"}
+        }))
+        .unwrap();
+        let report = energy_estimate(&fixture(), &profile);
+        assert_eq!(report.steps.len(), 5);
+        close(report.steps[0].kwh, 1.5);
+        close(report.steps[1].kwh, 0.75);
+        close(report.steps[2].kwh, 0.4);
+
+        let unknown = EnergyProfile::from_value(&json!({"actions": {"bake": "furnace"}}));
+        assert!(unknown.unwrap_err().to_string().contains("unknown appliance 'furnace'"));
+        let empty = EnergyProfile::from_value(&json!({"equipment": {"hob": {"model": "hob", "bands": []}}}));
+        assert!(empty.is_err());
+    }
+}
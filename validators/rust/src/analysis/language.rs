@@ -0,0 +1,241 @@
+// Language detection
+//
+// Guesses the language of a recipe's prose, `meta.description` and every
+// step's `human_text`, from character trigrams. Each word is padded with `_`
+// on both sides and cut into trigrams; the text's trigrams are compared with
+// the ranked profile of every language in `assets/languages.json`, a trigram
+// counting its rank in the profile and a missing one the profile's length.
+// The language with the smallest mean distance wins.
+//
+// Confidence is the lead over the runner-up, full at a 15% lead, scaled down
+// for texts shorter than a sentence or two. Single words, names like "Pizza
+// margherita", mixed-language texts and scripts no profile covers all come out
+// with a low confidence rather than an error, and a low confidence is never
+// reported.
+//
+// Each profile is the 300 most frequent trigrams of a few hundred words of
+// cooking prose, written out the same way in every language. The asset must
+// stay under 16 KiB (about 12 KiB for ten languages now, checked by the
+// tests), so a new language costs around 1.2 KiB and the detector stays small
+// enough to live in the default build.
+
+use crate::{codes, ValidationResult};
+use lazy_static::lazy_static;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// Confidence from which a detection is reported
+pub const MIN_CONFIDENCE: f64 = 0.8;
+
+/// Lead over the runner-up that gives full confidence
+const FULL_MARGIN: f64 = 0.15;
+
+/// Trigrams from which a text is long enough for full confidence
+const FULL_LENGTH: usize = 60;
+
+const PROFILES: &str = include_str!("../../assets/languages.json");
+
+lazy_static! {
+    /// Rank of each trigram, by language
+    static ref RANKS: BTreeMap<String, HashMap<String, usize>> = {
+        let profiles: BTreeMap<String, String> = serde_json::from_str(PROFILES).expect("bundled language profiles parse");
+        profiles
+            .into_iter()
+            .map(|(language, trigrams)| {
+                let ranks = trigrams.split(' ').enumerate().map(|(rank, t)| (t.to_string(), rank)).collect();
+                (language, ranks)
+            })
+            .collect()
+    };
+}
+
+/// Languages the detector knows, as BCP 47 primary tags
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    RANKS.keys().map(|l| l.as_str())
+}
+
+fn trigrams(text: &str) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for word in text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty()) {
+        let padded: Vec<char> = std::iter::once('_').chain(word.to_lowercase().chars()).chain(std::iter::once('_')).collect();
+        for window in padded.windows(3) {
+            *counts.entry(window.iter().collect()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Language of a text and the confidence in it, 0 to 1; None when the text
+/// has no letters
+pub fn detect_text(text: &str) -> Option<(&'static str, f64)> {
+    let counts = trigrams(text);
+    let total: usize = counts.values().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let mut distances: Vec<(&'static str, f64)> = RANKS
+        .iter()
+        .map(|(language, ranks)| {
+            let missing = ranks.len();
+            let sum: usize = counts.iter().map(|(t, n)| n * ranks.get(t).copied().unwrap_or(missing)).sum();
+            (language.as_str(), sum as f64 / (total * missing) as f64)
+        })
+        .collect();
+    distances.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    let (best, nearest) = (distances[0], distances.get(1).map_or(1.0, |d| d.1));
+    let margin = if nearest > 0.0 { (nearest - best.1) / nearest } else { 0.0 };
+    let confidence = (margin / FULL_MARGIN).min(1.0) * (total as f64 / FULL_LENGTH as f64).min(1.0);
+    Some((best.0, (confidence * 100.0).round() / 100.0))
+}
+
+/// The prose a recipe's language is judged by
+pub fn recipe_text(recipe: &Value) -> String {
+    let steps = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten();
+    recipe
+        .pointer("/meta/description")
+        .into_iter()
+        .chain(steps.filter_map(|s| s.get("human_text")))
+        .filter_map(|v| v.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Language of a recipe's description and step texts and the confidence in
+/// it, 0 to 1; None when there is no text to judge
+pub fn detect_language(recipe: &Value) -> Option<(String, f64)> {
+    detect_text(&recipe_text(recipe)).map(|(language, confidence)| (language.to_string(), confidence))
+}
+
+/// Whether a tag is well-formed BCP 47 as far as this rule cares: a primary
+/// language subtag of two or three letters, optionally followed by subtags
+/// of one to eight letters or digits, as in `en`, `de-AT` or `zh-Hant-TW`
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let primary = subtags.next().unwrap_or("");
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|s| (1..=8).contains(&s.len()) && s.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// Primary language subtag of a tag, lowercased
+fn primary(tag: &str) -> String {
+    tag.split('-').next().unwrap_or("").to_ascii_lowercase()
+}
+
+fn percent(confidence: f64) -> String {
+    format!("{:.0}", confidence * 100.0)
+}
+
+/// Validate `meta.language` and compare it with the detected language; with
+/// `require_declared`, also warn when it is missing but the text's language
+/// is clear
+pub fn check(recipe: &Value, require_declared: bool, result: &mut ValidationResult) {
+    let declared = match recipe.pointer("/meta/language") {
+        Some(Value::String(tag)) if is_language_tag(tag) => Some(tag.as_str()),
+        Some(value) => {
+            let tag = value.as_str().map_or_else(|| value.to_string(), |s| s.to_string());
+            result.error(codes::BAD_LANGUAGE_TAG, &[("tag", &tag)]);
+            return;
+        }
+        None => None,
+    };
+
+    let Some((detected, confidence)) = detect_text(&recipe_text(recipe)) else { return };
+    if confidence < MIN_CONFIDENCE {
+        return;
+    }
+    match declared {
+        Some(tag) if primary(tag) != detected => {
+            result.warning(codes::LANGUAGE_MISMATCH, &[("declared", &tag), ("detected", &detected), ("confidence", &percent(confidence))]);
+        }
+        None if require_declared => {
+            result.warning(codes::MISSING_LANGUAGE, &[("detected", &detected), ("confidence", &percent(confidence))]);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Most the bundled profiles may take up, in bytes
+    const PROFILE_BUDGET: usize = 16 * 1024;
+
+    fn recipe(language: Option<&str>, texts: &[&str]) -> Value {
+        let steps: Vec<Value> = texts.iter().map(|t| json!({"step_id": "s-01", "human_text": t})).collect();
+        let mut recipe = json!({"meta": {"name": "Eggs"}, "steps": steps});
+        if let Some(language) = language {
+            recipe["meta"]["language"] = json!(language);
+        }
+        recipe
+    }
+
+    #[test]
+    fn test_detects_each_bundled_language() {
+        let samples = [
+            ("en", "Boil the eggs for six minutes, then cool them in ice water and peel carefully."),
+            ("de", "Die Eier sechs Minuten kochen, dann in Eiswasser abschrecken und vorsichtig schälen."),
+            ("fr", "Faire cuire les œufs six minutes, puis les refroidir dans de l'eau glacée et les écaler."),
+            ("es", "Cocer los huevos seis minutos, luego enfriarlos en agua con hielo y pelarlos con cuidado."),
+            ("it", "Cuocere le uova per sei minuti, poi raffreddarle in acqua ghiacciata e sgusciarle."),
+            ("pt", "Coza os ovos durante seis minutos, depois arrefeça-os em água gelada e descasque-os."),
+            ("nl", "Kook de eieren zes minuten, laat ze dan afkoelen in ijswater en pel ze voorzichtig."),
+            ("sv", "Koka äggen i sex minuter, kyl dem sedan i isvatten och skala dem försiktigt."),
+            ("pl", "Gotuj jajka przez sześć minut, następnie schłodź je w lodowatej wodzie i ostrożnie obierz."),
+            ("tr", "Yumurtaları altı dakika haşlayın, sonra buzlu suda soğutun ve dikkatlice soyun."),
+        ];
+        assert_eq!(languages().count(), samples.len());
+        for (language, text) in samples {
+            let (detected, confidence) = detect_text(text).unwrap();
+            assert_eq!(detected, language, "{}", text);
+            assert!(confidence >= MIN_CONFIDENCE, "{} {}", language, confidence);
+        }
+        assert!(PROFILES.len() <= PROFILE_BUDGET, "language profiles take {} bytes", PROFILES.len());
+    }
+
+    #[test]
+    fn test_short_and_mixed_texts_are_not_confident() {
+        for text in ["Mix", "Pizza margherita", "Варить яйца шесть минут", "Mettre la pâte sur la plaque. Then bake for 20 minutes until golden."] {
+            let (_, confidence) = detect_text(text).unwrap();
+            assert!(confidence < MIN_CONFIDENCE, "{} {}", text, confidence);
+        }
+        assert_eq!(detect_text("12 - 15, 180"), None);
+        assert_eq!(detect_language(&json!({"meta": {"name": "Eggs"}})), None);
+    }
+
+    #[test]
+    fn test_language_tags() {
+        for tag in ["en", "de-AT", "zh-Hant-TW", "gsw", "es-419"] {
+            assert!(is_language_tag(tag), "{}", tag);
+        }
+        for tag in ["", "english", "e", "en_US", "de-", "en-toolongsubtag"] {
+            assert!(!is_language_tag(tag), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn test_checks_declared_language() {
+        let german = "Die Eier sechs Minuten kochen, dann in Eiswasser abschrecken und vorsichtig schälen.";
+        let codes_of = |recipe: &Value, require: bool| {
+            let mut result = ValidationResult::new();
+            check(recipe, require, &mut result);
+            result.issues.iter().map(|i| i.code).collect::<Vec<_>>()
+        };
+
+        assert_eq!(codes_of(&recipe(Some("de-DE"), &[german]), true), Vec::<&str>::new());
+        assert_eq!(codes_of(&recipe(Some("en"), &[german]), false), [codes::LANGUAGE_MISMATCH]);
+        assert_eq!(codes_of(&recipe(None, &[german]), false), Vec::<&str>::new());
+        assert_eq!(codes_of(&recipe(None, &[german]), true), [codes::MISSING_LANGUAGE]);
+        assert_eq!(codes_of(&recipe(Some("English"), &[german]), false), [codes::BAD_LANGUAGE_TAG]);
+        // Too little text to contradict the declaration
+        assert_eq!(codes_of(&recipe(Some("en"), &["Eier kochen"]), true), Vec::<&str>::new());
+
+        let mut result = ValidationResult::new();
+        check(&recipe(Some("en"), &[german]), false, &mut result);
+        assert_eq!(result.warnings[0], "meta.language is 'en' but the text reads as 'de' (100% confidence)");
+    }
+}
@@ -0,0 +1,255 @@
+// Recipe builder
+//
+// `RecipeBuilder` assembles a recipe from code that passes validation by
+// construction. The recipe id is a fresh `rcip-` UUID and `created_date` the
+// current time; ingredients are numbered `ing-0001, ing-0002, ...` and steps
+// `s-01, s-02, ...` in the order they are added, and every method that adds
+// one returns its id for later targets. An ingredient cannot be added without
+// its machine amount and allergen list (an empty list declares none), and a
+// step target must name an ingredient or an earlier step added before it, so
+// the references in the result always resolve.
+//
+// `build` gives the typed `Recipe` and `build_value` the document. Fields the
+// builder has no method for can be set on the typed recipe afterwards.
+
+use crate::ids::{generate_ingredient_id, generate_recipe_id, generate_step_id};
+use crate::recipe::{number, Author, Ingredient, MachineAmount, Meta, Recipe, Step};
+use crate::{Allergen, CookingAction, Unit};
+use chrono::{SecondsFormat, Utc};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Something the builder cannot turn into a valid recipe
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildError {
+    /// A text that must not be empty is
+    Empty(&'static str),
+    /// An amount that is negative or not a finite number
+    BadAmount { ingredient: String, value: f64 },
+    /// A target naming neither an ingredient nor an earlier step
+    UnknownTarget { step: String, target: String },
+    /// A recipe needs at least one ingredient and one step
+    Missing(&'static str),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::Empty(what) => write!(f, "{} must not be empty", what),
+            BuildError::BadAmount { ingredient, value } => {
+                write!(f, "ingredient '{}': amount {} is not a non-negative number", ingredient, value)
+            }
+            BuildError::UnknownTarget { step, target } => {
+                write!(f, "step {}: target '{}' is neither an ingredient nor an earlier step", step, target)
+            }
+            BuildError::Missing(what) => write!(f, "a recipe needs at least one {}", what),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builds a recipe one ingredient and step at a time
+#[derive(Debug, Clone)]
+pub struct RecipeBuilder {
+    id: String,
+    name: String,
+    author: String,
+    description: Option<String>,
+    created_date: String,
+    ingredients: Vec<Ingredient>,
+    steps: Vec<Step>,
+}
+
+fn require(text: &str, what: &'static str) -> Result<String, BuildError> {
+    if text.trim().is_empty() {
+        Err(BuildError::Empty(what))
+    } else {
+        Ok(text.to_string())
+    }
+}
+
+impl RecipeBuilder {
+    /// Start a recipe with a fresh id, created now
+    pub fn new(name: &str, author: &str) -> Self {
+        RecipeBuilder {
+            id: generate_recipe_id(),
+            name: name.to_string(),
+            author: author.to_string(),
+            description: None,
+            created_date: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            ingredients: Vec::new(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// The id the recipe will have
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Set `meta.description`
+    pub fn description(&mut self, text: &str) -> &mut Self {
+        self.description = Some(text.to_string());
+        self
+    }
+
+    /// Add an ingredient and return its id
+    pub fn add_ingredient(
+        &mut self,
+        name: &str,
+        human_amount: &str,
+        value: f64,
+        unit: Unit,
+        allergens: &[Allergen],
+    ) -> Result<String, BuildError> {
+        let name = require(name, "ingredient name")?;
+        let human_amount = require(human_amount, "human amount")?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(BuildError::BadAmount { ingredient: name, value });
+        }
+
+        let id = generate_ingredient_id(self.ingredients.len());
+        self.ingredients.push(Ingredient {
+            id: id.clone(),
+            name,
+            human_amount,
+            machine_amount: MachineAmount { value: number(value).expect("a finite amount"), unit, approximate: None, extra: Map::new() },
+            allergens: allergens.to_vec(),
+            may_contain_allergens: None,
+            optional: None,
+            extra: Map::new(),
+        });
+        Ok(id)
+    }
+
+    /// Add a step without targets and return its id
+    pub fn add_step(&mut self, action: CookingAction, text: &str) -> Result<String, BuildError> {
+        self.add_step_with_targets(action, text, &[])
+    }
+
+    /// Add a step acting on ingredients (`ing-0001`) or the results of
+    /// earlier steps (`s-01` or `s-01:result`) and return its id
+    pub fn add_step_with_targets(&mut self, action: CookingAction, text: &str, targets: &[&str]) -> Result<String, BuildError> {
+        let text = require(text, "step text")?;
+        let id = generate_step_id(self.steps.len());
+        let target = targets
+            .iter()
+            .map(|target| self.resolve(target).ok_or_else(|| BuildError::UnknownTarget { step: id.clone(), target: target.to_string() }))
+            .collect::<Result<Vec<String>, BuildError>>()?;
+
+        self.steps.push(Step {
+            step_id: id.clone(),
+            human_text: text,
+            action,
+            target: if target.is_empty() { None } else { Some(target) },
+            result: None,
+            hazards: None,
+            extra: Map::new(),
+        });
+        Ok(id)
+    }
+
+    /// The reference a target stands for, looking only at what was added
+    fn resolve(&self, target: &str) -> Option<String> {
+        if self.ingredients.iter().any(|ing| ing.id == target) {
+            return Some(target.to_string());
+        }
+        let step = target.strip_suffix(":result").unwrap_or(target);
+        self.steps.iter().any(|s| s.step_id == step).then(|| format!("{}:result", step))
+    }
+
+    /// The typed recipe
+    pub fn build(&self) -> Result<Recipe, BuildError> {
+        let name = require(&self.name, "recipe name")?;
+        let author = require(&self.author, "author")?;
+        if self.ingredients.is_empty() {
+            return Err(BuildError::Missing("ingredient"));
+        }
+        if self.steps.is_empty() {
+            return Err(BuildError::Missing("step"));
+        }
+
+        Ok(Recipe {
+            rcip_version: "0.1".to_string(),
+            id: self.id.clone(),
+            meta: Meta {
+                name,
+                description: self.description.clone(),
+                author: Author::Name(author),
+                diet_labels: None,
+                keywords: None,
+                difficulty: None,
+                prep_time_minutes: None,
+                cook_time_minutes: None,
+                total_time_minutes: None,
+                created_date: self.created_date.clone(),
+                updated_date: None,
+                version: None,
+                language: None,
+                extra: Map::new(),
+            },
+            ingredients: self.ingredients.clone(),
+            steps: self.steps.clone(),
+            extra: Map::new(),
+        })
+    }
+
+    /// The recipe document
+    pub fn build_value(&self) -> Result<Value, BuildError> {
+        self.build().map(|recipe| recipe.to_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codes, RCIPValidator};
+    use serde_json::json;
+
+    #[test]
+    fn test_built_recipe_validates() {
+        let mut builder = RecipeBuilder::new("Pancakes", "Test Cook");
+        builder.description("Thin pancakes for two.");
+        let flour = builder.add_ingredient("flour", "200 g", 200.0, Unit::G, &[Allergen::Wheat, Allergen::Gluten]).unwrap();
+        let milk = builder.add_ingredient("milk", "300 ml", 300.0, Unit::Ml, &[Allergen::Milk]).unwrap();
+        let salt = builder.add_ingredient("salt", "a pinch", 0.5, Unit::G, &[]).unwrap();
+        let batter = builder.add_step_with_targets(CookingAction::Mix, "Mix flour, milk and salt.", &[&flour, &milk, &salt]).unwrap();
+        builder.add_step_with_targets(CookingAction::Fry, "Fry thin pancakes.", &[&batter]).unwrap();
+        builder.add_step(CookingAction::Rest, "Let the pancakes rest.").unwrap();
+        assert_eq!((flour.as_str(), batter.as_str()), ("ing-0001", "s-01"));
+
+        let recipe = builder.build_value().unwrap();
+        assert_eq!(recipe["id"], builder.id());
+        assert_eq!(recipe["steps"][1]["target"], json!(["s-01:result"]));
+        assert_eq!(recipe["ingredients"][2]["machine_amount"], json!({"value": 0.5, "unit": "g"}));
+        assert_eq!(RCIPValidator::parse_recipe(&recipe).unwrap(), builder.build().unwrap());
+
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid && result.errors.is_empty(), "{:?}", result.errors);
+        let references = [codes::BAD_INGREDIENT_REF, codes::BAD_STEP_REF, codes::ID_SEQUENCE, codes::ID_PADDING];
+        assert!(!result.issues.iter().any(|i| references.contains(&i.code)), "{:?}", result.warnings);
+    }
+
+    #[test]
+    fn test_refuses_what_would_not_validate() {
+        let mut builder = RecipeBuilder::new("Toast", "Test Cook");
+        assert_eq!(builder.build().unwrap_err(), BuildError::Missing("ingredient"));
+        assert_eq!(
+            builder.add_ingredient("bread", "2 slices", -2.0, Unit::Pcs, &[Allergen::Wheat]).unwrap_err().to_string(),
+            "ingredient 'bread': amount -2 is not a non-negative number"
+        );
+        assert_eq!(builder.add_ingredient("bread", " ", 2.0, Unit::Pcs, &[]).unwrap_err(), BuildError::Empty("human amount"));
+
+        builder.add_ingredient("bread", "2 slices", 2.0, Unit::Pcs, &[Allergen::Wheat]).unwrap();
+        assert_eq!(builder.build().unwrap_err(), BuildError::Missing("step"));
+        assert_eq!(
+            builder.add_step_with_targets(CookingAction::Heat, "Toast the bread.", &["ing-0002"]).unwrap_err().to_string(),
+            "step s-01: target 'ing-0002' is neither an ingredient nor an earlier step"
+        );
+        assert!(builder.add_step_with_targets(CookingAction::Heat, "Toast the bread.", &["s-01"]).is_err());
+        assert_eq!(builder.add_step(CookingAction::Heat, "Toast the bread.").unwrap(), "s-01");
+        assert!(builder.build().is_ok());
+    }
+}
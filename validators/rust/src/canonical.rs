@@ -0,0 +1,238 @@
+// Canonical form
+//
+// Files written by different tools differ in key order, number formatting
+// and list order without differing in content, which makes diffs noisy.
+// `canonicalize` normalizes the content: allergen and diet label lists are
+// sorted with exact repeats dropped, whole numbers are written as integers
+// (`200.0` as `200`; negative zero keeps its sign) and `created_date` and
+// `updated_date` are converted to UTC with a `Z` suffix. Spellings are left
+// as they are; rewriting "Milk" as `milk` is a fix, not formatting.
+//
+// A `Value` keeps its keys sorted alphabetically, so key order is applied
+// when writing: `to_canonical_string` prints the members the schema declares
+// in the order it lists them, then any others (`x-` fields, ...) in
+// alphabetical order, two-space indented with a trailing newline. A file is
+// canonical when its text equals that output, which is what `--check` tests.
+//
+// Canonicalization never changes whether a recipe is valid or which errors
+// it has; the tests check that over the examples and the conformance corpus.
+
+use crate::precision::number_value;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+
+/// Lists sorted and deduplicated, `*` for array items
+const SORTED: &[&str] = &[
+    "/ingredients/*/allergens",
+    "/ingredients/*/may_contain_allergens",
+    "/meta/facility_allergens",
+    "/meta/diet_labels",
+];
+
+/// Timestamps written in UTC
+const TIMESTAMPS: &[&str] = &["/meta/created_date", "/meta/updated_date"];
+
+/// Member order of each object the schema describes, as the 0.1 schema
+/// lists them
+const KEY_ORDER: &[(&str, &[&str])] = &[
+    ("", &[
+        "rcip_version", "id", "meta", "ingredients", "steps", "device_profiles", "sensors", "images",
+        "compatibility", "notes", "tips", "troubleshooting", "extensions",
+    ]),
+    ("/meta", &[
+        "name", "description", "author", "origin", "servings", "facility_allergens", "diet_labels", "keywords",
+        "difficulty", "prep_time_minutes", "cook_time_minutes", "active_time_minutes", "total_time_minutes",
+        "created_date", "updated_date", "version", "license", "language",
+    ]),
+    ("/meta/author", &["name", "email", "organization"]),
+    ("/meta/origin", &["country", "region", "city", "cuisine_type", "traditional"]),
+    ("/meta/servings", &["amount", "unit", "adjustable"]),
+    ("/ingredients/*", &[
+        "id", "name", "human_amount", "machine_amount", "state", "brand", "group", "optional", "product",
+        "temperature_c", "storage", "external_ids", "nutritional", "substitutes", "allergens",
+        "may_contain_allergens", "notes", "since_version", "deprecated_in",
+    ]),
+    ("/ingredients/*/machine_amount", &["value", "unit", "approximate", "tolerance"]),
+    ("/ingredients/*/machine_amount/tolerance", &["min", "max"]),
+    ("/ingredients/*/product", &["brand", "product_name", "gtin", "required"]),
+    ("/ingredients/*/storage", &["max_temp_c", "frozen"]),
+    ("/ingredients/*/nutritional", &["per_100g", "per_serving"]),
+    ("/ingredients/*/substitutes/*", &["id", "name", "ratio", "notes"]),
+    ("/steps/*", &[
+        "step_id", "human_text", "action", "target", "depends_on", "result", "params", "device_profile_ref", "done_when",
+        "tolerance", "hazards", "since_version", "deprecated_in", "notes", "tips", "troubleshooting",
+    ]),
+    ("/steps/*/result", &["name", "description", "holdable_minutes", "approx_mass_g"]),
+    ("/steps/*/params", &[
+        "time_minutes", "time_seconds", "time_hours", "temperature_c", "temperature_f", "speed_rpm",
+        "pressure_bar", "method", "vessel", "surface", "from_frozen", "portions",
+    ]),
+    ("/steps/*/done_when", &["texture", "color", "temperature_c", "time_elapsed_minutes", "visual", "volume_increase"]),
+    ("/device_profiles/*", &[
+        "id", "type", "name", "manufacturer", "model", "params", "capabilities", "api_endpoint", "notes",
+        "since_version",
+    ]),
+    ("/sensors/*", &["id", "type", "target", "spec", "priority", "notes", "since_version"]),
+    ("/images/*", &["id", "caption", "stage", "step_ref", "data_uri", "url", "width", "height", "since_version"]),
+    ("/compatibility", &["schema_org_jsonld", "recommended_archive", "supported_formats", "compression", "signature"]),
+    ("/compatibility/signature", &["algorithm", "public_key"]),
+];
+
+/// The recipe in canonical form
+pub fn canonicalize(recipe: &Value) -> Value {
+    let mut canonical = recipe.clone();
+    normalize(&mut canonical, "");
+    canonical
+}
+
+fn normalize(value: &mut Value, path: &str) {
+    match value {
+        Value::Object(members) => {
+            for (key, member) in members.iter_mut() {
+                normalize(member, &format!("{}/{}", path, key));
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                normalize(item, &format!("{}/*", path));
+            }
+            if SORTED.contains(&path) && items.iter().all(|item| item.is_string()) {
+                items.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+                items.dedup();
+            }
+        }
+        Value::Number(number) => {
+            if let Some(float) = number.as_f64().filter(|_| number.is_f64()) {
+                if float.abs() < 9e15 && !(float == 0.0 && float.is_sign_negative()) {
+                    *value = number_value(float);
+                }
+            }
+        }
+        Value::String(text) if TIMESTAMPS.contains(&path) => {
+            if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+                *text = time.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::AutoSi, true);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The recipe's members at `path` in schema order, then the rest sorted
+fn ordered<'a>(members: &'a serde_json::Map<String, Value>, path: &str) -> Vec<(&'a String, &'a Value)> {
+    let declared = KEY_ORDER.iter().find(|(p, _)| *p == path).map(|(_, keys)| *keys).unwrap_or(&[]);
+    let rank = |key: &str| declared.iter().position(|d| *d == key).unwrap_or(declared.len());
+    let mut entries: Vec<(&String, &Value)> = members.iter().collect();
+    entries.sort_by_key(|(key, _)| rank(key));
+    entries
+}
+
+/// The canonical text of a recipe
+pub fn to_canonical_string(recipe: &Value) -> String {
+    let mut out = String::new();
+    write(&canonicalize(recipe), "", 0, &mut out);
+    out.push('\n');
+    out
+}
+
+fn write(value: &Value, path: &str, depth: usize, out: &mut String) {
+    let indent = |depth: usize| "  ".repeat(depth);
+    match value {
+        Value::Object(members) if !members.is_empty() => {
+            out.push_str("{\n");
+            let entries = ordered(members, path);
+            for (i, (key, member)) in entries.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                out.push_str(&Value::from(key.as_str()).to_string());
+                out.push_str(": ");
+                write(member, &format!("{}/{}", path, key), depth + 1, out);
+                out.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                write(item, &format!("{}/*", path), depth + 1, out);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Whether a file's text is already the canonical text of its recipe
+pub fn is_canonical(text: &str) -> Result<bool, serde_json::Error> {
+    let recipe: Value = serde_json::from_str(text)?;
+    Ok(to_canonical_string(&recipe) == text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::warm::global_validator;
+    use crate::Severity;
+    use serde_json::json;
+    use std::fs;
+    use std::path::Path;
+
+    fn recipes(dir: &Path, found: &mut Vec<(String, Value)>) {
+        let mut entries: Vec<_> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                recipes(&path, found);
+            } else if path.extension().is_some_and(|e| e == "rcip") {
+                if let Ok(recipe) = serde_json::from_str(&fs::read_to_string(&path).unwrap()) {
+                    found.push((path.display().to_string(), recipe));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalizes_lists_numbers_and_timestamps() {
+        let recipe = json!({
+            "meta": {"diet_labels": ["vegan", "dairy-free", "vegan"], "created_date": "2025-01-15T12:30:00+02:00", "prep_time_minutes": 10.0},
+            "ingredients": [{"allergens": ["wheat", "Milk", "gluten"], "machine_amount": {"value": 0.50, "unit": "g"}, "x-note": -0.0}]
+        });
+        let canonical = canonicalize(&recipe);
+        assert_eq!(canonical["meta"]["diet_labels"], json!(["dairy-free", "vegan"]));
+        assert_eq!(canonical["meta"]["created_date"], "2025-01-15T10:30:00Z");
+        assert_eq!(canonical["meta"]["prep_time_minutes"].to_string(), "10");
+        assert_eq!(canonical["ingredients"][0]["allergens"], json!(["Milk", "gluten", "wheat"]));
+        assert_eq!(canonical["ingredients"][0]["machine_amount"]["value"].to_string(), "0.5");
+        assert_eq!(canonical["ingredients"][0]["x-note"].to_string(), "-0.0");
+
+        let text = to_canonical_string(&json!({"steps": [], "id": "rcip-x", "x-b": {}, "a": 1, "rcip_version": "0.1"}));
+        assert_eq!(text, "{\n  \"rcip_version\": \"0.1\",\n  \"id\": \"rcip-x\",\n  \"steps\": [],\n  \"a\": 1,\n  \"x-b\": {}\n}\n");
+        assert!(is_canonical(&text).unwrap());
+        assert!(!is_canonical(&serde_json::to_string_pretty(&json!({"id": "rcip-x", "rcip_version": "0.1"})).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_canonical_form_keeps_validation_outcome() {
+        let mut corpus = Vec::new();
+        recipes(Path::new("../../conformance"), &mut corpus);
+        recipes(Path::new("../../examples"), &mut corpus);
+        assert!(corpus.len() > 10);
+
+        let validator = global_validator("0.1").unwrap();
+        let errors = |recipe: &Value| {
+            let result = validator.validate(recipe);
+            let mut codes: Vec<&str> = result.issues.iter().filter(|i| i.severity == Severity::Error).map(|i| i.code).collect();
+            codes.sort();
+            (result.valid, codes)
+        };
+        for (path, recipe) in &corpus {
+            let text = to_canonical_string(recipe);
+            let canonical: Value = serde_json::from_str(&text).unwrap();
+            assert_eq!(canonical, canonicalize(recipe), "{}", path);
+            assert_eq!(errors(&canonical), errors(recipe), "{}", path);
+            assert_eq!(to_canonical_string(&canonical), text, "{}", path);
+        }
+    }
+}
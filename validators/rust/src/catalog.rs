@@ -0,0 +1,576 @@
+// Recipe catalog
+//
+// An in-memory index over a directory of recipes, for search and ranking.
+// Each entry keeps what queries filter on (name, contained allergens, diet
+// labels, total time) and the per-serving nutrition summary when the recipe
+// has enough data for one. Files that cannot be read or parsed are listed
+// separately instead of failing the whole index.
+//
+// Nutrition sorting and bounds only consider recipes with a value for the
+// metric. The others are left out of the results and counted, never ranked
+// as zero. A summary that leaves some ingredients out understates every
+// amount, so sorting and bounds use the amounts scaled up by the summary's
+// coverage (`CatalogEntry::estimate`): a recipe half of whose ingredients
+// have data does not rank as if it had all of it. Ratios such as protein per
+// calorie are left as they are. The quality score ranks and filters the same
+// way as the nutrition metrics; it comes from validating the recipe against
+// the bundled schema.
+//
+// Ingredients are indexed by a normalized key (lowercase, singular, without
+// preparation words), with postings from each key to the entries using it,
+// so similarity search only scores recipes sharing an ingredient.
+
+use crate::labels::{compute_nutrition, NutritionSummary};
+use crate::scaling::measured;
+use crate::warm::global_validator;
+use crate::{allergens, RCIPError};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+mod similar;
+
+pub use similar::{similar_to, ScoredEntry, SimilarOptions};
+
+/// Words describing preparation or size rather than the ingredient
+const DESCRIPTORS: &[&str] = &[
+    "fresh", "large", "medium", "small", "chopped", "diced", "minced", "sliced", "grated", "ground",
+    "whole", "extra", "virgin", "raw", "dried", "peeled", "boneless", "skinless", "finely", "roughly",
+];
+
+fn singular(word: &str) -> String {
+    if let Some(stem) = word.strip_suffix("ies").filter(|s| s.len() > 1) {
+        format!("{}y", stem)
+    } else if let Some(stem) = word.strip_suffix("oes") {
+        format!("{}o", stem)
+    } else if word.len() > 3 && word.ends_with('s') && !word.ends_with("ss") {
+        word[..word.len() - 1].to_string()
+    } else {
+        word.to_string()
+    }
+}
+
+/// Key under which an ingredient name is indexed: lowercase singular words
+/// without descriptors
+pub fn ingredient_key(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty() && !DESCRIPTORS.contains(w))
+        .map(singular)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Ingredient keys with their share of the recipe's measurable amount, mass
+/// and volume counted alike at 1 g/ml. Unmeasurable amounts (pieces, to
+/// taste) count as an average measured ingredient.
+fn ingredient_shares(recipe: &Value) -> Vec<(String, f64)> {
+    let mut amounts: Vec<(String, Option<f64>)> = Vec::new();
+    for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+        let key = ingredient_key(ingredient.get("name").and_then(|v| v.as_str()).unwrap_or(""));
+        if key.is_empty() {
+            continue;
+        }
+        let amount = ingredient.get("machine_amount").and_then(|a| {
+            let contents = measured(a.get("value")?.as_f64()?, a.get("unit")?.as_str()?)?;
+            Some(contents.grams + contents.ml).filter(|v| *v > 0.0)
+        });
+        amounts.push((key, amount));
+    }
+
+    let known: Vec<f64> = amounts.iter().filter_map(|(_, a)| *a).collect();
+    let fallback = if known.is_empty() { 1.0 } else { known.iter().sum::<f64>() / known.len() as f64 };
+    let mut merged: BTreeMap<String, f64> = BTreeMap::new();
+    for (key, amount) in amounts {
+        *merged.entry(key).or_default() += amount.unwrap_or(fallback);
+    }
+    let total: f64 = merged.values().sum();
+    merged.into_iter().map(|(key, amount)| (key, amount / total)).collect()
+}
+
+/// A recipe in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogEntry {
+    pub path: PathBuf,
+    pub id: String,
+    pub name: String,
+    /// Allergens any ingredient contains
+    pub allergens: BTreeSet<String>,
+    pub diet_labels: Vec<String>,
+    pub total_minutes: Option<f64>,
+    pub nutrition: Option<NutritionSummary>,
+    /// Ingredient keys and their share of the recipe, summing to 1
+    pub ingredients: Vec<(String, f64)>,
+    /// Step actions in order
+    pub actions: Vec<String>,
+    /// Quality score out of 100, from validation
+    pub quality: Option<f64>,
+}
+
+impl CatalogEntry {
+    /// Index a parsed recipe
+    pub fn new(path: PathBuf, recipe: &Value) -> Self {
+        let meta = recipe.get("meta");
+        let text = |value: Option<&Value>| value.and_then(|v| v.as_str()).unwrap_or("").to_string();
+        CatalogEntry {
+            path,
+            id: text(recipe.get("id")),
+            name: text(meta.and_then(|m| m.get("name"))),
+            allergens: allergens::contained_with(recipe, &allergens::ProvenancePolicy::default()),
+            diet_labels: meta
+                .and_then(|m| m.get("diet_labels"))
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+                .unwrap_or_default(),
+            total_minutes: meta.and_then(|m| m.get("total_time_minutes")).and_then(|v| v.as_f64()),
+            nutrition: compute_nutrition(recipe).ok(),
+            ingredients: ingredient_shares(recipe),
+            actions: recipe
+                .get("steps")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|s| s.get("action").and_then(|v| v.as_str()))
+                .map(String::from)
+                .collect(),
+            quality: global_validator("0.1").ok().and_then(|v| v.validate(recipe).info.quality).map(|q| q.score),
+        }
+    }
+
+    /// Value of a metric, if the recipe has the data
+    pub fn metric(&self, metric: NutritionMetric) -> Option<f64> {
+        match metric {
+            NutritionMetric::Quality => self.quality,
+            _ => metric.value(self.nutrition.as_ref()?),
+        }
+    }
+
+    /// Value of a metric for ranking: amounts scaled up to the whole recipe
+    /// by the nutrition coverage, other metrics as they are; None without
+    /// data or without any coverage
+    pub fn estimate(&self, metric: NutritionMetric) -> Option<f64> {
+        let value = self.metric(metric)?;
+        match (metric.is_amount(), &self.nutrition) {
+            (true, Some(summary)) if summary.coverage > 0.0 => Some(value / summary.coverage.min(1.0)),
+            (true, _) => None,
+            (false, _) => Some(value),
+        }
+    }
+}
+
+/// Per-serving nutrition measures, and the quality score, recipes can be
+/// ranked and filtered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NutritionMetric {
+    CaloriesPerServing,
+    ProteinPerServing,
+    FatPerServing,
+    CarbsPerServing,
+    SugarPerServing,
+    FiberPerServing,
+    SodiumPerServing,
+    /// Grams of protein per 100 kcal
+    ProteinPerCalorie,
+    /// Quality score out of 100; not part of the nutrition summary
+    Quality,
+}
+
+const METRIC_NAMES: &[(&str, NutritionMetric)] = &[
+    ("calories-per-serving", NutritionMetric::CaloriesPerServing),
+    ("protein-per-serving", NutritionMetric::ProteinPerServing),
+    ("fat-per-serving", NutritionMetric::FatPerServing),
+    ("carbs-per-serving", NutritionMetric::CarbsPerServing),
+    ("sugar-per-serving", NutritionMetric::SugarPerServing),
+    ("fiber-per-serving", NutritionMetric::FiberPerServing),
+    ("sodium-per-serving", NutritionMetric::SodiumPerServing),
+    ("protein-per-calorie", NutritionMetric::ProteinPerCalorie),
+    ("quality", NutritionMetric::Quality),
+];
+
+impl NutritionMetric {
+    /// Value of the metric in a nutrition summary
+    pub fn value(self, summary: &NutritionSummary) -> Option<f64> {
+        match self {
+            NutritionMetric::CaloriesPerServing => summary.calories,
+            NutritionMetric::ProteinPerServing => summary.protein_g,
+            NutritionMetric::FatPerServing => summary.fat_g,
+            NutritionMetric::CarbsPerServing => summary.carbs_g,
+            NutritionMetric::SugarPerServing => summary.sugar_g,
+            NutritionMetric::FiberPerServing => summary.fiber_g,
+            NutritionMetric::SodiumPerServing => summary.sodium_mg,
+            NutritionMetric::ProteinPerCalorie => {
+                let calories = summary.calories.filter(|c| *c > 0.0)?;
+                Some(summary.protein_g? * 100.0 / calories)
+            }
+            NutritionMetric::Quality => None,
+        }
+    }
+
+    /// Whether the metric sums over ingredients, so missing data understates it
+    fn is_amount(self) -> bool {
+        !matches!(self, NutritionMetric::ProteinPerCalorie | NutritionMetric::Quality)
+    }
+
+    /// Unit the metric is expressed in
+    pub fn unit(self) -> &'static str {
+        match self {
+            NutritionMetric::CaloriesPerServing => "kcal",
+            NutritionMetric::SodiumPerServing => "mg",
+            NutritionMetric::ProteinPerCalorie => "g/100 kcal",
+            NutritionMetric::Quality => "/100",
+            _ => "g",
+        }
+    }
+}
+
+impl fmt::Display for NutritionMetric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = METRIC_NAMES.iter().find(|(_, m)| m == self).map(|(n, _)| *n).unwrap_or("?");
+        f.write_str(name)
+    }
+}
+
+impl FromStr for NutritionMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        METRIC_NAMES.iter().find(|(name, _)| *name == s).map(|(_, m)| *m).ok_or_else(|| {
+            let names: Vec<&str> = METRIC_NAMES.iter().map(|(n, _)| *n).collect();
+            format!("unknown metric '{}'; expected one of {}", s, names.join(", "))
+        })
+    }
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asc" => Ok(Direction::Asc),
+            "desc" => Ok(Direction::Desc),
+            other => Err(format!("unknown direction '{}'; expected asc or desc", other)),
+        }
+    }
+}
+
+/// Parse `METRIC[:asc|desc]`, ascending by default
+pub fn parse_sort(spec: &str) -> Result<(NutritionMetric, Direction), String> {
+    match spec.split_once(':') {
+        Some((metric, direction)) => Ok((metric.parse()?, direction.parse()?)),
+        None => Ok((spec.parse()?, Direction::Asc)),
+    }
+}
+
+/// Catalog filters, nutrition bounds, sort order and limit
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+    name: Option<String>,
+    exclude_allergens: Vec<String>,
+    diet_labels: Vec<String>,
+    bounds: Vec<(NutritionMetric, Option<f64>, Option<f64>)>,
+    sort: Option<(NutritionMetric, Direction)>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    /// A query matching every recipe
+    pub fn new() -> Self {
+        Query::default()
+    }
+
+    /// Only recipes whose name contains `text`, ignoring case
+    pub fn name_contains(mut self, text: &str) -> Self {
+        self.name = Some(text.to_lowercase());
+        self
+    }
+
+    /// Leave out recipes containing an allergen
+    pub fn exclude_allergen(mut self, allergen: &str) -> Self {
+        self.exclude_allergens.push(allergen.to_string());
+        self
+    }
+
+    /// Only recipes carrying a diet label
+    pub fn require_diet(mut self, label: &str) -> Self {
+        self.diet_labels.push(label.to_string());
+        self
+    }
+
+    /// Only recipes with the metric at or above `value`
+    pub fn min(mut self, metric: NutritionMetric, value: f64) -> Self {
+        self.bounds.push((metric, Some(value), None));
+        self
+    }
+
+    /// Only recipes with the metric at or below `value`
+    pub fn max(mut self, metric: NutritionMetric, value: f64) -> Self {
+        self.bounds.push((metric, None, Some(value)));
+        self
+    }
+
+    /// Order by a metric's `estimate`, leaving out recipes without a value
+    /// for it
+    pub fn sort_by(mut self, metric: NutritionMetric, direction: Direction) -> Self {
+        self.sort = Some((metric, direction));
+        self
+    }
+
+    /// Keep at most `limit` results
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Nutrition metrics the query needs a value for
+    fn metrics(&self) -> impl Iterator<Item = NutritionMetric> + '_ {
+        self.bounds.iter().map(|(m, _, _)| *m).chain(self.sort.map(|(m, _)| m))
+    }
+
+    /// Whether an entry passes the non-nutrition filters
+    pub fn admits(&self, entry: &CatalogEntry) -> bool {
+        self.name.as_ref().map(|n| entry.name.to_lowercase().contains(n)).unwrap_or(true)
+            && !self.exclude_allergens.iter().any(|a| entry.allergens.contains(a))
+            && self.diet_labels.iter().all(|d| entry.diet_labels.contains(d))
+    }
+}
+
+/// Matching entries, and how many were left out for lacking nutrition data
+#[derive(Debug)]
+pub struct SearchResults<'a> {
+    pub entries: Vec<&'a CatalogEntry>,
+    pub skipped_without_nutrition: usize,
+}
+
+/// Indexed recipes of a catalog
+#[derive(Debug, Default)]
+pub struct CatalogIndex {
+    entries: Vec<CatalogEntry>,
+    /// Positions of the entries using each ingredient key
+    postings: HashMap<String, Vec<usize>>,
+    /// Files that could not be read or parsed, with the reason
+    pub unreadable: Vec<(PathBuf, String)>,
+}
+
+fn compare(a: f64, b: f64, direction: Direction) -> Ordering {
+    let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+    match direction {
+        Direction::Asc => ordering,
+        Direction::Desc => ordering.reverse(),
+    }
+}
+
+impl CatalogIndex {
+    /// Index already parsed entries
+    pub fn new(entries: Vec<CatalogEntry>) -> Self {
+        let mut postings: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, entry) in entries.iter().enumerate() {
+            for (key, _) in &entry.ingredients {
+                postings.entry(key.clone()).or_default().push(i);
+            }
+        }
+        CatalogIndex { entries, postings, unreadable: Vec::new() }
+    }
+
+    /// Indexed recipes, in catalog order
+    pub fn entries(&self) -> &[CatalogEntry] {
+        &self.entries
+    }
+
+    /// Positions of the entries using an ingredient key
+    pub(crate) fn postings(&self, key: &str) -> &[usize] {
+        self.postings.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Index every `.rcip` and `.json` file in a directory
+    pub fn build(dir: &Path) -> Result<Self, RCIPError> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map(|e| e == "rcip" || e == "json").unwrap_or(false))
+            .collect();
+        paths.sort();
+
+        let (mut entries, mut unreadable) = (Vec::new(), Vec::new());
+        for path in paths {
+            let parsed = fs::read_to_string(&path)
+                .map_err(RCIPError::from)
+                .and_then(|content| serde_json::from_str::<Value>(&content).map_err(RCIPError::from));
+            match parsed {
+                Ok(recipe) => entries.push(CatalogEntry::new(path, &recipe)),
+                Err(e) => unreadable.push((path, e.to_string())),
+            }
+        }
+        Ok(CatalogIndex { unreadable, ..CatalogIndex::new(entries) })
+    }
+
+    /// Entries with a value for the metric, best first by `estimate`; see
+    /// `unranked` for how many were left out
+    pub fn rank_by(&self, metric: NutritionMetric, direction: Direction) -> Vec<&CatalogEntry> {
+        self.search(&Query::new().sort_by(metric, direction)).entries
+    }
+
+    /// Number of entries without a value for the metric
+    pub fn unranked(&self, metric: NutritionMetric) -> usize {
+        self.entries.iter().filter(|e| e.estimate(metric).is_none()).count()
+    }
+
+    /// Run a query; ties keep catalog order
+    pub fn search(&self, query: &Query) -> SearchResults<'_> {
+        let mut skipped = 0;
+        let mut entries: Vec<&CatalogEntry> = Vec::new();
+        for entry in self.entries.iter().filter(|e| query.admits(e)) {
+            if query.metrics().any(|m| entry.estimate(m).is_none()) {
+                skipped += 1;
+                continue;
+            }
+            let within = query.bounds.iter().all(|(metric, min, max)| {
+                let value = entry.estimate(*metric).unwrap_or_default();
+                min.map(|m| value >= m).unwrap_or(true) && max.map(|m| value <= m).unwrap_or(true)
+            });
+            if within {
+                entries.push(entry);
+            }
+        }
+
+        if let Some((metric, direction)) = query.sort {
+            entries.sort_by(|a, b| compare(a.estimate(metric).unwrap_or_default(), b.estimate(metric).unwrap_or_default(), direction));
+        }
+        if let Some(limit) = query.limit {
+            entries.truncate(limit);
+        }
+        SearchResults { entries, skipped_without_nutrition: skipped }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe(name: &str, kcal: Option<f64>, protein: f64, sodium: f64, allergens: &[&str]) -> Value {
+        let per_100g = match kcal {
+            Some(kcal) => json!({"calories": kcal, "protein": protein, "sodium": sodium}),
+            None => json!(null),
+        };
+        let mut ingredient = json!({"id": "ing-0001", "name": "Base", "allergens": allergens,
+                                    "machine_amount": {"value": 200, "unit": "g"}});
+        if !per_100g.is_null() {
+            ingredient["nutritional"] = json!({"per_100g": per_100g});
+        }
+        json!({"id": name, "meta": {"name": name, "servings": {"amount": 2}}, "ingredients": [ingredient]})
+    }
+
+    fn index() -> CatalogIndex {
+        let recipes = [
+            recipe("Lentil soup", Some(120.0), 9.0, 300.0, &[]),
+            recipe("Shrimp bowl", Some(150.0), 20.0, 500.0, &["shellfish"]),
+            recipe("Cheese toast", Some(300.0), 12.0, 600.0, &["milk", "wheat"]),
+            recipe("Mystery stew", None, 0.0, 0.0, &[]),
+        ];
+        CatalogIndex::new(recipes.iter().map(|r| CatalogEntry::new(PathBuf::from("x.rcip"), r)).collect())
+    }
+
+    fn names(entries: &[&CatalogEntry]) -> Vec<String> {
+        entries.iter().map(|e| e.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_ingredient_keys_and_shares() {
+        assert_eq!(ingredient_key("Fresh Tomatoes, diced"), "tomato");
+        assert_eq!(ingredient_key("Blueberries"), "blueberry");
+        assert_eq!(ingredient_key("Extra-virgin olive oil"), "olive oil");
+        assert_eq!(ingredient_key("Grass-fed butter"), "grass fed butter");
+
+        let recipe = json!({"ingredients": [
+            {"name": "Flour", "machine_amount": {"value": 300, "unit": "g"}},
+            {"name": "Water", "machine_amount": {"value": 100, "unit": "ml"}},
+            {"name": "Eggs", "machine_amount": {"value": 2, "unit": "pcs"}}
+        ]});
+        assert_eq!(
+            ingredient_shares(&recipe),
+            vec![("egg".to_string(), 0.3333333333333333), ("flour".to_string(), 0.5), ("water".to_string(), 0.16666666666666666)]
+        );
+    }
+
+    #[test]
+    fn test_rank_skips_recipes_without_nutrition() {
+        let index = index();
+        let sodium = index.rank_by(NutritionMetric::SodiumPerServing, Direction::Asc);
+        assert_eq!(names(&sodium), vec!["Lentil soup", "Shrimp bowl", "Cheese toast"]);
+        assert_eq!(sodium[0].metric(NutritionMetric::SodiumPerServing), Some(300.0));
+        assert_eq!(index.unranked(NutritionMetric::SodiumPerServing), 1);
+
+        let density = index.rank_by(NutritionMetric::ProteinPerCalorie, Direction::Desc);
+        assert_eq!(names(&density), vec!["Shrimp bowl", "Lentil soup", "Cheese toast"]);
+    }
+
+    #[test]
+    fn test_partial_nutrition_ranks_by_coverage() {
+        let mut partial = recipe("Half-known stew", Some(100.0), 5.0, 280.0, &[]);
+        partial["ingredients"].as_array_mut().unwrap().push(json!({"id": "ing-0002", "name": "Stock", "allergens": [],
+                                           "machine_amount": {"value": 200, "unit": "g"}}));
+        let entry = CatalogEntry::new(PathBuf::from("x.rcip"), &partial);
+        assert_eq!(entry.nutrition.as_ref().unwrap().coverage, 0.5);
+        assert_eq!(entry.metric(NutritionMetric::SodiumPerServing), Some(280.0));
+        assert_eq!(entry.estimate(NutritionMetric::SodiumPerServing), Some(560.0));
+        assert_eq!(entry.estimate(NutritionMetric::ProteinPerCalorie), Some(5.0));
+
+        let mut entries = index().entries;
+        entries.push(entry);
+        let index = CatalogIndex::new(entries);
+        let sodium = index.rank_by(NutritionMetric::SodiumPerServing, Direction::Asc);
+        assert_eq!(names(&sodium), vec!["Lentil soup", "Shrimp bowl", "Half-known stew", "Cheese toast"]);
+        let low_sodium = index.search(&Query::new().max(NutritionMetric::SodiumPerServing, 500.0));
+        assert_eq!(names(&low_sodium.entries), vec!["Lentil soup", "Shrimp bowl"]);
+    }
+
+    #[test]
+    fn test_query_combines_filters_and_bounds() {
+        let index = index();
+        let (metric, direction) = parse_sort("protein-per-serving:desc").unwrap();
+        let query = Query::new()
+            .exclude_allergen("shellfish")
+            .max(NutritionMetric::CaloriesPerServing, 500.0)
+            .sort_by(metric, direction)
+            .limit(20);
+        let results = index.search(&query);
+        assert_eq!(names(&results.entries), vec!["Cheese toast", "Lentil soup"]);
+        assert_eq!(results.skipped_without_nutrition, 1);
+
+        let everything = index.search(&Query::new().name_contains("STEW"));
+        assert_eq!(names(&everything.entries), vec!["Mystery stew"]);
+        assert_eq!(everything.skipped_without_nutrition, 0);
+
+        assert!(parse_sort("vitamins:desc").unwrap_err().starts_with("unknown metric 'vitamins'"));
+        assert!(parse_sort("calories-per-serving:up").is_err());
+    }
+    #[test]
+    fn test_rank_by_quality() {
+        let text = fs::read_to_string("../../examples/margherita-pizza.rcip").unwrap();
+        let example = CatalogEntry::new(PathBuf::from("margherita-pizza.rcip"), &serde_json::from_str(&text).unwrap());
+        let mut entries = index().entries;
+        entries.push(example);
+        let index = CatalogIndex::new(entries);
+
+        // Every recipe has a quality score, nutrition data or not
+        assert_eq!(index.unranked(NutritionMetric::Quality), 0);
+        let ranked = index.rank_by(NutritionMetric::Quality, Direction::Desc);
+        assert_eq!(ranked[0].name, "Classic Margherita Pizza");
+        assert!(ranked[0].quality.unwrap() > ranked[1].quality.unwrap());
+
+        let (metric, direction) = parse_sort("quality:desc").unwrap();
+        let results = index.search(&Query::new().min(metric, ranked[0].quality.unwrap()).sort_by(metric, direction));
+        assert_eq!(names(&results.entries), vec!["Classic Margherita Pizza"]);
+        assert_eq!(NutritionMetric::Quality.unit(), "/100");
+    }
+}
@@ -0,0 +1,188 @@
+// Structural similarity
+//
+// Candidates are the catalog entries sharing at least one ingredient key with
+// the recipe, found through the index postings, after allergen and diet
+// filters. Each is scored from three parts:
+// - ingredients: the overlap of the two amount distributions, the sum over
+//   shared keys of the smaller share, from 0 to 1;
+// - actions: the longest common subsequence of the step actions relative to
+//   the longer sequence;
+// - time: 1 minus the relative difference of the total times.
+// The parts are weighted 0.6, 0.25 and 0.15; when either recipe has no total
+// time the time part is dropped and the others are reweighted. Scores tie
+// break on catalog order, so results are deterministic.
+
+use super::{CatalogEntry, CatalogIndex};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+
+const INGREDIENT_WEIGHT: f64 = 0.6;
+const ACTION_WEIGHT: f64 = 0.25;
+const TIME_WEIGHT: f64 = 0.15;
+
+/// Shared ingredients listed per result
+const TOP_SHARED: usize = 3;
+
+/// Filters and result count for `similar_to`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimilarOptions {
+    /// Leave out recipes containing any of these allergens
+    pub exclude_allergens: Vec<String>,
+    /// Only recipes carrying all of these diet labels
+    pub diet_labels: Vec<String>,
+    pub top: usize,
+}
+
+impl Default for SimilarOptions {
+    fn default() -> Self {
+        SimilarOptions { exclude_allergens: Vec::new(), diet_labels: Vec::new(), top: 10 }
+    }
+}
+
+/// A similar recipe with the parts of its score
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoredEntry<'a> {
+    pub entry: &'a CatalogEntry,
+    pub score: f64,
+    pub ingredients: f64,
+    pub actions: f64,
+    /// None when either recipe has no total time
+    pub time: Option<f64>,
+    /// Shared ingredient keys contributing most to the ingredient part, with
+    /// their contribution
+    pub shared: Vec<(String, f64)>,
+}
+
+/// Longest common subsequence length
+fn lcs(a: &[String], b: &[String]) -> usize {
+    let mut row = vec![0; b.len() + 1];
+    for x in a {
+        let mut diagonal = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y { diagonal + 1 } else { above.max(row[j]) };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+fn action_similarity(a: &[String], b: &[String]) -> f64 {
+    let longer = a.len().max(b.len());
+    if longer == 0 {
+        return 0.0;
+    }
+    lcs(a, b) as f64 / longer as f64
+}
+
+fn time_proximity(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    let (a, b) = (a.filter(|t| *t > 0.0)?, b.filter(|t| *t > 0.0)?);
+    Some(1.0 - (a - b).abs() / a.max(b))
+}
+
+fn score<'a>(recipe: &CatalogEntry, candidate: &'a CatalogEntry) -> ScoredEntry<'a> {
+    let shares: HashMap<&str, f64> = candidate.ingredients.iter().map(|(k, s)| (k.as_str(), *s)).collect();
+    let mut shared: Vec<(String, f64)> = recipe
+        .ingredients
+        .iter()
+        .filter_map(|(key, share)| shares.get(key.as_str()).map(|other| (key.clone(), share.min(*other))))
+        .collect();
+    let ingredients: f64 = shared.iter().map(|(_, c)| c).sum();
+    shared.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.0.cmp(&b.0)));
+    shared.truncate(TOP_SHARED);
+
+    let actions = action_similarity(&recipe.actions, &candidate.actions);
+    let time = time_proximity(recipe.total_minutes, candidate.total_minutes);
+    let score = match time {
+        Some(time) => INGREDIENT_WEIGHT * ingredients + ACTION_WEIGHT * actions + TIME_WEIGHT * time,
+        None => (INGREDIENT_WEIGHT * ingredients + ACTION_WEIGHT * actions) / (INGREDIENT_WEIGHT + ACTION_WEIGHT),
+    };
+
+    ScoredEntry { entry: candidate, score, ingredients, actions, time, shared }
+}
+
+/// Catalog recipes most similar to `recipe`, best first. The recipe itself
+/// (same id) is never returned
+pub fn similar_to<'a>(index: &'a CatalogIndex, recipe: &Value, opts: &SimilarOptions) -> Vec<ScoredEntry<'a>> {
+    let query = CatalogEntry::new(PathBuf::new(), recipe);
+
+    let candidates: BTreeSet<usize> = query.ingredients.iter().flat_map(|(key, _)| index.postings(key)).copied().collect();
+    let mut scored: Vec<(usize, ScoredEntry)> = candidates
+        .into_iter()
+        .map(|i| (i, &index.entries()[i]))
+        .filter(|(_, entry)| query.id.is_empty() || entry.id != query.id)
+        .filter(|(_, entry)| !opts.exclude_allergens.iter().any(|a| entry.allergens.contains(a)))
+        .filter(|(_, entry)| opts.diet_labels.iter().all(|d| entry.diet_labels.contains(d)))
+        .map(|(i, entry)| (i, score(&query, entry)))
+        .collect();
+
+    scored.sort_by(|(i, a), (j, b)| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal).then(i.cmp(j)));
+    scored.into_iter().take(opts.top).map(|(_, s)| s).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe(id: &str, ingredients: &[(&str, f64, &[&str])], actions: &[&str], minutes: Option<f64>) -> Value {
+        let ingredients: Vec<Value> = ingredients
+            .iter()
+            .map(|(name, grams, allergens)| {
+                json!({"name": name, "allergens": allergens, "machine_amount": {"value": grams, "unit": "g"}})
+            })
+            .collect();
+        let steps: Vec<Value> = actions.iter().map(|a| json!({"action": a})).collect();
+        let mut meta = json!({"name": id});
+        if let Some(minutes) = minutes {
+            meta["total_time_minutes"] = json!(minutes);
+        }
+        json!({"id": id, "meta": meta, "ingredients": ingredients, "steps": steps})
+    }
+
+    fn index() -> CatalogIndex {
+        let recipes = [
+            recipe("shrimp-pasta", &[("Spaghetti", 200.0, &["wheat"]), ("Shrimp", 150.0, &["shellfish"]), ("Garlic", 10.0, &[])],
+                   &["boil", "fry", "combine"], Some(25.0)),
+            recipe("garlic-pasta", &[("Spaghetti", 200.0, &["wheat"]), ("Minced garlic", 20.0, &[]), ("Olive oil", 30.0, &[])],
+                   &["boil", "fry", "combine"], Some(20.0)),
+            recipe("tomato-pasta", &[("Spaghetti", 200.0, &["wheat"]), ("Tomatoes", 300.0, &[])],
+                   &["boil", "simmer", "combine"], None),
+            recipe("fruit-salad", &[("Apples", 200.0, &[]), ("Blueberries", 100.0, &[])], &["cut", "mix"], Some(10.0)),
+        ];
+        CatalogIndex::new(recipes.iter().map(|r| CatalogEntry::new(PathBuf::from("x.rcip"), r)).collect())
+    }
+
+    #[test]
+    fn test_similar_recipes_are_explained() {
+        let index = index();
+        let query = recipe("mine", &[("Spaghetti", 250.0, &["wheat"]), ("Garlic", 15.0, &[]), ("Olive oil", 25.0, &[])],
+                           &["boil", "fry", "combine"], Some(20.0));
+        let results = similar_to(&index, &query, &SimilarOptions::default());
+
+        let ids: Vec<&str> = results.iter().map(|r| r.entry.id.as_str()).collect();
+        assert_eq!(ids, vec!["garlic-pasta", "shrimp-pasta", "tomato-pasta"]);
+        assert_eq!(results[0].actions, 1.0);
+        assert_eq!(results[0].time, Some(1.0));
+        assert_eq!(results[0].shared.iter().map(|(k, _)| k.as_str()).collect::<Vec<_>>(), vec!["spaghetti", "olive oil", "garlic"]);
+        assert_eq!(results[2].time, None);
+        assert!(results[0].score > results[1].score && results[1].score > results[2].score);
+    }
+
+    #[test]
+    fn test_filters_apply_before_scoring() {
+        let index = index();
+        let source = recipe("shrimp-pasta", &[("Spaghetti", 200.0, &["wheat"]), ("Shrimp", 150.0, &["shellfish"])],
+                            &["boil"], Some(25.0));
+
+        let opts = SimilarOptions { exclude_allergens: vec!["wheat".to_string()], ..SimilarOptions::default() };
+        assert!(similar_to(&index, &source, &opts).is_empty());
+
+        let opts = SimilarOptions { top: 1, ..SimilarOptions::default() };
+        let results = similar_to(&index, &source, &opts);
+        assert_eq!(results.len(), 1);
+        assert_ne!(results[0].entry.id, "shrimp-pasta");
+        assert_eq!(lcs(&["a".into(), "b".into(), "c".into()], &["a".into(), "c".into()]), 2);
+    }
+}
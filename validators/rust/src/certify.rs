@@ -0,0 +1,395 @@
+// Machine executability certification
+//
+// Answers one question for robot kitchens: can a machine run this recipe
+// with no human interpretation? Each criterion is checked on its own and
+// lists the JSON paths failing it; a recipe is certified when none fail.
+// - step params: every step carries the params its action needs, as finite
+//   numbers (`ACTION_PARAMS`)
+// - machine amounts: every ingredient has a machine amount in a mass,
+//   volume or count unit, so a dispenser can measure it
+// - targets: every target is an ingredient id, `s-XX:result` or `@name`
+//   and resolves
+// - step graph: result dependencies are acyclic and exactly one step's
+//   result is used by no other step, the dish
+// - equipment: every vessel and `device_profile_ref` names a device profile
+// - text params: a temperature or time stated in `human_text` is also in
+//   the step's params, not only in the prose
+
+use crate::intermediates::producer;
+use crate::portions;
+use crate::scaling::{measured, step_equipment};
+use crate::{step_duration_minutes, step_temperature_c};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+lazy_static! {
+    static ref TEMPERATURE_TEXT: Regex = Regex::new(r"(?i)\d\s*(°|º|degrees?\b)").unwrap();
+    static ref TIME_TEXT: Regex = Regex::new(r"(?i)\d\s*(seconds?|secs?|minutes?|mins?|hours?|hrs?)\b").unwrap();
+}
+
+/// Units a machine can count out without a conversion
+const COUNT_UNITS: &[&str] = &["pcs", "dozen"];
+
+/// What a step's params must state for the machine to run it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Need {
+    Time,
+    Temperature,
+    TimeAndTemperature,
+    TimeOrTemperature,
+}
+
+/// Params each action needs; actions not listed need none
+pub const ACTION_PARAMS: &[(&str, Need)] = &[
+    ("heat", Need::TimeAndTemperature),
+    ("fry", Need::TimeAndTemperature),
+    ("saute", Need::TimeAndTemperature),
+    ("bake", Need::TimeAndTemperature),
+    ("roast", Need::TimeAndTemperature),
+    ("grill", Need::TimeAndTemperature),
+    ("boil", Need::Time),
+    ("simmer", Need::Time),
+    ("steam", Need::Time),
+    ("mix", Need::Time),
+    ("blend", Need::Time),
+    ("knead", Need::Time),
+    ("ferment", Need::Time),
+    ("proof", Need::Time),
+    ("rest", Need::Time),
+    ("wait", Need::Time),
+    ("cool", Need::TimeOrTemperature),
+    ("chill", Need::TimeOrTemperature),
+    ("freeze", Need::Temperature),
+];
+
+/// One requirement for certification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Criterion {
+    StepParams,
+    MachineAmounts,
+    Targets,
+    StepGraph,
+    Equipment,
+    TextParams,
+}
+
+/// Every criterion, in report order
+pub const CRITERIA: &[Criterion] = &[
+    Criterion::StepParams,
+    Criterion::MachineAmounts,
+    Criterion::Targets,
+    Criterion::StepGraph,
+    Criterion::Equipment,
+    Criterion::TextParams,
+];
+
+impl Criterion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Criterion::StepParams => "step params",
+            Criterion::MachineAmounts => "machine amounts",
+            Criterion::Targets => "targets",
+            Criterion::StepGraph => "step graph",
+            Criterion::Equipment => "equipment",
+            Criterion::TextParams => "text params",
+        }
+    }
+}
+
+/// A path failing a criterion and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub criterion: Criterion,
+    /// JSON pointer into the recipe
+    pub path: String,
+    pub reason: String,
+}
+
+/// Outcome of certifying one recipe
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CertificationReport {
+    pub failures: Vec<Failure>,
+}
+
+impl CertificationReport {
+    /// Whether the recipe passes every criterion
+    pub fn certified(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Failures of one criterion
+    pub fn failures_of(&self, criterion: Criterion) -> impl Iterator<Item = &Failure> {
+        self.failures.iter().filter(move |f| f.criterion == criterion)
+    }
+
+    fn fail(&mut self, criterion: Criterion, path: String, reason: String) {
+        self.failures.push(Failure { criterion, path, reason });
+    }
+}
+
+impl fmt::Display for CertificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for criterion in CRITERIA {
+            let failures: Vec<&Failure> = self.failures_of(*criterion).collect();
+            writeln!(f, "{} {}", if failures.is_empty() { "✅" } else { "❌" }, criterion.name())?;
+            for failure in failures {
+                writeln!(f, "     {}: {}", failure.path, failure.reason)?;
+            }
+        }
+        if self.certified() {
+            writeln!(f, "Certified for machine execution")
+        } else {
+            writeln!(f, "Not certified: {} problem(s)", self.failures.len())
+        }
+    }
+}
+
+fn array<'a>(recipe: &'a Value, key: &str) -> Vec<&'a Value> {
+    recipe.get(key).and_then(|v| v.as_array()).into_iter().flatten().collect()
+}
+
+fn step_id(step: &Value) -> &str {
+    step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?")
+}
+
+fn check_params(steps: &[&Value], report: &mut CertificationReport) {
+    for (index, step) in steps.iter().enumerate() {
+        let action = step.get("action").and_then(|v| v.as_str()).unwrap_or("");
+        let need = match ACTION_PARAMS.iter().find(|(a, _)| *a == action) {
+            Some((_, need)) => *need,
+            None => continue,
+        };
+        let (time, temperature) = (step_duration_minutes(step).is_some(), step_temperature_c(step).is_some());
+        let missing = match need {
+            Need::Time if !time => Some("a time"),
+            Need::Temperature if !temperature => Some("a temperature"),
+            Need::TimeAndTemperature if !time && !temperature => Some("a time and a temperature"),
+            Need::TimeAndTemperature if !time => Some("a time"),
+            Need::TimeAndTemperature if !temperature => Some("a temperature"),
+            Need::TimeOrTemperature if !time && !temperature => Some("a time or a temperature"),
+            _ => None,
+        };
+        if let Some(missing) = missing {
+            let reason = format!("'{}' needs {} in params", action, missing);
+            report.fail(Criterion::StepParams, format!("/steps/{}/params", index), reason);
+        }
+    }
+}
+
+fn check_amounts(ingredients: &[&Value], report: &mut CertificationReport) {
+    for (index, ingredient) in ingredients.iter().enumerate() {
+        let path = format!("/ingredients/{}/machine_amount", index);
+        let amount = match ingredient.get("machine_amount") {
+            Some(amount) => amount,
+            None => {
+                report.fail(Criterion::MachineAmounts, path, "missing".to_string());
+                continue;
+            }
+        };
+        let value = amount.get("value").and_then(|v| v.as_f64()).filter(|v| v.is_finite());
+        let unit = amount.get("unit").and_then(|v| v.as_str()).unwrap_or("");
+        if value.is_none() {
+            report.fail(Criterion::MachineAmounts, path, "no numeric value".to_string());
+        } else if measured(1.0, unit).is_none() && !COUNT_UNITS.contains(&unit) {
+            report.fail(Criterion::MachineAmounts, path, format!("unit '{}' cannot be measured by a machine", unit));
+        }
+    }
+}
+
+/// Steps whose results each step uses, after reporting targets that do not
+/// parse or resolve
+fn check_targets<'a>(
+    recipe: &'a Value,
+    steps: &[&'a Value],
+    ingredients: &[&Value],
+    report: &mut CertificationReport,
+) -> Vec<Vec<&'a str>> {
+    let ingredient_ids: HashSet<&str> = ingredients.iter().filter_map(|i| i.get("id").and_then(|v| v.as_str())).collect();
+    let step_ids: HashSet<&str> = steps.iter().map(|s| step_id(s)).collect();
+
+    let mut uses = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        let mut used = Vec::new();
+        let targets = step.get("target").and_then(|v| v.as_array()).into_iter().flatten();
+        for (position, target) in targets.enumerate() {
+            let path = format!("/steps/{}/target/{}", index, position);
+            let target = match target.as_str() {
+                Some(target) => target,
+                None => {
+                    report.fail(Criterion::Targets, path, "not a string".to_string());
+                    continue;
+                }
+            };
+            if target.starts_with("ing-") {
+                if !ingredient_ids.contains(target) {
+                    report.fail(Criterion::Targets, path, format!("no ingredient '{}'", target));
+                }
+            } else if portions::whole(target).ends_with(":result") || target.starts_with('@') {
+                match producer(recipe, target).filter(|id| step_ids.contains(id)) {
+                    Some(id) => used.push(id),
+                    None => report.fail(Criterion::Targets, path, format!("'{}' names no step result", target)),
+                }
+            } else {
+                report.fail(Criterion::Targets, path, format!("'{}' is not an ingredient id or step result", target));
+            }
+        }
+        uses.push(used);
+    }
+    uses
+}
+
+fn check_graph(steps: &[&Value], uses: &[Vec<&str>], report: &mut CertificationReport) {
+    let ids: Vec<&str> = steps.iter().map(|s| step_id(s)).collect();
+    let position: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+
+    // Depth-first search over "uses the result of" edges; 1 = on the path
+    let mut state = vec![0u8; steps.len()];
+    fn visit(node: usize, uses: &[Vec<&str>], position: &HashMap<&str, usize>, state: &mut [u8]) -> Option<usize> {
+        state[node] = 1;
+        for next in uses[node].iter().filter_map(|id| position.get(id).copied()) {
+            match state[next] {
+                1 => return Some(next),
+                0 => {
+                    if let Some(found) = visit(next, uses, position, state) {
+                        return Some(found);
+                    }
+                }
+                _ => {}
+            }
+        }
+        state[node] = 2;
+        None
+    }
+    let cycle = (0..steps.len()).find_map(|node| match state[node] {
+        0 => visit(node, uses, &position, &mut state),
+        _ => None,
+    });
+    if let Some(found) = cycle {
+        let reason = format!("step {} depends on its own result", ids[found]);
+        report.fail(Criterion::StepGraph, format!("/steps/{}", found), reason);
+        return;
+    }
+
+    let consumed: HashSet<&str> = uses.iter().flatten().copied().collect();
+    let outputs: Vec<&str> = ids.iter().copied().filter(|id| !consumed.contains(id)).collect();
+    if outputs.len() != 1 {
+        let reason = format!("{} steps have results no other step uses ({}); expected one", outputs.len(), outputs.join(", "));
+        report.fail(Criterion::StepGraph, "/steps".to_string(), reason);
+    }
+}
+
+fn check_equipment(recipe: &Value, steps: &[&Value], report: &mut CertificationReport) {
+    let profiles: HashSet<&str> =
+        array(recipe, "device_profiles").iter().filter_map(|p| p.get("id").and_then(|v| v.as_str())).collect();
+    for (index, step) in steps.iter().enumerate() {
+        if let Some(equipment) = step_equipment(step).filter(|e| !profiles.contains(e)) {
+            let reason = format!("'{}' has no device profile", equipment);
+            report.fail(Criterion::Equipment, format!("/steps/{}", index), reason);
+        }
+    }
+}
+
+fn check_text(steps: &[&Value], report: &mut CertificationReport) {
+    for (index, step) in steps.iter().enumerate() {
+        let text = step.get("human_text").and_then(|v| v.as_str()).unwrap_or("");
+        let path = format!("/steps/{}/human_text", index);
+        if TEMPERATURE_TEXT.is_match(text) && step_temperature_c(step).is_none() {
+            report.fail(Criterion::TextParams, path.clone(), "states a temperature missing from params".to_string());
+        }
+        if TIME_TEXT.is_match(text) && step_duration_minutes(step).is_none() {
+            report.fail(Criterion::TextParams, path, "states a time missing from params".to_string());
+        }
+    }
+}
+
+/// Check a recipe against every certification criterion
+pub fn check(recipe: &Value) -> CertificationReport {
+    let steps = array(recipe, "steps");
+    let ingredients = array(recipe, "ingredients");
+    let mut report = CertificationReport::default();
+
+    check_params(&steps, &mut report);
+    check_amounts(&ingredients, &mut report);
+    let uses = check_targets(recipe, &steps, &ingredients, &mut report);
+    check_graph(&steps, &uses, &mut report);
+    check_equipment(recipe, &steps, &mut report);
+    check_text(&steps, &mut report);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "machine_amount": {"value": 200, "unit": "g"}},
+                {"id": "ing-0002", "name": "eggs", "machine_amount": {"value": 2, "unit": "pcs"}}
+            ],
+            "device_profiles": [{"id": "mixer-1", "type": "mixer"}, {"id": "oven-1", "type": "oven"}],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "human_text": "Mix for 2 minutes", "target": ["ing-0001", "ing-0002"],
+                 "device_profile_ref": "mixer-1", "params": {"time_minutes": 2}, "result": {"name": "batter"}},
+                {"step_id": "s-02", "action": "bake", "human_text": "Bake at 180°C", "target": ["@batter"],
+                 "device_profile_ref": "oven-1", "params": {"time_minutes": 25, "temperature_c": 180}}
+            ]
+        })
+    }
+
+    fn failures(report: &CertificationReport) -> Vec<(Criterion, &str, &str)> {
+        report.failures.iter().map(|f| (f.criterion, f.path.as_str(), f.reason.as_str())).collect()
+    }
+
+    #[test]
+    fn test_certified_recipe() {
+        let report = check(&recipe());
+        assert!(report.certified(), "{}", report);
+        assert!(report.to_string().ends_with("Certified for machine execution\n"));
+    }
+
+    #[test]
+    fn test_each_criterion_reports_paths() {
+        let mut recipe = recipe();
+        recipe["ingredients"][1]["machine_amount"]["unit"] = json!("pinch");
+        recipe["steps"][0]["params"] = json!({});
+        recipe["steps"][1]["params"] = json!({"temperature_c": 180});
+        recipe["steps"][1]["human_text"] = json!("Bake for 25 min");
+        recipe["steps"][1]["device_profile_ref"] = json!("oven-2");
+        recipe["steps"][1]["target"] = json!(["@batter", "ing-0009"]);
+
+        let report = check(&recipe);
+        assert!(!report.certified());
+        assert_eq!(failures(&report), vec![
+            (Criterion::StepParams, "/steps/0/params", "'mix' needs a time in params"),
+            (Criterion::StepParams, "/steps/1/params", "'bake' needs a time in params"),
+            (Criterion::MachineAmounts, "/ingredients/1/machine_amount", "unit 'pinch' cannot be measured by a machine"),
+            (Criterion::Targets, "/steps/1/target/1", "no ingredient 'ing-0009'"),
+            (Criterion::Equipment, "/steps/1", "'oven-2' has no device profile"),
+            (Criterion::TextParams, "/steps/0/human_text", "states a time missing from params"),
+            (Criterion::TextParams, "/steps/1/human_text", "states a time missing from params"),
+        ]);
+    }
+
+    #[test]
+    fn test_cycles_and_outputs() {
+        let mut recipe = recipe();
+        recipe["steps"][0]["target"] = json!(["ing-0001", "s-02:result"]);
+        let report = check(&recipe);
+        assert_eq!(failures(&report), vec![(Criterion::StepGraph, "/steps/0", "step s-01 depends on its own result")]);
+
+        let mut recipe = self::recipe();
+        recipe["steps"][1]["target"] = json!(["ing-0002"]);
+        recipe["steps"][0]["target"] = json!(["ing-0001"]);
+        let report = check(&recipe);
+        assert_eq!(failures(&report), vec![(
+            Criterion::StepGraph,
+            "/steps",
+            "2 steps have results no other step uses (s-01, s-02); expected one"
+        )]);
+    }
+}
@@ -0,0 +1,199 @@
+// Refrigeration and freezing guards
+//
+// Blast-chiller integrations need a target temperature or time limit on every
+// `cool`/`chill`/`freeze` step, and those targets have to be physically
+// plausible. A chill step following a hot step must allow enough time for the
+// mass involved, which is taken from the vessel contents model in `scaling`.
+
+use crate::intermediates::producer;
+use crate::scaling::step_contents;
+use crate::{codes, step_duration_minutes, step_temperature_c, ValidationResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const HEAT_ACTIONS: &[&str] = &["heat", "boil", "simmer", "steam", "fry", "saute", "bake", "roast", "grill"];
+
+/// Thresholds for the chilling rules
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChillPolicy {
+    /// Report missing temperature/duration as an error instead of a warning
+    pub require_params: bool,
+    /// Freeze targets must be at or below this temperature
+    pub freeze_max_c: f64,
+    /// Chill targets must fall within this range
+    pub chill_min_c: f64,
+    pub chill_max_c: f64,
+    /// Steps above this temperature make the following chill a "hot" chill
+    pub hot_threshold_c: f64,
+    /// Minimum chilling time per kilogram of hot food
+    pub min_minutes_per_kg: f64,
+}
+
+impl Default for ChillPolicy {
+    fn default() -> Self {
+        ChillPolicy {
+            require_params: false,
+            freeze_max_c: -15.0,
+            chill_min_c: 0.0,
+            chill_max_c: 8.0,
+            hot_threshold_c: 60.0,
+            min_minutes_per_kg: 15.0,
+        }
+    }
+}
+
+/// Target temperature of a step, from its params or `done_when`
+fn target_temperature(step: &Value) -> Option<f64> {
+    step_temperature_c(step).or_else(|| {
+        step.get("done_when")
+            .and_then(|d| d.get("temperature_c"))
+            .and_then(|v| v.as_f64())
+    })
+}
+
+/// Apply the chilling rules to every cooling step of a recipe
+pub fn check(recipe: &Value, policy: &ChillPolicy, result: &mut ValidationResult) {
+    let steps = match recipe.get("steps").and_then(|v| v.as_array()) {
+        Some(steps) => steps,
+        None => return,
+    };
+
+    let contents: HashMap<String, f64> = step_contents(recipe)
+        .into_iter()
+        .map(|(id, c)| (id, c.grams))
+        .collect();
+
+    let mut hot_steps: HashMap<&str, f64> = HashMap::new();
+    let mut previous_hot: Option<f64> = None;
+
+    for (index, step) in steps.iter().enumerate() {
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+        let action = step.get("action").and_then(|v| v.as_str()).unwrap_or("");
+        let temperature = target_temperature(step);
+        let duration = step_duration_minutes(step);
+
+        if HEAT_ACTIONS.contains(&action) {
+            let hot = temperature.filter(|t| *t > policy.hot_threshold_c);
+            if let Some(t) = hot {
+                hot_steps.insert(step_id, t);
+            }
+            previous_hot = hot;
+            continue;
+        }
+
+        if !["cool", "chill", "freeze"].contains(&action) {
+            previous_hot = None;
+            continue;
+        }
+
+        if temperature.is_none() && duration.is_none() {
+            let params: [(&'static str, &dyn std::fmt::Display); 2] = [("index", &index), ("action", &action)];
+            if policy.require_params {
+                result.error(codes::MISSING_CHILL_PARAMS, &params);
+            } else {
+                result.warning(codes::MISSING_CHILL_PARAMS_WARNING, &params);
+            }
+        }
+
+        if let Some(t) = temperature {
+            if action == "freeze" && t > policy.freeze_max_c {
+                result.warning(codes::FREEZE_TARGET, &[("index", &index), ("target", &t), ("max", &policy.freeze_max_c)]);
+            }
+            if action == "chill" && !(policy.chill_min_c..=policy.chill_max_c).contains(&t) {
+                result.warning(codes::CHILL_TARGET, &[
+                    ("index", &index),
+                    ("target", &t),
+                    ("min", &policy.chill_min_c),
+                    ("max", &policy.chill_max_c),
+                ]);
+            }
+        }
+
+        // Where does the food come from? Explicit result targets, else the previous step.
+        let targets: Vec<&str> = step.get("target")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|t| t.as_str()).collect())
+            .unwrap_or_default();
+        let source_hot = if targets.is_empty() {
+            previous_hot
+        } else {
+            targets.iter()
+                .filter_map(|t| producer(recipe, t))
+                .filter_map(|id| hot_steps.get(id).copied())
+                .reduce(f64::max)
+        };
+
+        if let (Some(hot), Some(minutes)) = (source_hot, duration) {
+            let grams = contents.get(step_id).copied().unwrap_or(0.0);
+            let minimum = grams / 1000.0 * policy.min_minutes_per_kg;
+            if grams > 0.0 && minutes < minimum {
+                result.warning(codes::HOT_CHILL, &[
+                    ("index", &index),
+                    ("kg", &format!("{:.1}", grams / 1000.0)),
+                    ("from", &hot),
+                    ("minutes", &minutes),
+                    ("minimum", &format!("{:.0}", minimum)),
+                ]);
+            }
+        }
+
+        previous_hot = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(recipe: &Value, policy: &ChillPolicy) -> ValidationResult {
+        let mut result = ValidationResult::new();
+        check(recipe, policy, &mut result);
+        result
+    }
+
+    #[test]
+    fn test_missing_params() {
+        let recipe = json!({"steps": [{"step_id": "s-01", "action": "chill"}]});
+
+        let lenient = run(&recipe, &ChillPolicy::default());
+        assert!(lenient.valid);
+        assert_eq!(lenient.warnings.len(), 1);
+
+        let strict = run(&recipe, &ChillPolicy { require_params: true, ..ChillPolicy::default() });
+        assert!(!strict.valid);
+        assert!(strict.errors[0].contains("target temperature or duration"));
+    }
+
+    #[test]
+    fn test_target_ranges() {
+        let recipe = json!({"steps": [
+            {"step_id": "s-01", "action": "freeze", "params": {"temperature_c": -5}},
+            {"step_id": "s-02", "action": "chill", "params": {"temperature_c": 12}},
+            {"step_id": "s-03", "action": "chill", "done_when": {"temperature_c": 4}}
+        ]});
+
+        let result = run(&recipe, &ChillPolicy::default());
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.warnings[0].contains("freeze target -5°C"));
+        assert!(result.warnings[1].contains("chill target 12°C"));
+    }
+
+    #[test]
+    fn test_hot_chill_needs_time_for_mass() {
+        let recipe = json!({
+            "ingredients": [{"id": "ing-0001", "machine_amount": {"value": 5, "unit": "kg"}}],
+            "steps": [
+                {"step_id": "s-01", "action": "boil", "target": ["ing-0001"], "params": {"temperature_c": 100}},
+                {"step_id": "s-02", "action": "chill", "target": ["s-01:result"],
+                 "params": {"temperature_c": 4, "time_minutes": 2}}
+            ]
+        });
+
+        let result = run(&recipe, &ChillPolicy::default());
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].contains("allow at least 75 min"));
+    }
+}
@@ -0,0 +1,331 @@
+// Diet-label evidence
+//
+// The diet-label rules in `allergens` catch recipes that contain what a label
+// excludes. Auditors need the converse: positive evidence, ingredient by
+// ingredient, that nothing excluded is in the recipe. An ingredient supports
+// a claim through any of
+// - its own `allergens` array, explicitly listing nothing the label excludes
+//   (`[]` included)
+// - an external id that a resolver confirms, for a product with none of the
+//   excluded allergens
+// - an `x-verified-by` annotation naming who checked it
+// A claim is violated when an ingredient contains an excluded allergen by its
+// declaration or its resolved product, unverified when some ingredient has no
+// evidence, and verified otherwise. Labels that exclude no allergens, such as
+// `vegan`, cannot be backed by allergen evidence and are not reported. Traces
+// are left to the diet-label rules.
+
+use crate::allergens::DIET_EXCLUSIONS;
+use crate::resolve::Resolvers;
+use crate::{codes, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// One piece of evidence that an ingredient is free of a label's allergens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Evidence {
+    /// The ingredient's own allergen declaration
+    DeclaredAllergens { allergens: Vec<String> },
+    /// A product a resolver found for one of the ingredient's external ids
+    ExternalId { id_type: String, id: String, source: String },
+    /// An `x-verified-by` annotation
+    VerifiedBy { by: String },
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Evidence::DeclaredAllergens { allergens } if allergens.is_empty() => write!(f, "declares no allergens"),
+            Evidence::DeclaredAllergens { allergens } => write!(f, "declares only {}", allergens.join(", ")),
+            Evidence::ExternalId { id_type, id, source } => write!(f, "{} {} confirmed by {}", id_type, id, source),
+            Evidence::VerifiedBy { by } => write!(f, "verified by {}", by),
+        }
+    }
+}
+
+/// Whether a claim holds up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClaimStatus {
+    Verified,
+    Unverified,
+    Violated,
+}
+
+impl fmt::Display for ClaimStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClaimStatus::Verified => write!(f, "verified"),
+            ClaimStatus::Unverified => write!(f, "unverified"),
+            ClaimStatus::Violated => write!(f, "violated"),
+        }
+    }
+}
+
+/// The evidence one ingredient offers for a claim
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngredientEvidence {
+    pub id: String,
+    pub name: String,
+    pub supporting: Vec<Evidence>,
+    /// Evidence that was looked for and not found
+    pub missing: Vec<String>,
+    /// Excluded allergens the ingredient contains
+    pub contains: Vec<String>,
+}
+
+impl IngredientEvidence {
+    pub fn status(&self) -> ClaimStatus {
+        if !self.contains.is_empty() {
+            ClaimStatus::Violated
+        } else if self.supporting.is_empty() {
+            ClaimStatus::Unverified
+        } else {
+            ClaimStatus::Verified
+        }
+    }
+}
+
+/// A diet label and the evidence for it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Claim {
+    pub label: String,
+    pub excludes: Vec<String>,
+    pub status: ClaimStatus,
+    pub ingredients: Vec<IngredientEvidence>,
+}
+
+impl Claim {
+    /// Names of the ingredients without evidence
+    pub fn unverified_ingredients(&self) -> Vec<&str> {
+        self.ingredients
+            .iter()
+            .filter(|i| i.status() == ClaimStatus::Unverified)
+            .map(|i| i.name.as_str())
+            .collect()
+    }
+}
+
+/// Evidence for every diet label of a recipe
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ClaimsReport {
+    pub claims: Vec<Claim>,
+}
+
+impl ClaimsReport {
+    /// True when every claim is verified
+    pub fn verified(&self) -> bool {
+        self.claims.iter().all(|c| c.status == ClaimStatus::Verified)
+    }
+}
+
+impl fmt::Display for ClaimsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.claims.is_empty() {
+            return writeln!(f, "No allergen-based diet labels to verify");
+        }
+        for claim in &self.claims {
+            writeln!(f, "{} ({}): {}", claim.label, claim.excludes.join(", "), claim.status)?;
+            for ingredient in &claim.ingredients {
+                writeln!(f, "  {} {}: {}", ingredient.id, ingredient.name, ingredient.status())?;
+                for allergen in &ingredient.contains {
+                    writeln!(f, "    ✗ contains {}", allergen)?;
+                }
+                for evidence in &ingredient.supporting {
+                    writeln!(f, "    ✓ {}", evidence)?;
+                }
+                for missing in &ingredient.missing {
+                    writeln!(f, "    ? {}", missing)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// True if none of the allergens is excluded; records those that are
+fn free_of(allergens: &[String], excludes: &[&str], evidence: &mut IngredientEvidence) -> bool {
+    let mut free = true;
+    for allergen in allergens.iter().filter(|a| excludes.contains(&a.as_str())) {
+        free = false;
+        if !evidence.contains.contains(allergen) {
+            evidence.contains.push(allergen.clone());
+        }
+    }
+    free
+}
+
+fn ingredient_evidence(ingredient: &Value, excludes: &[&str], resolvers: Option<&Resolvers>) -> IngredientEvidence {
+    let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or(&id).to_string();
+    let mut evidence = IngredientEvidence { id, name, supporting: Vec::new(), missing: Vec::new(), contains: Vec::new() };
+
+    if ingredient.get("allergens").map(|v| v.is_array()).unwrap_or(false) {
+        let allergens = strings(ingredient.get("allergens"));
+        if free_of(&allergens, excludes, &mut evidence) {
+            evidence.supporting.push(Evidence::DeclaredAllergens { allergens });
+        }
+    } else {
+        evidence.missing.push("no allergens declared".to_string());
+    }
+
+    let ids = ingredient.get("external_ids").and_then(|v| v.as_object());
+    if let (Some(resolvers), Some(ids)) = (resolvers, ids) {
+        for (id_type, id) in ids {
+            let id = match id.as_str() {
+                Some(id) => id,
+                None => continue,
+            };
+            match resolvers.resolve(id_type, id) {
+                Ok(Some(product)) => {
+                    if free_of(&product.allergens, excludes, &mut evidence) {
+                        evidence.supporting.push(Evidence::ExternalId {
+                            id_type: id_type.clone(),
+                            id: id.to_string(),
+                            source: product.source,
+                        });
+                    }
+                }
+                Ok(None) => evidence.missing.push(format!("{} {} is unknown to the resolvers", id_type, id)),
+                Err(e) => evidence.missing.push(format!("{} {} could not be resolved: {}", id_type, id, e)),
+            }
+        }
+    }
+
+    match ingredient.get("x-verified-by") {
+        Some(Value::String(by)) if !by.trim().is_empty() => {
+            evidence.supporting.push(Evidence::VerifiedBy { by: by.clone() });
+        }
+        Some(_) => evidence.missing.push("x-verified-by does not name who verified it".to_string()),
+        None => {}
+    }
+
+    evidence
+}
+
+/// Gather the evidence for every allergen-based diet label of a recipe.
+/// External ids are only looked up when resolvers are given
+pub fn verify(recipe: &Value, resolvers: Option<&Resolvers>) -> ClaimsReport {
+    let ingredients: Vec<&Value> = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let mut claims = Vec::new();
+
+    for label in strings(recipe.pointer("/meta/diet_labels")) {
+        let excludes = match DIET_EXCLUSIONS.iter().find(|(name, _)| *name == label) {
+            Some((_, excludes)) => *excludes,
+            None => continue,
+        };
+        let ingredients: Vec<IngredientEvidence> = ingredients
+            .iter()
+            .map(|ingredient| ingredient_evidence(ingredient, excludes, resolvers))
+            .collect();
+        let statuses: Vec<ClaimStatus> = ingredients.iter().map(|i| i.status()).collect();
+        let status = if statuses.contains(&ClaimStatus::Violated) {
+            ClaimStatus::Violated
+        } else if statuses.contains(&ClaimStatus::Unverified) {
+            ClaimStatus::Unverified
+        } else {
+            ClaimStatus::Verified
+        };
+        claims.push(Claim {
+            label,
+            excludes: excludes.iter().map(|a| a.to_string()).collect(),
+            status,
+            ingredients,
+        });
+    }
+
+    ClaimsReport { claims }
+}
+
+/// Warn about diet labels without evidence for every ingredient. Violated
+/// labels are left to the diet-label rules
+pub fn check(recipe: &Value, resolvers: Option<&Resolvers>, result: &mut ValidationResult) {
+    for claim in verify(recipe, resolvers).claims {
+        if claim.status == ClaimStatus::Unverified {
+            result.warning(codes::UNVERIFIED_DIET_LABEL, &[
+                ("label", &claim.label),
+                ("ingredients", &claim.unverified_ingredients().join(", ")),
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::{MockResolver, ResolvedProduct};
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "meta": {"diet_labels": ["nut-free", "kosher"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "allergens": ["wheat", "gluten"]},
+                {"id": "ing-0002", "name": "chocolate", "external_ids": {"GTIN": "4000417025005"}},
+                {"id": "ing-0003", "name": "salt", "x-verified-by": "QA lab, batch 2025-14"}
+            ]
+        })
+    }
+
+    fn resolvers(allergens: &[&str]) -> Resolvers {
+        let mut resolvers = Resolvers::new();
+        resolvers.push(Box::new(MockResolver::new().with("GTIN", "4000417025005", ResolvedProduct {
+            source: "mock".to_string(),
+            allergens: allergens.iter().map(|a| a.to_string()).collect(),
+            ..Default::default()
+        })));
+        resolvers
+    }
+
+    #[test]
+    fn test_missing_evidence_leaves_claim_unverified() {
+        let report = verify(&recipe(), None);
+        assert_eq!(report.claims.len(), 1);
+        let claim = &report.claims[0];
+        assert_eq!(claim.status, ClaimStatus::Unverified);
+        assert_eq!(claim.unverified_ingredients(), vec!["chocolate"]);
+        assert_eq!(claim.ingredients[0].supporting, vec![Evidence::DeclaredAllergens {
+            allergens: vec!["wheat".to_string(), "gluten".to_string()],
+        }]);
+        assert_eq!(claim.ingredients[1].missing, vec!["no allergens declared"]);
+        assert_eq!(claim.ingredients[2].supporting[0].to_string(), "verified by QA lab, batch 2025-14");
+        assert!(!report.verified());
+
+        let mut result = ValidationResult::new();
+        check(&recipe(), None, &mut result);
+        assert_eq!(result.warnings, vec!["Diet label 'nut-free' is unverified: no allergen evidence for chocolate"]);
+    }
+
+    #[test]
+    fn test_resolver_confirms_or_violates() {
+        let report = verify(&recipe(), Some(&resolvers(&["milk", "soybeans"])));
+        assert_eq!(report.claims[0].status, ClaimStatus::Verified);
+        assert!(report.verified());
+        assert_eq!(report.claims[0].ingredients[1].supporting[0].to_string(), "GTIN 4000417025005 confirmed by mock");
+
+        let report = verify(&recipe(), Some(&resolvers(&["milk", "tree-nuts"])));
+        assert_eq!(report.claims[0].status, ClaimStatus::Violated);
+        assert_eq!(report.claims[0].ingredients[1].contains, vec!["tree-nuts"]);
+
+        let mut result = ValidationResult::new();
+        check(&recipe(), Some(&resolvers(&["tree-nuts"])), &mut result);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_report_serializes() {
+        let report = serde_json::to_value(verify(&recipe(), None)).unwrap();
+        assert_eq!(report["claims"][0]["status"], "unverified");
+        assert_eq!(report["claims"][0]["ingredients"][2]["supporting"][0], json!({"kind": "verified-by", "by": "QA lab, batch 2025-14"}));
+        assert_eq!(report["claims"][0]["ingredients"][0]["supporting"][0]["kind"], "declared-allergens");
+    }
+}
@@ -0,0 +1,148 @@
+// Stable rule codes
+//
+// Every issue the validator reports carries one of these codes. They key the
+// message catalogs in `assets/messages` and never change once released, so
+// tooling can match on them regardless of the output language.
+
+pub const NOT_INITIALIZED: &str = "RCIP-E-NOT-INITIALIZED";
+pub const SCHEMA: &str = "RCIP-E-SCHEMA";
+pub const SCHEMA_SKIPPED: &str = "RCIP-E-SCHEMA-SKIPPED";
+pub const READ_FAILED: &str = "RCIP-E-READ-FAILED";
+pub const BAD_RECIPE_ID: &str = "RCIP-E-BAD-RECIPE-ID";
+pub const VERSION_MISMATCH: &str = "RCIP-W-VERSION-MISMATCH";
+pub const DEPRECATED_FIELD: &str = "RCIP-W-DEPRECATED-FIELD";
+pub const REMOVED_FIELD: &str = "RCIP-E-REMOVED-FIELD";
+pub const MISE_EN_PLACE: &str = "RCIP-W-MISE-EN-PLACE";
+
+pub const BAD_INGREDIENT_ID: &str = "RCIP-E-BAD-INGREDIENT-ID";
+pub const UNCLASSIFIED_INGREDIENT: &str = "RCIP-W-UNCLASSIFIED-INGREDIENT";
+pub const MISSING_ALLERGENS: &str = "RCIP-E-MISSING-ALLERGENS";
+pub const ALLERGENS_NOT_ARRAY: &str = "RCIP-E-ALLERGENS-NOT-ARRAY";
+pub const BAD_ALLERGEN: &str = "RCIP-E-BAD-ALLERGEN";
+pub const OPTIONAL_NOT_BOOLEAN: &str = "RCIP-E-OPTIONAL-NOT-BOOLEAN";
+pub const MOSTLY_OPTIONAL: &str = "RCIP-W-MOSTLY-OPTIONAL";
+pub const BAD_TRACE_ALLERGEN: &str = "RCIP-E-BAD-TRACE-ALLERGEN";
+pub const REDUNDANT_TRACE: &str = "RCIP-W-REDUNDANT-TRACE";
+pub const BAD_FACILITY_ALLERGEN: &str = "RCIP-E-BAD-FACILITY-ALLERGEN";
+pub const NONCANONICAL_TERM: &str = "RCIP-W-NONCANONICAL-TERM";
+pub const DUPLICATE_TERM: &str = "RCIP-W-DUPLICATE-TERM";
+pub const MISSPELLED_TERM: &str = "RCIP-E-MISSPELLED-TERM";
+pub const UNKNOWN_FIELD: &str = "RCIP-E-UNKNOWN-FIELD";
+pub const MISSPELLED_FIELD: &str = "RCIP-E-MISSPELLED-FIELD";
+pub const BAD_DIET_LABEL: &str = "RCIP-E-BAD-DIET-LABEL";
+pub const DIET_CONFLICT: &str = "RCIP-E-DIET-CONFLICT";
+pub const DIET_TRACE_CONFLICT: &str = "RCIP-W-DIET-TRACE-CONFLICT";
+pub const DIET_OPTIONAL_CONFLICT: &str = "RCIP-W-DIET-OPTIONAL-CONFLICT";
+pub const DIET_NAME_CONFLICT: &str = "RCIP-W-DIET-NAME-CONFLICT";
+pub const UNVERIFIED_DIET_LABEL: &str = "RCIP-W-UNVERIFIED-DIET-LABEL";
+pub const UNDECLARED_ALLERGENS: &str = "RCIP-W-UNDECLARED-ALLERGENS";
+pub const UNCONFIRMED_ALLERGENS: &str = "RCIP-W-UNCONFIRMED-ALLERGENS";
+pub const LIKELY_ALLERGEN: &str = "RCIP-W-LIKELY-ALLERGEN";
+pub const MISSING_UNIT: &str = "RCIP-E-MISSING-UNIT";
+pub const NOT_WHOLE_COUNT: &str = "RCIP-E-NOT-WHOLE-COUNT";
+pub const TOO_PRECISE: &str = "RCIP-W-TOO-PRECISE";
+pub const UNPARSED_HUMAN_AMOUNT: &str = "RCIP-W-UNPARSED-HUMAN-AMOUNT";
+pub const AMOUNT_MISMATCH: &str = "RCIP-W-AMOUNT-MISMATCH";
+pub const BAD_GTIN: &str = "RCIP-E-BAD-GTIN";
+pub const UNRESOLVABLE_PRODUCT: &str = "RCIP-E-UNRESOLVABLE-PRODUCT";
+pub const REQUIRED_PRODUCT_SUBSTITUTES: &str = "RCIP-W-REQUIRED-PRODUCT-SUBSTITUTES";
+pub const BAD_STORAGE: &str = "RCIP-E-BAD-STORAGE";
+pub const INGREDIENT_ORDER: &str = "RCIP-W-INGREDIENT-ORDER";
+pub const USAGE_ORDER: &str = "RCIP-I-USAGE-ORDER";
+pub const MARKET_ADVISORY_ALLERGEN: &str = "RCIP-I-MARKET-ADVISORY-ALLERGEN";
+
+pub const BAD_STEP_ID: &str = "RCIP-E-BAD-STEP-ID";
+pub const BAD_ACTION: &str = "RCIP-E-BAD-ACTION";
+pub const NONSTANDARD_HAZARD: &str = "RCIP-W-NONSTANDARD-HAZARD";
+pub const MISSPELLED_HAZARD: &str = "RCIP-W-MISSPELLED-HAZARD";
+pub const BAD_INGREDIENT_REF: &str = "RCIP-E-BAD-INGREDIENT-REF";
+pub const BAD_STEP_REF: &str = "RCIP-E-BAD-STEP-REF";
+pub const UNKNOWN_RESULT_NAME: &str = "RCIP-E-UNKNOWN-RESULT-NAME";
+pub const DUPLICATE_RESULT_NAME: &str = "RCIP-E-DUPLICATE-RESULT-NAME";
+pub const DIVIDE_WITHOUT_PORTIONS: &str = "RCIP-W-DIVIDE-WITHOUT-PORTIONS";
+pub const PORTION_OUT_OF_RANGE: &str = "RCIP-E-PORTION-OUT-OF-RANGE";
+pub const PORTION_OF_UNDIVIDED: &str = "RCIP-E-PORTION-OF-UNDIVIDED";
+pub const UNUSED_PORTIONS: &str = "RCIP-W-UNUSED-PORTIONS";
+pub const UNUSED_INGREDIENT: &str = "RCIP-W-UNUSED-INGREDIENT";
+pub const STEP_CYCLE: &str = "RCIP-E-STEP-CYCLE";
+pub const DANGLING_STEP: &str = "RCIP-W-DANGLING-STEP";
+pub const DEVICE_LIMIT: &str = "RCIP-E-DEVICE-LIMIT";
+pub const EMPTY_TEXT: &str = "RCIP-E-EMPTY-TEXT";
+pub const BAD_GUIDANCE: &str = "RCIP-E-BAD-GUIDANCE";
+pub const BAD_LANGUAGE_TAG: &str = "RCIP-E-BAD-LANGUAGE-TAG";
+pub const PLACEHOLDER_TEXT: &str = "RCIP-W-PLACEHOLDER-TEXT";
+pub const LONG_TEXT: &str = "RCIP-W-LONG-TEXT";
+pub const ACTION_NOT_MENTIONED: &str = "RCIP-W-ACTION-NOT-MENTIONED";
+pub const CONDITIONAL_TEXT: &str = "RCIP-W-CONDITIONAL-TEXT";
+pub const LANGUAGE_MISMATCH: &str = "RCIP-W-LANGUAGE-MISMATCH";
+pub const MISSING_LANGUAGE: &str = "RCIP-W-MISSING-LANGUAGE";
+pub const ID_SEQUENCE: &str = "RCIP-W-ID-SEQUENCE";
+pub const ID_PADDING: &str = "RCIP-W-ID-PADDING";
+pub const MARKET_UNNAMED_SOURCE: &str = "RCIP-W-MARKET-UNNAMED-SOURCE";
+pub const MARKET_MAY_CONTAIN: &str = "RCIP-W-MARKET-MAY-CONTAIN";
+pub const MARKET_UNDECLARED_ALLERGEN: &str = "RCIP-W-MARKET-UNDECLARED-ALLERGEN";
+pub const UNUSED_DEVICE_ACTIONS: &str = "RCIP-W-UNUSED-DEVICE-ACTIONS";
+pub const IMPLAUSIBLE_HYDRATION: &str = "RCIP-W-IMPLAUSIBLE-HYDRATION";
+pub const IMPLAUSIBLE_SALT: &str = "RCIP-W-IMPLAUSIBLE-SALT";
+
+pub const MISSING_FIELD: &str = "RCIP-W-MISSING-FIELD";
+pub const NO_NUTRITION: &str = "RCIP-W-NO-NUTRITION";
+pub const NO_EXTERNAL_IDS: &str = "RCIP-W-NO-EXTERNAL-IDS";
+pub const LONG_COOK_TIME: &str = "RCIP-W-LONG-COOK-TIME";
+pub const TOTAL_TIME_MISMATCH: &str = "RCIP-W-TOTAL-TIME-MISMATCH";
+pub const TIME_PARTS_EXCEED_TOTAL: &str = "RCIP-W-TIME-PARTS-EXCEED-TOTAL";
+pub const NO_IMAGES: &str = "RCIP-W-NO-IMAGES";
+
+pub const MISSING_CHILL_PARAMS: &str = "RCIP-E-MISSING-CHILL-PARAMS";
+pub const MISSING_CHILL_PARAMS_WARNING: &str = "RCIP-W-MISSING-CHILL-PARAMS";
+pub const FREEZE_TARGET: &str = "RCIP-W-FREEZE-TARGET";
+pub const CHILL_TARGET: &str = "RCIP-W-CHILL-TARGET";
+pub const HOT_CHILL: &str = "RCIP-W-HOT-CHILL";
+pub const RESULT_HELD_TOO_LONG: &str = "RCIP-W-RESULT-HELD-TOO-LONG";
+pub const FROZEN_STORAGE_MISMATCH: &str = "RCIP-W-FROZEN-STORAGE-MISMATCH";
+pub const COLD_CHAIN_DELAY: &str = "RCIP-W-COLD-CHAIN-DELAY";
+pub const FROZEN_INTO_FRY: &str = "RCIP-W-FROZEN-INTO-FRY";
+
+pub const NON_FINITE: &str = "RCIP-E-NON-FINITE";
+pub const NUMBER_AS_STRING: &str = "RCIP-W-NUMBER-AS-STRING";
+pub const OUT_OF_RANGE: &str = "RCIP-E-OUT-OF-RANGE";
+pub const NEGATIVE_ZERO: &str = "RCIP-W-NEGATIVE-ZERO";
+
+pub const INGREDIENT_OVERUSED: &str = "RCIP-E-INGREDIENT-OVERUSED";
+pub const RESULT_NOT_READY: &str = "RCIP-E-RESULT-NOT-READY";
+pub const PORTION_OVERUSED: &str = "RCIP-E-PORTION-OVERUSED";
+pub const EQUIPMENT_CONFLICT: &str = "RCIP-E-EQUIPMENT-CONFLICT";
+
+pub const RULE_PACK_FAILED: &str = "RCIP-E-RULE-PACK-FAILED";
+pub const OBSERVER_FAILED: &str = "RCIP-E-OBSERVER-FAILED";
+
+pub const CORPUS_OUTLIER: &str = "RCIP-I-CORPUS-OUTLIER";
+
+/// Every code, for catalog completeness checks
+pub const ALL: &[&str] = &[
+    NOT_INITIALIZED, SCHEMA, SCHEMA_SKIPPED, READ_FAILED, BAD_RECIPE_ID, VERSION_MISMATCH, DEPRECATED_FIELD,
+    REMOVED_FIELD, MISE_EN_PLACE,
+    BAD_INGREDIENT_ID, UNCLASSIFIED_INGREDIENT, MISSING_ALLERGENS, ALLERGENS_NOT_ARRAY,
+    BAD_ALLERGEN, OPTIONAL_NOT_BOOLEAN, MOSTLY_OPTIONAL, BAD_TRACE_ALLERGEN, REDUNDANT_TRACE,
+    BAD_FACILITY_ALLERGEN, NONCANONICAL_TERM, DUPLICATE_TERM, MISSPELLED_TERM, UNKNOWN_FIELD,
+    MISSPELLED_FIELD, BAD_DIET_LABEL,
+    DIET_CONFLICT, DIET_TRACE_CONFLICT, DIET_OPTIONAL_CONFLICT, DIET_NAME_CONFLICT, UNVERIFIED_DIET_LABEL,
+    UNDECLARED_ALLERGENS, UNCONFIRMED_ALLERGENS, LIKELY_ALLERGEN, MISSING_UNIT, NOT_WHOLE_COUNT, TOO_PRECISE,
+    UNPARSED_HUMAN_AMOUNT, AMOUNT_MISMATCH,
+    BAD_GTIN, UNRESOLVABLE_PRODUCT, REQUIRED_PRODUCT_SUBSTITUTES, BAD_STORAGE, INGREDIENT_ORDER,
+    USAGE_ORDER, MARKET_ADVISORY_ALLERGEN,
+    BAD_STEP_ID, BAD_ACTION, NONSTANDARD_HAZARD, MISSPELLED_HAZARD, BAD_INGREDIENT_REF,
+    BAD_STEP_REF, UNKNOWN_RESULT_NAME, DUPLICATE_RESULT_NAME, DIVIDE_WITHOUT_PORTIONS,
+    PORTION_OUT_OF_RANGE, PORTION_OF_UNDIVIDED, UNUSED_PORTIONS, UNUSED_INGREDIENT, STEP_CYCLE, DANGLING_STEP, DEVICE_LIMIT, EMPTY_TEXT,
+    BAD_GUIDANCE, BAD_LANGUAGE_TAG, PLACEHOLDER_TEXT, LONG_TEXT, ACTION_NOT_MENTIONED, CONDITIONAL_TEXT,
+    LANGUAGE_MISMATCH, MISSING_LANGUAGE, ID_SEQUENCE,
+    ID_PADDING, MARKET_UNNAMED_SOURCE, MARKET_MAY_CONTAIN, MARKET_UNDECLARED_ALLERGEN, UNUSED_DEVICE_ACTIONS,
+    IMPLAUSIBLE_HYDRATION, IMPLAUSIBLE_SALT,
+    MISSING_FIELD, NO_NUTRITION, NO_EXTERNAL_IDS, LONG_COOK_TIME, TOTAL_TIME_MISMATCH, TIME_PARTS_EXCEED_TOTAL, NO_IMAGES,
+    MISSING_CHILL_PARAMS, MISSING_CHILL_PARAMS_WARNING, FREEZE_TARGET, CHILL_TARGET, HOT_CHILL,
+    RESULT_HELD_TOO_LONG, FROZEN_STORAGE_MISMATCH, COLD_CHAIN_DELAY, FROZEN_INTO_FRY,
+    NON_FINITE, NUMBER_AS_STRING, OUT_OF_RANGE, NEGATIVE_ZERO,
+    INGREDIENT_OVERUSED, RESULT_NOT_READY, PORTION_OVERUSED, EQUIPMENT_CONFLICT,
+    RULE_PACK_FAILED, OBSERVER_FAILED,
+    CORPUS_OUTLIER,
+];
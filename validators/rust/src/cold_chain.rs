@@ -0,0 +1,209 @@
+// Ingredient storage and the cold chain
+//
+// An ingredient may declare how it must be stored before use in a `storage`
+// object: `max_temp_c` and whether it is `frozen`. The types are always
+// checked, and so is that `frozen` agrees with `max_temp_c`: frozen means at
+// or below `frozen_max_c`. The cold-chain rules are opt-in and read the
+// timeline from `simulate::schedule`:
+// - a chilled ingredient (stored at or below `chilled_max_c`) whose first
+//   use starts later than `max_unchilled_minutes` into the recipe must either
+//   be first used by a chill-type step or be covered by an earlier untargeted
+//   chill-type step, such as "refrigerate everything until needed"
+// - a frozen ingredient must not go straight into a `fry` step unless that
+//   step sets `params.from_frozen: true`; a first `rest` or `wait` step is the
+//   thaw
+
+use crate::simulate::schedule;
+use crate::{codes, ValidationResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const EPSILON: f64 = 1e-6;
+const CHILL_ACTIONS: &[&str] = &["cool", "chill", "freeze"];
+const FRY_ACTIONS: &[&str] = &["fry"];
+
+/// Thresholds for the cold-chain rules
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ColdChainPolicy {
+    /// Ingredients stored at or below this temperature need the cold chain
+    pub chilled_max_c: f64,
+    /// Frozen storage means at or below this temperature
+    pub frozen_max_c: f64,
+    /// Longest a chilled ingredient may wait unchilled before its first use
+    pub max_unchilled_minutes: f64,
+}
+
+impl Default for ColdChainPolicy {
+    fn default() -> Self {
+        ColdChainPolicy {
+            chilled_max_c: 4.0,
+            frozen_max_c: -15.0,
+            max_unchilled_minutes: 30.0,
+        }
+    }
+}
+
+fn targets(step: &Value) -> impl Iterator<Item = &str> {
+    step.get("target").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|t| t.as_str())
+}
+
+fn action(step: &Value) -> &str {
+    step.get("action").and_then(|v| v.as_str()).unwrap_or("")
+}
+
+/// Check the types in an ingredient's `storage` object, and that its
+/// `frozen` flag agrees with its temperature
+pub fn check_storage(ingredient: &Value, index: usize, policy: &ColdChainPolicy, result: &mut ValidationResult) {
+    let storage = match ingredient.get("storage") {
+        Some(storage) => storage,
+        None => return,
+    };
+    if !storage.is_object() {
+        result.error(codes::BAD_STORAGE, &[("index", &index), ("field", &"storage")]);
+        return;
+    }
+    if storage.get("max_temp_c").map(|v| !v.is_number()).unwrap_or(false) {
+        result.error(codes::BAD_STORAGE, &[("index", &index), ("field", &"storage.max_temp_c")]);
+    }
+    if storage.get("frozen").map(|v| !v.is_boolean()).unwrap_or(false) {
+        result.error(codes::BAD_STORAGE, &[("index", &index), ("field", &"storage.frozen")]);
+    }
+
+    let max = storage.get("max_temp_c").and_then(|v| v.as_f64());
+    let frozen = storage.get("frozen").and_then(|v| v.as_bool());
+    if let (Some(max), Some(frozen)) = (max, frozen) {
+        if frozen != (max <= policy.frozen_max_c) {
+            result.warning(codes::FROZEN_STORAGE_MISMATCH, &[
+                ("index", &index),
+                ("frozen", &frozen),
+                ("max", &max),
+                ("limit", &policy.frozen_max_c),
+            ]);
+        }
+    }
+}
+
+/// Apply the cold-chain rules to every ingredient with storage requirements
+pub fn check(recipe: &Value, policy: &ColdChainPolicy, result: &mut ValidationResult) {
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let slots = schedule(recipe);
+    let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten();
+
+    for ingredient in ingredients {
+        let storage = match ingredient.get("storage") {
+            Some(storage) => storage,
+            None => continue,
+        };
+        let max = storage.get("max_temp_c").and_then(|v| v.as_f64());
+        let frozen = storage.get("frozen").and_then(|v| v.as_bool());
+
+        let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("");
+        let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or(id);
+        let first_use = steps.iter().zip(&slots).find(|(step, _)| targets(step).any(|t| t == id));
+        let (step, slot) = match first_use {
+            Some(first_use) => first_use,
+            None => continue,
+        };
+
+        if let Some(max) = max.filter(|m| *m <= policy.chilled_max_c) {
+            let late = slot.start_minutes > policy.max_unchilled_minutes + EPSILON;
+            let held = CHILL_ACTIONS.contains(&action(step))
+                || steps.iter().zip(&slots).any(|(other, other_slot)| {
+                    other_slot.start_minutes < slot.start_minutes
+                        && CHILL_ACTIONS.contains(&action(other))
+                        && targets(other).next().is_none()
+                });
+            if late && !held {
+                result.warning(codes::COLD_CHAIN_DELAY, &[
+                    ("step", &slot.step_id),
+                    ("name", &name),
+                    ("max", &max),
+                    ("minutes", &format!("{:.0}", slot.start_minutes)),
+                    ("limit", &policy.max_unchilled_minutes),
+                ]);
+            }
+        }
+
+        let from_frozen = step.pointer("/params/from_frozen").and_then(|v| v.as_bool()).unwrap_or(false);
+        if frozen == Some(true) && FRY_ACTIONS.contains(&action(step)) && !from_frozen {
+            result.warning(codes::FROZEN_INTO_FRY, &[("step", &slot.step_id), ("name", &name)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "cream", "storage": {"max_temp_c": 4, "frozen": false}},
+                {"id": "ing-0002", "name": "shrimp", "storage": {"max_temp_c": -18, "frozen": true}},
+                {"id": "ing-0003", "name": "onion"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "simmer", "target": ["ing-0003"], "params": {"time_minutes": 45}},
+                {"step_id": "s-02", "action": "fry", "target": ["ing-0002"], "params": {"time_minutes": 5}},
+                {"step_id": "s-03", "action": "add", "target": ["ing-0001", "s-01:result"]}
+            ]
+        })
+    }
+
+    fn run(recipe: &Value) -> ValidationResult {
+        let mut result = ValidationResult::new();
+        check(recipe, &ColdChainPolicy::default(), &mut result);
+        result
+    }
+
+    #[test]
+    fn test_storage_types() {
+        let policy = ColdChainPolicy::default();
+        let mut result = ValidationResult::new();
+        check_storage(&json!({"storage": {"max_temp_c": "4", "frozen": "no"}}), 0, &policy, &mut result);
+        check_storage(&json!({"storage": 4}), 1, &policy, &mut result);
+        check_storage(&json!({"storage": {"max_temp_c": 4, "frozen": false}}), 2, &policy, &mut result);
+        assert_eq!(result.errors, vec![
+            "Ingredient 0: storage.max_temp_c has the wrong type",
+            "Ingredient 0: storage.frozen has the wrong type",
+            "Ingredient 1: storage has the wrong type",
+        ]);
+    }
+
+    #[test]
+    fn test_cold_chain_and_frozen_frying() {
+        let result = run(&recipe());
+        assert_eq!(result.warnings, vec![
+            "Step s-03: 'cream' must be kept at or below 4°C but is first used unchilled after 45 min; the limit is 30 min",
+            "Step s-02: 'shrimp' must be kept at or below -18°C but is first used unchilled after 45 min; the limit is 30 min",
+            "Step s-02: frozen 'shrimp' goes straight into the pan; add a thawing step or set from_frozen: true",
+        ]);
+
+        let mut handled = recipe();
+        handled["steps"][1]["params"]["from_frozen"] = json!(true);
+        handled["steps"]
+            .as_array_mut()
+            .unwrap()
+            .insert(0, json!({"step_id": "s-00", "action": "chill", "params": {"temperature_c": 4}}));
+        assert!(run(&handled).warnings.is_empty());
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_frozen_flag_matches_temperature() {
+        let mut recipe = recipe();
+        recipe["ingredients"][0]["storage"]["frozen"] = json!(true);
+        recipe["steps"] = json!([]);
+
+        // Reported without opting in to the cold-chain rules
+        let mut validator = crate::RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let result = validator.validate(&recipe);
+        assert!(result.warnings.contains(
+            &"Ingredient 0: storage says frozen: true but max_temp_c is 4°C; frozen storage means at or below -15°C".to_string()
+        ), "{:?}", result.warnings);
+        assert!(!result.issues.iter().any(|i| i.code == codes::COLD_CHAIN_DELAY));
+    }
+}
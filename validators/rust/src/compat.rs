@@ -0,0 +1,392 @@
+// Device compatibility checks
+//
+// Appliance vendors publish capability manifests describing what a device
+// can execute. `check` compares every step of a recipe against a manifest.
+// The limit comparisons in `check_limits` are shared with `device_limits`,
+// which applies capabilities declared inside the recipe's device profiles.
+
+use crate::{numeric, step_duration_minutes, step_temperature_c, COOKING_ACTIONS};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Inclusive temperature range in °C
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TemperatureRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Capabilities published by an appliance vendor
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DeviceManifest {
+    pub id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Supported cooking actions
+    pub actions: Vec<String>,
+    #[serde(default)]
+    pub temperature_range_c: Option<TemperatureRange>,
+    #[serde(default)]
+    pub max_duration_minutes: Option<f64>,
+    #[serde(default)]
+    pub max_speed_rpm: Option<f64>,
+    /// Attachments available on the device
+    #[serde(default)]
+    pub attachments: Vec<String>,
+}
+
+/// Errors loading a device manifest
+#[derive(Debug)]
+pub enum ManifestError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ManifestError::Io(e) => write!(f, "cannot read manifest: {}", e),
+            ManifestError::Json(e) => write!(f, "manifest is not valid JSON: {}", e),
+            ManifestError::Invalid(problems) => write!(f, "invalid manifest: {}", problems.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+impl DeviceManifest {
+    /// Load and validate a manifest file
+    pub fn from_file(path: &Path) -> Result<Self, ManifestError> {
+        let content = fs::read_to_string(path).map_err(ManifestError::Io)?;
+        let value: Value = serde_json::from_str(&content).map_err(ManifestError::Json)?;
+        Self::from_value(&value)
+    }
+
+    /// Validate a manifest document, reporting every problem found
+    pub fn from_value(value: &Value) -> Result<Self, ManifestError> {
+        let problems = validate_manifest(value);
+        if !problems.is_empty() {
+            return Err(ManifestError::Invalid(problems));
+        }
+        DeviceManifest::deserialize(value).map_err(ManifestError::Json)
+    }
+
+    /// Display name, falling back to the id
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id)
+    }
+}
+
+fn validate_manifest(value: &Value) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let obj = match value.as_object() {
+        Some(obj) => obj,
+        None => return vec!["manifest must be a JSON object".to_string()],
+    };
+
+    match obj.get("id") {
+        Some(Value::String(id)) if !id.is_empty() => {}
+        Some(_) => problems.push("id: must be a non-empty string".to_string()),
+        None => problems.push("id: missing required field".to_string()),
+    }
+
+    if let Some(name) = obj.get("name") {
+        if !name.is_string() {
+            problems.push("name: must be a string".to_string());
+        }
+    }
+
+    match obj.get("actions").map(|v| v.as_array()) {
+        Some(Some(actions)) => {
+            for (i, action) in actions.iter().enumerate() {
+                match action.as_str() {
+                    Some(a) if COOKING_ACTIONS.contains(&a) => {}
+                    Some(a) => problems.push(format!("actions[{}]: unknown action '{}'", i, a)),
+                    None => problems.push(format!("actions[{}]: must be a string", i)),
+                }
+            }
+        }
+        Some(None) => problems.push("actions: must be an array".to_string()),
+        None => problems.push("actions: missing required field".to_string()),
+    }
+
+    if let Some(range) = obj.get("temperature_range_c") {
+        let min = range.get("min").and_then(|v| v.as_f64());
+        let max = range.get("max").and_then(|v| v.as_f64());
+        match (min, max) {
+            (Some(min), Some(max)) if min > max => {
+                problems.push(format!("temperature_range_c: min {} is greater than max {}", min, max));
+            }
+            (Some(_), Some(_)) => {}
+            _ => problems.push("temperature_range_c: min and max must both be numbers".to_string()),
+        }
+    }
+
+    if let Some(max) = obj.get("max_duration_minutes") {
+        if max.as_f64().map(|m| m <= 0.0).unwrap_or(true) {
+            problems.push("max_duration_minutes: must be a positive number".to_string());
+        }
+    }
+
+    if let Some(max) = obj.get("max_speed_rpm") {
+        if max.as_f64().map(|m| m <= 0.0).unwrap_or(true) {
+            problems.push("max_speed_rpm: must be a positive number".to_string());
+        }
+    }
+
+    if let Some(attachments) = obj.get("attachments") {
+        match attachments.as_array() {
+            Some(items) if items.iter().all(|a| a.is_string()) => {}
+            _ => problems.push("attachments: must be an array of strings".to_string()),
+        }
+    }
+
+    problems
+}
+
+/// Something a device cannot do for a step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Gap {
+    UnsupportedAction { action: String },
+    TemperatureOutOfRange { value: f64, min: f64, max: f64 },
+    DurationTooLong { value: f64, max: f64 },
+    SpeedTooHigh { value: f64, max: f64 },
+    MissingAttachment { attachment: String },
+}
+
+impl fmt::Display for Gap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Gap::UnsupportedAction { action } => write!(f, "action '{}' is not supported", action),
+            Gap::TemperatureOutOfRange { value, min, max } => {
+                write!(f, "temperature {}°C is outside {}–{}°C", value, min, max)
+            }
+            Gap::DurationTooLong { value, max } => {
+                write!(f, "duration {} min exceeds the {} min maximum", value, max)
+            }
+            Gap::SpeedTooHigh { value, max } => write!(f, "speed {} rpm exceeds the {} rpm maximum", value, max),
+            Gap::MissingAttachment { attachment } => write!(f, "attachment '{}' is not available", attachment),
+        }
+    }
+}
+
+/// Gaps found for a single step
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepGap {
+    pub step_id: String,
+    pub gaps: Vec<Gap>,
+    /// Whether the step carries `fallback_instructions` covering the gap
+    pub has_fallback: bool,
+}
+
+/// Overall compatibility verdict
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Verdict {
+    FullyCompatible,
+    CompatibleWithFallbacks,
+    Incompatible,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Verdict::FullyCompatible => write!(f, "fully compatible"),
+            Verdict::CompatibleWithFallbacks => write!(f, "compatible with fallbacks"),
+            Verdict::Incompatible => write!(f, "incompatible"),
+        }
+    }
+}
+
+/// Result of checking a recipe against a device manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CompatReport {
+    pub device: String,
+    pub steps: Vec<StepGap>,
+    pub verdict: Verdict,
+}
+
+/// Compare a single step against a device's capabilities
+pub fn check_step(step: &Value, manifest: &DeviceManifest) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+
+    if let Some(action) = step.get("action").and_then(|v| v.as_str()) {
+        if !manifest.actions.iter().any(|a| a == action) {
+            gaps.push(Gap::UnsupportedAction { action: action.to_string() });
+        }
+    }
+
+    gaps.extend(check_limits(step, manifest));
+
+    if let Some(attachment) = step.get("params").and_then(|p| p.get("attachment")).and_then(|v| v.as_str()) {
+        if !manifest.attachments.iter().any(|a| a == attachment) {
+            gaps.push(Gap::MissingAttachment { attachment: attachment.to_string() });
+        }
+    }
+
+    gaps
+}
+
+/// Compare a step's temperature, duration and speed against a device's
+/// limits; limits the device does not declare are not checked
+pub fn check_limits(step: &Value, manifest: &DeviceManifest) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+
+    if let (Some(value), Some(range)) = (step_temperature_c(step), &manifest.temperature_range_c) {
+        if value < range.min || value > range.max {
+            gaps.push(Gap::TemperatureOutOfRange { value, min: range.min, max: range.max });
+        }
+    }
+
+    if let (Some(value), Some(max)) = (step_duration_minutes(step), manifest.max_duration_minutes) {
+        if value > max {
+            gaps.push(Gap::DurationTooLong { value, max });
+        }
+    }
+
+    let speed = step.get("params").and_then(|p| p.get("speed_rpm")).and_then(numeric::finite);
+    if let (Some(value), Some(max)) = (speed, manifest.max_speed_rpm) {
+        if value > max {
+            gaps.push(Gap::SpeedTooHigh { value, max });
+        }
+    }
+
+    gaps
+}
+
+/// Check every step of a recipe against a device manifest
+pub fn check(recipe: &Value, manifest: &DeviceManifest) -> CompatReport {
+    let mut steps = Vec::new();
+
+    if let Some(step_list) = recipe.get("steps").and_then(|v| v.as_array()) {
+        for (i, step) in step_list.iter().enumerate() {
+            let gaps = check_step(step, manifest);
+            if gaps.is_empty() {
+                continue;
+            }
+
+            steps.push(StepGap {
+                step_id: step.get("step_id")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|| format!("#{}", i)),
+                gaps,
+                has_fallback: has_fallback(step),
+            });
+        }
+    }
+
+    let verdict = if steps.is_empty() {
+        Verdict::FullyCompatible
+    } else if steps.iter().all(|s| s.has_fallback) {
+        Verdict::CompatibleWithFallbacks
+    } else {
+        Verdict::Incompatible
+    };
+
+    CompatReport {
+        device: manifest.display_name().to_string(),
+        steps,
+        verdict,
+    }
+}
+
+fn has_fallback(step: &Value) -> bool {
+    match step.get("fallback_instructions") {
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(Value::Array(a)) => !a.is_empty(),
+        Some(Value::Object(o)) => !o.is_empty(),
+        _ => false,
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Device: {}", self.device)?;
+        for step in &self.steps {
+            let coverage = if step.has_fallback { "covered by fallback" } else { "no fallback" };
+            writeln!(f, "  Step {} ({}):", step.step_id, coverage)?;
+            for gap in &step.gaps {
+                writeln!(f, "    - {}", gap)?;
+            }
+        }
+        writeln!(f, "Verdict: {}", self.verdict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest() -> DeviceManifest {
+        DeviceManifest::from_value(&json!({
+            "id": "thermomix-tm6",
+            "actions": ["mix", "heat", "blend", "chop"],
+            "temperature_range_c": {"min": 37, "max": 160},
+            "max_duration_minutes": 99,
+            "max_speed_rpm": 10700,
+            "attachments": ["whisk"]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_fully_compatible() {
+        let recipe = json!({"steps": [
+            {"step_id": "s-01", "action": "mix", "params": {"time_minutes": 2, "attachment": "whisk"}},
+            {"step_id": "s-02", "action": "heat", "params": {"temperature_c": 100}}
+        ]});
+        let report = check(&recipe, &manifest());
+        assert_eq!(report.verdict, Verdict::FullyCompatible);
+        assert!(report.steps.is_empty());
+    }
+
+    #[test]
+    fn test_gaps_and_fallbacks() {
+        let recipe = json!({"steps": [
+            {"step_id": "s-01", "action": "bake", "params": {"temperature_c": 220},
+             "fallback_instructions": "Bake in a conventional oven"},
+            {"step_id": "s-02", "action": "heat", "params": {"time_hours": 2, "speed_rpm": 12000, "attachment": "varoma"}}
+        ]});
+        let report = check(&recipe, &manifest());
+
+        assert_eq!(report.verdict, Verdict::Incompatible);
+        assert_eq!(report.steps.len(), 2);
+        assert!(report.steps[0].has_fallback);
+        assert_eq!(report.steps[0].gaps.len(), 2);
+        assert!(report.steps[1].gaps.contains(&Gap::DurationTooLong { value: 120.0, max: 99.0 }));
+        assert!(report.steps[1].gaps.contains(&Gap::SpeedTooHigh { value: 12000.0, max: 10700.0 }));
+        assert!(report.steps[1].gaps.contains(&Gap::MissingAttachment { attachment: "varoma".to_string() }));
+    }
+
+    #[test]
+    fn test_compatible_with_fallbacks() {
+        let recipe = json!({"steps": [
+            {"step_id": "s-01", "action": "bake", "fallback_instructions": ["Use an oven"]}
+        ]});
+        assert_eq!(check(&recipe, &manifest()).verdict, Verdict::CompatibleWithFallbacks);
+    }
+
+    #[test]
+    fn test_invalid_manifest_reports_all_problems() {
+        let err = DeviceManifest::from_value(&json!({
+            "actions": ["mix", "teleport"],
+            "temperature_range_c": {"min": 200, "max": 100}
+        }))
+        .unwrap_err();
+
+        match err {
+            ManifestError::Invalid(problems) => {
+                assert_eq!(problems.len(), 3);
+                assert!(problems.iter().any(|p| p.contains("unknown action 'teleport'")));
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+// Conformance suite
+//
+// The `conformance/` directory at the repository root holds fixtures for
+// every RCIP implementation, grouped into one folder per category. Each
+// fixture is a recipe, `<name>.rcip`, next to the issues the reference
+// validator reports for it, `<name>.expected.json`:
+//
+//     {"issues": [{"code": "RCIP-E-BAD-STEP-REF", "path": ""}]}
+//
+// Only codes and JSON pointer paths are compared, never messages, so
+// implementations are free in wording and language. Issues are compared as
+// multisets: order does not matter, repeats do.
+//
+// `run` validates every fixture with the reference validator and reports the
+// fixtures whose issues differ from their expectation; the crate's own tests
+// run the shipped suite, so a rule that changes what a fixture reports fails
+// the build until the expectation is updated (`update` rewrites them all from
+// the reference validator). Other implementations hand their issues per
+// fixture to `compare`, or to `rcip-validator conformance --actual`.
+
+use crate::warm::global_validator;
+use crate::RCIPError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Schema version fixtures are validated against
+const SCHEMA_VERSION: &str = "0.1";
+
+const RECIPE_EXTENSION: &str = "rcip";
+const EXPECTED_SUFFIX: &str = ".expected.json";
+
+/// An issue as conformance compares it
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CodedIssue {
+    pub code: String,
+    /// JSON pointer of the part of the document at fault, "" for the root
+    #[serde(default)]
+    pub path: String,
+}
+
+impl fmt::Display for CodedIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.code)
+        } else {
+            write!(f, "{} at {}", self.code, self.path)
+        }
+    }
+}
+
+/// Contents of an `.expected.json` file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Expectation {
+    pub issues: Vec<CodedIssue>,
+}
+
+/// A recipe of the suite and its expectation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fixture {
+    /// Path from the suite root without extension, `references/bad-step-ref`
+    pub name: String,
+    /// First folder of the name; "" for fixtures at the root
+    pub category: String,
+    pub recipe: PathBuf,
+    pub expected: PathBuf,
+}
+
+/// How one fixture fared
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FixtureOutcome {
+    pub fixture: String,
+    pub category: String,
+    /// Expected issues that were not reported
+    pub missing: Vec<CodedIssue>,
+    /// Reported issues that were not expected
+    pub unexpected: Vec<CodedIssue>,
+    /// Why the fixture could not be checked at all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FixtureOutcome {
+    pub fn conforms(&self) -> bool {
+        self.error.is_none() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Outcome of a conformance run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConformanceReport {
+    pub fixtures: Vec<FixtureOutcome>,
+}
+
+impl ConformanceReport {
+    pub fn failures(&self) -> impl Iterator<Item = &FixtureOutcome> {
+        self.fixtures.iter().filter(|f| !f.conforms())
+    }
+
+    pub fn is_conformant(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Conforming and total fixtures per category
+    pub fn by_category(&self) -> BTreeMap<&str, (usize, usize)> {
+        let mut categories: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for outcome in &self.fixtures {
+            let counts = categories.entry(outcome.category.as_str()).or_default();
+            counts.0 += usize::from(outcome.conforms());
+            counts.1 += 1;
+        }
+        categories
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for outcome in self.failures() {
+            writeln!(f, "{}:", outcome.fixture)?;
+            if let Some(error) = &outcome.error {
+                writeln!(f, "  error: {}", error)?;
+            }
+            for issue in &outcome.missing {
+                writeln!(f, "  missing: {}", issue)?;
+            }
+            for issue in &outcome.unexpected {
+                writeln!(f, "  unexpected: {}", issue)?;
+            }
+        }
+        let conforming = self.fixtures.iter().filter(|f| f.conforms()).count();
+        writeln!(f, "{} of {} fixtures conform", conforming, self.fixtures.len())
+    }
+}
+
+/// Every fixture under `dir`, sorted by name
+pub fn fixtures(dir: &Path) -> Result<Vec<Fixture>, RCIPError> {
+    let mut found = Vec::new();
+    collect(dir, dir, &mut found)?;
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(found)
+}
+
+fn collect(root: &Path, dir: &Path, found: &mut Vec<Fixture>) -> Result<(), RCIPError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect(root, &path, found)?;
+            continue;
+        }
+        if path.extension().map(|e| e != RECIPE_EXTENSION).unwrap_or(true) {
+            continue;
+        }
+        let relative = path.strip_prefix(root).unwrap_or(&path).with_extension("");
+        let parts: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        found.push(Fixture {
+            name: parts.join("/"),
+            category: if parts.len() > 1 { parts[0].clone() } else { String::new() },
+            expected: path.with_file_name(format!("{}{}", stem, EXPECTED_SUFFIX)),
+            recipe: path,
+        });
+    }
+    Ok(())
+}
+
+fn read_recipe(fixture: &Fixture) -> Result<Value, String> {
+    let content = fs::read_to_string(&fixture.recipe).map_err(|e| e.to_string())?;
+    serde_json::from_str(&content).map_err(|e| format!("recipe is not JSON: {}", e))
+}
+
+fn read_expectation(fixture: &Fixture) -> Result<Expectation, String> {
+    let content = fs::read_to_string(&fixture.expected)
+        .map_err(|e| format!("cannot read {}: {}", fixture.expected.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("{} is not an expectation: {}", fixture.expected.display(), e))
+}
+
+/// Issues the reference validator reports for a recipe, sorted
+pub fn reference_issues(recipe: &Value) -> Result<Vec<CodedIssue>, RCIPError> {
+    let result = global_validator(SCHEMA_VERSION)?.validate(recipe);
+    let mut issues: Vec<CodedIssue> = result
+        .issues
+        .iter()
+        .map(|issue| CodedIssue { code: issue.code.to_string(), path: issue.path.clone() })
+        .collect();
+    issues.sort();
+    Ok(issues)
+}
+
+/// Expected issues not in `actual`, and issues in `actual` not expected
+fn diff(expected: &[CodedIssue], actual: &[CodedIssue]) -> (Vec<CodedIssue>, Vec<CodedIssue>) {
+    let mut unexpected: Vec<CodedIssue> = actual.to_vec();
+    let mut missing = Vec::new();
+    for issue in expected {
+        match unexpected.iter().position(|a| a == issue) {
+            Some(found) => {
+                unexpected.remove(found);
+            }
+            None => missing.push(issue.clone()),
+        }
+    }
+    missing.sort();
+    unexpected.sort();
+    (missing, unexpected)
+}
+
+fn outcome(fixture: &Fixture, actual: Result<Vec<CodedIssue>, String>) -> FixtureOutcome {
+    let mut outcome = FixtureOutcome {
+        fixture: fixture.name.clone(),
+        category: fixture.category.clone(),
+        missing: Vec::new(),
+        unexpected: Vec::new(),
+        error: None,
+    };
+    match (read_expectation(fixture), actual) {
+        (Ok(expected), Ok(actual)) => (outcome.missing, outcome.unexpected) = diff(&expected.issues, &actual),
+        (Err(e), _) | (_, Err(e)) => outcome.error = Some(e),
+    }
+    outcome
+}
+
+/// Check the reference validator against every fixture under `dir`
+pub fn run(dir: &Path) -> Result<ConformanceReport, RCIPError> {
+    let fixtures = fixtures(dir)?;
+    let mut report = ConformanceReport::default();
+    for fixture in &fixtures {
+        let actual = match read_recipe(fixture) {
+            Ok(recipe) => reference_issues(&recipe).map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        report.fixtures.push(outcome(fixture, actual));
+    }
+    Ok(report)
+}
+
+/// Check another implementation's issues, keyed by fixture name, against
+/// every fixture under `dir`; a fixture without an entry fails
+pub fn compare(dir: &Path, actual: &BTreeMap<String, Vec<CodedIssue>>) -> Result<ConformanceReport, RCIPError> {
+    let fixtures = fixtures(dir)?;
+    let mut report = ConformanceReport::default();
+    for fixture in &fixtures {
+        let issues = actual.get(&fixture.name).cloned().ok_or_else(|| "no results for this fixture".to_string());
+        report.fixtures.push(outcome(fixture, issues));
+    }
+    Ok(report)
+}
+
+/// Rewrite every expectation under `dir` from the reference validator;
+/// returns the fixtures whose expectation changed
+pub fn update(dir: &Path) -> Result<Vec<String>, RCIPError> {
+    let mut changed = Vec::new();
+    for fixture in fixtures(dir)? {
+        let recipe = read_recipe(&fixture).map_err(|e| RCIPError::ValidationError(format!("{}: {}", fixture.name, e)))?;
+        let expectation = Expectation { issues: reference_issues(&recipe)? };
+        if read_expectation(&fixture).ok().as_ref() != Some(&expectation) {
+            fs::write(&fixture.expected, format!("{}\n", serde_json::to_string_pretty(&expectation)?))?;
+            changed.push(fixture.name);
+        }
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issue(code: &str) -> CodedIssue {
+        CodedIssue { code: code.to_string(), path: String::new() }
+    }
+
+    #[test]
+    fn test_shipped_suite_conforms() {
+        let report = run(Path::new("../../conformance")).unwrap();
+        assert!(report.fixtures.len() >= 10, "{} fixtures", report.fixtures.len());
+        assert!(report.is_conformant(), "\n{}", report);
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("steps")).unwrap();
+        let recipe = fs::read_to_string("../../conformance/valid/flatbreads.rcip").unwrap();
+        fs::write(dir.path().join("steps/simple.rcip"), &recipe).unwrap();
+        fs::write(dir.path().join("steps/simple.expected.json"), r#"{"issues": [{"code": "RCIP-E-BAD-STEP-REF"}]}"#).unwrap();
+        fs::write(dir.path().join("orphan.rcip"), &recipe).unwrap();
+
+        let report = run(dir.path()).unwrap();
+        assert!(!report.is_conformant());
+        assert_eq!(report.fixtures[0].fixture, "orphan");
+        assert!(report.fixtures[0].error.as_deref().unwrap().starts_with("cannot read"));
+        assert_eq!(report.fixtures[1].category, "steps");
+        assert_eq!(report.fixtures[1].missing, vec![issue("RCIP-E-BAD-STEP-REF")]);
+        assert!(report.to_string().contains("steps/simple:\n  missing: RCIP-E-BAD-STEP-REF\n"));
+        assert!(report.to_string().ends_with("0 of 2 fixtures conform\n"));
+
+        assert_eq!(update(dir.path()).unwrap(), vec!["orphan", "steps/simple"]);
+        assert!(run(dir.path()).unwrap().is_conformant());
+        assert!(update(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compare_other_implementation() {
+        let dir = Path::new("../../conformance");
+        let mut actual: BTreeMap<String, Vec<CodedIssue>> = BTreeMap::new();
+        for fixture in fixtures(dir).unwrap() {
+            let mut issues = read_expectation(&fixture).unwrap().issues;
+            issues.reverse();
+            actual.insert(fixture.name, issues);
+        }
+        assert!(compare(dir, &actual).unwrap().is_conformant());
+
+        actual.get_mut("references/bad-step-ref").unwrap().push(issue("RCIP-W-NO-NUTRITION"));
+        let report = compare(dir, &actual).unwrap();
+        let failed: Vec<&str> = report.failures().map(|f| f.fixture.as_str()).collect();
+        assert_eq!(failed, vec!["references/bad-step-ref"]);
+        assert_eq!(report.by_category()["references"].1 - report.by_category()["references"].0, 1);
+    }
+}
@@ -0,0 +1,326 @@
+// Corpus statistics
+//
+// Percentiles of a few numeric metrics over a validated corpus (ingredient
+// count, step count, total time, calories per serving) and the files beyond a
+// chosen percentile of any of them. Past the 99th percentile a value is far
+// more often a data error, a missing decimal point or minutes entered as
+// seconds, than a genuinely extreme recipe.
+//
+// Percentiles are exact: each metric's values are sorted and the
+// nearest-rank value is taken, which stays cheap at tens of thousands of
+// files. A file is an outlier when its value is strictly above the threshold
+// percentile, so a corpus too small to have a tail flags nothing. Files
+// without a value for a metric, like recipes without nutrition data, are
+// left out of that metric.
+
+use crate::labels::compute_nutrition;
+use crate::{codes, numeric, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// A numeric metric tracked across the corpus
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    Ingredients,
+    Steps,
+    TotalTime,
+    CaloriesPerServing,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 4] = [Metric::Ingredients, Metric::Steps, Metric::TotalTime, Metric::CaloriesPerServing];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Ingredients => "ingredients",
+            Metric::Steps => "steps",
+            Metric::TotalTime => "total-time",
+            Metric::CaloriesPerServing => "calories-per-serving",
+        }
+    }
+
+    /// Unit the metric is expressed in, empty for counts
+    pub fn unit(self) -> &'static str {
+        match self {
+            Metric::TotalTime => "min",
+            Metric::CaloriesPerServing => "kcal",
+            _ => "",
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Metric::ALL.into_iter().find(|m| m.name() == s).ok_or_else(|| {
+            let names: Vec<&str> = Metric::ALL.iter().map(|m| m.name()).collect();
+            format!("unknown metric '{}'; expected one of {}", s, names.join(", "))
+        })
+    }
+}
+
+/// The metrics of one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusSample {
+    pub file: String,
+    pub values: BTreeMap<Metric, f64>,
+}
+
+impl CorpusSample {
+    /// Metrics of a parsed recipe
+    pub fn new(file: &str, recipe: &Value) -> Self {
+        let count = |key: &str| recipe.get(key).and_then(|v| v.as_array()).map(|a| a.len() as f64);
+        let values = [
+            (Metric::Ingredients, count("ingredients")),
+            (Metric::Steps, count("steps")),
+            (Metric::TotalTime, recipe.pointer("/meta/total_time_minutes").and_then(numeric::finite)),
+            (Metric::CaloriesPerServing, compute_nutrition(recipe).ok().and_then(|s| s.calories)),
+        ];
+        CorpusSample {
+            file: file.to_string(),
+            values: values.into_iter().filter_map(|(metric, value)| Some((metric, value?))).collect(),
+        }
+    }
+}
+
+/// Percentile above which a file is an outlier, per metric
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    pub percentiles: BTreeMap<Metric, f64>,
+}
+
+impl Thresholds {
+    /// The same percentile for every metric
+    pub fn uniform(percentile: f64) -> Self {
+        Thresholds { percentiles: Metric::ALL.into_iter().map(|m| (m, percentile)).collect() }
+    }
+
+    /// Use another percentile for one metric
+    pub fn with(mut self, metric: Metric, percentile: f64) -> Self {
+        self.percentiles.insert(metric, percentile);
+        self
+    }
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds::uniform(99.0)
+    }
+}
+
+/// Nearest-rank percentile of sorted values: the smallest value with at
+/// least `p` percent of the values at or below it
+pub fn percentile(sorted: &[f64], p: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p.clamp(0.0, 100.0) / 100.0 * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// Distribution of one metric
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    /// Files with a value for the metric
+    pub count: usize,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// A file beyond the threshold percentile of a metric
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Outlier {
+    pub file: String,
+    pub metric: Metric,
+    pub value: f64,
+    /// Percentile the value exceeds
+    pub percentile: f64,
+    /// Value at that percentile
+    pub threshold: f64,
+}
+
+/// Percentiles of every metric and the outliers among the files
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CorpusReport {
+    pub files: usize,
+    pub metrics: BTreeMap<Metric, Summary>,
+    /// Ordered by file, then metric
+    pub outliers: Vec<Outlier>,
+}
+
+impl CorpusReport {
+    pub fn build(samples: &[CorpusSample], thresholds: &Thresholds) -> Self {
+        let mut metrics = BTreeMap::new();
+        let mut outliers = Vec::new();
+        for metric in Metric::ALL {
+            let mut values: Vec<f64> = samples.iter().filter_map(|s| s.values.get(&metric).copied()).collect();
+            values.sort_by(f64::total_cmp);
+            let at = |p: f64| percentile(&values, p).unwrap_or(0.0);
+            if values.is_empty() {
+                continue;
+            }
+            metrics.insert(metric, Summary {
+                count: values.len(),
+                min: values[0],
+                p50: at(50.0),
+                p90: at(90.0),
+                p99: at(99.0),
+                max: values[values.len() - 1],
+            });
+
+            let Some(&p) = thresholds.percentiles.get(&metric) else { continue };
+            let threshold = at(p);
+            for sample in samples {
+                match sample.values.get(&metric) {
+                    Some(&value) if value > threshold => outliers.push(Outlier {
+                        file: sample.file.clone(),
+                        metric,
+                        value,
+                        percentile: p,
+                        threshold,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        outliers.sort_by(|a, b| a.file.cmp(&b.file).then(a.metric.cmp(&b.metric)));
+        CorpusReport { files: samples.len(), metrics, outliers }
+    }
+
+    /// Attach each outlier to its file's result as an RCIP-I-CORPUS-OUTLIER
+    /// notice
+    pub fn flag(&self, results: &mut [(String, ValidationResult)]) {
+        let mut by_file: HashMap<&str, Vec<&Outlier>> = HashMap::new();
+        for outlier in &self.outliers {
+            by_file.entry(outlier.file.as_str()).or_default().push(outlier);
+        }
+        for (file, result) in results.iter_mut() {
+            for outlier in by_file.get(file.as_str()).into_iter().flatten() {
+                result.notice(codes::CORPUS_OUTLIER, &[
+                    ("metric", &outlier.metric),
+                    ("value", &number(outlier.value)),
+                    ("percentile", &number(outlier.percentile)),
+                    ("threshold", &number(outlier.threshold)),
+                ]);
+                result.sort();
+            }
+        }
+    }
+}
+
+/// A value without a trailing `.0`, rounded to one decimal
+fn number(value: f64) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{:.0}", rounded)
+    } else {
+        format!("{:.1}", rounded)
+    }
+}
+
+impl fmt::Display for CorpusReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<22} {:>7} {:>9} {:>9} {:>9} {:>9}", "metric", "files", "p50", "p90", "p99", "max")?;
+        for (metric, summary) in &self.metrics {
+            let unit = metric.unit();
+            let cell = |value: f64| format!("{}{}{}", number(value), if unit.is_empty() { "" } else { " " }, unit);
+            writeln!(
+                f,
+                "{:<22} {:>7} {:>9} {:>9} {:>9} {:>9}",
+                metric.name(),
+                summary.count,
+                cell(summary.p50),
+                cell(summary.p90),
+                cell(summary.p99),
+                cell(summary.max)
+            )?;
+        }
+        if !self.outliers.is_empty() {
+            writeln!(f, "Outliers:")?;
+            for outlier in &self.outliers {
+                writeln!(
+                    f,
+                    "  {}: {} {} > p{} ({})",
+                    outlier.file,
+                    outlier.metric,
+                    number(outlier.value),
+                    number(outlier.percentile),
+                    number(outlier.threshold)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample(file: &str, steps: f64) -> CorpusSample {
+        CorpusSample { file: file.to_string(), values: BTreeMap::from([(Metric::Steps, steps)]) }
+    }
+
+    #[test]
+    fn test_percentiles_are_exact_nearest_rank() {
+        let values: Vec<f64> = (1..=200).map(f64::from).collect();
+        assert_eq!(percentile(&values, 50.0), Some(100.0));
+        assert_eq!(percentile(&values, 99.0), Some(198.0));
+        assert_eq!(percentile(&values, 100.0), Some(200.0));
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&[], 50.0), None);
+    }
+
+    #[test]
+    fn test_flags_files_past_the_threshold() {
+        let mut samples: Vec<CorpusSample> = (0..198).map(|i| sample(&format!("r{:03}.rcip", i), 5.0 + (i % 7) as f64)).collect();
+        samples.push(sample("typo.rcip", 500.0));
+        samples.push(sample("long.rcip", 80.0));
+
+        let report = CorpusReport::build(&samples, &Thresholds::default());
+        assert_eq!(report.files, 200);
+        let steps = &report.metrics[&Metric::Steps];
+        assert_eq!((steps.count, steps.p50, steps.max), (200, 8.0, 500.0));
+        let flagged: Vec<(&str, f64)> = report.outliers.iter().map(|o| (o.file.as_str(), o.value)).collect();
+        assert_eq!(flagged, [("long.rcip", 80.0), ("typo.rcip", 500.0)]);
+        assert!(!report.metrics.contains_key(&Metric::CaloriesPerServing));
+
+        let strict = CorpusReport::build(&samples, &Thresholds::default().with(Metric::Steps, 99.5));
+        assert_eq!(strict.outliers.len(), 1);
+
+        let mut results = vec![("typo.rcip".to_string(), ValidationResult::new()), ("r001.rcip".to_string(), ValidationResult::new())];
+        report.flag(&mut results);
+        assert_eq!(results[0].1.issues[0].code, codes::CORPUS_OUTLIER);
+        assert!(results[0].1.valid);
+        assert!(results[1].1.issues.is_empty());
+    }
+
+    #[test]
+    fn test_samples_a_recipe() {
+        let recipe = json!({
+            "meta": {"total_time_minutes": 45},
+            "ingredients": [{"name": "a"}, {"name": "b"}],
+            "steps": [{"step_id": "s-01"}]
+        });
+        let sample = CorpusSample::new("a.rcip", &recipe);
+        assert_eq!(sample.values.get(&Metric::Ingredients), Some(&2.0));
+        assert_eq!(sample.values.get(&Metric::Steps), Some(&1.0));
+        assert_eq!(sample.values.get(&Metric::TotalTime), Some(&45.0));
+        assert_eq!(sample.values.get(&Metric::CaloriesPerServing), None);
+    }
+}
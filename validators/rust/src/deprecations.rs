@@ -0,0 +1,390 @@
+// Deprecated fields
+//
+// `assets/deprecations.json` lists the fields on their way out of the
+// format: where each one lives (`*` for array items), the RCIP version that
+// deprecated it, the version that removes it, the field replacing it and the
+// change to SPECIFICATION.md that decided it (`source`). No released version
+// of the format has deprecated a document field yet, so the bundled registry
+// is empty; an entry is added together with the specification change it
+// cites, never ahead of it.
+// `check` reads the recipe's `rcip_version`: a field is a warning at or past
+// its deprecation and an error at or past its removal, and either message
+// names the replacement. `migrate` moves the fields marked as plain renames
+// to their replacement and reports the rest for a person to convert.
+//
+// Both go through `found`, so the fields the validator complains about are
+// exactly the ones the migration handles or explains. Recipes without a
+// readable `rcip_version` are left to the schema. The registry itself is
+// checked by `self_check::check_deprecations`. The `_in` variants take a
+// registry, which is how the tests exercise the rules.
+
+use crate::{codes, ValidationResult};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// A field on its way out of the format
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Deprecation {
+    /// Where the field lives, `*` for array items
+    pub path: String,
+    /// The field to use instead, with the same `*`s
+    pub replacement: String,
+    pub deprecated_in: String,
+    pub removed_in: String,
+    /// Whether moving the value to the replacement is all a migration takes
+    #[serde(default)]
+    pub rename: bool,
+    /// What a person has to do when it is not a plain rename
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migration_note: Option<String>,
+    /// The specification change that deprecates the field
+    pub source: String,
+}
+
+lazy_static! {
+    static ref REGISTRY: Vec<Deprecation> =
+        serde_json::from_str(include_str!("../assets/deprecations.json")).expect("bundled deprecations parse");
+}
+
+/// The bundled registry
+pub fn registry() -> &'static [Deprecation] {
+    &REGISTRY
+}
+
+/// Numeric components of a version such as "0.1" or "1.2.3"
+pub fn parse_version(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Compare two parsed versions, missing components counting as zero
+pub fn compare_versions(a: &[u32], b: &[u32]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn at_or_past(version: &[u32], point: &str) -> bool {
+    parse_version(point).map(|point| compare_versions(version, &point).is_ge()).unwrap_or(false)
+}
+
+/// A deprecated field present in a recipe
+#[derive(Debug, Clone, PartialEq)]
+pub struct Found {
+    pub deprecation: &'static Deprecation,
+    /// JSON pointer to the field
+    pub path: String,
+    /// JSON pointer to where its value belongs
+    pub replacement: String,
+    /// Whether the recipe's version no longer has the field at all
+    pub removed: bool,
+}
+
+/// Pointers to the values at a wildcard path, with the index each `*` took
+fn occurrences(node: &Value, segments: &[&str], pointer: String, indices: &[usize], found: &mut Vec<(String, Vec<usize>)>) {
+    match segments.split_first() {
+        None => found.push((pointer, indices.to_vec())),
+        Some((&"*", rest)) => {
+            for (i, item) in node.as_array().into_iter().flatten().enumerate() {
+                let indices = [indices, &[i]].concat();
+                occurrences(item, rest, format!("{}/{}", pointer, i), &indices, found);
+            }
+        }
+        Some((segment, rest)) => {
+            if let Some(child) = node.as_object().and_then(|o| o.get(*segment)) {
+                occurrences(child, rest, format!("{}/{}", pointer, segment), indices, found);
+            }
+        }
+    }
+}
+
+/// `path` with its `*`s replaced by `indices`, in order
+fn concrete(path: &str, indices: &[usize]) -> String {
+    let mut indices = indices.iter();
+    path.split('/')
+        .map(|segment| match segment {
+            "*" => indices.next().map(|i| i.to_string()).unwrap_or_else(|| segment.to_string()),
+            _ => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Deprecated fields in the recipe that its `rcip_version` has reached
+pub fn found(recipe: &Value) -> Vec<Found> {
+    found_in(registry(), recipe)
+}
+
+/// `found` against the given registry
+pub(crate) fn found_in(registry: &'static [Deprecation], recipe: &Value) -> Vec<Found> {
+    let version = match recipe.get("rcip_version").and_then(|v| v.as_str()).and_then(parse_version) {
+        Some(version) => version,
+        None => return Vec::new(),
+    };
+
+    let mut found = Vec::new();
+    for deprecation in registry {
+        if !at_or_past(&version, &deprecation.deprecated_in) {
+            continue;
+        }
+        let segments: Vec<&str> = deprecation.path.split('/').skip(1).collect();
+        let mut paths = Vec::new();
+        occurrences(recipe, &segments, String::new(), &[], &mut paths);
+        for (path, indices) in paths {
+            found.push(Found {
+                deprecation,
+                path,
+                replacement: concrete(&deprecation.replacement, &indices),
+                removed: at_or_past(&version, &deprecation.removed_in),
+            });
+        }
+    }
+    found
+}
+
+/// Report deprecated and removed fields
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    check_in(registry(), recipe, result)
+}
+
+/// `check` against the given registry
+pub(crate) fn check_in(registry: &'static [Deprecation], recipe: &Value, result: &mut ValidationResult) {
+    for found in found_in(registry, recipe) {
+        let params: [(&'static str, &dyn fmt::Display); 4] = [
+            ("path", &found.path),
+            ("replacement", &found.replacement),
+            ("deprecated_in", &found.deprecation.deprecated_in),
+            ("removed_in", &found.deprecation.removed_in),
+        ];
+        if found.removed {
+            result.error(codes::REMOVED_FIELD, &params);
+        } else {
+            result.warning(codes::DEPRECATED_FIELD, &params);
+        }
+    }
+}
+
+/// A deprecated field moved to its replacement
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Moved {
+    pub from: String,
+    pub to: String,
+}
+
+/// A deprecated field the migration left where it was
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Unmigrated {
+    pub path: String,
+    pub replacement: String,
+    pub reason: String,
+}
+
+/// What `migrate` did
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MigrationReport {
+    pub moved: Vec<Moved>,
+    pub unmigrated: Vec<Unmigrated>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for moved in &self.moved {
+            writeln!(f, "{}: moved to {}", moved.from, moved.to)?;
+        }
+        for field in &self.unmigrated {
+            writeln!(f, "{}: left in place, {} (replacement {})", field.path, field.reason, field.replacement)?;
+        }
+        Ok(())
+    }
+}
+
+/// Put `value` at `pointer`, creating the objects on the way
+pub(crate) fn insert(recipe: &mut Value, pointer: &str, value: Value) -> Result<(), String> {
+    let segments: Vec<&str> = pointer.split('/').skip(1).collect();
+    let (last, parents) = segments.split_last().ok_or_else(|| "the replacement is the whole recipe".to_string())?;
+    let mut node = recipe;
+    let mut at = String::new();
+    for segment in parents {
+        at = format!("{}/{}", at, segment);
+        node = match node {
+            Value::Object(fields) => fields.entry(segment.to_string()).or_insert_with(|| json!({})),
+            Value::Array(items) => {
+                let index = segment.parse::<usize>().ok();
+                index.and_then(|i| items.get_mut(i)).ok_or_else(|| format!("{} does not exist", at))?
+            }
+            _ => return Err(format!("{} is not an object", at)),
+        };
+    }
+    match node {
+        Value::Object(fields) => {
+            fields.insert(last.to_string(), value);
+            Ok(())
+        }
+        _ => Err(format!("{} is not an object", at)),
+    }
+}
+
+pub(crate) fn remove(recipe: &mut Value, pointer: &str) -> Option<Value> {
+    let (parent, field) = pointer.rsplit_once('/')?;
+    recipe.pointer_mut(parent)?.as_object_mut()?.remove(field)
+}
+
+/// Move the plain renames among the recipe's deprecated fields to their
+/// replacement. A replacement that already holds a different value wins and
+/// the old field stays, reported, for a person to reconcile
+pub fn migrate(recipe: &mut Value) -> MigrationReport {
+    migrate_in(registry(), recipe)
+}
+
+/// `migrate` against the given registry
+pub(crate) fn migrate_in(registry: &'static [Deprecation], recipe: &mut Value) -> MigrationReport {
+    let mut report = MigrationReport::default();
+    for found in found_in(registry, recipe) {
+        let unmigrated = |reason: String| Unmigrated {
+            path: found.path.clone(),
+            replacement: found.replacement.clone(),
+            reason,
+        };
+        if !found.deprecation.rename {
+            let note = found.deprecation.migration_note.clone();
+            report.unmigrated.push(unmigrated(note.unwrap_or_else(|| "not a plain rename".to_string())));
+            continue;
+        }
+
+        let value = match recipe.pointer(&found.path) {
+            Some(value) => value.clone(),
+            None => continue,
+        };
+        let outcome = match recipe.pointer(&found.replacement) {
+            Some(existing) if *existing == value => Ok(()),
+            Some(_) => Err(format!("{} already holds a different value", found.replacement)),
+            None => insert(recipe, &found.replacement, value),
+        };
+        match outcome {
+            Ok(()) => {
+                remove(recipe, &found.path);
+                report.moved.push(Moved {
+                    from: found.path,
+                    to: found.replacement,
+                });
+            }
+            Err(reason) => report.unmigrated.push(unmigrated(reason)),
+        }
+    }
+    report
+}
+
+/// A registry of fields the format never deprecated, for tests
+#[cfg(test)]
+pub(crate) fn test_registry() -> &'static [Deprecation] {
+    lazy_static! {
+        static ref TEST_REGISTRY: Vec<Deprecation> = serde_json::from_value(json!([
+            {"path": "/meta/prep_time", "replacement": "/meta/prep_time_minutes",
+             "deprecated_in": "0.1", "removed_in": "0.2", "rename": true, "source": "test"},
+            {"path": "/meta/cook_time", "replacement": "/meta/cook_time_minutes",
+             "deprecated_in": "0.1", "removed_in": "0.2", "rename": true, "source": "test"},
+            {"path": "/meta/total_time", "replacement": "/meta/total_time_minutes",
+             "deprecated_in": "0.1", "removed_in": "0.2", "rename": true, "source": "test"},
+            {"path": "/meta/servings_count", "replacement": "/meta/servings/amount",
+             "deprecated_in": "0.1", "removed_in": "0.2", "rename": true, "source": "test"},
+            {"path": "/steps/*/params/temperature", "replacement": "/steps/*/params/temperature_c",
+             "deprecated_in": "0.1", "removed_in": "0.3", "rename": true, "source": "test"},
+            {"path": "/steps/*/duration", "replacement": "/steps/*/params/time_minutes",
+             "deprecated_in": "0.1", "removed_in": "0.3", "rename": false, "source": "test",
+             "migration_note": "durations such as PT1H30M need converting to minutes"}
+        ]))
+        .unwrap();
+    }
+    &TEST_REGISTRY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(recipe: &Value, result: &mut ValidationResult) {
+        check_in(test_registry(), recipe, result)
+    }
+
+    fn migrate(recipe: &mut Value) -> MigrationReport {
+        migrate_in(test_registry(), recipe)
+    }
+
+    fn recipe(version: &str) -> Value {
+        json!({
+            "rcip_version": version,
+            "meta": {"name": "Stew", "cook_time": 90, "prep_time": 20, "prep_time_minutes": 25},
+            "steps": [
+                {"step_id": "s-01", "action": "heat", "params": {"temperature": 180}},
+                {"step_id": "s-02", "action": "simmer", "duration": "PT1H30M"}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_warns_then_errors_with_the_replacement() {
+        let mut result = ValidationResult::new();
+        check(&recipe("0.1"), &mut result);
+        assert!(result.valid);
+        assert_eq!(result.warnings.len(), 4);
+        assert!(result.warnings.contains(
+            &"/meta/cook_time is deprecated since RCIP 0.1 and removed in 0.2; use /meta/cook_time_minutes instead"
+                .to_string()
+        ));
+        assert!(result.warnings.iter().any(|w| w.starts_with("/steps/0/params/temperature ")
+            && w.ends_with("use /steps/0/params/temperature_c instead")));
+
+        let mut result = ValidationResult::new();
+        check(&recipe("0.2"), &mut result);
+        assert!(!result.valid);
+        assert_eq!(result.errors.len(), 2);
+        assert_eq!(result.warnings.len(), 2);
+        assert!(result.errors.contains(&"/meta/prep_time was removed in RCIP 0.2; use /meta/prep_time_minutes instead".to_string()));
+
+        let mut result = ValidationResult::new();
+        check(&recipe("0.0.9"), &mut result);
+        check(&recipe("draft"), &mut result);
+        assert!(result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_migrates_renames_and_reports_the_rest() {
+        let mut migrated = recipe("0.1");
+        let report = migrate(&mut migrated);
+        assert_eq!(migrated["meta"]["cook_time_minutes"], 90);
+        assert!(migrated["meta"].get("cook_time").is_none());
+        assert_eq!(migrated["steps"][0]["params"], json!({"temperature_c": 180}));
+        assert_eq!(report.moved.len(), 2);
+
+        // The existing prep_time_minutes wins; the duration needs a person
+        assert_eq!(migrated["meta"]["prep_time"], 20);
+        assert_eq!(migrated["meta"]["prep_time_minutes"], 25);
+        assert_eq!(migrated["steps"][1]["duration"], "PT1H30M");
+        let reasons: Vec<&str> = report.unmigrated.iter().map(|u| u.reason.as_str()).collect();
+        assert_eq!(
+            reasons,
+            ["/meta/prep_time_minutes already holds a different value", "durations such as PT1H30M need converting to minutes"]
+        );
+
+        // What is left is exactly what validation still reports
+        let mut result = ValidationResult::new();
+        check(&migrated, &mut result);
+        assert_eq!(result.warnings.len(), report.unmigrated.len());
+
+        let mut servings = json!({"rcip_version": "0.1", "meta": {"servings_count": 4}});
+        migrate(&mut servings);
+        assert_eq!(servings["meta"], json!({"servings": {"amount": 4}}));
+    }
+
+    #[test]
+    fn test_versions_and_paths() {
+        let v = |s| parse_version(s).unwrap();
+        assert_eq!(compare_versions(&v("0.1"), &v("0.1.0")), Ordering::Equal);
+        assert_eq!(compare_versions(&v("0.10"), &v("0.9")), Ordering::Greater);
+        assert_eq!(parse_version("1.x"), None);
+        assert_eq!(concrete("/steps/*/items/*/name", &[2, 0]), "/steps/2/items/0/name");
+    }
+}
@@ -0,0 +1,150 @@
+// Device profile limits
+//
+// Device profiles may declare `capabilities` with the fields an external
+// device manifest uses: `actions`, `temperature_range_c`,
+// `max_duration_minutes` and `max_speed_rpm`. Every step assigned to a profile
+// through `device_profile_ref`, or overridden for it by a step-id key in the
+// profile's `params`, is compared against those limits with the comparison
+// `compat` uses, overrides applied. Declared actions that no step of the
+// recipe uses suggest a profile copied from another recipe.
+
+use crate::compat::{self, DeviceManifest, Gap, TemperatureRange};
+use crate::{codes, numeric, ValidationResult};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+        .unwrap_or_default()
+}
+
+/// Capabilities declared by a device profile, if it declares any
+pub fn manifest(profile: &Value) -> Option<DeviceManifest> {
+    let capabilities = profile.get("capabilities")?;
+    let range = capabilities.get("temperature_range_c").and_then(|r| {
+        Some(TemperatureRange { min: numeric::finite(r.get("min")?)?, max: numeric::finite(r.get("max")?)? })
+    });
+
+    Some(DeviceManifest {
+        id: profile.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string(),
+        name: profile.get("name").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        actions: strings(capabilities.get("actions")),
+        temperature_range_c: range,
+        max_duration_minutes: capabilities.get("max_duration_minutes").and_then(numeric::finite),
+        max_speed_rpm: capabilities.get("max_speed_rpm").and_then(numeric::finite),
+        attachments: strings(capabilities.get("attachments")),
+    })
+}
+
+/// The step as the device would run it, with the profile's override applied
+fn effective_step(step: &Value, overrides: &serde_json::Map<String, Value>) -> Value {
+    let mut effective = step.clone();
+    if !effective.get("params").map(|p| p.is_object()).unwrap_or(false) {
+        effective["params"] = json!({});
+    }
+    if let Some(params) = effective["params"].as_object_mut() {
+        params.extend(overrides.clone());
+    }
+    effective
+}
+
+/// Check steps against the limits their device profiles declare
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let used: HashSet<&str> = steps.iter().filter_map(|s| s.get("action").and_then(|v| v.as_str())).collect();
+
+    for profile in recipe.get("device_profiles").and_then(|v| v.as_array()).into_iter().flatten() {
+        let manifest = match manifest(profile) {
+            Some(manifest) => manifest,
+            None => continue,
+        };
+
+        for step in &steps {
+            let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+            let assigned = step.get("device_profile_ref").and_then(|v| v.as_str()) == Some(manifest.id.as_str());
+            let overrides = profile.pointer(&format!("/params/{}", step_id)).and_then(|v| v.as_object());
+            if !assigned && overrides.is_none() {
+                continue;
+            }
+
+            let effective = match overrides {
+                Some(overrides) => effective_step(step, overrides),
+                None => (*step).clone(),
+            };
+
+            let mut gaps = Vec::new();
+            if let Some(action) = step.get("action").and_then(|v| v.as_str()) {
+                if !manifest.actions.is_empty() && !manifest.actions.iter().any(|a| a == action) {
+                    gaps.push(Gap::UnsupportedAction { action: action.to_string() });
+                }
+            }
+            gaps.extend(compat::check_limits(&effective, &manifest));
+
+            for gap in gaps {
+                result.error(codes::DEVICE_LIMIT, &[("step", &step_id), ("device", &manifest.id), ("problem", &gap)]);
+            }
+        }
+
+        let unused: Vec<&str> =
+            manifest.actions.iter().map(|a| a.as_str()).filter(|a| !used.contains(a)).collect();
+        if !unused.is_empty() {
+            result.warning(codes::UNUSED_DEVICE_ACTIONS, &[("device", &manifest.id), ("actions", &unused.join(", "))]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe() -> Value {
+        json!({
+            "device_profiles": [
+                {"id": "oven-01", "type": "oven",
+                 "capabilities": {"temperature_range_c": {"min": 50, "max": 250}, "actions": ["bake", "roast", "grill"]}},
+                {"id": "blender-01", "type": "blender",
+                 "params": {"s-03": {"speed_rpm": 30000}},
+                 "capabilities": {"max_duration_minutes": 10, "max_speed_rpm": 20000}},
+                {"id": "mixer-01", "type": "mixer", "params": {"speed": 2}}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "bake", "device_profile_ref": "oven-01", "params": {"temperature_c": 300}},
+                {"step_id": "s-02", "action": "blend", "device_profile_ref": "blender-01", "params": {"time_minutes": 15}},
+                {"step_id": "s-03", "action": "blend", "params": {"time_minutes": 2}},
+                {"step_id": "s-04", "action": "roast", "device_profile_ref": "oven-01", "params": {"temperature_f": 400}},
+                {"step_id": "s-05", "action": "mix", "device_profile_ref": "mixer-01", "params": {"time_hours": 3}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_steps_over_profile_limits() {
+        let mut result = ValidationResult::new();
+        check(&recipe(), &mut result);
+
+        assert_eq!(
+            result.errors,
+            vec![
+                "Step s-01: temperature 300°C is outside 50–250°C on device 'oven-01'",
+                "Step s-02: duration 15 min exceeds the 10 min maximum on device 'blender-01'",
+                "Step s-03: speed 30000 rpm exceeds the 20000 rpm maximum on device 'blender-01'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused_declared_actions() {
+        let mut result = ValidationResult::new();
+        check(&recipe(), &mut result);
+        assert_eq!(result.warnings, vec!["Device profile 'oven-01' declares actions the recipe never uses: grill"]);
+
+        let mut recipe = recipe();
+        recipe["steps"][3]["action"] = json!("grill");
+        recipe["steps"][3]["params"] = json!({"temperature_c": 200});
+        let mut result = ValidationResult::new();
+        check(&recipe, &mut result);
+        assert!(result.warnings[0].ends_with("never uses: roast"));
+    }
+}
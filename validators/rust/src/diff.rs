@@ -0,0 +1,439 @@
+// Semantic recipe diff
+//
+// Compares two versions of a recipe and classifies every change by how far
+// `meta.version` should move for it:
+// - major: what gets cooked or declared changes. An ingredient or step is
+//   added or removed, a step's action changes, or an allergen or trace
+//   appears
+// - minor: quantities and cooking parameters. Amounts, step params and
+//   targets, step order, servings, diet labels, an allergen or trace that
+//   goes away
+// - patch: everything else, such as names, descriptions, step text, images
+//   and other metadata
+//
+// Ingredients are matched by `id` and steps by `step_id`, so renumbering an
+// ingredient reads as a removal plus an addition. `meta.version` itself is
+// not compared, since it is what the classification is for.
+//
+// Changes are grouped by `Section` for reviewers. An allergen or trace the
+// new version contains is a safety matter, so the text rendering lists those
+// first under a warning and the JSON rendering repeats them in
+// `new_allergens`.
+
+use crate::allergens;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// How far a change should move the recipe version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeLevel {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for ChangeLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ChangeLevel::Patch => "patch",
+            ChangeLevel::Minor => "minor",
+            ChangeLevel::Major => "major",
+        })
+    }
+}
+
+/// Part of the recipe a change is in, in the order they are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Section {
+    Meta,
+    Ingredients,
+    Allergens,
+    Steps,
+    Other,
+}
+
+impl Section {
+    fn of(path: &str) -> Section {
+        match path.split('/').nth(1) {
+            Some("meta") => Section::Meta,
+            Some("ingredients") => Section::Ingredients,
+            Some("allergens") => Section::Allergens,
+            Some("steps") => Section::Steps,
+            _ => Section::Other,
+        }
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Section::Meta => "meta",
+            Section::Ingredients => "ingredients",
+            Section::Allergens => "allergens",
+            Section::Steps => "steps",
+            Section::Other => "other",
+        })
+    }
+}
+
+/// One difference between two versions of a recipe
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub level: ChangeLevel,
+    pub section: Section,
+    /// Where it is, as `/ingredients/<id>`, `/steps/<step_id>`,
+    /// `/allergens/<allergen>` or `/meta/<field>`
+    pub path: String,
+    pub summary: String,
+}
+
+impl Change {
+    /// Whether the new version contains an allergen or trace the old did not
+    pub fn is_new_allergen(&self) -> bool {
+        self.section == Section::Allergens && self.level == ChangeLevel::Major
+    }
+}
+
+/// Every difference between two versions of a recipe, in document order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecipeDiff {
+    pub changes: Vec<Change>,
+}
+
+impl RecipeDiff {
+    /// The largest change, or None when the versions are equivalent
+    pub fn level(&self) -> Option<ChangeLevel> {
+        self.changes.iter().map(|c| c.level).max()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Allergens and traces the new version contains that the old did not
+    pub fn new_allergens(&self) -> Vec<&Change> {
+        self.changes.iter().filter(|c| c.is_new_allergen()).collect()
+    }
+
+    /// The changes of each section that has any, in section order
+    pub fn by_section(&self) -> Vec<(Section, Vec<&Change>)> {
+        let mut sections: Vec<Section> = self.changes.iter().map(|c| c.section).collect();
+        sections.sort();
+        sections.dedup();
+        sections
+            .into_iter()
+            .map(|section| (section, self.changes.iter().filter(|c| c.section == section).collect()))
+            .collect()
+    }
+
+    /// The diff as JSON: the overall level, new allergens, and the changes
+    /// by section
+    pub fn to_json(&self) -> Value {
+        let sections: serde_json::Map<String, Value> =
+            self.by_section().into_iter().map(|(section, changes)| (section.to_string(), json!(changes))).collect();
+        json!({
+            "level": self.level(),
+            "new_allergens": self.new_allergens().iter().map(|c| &c.summary).collect::<Vec<_>>(),
+            "sections": sections
+        })
+    }
+
+    fn push(&mut self, level: ChangeLevel, path: String, summary: String) {
+        self.changes.push(Change { level, section: Section::of(&path), path, summary });
+    }
+}
+
+impl fmt::Display for RecipeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes");
+        }
+        for change in self.new_allergens() {
+            writeln!(f, "⚠️  {}", change.summary)?;
+        }
+        for (section, changes) in self.by_section() {
+            writeln!(f, "{}:", section)?;
+            for change in changes {
+                writeln!(f, "  {} {}", change.level, change.summary)?;
+            }
+        }
+        if let Some(level) = self.level() {
+            writeln!(f, "Suggested version bump: {}", level)?;
+        }
+        Ok(())
+    }
+}
+
+/// Fields compared on their own rather than as plain edits
+const INGREDIENT_FIELDS: &[&str] = &["id", "machine_amount", "allergens", "may_contain_allergens"];
+const STEP_FIELDS: &[&str] = &["step_id", "action", "params", "target"];
+const META_FIELDS: &[&str] = &["version", "servings", "diet_labels"];
+const TOP_LEVEL_FIELDS: &[&str] = &["meta", "ingredients", "steps"];
+
+fn keyed<'a>(recipe: &'a Value, array: &str, key: &str) -> Vec<(&'a str, &'a Value)> {
+    recipe
+        .get(array)
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| Some((item.get(key)?.as_str()?, item)))
+        .collect()
+}
+
+fn name<'a>(item: &'a Value, fallback: &'a str) -> &'a str {
+    item.get("name").and_then(|v| v.as_str()).unwrap_or(fallback)
+}
+
+/// Keys of two objects whose values differ, other than the skipped ones
+fn edited(old: &Value, new: &Value, skip: &[&str]) -> Vec<String> {
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|k| !skip.contains(&k.as_str()) && old.get(*k) != new.get(*k))
+        .cloned()
+        .collect()
+}
+
+fn amount(ingredient: &Value) -> String {
+    match ingredient.get("machine_amount") {
+        Some(a) => {
+            let value = a.get("value").map(|v| v.to_string()).unwrap_or_else(|| "?".to_string());
+            match a.get("unit").and_then(|u| u.as_str()) {
+                Some(unit) => format!("{} {}", value, unit),
+                None => value,
+            }
+        }
+        None => "none".to_string(),
+    }
+}
+
+fn diff_ingredients(old: &Value, new: &Value, diff: &mut RecipeDiff) {
+    let before: HashMap<&str, &Value> = keyed(old, "ingredients", "id").into_iter().collect();
+    let after = keyed(new, "ingredients", "id");
+    let after_ids: BTreeSet<&str> = after.iter().map(|(id, _)| *id).collect();
+
+    for (id, ingredient) in keyed(old, "ingredients", "id") {
+        if !after_ids.contains(id) {
+            let summary = format!("removed ingredient '{}'", name(ingredient, id));
+            diff.push(ChangeLevel::Major, format!("/ingredients/{}", id), summary);
+        }
+    }
+    for (id, ingredient) in after {
+        let path = format!("/ingredients/{}", id);
+        let previous = match before.get(id) {
+            Some(previous) => *previous,
+            None => {
+                diff.push(ChangeLevel::Major, path, format!("added ingredient '{}'", name(ingredient, id)));
+                continue;
+            }
+        };
+        if previous.get("machine_amount") != ingredient.get("machine_amount") {
+            let summary = format!(
+                "amount of '{}' changed from {} to {}",
+                name(ingredient, id),
+                amount(previous),
+                amount(ingredient)
+            );
+            diff.push(ChangeLevel::Minor, path.clone(), summary);
+        }
+        let fields = edited(previous, ingredient, INGREDIENT_FIELDS);
+        if !fields.is_empty() {
+            let summary = format!("edited ingredient '{}': {}", name(ingredient, id), fields.join(", "));
+            diff.push(ChangeLevel::Patch, path, summary);
+        }
+    }
+}
+
+fn diff_allergens(old: &Value, new: &Value, diff: &mut RecipeDiff) {
+    let policy = allergens::ProvenancePolicy::default();
+    for (kind, before, after) in [
+        ("allergen", allergens::contained_with(old, &policy), allergens::contained_with(new, &policy)),
+        ("trace of", allergens::traces(old), allergens::traces(new)),
+    ] {
+        for allergen in after.difference(&before) {
+            diff.push(ChangeLevel::Major, format!("/allergens/{}", allergen), format!("now contains {} {}", kind, allergen));
+        }
+        for allergen in before.difference(&after) {
+            diff.push(ChangeLevel::Minor, format!("/allergens/{}", allergen), format!("no longer contains {} {}", kind, allergen));
+        }
+    }
+}
+
+fn diff_steps(old: &Value, new: &Value, diff: &mut RecipeDiff) {
+    let before_steps = keyed(old, "steps", "step_id");
+    let after_steps = keyed(new, "steps", "step_id");
+    let before: HashMap<&str, &Value> = before_steps.iter().copied().collect();
+    let after: HashMap<&str, &Value> = after_steps.iter().copied().collect();
+
+    for (id, _) in &before_steps {
+        if !after.contains_key(id) {
+            diff.push(ChangeLevel::Major, format!("/steps/{}", id), format!("removed step {}", id));
+        }
+    }
+    for (id, step) in &after_steps {
+        let path = format!("/steps/{}", id);
+        let previous = match before.get(id) {
+            Some(previous) => *previous,
+            None => {
+                diff.push(ChangeLevel::Major, path, format!("added step {}", id));
+                continue;
+            }
+        };
+        let action = |s: &Value| s.get("action").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        if previous.get("action") != step.get("action") {
+            let summary = format!("step {} now does '{}' instead of '{}'", id, action(step), action(previous));
+            diff.push(ChangeLevel::Major, path.clone(), summary);
+        }
+        let parameters: Vec<&str> =
+            ["params", "target"].into_iter().filter(|k| previous.get(*k) != step.get(*k)).collect();
+        if !parameters.is_empty() {
+            diff.push(ChangeLevel::Minor, path.clone(), format!("changed {} of step {}", parameters.join(" and "), id));
+        }
+        let fields = edited(previous, step, STEP_FIELDS);
+        if !fields.is_empty() {
+            diff.push(ChangeLevel::Patch, path, format!("edited step {}: {}", id, fields.join(", ")));
+        }
+    }
+
+    let kept = |steps: &[(&str, &Value)], other: &HashMap<&str, &Value>| -> Vec<String> {
+        steps.iter().filter(|(id, _)| other.contains_key(id)).map(|(id, _)| id.to_string()).collect()
+    };
+    if kept(&before_steps, &after) != kept(&after_steps, &before) {
+        diff.push(ChangeLevel::Minor, "/steps".to_string(), "reordered steps".to_string());
+    }
+}
+
+fn diff_meta(old: &Value, new: &Value, diff: &mut RecipeDiff) {
+    let empty = Value::Null;
+    let before = old.get("meta").unwrap_or(&empty);
+    let after = new.get("meta").unwrap_or(&empty);
+    for field in ["servings", "diet_labels"] {
+        if before.get(field) != after.get(field) {
+            diff.push(ChangeLevel::Minor, format!("/meta/{}", field), format!("changed {}", field.replace('_', " ")));
+        }
+    }
+    for field in edited(before, after, META_FIELDS) {
+        diff.push(ChangeLevel::Patch, format!("/meta/{}", field), format!("edited {}", field.replace('_', " ")));
+    }
+    for field in edited(old, new, TOP_LEVEL_FIELDS) {
+        diff.push(ChangeLevel::Patch, format!("/{}", field), format!("edited {}", field.replace('_', " ")));
+    }
+}
+
+/// Classified differences from `old` to `new`
+pub fn diff_recipes(old: &Value, new: &Value) -> RecipeDiff {
+    let mut diff = RecipeDiff::default();
+    diff_meta(old, new, &mut diff);
+    diff_ingredients(old, new, &mut diff);
+    diff_allergens(old, new, &mut diff);
+    diff_steps(old, new, &mut diff);
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "meta": {"name": "Pancakes", "version": "1.0.0", "servings": {"amount": 2}},
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "machine_amount": {"value": 200, "unit": "g"}, "allergens": ["wheat"]},
+                {"id": "ing-0002", "name": "milk", "machine_amount": {"value": 300, "unit": "ml"}, "allergens": ["milk"]}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "human_text": "Whisk", "target": ["ing-0001", "ing-0002"]},
+                {"step_id": "s-02", "action": "fry", "human_text": "Fry", "target": ["s-01:result"], "params": {"time_minutes": 2}}
+            ]
+        })
+    }
+
+    fn summaries(diff: &RecipeDiff) -> Vec<(ChangeLevel, &str)> {
+        diff.changes.iter().map(|c| (c.level, c.summary.as_str())).collect()
+    }
+
+    #[test]
+    fn test_identical_and_version_only() {
+        let mut bumped = recipe();
+        bumped["meta"]["version"] = json!("1.0.1");
+        assert!(diff_recipes(&recipe(), &recipe()).is_empty());
+        assert_eq!(diff_recipes(&recipe(), &bumped).level(), None);
+    }
+
+    #[test]
+    fn test_text_and_quantity_changes() {
+        let mut new = recipe();
+        new["meta"]["description"] = json!("Thin pancakes");
+        new["ingredients"][0]["machine_amount"]["value"] = json!(250);
+        new["ingredients"][1]["name"] = json!("whole milk");
+        new["steps"][1]["params"]["time_minutes"] = json!(3);
+        new["steps"][0]["human_text"] = json!("Whisk until smooth");
+
+        let diff = diff_recipes(&recipe(), &new);
+        assert_eq!(summaries(&diff), vec![
+            (ChangeLevel::Patch, "edited description"),
+            (ChangeLevel::Minor, "amount of 'flour' changed from 200 g to 250 g"),
+            (ChangeLevel::Patch, "edited ingredient 'whole milk': name"),
+            (ChangeLevel::Patch, "edited step s-01: human_text"),
+            (ChangeLevel::Minor, "changed params of step s-02"),
+        ]);
+        assert_eq!(diff.level(), Some(ChangeLevel::Minor));
+    }
+
+    #[test]
+    fn test_structural_changes_are_major() {
+        let mut new = recipe();
+        new["ingredients"][1] = json!({"id": "ing-0003", "name": "oat milk", "machine_amount": {"value": 300, "unit": "ml"}});
+        new["ingredients"][0]["allergens"] = json!(["wheat", "gluten"]);
+        new["steps"][1]["action"] = json!("bake");
+        new["steps"] = json!([new["steps"][1], new["steps"][0]]);
+
+        let diff = diff_recipes(&recipe(), &new);
+        assert_eq!(summaries(&diff), vec![
+            (ChangeLevel::Major, "removed ingredient 'milk'"),
+            (ChangeLevel::Major, "added ingredient 'oat milk'"),
+            (ChangeLevel::Major, "now contains allergen gluten"),
+            (ChangeLevel::Minor, "no longer contains allergen milk"),
+            (ChangeLevel::Major, "step s-02 now does 'bake' instead of 'fry'"),
+            (ChangeLevel::Minor, "reordered steps"),
+        ]);
+        assert_eq!(diff.level(), Some(ChangeLevel::Major));
+    }
+
+    #[test]
+    fn test_grouped_by_section_with_new_allergens_first() {
+        let mut new = recipe();
+        new["meta"]["name"] = json!("Crêpes");
+        new["ingredients"][0]["allergens"] = json!(["wheat", "gluten"]);
+        new["ingredients"][1]["machine_amount"]["value"] = json!(250);
+        new["steps"] = json!([new["steps"][1], new["steps"][0]]);
+
+        let diff = diff_recipes(&recipe(), &new);
+        let sections: Vec<Section> = diff.by_section().iter().map(|(section, _)| *section).collect();
+        assert_eq!(sections, [Section::Meta, Section::Ingredients, Section::Allergens, Section::Steps]);
+        assert_eq!(diff.new_allergens().len(), 1);
+        assert_eq!(diff.new_allergens()[0].path, "/allergens/gluten");
+
+        assert_eq!(
+            diff.to_string(),
+            "⚠️  now contains allergen gluten\n\
+             meta:\n  patch edited name\n\
+             ingredients:\n  minor amount of 'milk' changed from 300 ml to 250 ml\n\
+             allergens:\n  major now contains allergen gluten\n\
+             steps:\n  minor reordered steps\n\
+             Suggested version bump: major\n"
+        );
+        let json = diff.to_json();
+        assert_eq!(json["level"], "major");
+        assert_eq!(json["new_allergens"], json!(["now contains allergen gluten"]));
+        assert_eq!(json["sections"]["ingredients"][0]["path"], "/ingredients/ing-0002");
+        assert_eq!(diff_recipes(&recipe(), &recipe()).to_string(), "No changes\n");
+    }
+}
@@ -0,0 +1,128 @@
+// Finding recipe files
+//
+// `find` lists the recipe files under a directory for `validate_directory`
+// and project roots. By default only the directory itself is searched, for
+// files ending in `.rcip` or `.json`. With `recursive` the search descends
+// into subdirectories, following symlinked directories but never into a
+// directory it has already searched, so a link back to an ancestor ends
+// instead of looping. Include and exclude globs (`project::glob_match`
+// syntax) are matched against the path relative to the root, with `/`
+// separators on every platform; when include globs are given they replace
+// the extension filter.
+
+use crate::project::glob_match;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Which files under a directory to validate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectoryOptions {
+    /// Descend into subdirectories
+    pub recursive: bool,
+    /// Globs a file's relative path must match one of; empty for any
+    /// `.rcip` or `.json` file
+    pub include: Vec<String>,
+    /// Globs that leave a file out, e.g. `**/drafts/**`
+    pub exclude: Vec<String>,
+}
+
+impl DirectoryOptions {
+    /// Search subdirectories too
+    pub fn recursive() -> Self {
+        DirectoryOptions { recursive: true, ..Default::default() }
+    }
+
+    /// Whether a relative path is selected
+    pub fn selects(&self, relative: &str) -> bool {
+        let included = if self.include.is_empty() {
+            relative.ends_with(".rcip") || relative.ends_with(".json")
+        } else {
+            self.include.iter().any(|g| glob_match(g, relative))
+        };
+        included && !self.exclude.iter().any(|g| glob_match(g, relative))
+    }
+}
+
+/// A path relative to `root`, `/`-separated
+pub fn relative_name(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let parts: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    parts.join("/")
+}
+
+/// Selected files under `root`, sorted by path
+pub fn find(root: &Path, options: &DirectoryOptions) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut searched = HashSet::new();
+    walk(root, options.recursive, &mut searched, &mut files)?;
+    let mut files: Vec<PathBuf> = files.into_iter().filter(|path| options.selects(&relative_name(root, path))).collect();
+    files.sort();
+    Ok(files)
+}
+
+fn walk(dir: &Path, recursive: bool, searched: &mut HashSet<PathBuf>, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    if !searched.insert(fs::canonicalize(dir)?) {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                walk(&path, recursive, searched, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["top.rcip", "notes.txt", "italian/pizza/recipe.rcip", "italian/drafts/new.rcip", "thai/curry.json"] {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "{}").unwrap();
+        }
+        dir
+    }
+
+    fn names(root: &Path, options: &DirectoryOptions) -> Vec<String> {
+        find(root, options).unwrap().iter().map(|p| relative_name(root, p)).collect()
+    }
+
+    #[test]
+    fn test_finds_files_by_depth_and_glob() {
+        let dir = tree();
+        assert_eq!(names(dir.path(), &DirectoryOptions::default()), ["top.rcip"]);
+        assert_eq!(
+            names(dir.path(), &DirectoryOptions::recursive()),
+            ["italian/drafts/new.rcip", "italian/pizza/recipe.rcip", "thai/curry.json", "top.rcip"]
+        );
+
+        let options = DirectoryOptions {
+            recursive: true,
+            include: vec!["**/*.rcip".to_string()],
+            exclude: vec!["**/drafts/**".to_string()],
+        };
+        assert_eq!(names(dir.path(), &options), ["italian/pizza/recipe.rcip", "top.rcip"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_cycles_end() {
+        let dir = tree();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("italian/pizza/loop")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("thai"), dir.path().join("thai-link")).unwrap();
+
+        let found = names(dir.path(), &DirectoryOptions::recursive());
+        assert_eq!(found.len(), 4, "{:?}", found);
+        assert!(found.contains(&"thai/curry.json".to_string()) != found.contains(&"thai-link/curry.json".to_string()));
+    }
+}
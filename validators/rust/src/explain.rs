@@ -0,0 +1,301 @@
+// Configuration explain
+//
+// Resolves which rules a validator would enforce, without validating
+// anything. Every setter records who set a value in a `Provenance` log; the
+// last assignment wins and earlier ones are kept so conflicts can be shown.
+// Settings nobody assigned come from the defaults. Rule enablement, options
+// and severity overrides are read from the same `rule_config` value that is
+// hashed into result provenance, so the explanation cannot drift from what
+// runs.
+
+use crate::{codes, Severity};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Where a setting came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SettingSource {
+    Default,
+    Profile,
+    ConfigFile,
+    Api,
+    Cli,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SettingSource::Default => "default",
+            SettingSource::Profile => "profile",
+            SettingSource::ConfigFile => "config file",
+            SettingSource::Api => "api",
+            SettingSource::Cli => "cli flag",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Log of setting assignments, in order
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    assignments: BTreeMap<String, Vec<(SettingSource, String)>>,
+}
+
+impl Provenance {
+    /// Record that `source` set the top-level setting `name`
+    pub fn record(&mut self, name: &str, source: SettingSource, value: String) {
+        self.assignments.entry(name.to_string()).or_default().push((source, value));
+    }
+
+    fn history(&self, name: &str) -> &[(SettingSource, String)] {
+        let top = name.split('.').next().unwrap_or(name);
+        self.assignments.get(top).map(|h| h.as_slice()).unwrap_or(&[])
+    }
+}
+
+/// One resolved setting
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveSetting {
+    pub name: String,
+    pub value: String,
+    pub source: SettingSource,
+    /// Earlier assignments that lost, oldest first
+    pub overridden: Vec<(SettingSource, String)>,
+}
+
+/// A rule as it would be enforced
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EffectiveRule {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub enabled: bool,
+    /// Setting that switches the rule on or off, if any
+    pub enabled_by: Option<EffectiveSetting>,
+    pub options: Vec<EffectiveSetting>,
+}
+
+/// (code, setting enabling it, `!` meaning enabled when false; option groups).
+/// A list setting enables its rules when non-empty
+pub(crate) const RULE_SETTINGS: &[(&str, Option<&str>, &[&str])] = &[
+    (codes::MISE_EN_PLACE, Some("mise_en_place"), &[]),
+    (codes::UNCLASSIFIED_INGREDIENT, Some("taxonomy_warnings"), &[]),
+    (codes::INGREDIENT_ORDER, Some("ingredient_order"), &[]),
+    (codes::USAGE_ORDER, Some("usage_order"), &[]),
+    (codes::ID_SEQUENCE, Some("id_sequence"), &[]),
+    (codes::ID_PADDING, Some("id_sequence"), &[]),
+    (codes::IMPLAUSIBLE_HYDRATION, Some("baking_checks"), &[]),
+    (codes::IMPLAUSIBLE_SALT, Some("baking_checks"), &[]),
+    (codes::MARKET_UNNAMED_SOURCE, Some("markets"), &[]),
+    (codes::MARKET_MAY_CONTAIN, Some("markets"), &[]),
+    (codes::MARKET_UNDECLARED_ALLERGEN, Some("markets"), &[]),
+    (codes::MARKET_ADVISORY_ALLERGEN, Some("markets"), &[]),
+    (codes::UNDECLARED_ALLERGENS, Some("resolvers"), &[]),
+    (codes::UNCONFIRMED_ALLERGENS, Some("resolvers"), &[]),
+    (codes::NOT_WHOLE_COUNT, None, &["precision_policy"]),
+    (codes::TOO_PRECISE, None, &["precision_policy"]),
+    (codes::MISSING_CHILL_PARAMS, Some("chill_policy.require_params"), &["chill_policy"]),
+    (codes::MISSING_CHILL_PARAMS_WARNING, Some("!chill_policy.require_params"), &["chill_policy"]),
+    (codes::FREEZE_TARGET, None, &["chill_policy.freeze_max_c"]),
+    (codes::CHILL_TARGET, None, &["chill_policy.chill_min_c", "chill_policy.chill_max_c"]),
+    (codes::HOT_CHILL, None, &["chill_policy.hot_threshold_c", "chill_policy.min_minutes_per_kg"]),
+    (codes::FROZEN_STORAGE_MISMATCH, None, &["cold_chain_policy.frozen_max_c"]),
+    (codes::COLD_CHAIN_DELAY, Some("cold_chain"), &["cold_chain_policy.chilled_max_c", "cold_chain_policy.max_unchilled_minutes"]),
+    (codes::FROZEN_INTO_FRY, Some("cold_chain"), &[]),
+    (codes::UNVERIFIED_DIET_LABEL, Some("verify_claims"), &["resolvers"]),
+    (codes::PLACEHOLDER_TEXT, None, &["text_policy.min_length", "text_policy.placeholders"]),
+    (codes::LONG_TEXT, None, &["text_policy.max_length"]),
+    (codes::CONDITIONAL_TEXT, None, &["text_policy.max_conditional_length"]),
+    (codes::ACTION_NOT_MENTIONED, Some("text_policy.require_action_mention"), &[]),
+    (codes::MISSING_LANGUAGE, Some("require_language"), &[]),
+    (codes::UNPARSED_HUMAN_AMOUNT, Some("readable_amounts"), &[]),
+    (codes::AMOUNT_MISMATCH, None, &["amount_tolerance"]),
+    (codes::DANGLING_STEP, Some("dangling_steps"), &[]),
+    (codes::TOTAL_TIME_MISMATCH, None, &["total_time_tolerance"]),
+    (codes::UNKNOWN_FIELD, Some("unknown_fields"), &[]),
+    (codes::MISSPELLED_FIELD, Some("unknown_fields"), &[]),
+    (codes::INGREDIENT_OVERUSED, Some("simulation"), &[]),
+    (codes::RESULT_NOT_READY, Some("simulation"), &[]),
+    (codes::PORTION_OVERUSED, Some("simulation"), &[]),
+    (codes::EQUIPMENT_CONFLICT, Some("simulation"), &[]),
+];
+
+fn severity_of(code: &str) -> Severity {
+    if code.starts_with("RCIP-W-") {
+        Severity::Warning
+    } else if code.starts_with("RCIP-I-") {
+        Severity::Info
+    } else {
+        Severity::Error
+    }
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(render).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+/// Leaf settings under a dotted name, e.g. `precision_policy.decimals.g`
+fn leaves(name: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                leaves(&format!("{}.{}", name, key), child, out);
+            }
+        }
+        other => out.push((name.to_string(), render(other))),
+    }
+}
+
+fn setting(name: &str, value: String, provenance: &Provenance) -> EffectiveSetting {
+    let history = provenance.history(name);
+    match history.split_last() {
+        Some(((source, _), earlier)) => EffectiveSetting {
+            name: name.to_string(),
+            value,
+            source: *source,
+            overridden: earlier.to_vec(),
+        },
+        None => EffectiveSetting { name: name.to_string(), value, source: SettingSource::Default, overridden: Vec::new() },
+    }
+}
+
+/// Resolve every rule against a rule configuration and its provenance
+pub fn effective_rules(config: &Value, provenance: &Provenance) -> Vec<EffectiveRule> {
+    let lookup = |name: &str| config.pointer(&format!("/{}", name.replace('.', "/")));
+
+    codes::ALL
+        .iter()
+        .map(|code| {
+            let (switch, groups) = RULE_SETTINGS
+                .iter()
+                .find(|(c, _, _)| c == code)
+                .map(|(_, switch, groups)| (*switch, *groups))
+                .unwrap_or((None, &[]));
+
+            let (enabled, enabled_by) = match switch {
+                Some(switch) => {
+                    let (name, inverted) = match switch.strip_prefix('!') {
+                        Some(name) => (name, true),
+                        None => (switch, false),
+                    };
+                    let value = lookup(name).cloned().unwrap_or(Value::Null);
+                    let set = match &value {
+                        Value::Array(items) => !items.is_empty(),
+                        other => other.as_bool().unwrap_or(false),
+                    };
+                    let on = set != inverted;
+                    (on, Some(setting(name, render(&value), provenance)))
+                }
+                None => (true, None),
+            };
+            let disabled = config.get("disabled_rules").filter(|d| d.as_array().is_some_and(|d| d.iter().any(|c| c == code)));
+            let (enabled, enabled_by) = match disabled {
+                Some(disabled) => (false, Some(setting("disabled_rules", render(disabled), provenance))),
+                None => (enabled, enabled_by),
+            };
+
+            let mut options = Vec::new();
+            for group in groups.iter() {
+                let mut found = Vec::new();
+                if let Some(value) = lookup(group) {
+                    leaves(group, value, &mut found);
+                }
+                options.extend(found.into_iter().map(|(name, value)| setting(&name, value, provenance)));
+            }
+
+            let overridden = config.get("severities").and_then(|s| s.get(*code));
+            let severity = match overridden.and_then(|v| Some((v, Severity::deserialize(v).ok()?))) {
+                Some((value, severity)) => {
+                    options.push(setting(&format!("severities.{}", code), render(value), provenance));
+                    severity
+                }
+                None => severity_of(code),
+            };
+
+            EffectiveRule { code, severity, enabled, enabled_by, options }
+        })
+        .collect()
+}
+
+fn describe(setting: &EffectiveSetting) -> String {
+    let mut text = format!("{} = {}  [{}]", setting.name, setting.value, setting.source);
+    for (source, value) in setting.overridden.iter().rev() {
+        text.push_str(&format!(", overrides {} [{}]", value, source));
+    }
+    text
+}
+
+/// Plain-text explanation, one rule per block
+pub fn render_text(rules: &[EffectiveRule]) -> String {
+    let mut out = String::new();
+    for rule in rules {
+        let severity = match rule.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        out.push_str(&format!("{:<40} {:<8} {}\n", rule.code, severity, if rule.enabled { "on" } else { "off" }));
+        for setting in rule.enabled_by.iter().chain(&rule.options) {
+            out.push_str(&format!("    {}\n", describe(setting)));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config(mise_en_place: bool) -> Value {
+        json!({
+            "precision_policy": {"decimals": {"g": 1}, "count_units": ["pcs"]},
+            "chill_policy": {"require_params": false, "freeze_max_c": -18.0},
+            "mise_en_place": mise_en_place,
+            "resolvers": false
+        })
+    }
+
+    fn rule<'a>(rules: &'a [EffectiveRule], code: &str) -> &'a EffectiveRule {
+        rules.iter().find(|r| r.code == code).unwrap()
+    }
+
+    #[test]
+    fn test_defaults() {
+        let rules = effective_rules(&config(false), &Provenance::default());
+        assert_eq!(rules.len(), codes::ALL.len());
+
+        assert!(!rule(&rules, codes::MISE_EN_PLACE).enabled);
+        assert!(!rule(&rules, codes::MISSING_CHILL_PARAMS).enabled);
+        assert!(rule(&rules, codes::MISSING_CHILL_PARAMS_WARNING).enabled);
+        assert!(rule(&rules, codes::BAD_RECIPE_ID).enabled);
+
+        let precise = rule(&rules, codes::TOO_PRECISE);
+        let names: Vec<&str> = precise.options.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["precision_policy.count_units", "precision_policy.decimals.g"]);
+        assert!(precise.options.iter().all(|o| o.source == SettingSource::Default));
+    }
+
+    #[test]
+    fn test_conflicting_sources() {
+        let mut provenance = Provenance::default();
+        provenance.record("mise_en_place", SettingSource::ConfigFile, "false".to_string());
+        provenance.record("mise_en_place", SettingSource::Cli, "true".to_string());
+
+        let rules = effective_rules(&config(true), &provenance);
+        let mise = rule(&rules, codes::MISE_EN_PLACE);
+        assert!(mise.enabled);
+        let switch = mise.enabled_by.as_ref().unwrap();
+        assert_eq!(switch.source, SettingSource::Cli);
+        assert_eq!(switch.overridden, vec![(SettingSource::ConfigFile, "false".to_string())]);
+
+        let text = render_text(&rules);
+        assert!(text.contains("mise_en_place = true  [cli flag], overrides false [config file]"));
+    }
+}
@@ -0,0 +1,362 @@
+// Recipe exports
+//
+// iCalendar (RFC 5545): steps are placed on the timeline computed by
+// `simulate::schedule`, from a start time. Only steps needing a person
+// produce events: device-driven steps (`device_profile_ref`) and passive
+// actions (wait, rest, proof, ferment) only move the clock, steps declared
+// shorter than a minute are skipped, and runs of consecutive short steps are
+// merged into one event. All times are written in UTC, so no VTIMEZONE
+// component is needed. Notes, tips and troubleshooting follow the step text
+// in the event description. Steps naming their result are titled by it, and
+// result references in step text read "the marinade" rather than a step id.
+//
+// Printable recipes in plain text, Markdown or HTML list the ingredients by
+// their product display name and number the steps, each followed by its
+// notes, tips and troubleshooting as callout boxes; recipe-wide guidance
+// comes last. Step cards for appliance displays are laid out by
+// `step_cards`, photo shot lists by `shots`.
+
+pub use crate::simulate::{schedule, ScheduledStep};
+pub use crate::shots::{shot_list, ShotList, ShotOptions};
+pub use crate::step_cards::{cards_to_markdown, to_step_cards, CardOptions, CardReport, StepCard};
+use crate::guidance::{self, Callout};
+use crate::{intermediates, product, step_cards, step_duration_minutes};
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use serde_json::Value;
+
+const PASSIVE_ACTIONS: &[&str] = &["wait", "rest", "proof", "ferment"];
+
+/// Steps shorter than this are merged with their neighbours
+const SHORT_STEP_MINUTES: f64 = 15.0;
+
+/// Minimum event length so reminders remain visible in calendars
+const MIN_EVENT_MINUTES: f64 = 5.0;
+
+struct Event {
+    uid: String,
+    summary: String,
+    description: String,
+    start_minutes: f64,
+    end_minutes: f64,
+}
+
+fn needs_person(step: &Value) -> bool {
+    let action = step.get("action").and_then(|v| v.as_str()).unwrap_or("");
+    step.get("device_profile_ref").is_none() && !PASSIVE_ACTIONS.contains(&action)
+}
+
+fn step_description(recipe: &Value, step: &Value) -> String {
+    let text = step.get("human_text").and_then(|v| v.as_str()).unwrap_or("");
+    let mut description = intermediates::friendly_text(recipe, text);
+    let hazards: Vec<&str> = step.get("hazards")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|h| h.as_str()).collect())
+        .unwrap_or_default();
+    if !hazards.is_empty() {
+        description.push_str(&format!("\nHazards: {}", hazards.join(", ")));
+    }
+    for callout in guidance::callouts(step, "en") {
+        description.push_str(&format!("\n{}: {}", callout.kind.label(), callout.text));
+    }
+    description
+}
+
+/// Export the step schedule as an iCalendar document
+pub fn to_ics(recipe: &Value, start: DateTime<Utc>) -> String {
+    let recipe_name = recipe.pointer("/meta/name").and_then(|v| v.as_str()).unwrap_or("Recipe");
+    let recipe_id = recipe.get("id").and_then(|v| v.as_str()).unwrap_or("rcip");
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+
+    let mut events: Vec<Event> = Vec::new();
+    let mut merging = false;
+
+    for (step, slot) in steps.iter().zip(schedule(recipe)) {
+        let duration = step_duration_minutes(step);
+        if !needs_person(step) || duration.map(|d| d < 1.0).unwrap_or(false) {
+            merging = false;
+            continue;
+        }
+
+        let short = duration.map(|d| d < SHORT_STEP_MINUTES).unwrap_or(true);
+        let text = step_description(recipe, step);
+        let title = step.pointer("/result/name").and_then(|v| v.as_str()).unwrap_or(&slot.step_id).to_string();
+
+        if short && merging {
+            if let Some(event) = events.last_mut() {
+                event.uid.push_str(&format!("+{}", slot.step_id));
+                event.summary.push_str(&format!(", {}", title));
+                event.description.push_str(&format!("\n\n{}", text));
+                event.end_minutes = event.end_minutes.max(slot.end_minutes);
+                continue;
+            }
+        }
+
+        events.push(Event {
+            uid: slot.step_id.clone(),
+            summary: format!("{}: {}", recipe_name, title),
+            description: text,
+            start_minutes: slot.start_minutes,
+            end_minutes: slot.end_minutes,
+        });
+        merging = short;
+    }
+
+    let stamp = format_utc(Utc::now());
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//RCIP//RCIP Validator//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+
+    for event in events {
+        let event_start = start + minutes(event.start_minutes);
+        let event_end = start + minutes(event.end_minutes.max(event.start_minutes + MIN_EVENT_MINUTES));
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@rcip-format.org", event.uid, recipe_id));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", format_utc(event_start)));
+        lines.push(format!("DTEND:{}", format_utc(event_end)));
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        lines.push(format!("DESCRIPTION:{}", escape_text(&event.description)));
+        lines.push("END:VEVENT".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|l| fold_line(l)).collect::<Vec<_>>().join("\r\n") + "\r\n"
+}
+
+/// A recipe laid out for people, resolved to one language
+struct Printable {
+    title: String,
+    ingredients: Vec<String>,
+    steps: Vec<(String, Vec<Callout>)>,
+    callouts: Vec<Callout>,
+}
+
+impl Printable {
+    fn new(recipe: &Value, locale: &str) -> Self {
+        let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten();
+        let steps = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten();
+        Printable {
+            title: recipe.pointer("/meta/name").and_then(|v| v.as_str()).unwrap_or("Recipe").to_string(),
+            ingredients: ingredients
+                .map(|ing| match step_cards::amount(ing) {
+                    Some(amount) => format!("{} {}", amount, product::display_name(ing)),
+                    None => product::display_name(ing),
+                })
+                .collect(),
+            steps: steps
+                .map(|step| (step_cards::step_text(recipe, step, Some(locale)), guidance::callouts(step, locale)))
+                .collect(),
+            callouts: guidance::callouts(recipe, locale),
+        }
+    }
+}
+
+/// Printable recipe as plain text, callouts boxed
+pub fn to_text(recipe: &Value, locale: &str) -> String {
+    let page = Printable::new(recipe, locale);
+    let mut out = format!("{}\n{}\n\nIngredients\n", page.title, "=".repeat(page.title.chars().count()));
+    for ingredient in &page.ingredients {
+        out.push_str(&format!("- {}\n", ingredient));
+    }
+    out.push_str("\nSteps\n");
+    for (i, (text, callouts)) in page.steps.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n", i + 1, text));
+        out.push_str(&guidance::to_text(callouts));
+    }
+    if !page.callouts.is_empty() {
+        out.push('\n');
+        out.push_str(&guidance::to_text(&page.callouts));
+    }
+    out
+}
+
+/// Printable recipe as Markdown, callouts as block quotes
+pub fn to_markdown(recipe: &Value, locale: &str) -> String {
+    let page = Printable::new(recipe, locale);
+    let mut out = format!("# {}\n\n## Ingredients\n\n", page.title);
+    for ingredient in &page.ingredients {
+        out.push_str(&format!("- {}\n", ingredient));
+    }
+    out.push_str("\n## Steps\n");
+    for (i, (text, callouts)) in page.steps.iter().enumerate() {
+        out.push_str(&format!("\n**{}.** {}\n", i + 1, text));
+        if !callouts.is_empty() {
+            out.push_str(&format!("\n{}", guidance::to_markdown(callouts)));
+        }
+    }
+    if !page.callouts.is_empty() {
+        out.push_str(&format!("\n{}", guidance::to_markdown(&page.callouts)));
+    }
+    out
+}
+
+/// Printable recipe as an HTML fragment, callouts as `aside` elements
+pub fn to_html(recipe: &Value, locale: &str) -> String {
+    let page = Printable::new(recipe, locale);
+    let escape = guidance::html_escape;
+    let mut out = format!("<article class=\"rcip-recipe\">\n<h1>{}</h1>\n<h2>Ingredients</h2>\n<ul>\n", escape(&page.title));
+    for ingredient in &page.ingredients {
+        out.push_str(&format!("<li>{}</li>\n", escape(ingredient)));
+    }
+    out.push_str("</ul>\n<h2>Steps</h2>\n<ol>\n");
+    for (text, callouts) in &page.steps {
+        out.push_str(&format!("<li><p>{}</p>\n{}</li>\n", escape(text), guidance::to_html(callouts)));
+    }
+    out.push_str("</ol>\n");
+    out.push_str(&guidance::to_html(&page.callouts));
+    out.push_str("</article>\n");
+    out
+}
+
+/// Parse a schedule start time: RFC 3339 with an offset, or a local-less
+/// `YYYY-MM-DDTHH:MM[:SS]` which is taken as UTC
+pub fn parse_start(text: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .map(|naive| Utc.from_utc_datetime(&naive))
+        .ok_or_else(|| format!("invalid start time '{}', expected YYYY-MM-DDTHH:MM", text))
+}
+
+fn minutes(value: f64) -> Duration {
+    Duration::seconds((value * 60.0).round() as i64)
+}
+
+fn format_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11)
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Fold a content line at 75 octets without splitting UTF-8 characters
+/// (RFC 5545 §3.1)
+fn fold_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len() + line.len() / 74 * 3);
+    let mut octets = 0;
+
+    for ch in line.chars() {
+        let width = ch.len_utf8();
+        if octets + width > 75 {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(ch);
+        octets += width;
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {"name": "Sourdough"},
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "human_text": "Feed the starter", "params": {"time_minutes": 5},
+                 "tips": ["Use lukewarm water"]},
+                {"step_id": "s-02", "action": "ferment", "human_text": "Let it rise", "params": {"time_hours": 8}},
+                {"step_id": "s-03", "action": "measure", "human_text": "Weigh flour", "params": {"time_seconds": 30}},
+                {"step_id": "s-04", "action": "mix", "human_text": "Mix dough; add salt, water", "hazards": ["sharp-tool"]},
+                {"step_id": "s-05", "action": "shape", "human_text": "Shape loaves", "params": {"time_minutes": 10},
+                 "result": {"name": "loaves"}},
+                {"step_id": "s-06", "action": "bake", "human_text": "Bake", "device_profile_ref": "oven-01",
+                 "target": ["@loaves"], "params": {"time_minutes": 45}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_schedule_follows_dependencies() {
+        let slots = schedule(&recipe());
+        assert_eq!(slots[1].start_minutes, 5.0);
+        assert_eq!(slots[1].end_minutes, 485.0);
+        assert_eq!(slots[4].end_minutes, 495.5);
+        assert_eq!(slots[5].start_minutes, 495.5);
+    }
+
+    #[test]
+    fn test_ics_events() {
+        let start = Utc.with_ymd_and_hms(2025, 6, 1, 8, 0, 0).unwrap();
+        let ics = to_ics(&recipe(), start);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        // s-01 alone, s-04 and s-05 merged; ferment, sub-minute and device steps skipped
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+        assert!(ics.contains("DTSTART:20250601T080000Z"));
+        assert!(ics.contains("DTSTART:20250601T160530Z"));
+        assert!(ics.contains("SUMMARY:Sourdough: s-04\\, loaves"));
+        assert!(ics.contains("Mix dough\\; add salt\\, water\\nHazards: sharp-tool"));
+        assert!(ics.contains("Feed the starter\\nTip: Use lukewarm water"));
+        assert!(!ics.contains("s-06"));
+    }
+
+    #[test]
+    fn test_printable_recipe() {
+        let mut recipe = recipe();
+        recipe["ingredients"] = json!([
+            {"id": "ing-0001", "name": "flour", "machine_amount": {"value": 500, "unit": "g"},
+             "product": {"brand": "King Arthur"}},
+            {"id": "ing-0002", "name": "salt"}
+        ]);
+        recipe["notes"] = json!([{"en": "Keeps <3 days", "de": "Hält 3 Tage"}]);
+
+        let text = to_text(&recipe, "en");
+        assert!(text.starts_with("Sourdough\n=========\n\nIngredients\n- 500 g flour, King Arthur (or equivalent)\n- salt\n"));
+        assert!(text.contains("1. Feed the starter\n+-------------------------+\n| Tip: Use lukewarm water |\n"));
+        assert!(text.contains("6. Bake\n\n+"));
+
+        let markdown = to_markdown(&recipe, "en");
+        assert!(markdown.contains("**1.** Feed the starter\n\n> **Tip:** Use lukewarm water\n"));
+        assert!(markdown.ends_with("**6.** Bake\n\n> **Note:** Keeps <3 days\n"));
+
+        let html = to_html(&recipe, "de");
+        assert!(html.contains("<li><p>Feed the starter</p>\n<aside class=\"rcip-callout rcip-tip\">"));
+        assert!(html.ends_with("</ol>\n<aside class=\"rcip-callout rcip-note\"><strong>Note</strong> Hält 3 Tage</aside>\n</article>\n"));
+    }
+
+    #[test]
+    fn test_parse_start() {
+        let expected = Utc.with_ymd_and_hms(2025, 6, 1, 8, 0, 0).unwrap();
+        assert_eq!(parse_start("2025-06-01T08:00"), Ok(expected));
+        assert_eq!(parse_start("2025-06-01T10:00:00+02:00"), Ok(expected));
+        assert!(parse_start("June 1st").is_err());
+    }
+
+    #[test]
+    fn test_line_folding() {
+        let line = format!("DESCRIPTION:{}", "é".repeat(80));
+        let folded = fold_line(&line);
+
+        for physical in folded.split("\r\n") {
+            assert!(physical.len() <= 75, "line of {} octets", physical.len());
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,554 @@
+// Fix pipeline
+//
+// The repairs `fix` can make run as one pipeline. Each `Fix` has a declared
+// place in the order: migrations first, so later fixes see current field
+// names, then value normalization, then fixes that add or reorder content,
+// and renumbering last, since ids follow listing order. A fix may also
+// declare others it cannot be combined with, like two ingredient sort orders.
+//
+// Before anything is written, every selected fix is tried on a copy of the
+// input to find the JSON pointers it would change. Two mutually exclusive
+// fixes whose changes overlap are both skipped as a conflict, since whichever
+// ran second would undo the first. The rest run in order, and a fix after
+// which the recipe has an error it did not have before is rolled back.
+//
+// Running the pipeline on its own output changes nothing; the tests check
+// that, and that no fix introduces an error, over the conformance corpus and
+// the examples.
+//
+// `fix_recipe` runs the `SAFE` fixes, the ones that only rewrite a value to
+// the spelling it unambiguously stands for: numbers, vocabulary terms such as
+// "TreeNuts", unit aliases such as "grams" and recipe ids in uppercase hex.
+// A value with more than one possible reading is left as it is and still
+// fails validation. Declaring a missing `allergens` list empty is a claim
+// about the ingredient, not a spelling, so `EmptyAllergens` only runs when
+// asked for.
+
+use crate::id_sequence::renumber_ids;
+use crate::infer::{self, InferenceOptions};
+use crate::ingredient_order::{sort_ingredients, SortOrder};
+use crate::precision::{round_amounts, PrecisionPolicy};
+use crate::warm::global_validator;
+use crate::{deprecations, import, mise_en_place, numeric, terms, RECIPE_ID_REGEX, UNITS};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+/// A repair the pipeline can make
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fix {
+    MigrateDeprecated,
+    NormalizeNumbers,
+    NormalizeTerms,
+    NormalizeUnits,
+    LowercaseIds,
+    RoundAmounts,
+    InferActions,
+    EmptyAllergens,
+    AddPrepPhase,
+    SortByAmount,
+    SortByName,
+    SortByUsage,
+    RenumberIds,
+}
+
+/// Where a fix runs and what it cannot be combined with
+struct Declaration {
+    fix: Fix,
+    name: &'static str,
+    excludes: &'static [Fix],
+}
+
+/// Every fix, in the order the pipeline runs them
+const PIPELINE: &[Declaration] = &[
+    Declaration { fix: Fix::MigrateDeprecated, name: "migrate-deprecated", excludes: &[] },
+    Declaration { fix: Fix::NormalizeNumbers, name: "normalize-numbers", excludes: &[] },
+    Declaration { fix: Fix::NormalizeTerms, name: "normalize-terms", excludes: &[] },
+    Declaration { fix: Fix::NormalizeUnits, name: "normalize-units", excludes: &[] },
+    Declaration { fix: Fix::LowercaseIds, name: "lowercase-ids", excludes: &[] },
+    Declaration { fix: Fix::RoundAmounts, name: "round-amounts", excludes: &[] },
+    Declaration { fix: Fix::InferActions, name: "infer-actions", excludes: &[] },
+    Declaration { fix: Fix::EmptyAllergens, name: "empty-allergens", excludes: &[] },
+    Declaration { fix: Fix::AddPrepPhase, name: "add-prep-phase", excludes: &[] },
+    Declaration { fix: Fix::SortByAmount, name: "sort-by-amount", excludes: &[Fix::SortByName, Fix::SortByUsage] },
+    Declaration { fix: Fix::SortByName, name: "sort-by-name", excludes: &[Fix::SortByAmount, Fix::SortByUsage] },
+    Declaration { fix: Fix::SortByUsage, name: "sort-by-usage", excludes: &[Fix::SortByAmount, Fix::SortByName] },
+    Declaration { fix: Fix::RenumberIds, name: "renumber-ids", excludes: &[] },
+];
+
+/// Fixes that only rewrite values to the spelling they stand for
+pub const SAFE: &[Fix] = &[Fix::NormalizeNumbers, Fix::NormalizeTerms, Fix::NormalizeUnits, Fix::LowercaseIds];
+
+impl Fix {
+    fn declaration(self) -> &'static Declaration {
+        PIPELINE.iter().find(|d| d.fix == self).expect("every fix is declared")
+    }
+
+    /// Every fix, in pipeline order
+    pub fn all() -> impl Iterator<Item = Fix> {
+        PIPELINE.iter().map(|d| d.fix)
+    }
+
+    pub fn name(self) -> &'static str {
+        self.declaration().name
+    }
+
+    /// Place in the pipeline, from 1
+    pub fn order(self) -> usize {
+        PIPELINE.iter().position(|d| d.fix == self).unwrap_or(0) + 1
+    }
+
+    /// Whether the two fixes cannot both apply to the same content
+    pub fn excludes(self, other: Fix) -> bool {
+        self.declaration().excludes.contains(&other) || other.declaration().excludes.contains(&self)
+    }
+}
+
+impl fmt::Display for Fix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Fix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PIPELINE.iter().find(|d| d.name == s).map(|d| d.fix).ok_or_else(|| {
+            let names: Vec<&str> = PIPELINE.iter().map(|d| d.name).collect();
+            format!("unknown fix '{}'; expected one of {}", s, names.join(", "))
+        })
+    }
+}
+
+/// Settings of the fixes that take any
+#[derive(Debug, Clone, Default)]
+pub struct FixOptions {
+    pub inference: InferenceOptions,
+    pub precision: PrecisionPolicy,
+}
+
+/// A fix that ran
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FixApplied {
+    /// Place in the pipeline, from 1
+    pub order: usize,
+    pub fix: Fix,
+    /// JSON pointers it changed
+    pub changed: Vec<String>,
+    /// What it did or left for a person, one line each
+    pub notes: Vec<String>,
+}
+
+/// Why a selected fix did not run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// It and a fix it excludes would both change these pointers
+    Conflict { with: Fix, paths: Vec<String> },
+    /// It left errors the recipe did not have, by rule code
+    NewErrors { codes: Vec<String> },
+    Failed { message: String },
+}
+
+/// A selected fix that did not run, and why
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FixSkipped {
+    pub fix: Fix,
+    #[serde(flatten)]
+    pub reason: SkipReason,
+}
+
+/// What the pipeline did
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FixReport {
+    /// In the order they ran
+    pub applied: Vec<FixApplied>,
+    pub skipped: Vec<FixSkipped>,
+}
+
+impl FixReport {
+    /// Whether any fix changed the recipe
+    pub fn changed(&self) -> bool {
+        self.applied.iter().any(|a| !a.changed.is_empty())
+    }
+}
+
+impl fmt::Display for FixReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for applied in &self.applied {
+            let changes = match applied.changed.len() {
+                0 => "no changes".to_string(),
+                1 => "1 change".to_string(),
+                n => format!("{} changes", n),
+            };
+            writeln!(f, "{}. {}: {}", applied.order, applied.fix, changes)?;
+            for note in &applied.notes {
+                writeln!(f, "   {}", note)?;
+            }
+        }
+        for skipped in &self.skipped {
+            match &skipped.reason {
+                SkipReason::Conflict { with, paths } => {
+                    let shown = paths.iter().take(3).cloned().collect::<Vec<_>>().join(", ");
+                    write!(f, "skipped {}: conflicts with {} at {}", skipped.fix, with, shown)?;
+                    match paths.len() {
+                        n if n > 3 => writeln!(f, " and {} more", n - 3)?,
+                        _ => writeln!(f)?,
+                    }
+                }
+                SkipReason::NewErrors { codes } => {
+                    writeln!(f, "skipped {}: it would introduce {}", skipped.fix, codes.join(", "))?
+                }
+                SkipReason::Failed { message } => writeln!(f, "skipped {}: {}", skipped.fix, message)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Ingredients of a recipe with their index
+fn ingredients(recipe: &mut Value) -> impl Iterator<Item = (usize, &mut Value)> {
+    recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()).into_iter().flatten().enumerate()
+}
+
+/// Rewrite machine amount units written as an alias, "grams" as `g`
+fn normalize_units(recipe: &mut Value) -> Vec<String> {
+    let mut notes = Vec::new();
+    for (i, ingredient) in ingredients(recipe) {
+        let Some(unit) = ingredient.pointer_mut("/machine_amount/unit") else {
+            continue;
+        };
+        let canonical = match unit.as_str() {
+            Some(alias) if !UNITS.contains(&alias) => import::unit_alias(alias.trim()),
+            _ => None,
+        };
+        if let Some(canonical) = canonical {
+            notes.push(format!("/ingredients/{}/machine_amount/unit: '{}' written as {}", i, unit.as_str().unwrap_or_default(), canonical));
+            *unit = Value::from(canonical);
+        }
+    }
+    notes
+}
+
+/// Lowercase a recipe id that is only invalid for its uppercase hex
+fn lowercase_ids(recipe: &mut Value) -> Vec<String> {
+    let Some(id) = recipe.get("id").and_then(|v| v.as_str()) else {
+        return Vec::new();
+    };
+    let lowercase = id.to_lowercase();
+    if lowercase == id || !RECIPE_ID_REGEX.is_match(&lowercase) {
+        return Vec::new();
+    }
+    recipe["id"] = Value::String(lowercase);
+    vec!["/id: written in lowercase".to_string()]
+}
+
+/// Give ingredients without an `allergens` list an empty one
+fn empty_allergens(recipe: &mut Value) -> Vec<String> {
+    let mut notes = Vec::new();
+    for (i, ingredient) in ingredients(recipe) {
+        if let Some(fields) = ingredient.as_object_mut().filter(|fields| !fields.contains_key("allergens")) {
+            fields.insert("allergens".to_string(), Value::Array(Vec::new()));
+            notes.push(format!("/ingredients/{}/allergens: declared empty; check the ingredient has none", i));
+        }
+    }
+    notes
+}
+
+/// Run one fix, returning its notes
+fn apply(fix: Fix, recipe: &mut Value, options: &FixOptions) -> Result<Vec<String>, String> {
+    let lines = |text: String| text.lines().map(String::from).collect::<Vec<_>>();
+    Ok(match fix {
+        Fix::MigrateDeprecated => lines(deprecations::migrate(recipe).to_string()),
+        Fix::NormalizeNumbers => {
+            numeric::normalize(recipe).into_iter().map(|p| format!("{}: written as a plain number", p)).collect()
+        }
+        Fix::NormalizeTerms => lines(terms::normalize(recipe).to_string()),
+        Fix::NormalizeUnits => normalize_units(recipe),
+        Fix::LowercaseIds => lowercase_ids(recipe),
+        Fix::EmptyAllergens => empty_allergens(recipe),
+        Fix::RoundAmounts => round_amounts(recipe, &options.precision)
+            .into_iter()
+            .map(|a| format!("{}: {} {} rounded to {}", a.ingredient_id, a.from, a.unit, a.to))
+            .collect(),
+        Fix::InferActions => lines(infer::actions_with(recipe, &options.inference).to_string()),
+        Fix::AddPrepPhase => {
+            mise_en_place::add_prep_phase(recipe).into_iter().map(|id| format!("added measure step {}", id)).collect()
+        }
+        Fix::SortByAmount | Fix::SortByName | Fix::SortByUsage => {
+            let order = match fix {
+                Fix::SortByAmount => SortOrder::ByAmountDesc,
+                Fix::SortByName => SortOrder::ByName,
+                _ => SortOrder::ByUsageOrder,
+            };
+            sort_ingredients(recipe, order);
+            Vec::new()
+        }
+        Fix::RenumberIds => renumber_ids(recipe)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|(old, new)| format!("{} renumbered to {}", old, new))
+            .collect(),
+    })
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// JSON pointers at which two documents differ, as deep as both have the
+/// same shape
+pub fn changed_paths(before: &Value, after: &Value) -> Vec<String> {
+    fn walk(before: &Value, after: &Value, at: &str, found: &mut Vec<String>) {
+        match (before, after) {
+            (Value::Object(a), Value::Object(b)) => {
+                let keys: std::collections::BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+                for key in keys {
+                    let at = format!("{}/{}", at, escape(key));
+                    match (a.get(key), b.get(key)) {
+                        (Some(x), Some(y)) => walk(x, y, &at, found),
+                        _ => found.push(at),
+                    }
+                }
+            }
+            (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+                for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                    walk(x, y, &format!("{}/{}", at, i), found);
+                }
+            }
+            (a, b) if a != b => found.push(at.to_string()),
+            _ => {}
+        }
+    }
+    let mut found = Vec::new();
+    walk(before, after, "", &mut found);
+    found
+}
+
+/// Whether one pointer is the other or lies inside it
+fn overlaps(a: &str, b: &str) -> bool {
+    let within = |inner: &str, outer: &str| inner == outer || inner.starts_with(&format!("{}/", outer));
+    within(a, b) || within(b, a)
+}
+
+/// Errors of a recipe by rule code, when the bundled schema is available
+fn error_counts(recipe: &Value) -> Option<BTreeMap<&'static str, usize>> {
+    let result = global_validator("0.1").ok()?.validate(recipe);
+    let mut counts = BTreeMap::new();
+    for issue in result.issues.iter().filter(|i| i.severity == crate::Severity::Error) {
+        *counts.entry(issue.code).or_insert(0) += 1;
+    }
+    Some(counts)
+}
+
+/// Run the selected fixes on a recipe in pipeline order, skipping conflicts
+/// and fixes that would introduce errors
+pub fn run(recipe: &mut Value, fixes: &[Fix], options: &FixOptions) -> FixReport {
+    let mut selected: Vec<Fix> = fixes.to_vec();
+    selected.sort();
+    selected.dedup();
+    let mut report = FixReport::default();
+
+    // What each fix would change on its own
+    let planned: HashMap<Fix, Vec<String>> = selected
+        .iter()
+        .map(|&fix| {
+            let mut copy = recipe.clone();
+            let changed = match apply(fix, &mut copy, options) {
+                Ok(_) => changed_paths(recipe, &copy),
+                Err(_) => Vec::new(),
+            };
+            (fix, changed)
+        })
+        .collect();
+    let mut conflicted: BTreeMap<Fix, (Fix, Vec<String>)> = BTreeMap::new();
+    for (i, &a) in selected.iter().enumerate() {
+        for &b in &selected[i + 1..] {
+            if !a.excludes(b) {
+                continue;
+            }
+            let paths: Vec<String> = planned[&a].iter().filter(|p| planned[&b].iter().any(|q| overlaps(p, q))).cloned().collect();
+            if !paths.is_empty() {
+                conflicted.entry(a).or_insert((b, paths.clone()));
+                conflicted.entry(b).or_insert((a, paths));
+            }
+        }
+    }
+
+    let mut errors = error_counts(recipe);
+    for fix in selected {
+        if let Some((with, paths)) = conflicted.remove(&fix) {
+            report.skipped.push(FixSkipped { fix, reason: SkipReason::Conflict { with, paths } });
+            continue;
+        }
+        let mut fixed = recipe.clone();
+        let notes = match apply(fix, &mut fixed, options) {
+            Ok(notes) => notes,
+            Err(message) => {
+                report.skipped.push(FixSkipped { fix, reason: SkipReason::Failed { message } });
+                continue;
+            }
+        };
+        let changed = changed_paths(recipe, &fixed);
+        if !changed.is_empty() {
+            let after = error_counts(&fixed);
+            if let (Some(before), Some(after)) = (&errors, &after) {
+                let new: Vec<String> = after
+                    .iter()
+                    .filter(|(code, count)| before.get(*code).copied().unwrap_or(0) < **count)
+                    .map(|(code, _)| code.to_string())
+                    .collect();
+                if !new.is_empty() {
+                    report.skipped.push(FixSkipped { fix, reason: SkipReason::NewErrors { codes: new } });
+                    continue;
+                }
+            }
+            errors = after;
+            *recipe = fixed;
+        }
+        report.applied.push(FixApplied { order: fix.order(), fix, changed, notes });
+    }
+    report
+}
+
+/// Apply the `SAFE` fixes to a copy of a recipe, returning it with the
+/// fixes that changed something
+pub fn fix_recipe(recipe: &Value) -> (Value, Vec<FixApplied>) {
+    fix_recipe_with(recipe, SAFE)
+}
+
+/// Apply the given fixes to a copy of a recipe, returning it with the fixes
+/// that changed something
+pub fn fix_recipe_with(recipe: &Value, fixes: &[Fix]) -> (Value, Vec<FixApplied>) {
+    let mut fixed = recipe.clone();
+    let report = run(&mut fixed, fixes, &FixOptions::default());
+    let applied = report.applied.into_iter().filter(|a| !a.changed.is_empty()).collect();
+    (fixed, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::path::Path;
+
+    fn recipes(dir: &Path, found: &mut Vec<(String, Value)>) {
+        let mut entries: Vec<_> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                recipes(&path, found);
+            } else if path.extension().map(|e| e == "rcip").unwrap_or(false) {
+                let recipe = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+                found.push((path.display().to_string(), recipe));
+            }
+        }
+    }
+
+    #[test]
+    fn test_idempotent_without_new_errors_over_the_corpus() {
+        let mut corpus = Vec::new();
+        recipes(Path::new("../../conformance"), &mut corpus);
+        recipes(Path::new("../../examples"), &mut corpus);
+        assert!(corpus.len() > 10);
+
+        let options = FixOptions::default();
+        for order in [Fix::SortByAmount, Fix::SortByName, Fix::SortByUsage] {
+            let fixes: Vec<Fix> = Fix::all().filter(|f| !f.excludes(order)).collect();
+            for (path, recipe) in &corpus {
+                let mut once = recipe.clone();
+                run(&mut once, &fixes, &options);
+                let mut twice = once.clone();
+                let again = run(&mut twice, &fixes, &options);
+                assert_eq!(once, twice, "{}: a second run with {} changed {:?}", path, order, again);
+                assert!(!again.changed(), "{}", path);
+
+                let (before, after) = (error_counts(recipe).unwrap(), error_counts(&once).unwrap());
+                for (code, count) in after {
+                    assert!(before.get(code).copied().unwrap_or(0) >= count, "{}: {} introduced", path, code);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_exclusive_fixes_that_overlap_are_both_skipped() {
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "Salt", "machine_amount": {"value": 5, "unit": "g"}},
+                {"id": "ing-0002", "name": "Flour", "machine_amount": {"value": 500, "unit": "g"}},
+                {"id": "ing-0003", "name": "Water", "machine_amount": {"value": 300, "unit": "g"}}
+            ],
+            "steps": []
+        });
+
+        let mut conflicting = recipe.clone();
+        let report = run(&mut conflicting, &[Fix::SortByName, Fix::SortByAmount], &FixOptions::default());
+        assert_eq!(conflicting, recipe);
+        assert!(report.applied.is_empty());
+        let skipped: Vec<Fix> = report.skipped.iter().map(|s| s.fix).collect();
+        assert_eq!(skipped, [Fix::SortByAmount, Fix::SortByName]);
+        assert!(matches!(&report.skipped[0].reason, SkipReason::Conflict { with: Fix::SortByName, .. }));
+        assert!(report.to_string().contains("skipped sort-by-amount: conflicts with sort-by-name at /ingredients/0"));
+
+        // Renumbering is declared after sorting, so ids follow the new order
+        let mut sorted = recipe.clone();
+        let report = run(&mut sorted, &[Fix::RenumberIds, Fix::SortByAmount], &FixOptions::default());
+        let orders: Vec<usize> = report.applied.iter().map(|a| a.order).collect();
+        assert_eq!(orders, [Fix::SortByAmount.order(), Fix::RenumberIds.order()]);
+        assert_eq!(sorted.pointer("/ingredients/0/name"), Some(&json!("Flour")));
+        assert_eq!(sorted.pointer("/ingredients/0/id"), Some(&json!("ing-0001")));
+    }
+
+    #[test]
+    fn test_fix_recipe_repairs_what_is_unambiguous() {
+        let mut broken: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        broken["id"] = json!("rcip-123E4567-E89B-12D3-A456-426614174000");
+        broken["ingredients"][0]["allergens"] = json!(["Eggs"]);
+        broken["meta"]["diet_labels"][1] = json!("GlutenFree");
+        broken["ingredients"][2]["machine_amount"]["unit"] = json!("grams");
+        broken["ingredients"][3]["machine_amount"]["unit"] = json!("splash");
+        broken["ingredients"][1].as_object_mut().unwrap().remove("allergens");
+        let validator = global_validator("0.1").unwrap();
+        assert!(!validator.validate(&broken).valid);
+
+        let (fixed, applied) = fix_recipe(&broken);
+        let fixes: Vec<Fix> = applied.iter().map(|a| a.fix).collect();
+        assert_eq!(fixes, [Fix::NormalizeTerms, Fix::NormalizeUnits, Fix::LowercaseIds]);
+        assert_eq!(fixed["id"], "rcip-123e4567-e89b-12d3-a456-426614174000");
+        assert_eq!(fixed["ingredients"][0]["allergens"], json!(["eggs"]));
+        assert_eq!(fixed["meta"]["diet_labels"][1], "gluten-free");
+        assert_eq!(fixed["ingredients"][2]["machine_amount"]["unit"], "g");
+        assert!(applied[1].notes.contains(&"/ingredients/2/machine_amount/unit: 'grams' written as g".to_string()));
+
+        // An unknown unit and a missing allergen list are left for a person
+        assert_eq!(fixed["ingredients"][3]["machine_amount"]["unit"], "splash");
+        let result = validator.validate(&fixed);
+        assert!(result.issues.iter().any(|i| i.code == crate::codes::MISSING_ALLERGENS));
+        assert!(result.errors.iter().any(|e| e.contains("splash")), "{:?}", result.errors);
+
+        broken["ingredients"][3]["machine_amount"]["unit"] = json!("g");
+        let (fixed, applied) = fix_recipe_with(&broken, &[SAFE, &[Fix::EmptyAllergens]].concat());
+        assert_eq!(applied.last().unwrap().fix, Fix::EmptyAllergens);
+        assert_eq!(fixed["ingredients"][1]["allergens"], json!([]));
+        let result = validator.validate(&fixed);
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(fix_recipe(&fixed), (fixed, Vec::new()));
+    }
+
+    #[test]
+    fn test_fixes_that_fail_are_skipped() {
+        let mut recipe = json!({
+            "ingredients": [{"id": "ing-0001", "name": "a"}, {"id": "ing-0001", "name": "b"}],
+            "steps": []
+        });
+        let report = run(&mut recipe, &[Fix::RenumberIds], &FixOptions::default());
+        assert!(matches!(&report.skipped[0].reason, SkipReason::Failed { message } if message.contains("ing-0001")));
+        assert_eq!(changed_paths(&json!({"a": [1, 2]}), &json!({"a": [1, 3], "b": 1})), ["/a/1", "/b"]);
+    }
+}
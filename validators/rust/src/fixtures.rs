@@ -0,0 +1,266 @@
+// Anonymized test fixtures
+//
+// `scrub` replaces every name, free-text field, URL and piece of author data
+// with generated placeholders while leaving ids, amounts, units, allergens,
+// references, params and everything else validation looks at untouched, so a
+// scrubbed recipe reproduces the validation issues of the original.
+// Extensions are vendor data of unknown meaning: their keys stay, so the
+// namespaces and shape the validator sees are the same, and every value in
+// them is cleared.
+
+use serde_json::{json, Map, Value};
+
+const WORDS: &[&str] = &[
+    "ipsum", "dolor", "sit", "amet", "consectetur", "adipiscing", "elit", "sed", "do",
+    "eiusmod", "tempor", "incididunt", "ut", "labore", "et", "dolore", "magna", "aliqua",
+    "enim", "minim", "veniam", "quis", "nostrud", "exercitation", "ullamco", "laboris",
+];
+
+/// Small deterministic generator (SplitMix64) so output only depends on the seed
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        SplitMix64(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform in [0, 1)
+    pub(crate) fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A recipe id shaped like a version 4 UUID, drawn from `rng`
+pub(crate) fn random_recipe_id(rng: &mut SplitMix64) -> String {
+    let (a, b) = (rng.next_u64(), rng.next_u64());
+    format!(
+        "rcip-{:08x}-{:04x}-4{:03x}-{:x}{:03x}-{:012x}",
+        a >> 32,
+        (a >> 16) & 0xffff,
+        a & 0xfff,
+        8 + ((b >> 62) & 0x3),
+        (b >> 48) & 0xfff,
+        b & 0xffff_ffff_ffff
+    )
+}
+
+struct Scrubber {
+    rng: SplitMix64,
+}
+
+impl Scrubber {
+    /// Placeholder prose with the same number of words as the original
+    fn words(&mut self, original: &str) -> String {
+        let count = original.split_whitespace().count().max(1);
+        (0..count)
+            .map(|_| WORDS[self.rng.below(WORDS.len())])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn text(&mut self, obj: &mut Map<String, Value>, key: &str) {
+        if let Some(Value::String(s)) = obj.get(key) {
+            let replacement = self.words(s);
+            obj.insert(key.to_string(), Value::String(replacement));
+        }
+    }
+
+    fn text_list(&mut self, obj: &mut Map<String, Value>, key: &str) {
+        if let Some(Value::Array(items)) = obj.get_mut(key) {
+            for item in items.iter_mut() {
+                if let Value::String(s) = item {
+                    *s = WORDS[self.rng.below(WORDS.len())].to_string();
+                }
+            }
+        }
+    }
+}
+
+fn set_if_present(obj: &mut Map<String, Value>, key: &str, value: String) {
+    if obj.get(key).map(|v| v.is_string()).unwrap_or(false) {
+        obj.insert(key.to_string(), Value::String(value));
+    }
+}
+
+/// Keep the keys and shape of a value, emptying every string and zeroing
+/// every number and flag
+fn clear(value: &mut Value) {
+    match value {
+        Value::Object(fields) => fields.values_mut().for_each(clear),
+        Value::Array(items) => items.iter_mut().for_each(clear),
+        Value::String(s) => s.clear(),
+        Value::Number(_) => *value = json!(0),
+        Value::Bool(b) => *b = false,
+        Value::Null => {}
+    }
+}
+
+fn objects_mut<'a>(recipe: &'a mut Value, key: &str) -> impl Iterator<Item = &'a mut Map<String, Value>> {
+    recipe
+        .get_mut(key)
+        .and_then(|v| v.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_object_mut())
+}
+
+/// Deterministically anonymize a recipe for sharing in bug reports
+pub fn scrub(recipe: &Value, seed: u64) -> Value {
+    let mut out = recipe.clone();
+    let mut s = Scrubber { rng: SplitMix64::new(seed) };
+
+    if let Some(meta) = out.get_mut("meta").and_then(|m| m.as_object_mut()) {
+        set_if_present(meta, "name", format!("Recipe {:08x}", s.rng.next_u64() as u32));
+        s.text(meta, "description");
+        s.text_list(meta, "keywords");
+
+        match meta.get_mut("author") {
+            Some(Value::String(author)) => *author = "author-1".to_string(),
+            Some(Value::Object(author)) => {
+                set_if_present(author, "name", "author-1".to_string());
+                set_if_present(author, "email", "author-1@example.com".to_string());
+                set_if_present(author, "organization", "organization-1".to_string());
+            }
+            _ => {}
+        }
+
+        if let Some(origin) = meta.get_mut("origin").and_then(|o| o.as_object_mut()) {
+            s.text(origin, "region");
+            s.text(origin, "city");
+        }
+    }
+
+    for (i, ingredient) in objects_mut(&mut out, "ingredients").enumerate() {
+        set_if_present(ingredient, "name", format!("ingredient-{}", i + 1));
+        set_if_present(ingredient, "brand", format!("brand-{}", i + 1));
+        s.text(ingredient, "notes");
+
+        if let Some(Value::Array(substitutes)) = ingredient.get_mut("substitutes") {
+            for (j, substitute) in substitutes.iter_mut().filter_map(|v| v.as_object_mut()).enumerate() {
+                set_if_present(substitute, "name", format!("substitute-{}", j + 1));
+                s.text(substitute, "notes");
+            }
+        }
+    }
+
+    for step in objects_mut(&mut out, "steps") {
+        s.text(step, "human_text");
+        s.text(step, "fallback_instructions");
+
+        if let Some(done_when) = step.get_mut("done_when").and_then(|d| d.as_object_mut()) {
+            s.text(done_when, "visual");
+            s.text(done_when, "texture");
+        }
+    }
+
+    for image in objects_mut(&mut out, "images") {
+        let id = image.get("id").and_then(|v| v.as_str()).unwrap_or("image").to_string();
+        s.text(image, "caption");
+        set_if_present(image, "url", format!("https://example.com/images/{}.jpg", id));
+        set_if_present(image, "data_uri", "data:image/png;base64,".to_string());
+    }
+
+    for (i, profile) in objects_mut(&mut out, "device_profiles").enumerate() {
+        let id = profile.get("id").and_then(|v| v.as_str()).unwrap_or("device").to_string();
+        set_if_present(profile, "name", format!("device-{}", i + 1));
+        set_if_present(profile, "manufacturer", format!("manufacturer-{}", i + 1));
+        set_if_present(profile, "model", format!("model-{}", i + 1));
+        set_if_present(profile, "api_endpoint", format!("https://example.com/devices/{}", id));
+    }
+
+    if let Some(signature) = out.pointer_mut("/compatibility/signature").and_then(|v| v.as_object_mut()) {
+        set_if_present(signature, "public_key", String::new());
+    }
+
+    if let Some(extensions) = out.get_mut("extensions") {
+        clear(extensions);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "no-fs"))]
+    use crate::RCIPValidator;
+
+    fn recipe() -> Value {
+        json!({
+            "rcip_version": "0.1",
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {
+                "name": "Grandma's Secret Stew",
+                "description": "Our signature dish since 1952",
+                "author": {"name": "Jane Chef", "email": "jane@restaurant.example"},
+                "created_date": "2025-01-15T10:00:00Z"
+            },
+            "ingredients": [
+                {
+                    "id": "ing-0001",
+                    "name": "Secret spice blend",
+                    "human_amount": "12.345 g",
+                    "machine_amount": {"value": 12.345, "unit": "g"},
+                    "allergens": ["mustard", "nuts"],
+                    "notes": "Supplier: ACME Spice Co"
+                }
+            ],
+            "steps": [
+                {"step_id": "s-01", "human_text": "Toast the blend in our copper pan", "action": "heat",
+                 "target": ["ing-0001", "ing-0002"]},
+                {"step_id": "s-02", "human_text": "Rest", "action": "rest", "target": ["s-09:result"]}
+            ],
+            "images": [{"id": "img-1", "url": "https://cdn.restaurant.example/stew.jpg"}],
+            "extensions": {"acme": {"sku": "ACME-991", "cost": 4.2, "tags": ["house"], "organic": true}}
+        })
+    }
+
+    #[test]
+    fn test_scrub_is_deterministic() {
+        assert_eq!(scrub(&recipe(), 42), scrub(&recipe(), 42));
+        assert_ne!(scrub(&recipe(), 42), scrub(&recipe(), 43));
+    }
+
+    #[test]
+    fn test_scrub_removes_private_text_and_keeps_structure() {
+        let scrubbed = scrub(&recipe(), 42);
+        let text = scrubbed.to_string();
+
+        for private in ["Grandma", "Jane", "restaurant.example", "ACME", "copper"] {
+            assert!(!text.contains(private), "{} leaked into scrubbed output", private);
+        }
+
+        assert_eq!(scrubbed["ingredients"][0]["name"], "ingredient-1");
+        assert_eq!(scrubbed["ingredients"][0]["machine_amount"], recipe()["ingredients"][0]["machine_amount"]);
+        assert_eq!(scrubbed["steps"][0]["target"], recipe()["steps"][0]["target"]);
+        assert_eq!(scrubbed["steps"][0]["human_text"].as_str().unwrap().split_whitespace().count(), 7);
+        assert_eq!(scrubbed["extensions"], json!({"acme": {"sku": "", "cost": 0, "tags": [""], "organic": false}}));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_scrub_preserves_validation_issues() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let original = validator.validate_recipe(&recipe());
+        let scrubbed = validator.validate_recipe(&scrub(&recipe(), 7));
+
+        assert!(!original.errors.is_empty());
+        assert_eq!(original.valid, scrubbed.valid);
+        assert_eq!(original.errors, scrubbed.errors);
+        assert_eq!(original.warnings, scrubbed.warnings);
+    }
+}
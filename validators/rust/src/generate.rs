@@ -0,0 +1,365 @@
+// Benchmark corpora
+//
+// `recipe` builds a plausible, schema-valid recipe from a seeded generator and
+// `corpus` writes many of them to a directory. Output depends only on the
+// seed, the count and the profile, so runs of different releases can be
+// compared on identical input. A profile mixes small, medium and huge recipes
+// and injects one known defect into a share of the files; `manifest.json`
+// records which file got which defect. Files are written as plain JSON; other
+// formats can be added to the profile once the validator reads them.
+//
+// Ids come from the seeded generator unless the profile asks for
+// deterministic ids, which are then derived from each recipe's content.
+
+use crate::fixtures::{random_recipe_id, SplitMix64};
+use crate::ids::{content_identity, IdStrategy};
+use crate::RCIPError;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+
+/// (name, unit, min, max, allergens)
+const INGREDIENTS: &[(&str, &str, u32, u32, &[&str])] = &[
+    ("all-purpose flour", "g", 100, 1000, &["wheat", "gluten"]),
+    ("whole milk", "ml", 50, 1000, &["milk"]),
+    ("large egg", "pcs", 1, 6, &["eggs"]),
+    ("unsalted butter", "g", 10, 250, &["milk"]),
+    ("granulated sugar", "g", 5, 400, &[]),
+    ("fine sea salt", "g", 1, 15, &[]),
+    ("olive oil", "ml", 5, 120, &[]),
+    ("yellow onion", "pcs", 1, 4, &[]),
+    ("garlic clove", "pcs", 1, 8, &[]),
+    ("carrot", "pcs", 1, 6, &[]),
+    ("celery stalk", "pcs", 1, 4, &["celery"]),
+    ("canned tomatoes", "g", 200, 800, &[]),
+    ("chicken stock", "ml", 100, 2000, &[]),
+    ("salmon fillet", "g", 150, 900, &["fish"]),
+    ("shrimp", "g", 100, 600, &["shellfish"]),
+    ("soy sauce", "ml", 5, 60, &["soybeans", "wheat"]),
+    ("sesame seeds", "g", 2, 30, &["sesame"]),
+    ("dijon mustard", "g", 5, 40, &["mustard"]),
+    ("roasted peanuts", "g", 10, 150, &["peanuts"]),
+    ("walnuts", "g", 10, 150, &["tree-nuts"]),
+    ("white wine", "ml", 50, 300, &["sulphites"]),
+    ("basmati rice", "g", 100, 600, &[]),
+    ("potato", "pcs", 1, 8, &[]),
+    ("fresh parsley", "g", 2, 30, &[]),
+    ("black pepper", "g", 1, 5, &[]),
+    ("lemon", "pcs", 1, 3, &[]),
+    ("heavy cream", "ml", 50, 500, &["milk"]),
+    ("dried yeast", "g", 3, 14, &[]),
+];
+
+/// (action, min minutes, max minutes)
+const ACTIONS: &[(&str, u32, u32)] = &[
+    ("measure", 1, 3),
+    ("chop", 2, 10),
+    ("dice", 2, 10),
+    ("mix", 1, 8),
+    ("combine", 1, 5),
+    ("knead", 5, 15),
+    ("heat", 2, 10),
+    ("saute", 3, 12),
+    ("simmer", 10, 90),
+    ("boil", 5, 30),
+    ("bake", 15, 75),
+    ("roast", 20, 120),
+    ("rest", 5, 60),
+    ("garnish", 1, 3),
+];
+
+/// Recipe size class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipeSize {
+    Small,
+    Medium,
+    Huge,
+}
+
+impl RecipeSize {
+    /// (min, max) ingredient and step counts
+    fn bounds(self) -> ((usize, usize), (usize, usize)) {
+        match self {
+            RecipeSize::Small => ((3, 8), (2, 6)),
+            RecipeSize::Medium => ((8, 20), (6, 15)),
+            RecipeSize::Huge => ((60, 150), (40, 120)),
+        }
+    }
+}
+
+/// A class of defect injected into a generated recipe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Defect {
+    MissingAllergens,
+    InvalidAction,
+    DanglingReference,
+    BadRecipeId,
+    MissingUnit,
+    OutOfRange,
+}
+
+impl Defect {
+    pub const ALL: &'static [Defect] = &[
+        Defect::MissingAllergens,
+        Defect::InvalidAction,
+        Defect::DanglingReference,
+        Defect::BadRecipeId,
+        Defect::MissingUnit,
+        Defect::OutOfRange,
+    ];
+}
+
+/// Shape of a generated corpus
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusProfile {
+    /// Relative weights of small, medium and huge recipes
+    pub size_mix: [u32; 3],
+    /// Share of files, 0.0 to 1.0, that get one injected defect
+    pub error_rate: f64,
+    /// `Random` keeps the ids drawn from the seed
+    pub ids: IdStrategy,
+}
+
+impl Default for CorpusProfile {
+    fn default() -> Self {
+        CorpusProfile { size_mix: [70, 25, 5], error_rate: 0.0, ids: IdStrategy::Random }
+    }
+}
+
+/// A file written by `corpus`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CorpusEntry {
+    pub file: String,
+    pub size: RecipeSize,
+    pub defect: Option<Defect>,
+}
+
+fn between(rng: &mut SplitMix64, (min, max): (usize, usize)) -> usize {
+    min + rng.below(max - min + 1)
+}
+
+fn pick_size(rng: &mut SplitMix64, mix: [u32; 3]) -> RecipeSize {
+    let total: u32 = mix.iter().sum();
+    if total == 0 {
+        return RecipeSize::Small;
+    }
+    let mut roll = rng.below(total as usize) as u32;
+    for (size, weight) in [RecipeSize::Small, RecipeSize::Medium, RecipeSize::Huge].into_iter().zip(mix) {
+        if roll < weight {
+            return size;
+        }
+        roll -= weight;
+    }
+    RecipeSize::Huge
+}
+
+/// Generate one schema-valid recipe
+pub(crate) fn recipe(rng: &mut SplitMix64, size: RecipeSize) -> Value {
+    let (ingredient_bounds, step_bounds) = size.bounds();
+    let ingredient_count = between(rng, ingredient_bounds);
+    let step_count = between(rng, step_bounds);
+
+    let ingredients: Vec<Value> = (1..=ingredient_count)
+        .map(|n| {
+            let (name, unit, min, max, allergens) = INGREDIENTS[rng.below(INGREDIENTS.len())];
+            let value = min + rng.below((max - min + 1) as usize) as u32;
+            json!({
+                "id": format!("ing-{:04}", n),
+                "name": name,
+                "human_amount": format!("{} {}", value, unit),
+                "machine_amount": {"value": value, "unit": unit},
+                "allergens": allergens,
+            })
+        })
+        .collect();
+
+    // Every ingredient is added by some step; each step after the first
+    // continues from the result of the previous one.
+    let mut targets: Vec<Vec<String>> = vec![Vec::new(); step_count];
+    for n in 1..=ingredient_count {
+        targets[rng.below(step_count)].push(format!("ing-{:04}", n));
+    }
+
+    let mut total_minutes = 0;
+    let steps: Vec<Value> = targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, mut target)| {
+            if i > 0 {
+                target.insert(0, format!("s-{:02}:result", i));
+            }
+            let (action, min, max) = ACTIONS[rng.below(ACTIONS.len())];
+            let minutes = min + rng.below((max - min + 1) as usize) as u32;
+            total_minutes += minutes;
+            json!({
+                "step_id": format!("s-{:02}", i + 1),
+                "human_text": format!("{} for {} minutes", action, minutes),
+                "action": action,
+                "target": target,
+                "params": {"time_minutes": minutes},
+            })
+        })
+        .collect();
+
+    let id = random_recipe_id(rng);
+    json!({
+        "rcip_version": "0.1",
+        "id": id,
+        "meta": {
+            "name": format!("Benchmark recipe {}", &id[5..13]),
+            "author": "rcip corpus generator",
+            "created_date": format!("2025-{:02}-{:02}T12:00:00Z", 1 + rng.below(12), 1 + rng.below(28)),
+            "servings": {"amount": 1 + rng.below(8)},
+            "total_time_minutes": total_minutes,
+        },
+        "ingredients": ingredients,
+        "steps": steps,
+    })
+}
+
+/// Break a generated recipe in the given way
+pub(crate) fn inject(recipe: &mut Value, defect: Defect, rng: &mut SplitMix64) {
+    let ingredient_count = recipe["ingredients"].as_array().map(|a| a.len()).unwrap_or(0);
+    let step_count = recipe["steps"].as_array().map(|a| a.len()).unwrap_or(0);
+    let ingredient = &mut recipe["ingredients"][rng.below(ingredient_count.max(1))];
+
+    match defect {
+        Defect::MissingAllergens => {
+            if let Some(fields) = ingredient.as_object_mut() {
+                fields.remove("allergens");
+            }
+        }
+        Defect::MissingUnit => {
+            if let Some(fields) = ingredient["machine_amount"].as_object_mut() {
+                fields.remove("unit");
+            }
+        }
+        Defect::OutOfRange => ingredient["machine_amount"]["value"] = json!(-1),
+        Defect::InvalidAction => recipe["steps"][rng.below(step_count.max(1))]["action"] = json!("teleport"),
+        Defect::DanglingReference => {
+            if let Some(target) = recipe["steps"][rng.below(step_count.max(1))]["target"].as_array_mut() {
+                target.push(json!("ing-9999"));
+            }
+        }
+        Defect::BadRecipeId => recipe["id"] = json!("recipe-1"),
+    }
+}
+
+/// Write `count` generated recipes and a `manifest.json` into `dir`
+pub fn corpus(dir: &Path, count: usize, seed: u64, profile: CorpusProfile) -> Result<Vec<CorpusEntry>, RCIPError> {
+    fs::create_dir_all(dir)?;
+    let mut seeds = SplitMix64::new(seed);
+    let width = count.to_string().len().max(4);
+    let mut entries = Vec::with_capacity(count);
+
+    for n in 1..=count {
+        // One generator per file, so a file's content does not depend on the
+        // profile choices made for the files before it
+        let mut rng = SplitMix64::new(seeds.next_u64());
+        let size = pick_size(&mut rng, profile.size_mix);
+        let mut generated = recipe(&mut rng, size);
+        if profile.ids.is_deterministic() {
+            let identity = content_identity(&generated);
+            profile.ids.assign(&mut generated, &identity);
+        }
+
+        let defect = if rng.unit() < profile.error_rate {
+            let defect = Defect::ALL[rng.below(Defect::ALL.len())];
+            inject(&mut generated, defect, &mut rng);
+            Some(defect)
+        } else {
+            None
+        };
+
+        let file = format!("recipe-{:0width$}.json", n, width = width);
+        fs::write(dir.join(&file), serde_json::to_string_pretty(&generated)?)?;
+        entries.push(CorpusEntry { file, size, defect });
+    }
+
+    let manifest = json!({"seed": seed, "count": count, "files": entries});
+    fs::write(dir.join("manifest.json"), serde_json::to_string_pretty(&manifest)?)?;
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "no-fs"))]
+    use crate::RCIPValidator;
+
+    fn read_all(dir: &Path) -> Vec<(String, String)> {
+        let mut files: Vec<(String, String)> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .map(|p| (p.file_name().unwrap().to_string_lossy().to_string(), fs::read_to_string(&p).unwrap()))
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn test_same_seed_same_corpus() {
+        let profile = CorpusProfile { size_mix: [5, 3, 1], error_rate: 0.3, ..CorpusProfile::default() };
+        let (a, b, c) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+
+        corpus(a.path(), 25, 7, profile.clone()).unwrap();
+        corpus(b.path(), 25, 7, profile.clone()).unwrap();
+        corpus(c.path(), 25, 8, profile).unwrap();
+
+        assert_eq!(read_all(a.path()).len(), 26);
+        assert_eq!(read_all(a.path()), read_all(b.path()));
+        assert_ne!(read_all(a.path()), read_all(c.path()));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_clean_recipes_validate_and_defects_fail() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let mut rng = SplitMix64::new(42);
+
+        for size in [RecipeSize::Small, RecipeSize::Medium, RecipeSize::Huge] {
+            let generated = recipe(&mut rng, size);
+            let result = validator.validate_recipe(&generated);
+            assert!(result.valid, "{:?}: {:?}", size, result.errors);
+
+            for defect in Defect::ALL {
+                let mut broken = generated.clone();
+                inject(&mut broken, *defect, &mut rng);
+                assert!(!validator.validate_recipe(&broken).valid, "{:?} went unnoticed", defect);
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_rate() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = CorpusProfile { error_rate: 0.5, ..CorpusProfile::default() };
+        let entries = corpus(dir.path(), 200, 1, profile).unwrap();
+
+        let defective = entries.iter().filter(|e| e.defect.is_some()).count();
+        assert!((70..=130).contains(&defective), "{} defective files", defective);
+        assert!(corpus(dir.path(), 10, 1, CorpusProfile::default()).unwrap().iter().all(|e| e.defect.is_none()));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_deterministic_ids() {
+        let profile = CorpusProfile { ids: IdStrategy::deterministic("bench"), ..CorpusProfile::default() };
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        corpus(a.path(), 5, 3, profile.clone()).unwrap();
+        corpus(b.path(), 5, 3, profile).unwrap();
+        assert_eq!(read_all(a.path()), read_all(b.path()));
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let generated: Value = serde_json::from_str(&fs::read_to_string(a.path().join("recipe-0001.json")).unwrap()).unwrap();
+        assert_eq!(&generated["id"].as_str().unwrap()[19..20], "5");
+        assert!(generated["ingredients"][0]["id"].as_str().unwrap().len() > "ing-0001".len());
+        assert!(validator.validate_recipe(&generated).valid);
+    }
+}
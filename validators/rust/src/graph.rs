@@ -0,0 +1,483 @@
+// Step dependency graph
+//
+// A step depends on every step whose result it targets, as `s-02:result`,
+// `@name` or a portion of either (see `intermediates::producer`), and on the
+// steps an explicit `depends_on` list names. Ingredient targets add no edge.
+// A result reference naming no step is kept as dangling rather than dropped,
+// so schedulers can refuse the recipe. Steps are nodes in document order; a
+// repeated step id refers to its first step.
+//
+// A cycle (s-02 uses s-03:result and s-03 uses s-02:result) means no step in
+// it can ever start. `cycles` finds the strongly connected components and
+// reports one cycle through each, starting from its earliest step.
+//
+// `execution_plan` orders an acyclic graph into waves: a step runs in the
+// wave after the last of its dependencies, so the steps of one wave have no
+// path between them and can run at the same time (preheating the oven while
+// chopping). Within a wave steps keep their document order.
+// `critical_path_minutes` walks the same order, finishing each step its
+// duration after the latest of its dependencies.
+
+use crate::intermediates::producer;
+use crate::{portions, step_duration_minutes};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A step target or `depends_on` entry naming no step
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DanglingReference {
+    pub step: String,
+    pub target: String,
+}
+
+/// Steps and the steps each one waits on
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RecipeGraph {
+    steps: Vec<String>,
+    /// Index of each step id's first step
+    positions: HashMap<String, usize>,
+    /// Dependencies of each step, as indices into `steps`
+    dependencies: Vec<Vec<usize>>,
+    dangling: Vec<DanglingReference>,
+}
+
+fn strings<'a>(step: &'a Value, field: &str) -> impl Iterator<Item = &'a str> {
+    step.get(field).and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_str())
+}
+
+impl RecipeGraph {
+    /// The graph of a recipe's steps
+    pub fn build(recipe: &Value) -> RecipeGraph {
+        let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+        let ids: Vec<String> = steps
+            .iter()
+            .map(|s| s.get("step_id").and_then(|v| v.as_str()).unwrap_or("?").to_string())
+            .collect();
+        let mut graph = RecipeGraph { steps: ids.clone(), ..Default::default() };
+        for (index, id) in ids.iter().enumerate() {
+            graph.positions.entry(id.clone()).or_insert(index);
+        }
+        for (index, step) in steps.iter().enumerate() {
+            let results = strings(step, "target")
+                .filter(|t| portions::whole(t).ends_with(":result") || t.starts_with('@'))
+                .map(|t| (t, producer(recipe, t)));
+            let explicit = strings(step, "depends_on").map(|d| (d, Some(producer(recipe, d).unwrap_or(d))));
+
+            let mut dependencies = Vec::new();
+            for (target, id) in results.chain(explicit) {
+                match id.and_then(|id| graph.positions.get(id)) {
+                    Some(&dependency) if !dependencies.contains(&dependency) => dependencies.push(dependency),
+                    Some(_) => {}
+                    None => graph.dangling.push(DanglingReference { step: ids[index].clone(), target: target.to_string() }),
+                }
+            }
+            graph.dependencies.push(dependencies);
+        }
+        graph
+    }
+
+    /// Step ids in document order
+    pub fn steps(&self) -> &[String] {
+        &self.steps
+    }
+
+    /// Index of a step in document order
+    pub fn position(&self, step: &str) -> Option<usize> {
+        self.positions.get(step).copied()
+    }
+
+    /// Steps `step` waits on, in the order it names them
+    pub fn dependencies(&self, step: &str) -> Vec<&str> {
+        self.position(step)
+            .map(|index| self.dependencies[index].iter().map(|&d| self.steps[d].as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Steps waiting on `step`, in document order
+    pub fn dependents(&self, step: &str) -> Vec<&str> {
+        let index = match self.position(step) {
+            Some(index) => index,
+            None => return Vec::new(),
+        };
+        self.dependencies
+            .iter()
+            .enumerate()
+            .filter(|(_, dependencies)| dependencies.contains(&index))
+            .map(|(dependent, _)| self.steps[dependent].as_str())
+            .collect()
+    }
+
+    /// Result references and `depends_on` entries naming no step
+    pub fn dangling(&self) -> &[DanglingReference] {
+        &self.dangling
+    }
+
+    /// One cycle through each group of steps that wait on each other, as
+    /// step ids in dependency order starting from the group's earliest step;
+    /// `[s-02, s-03]` means s-02 waits on s-03, which waits on s-02
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles: Vec<Vec<String>> = self
+            .components()
+            .into_iter()
+            .filter(|component| component.len() > 1 || self.dependencies[component[0]].contains(&component[0]))
+            .map(|component| {
+                let start = *component.iter().min().unwrap();
+                let members: HashSet<usize> = component.into_iter().collect();
+                self.cycle_through(start, &members).into_iter().map(|i| self.steps[i].clone()).collect()
+            })
+            .collect();
+        cycles.sort_by_key(|cycle| self.position(&cycle[0]));
+        cycles
+    }
+
+    /// Strongly connected components (Tarjan's algorithm), walked with an
+    /// explicit stack so a long chain of steps cannot overflow the call stack
+    fn components(&self) -> Vec<Vec<usize>> {
+        struct Search {
+            index: Vec<Option<usize>>,
+            low: Vec<usize>,
+            on_stack: Vec<bool>,
+            stack: Vec<usize>,
+            next: usize,
+        }
+
+        impl Search {
+            fn enter(&mut self, node: usize) {
+                self.index[node] = Some(self.next);
+                self.low[node] = self.next;
+                self.next += 1;
+                self.stack.push(node);
+                self.on_stack[node] = true;
+            }
+        }
+
+        let count = self.steps.len();
+        let mut search = Search {
+            index: vec![None; count],
+            low: vec![0; count],
+            on_stack: vec![false; count],
+            stack: Vec::new(),
+            next: 0,
+        };
+        let mut components = Vec::new();
+        // Each entry is a step being visited and how many of its
+        // dependencies have been looked at
+        let mut walk: Vec<(usize, usize)> = Vec::new();
+        for root in 0..count {
+            if search.index[root].is_some() {
+                continue;
+            }
+            search.enter(root);
+            walk.push((root, 0));
+            while let Some((node, seen)) = walk.last_mut() {
+                let node = *node;
+                if let Some(&next) = self.dependencies[node].get(*seen) {
+                    *seen += 1;
+                    match search.index[next] {
+                        None => {
+                            search.enter(next);
+                            walk.push((next, 0));
+                        }
+                        Some(index) if search.on_stack[next] => search.low[node] = search.low[node].min(index),
+                        Some(_) => {}
+                    }
+                    continue;
+                }
+
+                walk.pop();
+                if let Some(&(parent, _)) = walk.last() {
+                    search.low[parent] = search.low[parent].min(search.low[node]);
+                }
+                if Some(search.low[node]) == search.index[node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = search.stack.pop() {
+                        search.on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.push(component);
+                }
+            }
+        }
+        components
+    }
+
+    /// A path from `start` back to itself inside `component`, found by
+    /// breadth-first search so it is as short as possible
+    fn cycle_through(&self, start: usize, component: &HashSet<usize>) -> Vec<usize> {
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &next in &self.dependencies[node] {
+                if next == start {
+                    let mut path = vec![node];
+                    while let Some(&previous) = came_from.get(path.last().unwrap()) {
+                        path.push(previous);
+                    }
+                    path.reverse();
+                    return path;
+                }
+                if component.contains(&next) && !came_from.contains_key(&next) {
+                    came_from.insert(next, node);
+                    queue.push_back(next);
+                }
+            }
+        }
+        vec![start]
+    }
+
+    /// Step indices with every step after its dependencies, by Kahn's
+    /// algorithm; steps on or after a cycle are left out
+    fn topological_order(&self) -> Vec<usize> {
+        let count = self.steps.len();
+        let mut waiting: Vec<usize> = self.dependencies.iter().map(Vec::len).collect();
+        let mut dependents = vec![Vec::new(); count];
+        for (step, dependencies) in self.dependencies.iter().enumerate() {
+            for &dependency in dependencies {
+                dependents[dependency].push(step);
+            }
+        }
+        let mut order = Vec::with_capacity(count);
+        let mut ready: Vec<usize> = (0..count).filter(|&step| waiting[step] == 0).collect();
+        while let Some(step) = ready.pop() {
+            order.push(step);
+            for &dependent in &dependents[step] {
+                waiting[dependent] -= 1;
+                if waiting[dependent] == 0 {
+                    ready.push(dependent);
+                }
+            }
+        }
+        order
+    }
+}
+
+/// Minutes the longest chain of dependent steps takes, each step from its
+/// time params and untimed steps taking none; independent chains run side by
+/// side, so this is the least time the recipe can take
+pub fn critical_path_minutes(recipe: &Value) -> f64 {
+    let graph = RecipeGraph::build(recipe);
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let mut finish = vec![0.0; steps.len()];
+    for step in graph.topological_order() {
+        let start = graph.dependencies[step].iter().map(|&d| finish[d]).fold(0.0, f64::max);
+        finish[step] = start + step_duration_minutes(steps[step]).unwrap_or(0.0);
+    }
+    finish.into_iter().fold(0.0, f64::max)
+}
+
+
+/// A step in an execution plan
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlanEntry {
+    pub step_id: String,
+    pub action: Option<String>,
+    /// From the step's time params, when it has any
+    pub duration_minutes: Option<f64>,
+    /// Steps that must finish first
+    pub waits_on: Vec<String>,
+}
+
+/// Steps grouped into waves that run one after another; the steps of a wave
+/// may run in parallel
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct ExecutionPlan {
+    pub waves: Vec<Vec<PlanEntry>>,
+}
+
+impl ExecutionPlan {
+    /// Every step in a valid order: wave by wave
+    pub fn order(&self) -> impl Iterator<Item = &PlanEntry> {
+        self.waves.iter().flatten()
+    }
+}
+
+/// Why a recipe has no execution plan
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlanError {
+    /// Steps waiting on each other, as `RecipeGraph::cycles` gives them
+    Cycles(Vec<Vec<String>>),
+    /// Result references and `depends_on` entries naming no step
+    Dangling(Vec<DanglingReference>),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanError::Cycles(cycles) => {
+                let cycles: Vec<String> = cycles
+                    .iter()
+                    .map(|cycle| cycle.iter().chain(cycle.first()).cloned().collect::<Vec<_>>().join(" -> "))
+                    .collect();
+                write!(f, "steps wait on each other: {}", cycles.join("; "))
+            }
+            PlanError::Dangling(references) => {
+                let references: Vec<String> =
+                    references.iter().map(|r| format!("{} in step {}", r.target, r.step)).collect();
+                write!(f, "references to no step: {}", references.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// The order a kitchen controller can run a recipe's steps in, with the
+/// steps that may run in parallel grouped into waves
+pub fn execution_plan(recipe: &Value) -> Result<ExecutionPlan, PlanError> {
+    let graph = RecipeGraph::build(recipe);
+    let cycles = graph.cycles();
+    if !cycles.is_empty() {
+        return Err(PlanError::Cycles(cycles));
+    }
+    if !graph.dangling.is_empty() {
+        return Err(PlanError::Dangling(graph.dangling));
+    }
+
+    // A step's wave is one after its latest dependency's
+    let mut wave = vec![0usize; graph.steps.len()];
+    for step in graph.topological_order() {
+        wave[step] = graph.dependencies[step].iter().map(|&d| wave[d] + 1).max().unwrap_or(0);
+    }
+
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let mut plan = ExecutionPlan { waves: vec![Vec::new(); wave.iter().max().map_or(0, |w| w + 1)] };
+    for (index, step) in steps.iter().enumerate() {
+        plan.waves[wave[index]].push(PlanEntry {
+            step_id: graph.steps[index].clone(),
+            action: step.get("action").and_then(|v| v.as_str()).map(String::from),
+            duration_minutes: step_duration_minutes(step),
+            waits_on: graph.dependencies[index].iter().map(|&d| graph.steps[d].clone()).collect(),
+        });
+    }
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe(steps: Value) -> Value {
+        json!({"ingredients": [{"id": "ing-0001"}], "steps": steps})
+    }
+
+    #[test]
+    fn test_diamond_has_no_cycle() {
+        let graph = RecipeGraph::build(&recipe(json!([
+            {"step_id": "s-01", "target": ["ing-0001"], "result": {"name": "batter"}},
+            {"step_id": "s-02", "target": ["@batter[1]"]},
+            {"step_id": "s-03", "target": ["s-01:result[2]"]},
+            {"step_id": "s-04", "target": ["s-02:result", "s-03:result"], "depends_on": ["s-01"]}
+        ])));
+        assert!(graph.cycles().is_empty());
+        assert!(graph.dangling().is_empty());
+        assert_eq!(graph.dependencies("s-04"), ["s-02", "s-03", "s-01"]);
+        assert_eq!(graph.dependents("s-01"), ["s-02", "s-03", "s-04"]);
+        assert_eq!(graph.dependencies("s-01"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_two_and_three_step_cycles() {
+        let graph = RecipeGraph::build(&recipe(json!([
+            {"step_id": "s-01", "target": ["ing-0001"]},
+            {"step_id": "s-02", "target": ["s-03:result"]},
+            {"step_id": "s-03", "target": ["s-02:result"]},
+            {"step_id": "s-04", "target": ["s-06:result"]},
+            {"step_id": "s-05", "target": ["s-04:result", "s-01:result"]},
+            {"step_id": "s-06", "depends_on": ["s-05"]}
+        ])));
+        assert_eq!(graph.cycles(), [vec!["s-02", "s-03"], vec!["s-04", "s-06", "s-05"]]);
+    }
+
+    #[test]
+    fn test_long_chain_does_not_overflow() {
+        let count = 60_000;
+        let steps: Vec<Value> = (1..=count)
+            .map(|i| json!({"step_id": format!("s-{:05}", i), "target": [format!("s-{:05}:result", i + 1)]}))
+            .collect();
+        let graph = RecipeGraph::build(&recipe(Value::from(steps)));
+        assert!(graph.cycles().is_empty());
+        assert_eq!(graph.position("s-60000"), Some(count - 1));
+        assert_eq!(graph.dependencies("s-00001"), ["s-00002"]);
+    }
+
+    #[test]
+    fn test_plan_waves() {
+        let plan = execution_plan(&recipe(json!([
+            {"step_id": "s-01", "action": "heat", "target": [], "params": {"time_minutes": 10}},
+            {"step_id": "s-02", "action": "chop", "target": ["ing-0001"], "params": {"time_minutes": 5}},
+            {"step_id": "s-03", "action": "mix", "target": ["s-02:result"]},
+            {"step_id": "s-04", "action": "bake", "target": ["s-03:result"], "depends_on": ["s-01"]}
+        ])))
+        .unwrap();
+
+        let waves: Vec<Vec<&str>> =
+            plan.waves.iter().map(|wave| wave.iter().map(|e| e.step_id.as_str()).collect()).collect();
+        assert_eq!(waves, [vec!["s-01", "s-02"], vec!["s-03"], vec!["s-04"]]);
+        let order: Vec<&str> = plan.order().map(|e| e.step_id.as_str()).collect();
+        assert_eq!(order, ["s-01", "s-02", "s-03", "s-04"]);
+        assert_eq!(plan.waves[0][0].duration_minutes, Some(10.0));
+        assert_eq!(plan.waves[2][0].action.as_deref(), Some("bake"));
+        assert_eq!(plan.waves[2][0].waits_on, ["s-03", "s-01"]);
+    }
+
+    #[test]
+    fn test_plan_follows_dependencies_not_document_order() {
+        let plan = execution_plan(&recipe(json!([
+            {"step_id": "s-01", "target": ["s-02:result"]},
+            {"step_id": "s-02", "target": ["ing-0001"]}
+        ])))
+        .unwrap();
+        let order: Vec<&str> = plan.order().map(|e| e.step_id.as_str()).collect();
+        assert_eq!(order, ["s-02", "s-01"]);
+    }
+
+    #[test]
+    fn test_plan_of_a_long_reversed_chain() {
+        // Each step waits on the one after it, the worst order for a
+        // relaxation over document order
+        let count = 20_000;
+        let steps: Vec<Value> = (1..=count)
+            .map(|i| match i {
+                i if i == count => json!({"step_id": format!("s-{:05}", i), "target": ["ing-0001"]}),
+                i => json!({"step_id": format!("s-{:05}", i), "target": [format!("s-{:05}:result", i + 1)]}),
+            })
+            .collect();
+        let plan = execution_plan(&recipe(Value::from(steps))).unwrap();
+        assert_eq!(plan.waves.len(), count);
+        assert_eq!(plan.waves[0][0].step_id, "s-20000");
+        assert_eq!(plan.waves[count - 1][0].step_id, "s-00001");
+    }
+
+    #[test]
+    fn test_plan_errors() {
+        let cyclic = recipe(json!([
+            {"step_id": "s-01", "target": ["s-02:result"]},
+            {"step_id": "s-02", "target": ["s-01:result"]}
+        ]));
+        let err = execution_plan(&cyclic).unwrap_err();
+        assert_eq!(err, PlanError::Cycles(vec![vec!["s-01".to_string(), "s-02".to_string()]]));
+        assert_eq!(err.to_string(), "steps wait on each other: s-01 -> s-02 -> s-01");
+
+        let dangling = recipe(json!([{"step_id": "s-01", "target": ["s-07:result"]}]));
+        assert_eq!(
+            execution_plan(&dangling).unwrap_err().to_string(),
+            "references to no step: s-07:result in step s-01"
+        );
+    }
+
+    #[test]
+    fn test_self_reference_and_dangling() {
+        let graph = RecipeGraph::build(&recipe(json!([
+            {"step_id": "s-01", "target": ["s-01:result", "@nothing"], "depends_on": ["s-09"]}
+        ])));
+        assert_eq!(graph.cycles(), [vec!["s-01"]]);
+        let dangling: Vec<&str> = graph.dangling().iter().map(|d| d.target.as_str()).collect();
+        assert_eq!(dangling, ["@nothing", "s-09"]);
+    }
+}
@@ -0,0 +1,224 @@
+// Notes, tips and troubleshooting
+//
+// Steps and the recipe itself may carry `notes`, `tips` and `troubleshooting`,
+// each an array of entries or a single entry. Entries are non-empty strings
+// or localized objects mapping a language tag to a non-empty string. A lone
+// string is what older recipes used for `notes`. They are for people only: the
+// simulator and device-facing exports never read them, while human-facing
+// exports render them as callout boxes after the instruction they belong to.
+
+use crate::{codes, ValidationResult};
+use serde_json::Value;
+
+/// Guidance fields, in the order they are rendered
+pub const FIELDS: &[&str] = &["notes", "tips", "troubleshooting"];
+
+/// Kind of callout box
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutKind {
+    Note,
+    Tip,
+    Troubleshooting,
+}
+
+impl CalloutKind {
+    fn from_field(field: &str) -> Option<CalloutKind> {
+        match field {
+            "notes" => Some(CalloutKind::Note),
+            "tips" => Some(CalloutKind::Tip),
+            "troubleshooting" => Some(CalloutKind::Troubleshooting),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "Note",
+            CalloutKind::Tip => "Tip",
+            CalloutKind::Troubleshooting => "Troubleshooting",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            CalloutKind::Note => "note",
+            CalloutKind::Tip => "tip",
+            CalloutKind::Troubleshooting => "troubleshooting",
+        }
+    }
+}
+
+/// One guidance entry resolved to a single language
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callout {
+    pub kind: CalloutKind,
+    pub text: String,
+}
+
+fn valid_entry(entry: &Value) -> bool {
+    match entry {
+        Value::String(text) => !text.trim().is_empty(),
+        Value::Object(map) => {
+            !map.is_empty() && map.values().all(|v| v.as_str().map(|s| !s.trim().is_empty()).unwrap_or(false))
+        }
+        _ => false,
+    }
+}
+
+fn check_node(node: &Value, prefix: &str, result: &mut ValidationResult) {
+    for field in FIELDS {
+        let value = match node.get(*field) {
+            Some(value) => value,
+            None => continue,
+        };
+        let path = format!("{}{}", prefix, field);
+        match value.as_array() {
+            Some(entries) => {
+                for (i, entry) in entries.iter().enumerate() {
+                    if !valid_entry(entry) {
+                        result.error(codes::BAD_GUIDANCE, &[("field", &format!("{}[{}]", path, i))]);
+                    }
+                }
+            }
+            None if valid_entry(value) => {}
+            None => result.error(codes::BAD_GUIDANCE, &[("field", &path)]),
+        }
+    }
+}
+
+/// Validate the guidance fields of the recipe and of every step
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    check_node(recipe, "", result);
+    for (index, step) in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        check_node(step, &format!("steps[{}].", index), result);
+    }
+}
+
+/// Text of an entry in `locale`, falling back to its language, then English,
+/// then the first translation
+pub(crate) fn localized(entry: &Value, locale: &str) -> Option<String> {
+    match entry {
+        Value::String(text) => Some(text.trim().to_string()),
+        Value::Object(map) => {
+            let language = locale.split('-').next().unwrap_or(locale);
+            [locale, language, "en"]
+                .iter()
+                .find_map(|tag| map.get(*tag))
+                .or_else(|| map.values().next())
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Entries of a guidance field; a lone string or object is one entry
+fn entries(value: Option<&Value>) -> Vec<&Value> {
+    match value {
+        Some(Value::Array(items)) => items.iter().collect(),
+        Some(entry) => vec![entry],
+        None => Vec::new(),
+    }
+}
+
+/// Callouts of a step or recipe, skipping malformed entries
+pub fn callouts(node: &Value, locale: &str) -> Vec<Callout> {
+    let mut out = Vec::new();
+    for field in FIELDS {
+        let kind = match CalloutKind::from_field(field) {
+            Some(kind) => kind,
+            None => continue,
+        };
+        for entry in entries(node.get(*field)) {
+            if let Some(text) = localized(entry, locale).filter(|t| !t.is_empty()) {
+                out.push(Callout { kind, text });
+            }
+        }
+    }
+    out
+}
+
+/// Plain-text callout boxes
+pub fn to_text(callouts: &[Callout]) -> String {
+    let mut out = String::new();
+    for callout in callouts {
+        let line = format!("{}: {}", callout.kind.label(), callout.text);
+        let width = line.chars().count() + 2;
+        out.push_str(&format!("+{}+\n| {} |\n+{}+\n", "-".repeat(width), line, "-".repeat(width)));
+    }
+    out
+}
+
+/// Markdown callouts as block quotes
+pub fn to_markdown(callouts: &[Callout]) -> String {
+    callouts
+        .iter()
+        .map(|c| format!("> **{}:** {}\n", c.kind.label(), c.text.replace('\n', "\n> ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// HTML callouts as `aside` elements
+pub fn to_html(callouts: &[Callout]) -> String {
+    callouts
+        .iter()
+        .map(|c| {
+            format!(
+                "<aside class=\"rcip-callout rcip-{}\"><strong>{}</strong> {}</aside>\n",
+                c.kind.class(),
+                c.kind.label(),
+                html_escape(&c.text)
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_entries() {
+        let recipe = json!({
+            "notes": ["Keeps for two days"],
+            "tips": "Use cold butter",
+            "troubleshooting": 7,
+            "steps": [
+                {"step_id": "s-01", "tips": ["", {"en": "Chill the bowl", "de": "Schüssel kühlen"}, {}, 3]},
+                {"step_id": "s-02", "troubleshooting": [{"en": "Too runny? Add flour", "de": " "}]}
+            ]
+        });
+        let mut result = ValidationResult::new();
+        check(&recipe, &mut result);
+
+        let fields: Vec<&str> = result.issues.iter().map(|i| i.params[0].1.as_str()).collect();
+        assert_eq!(fields, vec!["troubleshooting", "steps[0].tips[0]", "steps[0].tips[2]", "steps[0].tips[3]", "steps[1].troubleshooting[0]"]);
+        assert!(result.issues.iter().all(|i| i.code == codes::BAD_GUIDANCE));
+        assert_eq!(callouts(&recipe, "en")[1].text, "Use cold butter");
+    }
+
+    #[test]
+    fn test_callouts_are_localized_and_rendered() {
+        let step = json!({
+            "step_id": "s-01",
+            "troubleshooting": [{"en": "Too runny? Add flour", "de": "Zu flüssig? Mehl zugeben"}],
+            "tips": ["Use <cold> butter"],
+            "notes": [{"fr": "Note"}]
+        });
+        let found = callouts(&step, "de-AT");
+        let texts: Vec<&str> = found.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["Note", "Use <cold> butter", "Zu flüssig? Mehl zugeben"]);
+
+        assert_eq!(to_markdown(&found[1..2]), "> **Tip:** Use <cold> butter\n");
+        assert_eq!(
+            to_html(&found[1..2]),
+            "<aside class=\"rcip-callout rcip-tip\"><strong>Tip</strong> Use &lt;cold&gt; butter</aside>\n"
+        );
+        assert_eq!(to_text(&found[..1]), "+------------+\n| Note: Note |\n+------------+\n");
+    }
+}
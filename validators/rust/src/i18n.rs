@@ -0,0 +1,102 @@
+// Message catalogs
+//
+// Issue messages are rendered from per-locale templates keyed by rule code,
+// with `{name}` placeholders filled from the issue parameters. Catalogs are
+// embedded JSON files under `assets/messages`. A missing locale or template
+// falls back to English, and a code without any template renders as the code
+// and its parameters, so rendering never fails. Rule-pack codes have no
+// template and bring their own `message` parameter instead.
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// Locales with a bundled catalog
+pub const LOCALES: &[&str] = &["en", "de"];
+
+const CATALOG_SOURCES: &[(&str, &str)] = &[
+    ("en", include_str!("../assets/messages/en.json")),
+    ("de", include_str!("../assets/messages/de.json")),
+];
+
+lazy_static! {
+    static ref CATALOGS: HashMap<&'static str, HashMap<String, String>> = CATALOG_SOURCES
+        .iter()
+        .map(|(locale, source)| {
+            let catalog = serde_json::from_str(source).expect("bundled message catalog is valid JSON");
+            (*locale, catalog)
+        })
+        .collect();
+}
+
+/// Bundled catalog for a locale tag, trying `de-AT` then `de`
+fn catalog(locale: &str) -> Option<&'static HashMap<String, String>> {
+    let tag = locale.to_lowercase().replace('_', "-");
+    CATALOGS.get(tag.as_str()).or_else(|| {
+        let primary = tag.split('-').next().unwrap_or("");
+        CATALOGS.get(primary)
+    })
+}
+
+/// Template for a code in the given locale, falling back to English
+pub fn template(locale: &str, code: &str) -> Option<&'static str> {
+    catalog(locale)
+        .and_then(|c| c.get(code))
+        .or_else(|| CATALOGS["en"].get(code))
+        .map(String::as_str)
+}
+
+/// Codes with a template in a bundled catalog itself, without fallback
+pub fn catalog_codes(locale: &str) -> Vec<&'static str> {
+    let mut codes: Vec<&str> = catalog(locale).into_iter().flat_map(|c| c.keys()).map(String::as_str).collect();
+    codes.sort();
+    codes
+}
+
+/// Render a message for a code with named parameters
+pub fn render(locale: &str, code: &str, params: &[(&str, String)]) -> String {
+    match template(locale, code) {
+        Some(template) => params.iter().fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        }),
+        None => {
+            if let Some((_, message)) = params.iter().find(|(name, _)| *name == "message") {
+                return format!("{}: {}", code, message);
+            }
+            let listed: Vec<String> = params.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+            format!("{} ({})", code, listed.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes;
+
+    #[test]
+    fn test_every_code_has_an_english_template() {
+        for code in codes::ALL {
+            assert!(CATALOGS["en"].contains_key(*code), "no English template for {}", code);
+        }
+    }
+
+    #[test]
+    fn test_no_orphaned_templates() {
+        for (locale, catalog) in CATALOGS.iter() {
+            for code in catalog.keys() {
+                assert!(codes::ALL.contains(&code.as_str()), "{} template for unknown code {}", locale, code);
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_with_fallbacks() {
+        let params = [("index", "2".to_string()), ("id", "bad".to_string())];
+        assert_eq!(render("en", codes::BAD_STEP_ID, &params), "Step 2: Invalid ID format: bad");
+        assert_eq!(render("de", codes::BAD_STEP_ID, &params), "Schritt 2: Ungültiges ID-Format: bad");
+        assert_eq!(render("de_AT", codes::BAD_STEP_ID, &params), "Schritt 2: Ungültiges ID-Format: bad");
+        assert_eq!(render("ja", codes::BAD_STEP_ID, &params), "Step 2: Invalid ID format: bad");
+        assert_eq!(render("de", "RCIP-X-UNKNOWN", &params), "RCIP-X-UNKNOWN (index=2, id=bad)");
+        assert_eq!(render("de", "acme/NO-PORK", &[("message", "pork found".to_string())]), "acme/NO-PORK: pork found");
+    }
+}
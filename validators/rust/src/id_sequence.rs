@@ -0,0 +1,312 @@
+// Id sequencing
+//
+// Tooling expects ingredient ids `ing-0001..ing-NNNN` and step ids
+// `s-01..s-NN` numbered in listing order with one padding width per family.
+// `check` reports the ids that break either expectation; `renumber_ids`
+// restores them. Renumbering builds the complete old-to-new mapping before
+// touching the recipe and refuses ambiguous input, so references are either
+// all rewritten or the recipe is left alone.
+//
+// Deterministic imports append a content hash to the number after an `h`,
+// `ing-0003he41a0c` (see `ids`). The number alone counts for the sequence
+// and padding checks, and renumbering keeps the hash, so such ids are in
+// sequence as generated and renumbering leaves them alone.
+//
+// References rewritten: step `target` and `depends_on` entries (`ing-…`,
+// `s-…`, `s-…:result`), sensor `target`, image `stage` and `step_ref`,
+// per-step overrides keyed by step id in device-profile `params`, and
+// substitutes whose `id` names another listed ingredient.
+
+use crate::{codes, ValidationResult};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// (array, id field, prefix, minimum padding width)
+const FAMILIES: &[(&str, &str, &str, usize)] = &[("ingredients", "id", "ing-", 4), ("steps", "step_id", "s-", 2)];
+
+/// Separates an id's number from the content hash after it
+const CONTENT_TAG: char = 'h';
+
+/// Errors renumbering ids
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenumberError {
+    /// The same id is declared twice, so references to it are ambiguous
+    Duplicate(String),
+}
+
+impl fmt::Display for RenumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenumberError::Duplicate(id) => write!(f, "id '{}' is declared more than once", id),
+        }
+    }
+}
+
+impl std::error::Error for RenumberError {}
+
+fn ids<'a>(recipe: &'a Value, array: &str, field: &str) -> Vec<&'a str> {
+    recipe
+        .get(array)
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|item| item.get(field).and_then(|v| v.as_str()))
+        .collect()
+}
+
+/// The part of an id after its prefix, split into the number and the
+/// content hash, if it has one
+fn split_tag(rest: &str) -> (&str, Option<&str>) {
+    match rest.split_once(CONTENT_TAG) {
+        Some((digits, hash)) if !hash.is_empty() && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) => {
+            (digits, Some(hash))
+        }
+        _ => (rest, None),
+    }
+}
+
+/// Numeric suffix of an id and its digit count
+fn number(id: &str, prefix: &str) -> Option<(usize, usize)> {
+    let (digits, _) = split_tag(id.strip_prefix(prefix)?);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().map(|n| (n, digits.len()))
+}
+
+/// Id of the item at `index` of `count` in `array`, padded as renumbering
+/// pads it, with `hash` appended when given
+pub(crate) fn positional_id(array: &str, index: usize, count: usize, hash: Option<&str>) -> String {
+    let (_, _, prefix, min_width) = FAMILIES.iter().find(|family| family.0 == array).expect("an id family");
+    let width = (*min_width).max(count.to_string().len());
+    let tag = hash.map(|hash| format!("{}{}", CONTENT_TAG, hash)).unwrap_or_default();
+    format!("{}{:0width$}{}", prefix, index + 1, tag, width = width)
+}
+
+/// Report ids numbered out of sequence or padded inconsistently
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    for (array, field, prefix, _) in FAMILIES {
+        let ids = ids(recipe, array, field);
+        let family = format!("{}[].{}", array, field);
+
+        let out_of_sequence: Vec<String> = ids
+            .iter()
+            .enumerate()
+            .filter(|(i, id)| number(id, prefix).map(|(n, _)| n != i + 1).unwrap_or(true))
+            .map(|(i, id)| format!("{} at {}", id, i + 1))
+            .collect();
+        if !out_of_sequence.is_empty() {
+            result.warning(
+                codes::ID_SEQUENCE,
+                &[("family", &family), ("count", &ids.len()), ("ids", &out_of_sequence.join(", "))],
+            );
+        }
+
+        // The most common width wins; ties go to the wider one
+        let mut widths: BTreeMap<usize, usize> = BTreeMap::new();
+        for (_, width) in ids.iter().filter_map(|id| number(id, prefix)) {
+            *widths.entry(width).or_default() += 1;
+        }
+        if widths.len() > 1 {
+            let width = widths.iter().max_by_key(|(w, count)| (**count, **w)).map(|(w, _)| *w).unwrap_or(0);
+            let offenders: Vec<&str> = ids
+                .iter()
+                .filter(|id| number(id, prefix).map(|(_, w)| w != width).unwrap_or(false))
+                .copied()
+                .collect();
+            result.warning(codes::ID_PADDING, &[("family", &family), ("width", &width), ("ids", &offenders.join(", "))]);
+        }
+    }
+}
+
+/// New id for a reference like `ing-3`, `s-2` or `s-2:result`
+fn rewrite(reference: &str, mapping: &HashMap<String, String>) -> Option<String> {
+    let (id, rest) = match reference.find(':') {
+        Some(i) => reference.split_at(i),
+        None => (reference, ""),
+    };
+    mapping.get(id).map(|new| format!("{}{}", new, rest))
+}
+
+fn rewrite_field(node: &mut Value, field: &str, mapping: &HashMap<String, String>) {
+    if let Some(new) = node.get(field).and_then(|v| v.as_str()).and_then(|r| rewrite(r, mapping)) {
+        node[field] = Value::String(new);
+    }
+}
+
+fn items_mut<'a>(recipe: &'a mut Value, array: &str) -> impl Iterator<Item = &'a mut Value> {
+    recipe.get_mut(array).and_then(|v| v.as_array_mut()).into_iter().flatten()
+}
+
+/// Renumber ingredients and steps in listing order, rewriting every reference
+///
+/// Returns the ids that changed, old to new.
+pub fn renumber_ids(recipe: &mut Value) -> Result<BTreeMap<String, String>, RenumberError> {
+    let mut mapping: HashMap<String, String> = HashMap::new();
+    for (array, field, prefix, _) in FAMILIES {
+        let ids = ids(recipe, array, field);
+        for (i, id) in ids.iter().enumerate() {
+            let hash = id.strip_prefix(prefix).and_then(|rest| split_tag(rest).1);
+            let new = positional_id(array, i, ids.len(), hash);
+            if mapping.insert(id.to_string(), new).is_some() {
+                return Err(RenumberError::Duplicate(id.to_string()));
+            }
+        }
+    }
+
+    rename_ids(recipe, &mapping);
+    Ok(mapping.into_iter().filter(|(old, new)| old != new).collect())
+}
+
+/// Rename ingredient and step ids, old to new, along with every reference
+/// to them
+pub(crate) fn rename_ids(recipe: &mut Value, mapping: &HashMap<String, String>) {
+    for ingredient in items_mut(recipe, "ingredients") {
+        rewrite_field(ingredient, "id", mapping);
+        for substitute in items_mut(ingredient, "substitutes") {
+            rewrite_field(substitute, "id", mapping);
+        }
+    }
+
+    for step in items_mut(recipe, "steps") {
+        rewrite_field(step, "step_id", mapping);
+        for field in ["target", "depends_on"] {
+            for reference in step.get_mut(field).and_then(|v| v.as_array_mut()).into_iter().flatten() {
+                if let Some(new) = reference.as_str().and_then(|r| rewrite(r, mapping)) {
+                    *reference = Value::String(new);
+                }
+            }
+        }
+    }
+
+    for sensor in items_mut(recipe, "sensors") {
+        rewrite_field(sensor, "target", mapping);
+    }
+
+    for image in items_mut(recipe, "images") {
+        rewrite_field(image, "stage", mapping);
+        rewrite_field(image, "step_ref", mapping);
+    }
+
+    for profile in items_mut(recipe, "device_profiles") {
+        if let Some(params) = profile.get_mut("params").and_then(|v| v.as_object_mut()) {
+            let renamed: Vec<(String, String)> = params
+                .keys()
+                .filter_map(|key| rewrite(key, mapping).map(|new| (key.clone(), new)))
+                .collect();
+            let mut moved = Vec::new();
+            for (old, new) in renamed {
+                if let Some(value) = params.remove(&old) {
+                    moved.push((new, value));
+                }
+            }
+            params.extend(moved);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-3", "name": "flour", "substitutes": [{"id": "ing-0007", "name": "the rye flour above"}]},
+                {"id": "ing-0007", "name": "rye flour", "substitutes": [{"id": "sub-0001", "name": "spelt"}]},
+                {"id": "ing-0008", "name": "water"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "target": ["ing-3", "ing-0007"], "device_profile_ref": "mixer-01"},
+                {"step_id": "s-5", "action": "mix", "target": ["s-01:result", "ing-0008"]},
+                {"step_id": "s-07", "action": "bake", "target": ["s-5"], "depends_on": ["s-01"], "device_profile_ref": "oven-01"}
+            ],
+            "sensors": [
+                {"id": "sensor-01", "type": "temperature", "target": "s-07:result"},
+                {"id": "sensor-02", "type": "color", "target": "crust"}
+            ],
+            "images": [{"id": "img-01", "stage": "s-5"}, {"id": "img-02", "stage": "final", "step_ref": "s-07"}],
+            "device_profiles": [
+                {"id": "mixer-01", "type": "mixer", "params": {"speed": 2, "s-01": {"speed": 4}}},
+                {"id": "oven-01", "type": "oven", "params": {"s-07": {"temperature_c": 250}}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_check_reports_offenders() {
+        let mut result = ValidationResult::new();
+        check(&recipe(), &mut result);
+
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::ID_SEQUENCE, codes::ID_PADDING, codes::ID_SEQUENCE, codes::ID_PADDING]);
+        assert_eq!(
+            result.warnings[0],
+            "ingredients[].id ids are not numbered 1 to 3 in listing order: ing-3 at 1, ing-0007 at 2, ing-0008 at 3"
+        );
+        assert_eq!(result.warnings[1], "ingredients[].id ids mix padding widths; expected 4 digits: ing-3");
+        assert_eq!(result.warnings[3], "steps[].step_id ids mix padding widths; expected 2 digits: s-5");
+
+        let mut clean = recipe();
+        renumber_ids(&mut clean).unwrap();
+        let mut result = ValidationResult::new();
+        check(&clean, &mut result);
+        assert!(result.issues.is_empty());
+    }
+
+    #[test]
+    fn test_renumber_rewrites_every_reference() {
+        let mut recipe = recipe();
+        let changed = renumber_ids(&mut recipe).unwrap();
+        assert_eq!(changed.len(), 5);
+        assert_eq!(changed["ing-0007"], "ing-0002");
+
+        assert_eq!(recipe["ingredients"][0]["id"], "ing-0001");
+        assert_eq!(recipe["ingredients"][0]["substitutes"][0]["id"], "ing-0002");
+        assert_eq!(recipe["ingredients"][1]["substitutes"][0]["id"], "sub-0001");
+        assert_eq!(recipe["steps"][0]["target"], json!(["ing-0001", "ing-0002"]));
+        assert_eq!(recipe["steps"][1]["step_id"], "s-02");
+        assert_eq!(recipe["steps"][1]["target"], json!(["s-01:result", "ing-0003"]));
+        assert_eq!(recipe["steps"][2]["step_id"], "s-03");
+        assert_eq!(recipe["steps"][2]["target"], json!(["s-02"]));
+        assert_eq!(recipe["steps"][2]["depends_on"], json!(["s-01"]));
+        assert_eq!(recipe["steps"][2]["device_profile_ref"], "oven-01");
+        assert_eq!(recipe["sensors"][0]["target"], "s-03:result");
+        assert_eq!(recipe["sensors"][1]["target"], "crust");
+        assert_eq!(recipe["images"][0]["stage"], "s-02");
+        assert_eq!(recipe["images"][1]["stage"], "final");
+        assert_eq!(recipe["images"][1]["step_ref"], "s-03");
+        assert_eq!(recipe["device_profiles"][0]["params"], json!({"speed": 2, "s-01": {"speed": 4}}));
+        assert_eq!(recipe["device_profiles"][1]["params"], json!({"s-03": {"temperature_c": 250}}));
+    }
+
+    #[test]
+    fn test_renumber_keeps_content_hashes() {
+        let mut recipe = json!({
+            "ingredients": [{"id": "ing-0002h0a1b2c"}, {"id": "ing-0001hffee00"}, {"id": "ing-3hx"}],
+            "steps": [{"step_id": "s-01h123abc", "target": ["ing-0001hffee00", "ing-0002h0a1b2c"]}]
+        });
+        let mut result = ValidationResult::new();
+        check(&recipe, &mut result);
+        assert_eq!(result.warnings[0], "ingredients[].id ids are not numbered 1 to 3 in listing order: ing-0002h0a1b2c at 1, ing-0001hffee00 at 2, ing-3hx at 3");
+        assert_eq!(result.issues.len(), 1);
+
+        let changed = renumber_ids(&mut recipe).unwrap();
+        assert_eq!(changed.len(), 3);
+        assert_eq!(recipe["ingredients"], json!([{"id": "ing-0001h0a1b2c"}, {"id": "ing-0002hffee00"}, {"id": "ing-0003"}]));
+        assert_eq!(recipe["steps"][0]["target"], json!(["ing-0002hffee00", "ing-0001h0a1b2c"]));
+        assert_eq!(recipe["steps"][0]["step_id"], "s-01h123abc");
+    }
+
+    #[test]
+    fn test_duplicates_leave_recipe_untouched() {
+        let mut recipe = recipe();
+        recipe["steps"][2]["step_id"] = json!("s-5");
+        let before = recipe.clone();
+
+        assert_eq!(renumber_ids(&mut recipe), Err(RenumberError::Duplicate("s-5".to_string())));
+        assert_eq!(recipe, before);
+    }
+}
@@ -0,0 +1,288 @@
+// Id strategies for generated documents
+//
+// Importers and the corpus generator give every document a recipe id and
+// every ingredient and step an id of its own. `IdStrategy::Random` draws the
+// recipe id fresh and numbers ingredients and steps by position.
+// `IdStrategy::Deterministic` derives everything from content instead, so
+// importing the same source twice produces byte-identical documents and an
+// id can be compared across pipeline runs:
+//
+// - the recipe id is a version 5 style UUID: SHA-256 over the namespace and
+//   the source identity (its URL, or the hash of the source itself), cut to
+//   128 bits with the version and variant bits set
+// - ingredient and step ids keep their position and append `h` and a short
+//   hash of their content, `ing-0003he41a0c` and `s-02hb17f9d`, so reordering
+//   or editing an item changes its id while untouched items keep theirs. The
+//   position is padded as `id_sequence` pads it, so the ids pass its sequence
+//   check and `renumber_ids` leaves them as they are
+//
+// References in step targets (`ing-0003`, `s-02:result`) follow the renamed
+// items.
+//
+// `generate_recipe_id` gives a fresh `rcip-` id for tools that create
+// documents themselves, always in lowercase hex as the id format requires.
+// With the `uuid` feature it is a UUIDv4 from the system's random source;
+// without it the same layout is drawn from a clock-seeded generator.
+// `generate_ingredient_id` and `generate_step_id` number items by listing
+// position, and the `is_valid_*` predicates apply the validator's own id
+// patterns.
+
+#[cfg(not(feature = "uuid"))]
+use crate::fixtures::{random_recipe_id, SplitMix64};
+use crate::id_sequence::positional_id;
+use crate::provenance::sha256_hex;
+use crate::{INGREDIENT_ID_REGEX, RECIPE_ID_REGEX, STEP_ID_REGEX};
+#[cfg(not(feature = "uuid"))]
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Hex digits of content hash in ingredient and step ids
+const ITEM_HASH_LEN: usize = 6;
+
+/// How generated documents get their ids
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum IdStrategy {
+    /// A fresh recipe id, positional ingredient and step ids
+    #[default]
+    Random,
+    /// Ids derived from the namespace, the source identity and content
+    Deterministic { namespace: String },
+}
+
+impl IdStrategy {
+    pub fn deterministic(namespace: impl Into<String>) -> Self {
+        IdStrategy::Deterministic { namespace: namespace.into() }
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self, IdStrategy::Deterministic { .. })
+    }
+
+    /// Recipe id for a source with the given identity
+    pub fn recipe_id(&self, identity: &str) -> String {
+        match self {
+            IdStrategy::Random => generate_recipe_id(),
+            IdStrategy::Deterministic { namespace } => name_based_id(namespace, identity),
+        }
+    }
+
+    /// Set the recipe id of a document and, when deterministic, re-id its
+    /// ingredients and steps
+    pub fn assign(&self, recipe: &mut Value, identity: &str) {
+        recipe["id"] = Value::String(self.recipe_id(identity));
+        if self.is_deterministic() {
+            assign_item_ids(recipe);
+        }
+    }
+}
+
+/// A fresh recipe id, `rcip-` and a version 4 UUID in lowercase
+#[cfg(feature = "uuid")]
+pub fn generate_recipe_id() -> String {
+    let mut buffer = uuid::Uuid::encode_buffer();
+    format!("rcip-{}", uuid::Uuid::new_v4().hyphenated().encode_lower(&mut buffer))
+}
+
+/// A fresh recipe id, `rcip-` and a version 4 UUID in lowercase
+#[cfg(not(feature = "uuid"))]
+pub fn generate_recipe_id() -> String {
+    let now = Utc::now();
+    let seed = (now.timestamp() as u64).wrapping_mul(1_000_000_000).wrapping_add(now.timestamp_subsec_nanos() as u64);
+    random_recipe_id(&mut SplitMix64::new(seed))
+}
+
+/// Id of the ingredient at a listing position, counting from 0: `ing-0001`
+pub fn generate_ingredient_id(index: usize) -> String {
+    format!("ing-{:04}", index + 1)
+}
+
+/// Id of the step at a listing position, counting from 0: `s-01`
+pub fn generate_step_id(index: usize) -> String {
+    format!("s-{:02}", index + 1)
+}
+
+/// Whether `id` has the recipe id format validation requires
+pub fn is_valid_recipe_id(id: &str) -> bool {
+    RECIPE_ID_REGEX.is_match(id)
+}
+
+/// Whether `id` has the ingredient id format validation requires
+pub fn is_valid_ingredient_id(id: &str) -> bool {
+    INGREDIENT_ID_REGEX.is_match(id)
+}
+
+/// Whether `id` has the step id format validation requires
+pub fn is_valid_step_id(id: &str) -> bool {
+    STEP_ID_REGEX.is_match(id)
+}
+
+/// Identity of a source without a URL: the hash of its JSON
+pub fn content_identity(source: &Value) -> String {
+    format!("sha256:{}", sha256_hex(source.to_string().as_bytes()))
+}
+
+fn name_based_id(namespace: &str, identity: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(namespace.as_bytes());
+    hasher.update([0]);
+    hasher.update(identity.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&hasher.finalize()[..16]);
+    bytes[6] = (bytes[6] & 0x0f) | 0x50;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("rcip-{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Short hash of an item with its id field left out
+fn item_hash(item: &Value, id_key: &str) -> String {
+    let mut content = item.clone();
+    if let Some(fields) = content.as_object_mut() {
+        fields.remove(id_key);
+    }
+    sha256_hex(content.to_string().as_bytes())[..ITEM_HASH_LEN].to_string()
+}
+
+fn assign_item_ids(recipe: &mut Value) {
+    let mut renamed: HashMap<String, String> = HashMap::new();
+
+    if let Some(ingredients) = recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()) {
+        let count = ingredients.len();
+        for (i, ingredient) in ingredients.iter_mut().enumerate() {
+            let id = positional_id("ingredients", i, count, Some(&item_hash(ingredient, "id")));
+            if let Some(old) = ingredient.get("id").and_then(|v| v.as_str()) {
+                renamed.insert(old.to_string(), id.clone());
+            }
+            ingredient["id"] = Value::String(id);
+        }
+    }
+
+    if let Some(steps) = recipe.get_mut("steps").and_then(|v| v.as_array_mut()) {
+        // Targets first, so a step's hash covers the ids it refers to
+        for step in steps.iter_mut() {
+            rename_targets(step, &renamed);
+        }
+        let mut steps_renamed = HashMap::new();
+        let count = steps.len();
+        for (i, step) in steps.iter_mut().enumerate() {
+            let id = positional_id("steps", i, count, Some(&item_hash(step, "step_id")));
+            if let Some(old) = step.get("step_id").and_then(|v| v.as_str()) {
+                steps_renamed.insert(old.to_string(), id.clone());
+            }
+            step["step_id"] = Value::String(id);
+        }
+        for step in steps.iter_mut() {
+            rename_targets(step, &steps_renamed);
+        }
+    }
+}
+
+/// Rewrite `id` and `id:result` targets that were renamed
+fn rename_targets(step: &mut Value, renamed: &HashMap<String, String>) {
+    let targets = match step.get_mut("target").and_then(|v| v.as_array_mut()) {
+        Some(targets) => targets,
+        None => return,
+    };
+    for target in targets {
+        let text = match target.as_str() {
+            Some(text) => text,
+            None => continue,
+        };
+        let (id, suffix) = match text.split_once(':') {
+            Some((id, suffix)) => (id, Some(suffix)),
+            None => (text, None),
+        };
+        if let Some(new) = renamed.get(id) {
+            *target = Value::String(match suffix {
+                Some(suffix) => format!("{}:{}", new, suffix),
+                None => new.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "id": "rcip-placeholder",
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour"},
+                {"id": "ing-0002", "name": "water"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "target": ["ing-0001", "ing-0002"]},
+                {"step_id": "s-02", "action": "bake", "target": ["s-01:result"]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_generated_ids_are_valid() {
+        let id = generate_recipe_id();
+        assert!(is_valid_recipe_id(&id), "{}", id);
+        assert_eq!(id, id.to_lowercase());
+        assert_ne!(id, generate_recipe_id());
+        assert!(!is_valid_recipe_id(&id.to_uppercase().replace("RCIP", "rcip")));
+        assert!(!is_valid_recipe_id("rcip-123"));
+
+        assert_eq!((generate_ingredient_id(0), generate_step_id(11)), ("ing-0001".to_string(), "s-12".to_string()));
+        assert!(is_valid_ingredient_id(&generate_ingredient_id(41)) && is_valid_step_id(&generate_step_id(0)));
+        assert!(!is_valid_ingredient_id("ingredient-1") && !is_valid_step_id("step-1") && !is_valid_step_id("s-"));
+    }
+
+    #[test]
+    fn test_recipe_id_is_name_based() {
+        let ids = IdStrategy::deterministic("https://example.com/recipes");
+        let id = ids.recipe_id("https://example.com/soup");
+        assert_eq!(id, ids.recipe_id("https://example.com/soup"));
+        assert_ne!(id, ids.recipe_id("https://example.com/stew"));
+        assert_ne!(id, IdStrategy::deterministic("other").recipe_id("https://example.com/soup"));
+
+        let pattern = regex::Regex::new(r"^rcip-[0-9a-f]{8}-[0-9a-f]{4}-5[0-9a-f]{3}-[89ab][0-9a-f]{3}-[0-9a-f]{12}$").unwrap();
+        assert!(pattern.is_match(&id), "{}", id);
+    }
+
+    #[test]
+    fn test_item_ids_follow_content_and_references() {
+        let ids = IdStrategy::deterministic("ns");
+        let mut first = recipe();
+        ids.assign(&mut first, "source");
+
+        let flour = first["ingredients"][0]["id"].as_str().unwrap().to_string();
+        let mix = first["steps"][0]["step_id"].as_str().unwrap().to_string();
+        assert!(flour.starts_with("ing-0001h") && flour.len() == 9 + ITEM_HASH_LEN);
+        assert!(mix.starts_with("s-01h") && mix.len() == 5 + ITEM_HASH_LEN);
+        assert_eq!(first["steps"][0]["target"][0], json!(flour));
+        assert_eq!(first["steps"][1]["target"][0], json!(format!("{}:result", mix)));
+
+        let mut edited = recipe();
+        edited["ingredients"][1]["name"] = json!("milk");
+        ids.assign(&mut edited, "source");
+        assert_eq!(edited["ingredients"][0]["id"], first["ingredients"][0]["id"]);
+        assert_ne!(edited["ingredients"][1]["id"], first["ingredients"][1]["id"]);
+
+        // In sequence for the strict checks, and kept by renumbering
+        let mut result = crate::ValidationResult::new();
+        crate::id_sequence::check(&first, &mut result);
+        assert!(result.issues.is_empty(), "{:?}", result.warnings);
+        let before = first.clone();
+        assert!(crate::id_sequence::renumber_ids(&mut first).unwrap().is_empty());
+        assert_eq!(first, before);
+    }
+
+    #[test]
+    fn test_random_keeps_item_ids() {
+        let mut recipe = recipe();
+        IdStrategy::Random.assign(&mut recipe, "source");
+        assert_ne!(recipe["id"], "rcip-placeholder");
+        assert_eq!(recipe["ingredients"][0]["id"], "ing-0001");
+        assert_eq!(recipe["steps"][1]["target"][0], "s-01:result");
+    }
+}
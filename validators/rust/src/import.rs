@@ -0,0 +1,737 @@
+// Import from recipe manager exports
+//
+// Paprika 3 and Mealie export recipes as JSON in their own shapes. The
+// converters map what RCIP can represent (names, ingredient lines, directions,
+// photos, categories, servings and times) into a new document with generated
+// ids, and list everything they could not carry over in an `ImportReport`.
+//
+// Ingredient lines are free text in both apps, so their amounts are read by
+// the `amounts` parser and the rest becomes name and notes; actions are guessed by `infer` from each direction
+// paragraph, falling back to `prepare`. Allergens cannot be derived
+// from free text and are left empty.
+//
+// `from_paprika` and `from_mealie` derive the recipe id from the export's own
+// uid or name. The `_with` variants take an `IdStrategy` instead; with a
+// deterministic strategy the source URL (or the hash of the entry) identifies
+// the recipe, and a missing creation date becomes the Unix epoch rather than
+// the time of the import, so repeated imports are byte-identical.
+
+use crate::amounts::leading_amount;
+use crate::fixtures::{random_recipe_id, SplitMix64};
+use crate::ids::{content_identity, IdStrategy};
+use crate::precision::{self, number_value, round_half_even, PrecisionPolicy};
+use crate::infer;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+
+lazy_static! {
+    static ref STEP_NUMBER_REGEX: Regex =
+        Regex::new(r"(?i)^\s*(step\s*)?\d+\s*[.):]\s*").unwrap();
+    static ref DURATION_PART_REGEX: Regex =
+        Regex::new(r"(?i)(\d+(?:\.\d+)?)\s*(days?|d|hours?|hrs?|h|minutes?|mins?|m|seconds?|secs?|s)\b").unwrap();
+    static ref ISO_DURATION_REGEX: Regex =
+        Regex::new(r"^P(?:(\d+(?:\.\d+)?)D)?(?:T(?:(\d+(?:\.\d+)?)H)?(?:(\d+(?:\.\d+)?)M)?(?:(\d+(?:\.\d+)?)S)?)?$").unwrap();
+}
+
+const UNIT_ALIASES: &[(&str, &[&str])] = &[
+    ("mg", &["mg", "milligram", "milligrams"]),
+    ("g", &["g", "gr", "gram", "grams", "gramme", "grammes"]),
+    ("kg", &["kg", "kilo", "kilos", "kilogram", "kilograms"]),
+    ("ml", &["ml", "milliliter", "milliliters", "millilitre", "millilitres"]),
+    ("l", &["l", "liter", "liters", "litre", "litres"]),
+    ("tsp", &["tsp", "tsps", "teaspoon", "teaspoons"]),
+    ("tbsp", &["tbsp", "tbsps", "tbs", "tablespoon", "tablespoons"]),
+    ("cup", &["cup", "cups"]),
+    ("fl-oz", &["floz", "fl-oz"]),
+    ("pt", &["pt", "pint", "pints"]),
+    ("qt", &["qt", "quart", "quarts"]),
+    ("gal", &["gal", "gallon", "gallons"]),
+    ("oz", &["oz", "ounce", "ounces"]),
+    ("lb", &["lb", "lbs", "pound", "pounds"]),
+    ("pcs", &["pc", "pcs", "piece", "pieces", "clove", "cloves", "whole"]),
+    ("dozen", &["dozen"]),
+    ("pinch", &["pinch", "pinches"]),
+    ("dash", &["dash", "dashes"]),
+    ("handful", &["handful", "handfuls"]),
+];
+
+/// What an importer could not carry over into the RCIP document
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub name: String,
+    pub unconverted: Vec<String>,
+}
+
+impl ImportReport {
+    fn note(&mut self, message: impl Into<String>) {
+        self.unconverted.push(message.into());
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.unconverted.is_empty() {
+            return writeln!(f, "{}: fully converted", self.name);
+        }
+        writeln!(f, "{}: {} item(s) not converted", self.name, self.unconverted.len())?;
+        for item in &self.unconverted {
+            writeln!(f, "  - {}", item)?;
+        }
+        Ok(())
+    }
+}
+
+/// An ingredient line split into amount, unit and name
+#[derive(Debug, Clone, PartialEq)]
+struct IngredientLine {
+    amount_text: String,
+    value: Option<f64>,
+    unit: Option<&'static str>,
+    name: String,
+    notes: Option<String>,
+}
+
+/// The unit a spelling such as "grams" or "Tbsp." stands for
+pub(crate) fn unit_alias(token: &str) -> Option<&'static str> {
+    let token = token.trim_end_matches('.').to_lowercase();
+    UNIT_ALIASES
+        .iter()
+        .find(|(_, aliases)| aliases.contains(&token.as_str()))
+        .map(|(unit, _)| *unit)
+}
+
+/// Split an ingredient line into its amount and the name and notes after
+/// it. A range keeps its lower end; an amount that needs a guess ("2 or 3")
+/// is no amount
+fn parse_ingredient_line(line: &str) -> IngredientLine {
+    let line = line.trim();
+    let (amount_text, value, unit, rest) = match leading_amount(line) {
+        Some(leading) => (
+            line[..leading.amount_end].to_string(),
+            (leading.quantity_end > 0).then_some(leading.amount.value),
+            leading.amount.unit.map(|unit| unit.name()),
+            leading.amount.descriptor,
+        ),
+        None => (String::new(), None, None, line.to_string()),
+    };
+
+    let (name, notes) = match rest.split_once(',') {
+        Some((name, notes)) => (name.trim().to_string(), Some(notes.trim().to_string()).filter(|n| !n.is_empty())),
+        None => (rest.trim().to_string(), None),
+    };
+
+    IngredientLine { amount_text, value, unit, name, notes }
+}
+
+fn ingredient_from_line(line: &str, index: usize, report: &mut ImportReport) -> Value {
+    let parsed = parse_ingredient_line(line);
+    let lower = line.to_lowercase();
+
+    let (value, unit, human_amount) = match (parsed.value, parsed.unit) {
+        (_, Some("to-taste")) => (0.0, "to-taste", "to taste".to_string()),
+        (Some(v), Some(u)) => (v, u, parsed.amount_text.clone()),
+        (Some(v), None) => (v, "pcs", parsed.amount_text.clone()),
+        (None, Some(u)) => (1.0, u, parsed.amount_text.clone()),
+        (None, None) => {
+            if !lower.contains("to taste") {
+                report.note(format!("ingredient '{}': no amount found, imported as to-taste", line));
+            }
+            (0.0, "to-taste", "to taste".to_string())
+        }
+    };
+
+    let value = if PrecisionPolicy::default().is_count_unit(unit) && value.fract() != 0.0 {
+        report.note(format!("ingredient '{}': {} pieces rounded up to {}", line, value, value.ceil()));
+        value.ceil()
+    } else {
+        round_half_even(value, 2)
+    };
+
+    let mut name = parsed.name.replace("to taste", "").trim().to_string();
+    if name.is_empty() {
+        name = line.trim().to_string();
+    }
+
+    let mut ingredient = json!({
+        "id": format!("ing-{:04}", index + 1),
+        "name": name,
+        "human_amount": human_amount,
+        "machine_amount": {"value": number_value(value), "unit": unit},
+        "allergens": []
+    });
+    if let Some(notes) = parsed.notes {
+        ingredient["notes"] = json!(notes);
+    }
+    ingredient
+}
+
+/// Guess the cooking action of a direction paragraph from its first cooking verb
+fn guess_action(text: &str) -> &'static str {
+    infer::infer_action(text).map(|inference| inference.action).unwrap_or("prepare")
+}
+
+fn steps_from_paragraphs<'a>(paragraphs: impl Iterator<Item = &'a str>) -> Vec<Value> {
+    paragraphs
+        .map(|p| STEP_NUMBER_REGEX.replace(p.trim(), "").trim().to_string())
+        .filter(|p| !p.is_empty())
+        .enumerate()
+        .map(|(i, text)| {
+            json!({
+                "step_id": format!("s-{:02}", i + 1),
+                "human_text": text,
+                "action": guess_action(&text)
+            })
+        })
+        .collect()
+}
+
+fn ingredients_from_lines<'a>(lines: impl Iterator<Item = &'a str>, report: &mut ImportReport) -> Vec<Value> {
+    let mut ingredients = Vec::new();
+    for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+        if line.ends_with(':') {
+            report.note(format!("section heading '{}' dropped", line));
+            continue;
+        }
+        let index = ingredients.len();
+        ingredients.push(ingredient_from_line(line, index, report));
+    }
+    ingredients
+}
+
+/// Minutes in a duration like "1 hr 30 mins", "45" or "PT1H30M"
+fn parse_minutes(text: &str) -> Option<f64> {
+    let text = text.trim();
+    if let Some(caps) = ISO_DURATION_REGEX.captures(text) {
+        let part = |i: usize| caps.get(i).and_then(|m| m.as_str().parse::<f64>().ok()).unwrap_or(0.0);
+        let minutes = part(1) * 1440.0 + part(2) * 60.0 + part(3) + part(4) / 60.0;
+        return if minutes > 0.0 { Some(minutes) } else { None };
+    }
+    if let Ok(minutes) = text.parse::<f64>() {
+        return Some(minutes).filter(|m| m.is_finite() && *m >= 0.0);
+    }
+
+    let mut total = None;
+    for caps in DURATION_PART_REGEX.captures_iter(text) {
+        let amount: f64 = caps[1].parse().ok()?;
+        let factor = match caps[2].to_lowercase().chars().next() {
+            Some('d') => 1440.0,
+            Some('h') => 60.0,
+            Some('m') => 1.0,
+            _ => 1.0 / 60.0,
+        };
+        total = Some(total.unwrap_or(0.0) + amount * factor);
+    }
+    total
+}
+
+/// Leading number of a servings string like "4 servings", with the rest as unit
+fn parse_servings(text: &str) -> Option<Value> {
+    let text = text.trim();
+    let leading = leading_amount(text).filter(|leading| leading.quantity_end > 0)?;
+    let unit = text[leading.quantity_end..].trim();
+    let unit = if unit.is_empty() { "servings" } else { unit };
+    Some(json!({"amount": number_value(leading.amount.value), "unit": unit}))
+}
+
+fn parse_date(text: &str) -> Option<String> {
+    let text = text.trim();
+    let time = DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S"]
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(text, f).ok())
+                .map(|naive| Utc.from_utc_datetime(&naive))
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+                .map(|naive| Utc.from_utc_datetime(&naive))
+        })?;
+    Some(time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+/// Recipe id derived from a stable key, so re-importing yields the same id
+fn generated_id(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+    random_recipe_id(&mut SplitMix64::new(hash))
+}
+
+fn text<'a>(source: &'a Value, key: &str) -> Option<&'a str> {
+    source.get(key).and_then(|v| v.as_str()).map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Whether a field carries any data worth reporting
+fn has_data(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(Value::Array(a)) => !a.is_empty(),
+        Some(Value::Object(o)) => o.values().any(|v| has_data(Some(v))),
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+        Some(Value::Bool(true)) => true,
+    }
+}
+
+struct Draft {
+    key: String,
+    identity: String,
+    meta: Map<String, Value>,
+    ingredients: Vec<Value>,
+    steps: Vec<Value>,
+    images: Vec<Value>,
+}
+
+impl Draft {
+    fn into_recipe(self, ids: Option<&IdStrategy>) -> Value {
+        let mut recipe = json!({
+            "rcip_version": "0.1",
+            "id": generated_id(&self.key),
+            "meta": Value::Object(self.meta),
+            "ingredients": self.ingredients,
+            "steps": self.steps
+        });
+        if !self.images.is_empty() {
+            recipe["images"] = Value::Array(self.images);
+        }
+        precision::round_amounts(&mut recipe, &PrecisionPolicy::default());
+        if let Some(ids) = ids {
+            ids.assign(&mut recipe, &self.identity);
+        }
+        recipe
+    }
+}
+
+fn new_meta(name: &str, author: &str, created: Option<&str>, ids: Option<&IdStrategy>, report: &mut ImportReport) -> Map<String, Value> {
+    let created = match created.and_then(parse_date) {
+        Some(created) => created,
+        None if ids.is_some_and(IdStrategy::is_deterministic) => {
+            report.note("created date unknown, set to 1970-01-01 for a reproducible import");
+            "1970-01-01T00:00:00Z".to_string()
+        }
+        None => Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+    };
+    let mut meta = Map::new();
+    meta.insert("name".to_string(), json!(name));
+    meta.insert("author".to_string(), json!(author));
+    meta.insert("created_date".to_string(), json!(created));
+    meta
+}
+
+fn insert_minutes(meta: &mut Map<String, Value>, key: &str, text: Option<&str>, report: &mut ImportReport) {
+    if let Some(text) = text {
+        match parse_minutes(text) {
+            Some(minutes) => {
+                meta.insert(key.to_string(), number_value(round_half_even(minutes, 1)));
+            }
+            None => report.note(format!("{}: could not parse '{}'", key, text)),
+        }
+    }
+}
+
+/// Convert a single Paprika 3 recipe export
+pub fn from_paprika(source: &Value) -> (Value, ImportReport) {
+    paprika(source, None)
+}
+
+/// Convert a single Paprika 3 recipe export, with ids from `ids`
+pub fn from_paprika_with(source: &Value, ids: &IdStrategy) -> (Value, ImportReport) {
+    paprika(source, Some(ids))
+}
+
+fn paprika(source: &Value, ids: Option<&IdStrategy>) -> (Value, ImportReport) {
+    let name = text(source, "name").unwrap_or("Untitled recipe").to_string();
+    let mut report = ImportReport { name: name.clone(), unconverted: Vec::new() };
+
+    let author = text(source, "source").unwrap_or("Unknown");
+    let mut meta = new_meta(&name, author, text(source, "created"), ids, &mut report);
+
+    if let Some(description) = text(source, "description") {
+        meta.insert("description".to_string(), json!(description));
+    }
+    if let Some(servings) = text(source, "servings") {
+        match parse_servings(servings) {
+            Some(servings) => {
+                meta.insert("servings".to_string(), servings);
+            }
+            None => report.note(format!("servings: could not parse '{}'", servings)),
+        }
+    }
+    if let Some(difficulty) = text(source, "difficulty") {
+        let level = match difficulty.to_lowercase().as_str() {
+            "easy" | "beginner" => Some("beginner"),
+            "medium" | "intermediate" => Some("intermediate"),
+            "hard" | "difficult" | "advanced" => Some("advanced"),
+            _ => None,
+        };
+        match level {
+            Some(level) => {
+                meta.insert("difficulty".to_string(), json!(level));
+            }
+            None => report.note(format!("difficulty: unknown level '{}'", difficulty)),
+        }
+    }
+    insert_minutes(&mut meta, "prep_time_minutes", text(source, "prep_time"), &mut report);
+    insert_minutes(&mut meta, "cook_time_minutes", text(source, "cook_time"), &mut report);
+    insert_minutes(&mut meta, "total_time_minutes", text(source, "total_time"), &mut report);
+
+    let categories: Vec<&str> = source.get("categories")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|c| c.as_str()).collect())
+        .unwrap_or_default();
+    if !categories.is_empty() {
+        meta.insert("keywords".to_string(), json!(categories));
+    }
+
+    let ingredients = ingredients_from_lines(text(source, "ingredients").unwrap_or("").lines(), &mut report);
+    let steps = steps_from_paragraphs(text(source, "directions").unwrap_or("").lines());
+
+    let mut images = Vec::new();
+    if let Some(photo) = text(source, "photo_data") {
+        let mime = if photo.starts_with("iVBOR") { "image/png" } else { "image/jpeg" };
+        images.push(json!({"id": "img-1", "data_uri": format!("data:{};base64,{}", mime, photo)}));
+    }
+
+    for field in ["notes", "nutritional_info", "rating", "source_url", "image_url", "photos"] {
+        if has_data(source.get(field)) {
+            report.note(format!("{} not converted", field));
+        }
+    }
+
+    let key = text(source, "uid").unwrap_or(&name).to_string();
+    let identity = text(source, "source_url").map(str::to_string).unwrap_or_else(|| content_identity(source));
+    let draft = Draft { key, identity, meta, ingredients, steps, images };
+    (draft.into_recipe(ids), report)
+}
+
+/// Line for a structured Mealie ingredient
+fn mealie_ingredient_line(ingredient: &Value) -> Option<String> {
+    if let Some(line) = ingredient.as_str() {
+        return Some(line.to_string());
+    }
+    if let Some(original) = text(ingredient, "originalText") {
+        return Some(original.to_string());
+    }
+
+    let food = ingredient.pointer("/food/name").and_then(|v| v.as_str());
+    if food.is_none() {
+        return text(ingredient, "note").or_else(|| text(ingredient, "display")).map(str::to_string);
+    }
+
+    let mut parts = Vec::new();
+    if let Some(q) = ingredient.get("quantity").and_then(|v| v.as_f64()).filter(|q| *q > 0.0) {
+        parts.push(q.to_string());
+    }
+    if let Some(unit) = ingredient.pointer("/unit/name").and_then(|v| v.as_str()) {
+        parts.push(unit.to_string());
+    }
+    parts.extend(food.map(str::to_string));
+    let mut line = parts.join(" ");
+    if let Some(note) = text(ingredient, "note") {
+        line.push_str(&format!(", {}", note));
+    }
+    Some(line)
+}
+
+/// Convert a single Mealie recipe export
+pub fn from_mealie(source: &Value) -> (Value, ImportReport) {
+    mealie(source, None)
+}
+
+/// Convert a single Mealie recipe export, with ids from `ids`
+pub fn from_mealie_with(source: &Value, ids: &IdStrategy) -> (Value, ImportReport) {
+    mealie(source, Some(ids))
+}
+
+fn mealie(source: &Value, ids: Option<&IdStrategy>) -> (Value, ImportReport) {
+    let name = text(source, "name").unwrap_or("Untitled recipe").to_string();
+    let mut report = ImportReport { name: name.clone(), unconverted: Vec::new() };
+
+    let created = text(source, "dateAdded").or_else(|| text(source, "createdAt"));
+    let mut meta = new_meta(&name, "Unknown", created, ids, &mut report);
+
+    if let Some(description) = text(source, "description") {
+        meta.insert("description".to_string(), json!(description));
+    }
+
+    let servings = source.get("recipeServings")
+        .and_then(|v| v.as_f64())
+        .filter(|s| *s > 0.0)
+        .map(|s| json!({"amount": number_value(s), "unit": "servings"}))
+        .or_else(|| text(source, "recipeYield").and_then(parse_servings));
+    match servings {
+        Some(servings) => {
+            meta.insert("servings".to_string(), servings);
+        }
+        None => {
+            if let Some(recipe_yield) = text(source, "recipeYield") {
+                report.note(format!("recipeYield: could not parse '{}'", recipe_yield));
+            }
+        }
+    }
+
+    insert_minutes(&mut meta, "prep_time_minutes", text(source, "prepTime"), &mut report);
+    insert_minutes(
+        &mut meta,
+        "cook_time_minutes",
+        text(source, "performTime").or_else(|| text(source, "cookTime")),
+        &mut report,
+    );
+    insert_minutes(&mut meta, "total_time_minutes", text(source, "totalTime"), &mut report);
+
+    let keywords: Vec<&str> = ["recipeCategory", "tags"]
+        .iter()
+        .filter_map(|key| source.get(*key).and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|c| c.get("name").and_then(|v| v.as_str()).or_else(|| c.as_str()))
+        .collect();
+    if !keywords.is_empty() {
+        meta.insert("keywords".to_string(), json!(keywords));
+    }
+
+    let lines: Vec<String> = source.get("recipeIngredient")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(mealie_ingredient_line)
+        .collect();
+    let ingredients = ingredients_from_lines(lines.iter().map(String::as_str), &mut report);
+
+    let instructions: Vec<&str> = source.get("recipeInstructions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|i| i.get("text").and_then(|v| v.as_str()).or_else(|| i.as_str()))
+        .collect();
+    let steps = steps_from_paragraphs(instructions.iter().copied().flat_map(|text| text.split("\n\n")));
+
+    for field in ["image", "orgURL", "nutrition", "notes", "tools", "rating", "extras"] {
+        if has_data(source.get(field)) {
+            report.note(format!("{} not converted", field));
+        }
+    }
+
+    let key = text(source, "id").or_else(|| text(source, "slug")).unwrap_or(&name).to_string();
+    let identity = text(source, "orgURL").map(str::to_string).unwrap_or_else(|| content_identity(source));
+    let draft = Draft { key, identity, meta, ingredients, steps, images: Vec::new() };
+    (draft.into_recipe(ids), report)
+}
+
+/// Recipes in an export file: a single recipe, an array, or `{"recipes": [...]}`
+pub fn export_entries(export: &Value) -> Vec<&Value> {
+    match export {
+        Value::Array(items) => items.iter().collect(),
+        Value::Object(map) => match map.get("recipes").and_then(|v| v.as_array()) {
+            Some(items) => items.iter().collect(),
+            None => vec![export],
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// File-name friendly version of a recipe name
+pub fn slug(name: &str) -> String {
+    let slug = name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() { "recipe".to_string() } else { slug }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "no-fs"))]
+    use crate::RCIPValidator;
+
+    #[cfg(not(feature = "no-fs"))]
+    fn paprika() -> Value {
+        json!({
+            "uid": "B7A2E2F0-1234",
+            "name": "Weeknight Tomato Soup",
+            "source": "Grandma's Kitchen",
+            "created": "2023-01-15 10:00:00",
+            "servings": "4 servings",
+            "prep_time": "10 mins",
+            "cook_time": "1 hr 5 mins",
+            "difficulty": "Easy",
+            "categories": ["Soups", "Vegetarian"],
+            "ingredients": "For the soup:\n2 tbsp olive oil\n1½ onions, diced\n800g canned tomatoes\n500 ml vegetable stock\nsalt to taste",
+            "directions": "1. Heat the oil in a large pot.\n\n2. Add the onion and cook until soft.\n3. Stir in tomatoes and stock; simmer for 20 minutes.\n4. Puree until smooth.",
+            "notes": "Freezes well.",
+            "photo_data": "/9j/4AAQSkZJRg==",
+            "rating": 5
+        })
+    }
+
+    #[test]
+    fn test_parse_ingredient_lines() {
+        let line = parse_ingredient_line("2 1/2 cups of flour, sifted");
+        assert_eq!(line.value, Some(2.5));
+        assert_eq!(line.unit, Some("cup"));
+        assert_eq!(line.amount_text, "2 1/2 cups");
+        assert_eq!(line.name, "flour");
+        assert_eq!(line.notes.as_deref(), Some("sifted"));
+
+        let line = parse_ingredient_line("500g flour");
+        assert_eq!((line.value, line.unit), (Some(500.0), Some("g")));
+
+        let line = parse_ingredient_line("¾ tsp salt");
+        assert_eq!((line.value, line.unit), (Some(0.75), Some("tsp")));
+
+        let line = parse_ingredient_line("8 fl oz milk");
+        assert_eq!((line.value, line.unit, line.name.as_str()), (Some(8.0), Some("fl-oz"), "milk"));
+
+        let line = parse_ingredient_line("2-3 cloves garlic");
+        assert_eq!((line.value, line.unit, line.name.as_str()), (Some(2.0), Some("pcs"), "garlic"));
+
+        let line = parse_ingredient_line("pinch of nutmeg");
+        assert_eq!((line.value, line.unit, line.name.as_str()), (None, Some("pinch"), "nutmeg"));
+
+        // Amounts are read by the same parser human_amount checks and scaling use
+        let line = parse_ingredient_line("a pinch of salt");
+        assert_eq!((line.value, line.unit, line.amount_text.as_str()), (None, Some("pinch"), "a pinch"));
+        let line = parse_ingredient_line("1½ Tbsp. olive oil");
+        assert_eq!((line.value, line.unit, line.amount_text.as_str()), (Some(1.5), Some("tbsp"), "1½ Tbsp."));
+        let line = parse_ingredient_line("2 or 3 eggs");
+        assert_eq!((line.value, line.unit, line.name.as_str()), (None, None, "2 or 3 eggs"));
+        assert_eq!(parse_servings("4-6 portions"), Some(json!({"amount": 4, "unit": "portions"})));
+    }
+
+    #[test]
+    fn test_guess_action() {
+        assert_eq!(guess_action("Preheat the oven to 200C"), "heat");
+        assert_eq!(guess_action("Then bake for 20 minutes"), "bake");
+        assert_eq!(guess_action("Chopped onions go in first"), "chop");
+        assert_eq!(guess_action("Enjoy!"), "prepare");
+    }
+
+    #[test]
+    fn test_parse_durations() {
+        assert_eq!(parse_minutes("1 hr 30 mins"), Some(90.0));
+        assert_eq!(parse_minutes("PT1H15M"), Some(75.0));
+        assert_eq!(parse_minutes("45"), Some(45.0));
+        assert_eq!(parse_minutes("overnight"), None);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_from_paprika() {
+        let (recipe, report) = from_paprika(&paprika());
+
+        assert_eq!(recipe["id"], from_paprika(&paprika()).0["id"]);
+        assert_eq!(recipe["meta"]["servings"], json!({"amount": 4, "unit": "servings"}));
+        assert_eq!(recipe["meta"]["cook_time_minutes"], json!(65));
+        assert_eq!(recipe["meta"]["difficulty"], "beginner");
+        assert_eq!(recipe["meta"]["created_date"], "2023-01-15T10:00:00Z");
+
+        let ingredients = recipe["ingredients"].as_array().unwrap();
+        assert_eq!(ingredients.len(), 5);
+        assert_eq!(ingredients[1]["machine_amount"], json!({"value": 2, "unit": "pcs"}));
+        assert_eq!(ingredients[4]["machine_amount"]["unit"], "to-taste");
+
+        let actions: Vec<&str> = recipe["steps"].as_array().unwrap().iter()
+            .map(|s| s["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, vec!["heat", "add", "mix", "blend"]);
+        assert_eq!(recipe["steps"][0]["human_text"], "Heat the oil in a large pot.");
+        assert!(recipe["images"][0]["data_uri"].as_str().unwrap().starts_with("data:image/jpeg;base64,"));
+
+        assert!(report.unconverted.iter().any(|u| u.contains("section heading")));
+        assert!(report.unconverted.iter().any(|u| u.contains("rounded up to 2")));
+        assert!(report.unconverted.iter().any(|u| u == "notes not converted"));
+        assert!(report.unconverted.iter().any(|u| u == "rating not converted"));
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid, "{:?}", result.errors);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_from_mealie() {
+        let export = json!({
+            "recipes": [{
+                "id": "5f3e0d4c-aaaa-bbbb-cccc-000000000001",
+                "name": "Pancakes",
+                "recipeYield": "8 pancakes",
+                "totalTime": "PT25M",
+                "dateAdded": "2024-03-02",
+                "recipeCategory": [{"name": "Breakfast"}],
+                "tags": [{"name": "Sweet"}],
+                "recipeIngredient": [
+                    {"quantity": 200, "unit": {"name": "g"}, "food": {"name": "flour"}, "note": ""},
+                    {"originalText": "2 eggs"},
+                    {"note": "butter for the pan", "food": null}
+                ],
+                "recipeInstructions": [
+                    {"text": "Whisk everything together.\n\nRest the batter for 10 minutes."},
+                    {"text": "Fry in butter."}
+                ],
+                "nutrition": {"calories": "250", "fatContent": null},
+                "image": "abc123"
+            }]
+        });
+
+        let entries = export_entries(&export);
+        assert_eq!(entries.len(), 1);
+        let (recipe, report) = from_mealie(entries[0]);
+
+        assert_eq!(recipe["meta"]["servings"], json!({"amount": 8, "unit": "pancakes"}));
+        assert_eq!(recipe["meta"]["total_time_minutes"], json!(25));
+        assert_eq!(recipe["meta"]["keywords"], json!(["Breakfast", "Sweet"]));
+        assert_eq!(recipe["ingredients"][0]["machine_amount"], json!({"value": 200, "unit": "g"}));
+        assert_eq!(recipe["ingredients"][1]["machine_amount"], json!({"value": 2, "unit": "pcs"}));
+        assert_eq!(recipe["steps"].as_array().unwrap().len(), 3);
+        assert_eq!(recipe["steps"][1]["action"], "rest");
+        assert_eq!(
+            report.unconverted,
+            vec![
+                "ingredient 'butter for the pan': no amount found, imported as to-taste",
+                "image not converted",
+                "nutrition not converted",
+            ]
+        );
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid, "{:?}", result.errors);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_deterministic_import_is_byte_identical() {
+        let mut source = paprika();
+        source.as_object_mut().unwrap().remove("created");
+        let ids = IdStrategy::deterministic("https://example.com/imports");
+
+        let (first, report) = from_paprika_with(&source, &ids);
+        let (second, _) = from_paprika_with(&source, &ids);
+        assert_eq!(serde_json::to_string_pretty(&first).unwrap(), serde_json::to_string_pretty(&second).unwrap());
+        assert_eq!(first["meta"]["created_date"], "1970-01-01T00:00:00Z");
+        assert!(report.unconverted.iter().any(|u| u.starts_with("created date unknown")));
+        assert!(first["ingredients"][0]["id"].as_str().unwrap().starts_with("ing-0001"));
+
+        source["source_url"] = json!("https://example.com/soup");
+        let (with_url, _) = from_paprika_with(&source, &ids);
+        assert_eq!(with_url["id"], ids.recipe_id("https://example.com/soup"));
+
+        let (random, _) = from_mealie_with(&json!({"name": "Toast"}), &IdStrategy::Random);
+        assert_ne!(random["meta"]["created_date"], "1970-01-01T00:00:00Z");
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let result = validator.validate_recipe(&first);
+        assert!(result.valid, "{:?}", result.errors);
+    }
+}
@@ -0,0 +1,409 @@
+// Action inference for legacy recipes
+//
+// Older recipes often have `human_text` for a step but no `action`, which
+// the schema requires. `actions` fills the gap from the text: the first verb
+// that opens an instruction wins. Clauses that only set a condition ("once
+// the butter has melted") are read after the others, filler words ("then",
+// "gently" and other adverbs) are skipped, and "let", "allow", "leave" and
+// "bring" defer to the verb they introduce ("let the dough rise" is `proof`,
+// "bring the milk to a simmer" is `simmer`). Verbs are matched through
+// `vocabulary::action_of`, this module's own lexicon of further synonyms,
+// and a few phrases ("bring to a boil", "set aside").
+//
+// Each guess gets a confidence: a multi-word phrase or the action's own name
+// scores highest, a synonym less, a verb buried mid-clause much less, and
+// agreeing context ("oven" for `bake`) adds a little. Steps whose best guess
+// stays below the threshold are left alone and reported. Inferred steps are
+// marked with `x-inferred: true` and `x-inferred-confidence`, so a reviewer
+// can find and confirm them.
+
+use crate::{guidance, vocabulary};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// Verbs naming an action that `vocabulary` does not know
+const EXTRA_VERBS: &[(&str, &[&str])] = &[
+    ("add", &["lower", "stuff", "fill"]),
+    ("mix", &["cream", "incorporate"]),
+    ("blend", &["liquidize", "liquidise", "emulsify", "mash", "process"]),
+    ("cut", &["halve", "quarter", "grate", "shred", "carve", "score"]),
+    ("slice", &["julienne"]),
+    ("dice", &["cube"]),
+    ("mince", &["crush"]),
+    ("heat", &["reheat", "microwave", "scald"]),
+    ("boil", &["blanch", "parboil"]),
+    ("simmer", &["poach", "braise", "stew", "reduce"]),
+    ("fry", &["deglaze"]),
+    ("saute", &["sauté", "sweat"]),
+    ("grill", &["broil", "barbecue", "char"]),
+    ("shape", &["form", "stretch", "mould", "mold"]),
+    ("proof", &["rise"]),
+    ("rest", &["stand", "sit"]),
+    ("strain", &["sieve", "rinse"]),
+    ("filter", &["sift"]),
+    ("spread", &["brush", "smear", "grease"]),
+    ("garnish", &["drizzle", "dust", "plate"]),
+    ("divide", &["portion", "split"]),
+    ("prepare", &["peel", "trim", "wash", "core", "pit", "zest", "crack"]),
+];
+
+/// Phrases naming an action, matched before single words
+const PHRASES: &[(&[&str], &str)] = &[
+    (&["bring", "to", "a", "boil"], "boil"),
+    (&["bring", "to", "the", "boil"], "boil"),
+    (&["bring", "to", "boil"], "boil"),
+    (&["rolling", "boil"], "boil"),
+    (&["set", "aside"], "rest"),
+    (&["stir", "fry"], "fry"),
+    (&["deep", "fry"], "fry"),
+    (&["pan", "fry"], "fry"),
+    (&["put", "in", "the", "oven"], "bake"),
+    (&["place", "in", "the", "oven"], "bake"),
+    (&["transfer", "to", "the", "oven"], "bake"),
+    (&["cut", "into"], "cut"),
+    (&["top", "with"], "garnish"),
+    (&["pat", "dry"], "prepare"),
+    (&["pre", "heat"], "heat"),
+];
+
+/// Words skipped at the start of an instruction
+const FILLERS: &[&str] = &[
+    "then", "now", "next", "finally", "meanwhile", "first", "again", "also", "and", "carefully",
+    "gently", "quickly", "slowly", "lightly", "immediately", "continue", "to", "start", "begin", "by",
+    "please", "just", "well",
+];
+
+/// Words that make a clause a condition rather than an instruction
+const CONDITIONS: &[&str] = &["once", "when", "while", "after", "before", "until", "if", "as"];
+
+/// Verbs that hand over to the verb after them; `rest` if none follows
+const DEFERRING: &[&str] = &["let", "allow", "leave", "bring"];
+
+/// How far after a deferring verb to look for the verb it introduces
+const DEFERRING_REACH: usize = 6;
+
+const PHRASE_CONFIDENCE: f64 = 0.9;
+const NAME_CONFIDENCE: f64 = 0.9;
+const SYNONYM_CONFIDENCE: f64 = 0.8;
+const LEXICON_CONFIDENCE: f64 = 0.75;
+const OPENING_BONUS: f64 = 0.05;
+const BURIED_PENALTY: f64 = 0.3;
+const CONTEXT_BONUS: f64 = 0.05;
+
+/// Options for inferring actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferenceOptions {
+    /// Guesses below this confidence are reported, not applied
+    pub threshold: f64,
+    /// Language of `human_text` to read when it carries translations
+    pub locale: String,
+}
+
+impl Default for InferenceOptions {
+    fn default() -> Self {
+        InferenceOptions { threshold: 0.6, locale: "en".to_string() }
+    }
+}
+
+/// An action guessed from a text, and the words it was guessed from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Inference {
+    pub action: &'static str,
+    pub confidence: f64,
+    pub evidence: String,
+}
+
+/// A step that got an action
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InferredStep {
+    pub step_id: String,
+    #[serde(flatten)]
+    pub inference: Inference,
+}
+
+/// A step left without an action, with the best guess if there was one
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UninferredStep {
+    pub step_id: String,
+    pub text: String,
+    pub best: Option<Inference>,
+}
+
+/// What an inference pass changed and what it left alone
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InferenceReport {
+    pub inferred: Vec<InferredStep>,
+    pub uninferred: Vec<UninferredStep>,
+}
+
+impl InferenceReport {
+    /// Whether every step missing an action got one
+    pub fn is_complete(&self) -> bool {
+        self.uninferred.is_empty()
+    }
+}
+
+impl fmt::Display for InferenceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.inferred {
+            writeln!(
+                f,
+                "{}: {} ({:.2}, from '{}')",
+                step.step_id, step.inference.action, step.inference.confidence, step.inference.evidence
+            )?;
+        }
+        for step in &self.uninferred {
+            match &step.best {
+                Some(best) => writeln!(
+                    f,
+                    "{}: left unset, best guess {} ({:.2}) is below the threshold",
+                    step.step_id, best.action, best.confidence
+                )?,
+                None => writeln!(f, "{}: left unset, no action found in '{}'", step.step_id, step.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The action a single word names, with the confidence of that reading
+fn verb(word: &str) -> Option<(&'static str, f64)> {
+    if let Some(action) = vocabulary::action_of(word) {
+        let own_name = vocabulary::stems(word).iter().any(|stem| stem == action);
+        return Some((action, if own_name { NAME_CONFIDENCE } else { SYNONYM_CONFIDENCE }));
+    }
+    vocabulary::stems(word).iter().find_map(|stem| {
+        EXTRA_VERBS
+            .iter()
+            .find(|(_, verbs)| verbs.contains(&stem.as_str()))
+            .map(|(action, _)| (*action, LEXICON_CONFIDENCE))
+    })
+}
+
+/// Phrase starting at `words[0]`, with the number of words it spans
+fn phrase(words: &[String]) -> Option<(&'static str, usize)> {
+    PHRASES.iter().find_map(|(parts, action)| {
+        let matches = parts.len() <= words.len()
+            && parts.iter().zip(words).all(|(part, word)| vocabulary::stems(word).iter().any(|s| s == part));
+        matches.then_some((*action, parts.len()))
+    })
+}
+
+/// First action in a clause: (action, confidence, evidence, opens the clause)
+fn clause_action(words: &[String]) -> Option<(&'static str, f64, String, bool)> {
+    let filler = |w: &String| FILLERS.contains(&w.as_str()) || w.ends_with("ly");
+    let start = words.iter().position(|w| !filler(w)).unwrap_or(words.len());
+    let mut i = start;
+    while i < words.len() {
+        let opening = i == start;
+        if let Some((action, len)) = phrase(&words[i..]) {
+            return Some((action, PHRASE_CONFIDENCE, words[i..i + len].join(" "), opening));
+        }
+        if opening && DEFERRING.contains(&words[i].as_str()) {
+            let deferred = (i + 1..words.len()).take(DEFERRING_REACH).find_map(|k| match phrase(&words[k..]) {
+                Some((action, len)) => Some((k + len, action, PHRASE_CONFIDENCE)),
+                None => verb(&words[k]).map(|(action, confidence)| (k + 1, action, confidence)),
+            });
+            return Some(match deferred {
+                Some((end, action, confidence)) => (action, confidence, words[i..end].join(" "), true),
+                None => ("rest", SYNONYM_CONFIDENCE, words[i].clone(), true),
+            });
+        }
+        if let Some((action, confidence)) = verb(&words[i]) {
+            return Some((action, confidence, words[i].clone(), opening));
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Guess the action of a step text, without applying any threshold
+pub fn infer_action(text: &str) -> Option<Inference> {
+    let clauses: Vec<Vec<String>> = text
+        .split(['.', ',', ';', ':', '!', '?', '\n'])
+        .map(|clause| {
+            clause
+                .split(|c: char| !c.is_alphabetic())
+                .filter(|w| !w.is_empty())
+                .map(str::to_lowercase)
+                .collect::<Vec<_>>()
+        })
+        .filter(|words| !words.is_empty())
+        .collect();
+
+    let is_condition = |words: &Vec<String>| CONDITIONS.contains(&words[0].as_str());
+    let found: Vec<(&'static str, f64, String, bool)> = clauses
+        .iter()
+        .filter(|words| !is_condition(words))
+        .chain(clauses.iter().filter(|words| is_condition(words)))
+        .filter_map(|words| clause_action(words).map(|(a, c, e, opening)| (a, c, e, opening && !is_condition(words))))
+        .collect();
+
+    let best = found.iter().find(|(_, _, _, opening)| *opening).or_else(|| found.first())?;
+    let (action, base, evidence, opening) = best.clone();
+
+    let mut confidence = base + if opening { OPENING_BONUS } else { -BURIED_PENALTY };
+    let mut others = found.iter().filter(|other| !std::ptr::eq(*other, best));
+    if others.any(|(a, ..)| *a == action) || vocabulary::mentions(&text.to_lowercase().replace(&evidence, ""), action) {
+        confidence += CONTEXT_BONUS;
+    }
+    let confidence = (confidence.clamp(0.0, 0.99) * 100.0).round() / 100.0;
+    Some(Inference { action, confidence, evidence })
+}
+
+fn missing_action(step: &Value) -> bool {
+    match step.get("action") {
+        None | Some(Value::Null) => true,
+        Some(Value::String(action)) => action.trim().is_empty(),
+        Some(_) => false,
+    }
+}
+
+/// Fill in missing step actions from `human_text`
+pub fn actions(recipe: &mut Value) -> InferenceReport {
+    actions_with(recipe, &InferenceOptions::default())
+}
+
+/// Fill in missing step actions from `human_text`, with custom options
+pub fn actions_with(recipe: &mut Value, options: &InferenceOptions) -> InferenceReport {
+    let mut report = InferenceReport::default();
+    let steps = match recipe.get_mut("steps").and_then(|v| v.as_array_mut()) {
+        Some(steps) => steps,
+        None => return report,
+    };
+
+    for (i, step) in steps.iter_mut().enumerate() {
+        if !missing_action(step) {
+            continue;
+        }
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).map(str::to_string).unwrap_or_else(|| format!("#{}", i));
+        let text = step.get("human_text").and_then(|t| guidance::localized(t, &options.locale)).unwrap_or_default();
+
+        match infer_action(&text) {
+            Some(inference) if inference.confidence >= options.threshold => {
+                step["action"] = json!(inference.action);
+                step["x-inferred"] = json!(true);
+                step["x-inferred-confidence"] = json!(inference.confidence);
+                report.inferred.push(InferredStep { step_id, inference });
+            }
+            best => report.uninferred.push(UninferredStep { step_id, text, best }),
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "no-fs"))]
+    use crate::RCIPValidator;
+
+    /// Step texts from legacy recipes, with the action a cook would give them
+    const LABELLED: &[(&str, &str)] = &[
+        ("Preheat the oven to 200°C.", "heat"),
+        ("Bake for 25 minutes until golden.", "bake"),
+        ("Chop the onions finely.", "chop"),
+        ("Finely chop the parsley.", "chop"),
+        ("Dice the carrots into 1 cm cubes.", "dice"),
+        ("Slice the tomatoes thinly.", "slice"),
+        ("Mince the garlic.", "mince"),
+        ("Crush the garlic with the flat of a knife.", "mince"),
+        ("Whisk the eggs and sugar until pale.", "mix"),
+        ("Cream the butter and sugar together.", "mix"),
+        ("Stir in the flour.", "mix"),
+        ("Combine the dry ingredients in a large bowl.", "combine"),
+        ("In a large bowl, whisk together the flour and salt.", "mix"),
+        ("Add the stock and bring to a boil.", "add"),
+        ("Bring to a boil, then reduce the heat.", "boil"),
+        ("Bring the water to a rolling boil.", "boil"),
+        ("Bring the milk to a simmer.", "simmer"),
+        ("Simmer for 20 minutes, stirring occasionally.", "simmer"),
+        ("Reduce the sauce by half.", "simmer"),
+        ("Poach the eggs for 3 minutes.", "simmer"),
+        ("Fry the bacon until crisp.", "fry"),
+        ("Stir-fry the vegetables for 3 minutes.", "fry"),
+        ("Sear the steak on both sides.", "fry"),
+        ("Sauté the mushrooms in butter.", "saute"),
+        ("Roast the potatoes for 40 minutes.", "roast"),
+        ("Grill the chicken for 6 minutes per side.", "grill"),
+        ("Broil until the cheese bubbles.", "grill"),
+        ("Steam the broccoli for 5 minutes.", "steam"),
+        ("Blanch the beans in salted water.", "boil"),
+        ("Puree the soup until smooth.", "blend"),
+        ("Blend everything until smooth.", "blend"),
+        ("Knead the dough for 10 minutes.", "knead"),
+        ("Let the dough rise for an hour.", "proof"),
+        ("Allow to cool completely.", "cool"),
+        ("Let rest for 10 minutes.", "rest"),
+        ("Set aside.", "rest"),
+        ("Leave to cool on a wire rack.", "cool"),
+        ("Refrigerate overnight.", "chill"),
+        ("Chill the dough for 30 minutes.", "chill"),
+        ("Freeze for at least 4 hours.", "freeze"),
+        ("Fold in the egg whites.", "fold"),
+        ("Roll out the dough to 3 mm.", "roll"),
+        ("Shape into a ball.", "shape"),
+        ("Drain the pasta.", "strain"),
+        ("Sift the flour and cocoa.", "filter"),
+        ("Weigh the flour.", "weigh"),
+        ("Divide the dough into 8 pieces.", "divide"),
+        ("Spread the frosting over the cake.", "spread"),
+        ("Sprinkle with parsley and serve.", "garnish"),
+        ("Dissolve the yeast in warm water.", "dissolve"),
+        ("Once the butter has melted, add the sugar.", "add"),
+        ("Peel the potatoes.", "prepare"),
+        ("Pour the batter into the tin.", "add"),
+        ("Separate the eggs.", "separate"),
+    ];
+
+    #[test]
+    fn test_labelled_step_texts() {
+        let misses: Vec<String> = LABELLED
+            .iter()
+            .filter_map(|(text, expected)| {
+                let got = infer_action(text).map(|i| i.action);
+                (got != Some(*expected)).then(|| format!("{:?}: expected {}, got {:?}", text, expected, got))
+            })
+            .collect();
+        assert!(misses.is_empty(), "{} of {} wrong:\n{}", misses.len(), LABELLED.len(), misses.join("\n"));
+    }
+
+    #[test]
+    fn test_confidence() {
+        let opening = infer_action("Bake in the oven for 25 minutes.").unwrap();
+        assert_eq!(opening.confidence, 0.99);
+        let synonym = infer_action("Whisk the eggs.").unwrap();
+        assert_eq!(synonym.confidence, 0.85);
+        let buried = infer_action("The onions should be chopped.").unwrap();
+        assert_eq!((buried.action, buried.confidence), ("chop", 0.6));
+        assert!(infer_action("Enjoy!").is_none());
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_inferred_recipe_validates() {
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let steps = recipe["steps"].as_array_mut().unwrap();
+        let texts: Vec<Value> = steps.iter().map(|s| s["human_text"].clone()).collect();
+        for step in steps.iter_mut() {
+            step.as_object_mut().unwrap().remove("action");
+        }
+        steps.push(json!({"step_id": "s-99", "human_text": "Enjoy with friends."}));
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let report = actions(&mut recipe);
+        assert_eq!(report.inferred.len(), texts.len());
+        assert_eq!(report.uninferred.len(), 1);
+        assert_eq!(report.uninferred[0].step_id, "s-99");
+        assert_eq!(recipe["steps"][0]["x-inferred"], json!(true));
+        assert!(recipe["steps"][0]["x-inferred-confidence"].as_f64().unwrap() >= 0.6);
+        assert!(recipe["steps"][texts.len()].get("action").is_none());
+
+        recipe["steps"].as_array_mut().unwrap().pop();
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid, "{:?}", result.errors);
+    }
+}
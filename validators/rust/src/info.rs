@@ -0,0 +1,524 @@
+// Recipe info
+//
+// `recipe_info` reads the summary validation reports (name, counts,
+// allergens, diet labels, times, baker's percentages) from a parsed recipe.
+// `extract_fast` gets the same summary straight from a file's bytes for
+// indexers that want nothing else. It deserializes only the fields the
+// summary reads, borrowing strings from the input, and skips everything else
+// without building values for it, so step text, images and nutrition are
+// never allocated. The baking helpers in `analysis` are shared between the
+// two paths, and the baking category of each ingredient name is remembered
+// per thread, since a corpus repeats its names.
+//
+// A document the sparse pass cannot take, such as a `meta` that is not an
+// object, an ingredient that is not an object or a key given twice, is
+// parsed in full and summarized the slow way instead. Malformed JSON is an
+// error on either path. The tests compare both paths on generated recipes
+// with random odd shapes injected; `benches/extract_info.rs` times them.
+
+use crate::analysis::BakingCategory;
+use crate::{allergens, analysis, numeric, terms, RCIPError, RecipeInfo};
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// Summary of a parsed recipe
+pub fn recipe_info(recipe: &Value) -> RecipeInfo {
+    let meta = recipe.get("meta");
+
+    RecipeInfo {
+        id: recipe.get("id").and_then(|v| v.as_str()).map(String::from),
+        name: meta.and_then(|m| m.get("name"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("Unknown")
+            .to_string(),
+        version: recipe.get("rcip_version")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string(),
+        recipe_version: meta.and_then(|m| m.get("version"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        ingredient_count: recipe.get("ingredients")
+            .and_then(|v| v.as_array())
+            .map(|a| a.len())
+            .unwrap_or(0),
+        step_count: recipe.get("steps")
+            .and_then(|v| v.as_array())
+            .map(|a| a.len())
+            .unwrap_or(0),
+        has_device_profiles: recipe.get("device_profiles")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false),
+        has_sensors: recipe.get("sensors")
+            .and_then(|v| v.as_array())
+            .map(|a| !a.is_empty())
+            .unwrap_or(false),
+        allergens: allergens::provenance(recipe),
+        traces: allergens::traces(recipe).into_iter().collect(),
+        diet_labels: meta.and_then(|m| m.get("diet_labels"))
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|s| terms::DIET_LABELS.canonical(s).to_string())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect()
+            })
+            .unwrap_or_default(),
+        difficulty: meta.and_then(|m| m.get("difficulty"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+        total_time: meta.and_then(|m| m.get("total_time_minutes"))
+            .and_then(numeric::finite),
+        baking: if analysis::is_baking(recipe) { analysis::baking_ratios(recipe) } else { None },
+        quality: None,
+        nutrition: None,
+        schema_version: None,
+    }
+}
+
+/// A scalar as the summary reads it: a string, a number, or anything else,
+/// which is skipped without being built
+enum Leaf<'a> {
+    Str(Cow<'a, str>),
+    Num(f64),
+    Other,
+}
+
+impl<'a> Leaf<'a> {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Leaf::Str(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Leaf::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+struct LeafVisitor;
+
+impl<'de> Visitor<'de> for LeafVisitor {
+    type Value = Leaf<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Str(Cow::Borrowed(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Str(Cow::Owned(v.to_string())))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Num(v as f64))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Num(v as f64))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Num(v))
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Other)
+    }
+
+    fn visit_unit<E>(self) -> Result<Leaf<'de>, E> {
+        Ok(Leaf::Other)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Leaf<'de>, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Leaf::Other)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Leaf<'de>, A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(Leaf::Other)
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Leaf<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LeafVisitor)
+    }
+}
+
+/// The strings of a list; empty for anything that is not a list
+struct Strings<'a>(Vec<Cow<'a, str>>);
+
+impl<'a> Strings<'a> {
+    fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|s| s.as_ref())
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Strings<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StringsVisitor;
+
+        impl<'de> Visitor<'de> for StringsVisitor {
+            type Value = Strings<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("any JSON value")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Strings<'de>, A::Error> {
+                let mut strings = Vec::new();
+                while let Some(item) = seq.next_element::<Leaf>()? {
+                    if let Leaf::Str(text) = item {
+                        strings.push(text);
+                    }
+                }
+                Ok(Strings(strings))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Strings<'de>, A::Error> {
+                LeafVisitor.visit_map(map).map(|_| Strings(Vec::new()))
+            }
+
+            fn visit_borrowed_str<E>(self, _: &'de str) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_str<E>(self, _: &str) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+
+            fn visit_unit<E>(self) -> Result<Strings<'de>, E> {
+                Ok(Strings(Vec::new()))
+            }
+        }
+
+        deserializer.deserialize_any(StringsVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct SparseRecipe<'a> {
+    #[serde(borrow)]
+    id: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    rcip_version: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    meta: Option<SparseMeta<'a>>,
+    #[serde(borrow)]
+    ingredients: Option<Vec<SparseIngredient<'a>>>,
+    #[serde(borrow)]
+    steps: Option<Vec<SparseStep<'a>>>,
+    device_profiles: Option<Vec<IgnoredAny>>,
+    sensors: Option<Vec<IgnoredAny>>,
+}
+
+#[derive(Deserialize)]
+struct SparseMeta<'a> {
+    #[serde(borrow)]
+    name: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    version: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    diet_labels: Option<Strings<'a>>,
+    #[serde(borrow)]
+    difficulty: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    total_time_minutes: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    facility_allergens: Option<Strings<'a>>,
+    #[serde(borrow)]
+    keywords: Option<Strings<'a>>,
+    #[serde(borrow)]
+    origin: Option<SparseOrigin<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SparseOrigin<'a> {
+    #[serde(borrow)]
+    cuisine_type: Option<Leaf<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SparseIngredient<'a> {
+    #[serde(borrow)]
+    id: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    name: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    allergens: Option<Strings<'a>>,
+    #[serde(borrow)]
+    may_contain_allergens: Option<Strings<'a>>,
+    #[serde(borrow, rename = "x-enriched-from")]
+    enriched_from: Option<SparseEnrichment<'a>>,
+    #[serde(borrow)]
+    machine_amount: Option<SparseAmount<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SparseEnrichment<'a> {
+    #[serde(borrow)]
+    source: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    fields: Option<Strings<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SparseAmount<'a> {
+    #[serde(borrow)]
+    value: Option<Leaf<'a>>,
+    #[serde(borrow)]
+    unit: Option<Leaf<'a>>,
+}
+
+#[derive(Deserialize)]
+struct SparseStep<'a> {
+    #[serde(borrow)]
+    action: Option<Leaf<'a>>,
+}
+
+/// Names remembered per thread before the memory starts over
+const REMEMBERED_NAMES: usize = 4096;
+
+thread_local! {
+    static CATEGORIES: RefCell<HashMap<String, Option<BakingCategory>>> = RefCell::new(HashMap::new());
+}
+
+/// `analysis::category`, remembered by name: a corpus repeats its
+/// ingredient names far more often than it introduces new ones
+fn remembered_category(name: &str) -> Option<BakingCategory> {
+    CATEGORIES.with(|categories| {
+        let mut categories = categories.borrow_mut();
+        if let Some(category) = categories.get(name) {
+            return *category;
+        }
+        if categories.len() >= REMEMBERED_NAMES {
+            categories.clear();
+        }
+        let category = analysis::category(name);
+        categories.insert(name.to_string(), category);
+        category
+    })
+}
+
+fn text(leaf: &Option<Leaf>) -> Option<String> {
+    leaf.as_ref().and_then(|l| l.as_str()).map(String::from)
+}
+
+fn strings<'s>(list: &'s Option<Strings>) -> impl Iterator<Item = &'s str> {
+    list.iter().flat_map(|l| l.iter())
+}
+
+impl<'a> SparseRecipe<'a> {
+    /// What `recipe_info` reports for the full document
+    fn summarize(&self) -> RecipeInfo {
+        let meta = self.meta.as_ref();
+        let ingredients = self.ingredients.as_deref().unwrap_or_default();
+        let steps = self.steps.as_deref().unwrap_or_default();
+
+        let allergen = |a| terms::ALLERGENS.canonical(a);
+        let contained: BTreeSet<&str> = ingredients.iter().flat_map(|i| strings(&i.allergens)).map(allergen).collect();
+        let sources = ingredients.iter().flat_map(|i| {
+            let enriched_by = i
+                .enriched_from
+                .as_ref()
+                .filter(|e| strings(&e.fields).any(|f| f == "allergens"))
+                .map(|e| e.source.as_ref().and_then(|s| s.as_str()).unwrap_or("resolver"));
+            let id = i.id.as_ref().and_then(|id| id.as_str()).unwrap_or("?");
+            let name = i.name.as_ref().and_then(|n| n.as_str()).unwrap_or("");
+            allergens::ingredient_sources(id, name, strings(&i.allergens), enriched_by)
+        });
+        let provenance = allergens::aggregate(sources);
+        let traces: BTreeSet<&str> = ingredients
+            .iter()
+            .flat_map(|i| strings(&i.may_contain_allergens))
+            .chain(meta.into_iter().flat_map(|m| strings(&m.facility_allergens)))
+            .map(allergen)
+            .filter(|a| !contained.contains(a))
+            .collect();
+
+        let tags = meta
+            .and_then(|m| m.origin.as_ref())
+            .and_then(|o| o.cuisine_type.as_ref())
+            .and_then(|c| c.as_str())
+            .into_iter()
+            .chain(meta.into_iter().flat_map(|m| strings(&m.keywords)));
+        let actions = steps.iter().filter_map(|s| s.action.as_ref().and_then(|a| a.as_str()));
+        let baking = if analysis::baking_signals(tags, actions) {
+            let ingredients = ingredients.iter().map(|i| {
+                let amount = i.machine_amount.as_ref();
+                (
+                    i.name.as_ref().and_then(|n| n.as_str()).unwrap_or(""),
+                    amount.and_then(|a| a.value.as_ref()).and_then(|v| v.as_f64()),
+                    amount.and_then(|a| a.unit.as_ref()).and_then(|u| u.as_str()),
+                )
+            });
+            analysis::baker_percentages(ingredients, remembered_category)
+        } else {
+            None
+        };
+
+        RecipeInfo {
+            id: text(&self.id),
+            name: meta.and_then(|m| text(&m.name)).unwrap_or_else(|| "Unknown".to_string()),
+            version: text(&self.rcip_version).unwrap_or_default(),
+            recipe_version: meta.and_then(|m| text(&m.version)),
+            ingredient_count: ingredients.len(),
+            step_count: steps.len(),
+            has_device_profiles: self.device_profiles.as_ref().is_some_and(|d| !d.is_empty()),
+            has_sensors: self.sensors.as_ref().is_some_and(|s| !s.is_empty()),
+            allergens: provenance,
+            traces: traces.into_iter().map(String::from).collect(),
+            diet_labels: meta
+                .map(|m| {
+                    let labels = strings(&m.diet_labels).map(|l| terms::DIET_LABELS.canonical(l));
+                    labels.collect::<BTreeSet<_>>().into_iter().map(String::from).collect()
+                })
+                .unwrap_or_default(),
+            difficulty: meta.and_then(|m| text(&m.difficulty)),
+            total_time: meta
+                .and_then(|m| m.total_time_minutes.as_ref())
+                .and_then(|t| t.as_f64())
+                .filter(|t| t.is_finite()),
+            baking,
+            quality: None,
+            nutrition: None,
+            schema_version: None,
+        }
+    }
+}
+
+/// Summary of a recipe file's bytes, without parsing the parts the summary
+/// does not read; the same result as parsing in full and `recipe_info`
+pub fn extract_fast(bytes: &[u8]) -> Result<RecipeInfo, RCIPError> {
+    match serde_json::from_slice::<SparseRecipe>(bytes) {
+        Ok(sparse) => Ok(sparse.summarize()),
+        Err(_) => Ok(recipe_info(&serde_json::from_slice(bytes)?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::SplitMix64;
+    use crate::generate::{self, Defect, RecipeSize};
+    use serde_json::json;
+
+    /// Places where a document can take an unusual shape
+    const POINTERS: &[&str] = &[
+        "/rcip_version", "/meta", "/meta/name", "/meta/version", "/meta/diet_labels", "/meta/total_time_minutes",
+        "/meta/origin", "/meta/origin/cuisine_type", "/meta/keywords", "/meta/facility_allergens", "/ingredients",
+        "/ingredients/0", "/ingredients/0/id", "/ingredients/0/name", "/ingredients/0/allergens",
+        "/ingredients/0/x-enriched-from", "/ingredients/0/machine_amount",
+        "/steps", "/steps/0", "/steps/0/action", "/sensors", "/device_profiles",
+    ];
+
+    fn replacement(rng: &mut SplitMix64) -> Value {
+        let values = [
+            json!(null), json!(7), json!(1.5e300), json!(true), json!("Bread"), json!([]), json!({}),
+            json!(["bakery", 3, null]), json!(["milk", "sesame"]), json!({"value": 500, "unit": "g"}),
+            json!([{"id": "x"}]), json!("knead"), json!({"source": "open-food-facts", "fields": ["allergens"]}),
+        ];
+        values[rng.below(values.len())].clone()
+    }
+
+    /// Set the value at `pointer`, adding the last key when it is missing
+    fn set(recipe: &mut Value, pointer: &str, value: Value) {
+        let (parent, key) = pointer.rsplit_once('/').unwrap();
+        match recipe.pointer_mut(parent) {
+            Some(Value::Object(fields)) => {
+                fields.insert(key.to_string(), value);
+            }
+            Some(Value::Array(items)) if !items.is_empty() => items[0] = value,
+            _ => {}
+        }
+    }
+
+    fn assert_same(bytes: &[u8]) -> RecipeInfo {
+        let slow = recipe_info(&serde_json::from_slice(bytes).unwrap());
+        assert_eq!(extract_fast(bytes).unwrap(), slow, "{}", String::from_utf8_lossy(bytes));
+        slow
+    }
+
+    #[test]
+    fn test_matches_full_parse_on_generated_corpus() {
+        let mut rng = SplitMix64::new(715);
+        for n in 0..400 {
+            let size = [RecipeSize::Small, RecipeSize::Medium, RecipeSize::Huge][n % 3];
+            let mut recipe = generate::recipe(&mut rng, size);
+            if n % 4 == 0 {
+                generate::inject(&mut recipe, Defect::ALL[rng.below(Defect::ALL.len())], &mut rng);
+            }
+            for _ in 0..rng.below(4) {
+                let value = replacement(&mut rng);
+                set(&mut recipe, POINTERS[rng.below(POINTERS.len())], value);
+            }
+            assert_same(&serde_json::to_vec(&recipe).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_matches_full_parse_on_examples() {
+        for entry in std::fs::read_dir("../../examples").unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            assert_same(&bytes);
+        }
+        let info = extract_fast(&std::fs::read("../../examples/margherita-pizza.rcip").unwrap()).unwrap();
+        assert_eq!(info.name, "Classic Margherita Pizza");
+        assert!(info.baking.is_some());
+    }
+
+    #[test]
+    fn test_unusual_documents_fall_back() {
+        // A key given twice: the full parse keeps the last one
+        assert_same(br#"{"meta": {"name": "First", "name": "Second"}, "steps": [{}, {}]}"#);
+        assert_same(br#"{"meta": "not an object", "ingredients": ["flour"]}"#);
+        assert_same(br#"[1, 2, 3]"#);
+        assert!(extract_fast(br#"{"meta": {"name": "Unclosed"}"#).is_err());
+    }
+
+    #[test]
+    fn test_aggregates_canonical_names() {
+        let bytes = br#"{
+            "meta": {"diet_labels": ["Vegetarian", "vegetarian "], "facility_allergens": ["Sesame", "nuts"]},
+            "ingredients": [{"allergens": ["Milk", "milk", "MILK "]}, {"may_contain_allergens": ["milk", "Tree Nuts"]}]
+        }"#;
+        let info = assert_same(bytes);
+        let allergens: Vec<String> = info.allergens.iter().map(|e| e.to_string()).collect();
+        assert_eq!(allergens, ["milk [?]"]);
+        assert_eq!(info.traces, ["nuts", "sesame", "tree-nuts"]);
+        assert_eq!(info.diet_labels, ["vegetarian"]);
+    }
+}
@@ -0,0 +1,611 @@
+// Drop-folder ingestion
+//
+// Partners drop recipes into an incoming directory; a nightly run validates
+// each one and files it away. Valid recipes move to `accepted/`, invalid ones
+// to `rejected/` next to a `<name>.issues.json` sidecar listing what is wrong,
+// and recipes that could not be read or moved because of IO errors are
+// retried and, once the retries are used up, moved to `quarantine/` for a
+// person to look at. A recipe that does not parse is invalid, not an IO
+// failure: it is rejected with RCIP-E-READ-FAILED like in `validate_directory`.
+//
+// Moves are renames within the output directory, so a recipe is either still
+// incoming or completely in place; across filesystems the recipe is copied to
+// a temporary file next to its destination and renamed into place. Sidecars
+// are written the same way, before the recipe moves. When the destination
+// already holds a file of the same name, the recipe keeps its name with the
+// first 12 hex digits of its SHA-256 inserted before the extension, and a
+// counter after them (`-2`, `-3`, ...) while that name is taken too, so
+// resubmissions never overwrite earlier ones: not identical bytes sent twice,
+// and not quarantined files, whose unreadable bytes leave only the name to
+// hash.
+//
+// All file access goes through `IngestIo`, so services can ingest from other
+// storage and tests can inject failures. A dry run reads and validates every
+// recipe and reports where each would go without creating, writing or moving
+// anything but the manifest.
+
+use crate::provenance::{sha256_hex, ValidationMeta};
+use crate::sources::is_recipe_name;
+use crate::{i18n, RCIPValidator, Severity, ValidationResult};
+use serde::Serialize;
+use serde_json::json;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// Subdirectory of the output directory for valid recipes
+pub const ACCEPTED: &str = "accepted";
+
+/// Subdirectory for invalid recipes and their sidecars
+pub const REJECTED: &str = "rejected";
+
+/// Subdirectory for recipes that kept failing with IO errors
+pub const QUARANTINE: &str = "quarantine";
+
+/// File access an ingestion run needs
+pub trait IngestIo {
+    /// Names of the entries directly inside a directory
+    fn list(&self, dir: &Path) -> io::Result<Vec<String>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn exists(&self, path: &Path) -> bool;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Write a file so that it appears complete or not at all
+    fn write_atomic(&self, path: &Path, bytes: &[u8]) -> io::Result<()>;
+    /// Move a file so that it is complete in exactly one place
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The local filesystem
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LocalIo;
+
+/// Temporary name next to `path`, hidden and not a recipe name
+fn temporary(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+impl IngestIo for LocalIo {
+    fn list(&self, dir: &Path) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(names)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn write_atomic(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let temporary = temporary(path);
+        let written = fs::File::create(&temporary).and_then(|mut file| {
+            io::Write::write_all(&mut file, bytes)?;
+            file.sync_all()
+        });
+        match written.and_then(|()| fs::rename(&temporary, path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&temporary);
+                Err(e)
+            }
+        }
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match fs::rename(from, to) {
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                self.write_atomic(to, &fs::read(from)?)?;
+                fs::remove_file(from)
+            }
+            moved => moved,
+        }
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+}
+
+/// What an ingestion run does
+pub struct IngestConfig<'a> {
+    /// Directory the recipes are dropped into
+    pub incoming: PathBuf,
+    /// Directory holding `accepted/`, `rejected/` and `quarantine/`; the
+    /// incoming directory by default
+    pub output: PathBuf,
+    pub validator: &'a RCIPValidator,
+    /// Further attempts at a failed read, write or move
+    pub retries: u32,
+    /// Wait before the first retry, doubled before each further one
+    pub retry_delay: Duration,
+    /// Report dispositions without moving or writing anything
+    pub dry_run: bool,
+    /// Where to write the run manifest, if anywhere
+    pub manifest: Option<PathBuf>,
+    pub io: &'a dyn IngestIo,
+}
+
+impl<'a> IngestConfig<'a> {
+    /// Ingest `incoming` in place with three retries from 100 ms on
+    pub fn new(incoming: impl Into<PathBuf>, validator: &'a RCIPValidator) -> Self {
+        let incoming = incoming.into();
+        IngestConfig {
+            output: incoming.clone(),
+            incoming,
+            validator,
+            retries: 3,
+            retry_delay: Duration::from_millis(100),
+            dry_run: false,
+            manifest: None,
+            io: &LocalIo,
+        }
+    }
+}
+
+/// Where a recipe ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Disposition {
+    Accepted,
+    Rejected,
+    Quarantined,
+    /// Even moving it to quarantine failed; it is still incoming
+    Stuck,
+}
+
+impl fmt::Display for Disposition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Disposition::Accepted => "accepted",
+            Disposition::Rejected => "rejected",
+            Disposition::Quarantined => "quarantined",
+            Disposition::Stuck => "stuck",
+        })
+    }
+}
+
+/// One incoming recipe and what became of it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngestEntry {
+    /// Name in the incoming directory
+    pub file: String,
+    pub disposition: Disposition,
+    /// Where the recipe is (or, in a dry run, would be) now
+    pub destination: PathBuf,
+    /// Whether a file of the same name was there and the recipe got a
+    /// content-hash suffix
+    pub renamed: bool,
+    /// Retries spent on this recipe's reads, writes and moves
+    pub retries: u32,
+    pub errors: usize,
+    pub warnings: usize,
+    /// The IO error that sent the recipe to quarantine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_error: Option<String>,
+}
+
+/// Outcome of an ingestion run, written as its manifest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IngestReport {
+    pub incoming: PathBuf,
+    pub output: PathBuf,
+    pub dry_run: bool,
+    pub meta: ValidationMeta,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub quarantined: usize,
+    pub stuck: usize,
+    /// In file-name order
+    pub entries: Vec<IngestEntry>,
+    /// Failures of the run itself: listing the incoming directory, writing
+    /// the manifest
+    pub errors: Vec<String>,
+}
+
+impl IngestReport {
+    /// Whether every recipe was accepted or rejected and the run itself
+    /// had no failures
+    pub fn clean(&self) -> bool {
+        self.quarantined == 0 && self.stuck == 0 && self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for IngestReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for entry in &self.entries {
+            write!(f, "{:<12} {} -> {}", entry.disposition, entry.file, entry.destination.display())?;
+            if entry.retries > 0 {
+                write!(f, " after {} retries", entry.retries)?;
+            }
+            if let Some(error) = &entry.io_error {
+                write!(f, " ({})", error)?;
+            }
+            writeln!(f)?;
+        }
+        for error in &self.errors {
+            writeln!(f, "Error: {}", error)?;
+        }
+        writeln!(
+            f,
+            "{}{} accepted, {} rejected, {} quarantined, {} stuck",
+            if self.dry_run { "Dry run: " } else { "" },
+            self.accepted,
+            self.rejected,
+            self.quarantined,
+            self.stuck
+        )
+    }
+}
+
+/// Sidecar of a rejected recipe
+fn issues_json(file: &str, result: &ValidationResult) -> Vec<u8> {
+    let issues: Vec<serde_json::Value> = result
+        .issues
+        .iter()
+        .map(|issue| {
+            json!({
+                "code": issue.code,
+                "severity": issue.severity,
+                "path": issue.location(),
+                "message": i18n::render(&result.locale, issue.code, &issue.params),
+            })
+        })
+        .collect();
+    let sidecar = json!({"file": file, "valid": result.valid, "issues": issues, "meta": result.meta});
+    (serde_json::to_string_pretty(&sidecar).unwrap() + "\n").into_bytes()
+}
+
+/// `name` with `tag` inserted before its extension
+fn suffixed(name: &str, tag: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => format!("{}.{}.{}", stem, tag, extension),
+        _ => format!("{}.{}", name, tag),
+    }
+}
+
+struct Run<'c, 'a> {
+    config: &'c IngestConfig<'a>,
+}
+
+impl Run<'_, '_> {
+    /// Run an IO operation, retrying failures with backoff
+    fn retrying<T>(&self, retries: &mut u32, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut delay = self.config.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match operation() {
+                Err(_) if attempt < self.config.retries => {
+                    attempt += 1;
+                    *retries += 1;
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                done => return done,
+            }
+        }
+    }
+
+    /// Free path for `file` in `dir`, and whether it needed a suffix
+    fn destination(&self, dir: &str, file: &str, bytes: &[u8]) -> (PathBuf, bool) {
+        let dir = self.config.output.join(dir);
+        let plain = dir.join(file);
+        if !self.config.io.exists(&plain) {
+            return (plain, false);
+        }
+        let digest = &sha256_hex(bytes)[..12];
+        let mut candidate = dir.join(suffixed(file, digest));
+        let mut copy = 2;
+        while self.config.io.exists(&candidate) {
+            candidate = dir.join(suffixed(file, &format!("{}-{}", digest, copy)));
+            copy += 1;
+        }
+        (candidate, true)
+    }
+
+    /// Move a recipe into `dir`, creating it first
+    fn place(&self, source: &Path, destination: &Path, retries: &mut u32) -> io::Result<()> {
+        if self.config.dry_run {
+            return Ok(());
+        }
+        let io = self.config.io;
+        if let Some(dir) = destination.parent() {
+            self.retrying(retries, || io.create_dir_all(dir))?;
+        }
+        self.retrying(retries, || io.rename(source, destination))
+    }
+
+    fn ingest(&self, file: &str) -> IngestEntry {
+        let io = self.config.io;
+        let source = self.config.incoming.join(file);
+        let mut retries = 0;
+
+        let placed = self.retrying(&mut retries, || io.read(&source)).and_then(|bytes| {
+            let result = self.config.validator.validate_bytes(&bytes);
+            let (errors, warnings) = (count(&result, Severity::Error), count(&result, Severity::Warning));
+            let (disposition, dir) = if result.valid { (Disposition::Accepted, ACCEPTED) } else { (Disposition::Rejected, REJECTED) };
+            let (destination, renamed) = self.destination(dir, file, &bytes);
+            if !result.valid && !self.config.dry_run {
+                let name = destination.file_name().unwrap().to_string_lossy();
+                let sidecar = destination.with_file_name(format!("{}.issues.json", name));
+                let contents = issues_json(file, &result);
+                self.retrying(&mut retries, || io.create_dir_all(destination.parent().unwrap()))?;
+                self.retrying(&mut retries, || io.write_atomic(&sidecar, &contents))?;
+                if let Err(e) = self.place(&source, &destination, &mut retries) {
+                    let _ = io.remove(&sidecar);
+                    return Err(e);
+                }
+            } else {
+                self.place(&source, &destination, &mut retries)?;
+            }
+            Ok(IngestEntry { file: file.to_string(), disposition, destination, renamed, retries, errors, warnings, io_error: None })
+        });
+
+        placed.unwrap_or_else(|e| {
+            // The bytes could not be read, so the name decides collisions
+            let (destination, renamed) = self.destination(QUARANTINE, file, file.as_bytes());
+            let mut entry = IngestEntry {
+                file: file.to_string(),
+                disposition: Disposition::Quarantined,
+                destination,
+                renamed,
+                retries,
+                errors: 0,
+                warnings: 0,
+                io_error: Some(e.to_string()),
+            };
+            if let Err(e) = self.place(&source, &entry.destination, &mut entry.retries) {
+                entry.disposition = Disposition::Stuck;
+                entry.destination = source;
+                entry.io_error = Some(format!("{}; moving to quarantine failed: {}", entry.io_error.unwrap_or_default(), e));
+            }
+            entry
+        })
+    }
+}
+
+fn count(result: &ValidationResult, severity: Severity) -> usize {
+    result.issues.iter().filter(|i| i.severity == severity).count()
+}
+
+/// Validate every recipe in the incoming directory and file it as accepted,
+/// rejected or quarantined
+pub fn run(config: IngestConfig) -> IngestReport {
+    let mut report = IngestReport {
+        incoming: config.incoming.clone(),
+        output: config.output.clone(),
+        dry_run: config.dry_run,
+        meta: config.validator.meta(),
+        accepted: 0,
+        rejected: 0,
+        quarantined: 0,
+        stuck: 0,
+        entries: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    let run = Run { config: &config };
+    let mut listing_retries = 0;
+    match run.retrying(&mut listing_retries, || config.io.list(&config.incoming)) {
+        Ok(mut names) => {
+            names.retain(|name| is_recipe_name(name) && !name.starts_with('.'));
+            names.sort();
+            report.entries = names.iter().map(|name| run.ingest(name)).collect();
+        }
+        Err(e) => report.errors.push(format!("cannot list {}: {}", config.incoming.display(), e)),
+    }
+
+    for entry in &report.entries {
+        match entry.disposition {
+            Disposition::Accepted => report.accepted += 1,
+            Disposition::Rejected => report.rejected += 1,
+            Disposition::Quarantined => report.quarantined += 1,
+            Disposition::Stuck => report.stuck += 1,
+        }
+    }
+
+    if let Some(manifest) = &config.manifest {
+        let contents = serde_json::to_string_pretty(&report).unwrap() + "\n";
+        let mut retries = 0;
+        if let Err(e) = run.retrying(&mut retries, || config.io.write_atomic(manifest, contents.as_bytes())) {
+            report.errors.push(format!("cannot write manifest {}: {}", manifest.display(), e));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codes, warm};
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    fn example() -> Vec<u8> {
+        fs::read("../../examples/simple-example.rcip").unwrap()
+    }
+
+    /// The local filesystem, with operations on some files failing a given
+    /// number of times first
+    #[derive(Default)]
+    struct FlakyIo {
+        failures: RefCell<HashMap<(&'static str, String), u32>>,
+    }
+
+    impl FlakyIo {
+        fn fail(self, operation: &'static str, file: &str, times: u32) -> Self {
+            self.failures.borrow_mut().insert((operation, file.to_string()), times);
+            self
+        }
+
+        fn check(&self, operation: &'static str, path: &Path) -> io::Result<()> {
+            let file = path.file_name().unwrap().to_string_lossy().into_owned();
+            match self.failures.borrow_mut().get_mut(&(operation, file)) {
+                Some(left) if *left > 0 => {
+                    *left -= 1;
+                    Err(io::Error::new(io::ErrorKind::Interrupted, format!("injected {} failure", operation)))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    impl IngestIo for FlakyIo {
+        fn list(&self, dir: &Path) -> io::Result<Vec<String>> {
+            LocalIo.list(dir)
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            self.check("read", path)?;
+            LocalIo.read(path)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            LocalIo.exists(path)
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            LocalIo.create_dir_all(path)
+        }
+
+        fn write_atomic(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+            self.check("write", path)?;
+            LocalIo.write_atomic(path, bytes)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            self.check("rename", from)?;
+            LocalIo.rename(from, to)
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            LocalIo.remove(path)
+        }
+    }
+
+    fn config<'a>(dir: &Path, io: &'a dyn IngestIo) -> IngestConfig<'a> {
+        let mut config = IngestConfig::new(dir.join("incoming"), warm::global_validator("0.1").unwrap());
+        config.output = dir.to_path_buf();
+        config.retry_delay = Duration::ZERO;
+        config.io = io;
+        config
+    }
+
+    fn drop_files(dir: &Path, files: &[(&str, &[u8])]) {
+        fs::create_dir_all(dir.join("incoming")).unwrap();
+        for (name, bytes) in files {
+            fs::write(dir.join("incoming").join(name), bytes).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_files_recipes_and_suffixes_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let recipe = example();
+        drop_files(dir.path(), &[("eggs.rcip", &recipe), ("broken.rcip", b"{"), ("notes.txt", b"")]);
+        fs::create_dir_all(dir.path().join(ACCEPTED)).unwrap();
+        fs::write(dir.path().join(ACCEPTED).join("eggs.rcip"), b"earlier").unwrap();
+
+        let mut config = config(dir.path(), &LocalIo);
+        config.manifest = Some(dir.path().join("manifest.json"));
+        let report = run(config);
+        assert!(report.clean(), "{}", report);
+        assert_eq!((report.accepted, report.rejected), (1, 1));
+
+        let eggs = &report.entries[1];
+        let suffixed = format!("eggs.{}.rcip", &sha256_hex(&recipe)[..12]);
+        assert!(eggs.renamed);
+        assert_eq!(eggs.destination, dir.path().join(ACCEPTED).join(&suffixed));
+        assert_eq!(fs::read(&eggs.destination).unwrap(), recipe);
+        assert_eq!(fs::read(dir.path().join(ACCEPTED).join("eggs.rcip")).unwrap(), b"earlier");
+
+        let rejected = dir.path().join(REJECTED);
+        assert!(rejected.join("broken.rcip").is_file());
+        let sidecar: serde_json::Value = serde_json::from_slice(&fs::read(rejected.join("broken.rcip.issues.json")).unwrap()).unwrap();
+        assert_eq!(sidecar["issues"][0]["code"], codes::READ_FAILED);
+        assert_eq!(sidecar["valid"], false);
+
+        assert_eq!(LocalIo.list(&dir.path().join("incoming")).unwrap(), ["notes.txt"]);
+        let manifest: serde_json::Value = serde_json::from_slice(&fs::read(dir.path().join("manifest.json")).unwrap()).unwrap();
+        assert_eq!(manifest["entries"][0]["disposition"], "rejected");
+        assert_eq!(manifest["accepted"], 1);
+    }
+
+    #[test]
+    fn test_retries_then_quarantines_io_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        let recipe = example();
+        drop_files(dir.path(), &[("a.rcip", &recipe), ("b.rcip", &recipe), ("c.rcip", &recipe), ("d.rcip", b"{")]);
+        let io = FlakyIo::default()
+            .fail("read", "a.rcip", 2)
+            .fail("read", "b.rcip", 10)
+            .fail("rename", "c.rcip", 10)
+            .fail("write", "d.rcip.issues.json", 4);
+
+        let report = run(config(dir.path(), &io));
+        let outcomes: Vec<(Disposition, u32)> = report.entries.iter().map(|e| (e.disposition, e.retries)).collect();
+        assert_eq!(outcomes, [
+            (Disposition::Accepted, 2),
+            (Disposition::Quarantined, 3),
+            (Disposition::Stuck, 6),
+            (Disposition::Quarantined, 3),
+        ]);
+        assert!(!report.clean());
+        assert!(dir.path().join(QUARANTINE).join("b.rcip").is_file());
+        assert!(dir.path().join(QUARANTINE).join("d.rcip").is_file());
+        assert!(!dir.path().join(REJECTED).join("d.rcip.issues.json").exists());
+        assert!(dir.path().join("incoming").join("c.rcip").is_file());
+        assert!(report.entries[1].io_error.as_deref().unwrap().contains("injected read failure"));
+
+        // Later quarantined copies of the same name get a suffix and then a
+        // counter, never replacing an earlier copy
+        let mut destinations = vec![dir.path().join(QUARANTINE).join("b.rcip")];
+        for _ in 0..3 {
+            drop_files(dir.path(), &[("b.rcip", &recipe)]);
+            let io = FlakyIo::default().fail("read", "b.rcip", 10);
+            let report = run(config(dir.path(), &io));
+            let b = report.entries.iter().find(|e| e.file == "b.rcip").unwrap();
+            assert!(b.renamed && b.destination.is_file());
+            destinations.push(b.destination.clone());
+        }
+        let digest = &sha256_hex(b"b.rcip")[..12];
+        let names: Vec<String> = destinations.iter().map(|d| d.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, [
+            "b.rcip".to_string(),
+            format!("b.{}.rcip", digest),
+            format!("b.{}-2.rcip", digest),
+            format!("b.{}-3.rcip", digest),
+        ]);
+        assert_eq!(LocalIo.list(&dir.path().join(QUARANTINE)).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_dry_run_moves_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        drop_files(dir.path(), &[("eggs.rcip", &example()), ("broken.rcip", b"{")]);
+
+        let mut config = config(dir.path(), &LocalIo);
+        config.dry_run = true;
+        let report = run(config);
+        assert_eq!((report.accepted, report.rejected), (1, 1));
+        assert_eq!(report.entries[1].destination, dir.path().join(ACCEPTED).join("eggs.rcip"));
+        assert_eq!(LocalIo.list(&dir.path().join("incoming")).unwrap().len(), 2);
+        assert!(!dir.path().join(ACCEPTED).exists() && !dir.path().join(REJECTED).exists());
+    }
+}
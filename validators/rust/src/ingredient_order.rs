@@ -0,0 +1,304 @@
+// Ingredient listing order
+//
+// Style guides, and labelling rules for packaged food, list ingredients in
+// descending order of quantity by mass. Machine amounts in mass units are
+// compared in grams; volumes, counts and special units cannot be compared
+// without a density and are treated as gaps: they neither violate the order
+// nor get moved by the sorter. Ingredients sharing a `group` ("For the
+// sauce") are ordered within their group only, a group being a contiguous run
+// of ingredients with the same `group` value.
+//
+// Separately, lists usually follow the order steps use the ingredients. The
+// usage-order notice measures that as the Spearman rank correlation between
+// list position and first-use step, and only speaks up when it is strongly
+// negative; many good recipes ignore the convention, so it is off by default.
+
+use crate::scaling::measured;
+use crate::{codes, ValidationResult};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// How `sort_ingredients` orders each group
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Heaviest first; ingredients without a mass stay where they are
+    ByAmountDesc,
+    /// Alphabetically by name, ignoring case
+    ByName,
+    /// In the order steps first reference them; unreferenced ones last
+    ByUsageOrder,
+}
+
+/// Machine amount in grams, for mass units only
+pub fn grams(ingredient: &Value) -> Option<f64> {
+    let ma = ingredient.get("machine_amount")?;
+    let value = ma.get("value")?.as_f64()?;
+    let contents = measured(value, ma.get("unit")?.as_str()?)?;
+    (contents.ml == 0.0).then_some(contents.grams)
+}
+
+/// Index of the first step referencing each ingredient id
+pub fn first_use(recipe: &Value) -> HashMap<String, usize> {
+    let mut first: HashMap<String, usize> = HashMap::new();
+    for (index, step) in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        for target in step.get("target").and_then(|v| v.as_array()).into_iter().flatten() {
+            if let Some(id) = target.as_str().filter(|t| t.starts_with("ing-")) {
+                first.entry(id.to_string()).or_insert(index);
+            }
+        }
+    }
+    first
+}
+
+/// Contiguous runs of ingredients sharing a `group`
+fn groups(ingredients: &[Value]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (index, ingredient) in ingredients.iter().enumerate() {
+        let group = ingredient.get("group");
+        match runs.last_mut() {
+            Some(run) if ingredients[run.start].get("group") == group => run.end = index + 1,
+            _ => runs.push(index..index + 1),
+        }
+    }
+    runs
+}
+
+fn id(ingredient: &Value) -> String {
+    ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string()
+}
+
+/// Warn about the first pair of mass-measured ingredients listed lighter
+/// before heavier
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    let ingredients = match recipe.get("ingredients").and_then(|v| v.as_array()) {
+        Some(ingredients) => ingredients,
+        None => return,
+    };
+
+    for run in groups(ingredients) {
+        let mut previous: Option<(&Value, f64)> = None;
+        for ingredient in &ingredients[run] {
+            let grams = match grams(ingredient) {
+                Some(grams) => grams,
+                None => continue,
+            };
+            if let Some((before, before_grams)) = previous {
+                if grams > before_grams {
+                    result.warning(codes::INGREDIENT_ORDER, &[
+                        ("first", &id(before)),
+                        ("first_g", &before_grams),
+                        ("second", &id(ingredient)),
+                        ("second_g", &grams),
+                    ]);
+                    return;
+                }
+            }
+            previous = Some((ingredient, grams));
+        }
+    }
+}
+
+/// Correlations at or below this get a usage-order notice
+pub const USAGE_ORDER_THRESHOLD: f64 = -0.5;
+
+/// How closely the listing order follows the order of first use
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageCorrelation {
+    /// Spearman's rho, from -1 (reversed) to 1 (same order)
+    pub rho: f64,
+    /// Ids of up to three ingredients whose list and use ranks differ most
+    pub out_of_place: Vec<String>,
+}
+
+/// Ranks starting at 1, ties sharing their average rank
+fn ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].partial_cmp(&values[*b]).unwrap_or(Ordering::Equal));
+
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for i in &order[start..end] {
+            ranks[*i] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+/// Rank correlation between list position and first-use step, over the
+/// ingredients steps reference; `None` below three or without any spread
+pub fn usage_correlation(recipe: &Value) -> Option<UsageCorrelation> {
+    let usage = first_use(recipe);
+    let used: Vec<(String, f64, f64)> = recipe.get("ingredients")?
+        .as_array()?
+        .iter()
+        .enumerate()
+        .filter_map(|(position, ing)| {
+            let id = id(ing);
+            let step = *usage.get(&id)?;
+            Some((id, position as f64, step as f64))
+        })
+        .collect();
+    if used.len() < 3 {
+        return None;
+    }
+
+    let listed = ranks(&used.iter().map(|u| u.1).collect::<Vec<_>>());
+    let first = ranks(&used.iter().map(|u| u.2).collect::<Vec<_>>());
+    let mean = (used.len() as f64 + 1.0) / 2.0;
+    let (mut covariance, mut var_listed, mut var_first) = (0.0, 0.0, 0.0);
+    for (a, b) in listed.iter().zip(&first) {
+        covariance += (a - mean) * (b - mean);
+        var_listed += (a - mean).powi(2);
+        var_first += (b - mean).powi(2);
+    }
+    if var_listed == 0.0 || var_first == 0.0 {
+        return None;
+    }
+
+    let mut displaced: Vec<(f64, &str)> = used
+        .iter()
+        .zip(listed.iter().zip(&first))
+        .map(|(u, (a, b))| ((a - b).abs(), u.0.as_str()))
+        .collect();
+    displaced.sort_by(|x, y| y.0.partial_cmp(&x.0).unwrap_or(Ordering::Equal));
+
+    Some(UsageCorrelation {
+        rho: covariance / (var_listed * var_first).sqrt(),
+        out_of_place: displaced.iter().take(3).map(|(_, id)| id.to_string()).collect(),
+    })
+}
+
+/// Note a listing order strongly opposed to the order of use
+pub fn check_usage(recipe: &Value, result: &mut ValidationResult) {
+    if let Some(correlation) = usage_correlation(recipe) {
+        if correlation.rho <= USAGE_ORDER_THRESHOLD {
+            let rho = format!("{:.2}", correlation.rho);
+            result.notice(codes::USAGE_ORDER, &[("rho", &rho), ("ingredients", &correlation.out_of_place.join(", "))]);
+        }
+    }
+}
+
+/// Reorder the ingredient list within each group. Ids and every other field
+/// are left untouched.
+pub fn sort_ingredients(recipe: &mut Value, order: SortOrder) {
+    let usage = first_use(recipe);
+    let ingredients = match recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()) {
+        Some(ingredients) => ingredients,
+        None => return,
+    };
+
+    for run in groups(ingredients) {
+        let slice = &mut ingredients[run];
+        match order {
+            SortOrder::ByAmountDesc => {
+                // Only mass-measured ingredients move, among their own slots
+                let slots: Vec<usize> = (0..slice.len()).filter(|i| grams(&slice[*i]).is_some()).collect();
+                let mut weighed: Vec<Value> = slots.iter().map(|i| slice[*i].take()).collect();
+                weighed.sort_by(|a, b| grams(b).partial_cmp(&grams(a)).unwrap_or(Ordering::Equal));
+                for (slot, ingredient) in slots.into_iter().zip(weighed) {
+                    slice[slot] = ingredient;
+                }
+            }
+            SortOrder::ByName => slice.sort_by_cached_key(|ing| {
+                ing.get("name").and_then(|v| v.as_str()).unwrap_or("").to_lowercase()
+            }),
+            SortOrder::ByUsageOrder => slice.sort_by_key(|ing| usage.get(&id(ing)).copied().unwrap_or(usize::MAX)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "salt", "machine_amount": {"value": 10, "unit": "g"}},
+                {"id": "ing-0002", "name": "Water", "machine_amount": {"value": 325, "unit": "ml"}},
+                {"id": "ing-0003", "name": "flour", "machine_amount": {"value": 0.5, "unit": "kg"}},
+                {"id": "ing-0004", "name": "tomatoes", "group": "sauce", "machine_amount": {"value": 400, "unit": "g"}},
+                {"id": "ing-0005", "name": "basil", "group": "sauce", "machine_amount": {"value": 5, "unit": "g"}}
+            ],
+            "steps": [
+                {"step_id": "s-01", "target": ["ing-0004", "ing-0005"]},
+                {"step_id": "s-02", "target": ["ing-0003", "ing-0002"]}
+            ]
+        })
+    }
+
+    fn ids(recipe: &Value) -> Vec<&str> {
+        recipe["ingredients"].as_array().unwrap().iter().map(|i| i["id"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_first_out_of_order_pair() {
+        let mut result = ValidationResult::new();
+        check(&recipe(), &mut result);
+        assert_eq!(result.warnings, vec!["Ingredient ing-0003 (500 g) is listed after lighter ing-0001 (10 g)"]);
+
+        let mut sorted = recipe();
+        sort_ingredients(&mut sorted, SortOrder::ByAmountDesc);
+        let mut result = ValidationResult::new();
+        check(&sorted, &mut result);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_usage_correlation() {
+        let mut recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001"}, {"id": "ing-0002"}, {"id": "ing-0003"}, {"id": "ing-0004"}, {"id": "ing-0005"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "target": ["ing-0005"]},
+                {"step_id": "s-02", "target": ["ing-0004", "ing-0003"]},
+                {"step_id": "s-03", "target": ["ing-0001"]},
+                {"step_id": "s-04", "target": ["ing-0002"]}
+            ]
+        });
+
+        let correlation = usage_correlation(&recipe).unwrap();
+        // Ranks listed 1..5 vs used 4, 5, 2.5, 2.5, 1: rho = -8.5 / sqrt(10 * 9.5)
+        assert!((correlation.rho + 0.872).abs() < 0.01, "{}", correlation.rho);
+        assert_eq!(correlation.out_of_place, vec!["ing-0005", "ing-0001", "ing-0002"]);
+
+        let mut result = ValidationResult::new();
+        check_usage(&recipe, &mut result);
+        assert!(result.valid);
+        assert_eq!(result.notices, vec![
+            "Ingredients are listed roughly in reverse order of use (rank correlation -0.87); most out of place: ing-0005, ing-0001, ing-0002"
+        ]);
+
+        sort_ingredients(&mut recipe, SortOrder::ByUsageOrder);
+        // The two ingredients first used together in s-02 keep it just under 1
+        assert!(usage_correlation(&recipe).unwrap().rho > 0.97);
+    }
+
+    #[test]
+    fn test_sorting_stays_within_groups() {
+        let mut by_amount = recipe();
+        sort_ingredients(&mut by_amount, SortOrder::ByAmountDesc);
+        // The volume-measured water keeps its slot
+        assert_eq!(ids(&by_amount), vec!["ing-0003", "ing-0002", "ing-0001", "ing-0004", "ing-0005"]);
+
+        let mut by_name = recipe();
+        sort_ingredients(&mut by_name, SortOrder::ByName);
+        assert_eq!(ids(&by_name), vec!["ing-0003", "ing-0001", "ing-0002", "ing-0005", "ing-0004"]);
+
+        let mut by_usage = recipe();
+        sort_ingredients(&mut by_usage, SortOrder::ByUsageOrder);
+        assert_eq!(ids(&by_usage), vec!["ing-0002", "ing-0003", "ing-0001", "ing-0004", "ing-0005"]);
+        assert_eq!(by_usage["ingredients"][1]["machine_amount"], json!({"value": 0.5, "unit": "kg"}));
+    }
+}
@@ -0,0 +1,232 @@
+// Named step results
+//
+// A step may describe its intermediate product in a `result` object: a name
+// ("marinade", "dough"), a description, how long it can wait before use
+// (`holdable_minutes`) and its approximate mass. Later steps target a named
+// result as `@marinade`, which means the same as `s-04:result`; everything
+// that follows result targets resolves both forms through `producer`, as well
+// as their portions, `@dough[2]` and `s-04:result[2]` (see `portions`).
+//
+// Names must be unique across steps. Holding times are checked against the
+// timeline from `simulate::schedule`: the wait is from the end of the
+// producing step to the start of each step using the result.
+
+use crate::portions::{self, portion_count};
+use crate::simulate::schedule;
+use crate::{codes, ValidationResult};
+use regex::{NoExpand, Regex};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const EPSILON: f64 = 1e-6;
+
+/// The intermediate a step declares in its `result` object
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intermediate<'a> {
+    pub step_id: &'a str,
+    pub name: &'a str,
+    pub description: Option<&'a str>,
+    pub holdable_minutes: Option<f64>,
+    pub approx_mass_g: Option<f64>,
+}
+
+fn steps(recipe: &Value) -> impl Iterator<Item = &Value> {
+    recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+fn targets(step: &Value) -> impl Iterator<Item = &str> {
+    step.get("target").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|t| t.as_str())
+}
+
+/// Named results in step order
+pub fn intermediates(recipe: &Value) -> Vec<Intermediate<'_>> {
+    steps(recipe)
+        .filter_map(|step| {
+            let result = step.get("result")?;
+            Some(Intermediate {
+                step_id: step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?"),
+                name: result.get("name").and_then(|v| v.as_str())?,
+                description: result.get("description").and_then(|v| v.as_str()),
+                holdable_minutes: result.get("holdable_minutes").and_then(|v| v.as_f64()),
+                approx_mass_g: result.get("approx_mass_g").and_then(|v| v.as_f64()),
+            })
+        })
+        .collect()
+}
+
+/// Step whose result a target refers to, for `s-01:result` and `@name`
+/// targets and their portions. None for ingredient targets and unknown names
+pub fn producer<'a>(recipe: &'a Value, target: &'a str) -> Option<&'a str> {
+    let target = portions::whole(target);
+    if let Some(step_id) = target.strip_suffix(":result") {
+        return Some(step_id);
+    }
+    let name = target.strip_prefix('@')?;
+    intermediates(recipe).into_iter().find(|i| i.name == name).map(|i| i.step_id)
+}
+
+/// Replace `@name` and `s-01:result` references in text with "the name"
+/// wherever the producing step names its result, and portion references
+/// with "one of the 4 portions of the name"
+pub fn friendly_text(recipe: &Value, text: &str) -> String {
+    let mut named = intermediates(recipe);
+    let name_of = |step_id: &str| named.iter().find(|i| i.step_id == step_id).map(|i| i.name);
+    let mut out = text.to_string();
+
+    // Portions first, while their references are still whole. A divide step
+    // rarely names its own result; its portions are of what it divides
+    for step in steps(recipe).filter(|s| s.get("action").and_then(|v| v.as_str()) == Some("divide")) {
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+        let own = name_of(step_id);
+        let divided = own.or_else(|| targets(step).filter_map(|t| producer(recipe, t)).find_map(name_of));
+        let portion = match (portion_count(step), divided) {
+            (Some(count), Some(name)) => format!("one of the {} portions of the {}", count, name),
+            (Some(count), None) => format!("one of the {} portions", count),
+            (None, Some(name)) => format!("a portion of the {}", name),
+            (None, None) => "a portion".to_string(),
+        };
+        let mut forms = vec![regex::escape(&format!("{}:result", step_id))];
+        if let Some(name) = own {
+            forms.push(regex::escape(&format!("@{}", name)));
+        }
+        if let Ok(pattern) = Regex::new(&format!(r"(?:{})\[\d+\]", forms.join("|"))) {
+            out = pattern.replace_all(&out, NoExpand(&portion)).into_owned();
+        }
+    }
+
+    // Longest first, so `@dough` does not clip `@dough-starter`
+    named.sort_by_key(|n| std::cmp::Reverse(n.name.len()));
+    for intermediate in named {
+        let friendly = format!("the {}", intermediate.name);
+        out = out
+            .replace(&format!("@{}", intermediate.name), &friendly)
+            .replace(&format!("{}:result", intermediate.step_id), &friendly);
+    }
+    out
+}
+
+/// Report result names used twice and `@name` targets naming no result
+pub fn check_references(recipe: &Value, result: &mut ValidationResult) {
+    let mut first: HashMap<&str, &str> = HashMap::new();
+    for intermediate in intermediates(recipe) {
+        if let Some(other) = first.insert(intermediate.name, intermediate.step_id) {
+            result.error(codes::DUPLICATE_RESULT_NAME, &[
+                ("step", &intermediate.step_id),
+                ("name", &intermediate.name),
+                ("other", &other),
+            ]);
+            first.insert(intermediate.name, other);
+        }
+    }
+
+    for step in steps(recipe) {
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+        for name in targets(step).filter_map(|t| portions::whole(t).strip_prefix('@')) {
+            if !first.contains_key(name) {
+                result.error(codes::UNKNOWN_RESULT_NAME, &[("step", &step_id), ("name", &name)]);
+            }
+        }
+    }
+}
+
+/// Warn when the schedule leaves an intermediate waiting longer than it holds
+pub fn check_holding(recipe: &Value, result: &mut ValidationResult) {
+    let holdable: HashMap<&str, (&str, f64)> = intermediates(recipe)
+        .into_iter()
+        .filter_map(|i| Some((i.step_id, (i.name, i.holdable_minutes?))))
+        .collect();
+    if holdable.is_empty() {
+        return;
+    }
+
+    let slots = schedule(recipe);
+    let ends: HashMap<&str, f64> = slots.iter().map(|s| (s.step_id.as_str(), s.end_minutes)).collect();
+
+    for (step, slot) in steps(recipe).zip(&slots) {
+        for producer_id in targets(step).filter_map(|t| producer(recipe, t)) {
+            let (name, limit) = match holdable.get(producer_id) {
+                Some(hold) => *hold,
+                None => continue,
+            };
+            let wait = slot.start_minutes - ends.get(producer_id).copied().unwrap_or(slot.start_minutes);
+            if wait > limit + EPSILON {
+                result.warning(codes::RESULT_HELD_TOO_LONG, &[
+                    ("step", &slot.step_id),
+                    ("name", &name),
+                    ("producer", &producer_id),
+                    ("minutes", &format!("{:.0}", wait)),
+                    ("holdable", &limit),
+                ]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [{"id": "ing-0001"}, {"id": "ing-0002"}],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "human_text": "Whisk the marinade", "target": ["ing-0001"],
+                 "params": {"time_minutes": 5}, "result": {"name": "marinade", "holdable_minutes": 30}},
+                {"step_id": "s-02", "action": "simmer", "human_text": "Simmer the stock", "target": ["ing-0002"],
+                 "params": {"time_minutes": 60}, "result": {"name": "stock"}},
+                {"step_id": "s-03", "action": "combine", "human_text": "Pour @marinade into s-02:result",
+                 "target": ["@marinade", "s-02:result"]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_named_targets_resolve() {
+        let recipe = recipe();
+        assert_eq!(producer(&recipe, "@marinade"), Some("s-01"));
+        assert_eq!(producer(&recipe, "s-02:result"), Some("s-02"));
+        assert_eq!(producer(&recipe, "@gravy"), None);
+        assert_eq!(producer(&recipe, "ing-0001"), None);
+        assert_eq!(friendly_text(&recipe, "Pour @marinade into s-02:result"), "Pour the marinade into the stock");
+
+        let mut divided = recipe.clone();
+        divided["steps"][2] = json!({"step_id": "s-03", "action": "divide", "target": ["@marinade"], "params": {"portions": 4}});
+        assert_eq!(producer(&divided, "s-03:result[2]"), Some("s-03"));
+        assert_eq!(friendly_text(&divided, "Take s-03:result[2]"), "Take one of the 4 portions of the marinade");
+
+        // s-03 waits for the stock, ready at minute 65, but only for the
+        // marinade when that is all it uses
+        assert_eq!(schedule(&recipe)[2].start_minutes, 65.0);
+        let mut marinade_only = recipe.clone();
+        marinade_only["steps"][2]["target"] = json!(["@marinade"]);
+        assert_eq!(schedule(&marinade_only)[2].start_minutes, 5.0);
+    }
+
+    #[test]
+    fn test_duplicate_and_unknown_names() {
+        let mut recipe = recipe();
+        recipe["steps"][1]["result"]["name"] = json!("marinade");
+        recipe["steps"][2]["target"] = json!(["@marinade", "@gravy"]);
+
+        let mut result = ValidationResult::new();
+        check_references(&recipe, &mut result);
+        assert_eq!(result.errors, vec![
+            "Step s-02: Result name 'marinade' is already used by s-01",
+            "Step s-03: No step result is named 'gravy'",
+        ]);
+    }
+
+    #[test]
+    fn test_holding_time() {
+        let mut result = ValidationResult::new();
+        check_holding(&recipe(), &mut result);
+        assert_eq!(result.warnings, vec!["Step s-03: The marinade from s-01 waits 60 min but holds only 30 min"]);
+
+        let mut relaxed = recipe();
+        relaxed["steps"][0]["result"]["holdable_minutes"] = json!(90);
+        let mut result = ValidationResult::new();
+        check_holding(&relaxed, &mut result);
+        assert!(result.warnings.is_empty());
+    }
+}
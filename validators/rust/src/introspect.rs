@@ -0,0 +1,116 @@
+// Schema introspection
+//
+// Answers questions about a loaded JSON Schema by document path rather than
+// by schema structure: "is `/ingredients/*/allergens` required", "which
+// values does `/steps/*/action` allow". Paths use `*` for array items.
+// Local `$ref`s (`#/...`) are followed; anything the walk cannot follow
+// (remote refs, combinators) answers `None`, so callers treat it as unknown.
+
+use serde_json::Value;
+
+/// Follow local `$ref`s until a node without one
+fn resolve<'a>(schema: &'a Value, mut node: &'a Value) -> &'a Value {
+    for _ in 0..32 {
+        match node.get("$ref").and_then(|r| r.as_str()).and_then(|r| r.strip_prefix('#')) {
+            Some(pointer) => match schema.pointer(pointer) {
+                Some(target) => node = target,
+                None => break,
+            },
+            None => break,
+        }
+    }
+    node
+}
+
+/// Schema node describing the value at a document path
+pub fn node_at<'a>(schema: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut node = resolve(schema, schema);
+    for segment in path.trim_start_matches('/').split('/').filter(|s| !s.is_empty()) {
+        let next = if segment == "*" {
+            node.get("items")?
+        } else {
+            node.get("properties")?.get(segment)?
+        };
+        node = resolve(schema, next);
+    }
+    Some(node)
+}
+
+fn split_last(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Whether the schema requires the field at `path` whenever its parent exists
+pub fn is_required(schema: &Value, path: &str) -> Option<bool> {
+    let (parent, field) = split_last(path);
+    let parent = node_at(schema, parent)?;
+    let required = parent.get("required").and_then(|r| r.as_array());
+    Some(required.map(|r| r.iter().any(|f| f.as_str() == Some(field))).unwrap_or(false))
+}
+
+/// Values allowed by an `enum` at `path`; for arrays, the values of the items
+pub fn enum_values<'a>(schema: &'a Value, path: &str) -> Option<Vec<&'a str>> {
+    let mut node = node_at(schema, path)?;
+    if node.get("enum").is_none() {
+        node = resolve(schema, node.get("items")?);
+    }
+    Some(node.get("enum")?.as_array()?.iter().filter_map(|v| v.as_str()).collect())
+}
+
+/// Regex `pattern` of the string at `path`
+pub fn pattern<'a>(schema: &'a Value, path: &str) -> Option<&'a str> {
+    node_at(schema, path)?.get("pattern")?.as_str()
+}
+
+/// Property names the schema declares for the object at `path`
+pub fn properties<'a>(schema: &'a Value, path: &str) -> Option<Vec<&'a str>> {
+    Some(node_at(schema, path)?.get("properties")?.as_object()?.keys().map(|k| k.as_str()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["ingredients"],
+            "properties": {
+                "id": {"type": "string", "pattern": "^rcip-"},
+                "ingredients": {"type": "array", "items": {"$ref": "#/$defs/ingredient"}}
+            },
+            "$defs": {
+                "ingredient": {
+                    "type": "object",
+                    "required": ["id", "allergens"],
+                    "properties": {
+                        "id": {"type": "string"},
+                        "allergens": {"type": "array", "items": {"type": "string", "enum": ["milk", "eggs"]}}
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_paths_through_refs() {
+        let schema = schema();
+        assert_eq!(is_required(&schema, "/ingredients"), Some(true));
+        assert_eq!(is_required(&schema, "/ingredients/*/allergens"), Some(true));
+        assert_eq!(is_required(&schema, "/id"), Some(false));
+        assert_eq!(is_required(&schema, "/steps/*/action"), None);
+        assert_eq!(enum_values(&schema, "/ingredients/*/allergens"), Some(vec!["milk", "eggs"]));
+        assert_eq!(pattern(&schema, "/id"), Some("^rcip-"));
+        assert_eq!(properties(&schema, "/ingredients/*"), Some(vec!["allergens", "id"]));
+    }
+
+    #[test]
+    fn test_ref_cycles_terminate() {
+        let schema = json!({"$ref": "#", "properties": {}});
+        assert!(node_at(&schema, "/anything").is_none());
+    }
+}
@@ -0,0 +1,365 @@
+// Validation journal for audits
+//
+// Food-safety audits ask for proof that every published recipe version was
+// validated, by which validator, with what result. A journal is an
+// append-only JSON-lines file: each entry names the file, the SHA-256 of its
+// bytes, the `ValidationMeta` of the result and the outcome, and carries the
+// hash of the entry before it and a hash over itself. Editing, removing or
+// reordering an entry breaks the chain at that entry, and a cut through the
+// last line leaves it incomplete; `verify` reports the first entry affected.
+// Dropping whole entries from the end leaves a valid, shorter chain, so
+// auditors keep the `head` hash of each summary and compare later summaries
+// against it.
+//
+// The hash recorded is the one the validator took of the bytes it parsed
+// (`set_fingerprint_files`); the file is never read a second time, so a file
+// changed between validation and recording cannot be journaled under a
+// result that was not about its content.
+//
+// Writers take an exclusive lock on the file for the read-append cycle, so
+// concurrent validation runs never interleave or fork the chain; `verify`
+// takes a shared lock.
+
+use crate::provenance::{sha256_hex, ValidationMeta};
+use crate::{RCIPError, ValidationResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// `prev` of the first entry
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A validated file, as the journal records it
+#[derive(Debug, Clone)]
+pub struct FileValidation {
+    pub path: PathBuf,
+    /// SHA-256 of the file's bytes
+    pub recipe_sha256: String,
+    pub result: ValidationResult,
+}
+
+impl FileValidation {
+    pub fn new(path: &Path, bytes: &[u8], result: ValidationResult) -> Self {
+        FileValidation { path: path.to_path_buf(), recipe_sha256: sha256_hex(bytes), result }
+    }
+
+    /// Record a file by the `source_sha256` of its result: the hash of the
+    /// bytes that were validated, not of whatever is on disk by now. Results
+    /// validated without `set_fingerprint_files` have none and are refused
+    pub fn validated(path: &Path, result: ValidationResult) -> Result<Self, RCIPError> {
+        let recipe_sha256 = result.source_sha256.clone().ok_or_else(|| {
+            RCIPError::ValidationError(format!("{}: validated without a fingerprint of its bytes", path.display()))
+        })?;
+        Ok(FileValidation { path: path.to_path_buf(), recipe_sha256, result })
+    }
+}
+
+/// What a validation concluded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub valid: bool,
+    pub errors: usize,
+    pub warnings: usize,
+    pub notices: usize,
+    /// Issue codes, in result order
+    pub codes: Vec<String>,
+}
+
+/// One line of a journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Position in the journal, from 1
+    pub seq: usize,
+    /// Hash of the previous entry
+    pub prev: String,
+    pub file: String,
+    pub recipe_sha256: String,
+    pub meta: Option<ValidationMeta>,
+    pub outcome: Outcome,
+    /// SHA-256 over the entry with this field left out
+    #[serde(default)]
+    pub hash: String,
+}
+
+impl JournalEntry {
+    fn compute_hash(&self) -> String {
+        let mut unsealed = self.clone();
+        unsealed.hash = String::new();
+        let mut value = serde_json::to_value(&unsealed).unwrap_or(Value::Null);
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("hash");
+        }
+        sha256_hex(value.to_string().as_bytes())
+    }
+}
+
+/// A journal that verified, end to end
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JournalSummary {
+    pub entries: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    /// Hash of the last entry; keep it to detect later removal of entries
+    pub head: Option<String>,
+}
+
+impl fmt::Display for JournalSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} entries intact ({} valid, {} invalid)", self.entries, self.valid, self.invalid)?;
+        if let Some(head) = &self.head {
+            writeln!(f, "head {}", head)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a journal failed verification; entries count from 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum JournalCorruption {
+    /// The journal could not be read at all
+    Unreadable(String),
+    /// A line that is not a journal entry
+    Malformed { entry: usize, error: String },
+    /// An entry whose content no longer matches its hash
+    Tampered { entry: usize },
+    /// An entry that does not follow the one before it: entries before it
+    /// were removed, reordered or rewritten
+    BrokenChain { entry: usize },
+    /// The last line was cut off
+    Truncated { entry: usize },
+}
+
+impl JournalCorruption {
+    /// First corrupted entry, if the journal could be read
+    pub fn entry(&self) -> Option<usize> {
+        match self {
+            JournalCorruption::Unreadable(_) => None,
+            JournalCorruption::Malformed { entry, .. }
+            | JournalCorruption::Tampered { entry }
+            | JournalCorruption::BrokenChain { entry }
+            | JournalCorruption::Truncated { entry } => Some(*entry),
+        }
+    }
+}
+
+impl fmt::Display for JournalCorruption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalCorruption::Unreadable(e) => write!(f, "cannot read journal: {}", e),
+            JournalCorruption::Malformed { entry, error } => write!(f, "entry {} is not a journal entry: {}", entry, error),
+            JournalCorruption::Tampered { entry } => write!(f, "entry {} was modified after it was written", entry),
+            JournalCorruption::BrokenChain { entry } => {
+                write!(f, "entry {} does not follow the entry before it; entries were removed or reordered", entry)
+            }
+            JournalCorruption::Truncated { entry } => write!(f, "entry {} is incomplete; the journal was truncated", entry),
+        }
+    }
+}
+
+impl Error for JournalCorruption {}
+
+/// An append-only, hash-chained validation log
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Open a journal, creating an empty one if the file does not exist
+    pub fn open(path: &Path) -> Result<Self, RCIPError> {
+        OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Journal { path: path.to_path_buf() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry for a validated file
+    pub fn record(&self, validation: &FileValidation) -> Result<JournalEntry, RCIPError> {
+        let mut file = OpenOptions::new().read(true).append(true).open(&self.path)?;
+        file.lock()?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let (seq, prev) = match last_entry(&content)? {
+            Some(last) => (last.seq + 1, last.hash),
+            None => (1, GENESIS.to_string()),
+        };
+
+        let result = &validation.result;
+        let mut entry = JournalEntry {
+            seq,
+            prev,
+            file: validation.path.display().to_string(),
+            recipe_sha256: validation.recipe_sha256.clone(),
+            meta: result.meta.clone(),
+            outcome: Outcome {
+                valid: result.valid,
+                errors: result.errors.len(),
+                warnings: result.warnings.len(),
+                notices: result.notices.len(),
+                codes: result.issues.iter().map(|i| i.code.to_string()).collect(),
+            },
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        file.write_all(format!("{}\n", serde_json::to_string(&entry)?).as_bytes())?;
+        file.sync_all()?;
+        file.unlock()?;
+        Ok(entry)
+    }
+
+    /// Check every entry and the chain between them
+    pub fn verify(&self) -> Result<JournalSummary, JournalCorruption> {
+        let unreadable = |e: std::io::Error| JournalCorruption::Unreadable(e.to_string());
+        let mut file = File::open(&self.path).map_err(unreadable)?;
+        file.lock_shared().map_err(unreadable)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content).map_err(unreadable)?;
+        verify_content(&content)
+    }
+}
+
+fn last_entry(content: &str) -> Result<Option<JournalEntry>, RCIPError> {
+    if !content.is_empty() && !content.ends_with('\n') {
+        return Err(RCIPError::ValidationError("journal ends in an incomplete entry; verify it before appending".to_string()));
+    }
+    match content.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => Ok(Some(serde_json::from_str(line)?)),
+        None => Ok(None),
+    }
+}
+
+fn verify_content(content: &str) -> Result<JournalSummary, JournalCorruption> {
+    let mut summary = JournalSummary { entries: 0, valid: 0, invalid: 0, head: None };
+    let mut prev = GENESIS.to_string();
+    let lines: Vec<&str> = content.split_terminator('\n').collect();
+
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 1;
+        let entry: JournalEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(_) if number == lines.len() && !content.ends_with('\n') => {
+                return Err(JournalCorruption::Truncated { entry: number })
+            }
+            Err(e) => return Err(JournalCorruption::Malformed { entry: number, error: e.to_string() }),
+        };
+        if entry.hash != entry.compute_hash() {
+            return Err(JournalCorruption::Tampered { entry: number });
+        }
+        if entry.prev != prev || entry.seq != number {
+            return Err(JournalCorruption::BrokenChain { entry: number });
+        }
+        if entry.outcome.valid {
+            summary.valid += 1;
+        } else {
+            summary.invalid += 1;
+        }
+        summary.entries += 1;
+        prev = entry.hash;
+    }
+
+    summary.head = (summary.entries > 0).then_some(prev);
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+
+    fn validation(name: &str, valid: bool) -> FileValidation {
+        let mut result = ValidationResult::new();
+        if !valid {
+            result.error(crate::codes::MISSING_ALLERGENS, &[("ingredient", &"flour")]);
+        }
+        FileValidation::new(Path::new(name), name.as_bytes(), result)
+    }
+
+    fn journal_with(entries: usize) -> (tempfile::TempDir, Journal) {
+        let dir = tempfile::tempdir().unwrap();
+        let journal = Journal::open(&dir.path().join("audit.log")).unwrap();
+        for n in 0..entries {
+            journal.record(&validation(&format!("recipe-{}.rcip", n), n % 2 == 0)).unwrap();
+        }
+        (dir, journal)
+    }
+
+    #[test]
+    fn test_record_and_verify() {
+        let (_dir, journal) = journal_with(3);
+        let summary = journal.verify().unwrap();
+        assert_eq!((summary.entries, summary.valid, summary.invalid), (3, 2, 1));
+
+        let content = fs::read_to_string(journal.path()).unwrap();
+        let entries: Vec<JournalEntry> = content.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(entries[0].prev, GENESIS);
+        assert_eq!(entries[2].prev, entries[1].hash);
+        assert_eq!(summary.head.as_ref(), Some(&entries[2].hash));
+        assert_eq!(entries[1].outcome.codes, vec![crate::codes::MISSING_ALLERGENS]);
+    }
+
+    #[test]
+    fn test_detects_tampering_removal_and_truncation() {
+        let (_dir, journal) = journal_with(4);
+        let original = fs::read_to_string(journal.path()).unwrap();
+        let lines: Vec<&str> = original.lines().collect();
+
+        fs::write(journal.path(), original.replacen("\"valid\":false", "\"valid\":true", 1)).unwrap();
+        assert_eq!(journal.verify(), Err(JournalCorruption::Tampered { entry: 2 }));
+
+        fs::write(journal.path(), format!("{}\n{}\n{}\n", lines[0], lines[2], lines[3])).unwrap();
+        assert_eq!(journal.verify(), Err(JournalCorruption::BrokenChain { entry: 2 }));
+
+        fs::write(journal.path(), &original[..original.len() - 20]).unwrap();
+        assert_eq!(journal.verify().unwrap_err().entry(), Some(4));
+        assert!(matches!(journal.verify(), Err(JournalCorruption::Truncated { entry: 4 })));
+        assert!(journal.record(&validation("late.rcip", true)).is_err());
+    }
+
+    #[test]
+    fn test_records_the_bytes_that_were_validated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("soup.rcip");
+        let validated = br#"{"meta": {"name": "Soup"}, "ingredients": [], "steps": []}"#;
+        fs::write(&path, validated).unwrap();
+
+        let mut validator = crate::RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let unfingerprinted = validator.validate_file(&path).unwrap();
+        validator.set_fingerprint_files(true);
+        let result = validator.validate_file(&path).unwrap();
+        // A change after validation must not end up in the journal
+        fs::write(&path, b"{}").unwrap();
+
+        let validation = FileValidation::validated(&path, result).unwrap();
+        assert_eq!(validation.recipe_sha256, sha256_hex(validated));
+        assert!(FileValidation::validated(&path, unfingerprinted).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_writers_keep_the_chain() {
+        let (_dir, journal) = journal_with(0);
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let journal = journal.clone();
+                thread::spawn(move || {
+                    for n in 0..10 {
+                        journal.record(&validation(&format!("w{}-{}.rcip", w, n), true)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(journal.verify().unwrap().entries, 40);
+    }
+}
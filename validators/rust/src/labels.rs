@@ -0,0 +1,329 @@
+// Regulatory label exports
+//
+// EU FIC (Regulation 1169/2011, Annex II) mapping decisions:
+// - `lactose` is not one of the 14 regulated allergens; it is a milk
+//   derivative and is declared under "milk".
+// - `wheat` and `gluten` both map to "cereals containing gluten"; the cereal
+//   is named when it can be inferred (wheat, rye, barley, oats, spelt, kamut).
+// - `tree-nuts` maps to "nuts"; the specific nut is named when it can be
+//   inferred from the ingredient names.
+// - `shellfish` maps to "crustaceans" and `sulphites` to "sulphur dioxide
+//   and sulphites".
+// - "May contain" entries come from ingredients' `may_contain_allergens`,
+//   from `meta.facility_allergens` (source "facility") and from steps flagged
+//   with the `allergen-cross-contact` hazard, which list the allergens at risk
+//   in `cross_contact_allergens`. Anything already declared as contained is
+//   not repeated in the "may contain" clause.
+// - "Contains" entries are the contained allergens whose provenance the
+//   given `allergens::ProvenancePolicy` considers; `eu_fic` uses the default
+//   one, so allergens only inferred from names or resolvers are left out.
+
+use crate::allergens::{provenance, AllergenSource, ProvenancePolicy};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+mod nutrition;
+
+pub use nutrition::{
+    compute_nutrition, nutrition_facts, NutrientRow, NutritionError, NutritionPanel, NutritionSummary, UncoveredIngredient,
+    MIN_COVERAGE,
+};
+
+/// The 14 allergens regulated by EU FIC, in Annex II order
+pub const EU_FIC_ALLERGENS: [&str; 14] = [
+    "cereals containing gluten",
+    "crustaceans",
+    "eggs",
+    "fish",
+    "peanuts",
+    "soybeans",
+    "milk",
+    "nuts",
+    "celery",
+    "mustard",
+    "sesame seeds",
+    "sulphur dioxide and sulphites",
+    "lupin",
+    "molluscs",
+];
+
+pub(crate) const NUT_KEYWORDS: &[(&str, &str)] = &[
+    ("almond", "almonds"),
+    ("hazelnut", "hazelnuts"),
+    ("walnut", "walnuts"),
+    ("cashew", "cashews"),
+    ("pecan", "pecan nuts"),
+    ("brazil", "Brazil nuts"),
+    ("pistachio", "pistachio nuts"),
+    ("macadamia", "macadamia nuts"),
+];
+
+pub(crate) const CEREAL_KEYWORDS: &[(&str, &str)] = &[
+    ("wheat", "wheat"),
+    ("rye", "rye"),
+    ("barley", "barley"),
+    ("oat", "oats"),
+    ("spelt", "spelt"),
+    ("kamut", "kamut"),
+];
+
+/// Map an RCIP allergen onto its EU FIC regulated name
+pub fn eu_fic_name(allergen: &str) -> Option<&'static str> {
+    match allergen {
+        "wheat" | "gluten" => Some("cereals containing gluten"),
+        "shellfish" => Some("crustaceans"),
+        "eggs" => Some("eggs"),
+        "fish" => Some("fish"),
+        "peanuts" => Some("peanuts"),
+        "soybeans" => Some("soybeans"),
+        "milk" | "lactose" => Some("milk"),
+        "tree-nuts" => Some("nuts"),
+        "celery" => Some("celery"),
+        "mustard" => Some("mustard"),
+        "sesame" => Some("sesame seeds"),
+        "sulphites" => Some("sulphur dioxide and sulphites"),
+        "lupins" => Some("lupin"),
+        "molluscs" => Some("molluscs"),
+        _ => None,
+    }
+}
+
+/// A regulated allergen on a label
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FicAllergen {
+    /// Regulated name from Annex II
+    pub name: String,
+    /// Specific cereal or nut, when inferable
+    pub details: Vec<String>,
+    /// Ingredient ids (or step ids for cross-contact) the allergen comes from
+    pub sources: Vec<String>,
+}
+
+/// EU FIC allergen declaration for a recipe
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FicLabel {
+    pub contains: Vec<FicAllergen>,
+    pub may_contain: Vec<FicAllergen>,
+    pub text: String,
+}
+
+#[derive(Default)]
+struct Entry {
+    details: BTreeSet<String>,
+    sources: BTreeSet<String>,
+}
+
+/// Build an EU FIC allergen label from a recipe
+pub fn eu_fic(recipe: &Value) -> FicLabel {
+    eu_fic_with(recipe, &ProvenancePolicy::default())
+}
+
+/// Build an EU FIC allergen label, declaring the contained allergens whose
+/// provenance the policy considers
+pub fn eu_fic_with(recipe: &Value, policy: &ProvenancePolicy) -> FicLabel {
+    let mut contains: BTreeMap<&'static str, Entry> = BTreeMap::new();
+    let mut may_contain: BTreeMap<&'static str, Entry> = BTreeMap::new();
+
+    let names: BTreeMap<&str, String> = recipe
+        .get("ingredients")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|i| {
+            let name = i.get("name").and_then(|v| v.as_str()).unwrap_or("").to_lowercase();
+            Some((i.get("id")?.as_str()?, name))
+        })
+        .collect();
+    for entry in provenance(recipe) {
+        let regulated = match eu_fic_name(&entry.allergen) {
+            Some(regulated) => regulated,
+            None => continue,
+        };
+        for source in entry.sources.iter().filter(|s| policy.considers(s.provenance())) {
+            let id = match source {
+                AllergenSource::Declared { ingredient, .. } | AllergenSource::Inferred { ingredient, .. } => ingredient,
+                AllergenSource::Inherited { recipe } => recipe,
+            };
+            let label = contains.entry(regulated).or_default();
+            label.sources.insert(id.to_string());
+            let name = names.get(id.as_str()).map(String::as_str).unwrap_or("");
+            label.details.extend(infer_details(&entry.allergen, name));
+        }
+    }
+
+    let mut traces: Vec<(&str, &str)> = Vec::new();
+    for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+        let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?");
+        traces.extend(string_array(ingredient.get("may_contain_allergens")).into_iter().map(|a| (id, a)));
+    }
+    traces.extend(string_array(recipe.pointer("/meta/facility_allergens")).into_iter().map(|a| ("facility", a)));
+    for (source, allergen) in traces {
+        if let Some(regulated) = eu_fic_name(allergen) {
+            if !contains.contains_key(regulated) {
+                may_contain.entry(regulated).or_default().sources.insert(source.to_string());
+            }
+        }
+    }
+
+    if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
+        for step in steps {
+            let flagged = string_array(step.get("hazards")).contains(&"allergen-cross-contact");
+            if !flagged {
+                continue;
+            }
+            let id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+            for allergen in string_array(step.get("cross_contact_allergens")) {
+                if let Some(regulated) = eu_fic_name(allergen) {
+                    if !contains.contains_key(regulated) {
+                        may_contain.entry(regulated).or_default().sources.insert(id.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let contains = into_ordered(contains);
+    let may_contain = into_ordered(may_contain);
+    let text = render_text(&contains, &may_contain);
+
+    FicLabel { contains, may_contain, text }
+}
+
+fn string_array(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+fn infer_details(allergen: &str, ingredient_name: &str) -> Vec<String> {
+    let keywords = match allergen {
+        "tree-nuts" => NUT_KEYWORDS,
+        "wheat" | "gluten" => CEREAL_KEYWORDS,
+        _ => return Vec::new(),
+    };
+
+    let mut details: Vec<String> = keywords
+        .iter()
+        .filter(|(keyword, _)| ingredient_name.contains(keyword))
+        .map(|(_, detail)| detail.to_string())
+        .collect();
+
+    if allergen == "wheat" && details.is_empty() {
+        details.push("wheat".to_string());
+    }
+    details
+}
+
+fn into_ordered(entries: BTreeMap<&'static str, Entry>) -> Vec<FicAllergen> {
+    let mut allergens: Vec<FicAllergen> = entries
+        .into_iter()
+        .map(|(name, entry)| FicAllergen {
+            name: name.to_string(),
+            details: entry.details.into_iter().collect(),
+            sources: entry.sources.into_iter().collect(),
+        })
+        .collect();
+
+    allergens.sort_by_key(|a| EU_FIC_ALLERGENS.iter().position(|n| *n == a.name));
+    allergens
+}
+
+fn emphasized(allergen: &FicAllergen) -> String {
+    if allergen.details.is_empty() {
+        allergen.name.to_uppercase()
+    } else {
+        format!("{} ({})", allergen.name.to_uppercase(), allergen.details.join(", ").to_uppercase())
+    }
+}
+
+fn render_text(contains: &[FicAllergen], may_contain: &[FicAllergen]) -> String {
+    let mut parts = Vec::new();
+
+    if contains.is_empty() {
+        parts.push("Contains no allergens requiring declaration.".to_string());
+    } else {
+        let names: Vec<String> = contains.iter().map(emphasized).collect();
+        parts.push(format!("Contains: {}.", names.join(", ")));
+    }
+
+    if !may_contain.is_empty() {
+        let names: Vec<String> = may_contain.iter().map(emphasized).collect();
+        parts.push(format!("May contain: {}.", names.join(", ")));
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_eu_fic_mapping_and_details() {
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "Whole wheat flour", "allergens": ["wheat", "gluten"]},
+                {"id": "ing-0002", "name": "Butter", "allergens": ["milk", "lactose"]},
+                {"id": "ing-0003", "name": "Toasted almonds", "allergens": ["tree-nuts"]}
+            ],
+            "steps": []
+        });
+
+        let label = eu_fic(&recipe);
+        let names: Vec<&str> = label.contains.iter().map(|a| a.name.as_str()).collect();
+
+        assert_eq!(names, vec!["cereals containing gluten", "milk", "nuts"]);
+        assert_eq!(label.contains[0].details, vec!["wheat"]);
+        assert_eq!(label.contains[2].details, vec!["almonds"]);
+        assert_eq!(label.text, "Contains: CEREALS CONTAINING GLUTEN (WHEAT), MILK, NUTS (ALMONDS).");
+    }
+
+    #[test]
+    fn test_lactose_alone_is_declared_as_milk() {
+        let recipe = json!({"ingredients": [
+            {"id": "ing-0001", "name": "lactose powder", "allergens": ["lactose"]}
+        ]});
+        let label = eu_fic(&recipe);
+        assert_eq!(label.contains.len(), 1);
+        assert_eq!(label.contains[0].name, "milk");
+    }
+
+    #[test]
+    fn test_may_contain_from_cross_contact_steps() {
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "allergens": ["gluten"]}
+            ],
+            "steps": [
+                {"step_id": "s-01", "hazards": ["allergen-cross-contact"],
+                 "cross_contact_allergens": ["peanuts", "gluten"]}
+            ]
+        });
+
+        let label = eu_fic(&recipe);
+        assert_eq!(label.may_contain.len(), 1);
+        assert_eq!(label.may_contain[0].name, "peanuts");
+        assert_eq!(label.may_contain[0].sources, vec!["s-01"]);
+        assert!(label.text.ends_with("May contain: PEANUTS."));
+    }
+
+    #[test]
+    fn test_may_contain_from_ingredients_and_facility() {
+        let recipe = json!({
+            "meta": {"facility_allergens": ["sesame", "milk"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "oats", "allergens": [], "may_contain_allergens": ["gluten"]},
+                {"id": "ing-0002", "name": "butter", "allergens": ["milk"]}
+            ]
+        });
+
+        let label = eu_fic(&recipe);
+        let names: Vec<&str> = label.may_contain.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["cereals containing gluten", "sesame seeds"]);
+        assert_eq!(label.may_contain[0].sources, vec!["ing-0001"]);
+        assert_eq!(label.may_contain[1].sources, vec!["facility"]);
+    }
+}
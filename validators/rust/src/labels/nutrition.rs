@@ -0,0 +1,666 @@
+// US FDA-style Nutrition Facts panel
+//
+// Amounts are aggregated from each ingredient's `nutritional.per_100g` data
+// scaled by its machine amount (converted to grams; volumes and counts have
+// no weight without a density), or from its `nutritional.per_serving` data,
+// the ingredient's share of one serving, times the servings. They are
+// divided per serving, then rounded following 21 CFR 101.9. Percent daily values use the 2016 reference values and are
+// computed from unrounded amounts. Nutrients no ingredient declares are left
+// off the panel rather than shown as zero. When optional ingredients
+// contribute, the panel also carries the figures without them.
+//
+// `compute_nutrition` gives the same figures unrounded, with the ingredients
+// they leave out and how much of the recipe they cover, so a caller can tell
+// how complete the estimate is. Coverage is the lower of the share of the
+// weighed mass and the share of all ingredients with usable data: a volume or
+// count without data has no weight, but it still leaves the estimate short.
+// `add` sums the nutrition of a recipe's components.
+
+use crate::units::Dimension;
+use crate::{is_optional, Unit};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Errors building a nutrition panel
+#[derive(Debug, Clone, PartialEq)]
+pub enum NutritionError {
+    /// `meta.servings.amount` is missing or not positive
+    MissingServings,
+    /// No ingredient has usable nutrition data
+    NoNutritionData,
+}
+
+impl fmt::Display for NutritionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NutritionError::MissingServings => write!(f, "meta.servings.amount must be a positive number"),
+            NutritionError::NoNutritionData => write!(f, "no ingredient has nutrition data with a mass amount"),
+        }
+    }
+}
+
+impl std::error::Error for NutritionError {}
+
+/// How an amount is rounded for the panel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rounding {
+    Fat,
+    Sodium,
+    Gram,
+    Mineral,
+}
+
+struct NutrientDef {
+    key: &'static str,
+    label: &'static str,
+    unit: &'static str,
+    daily_value: Option<f64>,
+    rounding: Rounding,
+}
+
+const NUTRIENTS: &[NutrientDef] = &[
+    NutrientDef { key: "fat", label: "Total Fat", unit: "g", daily_value: Some(78.0), rounding: Rounding::Fat },
+    NutrientDef { key: "saturated_fat", label: "Saturated Fat", unit: "g", daily_value: Some(20.0), rounding: Rounding::Fat },
+    NutrientDef { key: "sodium", label: "Sodium", unit: "mg", daily_value: Some(2300.0), rounding: Rounding::Sodium },
+    NutrientDef { key: "carbs", label: "Total Carbohydrate", unit: "g", daily_value: Some(275.0), rounding: Rounding::Gram },
+    NutrientDef { key: "fiber", label: "Dietary Fiber", unit: "g", daily_value: Some(28.0), rounding: Rounding::Gram },
+    NutrientDef { key: "sugar", label: "Total Sugars", unit: "g", daily_value: None, rounding: Rounding::Gram },
+    NutrientDef { key: "protein", label: "Protein", unit: "g", daily_value: None, rounding: Rounding::Gram },
+    NutrientDef { key: "calcium", label: "Calcium", unit: "mg", daily_value: Some(1300.0), rounding: Rounding::Mineral },
+];
+
+/// One nutrient row on the panel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NutrientRow {
+    pub name: String,
+    /// Rounded amount per serving
+    pub amount: f64,
+    pub unit: String,
+    /// True when the amount is declared as "less than" `amount`
+    pub less_than: bool,
+    pub percent_dv: Option<u32>,
+}
+
+impl NutrientRow {
+    /// Amount as printed on the panel, e.g. `4.5g` or `<1g`
+    pub fn display_amount(&self) -> String {
+        if self.less_than {
+            format!("<{}{}", format_number(self.amount), self.unit)
+        } else {
+            format!("{}{}", format_number(self.amount), self.unit)
+        }
+    }
+}
+
+/// A rendered Nutrition Facts panel
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NutritionPanel {
+    pub serving_size: String,
+    pub serving_size_g: f64,
+    pub servings_per_container: f64,
+    pub calories: Option<f64>,
+    pub rows: Vec<NutrientRow>,
+    /// The same panel leaving out optional ingredients, if any were counted
+    pub without_optional: Option<Box<NutritionPanel>>,
+}
+
+/// Build a Nutrition Facts panel for a recipe
+pub fn nutrition_facts(recipe: &Value) -> Result<NutritionPanel, NutritionError> {
+    let mut panel = build(recipe, true)?;
+    let counted_optional = recipe.get("ingredients")
+        .and_then(|v| v.as_array())
+        .map(|ingredients| ingredients.iter().any(|ing| is_optional(ing) && ingredient_grams(ing).is_some()))
+        .unwrap_or(false);
+    if counted_optional {
+        panel.without_optional = build(recipe, false).ok().map(Box::new);
+    }
+    Ok(panel)
+}
+
+/// Coverage `RecipeInfo` requires before it reports nutrition
+pub const MIN_COVERAGE: f64 = 0.8;
+
+/// An ingredient without nutrition data the figures could use
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UncoveredIngredient {
+    pub id: String,
+    pub name: String,
+    /// Weight, when the machine amount is a mass
+    pub grams: Option<f64>,
+    /// Share of the recipe's weighed mass, 0 to 1
+    pub share: Option<f64>,
+}
+
+/// Unrounded per-serving amounts, for comparing recipes rather than labeling
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NutritionSummary {
+    pub servings: f64,
+    pub calories: Option<f64>,
+    pub fat_g: Option<f64>,
+    pub saturated_fat_g: Option<f64>,
+    pub sodium_mg: Option<f64>,
+    pub carbs_g: Option<f64>,
+    pub fiber_g: Option<f64>,
+    pub sugar_g: Option<f64>,
+    pub protein_g: Option<f64>,
+    pub calcium_mg: Option<f64>,
+    /// How much of the recipe the figures cover, 0 to 1: the lower of the
+    /// share of the weighed mass and the share of the ingredients
+    pub coverage: f64,
+    /// Ingredients the figures leave out
+    pub uncovered: Vec<UncoveredIngredient>,
+}
+
+impl NutritionSummary {
+    /// The whole recipe's amounts: each figure times `servings`, as one
+    /// serving
+    pub fn total(&self) -> NutritionSummary {
+        let times = |value: Option<f64>| value.map(|v| v * self.servings);
+        NutritionSummary {
+            servings: 1.0,
+            calories: times(self.calories),
+            fat_g: times(self.fat_g),
+            saturated_fat_g: times(self.saturated_fat_g),
+            sodium_mg: times(self.sodium_mg),
+            carbs_g: times(self.carbs_g),
+            fiber_g: times(self.fiber_g),
+            sugar_g: times(self.sugar_g),
+            protein_g: times(self.protein_g),
+            calcium_mg: times(self.calcium_mg),
+            coverage: self.coverage,
+            uncovered: self.uncovered.clone(),
+        }
+    }
+
+    /// The sum with a component's nutrition, per serving of this recipe: the
+    /// component's whole amounts go into these servings. Coverage is the
+    /// lower of the two and the uncovered ingredients are those of both
+    pub fn add(&self, other: &NutritionSummary) -> NutritionSummary {
+        let whole = other.total();
+        let sum = |mine: Option<f64>, theirs: Option<f64>| match (mine, theirs) {
+            (Some(a), Some(b)) => Some(a + b / self.servings),
+            (a, b) => a.or(b.map(|b| b / self.servings)),
+        };
+        NutritionSummary {
+            servings: self.servings,
+            calories: sum(self.calories, whole.calories),
+            fat_g: sum(self.fat_g, whole.fat_g),
+            saturated_fat_g: sum(self.saturated_fat_g, whole.saturated_fat_g),
+            sodium_mg: sum(self.sodium_mg, whole.sodium_mg),
+            carbs_g: sum(self.carbs_g, whole.carbs_g),
+            fiber_g: sum(self.fiber_g, whole.fiber_g),
+            sugar_g: sum(self.sugar_g, whole.sugar_g),
+            protein_g: sum(self.protein_g, whole.protein_g),
+            calcium_mg: sum(self.calcium_mg, whole.calcium_mg),
+            coverage: self.coverage.min(other.coverage),
+            uncovered: self.uncovered.iter().chain(&other.uncovered).cloned().collect(),
+        }
+    }
+}
+
+impl fmt::Display for NutritionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let figures: Vec<String> = [
+            (self.calories, "kcal", ""),
+            (self.protein_g, "g", " protein"),
+            (self.fat_g, "g", " fat"),
+            (self.carbs_g, "g", " carbs"),
+            (self.sodium_mg, "mg", " sodium"),
+        ]
+        .iter()
+        .filter_map(|(value, unit, name)| value.map(|v| format!("{:.0} {}{}", v, unit, name)))
+        .collect();
+        write!(f, "{} per serving ({:.0}% covered)", figures.join(", "), self.coverage * 100.0)
+    }
+}
+
+/// Per-serving nutrition of a recipe, optional ingredients included as on
+/// the panel, with the ingredients it does not cover
+pub fn compute_nutrition(recipe: &Value) -> Result<NutritionSummary, NutritionError> {
+    let totals = accumulate(recipe, true)?;
+    let per_serving = |key: &str| {
+        let i = NUTRIENTS.iter().position(|def| def.key == key)?;
+        totals.nutrients[i].map(|t| t / totals.servings)
+    };
+    let by_mass = match totals.grams {
+        grams if grams > 0.0 => totals.covered_grams / grams,
+        _ if totals.uncovered.is_empty() => 1.0,
+        _ => 0.0,
+    };
+    let by_count = 1.0 - totals.uncovered.len() as f64 / totals.ingredients as f64;
+    let coverage = by_mass.min(by_count);
+    Ok(NutritionSummary {
+        servings: totals.servings,
+        calories: totals.calories.map(|c| c / totals.servings),
+        fat_g: per_serving("fat"),
+        saturated_fat_g: per_serving("saturated_fat"),
+        sodium_mg: per_serving("sodium"),
+        carbs_g: per_serving("carbs"),
+        fiber_g: per_serving("fiber"),
+        sugar_g: per_serving("sugar"),
+        protein_g: per_serving("protein"),
+        calcium_mg: per_serving("calcium"),
+        coverage,
+        uncovered: totals.uncovered.clone(),
+    })
+}
+
+/// Whole-recipe amounts, in `NUTRIENTS` order
+struct Totals {
+    servings: f64,
+    serving_unit: String,
+    grams: f64,
+    covered_grams: f64,
+    /// Ingredients counted, with or without data
+    ingredients: usize,
+    calories: Option<f64>,
+    nutrients: Vec<Option<f64>>,
+    uncovered: Vec<UncoveredIngredient>,
+}
+
+fn accumulate(recipe: &Value, include_optional: bool) -> Result<Totals, NutritionError> {
+    let servings = recipe.get("meta")
+        .and_then(|m| m.get("servings"))
+        .and_then(|s| s.get("amount"))
+        .and_then(|v| v.as_f64())
+        .filter(|s| *s > 0.0)
+        .ok_or(NutritionError::MissingServings)?;
+
+    let serving_unit = recipe.get("meta")
+        .and_then(|m| m.get("servings"))
+        .and_then(|s| s.get("unit"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("serving");
+
+    let mut total_grams = 0.0;
+    let mut covered_grams = 0.0;
+    let mut calories: Option<f64> = None;
+    let mut totals: Vec<Option<f64>> = vec![None; NUTRIENTS.len()];
+    let mut uncovered = Vec::new();
+    let mut covered = false;
+    let mut counted = 0;
+
+    for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+        if !include_optional && is_optional(ingredient) {
+            continue;
+        }
+        let grams = ingredient_grams(ingredient);
+        total_grams += grams.unwrap_or(0.0);
+        counted += 1;
+
+        let nutritional = ingredient.get("nutritional");
+        let per_100g = nutritional.and_then(|n| n.get("per_100g"));
+        let per_serving = nutritional.and_then(|n| n.get("per_serving"));
+        let (data, factor) = match (per_100g, grams, per_serving) {
+            (Some(per_100g), Some(grams), _) => (per_100g, grams / 100.0),
+            (_, _, Some(per_serving)) => (per_serving, servings),
+            _ => {
+                let text = |key: &str| ingredient.get(key).and_then(|v| v.as_str()).unwrap_or("?").to_string();
+                uncovered.push(UncoveredIngredient { id: text("id"), name: text("name"), grams, share: None });
+                continue;
+            }
+        };
+        covered = true;
+        covered_grams += grams.unwrap_or(0.0);
+
+        if let Some(kcal) = data.get("calories").and_then(|v| v.as_f64()) {
+            *calories.get_or_insert(0.0) += kcal * factor;
+        }
+        for (def, total) in NUTRIENTS.iter().zip(totals.iter_mut()) {
+            if let Some(value) = data.get(def.key).and_then(|v| v.as_f64()) {
+                *total.get_or_insert(0.0) += value * factor;
+            }
+        }
+    }
+
+    if !covered {
+        return Err(NutritionError::NoNutritionData);
+    }
+    for ingredient in &mut uncovered {
+        ingredient.share = ingredient.grams.filter(|_| total_grams > 0.0).map(|grams| grams / total_grams);
+    }
+
+    Ok(Totals {
+        servings,
+        serving_unit: serving_unit.to_string(),
+        grams: total_grams,
+        covered_grams,
+        ingredients: counted,
+        calories,
+        nutrients: totals,
+        uncovered,
+    })
+}
+
+fn build(recipe: &Value, include_optional: bool) -> Result<NutritionPanel, NutritionError> {
+    let totals = accumulate(recipe, include_optional)?;
+    let servings = totals.servings;
+
+    let rows = NUTRIENTS
+        .iter()
+        .zip(totals.nutrients)
+        .filter_map(|(def, total)| total.map(|t| nutrient_row(def, t / servings)))
+        .collect();
+
+    Ok(NutritionPanel {
+        serving_size: format!("1 {}", totals.serving_unit),
+        serving_size_g: (totals.grams / servings).round(),
+        servings_per_container: servings,
+        calories: totals.calories.map(|c| round_calories(c / servings)),
+        rows,
+        without_optional: None,
+    })
+}
+
+fn ingredient_grams(ingredient: &Value) -> Option<f64> {
+    let ma = ingredient.get("machine_amount")?;
+    let value = ma.get("value")?.as_f64()?;
+    let unit = Unit::parse(ma.get("unit")?.as_str()?).filter(|unit| unit.dimension() == Dimension::Mass)?;
+    Some(unit.to_base(value))
+}
+
+fn nutrient_row(def: &NutrientDef, amount: f64) -> NutrientRow {
+    let (rounded, less_than) = match def.rounding {
+        Rounding::Fat => (round_fat(amount), false),
+        Rounding::Sodium => (round_sodium(amount), false),
+        Rounding::Gram => round_grams(amount),
+        Rounding::Mineral => (round_to(amount, 10.0), false),
+    };
+
+    let percent_dv = def.daily_value.map(|dv| {
+        let percent = amount / dv * 100.0;
+        if def.rounding == Rounding::Mineral {
+            round_mineral_percent(percent)
+        } else {
+            percent.round() as u32
+        }
+    });
+
+    NutrientRow {
+        name: def.label.to_string(),
+        amount: rounded,
+        unit: def.unit.to_string(),
+        less_than,
+        percent_dv,
+    }
+}
+
+fn round_to(value: f64, step: f64) -> f64 {
+    (value / step).round() * step
+}
+
+/// Calories: < 5 → 0, ≤ 50 → nearest 5, above → nearest 10
+pub(crate) fn round_calories(kcal: f64) -> f64 {
+    if kcal < 5.0 {
+        0.0
+    } else if kcal <= 50.0 {
+        round_to(kcal, 5.0)
+    } else {
+        round_to(kcal, 10.0)
+    }
+}
+
+/// Fats: < 0.5 g → 0, < 5 g → nearest 0.5 g, above → nearest 1 g
+pub(crate) fn round_fat(grams: f64) -> f64 {
+    if grams < 0.5 {
+        0.0
+    } else if grams < 5.0 {
+        round_to(grams, 0.5)
+    } else {
+        grams.round()
+    }
+}
+
+/// Sodium: < 5 mg → 0, ≤ 140 mg → nearest 5 mg, above → nearest 10 mg
+pub(crate) fn round_sodium(mg: f64) -> f64 {
+    if mg < 5.0 {
+        0.0
+    } else if mg <= 140.0 {
+        round_to(mg, 5.0)
+    } else {
+        round_to(mg, 10.0)
+    }
+}
+
+/// Carbohydrates, fiber, sugars, protein: < 0.5 g → 0, < 1 g → "<1 g",
+/// above → nearest 1 g
+pub(crate) fn round_grams(grams: f64) -> (f64, bool) {
+    if grams < 0.5 {
+        (0.0, false)
+    } else if grams < 1.0 {
+        (1.0, true)
+    } else {
+        (grams.round(), false)
+    }
+}
+
+/// Vitamin and mineral %DV: < 2% → 0, ≤ 10% → nearest 2%, ≤ 50% → nearest
+/// 5%, above → nearest 10%
+pub(crate) fn round_mineral_percent(percent: f64) -> u32 {
+    let rounded = if percent < 2.0 {
+        0.0
+    } else if percent <= 10.0 {
+        round_to(percent, 2.0)
+    } else if percent <= 50.0 {
+        round_to(percent, 5.0)
+    } else {
+        round_to(percent, 10.0)
+    };
+    rounded as u32
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}", value)
+    }
+}
+
+impl NutritionPanel {
+    /// Plain-text rendering
+    pub fn to_text(&self) -> String {
+        let rule = "-".repeat(40);
+        let mut out = String::new();
+
+        out.push_str("Nutrition Facts\n");
+        out.push_str(&format!("{} servings per container\n", format_number(self.servings_per_container)));
+        out.push_str(&format!("Serving size {:>24}\n", format!("{} ({}g)", self.serving_size, format_number(self.serving_size_g))));
+        out.push_str(&rule);
+        out.push('\n');
+        if let Some(calories) = self.calories {
+            out.push_str(&format!("Calories {:>31}\n", format_number(calories)));
+            out.push_str(&rule);
+            out.push('\n');
+        }
+        out.push_str(&format!("{:>40}\n", "% Daily Value*"));
+        for row in &self.rows {
+            let dv = row.percent_dv.map(|p| format!("{}%", p)).unwrap_or_default();
+            out.push_str(&format!("{:<30}{:>10}\n", format!("{} {}", row.name, row.display_amount()), dv));
+        }
+        out.push_str(&rule);
+        out.push('\n');
+        if let Some(calories) = self.without_optional.as_ref().and_then(|p| p.calories) {
+            out.push_str(&format!("Without optional ingredients: {} calories\n", format_number(calories)));
+        }
+        out.push_str("* The % Daily Value tells you how much a nutrient in a serving\n");
+        out.push_str("of food contributes to a daily diet. 2,000 calories a day is\n");
+        out.push_str("used for general nutrition advice.\n");
+        out
+    }
+
+    /// HTML rendering
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("<section class=\"nutrition-facts\">\n");
+        out.push_str("  <h1>Nutrition Facts</h1>\n");
+        out.push_str(&format!(
+            "  <p>{} servings per container</p>\n",
+            format_number(self.servings_per_container)
+        ));
+        out.push_str(&format!(
+            "  <p><strong>Serving size</strong> {} ({}g)</p>\n",
+            html_escape(&self.serving_size),
+            format_number(self.serving_size_g)
+        ));
+        if let Some(calories) = self.calories {
+            out.push_str(&format!("  <p class=\"calories\"><strong>Calories</strong> {}</p>\n", format_number(calories)));
+        }
+        out.push_str("  <table>\n");
+        out.push_str("    <tr><th></th><th>% Daily Value*</th></tr>\n");
+        for row in &self.rows {
+            let dv = row.percent_dv.map(|p| format!("{}%", p)).unwrap_or_default();
+            out.push_str(&format!(
+                "    <tr><td><strong>{}</strong> {}</td><td>{}</td></tr>\n",
+                row.name,
+                html_escape(&row.display_amount()),
+                dv
+            ));
+        }
+        out.push_str("  </table>\n");
+        if let Some(calories) = self.without_optional.as_ref().and_then(|p| p.calories) {
+            out.push_str(&format!("  <p class=\"without-optional\">Without optional ingredients: {} calories</p>\n", format_number(calories)));
+        }
+        out.push_str("  <p class=\"footnote\">* The % Daily Value tells you how much a nutrient in a serving of food contributes to a daily diet. 2,000 calories a day is used for general nutrition advice.</p>\n");
+        out.push_str("</section>\n");
+        out
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rounding_rules() {
+        let calories = [(4.9, 0.0), (5.0, 5.0), (47.0, 45.0), (50.0, 50.0), (54.0, 50.0), (56.0, 60.0), (233.0, 230.0)];
+        for (input, expected) in calories {
+            assert_eq!(round_calories(input), expected, "calories {}", input);
+        }
+
+        let fats = [(0.4, 0.0), (0.5, 0.5), (1.2, 1.0), (1.3, 1.5), (4.7, 4.5), (5.0, 5.0), (7.6, 8.0)];
+        for (input, expected) in fats {
+            assert_eq!(round_fat(input), expected, "fat {}", input);
+        }
+
+        let sodium = [(4.0, 0.0), (7.0, 5.0), (138.0, 140.0), (140.0, 140.0), (144.0, 140.0), (146.0, 150.0)];
+        for (input, expected) in sodium {
+            assert_eq!(round_sodium(input), expected, "sodium {}", input);
+        }
+
+        let grams = [(0.4, (0.0, false)), (0.6, (1.0, true)), (1.0, (1.0, false)), (12.5, (13.0, false))];
+        for (input, expected) in grams {
+            assert_eq!(round_grams(input), expected, "grams {}", input);
+        }
+
+        let minerals = [(1.9, 0), (2.0, 2), (7.0, 8), (10.0, 10), (12.0, 10), (13.0, 15), (50.0, 50), (64.0, 60)];
+        for (input, expected) in minerals {
+            assert_eq!(round_mineral_percent(input), expected, "mineral %DV {}", input);
+        }
+    }
+
+    #[test]
+    fn test_panel_per_serving() {
+        let recipe = json!({
+            "meta": {"servings": {"amount": 2, "unit": "pizza"}},
+            "ingredients": [
+                {"id": "ing-0001", "machine_amount": {"value": 500, "unit": "g"},
+                 "nutritional": {"per_100g": {"calories": 361, "protein": 11.0, "fat": 1.5}}},
+                {"id": "ing-0002", "machine_amount": {"value": 30, "unit": "g"},
+                 "nutritional": {"per_100g": {"calories": 884, "fat": 100}}},
+                {"id": "ing-0003", "machine_amount": {"value": 325, "unit": "ml"}}
+            ]
+        });
+
+        let panel = nutrition_facts(&recipe).unwrap();
+
+        assert_eq!(panel.servings_per_container, 2.0);
+        assert_eq!(panel.serving_size_g, 265.0);
+        // (1805 + 265.2) / 2 = 1035.1
+        assert_eq!(panel.calories, Some(1040.0));
+
+        let names: Vec<&str> = panel.rows.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Total Fat", "Protein"]);
+        // (7.5 + 30) / 2 = 18.75 g → 19 g, 24% DV
+        assert_eq!(panel.rows[0].amount, 19.0);
+        assert_eq!(panel.rows[0].percent_dv, Some(24));
+        assert!(!panel.to_text().contains("Sodium"));
+        assert!(panel.to_html().contains("<strong>Protein</strong> 28g"));
+        assert!(panel.without_optional.is_none());
+    }
+
+    #[test]
+    fn test_compute_nutrition_coverage() {
+        let recipe = json!({
+            "meta": {"servings": {"amount": 4}},
+            "ingredients": [
+                {"id": "ing-0001", "name": "Flour", "machine_amount": {"value": 1, "unit": "lb"},
+                 "nutritional": {"per_100g": {"calories": 360, "protein": 10}}},
+                {"id": "ing-0002", "name": "Olive Oil", "machine_amount": {"value": 2, "unit": "tbsp"},
+                 "nutritional": {"per_serving": {"calories": 60, "fat": 7}}},
+                {"id": "ing-0003", "name": "Butter", "machine_amount": {"value": 113.4, "unit": "g"}},
+                {"id": "ing-0004", "name": "Water", "machine_amount": {"value": 300, "unit": "ml"}}
+            ]
+        });
+
+        let summary = compute_nutrition(&recipe).unwrap();
+        // 453.6 g of flour: 1633 kcal, plus the oil's 60 kcal a serving
+        assert!((summary.calories.unwrap() - (360.0 * 4.5359237 / 4.0 + 60.0)).abs() < 1e-9);
+        assert_eq!(summary.fat_g, Some(7.0));
+        // 80% of the weight, but only two of the four ingredients
+        assert!((summary.coverage - 0.5).abs() < 1e-9);
+
+        let uncovered: Vec<(&str, Option<f64>)> =
+            summary.uncovered.iter().map(|u| (u.name.as_str(), u.share.map(|s| (s * 10.0).round() / 10.0))).collect();
+        assert_eq!(uncovered, vec![("Butter", Some(0.2)), ("Water", None)]);
+
+        let total = summary.total();
+        assert_eq!(total.servings, 1.0);
+        assert_eq!(total.fat_g, Some(28.0));
+        assert!(summary.to_string().ends_with("per serving (50% covered)"));
+
+        // Without the water, 80% of the weight but two of three ingredients
+        let mut weighed = recipe.clone();
+        weighed["ingredients"].as_array_mut().unwrap().pop();
+        assert!((compute_nutrition(&weighed).unwrap().coverage - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_panel_without_optional() {
+        let recipe = json!({
+            "meta": {"servings": {"amount": 1}},
+            "ingredients": [
+                {"id": "ing-0001", "machine_amount": {"value": 100, "unit": "g"},
+                 "nutritional": {"per_100g": {"calories": 200}}},
+                {"id": "ing-0002", "machine_amount": {"value": 20, "unit": "g"}, "optional": true,
+                 "nutritional": {"per_100g": {"calories": 400}}}
+            ]
+        });
+
+        let panel = nutrition_facts(&recipe).unwrap();
+        assert_eq!(panel.calories, Some(280.0));
+        let without = panel.without_optional.as_ref().unwrap();
+        assert_eq!(without.calories, Some(200.0));
+        assert_eq!(without.serving_size_g, 100.0);
+        assert!(panel.to_text().contains("Without optional ingredients: 200 calories"));
+    }
+
+    #[test]
+    fn test_missing_data_errors() {
+        let no_servings = json!({"meta": {}, "ingredients": []});
+        assert_eq!(nutrition_facts(&no_servings), Err(NutritionError::MissingServings));
+
+        let no_data = json!({
+            "meta": {"servings": {"amount": 1}},
+            "ingredients": [{"machine_amount": {"value": 10, "unit": "g"}}]
+        });
+        assert_eq!(nutrition_facts(&no_data), Err(NutritionError::NoNutritionData));
+    }
+}
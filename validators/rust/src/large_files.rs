@@ -0,0 +1,206 @@
+// Large recipe files
+//
+// Recipes with embedded step photos can reach hundreds of megabytes. Reading
+// them into a `String` before parsing holds the file twice, so the file can
+// instead be memory-mapped and parsed straight from the map; UTF-8 is checked
+// by the parser as it goes. Mapping is an optimisation only: when it fails
+// (empty files, special filesystems, platforms without mmap) the caller reads
+// the file normally.
+//
+// Base64 `data:` URIs can also be left out of the parsed tree. Unescaped
+// strings are borrowed from the input while parsing, so a payload is never
+// copied; it is replaced by a placeholder that keeps the media type and
+// records the decoded size, e.g. `data:image/jpeg;rcip-omitted-bytes=48213,`.
+// The placeholder still matches the schema's `^data:` pattern, and
+// `payload_size` answers for placeholders and real payloads alike. The size
+// is that of the bytes the payload decodes to: only base64 digits count, so
+// padding and the line breaks some encoders wrap payloads with do not.
+
+use memmap2::Mmap;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Number, Value};
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+
+const BASE64_MARKER: &str = ";base64,";
+const OMITTED_MARKER: &str = ";rcip-omitted-bytes=";
+
+/// Map a file into memory, or None when the platform or filesystem refuses
+pub fn map_file(path: &Path) -> Option<Mmap> {
+    let file = File::open(path).ok()?;
+    // SAFETY: the map is only read while parsing. A file truncated by another
+    // process meanwhile can fault; callers opt into that for large files.
+    unsafe { Mmap::map(&file) }.ok()
+}
+
+/// Number of bytes a base64 `data:` URI decodes to, or the size a
+/// placeholder recorded
+pub fn payload_size(data_uri: &str) -> Option<usize> {
+    if !data_uri.starts_with("data:") {
+        return None;
+    }
+    if let Some(i) = data_uri.find(OMITTED_MARKER) {
+        let rest = &data_uri[i + OMITTED_MARKER.len()..];
+        return rest.split(',').next()?.parse().ok();
+    }
+    let i = data_uri.find(BASE64_MARKER)?;
+    let digits = data_uri[i + BASE64_MARKER.len()..]
+        .bytes()
+        .filter(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'-' | b'_'))
+        .count();
+    Some(digits * 3 / 4)
+}
+
+/// Placeholder for a base64 `data:` URI, or None for any other string
+pub fn placeholder(data_uri: &str) -> Option<String> {
+    let i = data_uri.find(BASE64_MARKER).filter(|_| data_uri.starts_with("data:"))?;
+    Some(format!("{}{}{},", &data_uri[..i], OMITTED_MARKER, payload_size(data_uri)?))
+}
+
+/// A JSON value whose base64 payloads were replaced while parsing
+struct Lean(Value);
+
+impl<'de> Deserialize<'de> for Lean {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(LeanVisitor).map(Lean)
+    }
+}
+
+struct LeanVisitor;
+
+impl<'de> Visitor<'de> for LeanVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(v.into()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(placeholder(v).unwrap_or_else(|| v.to_string())))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(placeholder(&v).unwrap_or(v)))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Lean::deserialize(deserializer).map(|lean| lean.0)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(Lean(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some((key, Lean(value))) = map.next_entry::<String, Lean>()? {
+            object.insert(key, value);
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Parse JSON bytes, replacing base64 `data:` URIs with placeholders
+pub fn parse_without_payloads(bytes: &[u8]) -> Result<Value, serde_json::Error> {
+    serde_json::from_slice::<Lean>(bytes).map(|lean| lean.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_payload_sizes() {
+        assert_eq!(payload_size("data:image/png;base64,aGVsbG8="), Some(5));
+        assert_eq!(payload_size("data:image/png;base64,aGVsbG8h"), Some(6));
+        assert_eq!(payload_size("data:image/png;base64,aGk"), Some(2));
+        // Wrapped as MIME encoders do, or with a trailing newline
+        assert_eq!(payload_size("data:image/png;base64,aGVs\r\nbG8=\n"), Some(5));
+        assert_eq!(payload_size("data:image/png;base64,aGVs bG8h"), Some(6));
+        assert_eq!(payload_size("data:image/png;rcip-omitted-bytes=48213,"), Some(48213));
+        assert_eq!(payload_size("https://example.com/a.png"), None);
+        assert_eq!(placeholder("data:image/png;base64,aGVsbG8="), Some("data:image/png;rcip-omitted-bytes=5,".to_string()));
+        assert_eq!(placeholder("data:text/plain,hello"), None);
+    }
+
+    #[test]
+    fn test_parse_without_payloads() {
+        let source = r#"{"images": [{"id": "img-1", "data_uri": "data:image/jpeg;base64,/9j/4AAQ"}],
+                         "meta": {"name": "Café", "servings": 2.5, "tags": [null, true, -3]}}"#;
+        let parsed = parse_without_payloads(source.as_bytes()).unwrap();
+        assert_eq!(
+            parsed,
+            json!({"images": [{"id": "img-1", "data_uri": "data:image/jpeg;rcip-omitted-bytes=6,"}],
+                   "meta": {"name": "Café", "servings": 2.5, "tags": [null, true, -3]}})
+        );
+        assert!(parse_without_payloads(b"{\"a\": \"\xff\"}").is_err());
+    }
+
+    #[test]
+    fn test_mapped_file_parses_like_a_read_file() {
+        let path = Path::new("../../examples/margherita-pizza.rcip");
+        let map = map_file(path).unwrap();
+        let mapped: Value = serde_json::from_slice(&map).unwrap();
+        let read: Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(mapped, read);
+    }
+
+    /// Generates a 100 MB recipe; run with `cargo test -- --ignored`
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    #[ignore]
+    fn test_hundred_megabyte_fixture() {
+        let example = Path::new("../../examples/margherita-pizza.rcip");
+        let mut recipe: Value = serde_json::from_str(&std::fs::read_to_string(example).unwrap()).unwrap();
+        recipe["images"] = json!([{"id": "img-1", "data_uri": "PAYLOAD"}]);
+        let payload = format!("data:image/jpeg;base64,{}", "A".repeat(100 * 1024 * 1024));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.rcip");
+        std::fs::write(&path, recipe.to_string().replace("\"PAYLOAD\"", &format!("\"{}\"", payload))).unwrap();
+        drop(payload);
+
+        let mut validator = crate::RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let expected = validator.validate_file(&path).unwrap();
+
+        validator.set_memory_map(true);
+        validator.set_skip_binary_payloads(true);
+        let lean = validator.validate_file(&path).unwrap();
+        assert_eq!(lean.errors, expected.errors);
+        assert_eq!(lean.warnings, expected.warnings);
+
+        let map = map_file(&path).unwrap();
+        let parsed = parse_without_payloads(&map).unwrap();
+        assert_eq!(parsed["images"][0]["data_uri"], "data:image/jpeg;rcip-omitted-bytes=78643200,");
+    }
+}
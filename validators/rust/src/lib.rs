@@ -5,15 +5,109 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use jsonschema::{JSONSchema, ValidationError};
+use jsonschema::JSONSchema;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+#[cfg(not(feature = "no-fs"))]
 use std::fs;
+use std::io::Read;
+#[cfg(not(feature = "no-fs"))]
 use std::path::{Path, PathBuf};
 use std::error::Error;
 use std::fmt;
+use std::time::Instant;
 use lazy_static::lazy_static;
 
+pub mod allergens;
+pub mod amounts;
+pub mod analysis;
+pub mod builder;
+pub mod canonical;
+pub mod catalog;
+pub mod certify;
+pub mod chilling;
+pub mod claims;
+pub mod codes;
+pub mod cold_chain;
+pub mod compat;
+pub mod corpus;
+#[cfg(not(feature = "no-fs"))]
+pub mod conformance;
+pub mod deprecations;
+pub mod device_limits;
+pub mod diff;
+#[cfg(not(feature = "no-fs"))]
+pub mod discover;
+pub mod explain;
+pub mod export;
+pub mod fix;
+pub mod fixtures;
+pub mod generate;
+pub mod graph;
+pub mod guidance;
+pub mod i18n;
+pub mod ids;
+pub mod id_sequence;
+pub mod import;
+pub mod infer;
+pub mod info;
+#[cfg(not(feature = "no-fs"))]
+pub mod ingest;
+pub mod ingredient_order;
+pub mod intermediates;
+pub mod introspect;
+#[cfg(all(feature = "journal", not(feature = "no-fs")))]
+pub mod journal;
+pub mod labels;
+pub mod large_files;
+pub mod markets;
+pub mod merge;
+pub mod migrate;
+pub mod mise_en_place;
+pub mod numeric;
+pub mod observer;
+pub mod patch;
+pub mod portions;
+pub mod precision;
+pub mod product;
+#[cfg(not(feature = "no-fs"))]
+pub mod project;
+pub mod provenance;
+pub mod recipe;
+pub mod release_notes;
+pub mod reporter;
+pub mod resolve;
+pub mod rule_config;
+#[cfg(feature = "wasm-rules")]
+pub mod rule_pack;
+pub mod rules;
+pub mod scaling;
+pub mod self_check;
+pub mod shots;
+pub mod simulate;
+pub mod sources;
+pub mod step_cards;
+pub mod taxonomy;
+pub mod terms;
+pub mod text_quality;
+pub mod units;
+pub mod unknown_fields;
+pub mod vocabulary;
+pub mod warm;
+pub mod wizard;
+pub mod stats;
+
+use chilling::ChillPolicy;
+use cold_chain::ColdChainPolicy;
+use explain::{EffectiveRule, SettingSource};
+use observer::{Guard, NoopObserver, Phase, ValidationObserver};
+#[cfg(not(feature = "no-fs"))]
+use observer::Recorder;
+use precision::{PrecisionIssue, PrecisionPolicy};
+use provenance::ValidationMeta;
+use resolve::Resolvers;
+use text_quality::TextPolicy;
+
 // Regex patterns for validation
 lazy_static! {
     static ref RECIPE_ID_REGEX: Regex =
@@ -49,8 +143,15 @@ pub enum DietLabel {
     Paleo,
 }
 
+/// Canonical diet label names, as they appear in recipe documents
+pub const DIET_LABELS: &[&str] = &[
+    "vegetarian", "vegan", "gluten-free", "dairy-free", "nut-free", "egg-free",
+    "soy-free", "fish-free", "shellfish-free", "kosher", "halal", "low-sodium",
+    "low-carb", "keto", "paleo"
+];
+
 /// Valid allergens
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Allergen {
     Milk,
@@ -86,8 +187,17 @@ pub enum CookingAction {
     Dissolve, Prepare, Spread, Garnish, Divide,
 }
 
-/// Valid measurement units
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+/// Canonical action names, as they appear in recipe documents
+pub const COOKING_ACTIONS: &[&str] = &[
+    "add", "mix", "combine", "blend", "cut", "slice", "dice", "chop", "mince",
+    "heat", "boil", "simmer", "steam", "fry", "saute", "bake", "roast", "grill",
+    "cool", "chill", "freeze", "knead", "fold", "roll", "shape", "ferment",
+    "proof", "rest", "strain", "filter", "separate", "measure", "weigh", "wait",
+    "dissolve", "prepare", "spread", "garnish", "divide"
+];
+
+/// Valid measurement units; `units` converts between them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Unit {
     // Mass
@@ -100,18 +210,310 @@ pub enum Unit {
     Pinch, Dash, Handful, ToTaste,
 }
 
+/// Canonical unit names, as they appear in recipe documents
+pub const UNITS: &[&str] = &[
+    "mg", "g", "kg", "oz", "lb", "ml", "l", "tsp", "tbsp", "cup", "fl-oz",
+    "pt", "qt", "gal", "pcs", "dozen", "pinch", "dash", "handful", "to-taste"
+];
+
+/// Standard step hazards
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hazard {
+    HotSurface,
+    SharpTool,
+    Electrical,
+    Chemical,
+    Pressure,
+    AllergenCrossContact,
+}
+
+/// Canonical hazard names, as they appear in recipe documents
+pub const HAZARDS: &[&str] = &[
+    "hot-surface", "sharp-tool", "electrical", "chemical", "pressure", "allergen-cross-contact"
+];
+
+/// Issue severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Advice that is not a problem, from rules that are off by default
+    Info,
+}
+
+/// A rule finding: its code and the raw parameters its message was rendered from
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Issue {
+    pub code: &'static str,
+    pub severity: Severity,
+    #[serde(serialize_with = "serialize_params")]
+    pub params: Vec<(&'static str, String)>,
+    /// JSON pointer of the document the issue belongs to, "" for the root
+    pub path: String,
+    /// JSON pointer of the value the issue is about, relative to the
+    /// document at `path`; "" when it concerns the document as a whole
+    pub pointer: String,
+}
+
+/// Message parameters as a JSON object
+fn serialize_params<S: serde::Serializer>(params: &[(&'static str, String)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(params.iter().map(|(name, value)| (name, value)))
+}
+
+impl Issue {
+    /// JSON pointer of the value the issue is about, from the root of the
+    /// validated document
+    pub fn location(&self) -> String {
+        reroot(&self.path, &self.pointer)
+    }
+}
+
+/// An issue as tooling consumes it: its stable code (see `codes`), severity,
+/// the JSON pointer of the offending value and the rendered message
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    pub code: &'static str,
+    pub severity: Severity,
+    /// JSON pointer such as `/ingredients/3/allergens`, "" for the document
+    pub path: String,
+    pub message: String,
+}
+
+/// Whether a warning is denied: some pattern is part of its code or its
+/// message, or there are no patterns at all
+fn denies(patterns: &[String], code: &str, message: &str) -> bool {
+    patterns.is_empty() || patterns.iter().any(|p| code.contains(p.as_str()) || message.contains(p.as_str()))
+}
+
+/// Join a JSON pointer prefix and a pointer relative to it
+fn reroot(prefix: &str, path: &str) -> String {
+    format!("{}{}", prefix.trim_end_matches('/'), path)
+}
+
 /// Validation result
-#[derive(Debug, Clone)]
+///
+/// Results from `validate_recipe` are deterministic: issues are ordered by
+/// (`Issue::location`, code, message), the message lists follow that order, and the
+/// allergen, trace and diet-label lists in `info` are sorted. Only the
+/// timestamp in `meta` differs between runs.
+#[derive(Debug, Clone, Serialize)]
 pub struct ValidationResult {
     pub valid: bool,
     pub errors: Vec<String>,
     pub warnings: Vec<String>,
+    pub notices: Vec<String>,
     pub info: RecipeInfo,
+    /// Structured form of `errors`, `warnings` and `notices`
+    pub issues: Vec<Issue>,
+    /// Locale the messages are rendered in
+    pub locale: String,
+    /// Validator, schema and configuration that produced this result
+    pub meta: Option<ValidationMeta>,
+    /// Whether the JSON Schema was applied; false when the validator runs
+    /// degraded because its schema could not be used (see `SchemaStatus`)
+    pub schema_checked: bool,
+    /// SHA-256 of the bytes the recipe was validated from, for files read
+    /// with `set_fingerprint_files` on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_sha256: Option<String>,
+    /// Pointer the issues being recorded are about, set by `within`
+    #[serde(skip)]
+    scope: String,
+}
+
+impl ValidationResult {
+    /// Empty, valid result
+    pub fn new() -> Self {
+        ValidationResult::with_locale("en")
+    }
+
+    /// Empty, valid result rendering messages in the given locale
+    pub fn with_locale(locale: &str) -> Self {
+        ValidationResult {
+            valid: true,
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            notices: Vec::new(),
+            info: RecipeInfo::default(),
+            issues: Vec::new(),
+            locale: locale.to_string(),
+            meta: None,
+            schema_checked: true,
+            source_sha256: None,
+            scope: String::new(),
+        }
+    }
+
+    /// Result holding the given issues, messages rendered in English
+    pub fn from_issues(issues: Vec<Issue>) -> Self {
+        let mut result = ValidationResult::new();
+        for issue in issues {
+            result.push(issue);
+        }
+        result
+    }
+
+    /// Fold in the result of a part of this document found at `path_prefix`,
+    /// e.g. "/components/0". The part's issues are re-rooted under the prefix
+    /// and their messages re-rendered in this result's locale; counts add up,
+    /// allergen sets are unioned and the longer total time wins.
+    pub fn merge(mut self, other: ValidationResult, path_prefix: &str) -> ValidationResult {
+        self.valid &= other.valid;
+        self.schema_checked &= other.schema_checked;
+        for issue in other.issues {
+            let path = reroot(path_prefix, &issue.path);
+            self.push(Issue { path, ..issue });
+        }
+        self.info.merge(other.info);
+        if self.meta.is_none() {
+            self.meta = other.meta;
+        }
+        self
+    }
+
+    /// Re-file issues of the given codes under another severity; validity
+    /// follows the remaining errors
+    pub fn override_severities(&mut self, severities: &BTreeMap<&'static str, Severity>) {
+        if severities.is_empty() {
+            return;
+        }
+        let issues = std::mem::take(&mut self.issues);
+        self.valid = true;
+        self.errors.clear();
+        self.warnings.clear();
+        self.notices.clear();
+        for issue in issues {
+            let severity = severities.get(issue.code).copied().unwrap_or(issue.severity);
+            self.push(Issue { severity, ..issue });
+        }
+    }
+
+    /// Drop the issues of disabled rules; validity follows the remaining
+    /// errors
+    pub fn disable_rules(&mut self, disabled: &BTreeSet<&'static str>) {
+        if disabled.is_empty() {
+            return;
+        }
+        let issues = std::mem::take(&mut self.issues);
+        self.valid = true;
+        self.errors.clear();
+        self.warnings.clear();
+        self.notices.clear();
+        for issue in issues.into_iter().filter(|issue| !disabled.contains(issue.code)) {
+            self.push(issue);
+        }
+    }
+
+    /// Re-file warnings as errors: those whose code or message contains one
+    /// of `patterns`, or every warning when `patterns` is empty
+    pub fn deny_warnings(&mut self, patterns: &[String]) {
+        let issues = std::mem::take(&mut self.issues);
+        self.valid = true;
+        self.errors.clear();
+        self.warnings.clear();
+        self.notices.clear();
+        for issue in issues {
+            let denied = issue.severity == Severity::Warning
+                && denies(patterns, issue.code, &i18n::render(&self.locale, issue.code, &issue.params));
+            let severity = if denied { Severity::Error } else { issue.severity };
+            self.push(Issue { severity, ..issue });
+        }
+    }
+
+    /// Order issues by (location, code, message) and rebuild the message
+    /// lists to match
+    pub fn sort(&mut self) {
+        let locale = self.locale.clone();
+        let mut issues = std::mem::take(&mut self.issues);
+        issues.sort_by_cached_key(|issue| (issue.location(), issue.code, i18n::render(&locale, issue.code, &issue.params)));
+        self.errors.clear();
+        self.warnings.clear();
+        self.notices.clear();
+        for issue in issues {
+            self.push(issue);
+        }
+    }
+
+    /// Add a finished issue, rendering its message with the path in front
+    fn push(&mut self, issue: Issue) {
+        let message = i18n::render(&self.locale, issue.code, &issue.params);
+        let message = if issue.path.is_empty() { message } else { format!("{}: {}", issue.path, message) };
+        match issue.severity {
+            Severity::Error => {
+                self.valid = false;
+                self.errors.push(message);
+            }
+            Severity::Warning => self.warnings.push(message),
+            Severity::Info => self.notices.push(message),
+        }
+        self.issues.push(issue);
+    }
+
+    /// Issues with their location and message, in the order of `issues`
+    pub fn validation_issues(&self) -> Vec<ValidationIssue> {
+        self.issues
+            .iter()
+            .map(|issue| ValidationIssue {
+                code: issue.code,
+                severity: issue.severity,
+                path: issue.location(),
+                message: i18n::render(&self.locale, issue.code, &issue.params),
+            })
+            .collect()
+    }
+
+    /// Record the issues `rules` finds as being about the value at `pointer`;
+    /// an issue with a `path` parameter is located there instead
+    pub fn within(&mut self, pointer: &str, rules: impl FnOnce(&mut ValidationResult)) {
+        let outer = std::mem::replace(&mut self.scope, pointer.to_string());
+        rules(self);
+        self.scope = outer;
+    }
+
+    /// Record an error; the result becomes invalid
+    pub fn error(&mut self, code: &'static str, params: &[(&'static str, &dyn fmt::Display)]) {
+        self.valid = false;
+        let message = self.record(code, Severity::Error, params);
+        self.errors.push(message);
+    }
+
+    /// Record a warning
+    pub fn warning(&mut self, code: &'static str, params: &[(&'static str, &dyn fmt::Display)]) {
+        let message = self.record(code, Severity::Warning, params);
+        self.warnings.push(message);
+    }
+
+    /// Record a notice
+    pub fn notice(&mut self, code: &'static str, params: &[(&'static str, &dyn fmt::Display)]) {
+        let message = self.record(code, Severity::Info, params);
+        self.notices.push(message);
+    }
+
+    fn record(&mut self, code: &'static str, severity: Severity, params: &[(&'static str, &dyn fmt::Display)]) -> String {
+        let params: Vec<(&'static str, String)> = params.iter().map(|(name, value)| (*name, value.to_string())).collect();
+        let message = i18n::render(&self.locale, code, &params);
+        let pointer = match params.iter().find(|(name, _)| *name == "path") {
+            Some((_, path)) if path.is_empty() || path.starts_with('/') => path.clone(),
+            _ => self.scope.clone(),
+        };
+        self.issues.push(Issue { code, severity, params, path: String::new(), pointer });
+        message
+    }
+}
+
+impl Default for ValidationResult {
+    fn default() -> Self {
+        ValidationResult::new()
+    }
 }
 
 /// Recipe information
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
 pub struct RecipeInfo {
+    /// The recipe's `id`, naming it when a parent inherits its allergens
+    pub id: Option<String>,
     pub name: String,
     pub version: String,
     pub recipe_version: Option<String>,
@@ -119,18 +521,83 @@ pub struct RecipeInfo {
     pub step_count: usize,
     pub has_device_profiles: bool,
     pub has_sensors: bool,
-    pub allergens: Vec<String>,
+    /// Contained and suggested allergens and where each comes from
+    pub allergens: Vec<allergens::AllergenEntry>,
+    /// Allergens the recipe may contain traces of, apart from the declared
+    /// `allergens`
+    pub traces: Vec<String>,
     pub diet_labels: Vec<String>,
     pub difficulty: Option<String>,
     pub total_time: Option<f64>,
+    /// Baker's percentages, for bread and baking recipes with weighable flour
+    pub baking: Option<analysis::BakingRatios>,
+    /// Quality score, set once validation has finished
+    pub quality: Option<analysis::QualityScore>,
+    /// Per-serving nutrition, set by validation when its data covers at
+    /// least `labels::MIN_COVERAGE` of the recipe
+    pub nutrition: Option<labels::NutritionSummary>,
+    /// Version of the schema the recipe was checked against, set by
+    /// validation; `None` when no schema was checked
+    pub schema_version: Option<String>,
+}
+
+impl RecipeInfo {
+    /// Combine with the info of a component of the same recipe
+    fn merge(&mut self, other: RecipeInfo) {
+        self.ingredient_count += other.ingredient_count;
+        self.step_count += other.step_count;
+        self.has_device_profiles |= other.has_device_profiles;
+        self.has_sensors |= other.has_sensors;
+
+        let union = |a: &mut Vec<String>, b: Vec<String>| {
+            let merged: BTreeSet<String> = a.drain(..).chain(b).collect();
+            *a = merged.into_iter().collect();
+        };
+        // The component's allergens are inherited from it, unless only its
+        // ingredient names suggest them; those stay inferred, so merging
+        // never turns a guess into an allergen the default policy counts
+        let component = other.id.clone().unwrap_or_else(|| other.name.clone());
+        let own = self.allergens.drain(..).flat_map(|e| e.sources.into_iter().map(move |s| (e.allergen.clone(), s)));
+        let inherited = other.allergens.into_iter().flat_map(|e| {
+            let sources = if e.sources.iter().all(|s| s.provenance() == allergens::Provenance::Inferred) {
+                e.sources
+            } else {
+                vec![allergens::AllergenSource::Inherited { recipe: component.clone() }]
+            };
+            sources.into_iter().map(move |s| (e.allergen.clone(), s))
+        });
+        self.allergens = allergens::aggregate(own.collect::<Vec<_>>().into_iter().chain(inherited));
+        union(&mut self.traces, other.traces);
+        // Allergens only suggested by name do not displace traces
+        let contained = allergens::ProvenancePolicy::default();
+        self.traces.retain(|t| !self.allergens.iter().any(|e| &e.allergen == t && e.considered(&contained)));
+
+        self.total_time = match (self.total_time, other.total_time) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.baking = self.baking.take().or(other.baking);
+        self.nutrition = match (self.nutrition.take(), other.nutrition) {
+            (Some(a), Some(b)) => Some(a.add(&b)),
+            (a, b) => a.or(b),
+        };
+        // A recipe is as good as its weakest component
+        self.quality = match (self.quality.take(), other.quality) {
+            (Some(a), Some(b)) => Some(if b.score < a.score { b } else { a }),
+            (a, b) => a.or(b),
+        };
+    }
 }
 
 /// Validation statistics
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize)]
 pub struct ValidationStats {
     pub validated: u32,
     pub passed: u32,
     pub failed: u32,
+    pub errors: u32,
+    pub warnings: u32,
+    pub rule_counts: BTreeMap<String, u32>,
 }
 
 /// Custom error type
@@ -140,6 +607,11 @@ pub enum RCIPError {
     JsonError(serde_json::Error),
     ValidationError(String),
     SchemaError(String),
+    PatchError(String),
+    /// A recipe cannot be migrated between the versions asked for
+    MigrationError(String),
+    /// No schema of this version is bundled into the binary
+    UnknownSchemaVersion(String),
 }
 
 impl fmt::Display for RCIPError {
@@ -149,6 +621,12 @@ impl fmt::Display for RCIPError {
             RCIPError::JsonError(e) => write!(f, "JSON error: {}", e),
             RCIPError::ValidationError(e) => write!(f, "Validation error: {}", e),
             RCIPError::SchemaError(e) => write!(f, "Schema error: {}", e),
+            RCIPError::PatchError(e) => write!(f, "Patch error: {}", e),
+            RCIPError::MigrationError(e) => write!(f, "Migration error: {}", e),
+            RCIPError::UnknownSchemaVersion(version) => {
+                let bundled: Vec<&str> = warm::BUNDLED_SCHEMAS.iter().map(|(v, _)| *v).collect();
+                write!(f, "Unknown schema version: {} (bundled: {})", version, bundled.join(", "))
+            }
         }
     }
 }
@@ -167,12 +645,313 @@ impl From<serde_json::Error> for RCIPError {
     }
 }
 
+/// Total duration of a step in minutes, from its `time_hours`, `time_minutes`
+/// and `time_seconds` params
+pub fn step_duration_minutes(step: &Value) -> Option<f64> {
+    let params = step.get("params")?;
+    let hours = params.get("time_hours").and_then(numeric::finite);
+    let minutes = params.get("time_minutes").and_then(numeric::finite);
+    let seconds = params.get("time_seconds").and_then(numeric::finite);
+
+    if hours.is_none() && minutes.is_none() && seconds.is_none() {
+        return None;
+    }
+
+    Some(hours.unwrap_or(0.0) * 60.0 + minutes.unwrap_or(0.0) + seconds.unwrap_or(0.0) / 60.0)
+}
+
+/// Target temperature of a step in °C, converting `temperature_f` if needed
+pub fn step_temperature_c(step: &Value) -> Option<f64> {
+    let params = step.get("params")?;
+    if let Some(c) = params.get("temperature_c").and_then(numeric::finite) {
+        return Some(c);
+    }
+    params.get("temperature_f")
+        .and_then(numeric::finite)
+        .map(|f| (f - 32.0) * 5.0 / 9.0)
+}
+
+/// Differences between a schema and what the built-in rules assume
+pub fn schema_divergences(schema: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+
+    if introspect::is_required(schema, "/ingredients/*/allergens") != Some(true) {
+        found.push("ingredients[].allergens is optional in the schema but required by the validator".to_string());
+    }
+
+    let patterns = [
+        ("/id", "id", &*RECIPE_ID_REGEX),
+        ("/ingredients/*/id", "ingredients[].id", &*INGREDIENT_ID_REGEX),
+        ("/steps/*/step_id", "steps[].step_id", &*STEP_ID_REGEX),
+    ];
+    for (path, field, regex) in patterns {
+        match introspect::pattern(schema, path) {
+            Some(pattern) if pattern == regex.as_str() => {}
+            Some(pattern) => found.push(format!(
+                "{} pattern {} differs from the built-in {}",
+                field, pattern, regex.as_str()
+            )),
+            None => found.push(format!("{} has no pattern in the schema; the validator enforces {}", field, regex.as_str())),
+        }
+    }
+
+    match introspect::enum_values(schema, "/steps/*/action") {
+        Some(allowed) => {
+            let missing: Vec<&str> = COOKING_ACTIONS.iter().copied().filter(|a| !allowed.contains(a)).collect();
+            let extra: Vec<&str> = allowed.iter().copied().filter(|a| !COOKING_ACTIONS.contains(a)).collect();
+            if !missing.is_empty() {
+                found.push(format!("steps[].action enum lacks built-in actions: {}", missing.join(", ")));
+            }
+            if !extra.is_empty() {
+                found.push(format!("steps[].action enum allows actions the validator rejects: {}", extra.join(", ")));
+            }
+        }
+        None => found.push("steps[].action has no enum in the schema; the validator only accepts built-in actions".to_string()),
+    }
+
+    found
+}
+
+/// Whether an ingredient is marked `"optional": true` (garnishes, serving
+/// suggestions)
+pub fn is_optional(ingredient: &Value) -> bool {
+    ingredient.get("optional").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// What a step takes: the entries of its `target`
+fn step_targets(step: &Value) -> impl Iterator<Item = &str> {
+    step.get("target").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|t| t.as_str())
+}
+
+/// Human-readable report for one result, as printed by the CLI
+pub fn render_report(result: &ValidationResult, recipe_name: &str) -> String {
+    use std::fmt::Write as _;
+    let mut out = String::new();
+    writeln!(out, "\n{}", "=".repeat(60)).unwrap();
+    writeln!(out, "Recipe: {}", recipe_name).unwrap();
+    writeln!(out, "Status: {}", if result.valid { "✅ VALID" } else { "❌ INVALID" }).unwrap();
+
+    writeln!(out, "\n📊 Recipe Info:").unwrap();
+    writeln!(out, "  - RCIP Version: {}", result.info.version).unwrap();
+    writeln!(out, "  - Ingredients: {}", result.info.ingredient_count).unwrap();
+    writeln!(out, "  - Steps: {}", result.info.step_count).unwrap();
+    writeln!(out, "  - Difficulty: {}", result.info.difficulty.as_ref().unwrap_or(&"not specified".to_string())).unwrap();
+
+    if let Some(time) = result.info.total_time {
+        writeln!(out, "  - Total Time: {} minutes", time).unwrap();
+    }
+
+    if !result.info.allergens.is_empty() {
+        let allergens: Vec<String> = result.info.allergens.iter().map(|e| e.to_string()).collect();
+        writeln!(out, "  - Allergens: {}", allergens.join(", ")).unwrap();
+    }
+
+    if !result.info.traces.is_empty() {
+        writeln!(out, "  - May Contain: {}", result.info.traces.join(", ")).unwrap();
+    }
+
+    if !result.info.diet_labels.is_empty() {
+        writeln!(out, "  - Diet Labels: {}", result.info.diet_labels.join(", ")).unwrap();
+    }
+
+    if let Some(baking) = &result.info.baking {
+        writeln!(
+            out,
+            "  - Baker's %: hydration {:.0}%, salt {:.1}%, fat {:.1}%, sugar {:.1}%, leavening {:.1}% of {:.0} g flour",
+            baking.hydration, baking.salt, baking.fat, baking.sugar, baking.leavening, baking.flour_g
+        )
+        .unwrap();
+    }
+
+    if let Some(nutrition) = &result.info.nutrition {
+        writeln!(out, "  - Nutrition: {}", nutrition).unwrap();
+    }
+
+    if let Some(quality) = &result.info.quality {
+        writeln!(out, "  - Quality: {}", quality).unwrap();
+    }
+
+    if !result.errors.is_empty() {
+        writeln!(out, "\n❌ Errors ({}):", result.errors.len()).unwrap();
+        for (i, error) in result.errors.iter().take(10).enumerate() {
+            writeln!(out, "  {}. {}", i + 1, error).unwrap();
+        }
+        if result.errors.len() > 10 {
+            writeln!(out, "  ... and {} more errors", result.errors.len() - 10).unwrap();
+        }
+    }
+
+    if !result.warnings.is_empty() {
+        writeln!(out, "\n⚠️  Warnings ({}):", result.warnings.len()).unwrap();
+        for warning in &result.warnings {
+            writeln!(out, "  - {}", warning).unwrap();
+        }
+    }
+
+    if !result.notices.is_empty() {
+        writeln!(out, "\nℹ️  Notices ({}):", result.notices.len()).unwrap();
+        for notice in &result.notices {
+            writeln!(out, "  - {}", notice).unwrap();
+        }
+    }
+
+    if let Some(meta) = &result.meta {
+        writeln!(out, "\n{}", meta).unwrap();
+    }
+    writeln!(out, "{}", "=".repeat(60)).unwrap();
+    out
+}
+
+/// Files the directory summary lists by quality
+const LOWEST_QUALITY_LISTED: usize = 5;
+
+/// Files of a batch with their results, least quality first; files without
+/// a score are left out
+pub fn lowest_quality(results: &[(String, ValidationResult)], limit: usize) -> Vec<(&str, f64)> {
+    let mut scored: Vec<(&str, f64)> = results
+        .iter()
+        .filter_map(|(file, result)| Some((file.as_str(), result.info.quality.as_ref()?.score)))
+        .collect();
+    scored.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+    scored.truncate(limit);
+    scored
+}
+
+/// One CSV row per file: validity, issue counts and quality score
+pub fn render_csv(results: &[(String, ValidationResult)]) -> String {
+    let field = |text: &str| {
+        if text.contains([',', '"', '\n']) {
+            format!("\"{}\"", text.replace('"', "\"\""))
+        } else {
+            text.to_string()
+        }
+    };
+    let mut out = String::from("file,valid,errors,warnings,notices,quality\n");
+    for (file, result) in results {
+        let quality = result.info.quality.as_ref().map(|q| format!("{:.1}", q.score)).unwrap_or_default();
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            field(file),
+            result.valid,
+            result.errors.len(),
+            result.warnings.len(),
+            result.notices.len(),
+            quality
+        ));
+    }
+    out
+}
+
+/// A file of a directory run, validated but not yet reported
+#[cfg(not(feature = "no-fs"))]
+struct CheckedFile {
+    result: ValidationResult,
+    recorder: Recorder,
+    recipe_name: String,
+    sample: Option<corpus::CorpusSample>,
+}
+
+/// One file's entry in the JSON report: its name and its result
+#[derive(Serialize)]
+struct FileReport<'a> {
+    file: &'a str,
+    #[serde(flatten)]
+    result: &'a ValidationResult,
+}
+
+/// A run as one JSON document: every file's result, then the summary stats
+pub fn render_json(results: &[(String, ValidationResult)], stats: &ValidationStats) -> String {
+    let files: Vec<FileReport> = results.iter().map(|(file, result)| FileReport { file, result }).collect();
+    serde_json::to_string_pretty(&serde_json::json!({"results": files, "stats": stats})).expect("results serialize to JSON")
+}
+
+/// Built-in profiles. `strict` turns on every opt-in recipe check and
+/// requires chill parameters
+pub const PROFILES: &[&str] = &["default", "strict"];
+
+/// A profile name not in `PROFILES`
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownProfile(pub String);
+
+impl fmt::Display for UnknownProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown profile '{}'; supported: {}", self.0, PROFILES.join(", "))
+    }
+}
+
+impl Error for UnknownProfile {}
+
+/// Whether the validator could compile its JSON Schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaStatus {
+    /// `init` has not run
+    Uninitialized,
+    Compiled,
+    /// The schema could not be parsed or compiled, for this reason. Recipes
+    /// are still checked by the built-in rules, and each result carries an
+    /// RCIP-E-SCHEMA-SKIPPED error
+    Failed(String),
+}
+
+/// A schema loaded for recipes of another version than the validator's
+struct VersionSchema {
+    schema: Value,
+    compiled: JSONSchema,
+}
+
 /// RCIP Validator
 pub struct RCIPValidator {
     schema_version: String,
     schema: Option<Value>,
+    schema_sha256: Option<String>,
+    schema_divergences: Vec<String>,
+    schema_status: SchemaStatus,
+    require_schema: bool,
+    strict_schema: bool,
+    memory_map: bool,
+    skip_binary_payloads: bool,
+    fingerprint_files: bool,
+    reporter: Box<dyn reporter::Reporter>,
+    jobs: Option<usize>,
+    outlier_thresholds: Option<corpus::Thresholds>,
+    corpus_report: Option<corpus::CorpusReport>,
     compiled_schema: Option<JSONSchema>,
+    versions: BTreeMap<String, VersionSchema>,
     stats: ValidationStats,
+    precision_policy: PrecisionPolicy,
+    taxonomy_warnings: bool,
+    chill_policy: ChillPolicy,
+    cold_chain: bool,
+    verify_claims: bool,
+    cold_chain_policy: ColdChainPolicy,
+    text_policy: TextPolicy,
+    quality_weights: analysis::QualityWeights,
+    mise_en_place: bool,
+    ingredient_order: bool,
+    usage_order: bool,
+    id_sequence: bool,
+    markets: Vec<&'static markets::Market>,
+    allergen_provenance: allergens::ProvenancePolicy,
+    baking_checks: bool,
+    require_language: bool,
+    readable_amounts: bool,
+    amount_tolerance: f64,
+    dangling_steps: bool,
+    total_time_tolerance: f64,
+    unknown_fields: bool,
+    simulation: bool,
+    resolvers: Option<Resolvers>,
+    locale: String,
+    profile: String,
+    severities: BTreeMap<&'static str, Severity>,
+    denied_warnings: Option<Vec<String>>,
+    disabled_rules: BTreeSet<&'static str>,
+    custom_rules: Vec<Box<dyn rules::ValidationRule>>,
+    setting_source: SettingSource,
+    provenance: explain::Provenance,
+    #[cfg(feature = "wasm-rules")]
+    rule_packs: Vec<rule_pack::RulePack>,
+    item_cache: patch::ItemCache,
 }
 
 impl RCIPValidator {
@@ -181,729 +960,4591 @@ impl RCIPValidator {
         RCIPValidator {
             schema_version: schema_version.to_string(),
             schema: None,
+            schema_sha256: None,
+            schema_divergences: Vec::new(),
+            strict_schema: false,
+            schema_status: SchemaStatus::Uninitialized,
+            require_schema: false,
+            memory_map: false,
+            skip_binary_payloads: false,
+            fingerprint_files: false,
+            reporter: Box::new(reporter::NullReporter),
+            jobs: None,
+            outlier_thresholds: None,
+            corpus_report: None,
             compiled_schema: None,
+            versions: BTreeMap::new(),
             stats: ValidationStats::default(),
+            precision_policy: PrecisionPolicy::default(),
+            taxonomy_warnings: false,
+            chill_policy: ChillPolicy::default(),
+            cold_chain: false,
+            verify_claims: false,
+            cold_chain_policy: ColdChainPolicy::default(),
+            text_policy: TextPolicy::default(),
+            quality_weights: analysis::QualityWeights::default(),
+            mise_en_place: false,
+            ingredient_order: false,
+            usage_order: false,
+            id_sequence: false,
+            markets: Vec::new(),
+            allergen_provenance: allergens::ProvenancePolicy::default(),
+            baking_checks: false,
+            require_language: false,
+            readable_amounts: false,
+            amount_tolerance: amounts::DEFAULT_TOLERANCE,
+            dangling_steps: false,
+            total_time_tolerance: analysis::DEFAULT_TOTAL_TIME_TOLERANCE,
+            unknown_fields: false,
+            simulation: false,
+            resolvers: None,
+            locale: "en".to_string(),
+            profile: "default".to_string(),
+            severities: BTreeMap::new(),
+            denied_warnings: None,
+            disabled_rules: BTreeSet::new(),
+            custom_rules: Vec::new(),
+            setting_source: SettingSource::Api,
+            provenance: explain::Provenance::default(),
+            #[cfg(feature = "wasm-rules")]
+            rule_packs: Vec::new(),
+            item_cache: patch::ItemCache::default(),
         }
     }
 
-    /// Initialize validator with schema
-    pub fn init(&mut self, schema_path: Option<&Path>) -> Result<(), RCIPError> {
-        let path = if let Some(p) = schema_path {
-            p.to_path_buf()
-        } else {
-            PathBuf::from(format!("../../schemas/rcip-v{}.json", self.schema_version))
-        };
+    /// Create a validator that rejects fields the schema does not declare;
+    /// see `set_unknown_fields`
+    pub fn new_strict(schema_version: &str) -> Self {
+        let mut validator = RCIPValidator::new(schema_version);
+        validator.set_unknown_fields(true);
+        validator
+    }
 
-        let schema_content = fs::read_to_string(&path)?;
-        let schema: Value = serde_json::from_str(&schema_content)?;
+    /// Attribute settings made from now on to `source` (default: API)
+    pub fn set_setting_source(&mut self, source: SettingSource) {
+        self.setting_source = source;
+    }
 
-        match JSONSchema::compile(&schema) {
-            Ok(compiled) => {
-                self.compiled_schema = Some(compiled);
-                self.schema = Some(schema);
-                println!("✅ RCIP Validator initialized with schema v{}", self.schema_version);
-                Ok(())
-            }
-            Err(e) => Err(RCIPError::SchemaError(format!("Failed to compile schema: {}", e)))
-        }
+    /// Set the machine-amount precision policy
+    pub fn set_precision_policy(&mut self, policy: PrecisionPolicy) {
+        self.provenance.record("precision_policy", self.setting_source, json!(policy).to_string());
+        self.precision_policy = policy;
     }
 
-    /// Validate a recipe
-    pub fn validate_recipe(&mut self, recipe: &Value) -> ValidationResult {
-        let mut result = ValidationResult {
-            valid: true,
-            errors: Vec::new(),
-            warnings: Vec::new(),
-            info: RecipeInfo::default(),
-        };
+    /// Set the thresholds used by the cool/chill/freeze rules
+    pub fn set_chill_policy(&mut self, policy: ChillPolicy) {
+        self.provenance.record("chill_policy", self.setting_source, json!(policy).to_string());
+        self.chill_policy = policy;
+    }
 
-        // Check if validator is initialized
-        let compiled_schema = match &self.compiled_schema {
-            Some(s) => s,
-            None => {
-                result.valid = false;
-                result.errors.push("Validator not initialized. Call init() first.".to_string());
-                return result;
-            }
-        };
+    /// Check chilled and frozen ingredients against the recipe timeline
+    /// (off by default)
+    pub fn set_cold_chain(&mut self, enabled: bool) {
+        self.provenance.record("cold_chain", self.setting_source, enabled.to_string());
+        self.cold_chain = enabled;
+    }
 
-        // JSON Schema validation
-        if let Err(errors) = compiled_schema.validate(recipe) {
-            result.valid = false;
-            for error in errors {
-                result.errors.push(format!("{}: {}", error.instance_path, error));
-            }
-        }
+    /// Warn about diet labels without allergen evidence for every ingredient
+    /// (off by default)
+    pub fn set_verify_claims(&mut self, enabled: bool) {
+        self.provenance.record("verify_claims", self.setting_source, enabled.to_string());
+        self.verify_claims = enabled;
+    }
 
-        // Custom validations
-        self.validate_custom_rules(recipe, &mut result);
+    /// Set the thresholds used by the cold-chain rules
+    pub fn set_cold_chain_policy(&mut self, policy: ColdChainPolicy) {
+        self.provenance.record("cold_chain_policy", self.setting_source, json!(policy).to_string());
+        self.cold_chain_policy = policy;
+    }
 
-        // Check warnings
-        result.warnings = self.check_warnings(recipe);
+    /// Set the thresholds used by the step text-quality rules
+    pub fn set_text_policy(&mut self, policy: TextPolicy) {
+        self.provenance.record("text_policy", self.setting_source, json!(policy).to_string());
+        self.text_policy = policy;
+    }
 
-        // Get recipe info
-        result.info = self.get_recipe_info(recipe);
+    /// Set the component weights and rule penalties of the quality score
+    pub fn set_quality_weights(&mut self, weights: analysis::QualityWeights) {
+        self.provenance.record("quality_weights", self.setting_source, json!(weights).to_string());
+        self.quality_weights = weights;
+    }
 
-        // Update stats
-        self.stats.validated += 1;
-        if result.valid {
-            self.stats.passed += 1;
-        } else {
-            self.stats.failed += 1;
-        }
+    /// Warn about ingredients used before a measure/prepare step (off by default)
+    pub fn set_mise_en_place(&mut self, enabled: bool) {
+        self.provenance.record("mise_en_place", self.setting_source, enabled.to_string());
+        self.mise_en_place = enabled;
+    }
 
-        result
+    /// Warn when mass-measured ingredients are not listed heaviest first
+    /// (off by default)
+    pub fn set_ingredient_order(&mut self, enabled: bool) {
+        self.provenance.record("ingredient_order", self.setting_source, enabled.to_string());
+        self.ingredient_order = enabled;
     }
 
-    /// Validate a recipe file
-    pub fn validate_file(&mut self, file_path: &Path) -> Result<ValidationResult, RCIPError> {
-        let content = fs::read_to_string(file_path)?;
-        let recipe: Value = serde_json::from_str(&content)?;
+    /// Note ingredient lists ordered strongly against the order steps use
+    /// them (off by default)
+    pub fn set_usage_order(&mut self, enabled: bool) {
+        self.provenance.record("usage_order", self.setting_source, enabled.to_string());
+        self.usage_order = enabled;
+    }
 
-        println!("\n📄 Validating: {}", file_path.file_name().unwrap().to_str().unwrap());
+    /// Warn when ingredient and step ids are not numbered in listing order
+    /// with consistent padding (off by default)
+    pub fn set_id_sequence(&mut self, enabled: bool) {
+        self.provenance.record("id_sequence", self.setting_source, enabled.to_string());
+        self.id_sequence = enabled;
+    }
 
-        let result = self.validate_recipe(&recipe);
-        let recipe_name = recipe.get("meta")
-            .and_then(|m| m.get("name"))
-            .and_then(|n| n.as_str())
-            .unwrap_or("Unknown Recipe");
+    /// Apply the labeling rules of these markets, e.g. `["EU", "US"]`;
+    /// each market's findings name it. Empty by default
+    pub fn set_markets(&mut self, ids: &[&str]) -> Result<(), markets::UnknownMarket> {
+        let selected = ids.iter().map(|id| markets::market(id)).collect::<Result<Vec<_>, _>>()?;
+        let names: Vec<&str> = selected.iter().map(|m| m.id).collect();
+        self.provenance.record("markets", self.setting_source, names.join(", "));
+        self.markets = selected;
+        Ok(())
+    }
 
-        self.print_result(&result, recipe_name);
-        Ok(result)
+    /// The allergen provenances diet-label and market labeling rules act on;
+    /// declared and inherited by default
+    pub fn set_allergen_provenance(&mut self, policy: allergens::ProvenancePolicy) {
+        self.provenance.record("allergen_provenance", self.setting_source, policy.to_string());
+        self.allergen_provenance = policy;
     }
 
-    /// Validate all recipes in a directory
-    pub fn validate_directory(&mut self, dir_path: &Path) -> Result<Vec<(String, ValidationResult)>, RCIPError> {
-        let mut results = Vec::new();
+    /// Warn when a bread or baking recipe's hydration or salt percentage is
+    /// implausible, usually a unit error (off by default)
+    pub fn set_baking_checks(&mut self, enabled: bool) {
+        self.provenance.record("baking_checks", self.setting_source, enabled.to_string());
+        self.baking_checks = enabled;
+    }
 
-        let entries = fs::read_dir(dir_path)?;
-        let mut recipe_files = Vec::new();
+    /// Warn when `meta.language` is missing but the language of the
+    /// description and step texts is clear (off by default)
+    pub fn set_require_language(&mut self, enabled: bool) {
+        self.provenance.record("require_language", self.setting_source, enabled.to_string());
+        self.require_language = enabled;
+    }
 
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            if let Some(ext) = path.extension() {
-                if ext == "rcip" || ext == "json" {
-                    recipe_files.push(path);
-                }
-            }
-        }
+    /// Warn when a `human_amount` does not start with a quantity
+    /// `amounts::parse_human_amount` can read (off by default)
+    pub fn set_readable_amounts(&mut self, enabled: bool) {
+        self.provenance.record("readable_amounts", self.setting_source, enabled.to_string());
+        self.readable_amounts = enabled;
+    }
 
-        println!("\n🔍 Found {} recipe files to validate\n", recipe_files.len());
+    /// How far, as a fraction, a machine amount may be from its human amount
+    /// before `RCIP-W-AMOUNT-MISMATCH` (0.05 by default)
+    pub fn set_amount_tolerance(&mut self, tolerance: f64) {
+        self.provenance.record("amount_tolerance", self.setting_source, tolerance.to_string());
+        self.amount_tolerance = tolerance;
+    }
 
-        for file_path in recipe_files {
-            let file_name = file_path.file_name().unwrap().to_str().unwrap().to_string();
-            match self.validate_file(&file_path) {
-                Ok(result) => results.push((file_name, result)),
-                Err(e) => {
-                    let mut result = ValidationResult {
-                        valid: false,
-                        errors: vec![format!("Error reading file: {}", e)],
-                        warnings: Vec::new(),
-                        info: RecipeInfo::default(),
-                    };
-                    results.push((file_name, result));
-                }
-            }
-        }
+    /// Warn about steps that take no ingredient and no step's result, such
+    /// as "combine everything" (off by default)
+    pub fn set_dangling_steps(&mut self, enabled: bool) {
+        self.provenance.record("dangling_steps", self.setting_source, enabled.to_string());
+        self.dangling_steps = enabled;
+    }
 
-        self.print_summary();
-        Ok(results)
+    /// How far, as a fraction of the steps' critical path, `total_time_minutes`
+    /// may be off before `RCIP-W-TOTAL-TIME-MISMATCH` (0.25 by default)
+    pub fn set_total_time_tolerance(&mut self, tolerance: f64) {
+        self.provenance.record("total_time_tolerance", self.setting_source, tolerance.to_string());
+        self.total_time_tolerance = tolerance;
     }
 
-    /// Apply custom validation rules
-    fn validate_custom_rules(&self, recipe: &Value, result: &mut ValidationResult) {
-        // Validate recipe ID
-        if let Some(id) = recipe.get("id").and_then(|v| v.as_str()) {
-            if !RECIPE_ID_REGEX.is_match(id) {
-                result.valid = false;
-                result.errors.push(format!("Invalid recipe ID format: {}", id));
-            }
-        }
+    /// Report keys the schema does not declare for the object they appear
+    /// in, with the key likely meant; `x-` keys and `extensions` are allowed
+    /// (off by default)
+    pub fn set_unknown_fields(&mut self, enabled: bool) {
+        self.provenance.record("unknown_fields", self.setting_source, enabled.to_string());
+        self.unknown_fields = enabled;
+    }
 
-        // Validate ingredients
-        if let Some(ingredients) = recipe.get("ingredients").and_then(|v| v.as_array()) {
-            for (i, ingredient) in ingredients.iter().enumerate() {
-                self.validate_ingredient(ingredient, i, result);
-            }
-        }
+    /// Dry-run recipes and report ingredients used up twice, results used
+    /// too early and equipment double-booking (off by default)
+    pub fn set_simulation(&mut self, enabled: bool) {
+        self.provenance.record("simulation", self.setting_source, enabled.to_string());
+        self.simulation = enabled;
+    }
 
-        // Validate steps
-        if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
-            for (i, step) in steps.iter().enumerate() {
-                self.validate_step(step, i, result);
-            }
+    /// Warn about ingredients the taxonomy cannot classify (off by default)
+    pub fn set_taxonomy_warnings(&mut self, enabled: bool) {
+        self.provenance.record("taxonomy_warnings", self.setting_source, enabled.to_string());
+        self.taxonomy_warnings = enabled;
+    }
+
+    /// Apply a built-in profile (see `PROFILES`). Its settings are
+    /// attributed to the profile, so later config files and flags override
+    /// them
+    pub fn set_profile(&mut self, name: &str) -> Result<(), UnknownProfile> {
+        if !PROFILES.contains(&name) {
+            return Err(UnknownProfile(name.to_string()));
         }
+        let source = std::mem::replace(&mut self.setting_source, SettingSource::Profile);
+        if name == "strict" {
+            self.set_mise_en_place(true);
+            self.set_ingredient_order(true);
+            self.set_usage_order(true);
+            self.set_taxonomy_warnings(true);
+            self.set_id_sequence(true);
+            self.set_baking_checks(true);
+            self.set_require_language(true);
+            self.set_readable_amounts(true);
+            self.set_simulation(true);
+            self.set_cold_chain(true);
+            self.set_verify_claims(true);
+            self.set_chill_policy(ChillPolicy { require_params: true, ..self.chill_policy.clone() });
+        }
+        self.setting_source = source;
+        self.profile = name.to_string();
+        Ok(())
+    }
 
-        // Validate cross-references
-        self.validate_references(recipe, result);
+    /// Report a rule's findings at another severity, e.g. a warning as an
+    /// error. Unknown codes are refused
+    pub fn set_severity(&mut self, code: &str, severity: Severity) -> Result<(), String> {
+        let code = codes::ALL.iter().find(|c| **c == code).copied().ok_or_else(|| format!("unknown rule code '{}'", code))?;
+        self.provenance.record("severities", self.setting_source, format!("{}={}", code, json!(severity).as_str().unwrap_or("")));
+        self.severities.insert(code, severity);
+        Ok(())
+    }
 
-        // Check version compatibility
-        if let Some(version) = recipe.get("rcip_version").and_then(|v| v.as_str()) {
-            if version != self.schema_version {
-                result.warnings.push(format!(
-                    "Recipe version {} may not be fully compatible with validator version {}",
-                    version, self.schema_version
-                ));
+    /// Turn rules on or off (see `rule_config`). Nothing changes when the
+    /// config names an unknown rule
+    pub fn set_rule_config(&mut self, config: &rule_config::RuleConfig) -> Result<(), rule_config::RuleConfigError> {
+        config.check()?;
+        for (code, enabled) in &config.rules {
+            let code = rule_config::rule_id(code).expect("checked above");
+            if *enabled {
+                self.disabled_rules.remove(code);
+            } else {
+                self.disabled_rules.insert(code);
             }
         }
+        let disabled: Vec<&str> = self.disabled_rules.iter().copied().collect();
+        self.provenance.record("disabled_rules", self.setting_source, disabled.join(", "));
+        Ok(())
     }
 
-    /// Validate an ingredient
-    fn validate_ingredient(&self, ingredient: &Value, index: usize, result: &mut ValidationResult) {
-        // Check ID format
-        if let Some(id) = ingredient.get("id").and_then(|v| v.as_str()) {
-            if !INGREDIENT_ID_REGEX.is_match(id) {
-                result.valid = false;
-                result.errors.push(format!("Ingredient {}: Invalid ID format: {}", index, id));
-            }
+    /// Run a custom rule after the built-in rules, and after the custom
+    /// rules added before it
+    pub fn add_rule(&mut self, rule: Box<dyn rules::ValidationRule>) {
+        self.provenance.record("custom_rules", self.setting_source, rule.name().to_string());
+        self.custom_rules.push(rule);
+    }
+
+    /// Report every warning as an error, so a recipe with warnings is
+    /// invalid (off by default)
+    pub fn set_strict_warnings(&mut self, enabled: bool) {
+        self.provenance.record("strict_warnings", self.setting_source, enabled.to_string());
+        self.denied_warnings = if enabled { Some(Vec::new()) } else { None };
+    }
+
+    /// Report as errors only the warnings whose code or message contains one
+    /// of `patterns`, e.g. `["NO-IMAGES", "NO-NUTRITION"]`; the rest stay
+    /// warnings
+    pub fn set_denied_warnings(&mut self, patterns: &[&str]) {
+        self.provenance.record("strict_warnings", self.setting_source, patterns.join(", "));
+        self.denied_warnings = Some(patterns.iter().map(|p| p.to_string()).collect());
+    }
+
+    /// Apply one `rule_config` setting by name, as written in config files:
+    /// a switch such as `mise_en_place = true`, `markets`, or a policy table
+    /// such as `chill_policy`, whose missing fields keep their defaults
+    pub fn apply_setting(&mut self, name: &str, value: &Value) -> Result<(), String> {
+        fn parse<T: serde::de::DeserializeOwned>(name: &str, value: &Value) -> Result<T, String> {
+            T::deserialize(value).map_err(|e| format!("invalid value for '{}': {}", name, e))
         }
 
-        // Check allergens (must be present, can be empty)
-        match ingredient.get("allergens") {
-            None => {
-                result.valid = false;
-                result.errors.push(format!("Ingredient {}: Missing required allergens field", index));
+        match name {
+            "precision_policy" => self.set_precision_policy(parse(name, value)?),
+            "chill_policy" => self.set_chill_policy(parse(name, value)?),
+            "cold_chain" => self.set_cold_chain(parse(name, value)?),
+            "verify_claims" => self.set_verify_claims(parse(name, value)?),
+            "cold_chain_policy" => self.set_cold_chain_policy(parse(name, value)?),
+            "text_policy" => self.set_text_policy(parse(name, value)?),
+            "quality_weights" => self.set_quality_weights(parse(name, value)?),
+            "taxonomy_warnings" => self.set_taxonomy_warnings(parse(name, value)?),
+            "mise_en_place" => self.set_mise_en_place(parse(name, value)?),
+            "ingredient_order" => self.set_ingredient_order(parse(name, value)?),
+            "usage_order" => self.set_usage_order(parse(name, value)?),
+            "id_sequence" => self.set_id_sequence(parse(name, value)?),
+            "baking_checks" => self.set_baking_checks(parse(name, value)?),
+            "require_language" => self.set_require_language(parse(name, value)?),
+            "readable_amounts" => self.set_readable_amounts(parse(name, value)?),
+            "amount_tolerance" => self.set_amount_tolerance(parse(name, value)?),
+            "dangling_steps" => self.set_dangling_steps(parse(name, value)?),
+            "total_time_tolerance" => self.set_total_time_tolerance(parse(name, value)?),
+            "unknown_fields" => self.set_unknown_fields(parse(name, value)?),
+            "simulation" => self.set_simulation(parse(name, value)?),
+            "markets" => {
+                let ids: Vec<String> = parse(name, value)?;
+                let ids: Vec<&str> = ids.iter().map(|id| id.as_str()).collect();
+                self.set_markets(&ids).map_err(|e| e.to_string())?;
             }
-            Some(allergens) if !allergens.is_array() => {
-                result.valid = false;
-                result.errors.push(format!("Ingredient {}: allergens must be an array", index));
+            "allergen_provenance" => {
+                let kinds: Vec<String> = parse(name, value)?;
+                self.set_allergen_provenance(kinds.join(",").parse()?);
             }
-            Some(allergens) => {
-                // Validate allergen values
-                let valid_allergens = vec![
-                    "milk", "eggs", "fish", "shellfish", "tree-nuts", "peanuts",
-                    "wheat", "gluten", "soybeans", "sesame", "celery", "mustard",
-                    "molluscs", "lupins", "sulphites", "lactose"
-                ];
-
-                if let Some(allergen_array) = allergens.as_array() {
-                    for allergen in allergen_array {
-                        if let Some(allergen_str) = allergen.as_str() {
-                            if !valid_allergens.contains(&allergen_str) {
-                                result.errors.push(format!(
-                                    "Ingredient {}: Invalid allergen '{}'",
-                                    index, allergen_str
-                                ));
-                            }
-                        }
-                    }
+            "disabled_rules" => {
+                let codes: Vec<String> = parse(name, value)?;
+                let mut config = rule_config::RuleConfig::default();
+                for code in &codes {
+                    config.disable(code);
                 }
+                self.set_rule_config(&config).map_err(|e| e.to_string())?;
             }
-        }
-
-        // Check machine amount
-        if let Some(ma) = ingredient.get("machine_amount") {
-            if let Some(value) = ma.get("value") {
-                if !value.is_number() || value.as_f64().unwrap_or(-1.0) < 0.0 {
-                    result.errors.push(format!(
-                        "Ingredient {}: machine_amount.value must be non-negative number",
-                        index
-                    ));
+            // `true` for every warning, or a list of codes and message parts
+            "strict_warnings" => match value {
+                Value::Bool(enabled) => self.set_strict_warnings(*enabled),
+                _ => {
+                    let patterns: Vec<String> = parse(name, value)?;
+                    self.set_denied_warnings(&patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>());
                 }
-            }
-            if ma.get("unit").is_none() {
-                result.errors.push(format!("Ingredient {}: machine_amount.unit is required", index));
-            }
+            },
+            _ => return Err(format!("unknown setting '{}'", name)),
         }
+        Ok(())
     }
 
-    /// Validate a step
-    fn validate_step(&self, step: &Value, index: usize, result: &mut ValidationResult) {
-        // Check ID format
-        if let Some(id) = step.get("step_id").and_then(|v| v.as_str()) {
-            if !STEP_ID_REGEX.is_match(id) {
-                result.valid = false;
-                result.errors.push(format!("Step {}: Invalid ID format: {}", index, id));
-            }
-        }
+    /// Render issue messages in this locale, e.g. "de"; untranslated
+    /// messages fall back to English
+    pub fn set_locale(&mut self, locale: &str) {
+        self.locale = locale.to_string();
+    }
 
-        // Check action
-        if let Some(action) = step.get("action").and_then(|v| v.as_str()) {
-            let valid_actions = vec![
-                "add", "mix", "combine", "blend", "cut", "slice", "dice", "chop", "mince",
-                "heat", "boil", "simmer", "steam", "fry", "saute", "bake", "roast", "grill",
-                "cool", "chill", "freeze", "knead", "fold", "roll", "shape", "ferment",
-                "proof", "rest", "strain", "filter", "separate", "measure", "weigh", "wait",
-                "dissolve", "prepare", "spread", "garnish", "divide"
-            ];
+    /// Cross-check declared allergens against products resolved from
+    /// `external_ids` (off by default)
+    pub fn set_resolvers(&mut self, resolvers: Resolvers) {
+        self.provenance.record("resolvers", self.setting_source, "true".to_string());
+        self.resolvers = Some(resolvers);
+    }
+
+    /// Run a WASM rule pack after the built-in rules
+    #[cfg(feature = "wasm-rules")]
+    pub fn add_rule_pack(&mut self, pack: rule_pack::RulePack) {
+        self.provenance.record("rule_packs", self.setting_source, pack.name().to_string());
+        self.rule_packs.push(pack);
+    }
+
+    /// Fail `init` when the schema disagrees with the built-in rules instead
+    /// of printing warnings
+    pub fn set_strict_schema(&mut self, strict: bool) {
+        self.strict_schema = strict;
+    }
+
+    /// Fail `init` when the schema cannot be parsed or compiled instead of
+    /// validating with the built-in rules only (off by default)
+    pub fn set_require_schema(&mut self, required: bool) {
+        self.require_schema = required;
+    }
+
+    /// Whether the schema compiled; see `SchemaStatus`
+    pub fn schema_status(&self) -> &SchemaStatus {
+        &self.schema_status
+    }
+
+    /// Memory-map recipe files instead of reading them into memory, falling
+    /// back to reading when mapping fails (off by default)
+    pub fn set_memory_map(&mut self, enabled: bool) {
+        self.memory_map = enabled;
+    }
+
+    /// Record in each file's result the SHA-256 of the exact bytes it was
+    /// validated from, as `source_sha256` (off by default)
+    pub fn set_fingerprint_files(&mut self, enabled: bool) {
+        self.fingerprint_files = enabled;
+    }
+
+    /// Most threads a directory is validated on with the `parallel` feature;
+    /// None for one per core. Without the feature files are validated one
+    /// at a time whatever this says
+    pub fn set_jobs(&mut self, jobs: Option<usize>) {
+        self.jobs = jobs;
+    }
+
+    /// Where the file-based API reports progress and results; a
+    /// `NullReporter`, which prints nothing, unless set (see `reporter`)
+    pub fn set_reporter(&mut self, reporter: Box<dyn reporter::Reporter>) {
+        self.reporter = reporter;
+    }
+
+    /// Replace base64 `data:` URIs in recipe files with size-recording
+    /// placeholders while parsing (off by default)
+    pub fn set_skip_binary_payloads(&mut self, enabled: bool) {
+        self.skip_binary_payloads = enabled;
+    }
+
+    /// Flag files beyond these percentiles of the corpus with
+    /// RCIP-I-CORPUS-OUTLIER notices when validating a directory or source
+    /// (off by default)
+    pub fn set_flag_outliers(&mut self, thresholds: Option<corpus::Thresholds>) {
+        self.outlier_thresholds = thresholds;
+    }
+
+    /// Percentiles and outliers of the last directory or source validated
+    /// with outlier flagging on
+    pub fn corpus_report(&self) -> Option<&corpus::CorpusReport> {
+        self.corpus_report.as_ref()
+    }
+
+    /// Differences between the loaded schema and the built-in rules
+    pub fn schema_divergences(&self) -> &[String] {
+        &self.schema_divergences
+    }
+
+    /// Effective rule configuration, as hashed into result provenance
+    pub fn rule_config(&self) -> Value {
+        json!({
+            "precision_policy": self.precision_policy,
+            "chill_policy": self.chill_policy,
+            "cold_chain": self.cold_chain,
+            "verify_claims": self.verify_claims,
+            "cold_chain_policy": self.cold_chain_policy,
+            "text_policy": self.text_policy,
+            "quality_weights": self.quality_weights,
+            "taxonomy_warnings": self.taxonomy_warnings,
+            "mise_en_place": self.mise_en_place,
+            "ingredient_order": self.ingredient_order,
+            "usage_order": self.usage_order,
+            "id_sequence": self.id_sequence,
+            "markets": self.markets.iter().map(|m| m.id).collect::<Vec<_>>(),
+            "baking_checks": self.baking_checks,
+            "require_language": self.require_language,
+            "readable_amounts": self.readable_amounts,
+            "amount_tolerance": self.amount_tolerance,
+            "dangling_steps": self.dangling_steps,
+            "total_time_tolerance": self.total_time_tolerance,
+            "unknown_fields": self.unknown_fields,
+            "simulation": self.simulation,
+            "resolvers": self.resolvers.is_some(),
+            "rule_packs": self.rule_pack_hashes(),
+            "severities": self.severities,
+            "strict_warnings": self.denied_warnings,
+            "disabled_rules": self.disabled_rules,
+            "schema_versions": self.versions.keys().collect::<Vec<_>>(),
+            "custom_rules": self.custom_rules.iter().map(|rule| rule.name()).collect::<Vec<_>>(),
+        })
+    }
+
+    #[cfg(feature = "wasm-rules")]
+    fn rule_pack_hashes(&self) -> Value {
+        self.rule_packs.iter().map(|p| json!({"name": p.name(), "sha256": p.sha256()})).collect()
+    }
+
+    #[cfg(not(feature = "wasm-rules"))]
+    fn rule_pack_hashes(&self) -> Value {
+        json!([])
+    }
+
+    /// Every rule with its effective severity, options and where each
+    /// setting came from; nothing is validated
+    pub fn effective_rules(&self) -> Vec<EffectiveRule> {
+        explain::effective_rules(&self.rule_config(), &self.provenance)
+    }
+
+    /// Provenance stamp for results produced with the current setup
+    pub fn meta(&self) -> ValidationMeta {
+        ValidationMeta::new(&self.schema_version, self.schema_sha256.clone(), &self.profile, &self.rule_config())
+    }
+
+    /// Validator compiled from the schema of `version` embedded in the
+    /// binary; reads no files
+    pub fn with_bundled_schema(version: &str) -> Result<Self, RCIPError> {
+        let mut validator = RCIPValidator::new(version);
+        validator.init_bundled()?;
+        Ok(validator)
+    }
+
+    /// Initialize validator with the embedded schema of its version
+    pub fn init_bundled(&mut self) -> Result<(), RCIPError> {
+        let schema = warm::bundled_schema(&self.schema_version)
+            .ok_or_else(|| RCIPError::UnknownSchemaVersion(self.schema_version.clone()))?;
+        self.init_from_str(schema)
+    }
 
-            if !valid_actions.contains(&action) {
-                result.errors.push(format!("Step {}: Invalid action '{}'", index, action));
+    /// Initialize validator with schema. Without a path the schema is read
+    /// from the source tree (`../../schemas`); with the `bundled-schemas`
+    /// feature, the embedded schema is used when that file does not exist
+    #[cfg(not(feature = "no-fs"))]
+    pub fn init(&mut self, schema_path: Option<&Path>) -> Result<(), RCIPError> {
+        match schema_path {
+            Some(path) => self.init_from_path(path, false),
+            None => {
+                let path = PathBuf::from(format!("../../schemas/rcip-v{}.json", self.schema_version));
+                self.init_from_path(&path, cfg!(feature = "bundled-schemas"))
             }
         }
+    }
 
-        // Check hazards
-        if let Some(hazards) = step.get("hazards").and_then(|v| v.as_array()) {
-            let valid_hazards = vec![
-                "hot-surface", "sharp-tool", "electrical", "chemical", "pressure", "allergen-cross-contact"
-            ];
-
-            for hazard in hazards {
-                if let Some(hazard_str) = hazard.as_str() {
-                    if !valid_hazards.contains(&hazard_str) {
-                        result.warnings.push(format!("Step {}: Non-standard hazard '{}'", index, hazard_str));
-                    }
-                }
+    /// Initialize from a schema file, or from the embedded schema when
+    /// `bundled_fallback` is set and the file does not exist
+    #[cfg(not(feature = "no-fs"))]
+    fn init_from_path(&mut self, path: &Path, bundled_fallback: bool) -> Result<(), RCIPError> {
+        let schema_content = match fs::read_to_string(path) {
+            Err(e) if bundled_fallback && e.kind() == std::io::ErrorKind::NotFound => {
+                return self.init_bundled();
             }
+            read => read?,
+        };
+        self.init_from_str(&schema_content)?;
+        if self.schema_status == SchemaStatus::Compiled {
+            self.reporter.initialized(&self.schema_version);
         }
+        Ok(())
     }
 
-    /// Validate cross-references
-    fn validate_references(&self, recipe: &Value, result: &mut ValidationResult) {
-        // Collect all IDs
-        let mut ingredient_ids = HashSet::new();
-        let mut step_ids = HashSet::new();
+    /// Initialize validator with schema JSON already in memory; touches no
+    /// files and prints nothing. A schema that does not parse or compile
+    /// leaves the validator degraded (`SchemaStatus::Failed`) unless
+    /// `set_require_schema` is on, in which case it is an error
+    pub fn init_from_str(&mut self, schema_content: &str) -> Result<(), RCIPError> {
+        self.schema_sha256 = Some(provenance::sha256_hex(schema_content.as_bytes()));
+        let schema: Value = match serde_json::from_str(schema_content) {
+            Ok(schema) => schema,
+            Err(e) if self.require_schema => return Err(e.into()),
+            Err(e) => {
+                self.degrade(format!("the schema is not valid JSON: {}", e));
+                return Ok(());
+            }
+        };
 
-        if let Some(ingredients) = recipe.get("ingredients").and_then(|v| v.as_array()) {
-            for ing in ingredients {
-                if let Some(id) = ing.get("id").and_then(|v| v.as_str()) {
-                    ingredient_ids.insert(id.to_string());
-                }
+        self.schema_divergences = schema_divergences(&schema);
+        if !self.schema_divergences.is_empty() {
+            if self.strict_schema {
+                return Err(RCIPError::SchemaError(format!(
+                    "schema disagrees with built-in rules: {}",
+                    self.schema_divergences.join("; ")
+                )));
+            }
+            for divergence in &self.schema_divergences {
+                self.reporter.schema_divergence(divergence);
             }
         }
 
-        if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
-            for step in steps {
-                if let Some(id) = step.get("step_id").and_then(|v| v.as_str()) {
-                    step_ids.insert(id.to_string());
-                }
+        match JSONSchema::compile(&schema) {
+            Ok(compiled) => {
+                self.compiled_schema = Some(compiled);
+                self.schema = Some(schema);
+                self.schema_status = SchemaStatus::Compiled;
+                Ok(())
+            }
+            Err(e) if self.require_schema => Err(RCIPError::SchemaError(format!("Failed to compile schema: {}", e))),
+            Err(e) => {
+                self.degrade(format!("the schema failed to compile: {}", e));
+                Ok(())
             }
         }
+    }
 
-        // Check step targets
-        if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
-            for step in steps {
-                if let Some(targets) = step.get("target").and_then(|v| v.as_array()) {
-                    for target in targets {
-                        if let Some(target_str) = target.as_str() {
-                            if target_str.starts_with("ing-") && !ingredient_ids.contains(target_str) {
-                                result.errors.push(format!(
-                                    "Step {}: Invalid ingredient reference '{}'",
-                                    step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?"),
-                                    target_str
-                                ));
-                            } else if target_str.contains(":result") {
-                                let step_ref = target_str.split(':').next().unwrap();
-                                if !step_ids.contains(step_ref) {
-                                    result.errors.push(format!(
-                                        "Step {}: Invalid step reference '{}'",
-                                        step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?"),
-                                        target_str
-                                    ));
-                                }
-                            }
-                        }
-                    }
-                }
+    /// Also load the schemas of these versions, read like `init(None)`
+    /// reads the validator's own. Each recipe is then checked against the
+    /// schema its `rcip_version` names; one of a version without a schema
+    /// is checked against the validator's, with a version-mismatch warning
+    #[cfg(not(feature = "no-fs"))]
+    pub fn init_versions(&mut self, versions: &[&str]) -> Result<(), RCIPError> {
+        for version in versions {
+            if *version == self.schema_version {
+                continue;
             }
+            let path = PathBuf::from(format!("../../schemas/rcip-v{}.json", version));
+            let content = match fs::read_to_string(&path) {
+                Err(e) if cfg!(feature = "bundled-schemas") && e.kind() == std::io::ErrorKind::NotFound => warm::bundled_schema(version)
+                    .ok_or_else(|| RCIPError::UnknownSchemaVersion(version.to_string()))?
+                    .to_string(),
+                read => read?,
+            };
+            self.add_schema_version(version, &content)?;
         }
+        Ok(())
     }
 
-    /// Check for warnings
-    fn check_warnings(&self, recipe: &Value) -> Vec<String> {
-        let mut warnings = Vec::new();
-
-        let meta = recipe.get("meta");
+    /// Check recipes whose `rcip_version` is `version` against this schema
+    /// JSON; touches no files. Unlike the validator's own schema, one that
+    /// does not parse or compile is always an error
+    pub fn add_schema_version(&mut self, version: &str, schema_content: &str) -> Result<(), RCIPError> {
+        let schema: Value = serde_json::from_str(schema_content)?;
+        let compiled = JSONSchema::compile(&schema)
+            .map_err(|e| RCIPError::SchemaError(format!("Failed to compile schema v{}: {}", version, e)))?;
+        self.provenance.record("schema_versions", self.setting_source, version.to_string());
+        self.versions.insert(version.to_string(), VersionSchema { schema, compiled });
+        Ok(())
+    }
 
-        // Check for missing recommended fields
-        if meta.and_then(|m| m.get("description")).is_none() {
-            warnings.push("Missing recommended field: meta.description".to_string());
-        }
+    /// The loaded schema a recipe's own version names, if not the
+    /// validator's
+    fn versioned(&self, recipe: &Value) -> Option<(&str, &VersionSchema)> {
+        let version = recipe.get("rcip_version").and_then(|v| v.as_str())?;
+        self.versions.get_key_value(version).map(|(version, schema)| (version.as_str(), schema))
+    }
 
-        if meta.and_then(|m| m.get("servings")).is_none() {
-            warnings.push("Missing recommended field: meta.servings".to_string());
-        }
+    /// The schema version a recipe is checked against: its `rcip_version`
+    /// when that schema is loaded, the validator's otherwise
+    pub fn schema_version_for(&self, recipe: &Value) -> &str {
+        self.versioned(recipe).map(|(version, _)| version).unwrap_or(&self.schema_version)
+    }
 
-        if meta.and_then(|m| m.get("difficulty")).is_none() {
-            warnings.push("Missing recommended field: meta.difficulty".to_string());
-        }
+    /// Run without a schema, built-in rules only
+    fn degrade(&mut self, reason: String) {
+        self.compiled_schema = None;
+        self.schema = None;
+        self.schema_status = SchemaStatus::Failed(reason);
+    }
 
-        // Check for missing nutritional data
-        let has_nutritional = recipe.get("ingredients")
-            .and_then(|v| v.as_array())
-            .map(|ingredients| {
-                ingredients.iter().any(|ing| ing.get("nutritional").is_some())
-            })
-            .unwrap_or(false);
+    /// Validate a recipe and count it in the session statistics
+    pub fn validate_recipe(&mut self, recipe: &Value) -> ValidationResult {
+        let result = self.validate(recipe);
+        self.record_stats(&result);
+        result
+    }
 
-        if !has_nutritional {
-            warnings.push("No nutritional data provided for any ingredient".to_string());
+    fn record_stats(&mut self, result: &ValidationResult) {
+        self.stats.validated += 1;
+        self.stats.errors += result.errors.len() as u32;
+        self.stats.warnings += result.warnings.len() as u32;
+        for issue in &result.issues {
+            *self.stats.rule_counts.entry(issue.code.to_string()).or_insert(0) += 1;
+        }
+        if result.valid {
+            self.stats.passed += 1;
+        } else {
+            self.stats.failed += 1;
         }
+    }
 
-        // Check for missing external IDs
-        let has_external_ids = recipe.get("ingredients")
-            .and_then(|v| v.as_array())
-            .map(|ingredients| {
-                ingredients.iter().any(|ing| {
-                    ing.get("external_ids")
-                        .and_then(|e| e.as_object())
-                        .map(|o| !o.is_empty())
-                        .unwrap_or(false)
-                })
-            })
-            .unwrap_or(false);
+    /// Validate a recipe without touching the statistics, so one validator
+    /// can serve concurrent callers through a shared reference
+    pub fn validate(&self, recipe: &Value) -> ValidationResult {
+        self.validate_recipe_with_observer(recipe, &mut NoopObserver)
+    }
 
-        if !has_external_ids {
-            warnings.push("No external IDs (USDA, GTIN, etc.) provided".to_string());
-        }
+    /// Parse a recipe into the typed structs of `recipe`
+    pub fn parse_recipe(recipe: &Value) -> Result<recipe::Recipe, RCIPError> {
+        recipe::Recipe::from_value(recipe)
+    }
 
-        // Check for very long cooking times
-        if let Some(total_time) = meta.and_then(|m| m.get("total_time_minutes")).and_then(|v| v.as_f64()) {
-            if total_time > 1440.0 {
-                warnings.push(format!(
-                    "Very long cooking time ({} min / {:.1} hours)",
-                    total_time, total_time / 60.0
-                ));
-            }
-        }
+    /// Validate a typed recipe; the issues are those of the document it was
+    /// parsed from
+    pub fn validate_typed(&self, recipe: &recipe::Recipe) -> ValidationResult {
+        self.validate(&recipe.to_value())
+    }
 
-        // Check for missing images
-        if recipe.get("images").and_then(|v| v.as_array()).map(|a| a.is_empty()).unwrap_or(true) {
-            warnings.push("No images provided for recipe".to_string());
-        }
+    /// Validate a recipe, reporting each phase and issue to an observer as
+    /// validation runs (see `observer`)
+    pub fn validate_recipe_with_observer(&self, recipe: &Value, observer: &mut dyn ValidationObserver) -> ValidationResult {
+        self.validate_guarded(recipe, &mut Guard::new(observer), None)
+    }
 
-        warnings
+    /// Validate a recipe, reusing the cached issues of the items an
+    /// item-scoped phase checked unchanged last time (see `patch`)
+    fn validate_cached(&self, recipe: &Value, cache: &mut patch::ItemCache) -> ValidationResult {
+        cache.begin(&self.meta().fingerprint());
+        let result = self.validate_guarded(recipe, &mut Guard::new(&mut NoopObserver), Some(&mut *cache));
+        cache.finish();
+        result
     }
 
-    /// Get recipe information
-    fn get_recipe_info(&self, recipe: &Value) -> RecipeInfo {
-        let meta = recipe.get("meta");
+    fn validate_guarded(&self, recipe: &Value, guard: &mut Guard, cache: Option<&mut patch::ItemCache>) -> ValidationResult {
+        let mut result = ValidationResult::with_locale(&self.locale);
+        result.meta = Some(self.meta());
 
-        // Get all allergens
-        let mut allergens = HashSet::new();
-        if let Some(ingredients) = recipe.get("ingredients").and_then(|v| v.as_array()) {
-            for ingredient in ingredients {
-                if let Some(allergen_array) = ingredient.get("allergens").and_then(|v| v.as_array()) {
-                    for allergen in allergen_array {
-                        if let Some(allergen_str) = allergen.as_str() {
-                            allergens.insert(allergen_str.to_string());
+        // JSON Schema validation, or a note that it was skipped
+        let versioned = self.versioned(recipe);
+        let (status, compiled) = match versioned {
+            Some((_, schema)) => (&SchemaStatus::Compiled, Some(&schema.compiled)),
+            None => (&self.schema_status, self.compiled_schema.as_ref()),
+        };
+        match (status, compiled) {
+            (SchemaStatus::Compiled, Some(compiled_schema)) => {
+                self.phase(Phase::Schema, guard, &mut result, |result| {
+                    if let Err(errors) = compiled_schema.validate(recipe) {
+                        // Names spelled loosely are reported by the vocabulary rules
+                        let errors = errors.filter(|e| !terms::is_variant(recipe, &e.instance_path.to_string()));
+                        for error in errors {
+                            result.error(codes::SCHEMA, &[("path", &error.instance_path), ("detail", &error)]);
                         }
                     }
-                }
+                });
+            }
+            (SchemaStatus::Failed(reason), _) => {
+                result.error(codes::SCHEMA_SKIPPED, &[("reason", reason)]);
+                result.schema_checked = false;
+            }
+            _ => {
+                result.error(codes::NOT_INITIALIZED, &[]);
+                result.schema_checked = false;
+                return result;
             }
         }
 
-        RecipeInfo {
-            name: meta.and_then(|m| m.get("name"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("Unknown")
-                .to_string(),
-            version: recipe.get("rcip_version")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string(),
-            recipe_version: meta.and_then(|m| m.get("version"))
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string()),
-            ingredient_count: recipe.get("ingredients")
-                .and_then(|v| v.as_array())
-                .map(|a| a.len())
-                .unwrap_or(0),
-            step_count: recipe.get("steps")
-                .and_then(|v| v.as_array())
-                .map(|a| a.len())
-                .unwrap_or(0),
-            has_device_profiles: recipe.get("device_profiles")
-                .and_then(|v| v.as_array())
-                .map(|a| !a.is_empty())
-                .unwrap_or(false),
-            has_sensors: recipe.get("sensors")
-                .and_then(|v| v.as_array())
-                .map(|a| !a.is_empty())
-                .unwrap_or(false),
-            allergens: allergens.into_iter().collect(),
-            diet_labels: meta.and_then(|m| m.get("diet_labels"))
-                .and_then(|v| v.as_array())
-                .map(|a| {
-                    a.iter()
-                        .filter_map(|v| v.as_str())
-                        .map(|s| s.to_string())
-                        .collect()
-                })
-                .unwrap_or_else(Vec::new),
-            difficulty: meta.and_then(|m| m.get("difficulty"))
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string()),
-            total_time: meta.and_then(|m| m.get("total_time_minutes"))
-                .and_then(|v| v.as_f64()),
-        }
-    }
-
-    /// Print validation result
-    fn print_result(&self, result: &ValidationResult, recipe_name: &str) {
-        println!("\n{}", "=".repeat(60));
-        println!("Recipe: {}", recipe_name);
-        println!("Status: {}", if result.valid { "✅ VALID" } else { "❌ INVALID" });
+        // Custom validations
+        self.validate_custom_rules(recipe, &mut result, guard, cache);
 
-        println!("\n📊 Recipe Info:");
-        println!("  - RCIP Version: {}", result.info.version);
-        println!("  - Ingredients: {}", result.info.ingredient_count);
-        println!("  - Steps: {}", result.info.step_count);
-        println!("  - Difficulty: {}", result.info.difficulty.as_ref().unwrap_or(&"not specified".to_string()));
+        // Check warnings
+        self.phase(Phase::Warnings, guard, &mut result, |result| self.check_warnings(recipe, result));
 
-        if let Some(time) = result.info.total_time {
-            println!("  - Total Time: {} minutes", time);
+        // Run custom rules
+        if !self.custom_rules.is_empty() {
+            self.phase(Phase::CustomRules, guard, &mut result, |result| {
+                for rule in &self.custom_rules {
+                    rule.check(recipe, result);
+                }
+            });
         }
 
-        if !result.info.allergens.is_empty() {
-            println!("  - Allergens: {}", result.info.allergens.join(", "));
+        // Get recipe info
+        self.phase(Phase::Info, guard, &mut result, |result| {
+            result.info = info::recipe_info(recipe);
+            result.info.nutrition =
+                labels::compute_nutrition(recipe).ok().filter(|n| n.coverage >= labels::MIN_COVERAGE);
+        });
+        if result.schema_checked {
+            result.info.schema_version = Some(self.schema_version_for(recipe).to_string());
         }
-
-        if !result.info.diet_labels.is_empty() {
-            println!("  - Diet Labels: {}", result.info.diet_labels.join(", "));
+        guard.report(&mut result);
+        result.disable_rules(&self.disabled_rules);
+        result.override_severities(&self.severities);
+        if let Some(patterns) = &self.denied_warnings {
+            result.deny_warnings(patterns);
         }
+        result.sort();
+        result.info.quality = Some(analysis::quality_score_with(recipe, &result, &self.quality_weights));
+        result
+    }
 
-        if !result.errors.is_empty() {
-            println!("\n❌ Errors ({}):", result.errors.len());
-            for (i, error) in result.errors.iter().take(10).enumerate() {
-                println!("  {}. {}", i + 1, error);
-            }
-            if result.errors.len() > 10 {
-                println!("  ... and {} more errors", result.errors.len() - 10);
+    /// Run one phase of validation, timing it and reporting the issues it adds
+    fn phase(&self, phase: Phase, guard: &mut Guard, result: &mut ValidationResult, rules: impl FnOnce(&mut ValidationResult)) {
+        guard.notify(phase.name(), |observer| observer.on_phase_start(phase));
+        let (started, before) = (Instant::now(), result.issues.len());
+        rules(result);
+        let elapsed = started.elapsed();
+        for issue in result.issues[before..].iter().filter(|issue| !self.disabled_rules.contains(issue.code)) {
+            let mut severity = self.severities.get(issue.code).copied().unwrap_or(issue.severity);
+            if let Some(patterns) = &self.denied_warnings {
+                if severity == Severity::Warning && denies(patterns, issue.code, &i18n::render(&self.locale, issue.code, &issue.params)) {
+                    severity = Severity::Error;
+                }
             }
+            let issue = Issue { severity, ..issue.clone() };
+            guard.notify(phase.name(), |observer| observer.on_issue(&issue));
         }
+        guard.notify(phase.name(), |observer| observer.on_phase_end(phase, elapsed));
+    }
 
-        if !result.warnings.is_empty() {
-            println!("\n⚠️  Warnings ({}):", result.warnings.len());
-            for warning in &result.warnings {
-                println!("  - {}", warning);
+    /// Validate a recipe held in memory as raw JSON bytes, parsed in place
+    /// without going through a `String`. Honours `set_skip_binary_payloads`;
+    /// a payload that does not parse yields an invalid result
+    pub fn validate_bytes(&self, bytes: &[u8]) -> ValidationResult {
+        match self.parse_recipe_bytes(bytes) {
+            Ok(recipe) => self.validate(&recipe),
+            Err(e) => {
+                let mut result = ValidationResult::with_locale(&self.locale);
+                result.meta = Some(self.meta());
+                result.error(codes::READ_FAILED, &[("error", &RCIPError::from(e))]);
+                result
             }
         }
-
-        println!("{}", "=".repeat(60));
     }
 
-    /// Print validation summary
-    fn print_summary(&self) {
-        println!("\n{}", "=".repeat(60));
-        println!("📈 VALIDATION SUMMARY");
-        println!("{}", "=".repeat(60));
-        println!("Total Validated: {}", self.stats.validated);
-
-        if self.stats.validated > 0 {
-            let pass_rate = (self.stats.passed as f64 / self.stats.validated as f64) * 100.0;
-            let fail_rate = (self.stats.failed as f64 / self.stats.validated as f64) * 100.0;
-            println!("✅ Passed: {} ({:.0}%)", self.stats.passed, pass_rate);
-            println!("❌ Failed: {} ({:.0}%)", self.stats.failed, fail_rate);
+    /// Parse a recipe held in memory, without payloads when so configured
+    fn parse_recipe_bytes(&self, bytes: &[u8]) -> Result<Value, serde_json::Error> {
+        if self.skip_binary_payloads {
+            large_files::parse_without_payloads(bytes)
+        } else {
+            serde_json::from_slice(bytes)
         }
+    }
 
-        println!("{}\n", "=".repeat(60));
+    /// Parse and validate a recipe held in a string, without touching the
+    /// filesystem. Counts in the statistics and is reported like
+    /// `validate_file`, and gives the same result for the same content
+    pub fn validate_str(&mut self, recipe: &str) -> Result<ValidationResult, RCIPError> {
+        let recipe = self.parse_recipe_bytes(recipe.as_bytes())?;
+        Ok(self.validate_counted(&recipe, &mut Guard::new(&mut NoopObserver)))
     }
 
-    /// Reset statistics
-    pub fn reset_stats(&mut self) {
-        self.stats = ValidationStats::default();
+    /// Read a recipe to the end, e.g. from a request body or stdin, then
+    /// parse and validate it like `validate_str`
+    pub fn validate_reader<R: Read>(&mut self, mut reader: R) -> Result<ValidationResult, RCIPError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let recipe = self.parse_recipe_bytes(&bytes)?;
+        Ok(self.validate_counted(&recipe, &mut Guard::new(&mut NoopObserver)))
     }
 
-    /// Get statistics
-    pub fn get_stats(&self) -> &ValidationStats {
-        &self.stats
+    /// Validate a parsed recipe, count it in the statistics and report it
+    fn validate_counted(&mut self, recipe: &Value, guard: &mut Guard) -> ValidationResult {
+        let result = self.validate_guarded(recipe, guard, None);
+        self.record_stats(&result);
+        let recipe_name = recipe.pointer("/meta/name").and_then(|n| n.as_str()).unwrap_or("Unknown Recipe");
+        self.reporter.file_result(recipe_name, &result);
+        result
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::json;
+    /// Validate a recipe file
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_file(&mut self, file_path: &Path) -> Result<ValidationResult, RCIPError> {
+        self.validate_file_with_observer(file_path, &mut NoopObserver)
+    }
 
-    #[test]
-    fn test_valid_minimal_recipe() {
-        let mut validator = RCIPValidator::new("0.1");
+    /// Validate a recipe file, reporting its phases and issues to an observer
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_file_with_observer(&mut self, file_path: &Path, observer: &mut dyn ValidationObserver) -> Result<ValidationResult, RCIPError> {
+        self.validate_file_guarded(file_path, &mut Guard::new(observer)).map(|(result, _)| result)
+    }
 
-        let recipe = json!({
-            "rcip_version": "0.1",
-            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
-            "meta": {
-                "name": "Test Recipe",
-                "author": "Test Author",
-                "created_date": "2025-01-15T10:00:00Z"
-            },
-            "ingredients": [
-                {
-                    "id": "ing-0001",
-                    "name": "test ingredient",
-                    "human_amount": "1 piece",
-                    "machine_amount": {
-                        "value": 1,
-                        "unit": "pcs"
-                    },
-                    "allergens": []
-                }
-            ],
-            "steps": [
-                {
-                    "step_id": "s-01",
-                    "human_text": "Test step",
-                    "action": "mix"
-                }
-            ]
-        });
+    #[cfg(not(feature = "no-fs"))]
+    fn validate_file_guarded(&mut self, file_path: &Path, guard: &mut Guard) -> Result<(ValidationResult, Value), RCIPError> {
+        let mut sha256 = None;
+        let recipe = self.read_recipe_file(file_path, &mut sha256)?;
 
-        let result = validator.validate_recipe(&recipe);
-        assert!(result.valid, "Minimal recipe should be valid");
+        self.reporter.file_start(file_path.file_name().unwrap().to_str().unwrap());
+        let mut result = self.validate_counted(&recipe, guard);
+        result.source_sha256 = sha256;
+        Ok((result, recipe))
     }
 
-    #[test]
-    fn test_invalid_recipe_id() {
-        let mut validator = RCIPValidator::new("0.1");
+    /// Parse a recipe file, mapped and without payloads when so configured.
+    /// With `fingerprint_files` on, `sha256` is set to the hash of the bytes
+    /// parsed before they are parsed, so a file that does not parse has one
+    #[cfg(not(feature = "no-fs"))]
+    fn read_recipe_file(&self, file_path: &Path, sha256: &mut Option<String>) -> Result<Value, RCIPError> {
+        let mut fingerprint = |bytes: &[u8]| {
+            if self.fingerprint_files {
+                *sha256 = Some(provenance::sha256_hex(bytes));
+            }
+        };
 
-        let recipe = json!({
-            "rcip_version": "0.1",
-            "id": "invalid-id",
-            "meta": {
+        if self.memory_map {
+            if let Some(map) = large_files::map_file(file_path) {
+                fingerprint(&map[..]);
+                return Ok(self.parse_recipe_bytes(&map[..])?);
+            }
+        }
+        if self.skip_binary_payloads {
+            let bytes = fs::read(file_path)?;
+            fingerprint(&bytes);
+            return Ok(self.parse_recipe_bytes(&bytes)?);
+        }
+        let content = fs::read_to_string(file_path)?;
+        fingerprint(content.as_bytes());
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Validate all recipes in a directory
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_directory(&mut self, dir_path: &Path) -> Result<Vec<(String, ValidationResult)>, RCIPError> {
+        self.validate_directory_with_observer(dir_path, &mut NoopObserver)
+    }
+
+    /// Validate all recipes in a directory, reporting each file as well as
+    /// its phases and issues to an observer
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_directory_with_observer(&mut self, dir_path: &Path, observer: &mut dyn ValidationObserver) -> Result<Vec<(String, ValidationResult)>, RCIPError> {
+        self.validate_directory_with_options(dir_path, &discover::DirectoryOptions::default(), observer)
+    }
+
+    /// Validate the recipes a `DirectoryOptions` selects under a directory,
+    /// subdirectories included when it is recursive. Results are named by
+    /// their path relative to the directory, `/`-separated, in path order
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_directory_with_options(
+        &mut self,
+        dir_path: &Path,
+        options: &discover::DirectoryOptions,
+        observer: &mut dyn ValidationObserver,
+    ) -> Result<Vec<(String, ValidationResult)>, RCIPError> {
+        let recipe_files = discover::find(dir_path, options)?;
+        self.reporter.batch_start(recipe_files.len(), None);
+
+        // Read, parse and validate every file, in parallel with the
+        // `parallel` feature; events are buffered and delivered in file order
+        let checked: Vec<(Option<String>, Result<CheckedFile, RCIPError>)> = self.each_file(&recipe_files, |file_path| {
+            let mut sha256 = None;
+            let checked = self.read_recipe_file(file_path, &mut sha256).map(|recipe| {
+                let mut recorder = Recorder::default();
+                let result = self.validate_recipe_with_observer(&recipe, &mut recorder);
+                let recipe_name = recipe.pointer("/meta/name").and_then(|n| n.as_str()).unwrap_or("Unknown Recipe").to_string();
+                let sample = self.outlier_thresholds.is_some().then(|| {
+                    corpus::CorpusSample::new(&discover::relative_name(dir_path, file_path), &recipe)
+                });
+                CheckedFile { result, recorder, recipe_name, sample }
+            });
+            (sha256, checked)
+        });
+
+        let mut results = Vec::new();
+        let mut samples = Vec::new();
+        for (file_path, (sha256, checked)) in recipe_files.iter().zip(checked) {
+            let file_name = discover::relative_name(dir_path, file_path);
+            let mut guard = Guard::new(&mut *observer);
+            guard.notify("file start", |observer| observer.on_file_start(file_path));
+            let mut result = match checked {
+                Ok(checked) => {
+                    self.reporter.file_start(&file_name);
+                    checked.recorder.replay(&mut guard);
+                    self.record_stats(&checked.result);
+                    self.reporter.file_result(&checked.recipe_name, &checked.result);
+                    samples.extend(checked.sample);
+                    checked.result
+                }
+                Err(e) => {
+                    let mut result = ValidationResult::with_locale(&self.locale);
+                    result.meta = Some(self.meta());
+                    result.error(codes::READ_FAILED, &[("error", &e)]);
+                    result
+                }
+            };
+            result.source_sha256 = sha256;
+            guard.notify("file end", |observer| observer.on_file_end(file_path, &result));
+            if guard.report(&mut result) {
+                result.override_severities(&self.severities);
+                result.sort();
+            }
+            results.push((file_name, result));
+        }
+
+        self.flag_outliers(&samples, &mut results);
+        self.reporter.batch_end(&results, &self.stats);
+        Ok(results)
+    }
+
+    /// Run `work` on every file, one after the other, returning the outcomes
+    /// in file order
+    #[cfg(all(not(feature = "no-fs"), not(feature = "parallel")))]
+    fn each_file<T: Send>(&self, files: &[PathBuf], work: impl Fn(&Path) -> T + Sync) -> Vec<T> {
+        files.iter().map(|file| work(file)).collect()
+    }
+
+    /// Run `work` on every file on a thread pool of `set_jobs` threads,
+    /// returning the outcomes in file order
+    #[cfg(all(not(feature = "no-fs"), feature = "parallel"))]
+    fn each_file<T: Send>(&self, files: &[PathBuf], work: impl Fn(&Path) -> T + Sync) -> Vec<T> {
+        use rayon::prelude::*;
+        let run = || files.par_iter().map(|file| work(file)).collect();
+        match self.jobs.map(|jobs| rayon::ThreadPoolBuilder::new().num_threads(jobs).build()) {
+            Some(Ok(pool)) => pool.install(run),
+            _ => run(),
+        }
+    }
+
+    /// Validate every recipe a source lists, naming results by the source's
+    /// `display_name`. Entries that cannot be listed or read are results with
+    /// RCIP-E-READ-FAILED, like files that do not parse, so a failing object
+    /// never ends the run
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_source(&mut self, source: &dyn sources::RecipeSource) -> Vec<(String, ValidationResult)> {
+        self.validate_source_with_observer(source, &mut NoopObserver)
+    }
+
+    /// Validate every recipe a source lists, reporting each entry as well as
+    /// its phases and issues to an observer
+    #[cfg(not(feature = "no-fs"))]
+    pub fn validate_source_with_observer(&mut self, source: &dyn sources::RecipeSource, observer: &mut dyn ValidationObserver) -> Vec<(String, ValidationResult)> {
+        let listed = source.list();
+        self.reporter.batch_start(listed.len(), Some(&source.location()));
+
+        let mut results = Vec::new();
+        let mut samples = Vec::new();
+        for listed in listed {
+            let name = source.display_name(listed.name());
+            let path = Path::new(&name);
+            let mut guard = Guard::new(&mut *observer);
+            guard.notify("file start", |observer| observer.on_file_start(path));
+            let bytes = match listed {
+                sources::Listed::Entry(entry) => source.read(&entry),
+                sources::Listed::Failed { error, .. } => Err(error),
+            };
+            let parsed = bytes.and_then(|bytes| {
+                Ok(if self.skip_binary_payloads {
+                    large_files::parse_without_payloads(&bytes)?
+                } else {
+                    serde_json::from_slice::<Value>(&bytes)?
+                })
+            });
+            let mut result = match parsed {
+                Ok(recipe) => {
+                    self.reporter.file_start(&name);
+                    let result = self.validate_guarded(&recipe, &mut guard, None);
+                    self.record_stats(&result);
+                    let recipe_name = recipe.pointer("/meta/name").and_then(|n| n.as_str()).unwrap_or("Unknown Recipe");
+                    self.reporter.file_result(recipe_name, &result);
+                    if self.outlier_thresholds.is_some() {
+                        samples.push(corpus::CorpusSample::new(&name, &recipe));
+                    }
+                    result
+                }
+                Err(e) => {
+                    let mut result = ValidationResult::with_locale(&self.locale);
+                    result.meta = Some(self.meta());
+                    result.error(codes::READ_FAILED, &[("error", &e)]);
+                    result
+                }
+            };
+            guard.notify("file end", |observer| observer.on_file_end(path, &result));
+            if guard.report(&mut result) {
+                result.override_severities(&self.severities);
+                result.sort();
+            }
+            results.push((name, result));
+        }
+
+        self.flag_outliers(&samples, &mut results);
+        self.reporter.batch_end(&results, &self.stats);
+        results
+    }
+
+    /// Build the corpus report from the samples of a run and flag its
+    /// outliers, when outlier flagging is on
+    #[cfg(not(feature = "no-fs"))]
+    fn flag_outliers(&mut self, samples: &[corpus::CorpusSample], results: &mut [(String, ValidationResult)]) {
+        let Some(thresholds) = &self.outlier_thresholds else { return };
+        let report = corpus::CorpusReport::build(samples, thresholds);
+        report.flag(results);
+        if !report.outliers.is_empty() {
+            for (_, result) in results.iter_mut() {
+                result.override_severities(&self.severities);
+            }
+        }
+        self.corpus_report = Some(report);
+    }
+
+    /// Apply custom validation rules
+    fn validate_custom_rules(&self, recipe: &Value, result: &mut ValidationResult, guard: &mut Guard, mut cache: Option<&mut patch::ItemCache>) {
+        // Reject undeclared fields
+        let schema = self.versioned(recipe).map(|(_, versioned)| &versioned.schema).or(self.schema.as_ref());
+        if let (true, Some(schema)) = (self.unknown_fields, schema) {
+            self.phase(Phase::UnknownFields, guard, result, |result| unknown_fields::check(schema, recipe, result));
+        }
+
+        // Validate recipe ID
+        self.phase(Phase::RecipeId, guard, result, |result| {
+            if let Some(id) = recipe.get("id").and_then(|v| v.as_str()) {
+                if !RECIPE_ID_REGEX.is_match(id) {
+                    result.within("/id", |result| result.error(codes::BAD_RECIPE_ID, &[("id", &id)]));
+                }
+            }
+        });
+
+        // Validate numeric fields
+        self.phase(Phase::Numeric, guard, result, |result| numeric::check(recipe, result));
+
+        // Validate ingredients
+        self.phase(Phase::Ingredients, guard, result, |result| {
+            if let Some(ingredients) = recipe.get("ingredients").and_then(|v| v.as_array()) {
+                for (i, ingredient) in ingredients.iter().enumerate() {
+                    patch::ItemCache::check(cache.as_deref_mut(), Phase::Ingredients, i, ingredient, result, |result| {
+                        result.within(&format!("/ingredients/{}", i), |result| self.validate_ingredient(ingredient, i, result))
+                    });
+                }
+            }
+        });
+
+        // Validate may-contain declarations and diet labels
+        self.phase(Phase::Allergens, guard, result, |result| {
+            allergens::check(recipe, &self.allergen_provenance, result)
+        });
+
+        // Check the evidence behind diet labels
+        if self.verify_claims {
+            self.phase(Phase::Claims, guard, result, |result| {
+                claims::check(recipe, self.resolvers.as_ref(), result)
+            });
+        }
+
+        // Apply market labeling rules
+        if !self.markets.is_empty() {
+            self.phase(Phase::Markets, guard, result, |result| {
+                for market in &self.markets {
+                    markets::check(recipe, market, &self.allergen_provenance, result);
+                }
+            });
+        }
+
+        // Validate steps
+        self.phase(Phase::Steps, guard, result, |result| {
+            if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
+                for (i, step) in steps.iter().enumerate() {
+                    patch::ItemCache::check(cache.as_deref_mut(), Phase::Steps, i, step, result, |result| {
+                        result.within(&format!("/steps/{}", i), |result| self.validate_step(step, i, result))
+                    });
+                }
+            }
+        });
+
+        // Check ingredient listing order
+        if self.ingredient_order || self.usage_order {
+            self.phase(Phase::IngredientOrder, guard, result, |result| {
+                if self.ingredient_order {
+                    ingredient_order::check(recipe, result);
+                }
+                if self.usage_order {
+                    ingredient_order::check_usage(recipe, result);
+                }
+            });
+        }
+
+        // Check id numbering
+        if self.id_sequence {
+            self.phase(Phase::IdSequence, guard, result, |result| id_sequence::check(recipe, result));
+        }
+
+        // Check baker's percentages
+        if self.baking_checks {
+            self.phase(Phase::Baking, guard, result, |result| analysis::check(recipe, result));
+        }
+
+        // Check step text quality
+        self.phase(Phase::TextQuality, guard, result, |result| text_quality::check(recipe, &self.text_policy, result));
+
+        // Validate meta.language against the language of the text
+        self.phase(Phase::Language, guard, result, |result| {
+            analysis::check_language(recipe, self.require_language, result)
+        });
+
+        // Validate notes, tips and troubleshooting
+        self.phase(Phase::Guidance, guard, result, |result| guidance::check(recipe, result));
+
+        // Check mise-en-place ordering
+        if self.mise_en_place {
+            self.phase(Phase::MiseEnPlace, guard, result, |result| {
+                let missing = mise_en_place::unprepped(recipe);
+                if !missing.is_empty() {
+                    let listed: Vec<String> = missing.iter()
+                        .map(|m| format!("{} ({} in {})", m.ingredient_id, m.action, m.step_id))
+                        .collect();
+                    result.warning(codes::MISE_EN_PLACE, &[("ingredients", &listed.join(", "))]);
+                }
+            });
+        }
+
+        // Validate cooling steps and how long intermediates wait
+        self.phase(Phase::Chilling, guard, result, |result| {
+            chilling::check(recipe, &self.chill_policy, result);
+            intermediates::check_holding(recipe, result);
+        });
+        if self.cold_chain {
+            self.phase(Phase::ColdChain, guard, result, |result| {
+                cold_chain::check(recipe, &self.cold_chain_policy, result)
+            });
+        }
+
+        // Validate cross-references
+        self.phase(Phase::References, guard, result, |result| self.validate_references(recipe, result));
+
+        // Check steps against their device profiles' capabilities
+        self.phase(Phase::DeviceLimits, guard, result, |result| device_limits::check(recipe, result));
+
+        // Dry-run the recipe
+        if self.simulation {
+            self.phase(Phase::Simulation, guard, result, |result| simulate::check(recipe, result));
+        }
+
+        // Run plug-in rule packs
+        #[cfg(feature = "wasm-rules")]
+        if !self.rule_packs.is_empty() {
+            self.phase(Phase::RulePacks, guard, result, |result| {
+                for pack in &self.rule_packs {
+                    pack.check(recipe, result);
+                }
+            });
+        }
+
+        // Check version compatibility and deprecated fields
+        self.phase(Phase::Version, guard, result, |result| {
+            if let Some(version) = recipe.get("rcip_version").and_then(|v| v.as_str()) {
+                if version != self.schema_version && !self.versions.contains_key(version) {
+                    result.within("/rcip_version", |result| {
+                        result.warning(codes::VERSION_MISMATCH, &[
+                            ("recipe_version", &version),
+                            ("validator_version", &self.schema_version),
+                        ])
+                    });
+                }
+            }
+            deprecations::check(recipe, result);
+        });
+    }
+
+    /// Validate an ingredient
+    fn validate_ingredient(&self, ingredient: &Value, index: usize, result: &mut ValidationResult) {
+        let field = |name: &str| format!("/ingredients/{}/{}", index, name);
+
+        // Check ID format
+        if let Some(id) = ingredient.get("id").and_then(|v| v.as_str()) {
+            if !INGREDIENT_ID_REGEX.is_match(id) {
+                result.within(&field("id"), |result| result.error(codes::BAD_INGREDIENT_ID, &[("index", &index), ("id", &id)]));
+            }
+        }
+
+        // Check taxonomy coverage
+        if self.taxonomy_warnings {
+            if let Some(name) = ingredient.get("name").and_then(|v| v.as_str()) {
+                if taxonomy::classify(name).is_none() {
+                    result.within(&field("name"), |result| {
+                        result.warning(codes::UNCLASSIFIED_INGREDIENT, &[("index", &index), ("name", &name)])
+                    });
+                }
+            }
+        }
+
+        // Check allergens (must be present, can be empty)
+        match ingredient.get("allergens") {
+            None => {
+                result.error(codes::MISSING_ALLERGENS, &[("index", &index)]);
+            }
+            Some(allergens) if !allergens.is_array() => {
+                result.within(&field("allergens"), |result| result.error(codes::ALLERGENS_NOT_ARRAY, &[("index", &index)]));
+            }
+            Some(allergens) => {
+                // Validate allergen values
+                let pointer = field("allergens");
+                terms::ALLERGENS.check(allergens, &pointer, result, |result, allergen| {
+                    result.error(codes::BAD_ALLERGEN, &[("index", &index), ("allergen", &allergen)]);
+                });
+                // Allergens the name suggests that nobody declared
+                result.within(&pointer, |result| allergens::check_suggestions(ingredient, index, result));
+            }
+        }
+
+        if ingredient.get("optional").map(|v| !v.is_boolean()).unwrap_or(false) {
+            result.within(&field("optional"), |result| result.error(codes::OPTIONAL_NOT_BOOLEAN, &[("index", &index)]));
+        }
+
+        // Check the branded product declaration and storage requirements
+        product::check(ingredient, index, result);
+        cold_chain::check_storage(ingredient, index, &self.cold_chain_policy, result);
+
+        // Cross-check allergens against resolved product data
+        if let Some(resolvers) = &self.resolvers {
+            let declared = ingredient.get("allergens").and_then(|v| v.as_array());
+            if let (Ok(Some(product)), Some(declared)) = (resolvers.resolve_ingredient(ingredient), declared) {
+                let declared: Vec<&str> = declared.iter().filter_map(|a| a.as_str()).collect();
+                if !product.allergens.is_empty() {
+                    let (missing, extra) = resolve::allergen_discrepancies(&declared, &product);
+                    result.within(&field("allergens"), |result| {
+                        if !missing.is_empty() {
+                            result.warning(codes::UNDECLARED_ALLERGENS, &[
+                                ("index", &index),
+                                ("source", &product.source),
+                                ("allergens", &missing.join(", ")),
+                            ]);
+                        }
+                        if !extra.is_empty() {
+                            result.warning(codes::UNCONFIRMED_ALLERGENS, &[
+                                ("index", &index),
+                                ("source", &product.source),
+                                ("allergens", &extra.join(", ")),
+                            ]);
+                        }
+                    });
+                }
+            }
+        }
+
+        // Check that the human amount can be read
+        if let Some(text) = ingredient.get("human_amount").and_then(|v| v.as_str()).filter(|_| self.readable_amounts) {
+            if amounts::parse_human_amount(text).is_none() {
+                result.within(&field("human_amount"), |result| {
+                    result.warning(codes::UNPARSED_HUMAN_AMOUNT, &[("index", &index), ("text", &text)])
+                });
+            }
+        }
+
+        // Compare the human amount with the machine amount
+        amounts::check_consistency(ingredient, index, self.amount_tolerance, result);
+
+        // Check machine amount
+        if let Some(ma) = ingredient.get("machine_amount") {
+            // The value itself is covered by the numeric sanity pass
+            if ma.get("unit").is_none() {
+                result.within(&field("machine_amount"), |result| result.error(codes::MISSING_UNIT, &[("index", &index)]));
+            }
+
+            // Check precision against the dispenser policy
+            if let (Some(Value::Number(value)), Some(unit)) = (ma.get("value"), ma.get("unit").and_then(|v| v.as_str())) {
+                result.within(&field("machine_amount/value"), |result| match precision::check(value, unit, &self.precision_policy) {
+                    Some(PrecisionIssue::NotWholeCount) => {
+                        result.error(codes::NOT_WHOLE_COUNT, &[("index", &index), ("value", value), ("unit", &unit)]);
+                    }
+                    Some(PrecisionIssue::TooPrecise { decimals, allowed }) => {
+                        result.warning(codes::TOO_PRECISE, &[
+                            ("index", &index),
+                            ("value", value),
+                            ("decimals", &decimals),
+                            ("allowed", &allowed),
+                            ("unit", &unit),
+                        ]);
+                    }
+                    None => {}
+                });
+            }
+        }
+    }
+
+    /// Validate a step
+    fn validate_step(&self, step: &Value, index: usize, result: &mut ValidationResult) {
+        let field = |name: &str| format!("/steps/{}/{}", index, name);
+
+        // Check ID format
+        if let Some(id) = step.get("step_id").and_then(|v| v.as_str()) {
+            if !STEP_ID_REGEX.is_match(id) {
+                result.within(&field("step_id"), |result| result.error(codes::BAD_STEP_ID, &[("index", &index), ("id", &id)]));
+            }
+        }
+
+        // Check action
+        if let Some(action) = step.get("action").and_then(|v| v.as_str()) {
+            if !COOKING_ACTIONS.contains(&action) {
+                result.within(&field("action"), |result| result.error(codes::BAD_ACTION, &[("index", &index), ("action", &action)]));
+            }
+        }
+
+        // Check hazards
+        if let Some(hazards) = step.get("hazards") {
+            let pointer = format!("/steps/{}/hazards", index);
+            terms::HAZARDS.check(hazards, &pointer, result, |result, hazard| {
+                result.warning(codes::NONSTANDARD_HAZARD, &[("index", &index), ("hazard", &hazard)]);
+            });
+        }
+    }
+
+    /// Validate cross-references
+    fn validate_references(&self, recipe: &Value, result: &mut ValidationResult) {
+        // Collect all IDs
+        let mut ingredient_ids = HashSet::new();
+        let mut step_ids = HashSet::new();
+
+        if let Some(ingredients) = recipe.get("ingredients").and_then(|v| v.as_array()) {
+            for ing in ingredients {
+                if let Some(id) = ing.get("id").and_then(|v| v.as_str()) {
+                    ingredient_ids.insert(id.to_string());
+                }
+            }
+        }
+
+        if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
+            for step in steps {
+                if let Some(id) = step.get("step_id").and_then(|v| v.as_str()) {
+                    step_ids.insert(id.to_string());
+                }
+            }
+        }
+
+        // Check step targets
+        if let Some(steps) = recipe.get("steps").and_then(|v| v.as_array()) {
+            for (i, step) in steps.iter().enumerate() {
+                if let Some(targets) = step.get("target").and_then(|v| v.as_array()) {
+                    for (j, target) in targets.iter().enumerate() {
+                        if let Some(target_str) = target.as_str() {
+                            let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+                            let code = if target_str.starts_with("ing-") && !ingredient_ids.contains(target_str) {
+                                codes::BAD_INGREDIENT_REF
+                            } else if target_str.contains(":result") && !step_ids.contains(target_str.split(':').next().unwrap()) {
+                                codes::BAD_STEP_REF
+                            } else {
+                                continue;
+                            };
+                            result.within(&format!("/steps/{}/target/{}", i, j), |result| {
+                                result.error(code, &[("step", &step_id), ("target", &target_str)])
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Every ingredient should be used by a step, or a machine loads it and
+        // never dispenses it
+        let steps = recipe.get("steps").and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+        let used: HashSet<&str> = steps.iter().flat_map(step_targets).collect();
+        if !steps.is_empty() {
+            let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten();
+            for (i, ingredient) in ingredients.enumerate() {
+                let id = match ingredient.get("id").and_then(|v| v.as_str()) {
+                    Some(id) if !used.contains(id) => id,
+                    _ => continue,
+                };
+                let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("?");
+                result.within(&format!("/ingredients/{}", i), |result| {
+                    result.warning(codes::UNUSED_INGREDIENT, &[("id", &id), ("name", &name)])
+                });
+            }
+        }
+
+        // Steps taking neither an ingredient nor a step's result
+        if self.dangling_steps {
+            for (i, step) in steps.iter().enumerate() {
+                let takes_input = step_targets(step)
+                    .any(|r| ingredient_ids.contains(r) || r.contains(":result") || r.starts_with('@'));
+                if !takes_input {
+                    let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+                    result.within(&format!("/steps/{}", i), |result| {
+                        result.warning(codes::DANGLING_STEP, &[("step", &step_id)])
+                    });
+                }
+            }
+        }
+
+        // Steps waiting on each other's results can never start
+        let graph = graph::RecipeGraph::build(recipe);
+        for cycle in graph.cycles() {
+            let closed: Vec<&str> = cycle.iter().chain(cycle.first()).map(String::as_str).collect();
+            let index = graph.position(&cycle[0]).unwrap_or(0);
+            result.within(&format!("/steps/{}", index), |result| {
+                result.error(codes::STEP_CYCLE, &[("cycle", &closed.join(" -> "))])
+            });
+        }
+
+        // Check result names and `@name` targets
+        intermediates::check_references(recipe, result);
+
+        // Check portion counts and `[n]` portion references
+        portions::check(recipe, result);
+    }
+
+    /// Check for warnings
+    fn check_warnings(&self, recipe: &Value, result: &mut ValidationResult) {
+        let meta = recipe.get("meta");
+
+        // Check for missing recommended fields
+        for field in ["description", "servings", "difficulty"] {
+            if meta.and_then(|m| m.get(field)).is_none() {
+                result.within(&format!("/meta/{}", field), |result| {
+                    result.warning(codes::MISSING_FIELD, &[("field", &format!("meta.{}", field))])
+                });
+            }
+        }
+
+        // Check for missing nutritional data
+        let has_nutritional = recipe.get("ingredients")
+            .and_then(|v| v.as_array())
+            .map(|ingredients| {
+                ingredients.iter().any(|ing| ing.get("nutritional").is_some())
+            })
+            .unwrap_or(false);
+
+        if !has_nutritional {
+            result.warning(codes::NO_NUTRITION, &[]);
+        }
+
+        // Optional is for garnishes; a mostly optional recipe is misusing it
+        let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+        let optional = ingredients.iter().filter(|ing| is_optional(ing)).count();
+        if optional * 2 > ingredients.len() {
+            result.warning(codes::MOSTLY_OPTIONAL, &[("optional", &optional), ("total", &ingredients.len())]);
+        }
+
+        // Check for missing external IDs
+        let has_external_ids = recipe.get("ingredients")
+            .and_then(|v| v.as_array())
+            .map(|ingredients| {
+                ingredients.iter().any(|ing| {
+                    ing.get("external_ids")
+                        .and_then(|e| e.as_object())
+                        .map(|o| !o.is_empty())
+                        .unwrap_or(false)
+                })
+            })
+            .unwrap_or(false);
+
+        if !has_external_ids {
+            result.warning(codes::NO_EXTERNAL_IDS, &[]);
+        }
+
+        // Check for very long cooking times
+        if let Some(total_time) = meta.and_then(|m| m.get("total_time_minutes")).and_then(numeric::finite) {
+            if total_time > 1440.0 {
+                let hours = format!("{:.1}", total_time / 60.0);
+                result.within("/meta/total_time_minutes", |result| {
+                    result.warning(codes::LONG_COOK_TIME, &[("minutes", &total_time), ("hours", &hours)])
+                });
+            }
+        }
+
+        // Check the declared total time against the steps and its parts
+        analysis::check_total_time(recipe, self.total_time_tolerance, result);
+
+        // Check for missing images
+        if recipe.get("images").and_then(|v| v.as_array()).map(|a| a.is_empty()).unwrap_or(true) {
+            result.warning(codes::NO_IMAGES, &[]);
+        }
+    }
+
+    /// Reset statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = ValidationStats::default();
+    }
+
+    /// Get statistics
+    pub fn get_stats(&self) -> &ValidationStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_minimal_recipe() {
+        let mut validator = RCIPValidator::new("0.1");
+
+        let recipe = json!({
+            "rcip_version": "0.1",
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {
+                "name": "Test Recipe",
+                "author": "Test Author",
+                "created_date": "2025-01-15T10:00:00Z"
+            },
+            "ingredients": [
+                {
+                    "id": "ing-0001",
+                    "name": "test ingredient",
+                    "human_amount": "1 piece",
+                    "machine_amount": {
+                        "value": 1,
+                        "unit": "pcs"
+                    },
+                    "allergens": []
+                }
+            ],
+            "steps": [
+                {
+                    "step_id": "s-01",
+                    "human_text": "Test step",
+                    "action": "mix"
+                }
+            ]
+        });
+
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid, "Minimal recipe should be valid");
+    }
+
+    #[test]
+    fn test_invalid_recipe_id() {
+        let mut validator = RCIPValidator::new("0.1");
+
+        let recipe = json!({
+            "rcip_version": "0.1",
+            "id": "invalid-id",
+            "meta": {
+                "name": "Test Recipe",
+                "author": "Test Author",
+                "created_date": "2025-01-15T10:00:00Z"
+            },
+            "ingredients": [],
+            "steps": []
+        });
+
+        let result = validator.validate_recipe(&recipe);
+        assert!(!result.valid, "Recipe with invalid ID should not be valid");
+        assert!(result.errors.iter().any(|e| e.contains("Invalid recipe ID format")));
+    }
+
+    #[test]
+    fn test_missing_allergens() {
+        let mut validator = RCIPValidator::new("0.1");
+
+        let recipe = json!({
+            "rcip_version": "0.1",
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {
+                "name": "Test Recipe",
+                "author": "Test Author",
+                "created_date": "2025-01-15T10:00:00Z"
+            },
+            "ingredients": [
+                {
+                    "id": "ing-0001",
+                    "name": "test ingredient",
+                    "human_amount": "1 piece",
+                    "machine_amount": {
+                        "value": 1,
+                        "unit": "pcs"
+                    }
+                    // Missing allergens field
+                }
+            ],
+            "steps": []
+        });
+
+        let result = validator.validate_recipe(&recipe);
+        assert!(!result.valid, "Recipe with missing allergens should not be valid");
+        assert!(result.errors.iter().any(|e| e.contains("Missing required allergens field")));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let mut validator = RCIPValidator::new("0.1");
+
+        let recipe = json!({
+            "rcip_version": "0.1",
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {
                 "name": "Test Recipe",
                 "author": "Test Author",
                 "created_date": "2025-01-15T10:00:00Z"
+                // Missing recommended fields
+            },
+            "ingredients": [
+                {
+                    "id": "ing-0001",
+                    "name": "test ingredient",
+                    "human_amount": "1 piece",
+                    "machine_amount": {
+                        "value": 1,
+                        "unit": "pcs"
+                    },
+                    "allergens": []
+                }
+            ],
+            "steps": [
+                {
+                    "step_id": "s-01",
+                    "human_text": "Test step",
+                    "action": "mix"
+                }
+            ]
+        });
+
+        let result = validator.validate_recipe(&recipe);
+        assert!(!result.warnings.is_empty(), "Should have warnings for missing recommended fields");
+        assert!(result.warnings.iter().any(|w| w.contains("meta.description")));
+        assert!(result.warnings.iter().any(|w| w.contains("meta.servings")));
+    }
+    #[test]
+    fn test_quality_reports() {
+        let scored = |score: f64, errors: usize| {
+            let mut result = ValidationResult::new();
+            result.valid = errors == 0;
+            result.errors = vec!["x".to_string(); errors];
+            result.info.quality = Some(analysis::QualityScore { score, components: vec![] });
+            result
+        };
+        let results = vec![
+            ("b.rcip".to_string(), scored(80.0, 0)),
+            ("a, first.rcip".to_string(), scored(42.5, 2)),
+            ("c.rcip".to_string(), ValidationResult::new()),
+        ];
+
+        assert_eq!(lowest_quality(&results, 1), vec![("a, first.rcip", 42.5)]);
+        assert_eq!(lowest_quality(&results, 5).len(), 2);
+        assert_eq!(
+            render_csv(&results),
+            "file,valid,errors,warnings,notices,quality\n\
+             b.rcip,true,0,0,0,80.0\n\
+             \"a, first.rcip\",false,2,0,0,42.5\n\
+             c.rcip,true,0,0,0,\n"
+        );
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_directory_results_in_file_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let example = include_str!("../../../examples/simple-example.rcip");
+        for i in 0..12 {
+            let content = if i % 3 == 0 { example.replacen("ing-", "ingredient-", 1) } else { example.to_string() };
+            fs::write(dir.path().join(format!("recipe-{:02}.rcip", 11 - i)), content).unwrap();
+        }
+
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        validator.set_jobs(Some(4));
+        let mut profile = observer::RuleProfile::new();
+        let options = discover::DirectoryOptions::default();
+        let results = validator.validate_directory_with_options(dir.path(), &options, &mut profile).unwrap();
+
+        let names: Vec<&str> = results.iter().map(|(name, _)| name.as_str()).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        for (name, result) in &results {
+            let content = fs::read_to_string(dir.path().join(name)).unwrap();
+            let expected = validator.validate(&serde_json::from_str(&content).unwrap());
+            assert_eq!(result.issues, expected.issues, "{}", name);
+        }
+
+        let stats = validator.get_stats();
+        assert_eq!((stats.validated, stats.passed, stats.failed), (12, 8, 4));
+        assert_eq!(profile.phases[&Phase::Schema].runs, 12);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_recipes_are_checked_against_their_own_version() {
+        let v1: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let (mut v2, mut v3) = (v1.clone(), v1.clone());
+        v2["rcip_version"] = json!("0.2");
+        v3["rcip_version"] = json!("0.3");
+        let mut schema: Value = serde_json::from_str(warm::bundled_schema("0.1").unwrap()).unwrap();
+        schema["properties"]["rcip_version"]["const"] = json!("0.2");
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, recipe) in [("a.rcip", &v1), ("b.rcip", &v2), ("c.rcip", &v3)] {
+            fs::write(dir.path().join(name), recipe.to_string()).unwrap();
+        }
+        let mismatched = |result: &ValidationResult| result.issues.iter().any(|i| i.code == codes::VERSION_MISMATCH);
+
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let single = validator.validate_directory(dir.path()).unwrap();
+        assert!(!single[1].1.valid && mismatched(&single[1].1));
+
+        validator.add_schema_version("0.2", &schema.to_string()).unwrap();
+        let results = validator.validate_directory(dir.path()).unwrap();
+        let used: Vec<Option<&str>> = results.iter().map(|(_, r)| r.info.schema_version.as_deref()).collect();
+        assert_eq!(used, [Some("0.1"), Some("0.2"), Some("0.1")]);
+        assert!(results[0].1.valid && !mismatched(&results[0].1));
+        assert!(results[1].1.valid && !mismatched(&results[1].1), "{:?}", results[1].1.errors);
+        // No 0.3 schema: the default one, with a warning
+        assert!(mismatched(&results[2].1));
+        assert!(validator.add_schema_version("0.4", "{\"type\": 12}").is_err());
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_in_memory_validation_matches_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let example = include_str!("../../../examples/margherita-pizza.rcip");
+        for content in [example.to_string(), example.replacen("\"ing-", "\"ingredient-", 2)] {
+            let path = dir.path().join("recipe.rcip");
+            fs::write(&path, &content).unwrap();
+
+            let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+            let from_file = validator.validate_file(&path).unwrap();
+            let from_str = validator.validate_str(&content).unwrap();
+            let from_reader = validator.validate_reader(content.as_bytes()).unwrap();
+            for result in [&from_str, &from_reader] {
+                assert_eq!(result.valid, from_file.valid);
+                assert_eq!(result.errors, from_file.errors);
+                assert_eq!(result.warnings, from_file.warnings);
+                assert_eq!(result.info, from_file.info);
+            }
+            assert_eq!(validator.get_stats().validated, 3);
+        }
+
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        assert!(matches!(validator.validate_str("{"), Err(RCIPError::JsonError(_))));
+    }
+
+    #[test]
+    fn test_json_report() {
+        let validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        recipe["ingredients"][0]["optional"] = json!("yes");
+        let results = vec![("simple.rcip".to_string(), validator.validate(&recipe))];
+        let stats = ValidationStats { validated: 1, failed: 1, ..Default::default() };
+
+        let report: Value = serde_json::from_str(&render_json(&results, &stats)).unwrap();
+        let file = &report["results"][0];
+        assert_eq!(file["file"], "simple.rcip");
+        assert_eq!(file["valid"], false);
+        assert_eq!(file["errors"].as_array().unwrap().len(), results[0].1.errors.len());
+        assert_eq!(file["info"]["ingredient_count"], results[0].1.info.ingredient_count);
+        let issue = file["issues"].as_array().unwrap().iter().find(|i| i["code"] == codes::OPTIONAL_NOT_BOOLEAN).unwrap();
+        assert_eq!(issue["severity"], "error");
+        assert_eq!(issue["pointer"], "/ingredients/0/optional");
+        assert_eq!(issue["params"]["index"], "0");
+        assert!(file.get("scope").is_none());
+        assert_eq!(report["stats"]["failed"], 1);
+    }
+
+    #[test]
+    fn test_inherited_allergens_are_reported() {
+        let validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let egg: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let mut dish = egg.clone();
+        dish["id"] = json!("rcip-00000000-0000-4000-8000-000000000001");
+        dish["ingredients"] = json!([dish["ingredients"][1].clone()]);
+        let merged = validator.validate(&dish).merge(validator.validate(&egg), "/components/0");
+
+        let eggs = &merged.info.allergens[0];
+        assert_eq!(eggs.sources, vec![allergens::AllergenSource::Inherited { recipe: egg["id"].as_str().unwrap().to_string() }]);
+        assert!(eggs.considered(&allergens::ProvenancePolicy::default()));
+        let report = render_report(&merged, "Dish");
+        assert!(report.contains("  - Allergens: eggs [from rcip-123e4567-e89b-12d3-a456-426614174000]\n"), "{}", report);
+
+        let results = vec![("dish.rcip".to_string(), merged)];
+        let json: Value = serde_json::from_str(&render_json(&results, &ValidationStats::default())).unwrap();
+        assert_eq!(
+            json["results"][0]["info"]["allergens"][0]["sources"],
+            json!([{"provenance": "inherited", "recipe": "rcip-123e4567-e89b-12d3-a456-426614174000"}])
+        );
+    }
+
+    #[test]
+    fn test_strict_warnings() {
+        let recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let normal = validator.validate(&recipe);
+        assert!(normal.valid);
+        assert!(normal.issues.iter().any(|i| i.code == codes::NO_IMAGES && i.severity == Severity::Warning));
+
+        validator.set_strict_warnings(true);
+        let strict = validator.validate(&recipe);
+        assert!(!strict.valid);
+        assert!(strict.warnings.is_empty());
+        assert_eq!(strict.errors.len(), normal.errors.len() + normal.warnings.len());
+
+        validator.set_denied_warnings(&["NO-EXTERNAL-IDS"]);
+        assert!(validator.validate(&recipe).valid);
+        validator.set_denied_warnings(&["No images"]);
+        let selective = validator.validate(&recipe);
+        assert!(!selective.valid);
+        assert!(selective.issues.iter().any(|i| i.code == codes::NO_IMAGES && i.severity == Severity::Error));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_schema_divergences() {
+        let schema: Value = serde_json::from_str(&fs::read_to_string("../../schemas/rcip-v0.1.json").unwrap()).unwrap();
+        assert!(schema_divergences(&schema).is_empty());
+
+        let mut loose = schema.clone();
+        loose["$defs"]["ingredient"]["required"] = json!(["id", "name"]);
+        loose["$defs"]["step"]["properties"]["action"]["enum"] = json!(["mix", "sous-vide"]);
+        let found = schema_divergences(&loose);
+        assert_eq!(found.len(), 3);
+        assert!(found[0].starts_with("ingredients[].allergens is optional"));
+        assert!(found[2].ends_with("rejects: sous-vide"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("loose.json");
+        fs::write(&path, loose.to_string()).unwrap();
+        let mut validator = RCIPValidator::new("0.1");
+        assert!(validator.init(Some(&path)).is_ok());
+        assert_eq!(validator.schema_divergences().len(), 3);
+        validator.set_strict_schema(true);
+        assert!(validator.init(Some(&path)).is_err());
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_results_carry_provenance() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let result = validator.validate_recipe(&json!({}));
+        let meta = result.meta.unwrap();
+        assert_eq!(meta.validator_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(meta.schema_sha256.map(|s| s.len()), Some(64));
+
+        validator.set_mise_en_place(true);
+        let changed = validator.validate_recipe(&json!({})).meta.unwrap();
+        assert_ne!(meta.rule_config_hash, changed.rule_config_hash);
+    }
+
+    #[test]
+    fn test_issues_point_at_offending_values() {
+        let validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        recipe["ingredients"][1]["allergens"] = json!(["milk", "Milk"]);
+        recipe["ingredients"][2]["optional"] = json!("yes");
+        recipe["steps"][0]["target"] = json!([recipe["steps"][0]["target"][0], "ing-9999"]);
+        recipe["meta"].as_object_mut().unwrap().remove("difficulty");
+
+        let result = validator.validate(&recipe);
+        let located = |code: &str| -> Vec<String> {
+            result.validation_issues().into_iter().filter(|i| i.code == code).map(|i| i.path).collect()
+        };
+        assert_eq!(located(codes::DUPLICATE_TERM), ["/ingredients/1/allergens/1"]);
+        assert_eq!(located(codes::OPTIONAL_NOT_BOOLEAN), ["/ingredients/2/optional"]);
+        assert_eq!(located(codes::BAD_INGREDIENT_REF), ["/steps/0/target/1"]);
+        assert_eq!(located(codes::MISSING_FIELD), ["/meta/difficulty"]);
+        assert!(located(codes::SCHEMA).contains(&"/ingredients/2/optional".to_string()));
+
+        let issues = result.validation_issues();
+        let error = issues.iter().find(|i| i.code == codes::BAD_INGREDIENT_REF).unwrap();
+        assert_eq!(error.severity, Severity::Error);
+        assert!(result.errors.iter().any(|e| e == &error.message));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_strict_profile_turns_on_opt_in_checks() {
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001"}, {"id": "ing-0002"}, {"id": "ing-0003"}, {"id": "ing-0004", "name": "Quux"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "target": ["ing-0004"]},
+                {"step_id": "s-02", "target": ["ing-0003"]},
+                {"step_id": "s-03", "target": ["ing-0002"]},
+                {"step_id": "s-04", "target": ["ing-0001"]}
+            ]
+        });
+        let codes = |validator: &RCIPValidator| validator.validate(&recipe).issues.iter().map(|i| i.code).collect::<Vec<_>>();
+
+        let mut default = RCIPValidator::new("0.1");
+        default.init(None).unwrap();
+        let mut strict = RCIPValidator::new("0.1");
+        strict.set_profile("strict").unwrap();
+        strict.init(None).unwrap();
+        for setting in ["usage_order", "taxonomy_warnings"] {
+            assert_eq!(strict.rule_config()[setting], true, "{}", setting);
+        }
+        for code in [codes::USAGE_ORDER, codes::UNCLASSIFIED_INGREDIENT] {
+            assert!(codes(&strict).contains(&code), "{}", code);
+            assert!(!codes(&default).contains(&code), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_merge_reroots_paths() {
+        let issue = |code, severity, path: &str| Issue {
+            code,
+            severity,
+            params: vec![("index", "1".to_string()), ("field", "meta.description".to_string())],
+            path: path.to_string(),
+            pointer: String::new(),
+        };
+
+        let mut sauce = ValidationResult::from_issues(vec![issue(codes::MISSING_ALLERGENS, Severity::Error, "/ingredients/1")]);
+        let declared = |allergen: &str, ingredient: &str| allergens::AllergenEntry {
+            allergen: allergen.to_string(),
+            sources: vec![allergens::AllergenSource::Declared { ingredient: ingredient.to_string(), resolver: None }],
+        };
+        sauce.info.id = Some("rcip-sauce".to_string());
+        sauce.info.allergens = vec![declared("milk", "ing-0002")];
+        sauce.info.ingredient_count = 2;
+        sauce.info.step_count = 3;
+        sauce.info.has_sensors = true;
+        sauce.info.total_time = Some(20.0);
+        assert!(!sauce.valid);
+        assert_eq!(sauce.errors, vec!["/ingredients/1: Ingredient 1: Missing required allergens field"]);
+
+        let mut garnish = ValidationResult::from_issues(vec![issue(codes::MISSING_FIELD, Severity::Warning, "")]);
+        garnish.info.id = Some("rcip-garnish".to_string());
+        let almond = allergens::AllergenSource::Inferred { ingredient: "ing-0001".to_string(), basis: "almond".to_string() };
+        garnish.info.allergens = vec![
+            declared("milk", "ing-0001"),
+            declared("sesame", "ing-0001"),
+            allergens::AllergenEntry { allergen: "tree-nuts".to_string(), sources: vec![almond] },
+        ];
+        garnish.info.traces = vec!["peanuts".to_string()];
+        garnish.info.ingredient_count = 1;
+        garnish.info.total_time = Some(5.0);
+        assert!(garnish.valid);
+
+        let nutrition = |servings: u32, kcal: u32, protein: Option<u32>| {
+            let per_100g = match protein {
+                Some(protein) => json!({"calories": kcal, "protein": protein}),
+                None => json!({"calories": kcal}),
+            };
+            labels::compute_nutrition(&json!({
+                "meta": {"servings": {"amount": servings}},
+                "ingredients": [{"id": "ing-0001", "machine_amount": {"value": 100, "unit": "g"},
+                                 "nutritional": {"per_100g": per_100g}}]
+            }))
+            .ok()
+        };
+        sauce.info.nutrition = nutrition(2, 200, Some(8));
+        garnish.info.nutrition = nutrition(4, 40, None);
+
+        // A component whose own sub-component was already merged under a prefix
+        let component = garnish.merge(sauce, "/components/1/");
+        assert_eq!(component.info.allergens[0].to_string(), "milk [ing-0001, from rcip-sauce]");
+        // The whole sauce goes into the garnish's four servings
+        let summed = component.info.nutrition.as_ref().unwrap();
+        assert_eq!((summed.servings, summed.calories, summed.protein_g), (4.0, Some(60.0), Some(2.0)));
+        let mut dish = ValidationResult::new();
+        dish.info.traces = vec!["sesame".to_string()];
+        let dish = dish.merge(component, "/components/0");
+
+        assert!(!dish.valid);
+        let paths: Vec<&str> = dish.issues.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(paths, vec!["/components/0", "/components/0/components/1/ingredients/1"]);
+        assert_eq!(dish.errors, vec!["/components/0/components/1/ingredients/1: Ingredient 1: Missing required allergens field"]);
+        assert_eq!(dish.warnings, vec!["/components/0: Missing recommended field: meta.description"]);
+        // Each component's allergens are inherited from it, not from deeper
+        // down, and one only a name suggests stays inferred
+        let allergens: Vec<String> = dish.info.allergens.iter().map(|e| e.to_string()).collect();
+        assert_eq!(allergens, vec![
+            "milk [from rcip-garnish]",
+            "sesame [from rcip-garnish]",
+            "tree-nuts [ing-0001 inferred from \"almond\"]",
+        ]);
+        assert_eq!(dish.info.traces, vec!["peanuts"]);
+        assert_eq!((dish.info.ingredient_count, dish.info.step_count), (3, 3));
+        assert!(dish.info.has_sensors);
+        assert_eq!(dish.info.total_time, Some(20.0));
+    }
+
+    #[test]
+    fn test_broken_schema_degrades_to_built_in_rules() {
+        let recipe = json!({"rcip_version": "0.1", "id": "invalid-id", "ingredients": [], "steps": []});
+        for schema in ["{\"type\": ", "{\"type\": 12}"] {
+            let mut validator = RCIPValidator::new("0.1");
+            validator.init_from_str(schema).unwrap();
+            assert!(matches!(validator.schema_status(), SchemaStatus::Failed(_)));
+
+            let result = validator.validate(&recipe);
+            assert!(!result.valid);
+            assert!(!result.schema_checked);
+            let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+            assert!(found.contains(&codes::SCHEMA_SKIPPED));
+            assert!(found.contains(&codes::BAD_RECIPE_ID), "{:?}", found);
+
+            let mut strict = RCIPValidator::new("0.1");
+            strict.set_require_schema(true);
+            assert!(strict.init_from_str(schema).is_err());
+        }
+
+        let validator = warm::global_validator("0.1").unwrap();
+        assert_eq!(validator.schema_status(), &SchemaStatus::Compiled);
+        assert!(validator.validate(&recipe).schema_checked);
+    }
+
+    #[test]
+    fn test_bundled_schema_needs_no_files() {
+        let validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        assert_eq!(validator.schema_status(), &SchemaStatus::Compiled);
+        let result = validator.validate_bytes(include_bytes!("../../../examples/simple-example.rcip"));
+        assert!(result.valid, "{:?}", result.errors);
+        assert_eq!(result.meta.unwrap().schema_sha256, validator.schema_sha256);
+
+        let unknown = RCIPValidator::with_bundled_schema("9.9").err().unwrap();
+        assert!(matches!(&unknown, RCIPError::UnknownSchemaVersion(v) if v == "9.9"));
+        assert_eq!(unknown.to_string(), "Unknown schema version: 9.9 (bundled: 0.1)");
+        assert!(matches!(warm::global_validator("9.9"), Err(RCIPError::UnknownSchemaVersion(_))));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_missing_schema_file_falls_back_to_bundled_schema() {
+        let missing = Path::new("no/such/rcip-v0.1.json");
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init_from_path(missing, true).unwrap();
+        assert_eq!(validator.schema_status(), &SchemaStatus::Compiled);
+
+        let mut validator = RCIPValidator::new("0.1");
+        assert!(matches!(validator.init_from_path(missing, false), Err(RCIPError::IoError(_))));
+        assert!(matches!(validator.init(Some(missing)), Err(RCIPError::IoError(_))));
+
+        let mut validator = RCIPValidator::new("9.9");
+        assert!(matches!(validator.init_from_path(missing, true), Err(RCIPError::UnknownSchemaVersion(_))));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_markets() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        assert!(validator.set_markets(&["EU", "XX"]).is_err());
+        validator.set_markets(&["EU", "JP"]).unwrap();
+
+        let recipe = json!({
+            "meta": {"facility_allergens": ["eggs"]},
+            "ingredients": [{"id": "ing-0001", "name": "Nuts", "allergens": ["tree-nuts"]}]
+        });
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.warnings.iter().any(|w| w.starts_with("[EU] Ingredient 0:")));
+        assert!(result.warnings.iter().any(|w| w.starts_with("[JP] 'May contain'")));
+        assert!(!result.warnings.iter().any(|w| w.starts_with("[JP] Ingredient")));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_optional_ingredients() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "basil", "allergens": [], "optional": true},
+                {"id": "ing-0002", "name": "parmesan", "allergens": ["milk"], "optional": "yes"}
+            ]
+        });
+        let result = validator.validate_recipe(&recipe);
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert!(found.contains(&codes::OPTIONAL_NOT_BOOLEAN));
+        assert!(!found.contains(&codes::MOSTLY_OPTIONAL));
+
+        let mut recipe = recipe;
+        recipe["ingredients"][1]["optional"] = json!(true);
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.warnings.contains(&"2 of 2 ingredients are optional; optional is meant for garnishes and serving suggestions".to_string()));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_unused_ingredients_and_dangling_steps() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "allergens": ["wheat"]},
+                {"id": "ing-0002", "name": "sugar", "allergens": []},
+                {"id": "ing-0003", "name": "butter", "allergens": ["milk"]}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "heat", "target": []},
+                {"step_id": "s-02", "action": "mix", "target": ["ing-0001", "ing-0003"], "uses": ["ing-0002"]},
+                {"step_id": "s-03", "action": "bake", "target": ["s-02:result"]}
+            ]
+        });
+        let result = validator.validate_recipe(&recipe);
+        let unused: Vec<&Issue> = result.issues.iter().filter(|i| i.code == codes::UNUSED_INGREDIENT).collect();
+        assert_eq!(unused.len(), 1);
+        assert_eq!(unused[0].pointer, "/ingredients/1");
+        // `uses` is not part of the schema, so it takes nothing
+        assert!(result.warnings.contains(&"Ingredient ing-0002 (sugar) is not used by any step".to_string()));
+        assert!(!result.issues.iter().any(|i| i.code == codes::DANGLING_STEP));
+
+        validator.set_dangling_steps(true);
+        let result = validator.validate_recipe(&recipe);
+        let dangling: Vec<&str> =
+            result.issues.iter().filter(|i| i.code == codes::DANGLING_STEP).map(|i| i.pointer.as_str()).collect();
+        assert_eq!(dangling, ["/steps/0"]);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_step_cycles_are_errors() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+
+        let recipe = json!({
+            "ingredients": [{"id": "ing-0001", "name": "water", "allergens": []}],
+            "steps": [
+                {"step_id": "s-01", "action": "heat", "target": ["ing-0001"]},
+                {"step_id": "s-02", "action": "mix", "target": ["s-03:result"]},
+                {"step_id": "s-03", "action": "wait", "target": ["s-02:result"]}
+            ]
+        });
+        let result = validator.validate_recipe(&recipe);
+        let cycle = result.issues.iter().find(|i| i.code == codes::STEP_CYCLE).unwrap();
+        assert_eq!(cycle.pointer, "/steps/1");
+        assert!(result.errors.contains(&"Steps wait on each other's results and can never start: s-02 -> s-03 -> s-02".to_string()));
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_reports_are_deterministic() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        validator.set_taxonomy_warnings(true);
+
+        let mut recipe: Value = serde_json::from_str(&fs::read_to_string("../../examples/margherita-pizza.rcip").unwrap()).unwrap();
+        recipe["meta"]["diet_labels"] = json!(["vegetarian", "nut-free", "egg-free"]);
+        recipe["ingredients"][0]["id"] = json!("bad id");
+        recipe["ingredients"][1]["allergens"] = json!(["sesame", "milk", "wheat"]);
+
+        let render = |validator: &mut RCIPValidator| {
+            let mut result = validator.validate_recipe(&recipe);
+            result.meta.as_mut().unwrap().timestamp = "2000-01-01T00:00:00Z".to_string();
+            render_report(&result, "Margherita")
+        };
+
+        let first = render(&mut validator);
+        assert!(first.contains("Diet Labels: egg-free, nut-free, vegetarian"));
+        for _ in 0..50 {
+            assert_eq!(render(&mut validator), first);
+        }
+
+        let result = validator.validate_recipe(&recipe);
+        let mut sorted = result.issues.clone();
+        sorted.sort_by_key(|i| (i.location(), i.code));
+        assert_eq!(result.issues.iter().map(|i| i.code).collect::<Vec<_>>(), sorted.iter().map(|i| i.code).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_issues_sort_by_location() {
+        let issue = |code, path: &str, pointer: &str| Issue {
+            code,
+            severity: Severity::Error,
+            params: vec![("ingredient", "flour".to_string())],
+            path: path.to_string(),
+            pointer: pointer.to_string(),
+        };
+        let mut result = ValidationResult::from_issues(vec![
+            issue(codes::BAD_INGREDIENT_ID, "", "/steps/0"),
+            issue(codes::MISSING_ALLERGENS, "/components/1", "/ingredients/0"),
+            issue(codes::MISSING_ALLERGENS, "", "/ingredients/0"),
+        ]);
+        result.sort();
+        let locations: Vec<String> = result.issues.iter().map(|i| i.location()).collect();
+        assert_eq!(locations, ["/components/1/ingredients/0", "/ingredients/0", "/steps/0"]);
+        assert!(result.errors[0].starts_with("/components/1: "), "{}", result.errors[0]);
+    }
+
+    #[test]
+    fn test_effective_rules() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.set_simulation(false);
+        validator.set_setting_source(SettingSource::Cli);
+        validator.set_simulation(true);
+
+        let rules = validator.effective_rules();
+        let overused = rules.iter().find(|r| r.code == codes::INGREDIENT_OVERUSED).unwrap();
+        assert!(overused.enabled);
+        let switch = overused.enabled_by.as_ref().unwrap();
+        assert_eq!(switch.source, SettingSource::Cli);
+        assert_eq!(switch.overridden, vec![(SettingSource::Api, "false".to_string())]);
+
+        let mise = rules.iter().find(|r| r.code == codes::MISE_EN_PLACE).unwrap();
+        assert!(!mise.enabled);
+        assert_eq!(mise.enabled_by.as_ref().unwrap().source, SettingSource::Default);
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_localized_messages() {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        validator.set_locale("de");
+
+        let recipe = json!({
+            "rcip_version": "0.1",
+            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
+            "meta": {"name": "Test", "author": "Test", "created_date": "2025-01-15T10:00:00Z"},
+            "ingredients": [{"id": "ing-0001", "name": "salt", "human_amount": "1 pinch",
+                             "machine_amount": {"value": 1, "unit": "g"}}],
+            "steps": []
+        });
+
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.errors.contains(&"Zutat 0: Pflichtfeld allergens fehlt".to_string()));
+        assert!(result.warnings.contains(&"Empfohlenes Feld fehlt: meta.description".to_string()));
+
+        // Codes and parameters do not depend on the locale
+        let issue = result.issues.iter().find(|i| i.code == codes::MISSING_ALLERGENS).unwrap();
+        assert_eq!(issue.severity, Severity::Error);
+        assert_eq!(issue.params, vec![("index", "0".to_string())]);
+    }
+}
+
+// CLI binary implementation (src/main.rs)
+#[cfg(not(feature = "no-fs"))]
+pub mod cli {
+    use super::*;
+    use clap::{Arg, Command};
+    use std::process;
+
+    /// Exit status when a validated recipe is invalid
+    pub const EXIT_INVALID: i32 = 1;
+    /// Exit status for usage, IO and setup errors
+    pub const EXIT_ERROR: i32 = 2;
+    /// Exit status when validation ran without the schema
+    pub const EXIT_DEGRADED: i32 = 3;
+
+    pub fn run() {
+        let matches = Command::new("RCIP Validator")
+            .version("1.0.0")
+            .author("Alexey Kozlov")
+            .about("Validates RCIP format recipes")
+            // --version selects the schema version
+            .disable_version_flag(true)
+            .subcommand_negates_reqs(true)
+            .args_conflicts_with_subcommands(true)
+            .arg(
+                Arg::new("target")
+                    .help("Recipe file, directory, .zip archive, s3://bucket/prefix or - for stdin to validate")
+                    .required_unless_present_any(["project", "new-id"])
+                    .index(1),
+            )
+            .arg(
+                Arg::new("recursive")
+                    .short('r')
+                    .long("recursive")
+                    .help("Also validate recipes in subdirectories of a directory target")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("include")
+                    .long("include")
+                    .value_name("GLOB")
+                    .help("Only validate files whose path relative to the directory matches, e.g. **/*.rcip (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("exclude")
+                    .long("exclude")
+                    .value_name("GLOB")
+                    .help("Skip files whose path relative to the directory matches, e.g. **/drafts/** (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("jobs")
+                    .short('j')
+                    .long("jobs")
+                    .value_name("N")
+                    .value_parser(clap::value_parser!(usize))
+                    .help("Validate directory files on at most N threads (needs the parallel feature; default one per core)"),
+            )
+            .arg(
+                Arg::new("project")
+                    .long("project")
+                    .value_name("MANIFEST")
+                    .help("Validate every root of an rcip.toml project manifest with its own settings")
+                    .conflicts_with("target"),
+            )
+            .arg(
+                Arg::new("version")
+                    .short('v')
+                    .long("version")
+                    .value_name("VERSION")
+                    .help("RCIP schema version")
+                    .default_value("0.1"),
+            )
+            .arg(
+                Arg::new("schema")
+                    .short('s')
+                    .long("schema")
+                    .value_name("PATH")
+                    .help("Path to custom schema file"),
+            )
+            .arg(
+                Arg::new("stats-file")
+                    .long("stats-file")
+                    .value_name("PATH")
+                    .help("Append local usage statistics for this run to a JSON-lines file"),
+            )
+            .arg(
+                Arg::new("strict-schema")
+                    .long("strict-schema")
+                    .help("Fail when the schema disagrees with the built-in rules")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("rules")
+                    .long("rules")
+                    .value_name("PATH")
+                    .help("Turn rules on or off with a JSON or TOML rule config (repeatable; later files win)")
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("disable")
+                    .long("disable")
+                    .value_name("RULE_ID")
+                    .help("Turn off a rule by its issue code, e.g. RCIP-W-NO-EXTERNAL-IDS (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("deny-warnings")
+                    .long("deny-warnings")
+                    .value_name("CODES")
+                    .help("Report warnings as errors; with =CODES only those whose code or message contains one of them, comma-separated")
+                    .num_args(0..=1)
+                    .require_equals(true)
+                    .value_delimiter(',')
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("fix")
+                    .long("fix")
+                    .help("Repair what can be repaired unambiguously (term and unit spellings, uppercase ids), rewrite the files and validate them")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("fix-allergens-empty")
+                    .long("fix-allergens-empty")
+                    .help("With --fix, declare an empty allergen list on ingredients that have none")
+                    .requires("fix")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("dry-run")
+                    .long("dry-run")
+                    .help("With --fix, report what would change without writing")
+                    .requires("fix")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("new-id")
+                    .long("new-id")
+                    .help("Print a fresh recipe id and exit")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("migrate-to")
+                    .long("migrate-to")
+                    .value_name("VERSION")
+                    .help("Upgrade the target recipes to this RCIP version, writing each next to the original as NAME.vVERSION.rcip"),
+            )
+            .arg(
+                Arg::new("write")
+                    .long("write")
+                    .help("With --migrate-to, --canonicalize or --scale, replace the original files")
+                    .requires("rewrite")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("canonicalize")
+                    .long("canonicalize")
+                    .help("Print the target recipes in canonical form: schema key order, sorted allergen and diet label lists, plain numbers, UTC timestamps")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("check")
+                    .long("check")
+                    .help("With --canonicalize, list the files not in canonical form and exit 1 if there are any, writing nothing")
+                    .requires("canonicalize")
+                    .conflicts_with("write")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("scale")
+                    .long("scale")
+                    .value_name("FACTOR")
+                    .help("Print the target recipes with every amount and the servings multiplied by FACTOR")
+                    .value_parser(clap::value_parser!(f64)),
+            )
+            .arg(
+                Arg::new("round-counts")
+                    .long("round-counts")
+                    .value_name("MODE")
+                    .help("With --scale, how piece counts are rounded: round (at least one), ceil or keep the fraction")
+                    .value_parser(["round", "ceil", "keep"])
+                    .default_value("round")
+                    .requires("scale"),
+            )
+            .group(clap::ArgGroup::new("rewrite").args(["migrate-to", "canonicalize", "scale"]).multiple(false))
+            .arg(
+                Arg::new("versions")
+                    .long("versions")
+                    .value_name("VERSIONS")
+                    .help("Also load these schema versions, comma-separated, and check each recipe against the one its rcip_version names")
+                    .value_delimiter(',')
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("strict")
+                    .long("strict")
+                    .help("Reject fields the schema does not declare, suggesting the field likely meant")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("require-schema")
+                    .long("require-schema")
+                    .help("Fail when the schema cannot be parsed or compiled instead of running the built-in rules only")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("mmap")
+                    .long("mmap")
+                    .help("Memory-map recipe files instead of reading them, for very large files")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("skip-binary-payloads")
+                    .long("skip-binary-payloads")
+                    .help("Replace embedded base64 data URIs with placeholders recording their size")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("product-db")
+                    .long("product-db")
+                    .value_name("SNAPSHOT")
+                    .help("Cross-check allergens of ingredients with external ids against a product snapshot (needs the product-db feature)"),
+            )
+            .arg(
+                Arg::new("explain-config")
+                    .long("explain-config")
+                    .help("Print the effective rule set and where each setting came from, without validating")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("csv")
+                    .long("csv")
+                    .value_name("FILE")
+                    .help("Write one line per validated file, with its issue counts and quality score, to a CSV file"),
+            )
+            .arg(
+                Arg::new("flag-outliers")
+                    .long("flag-outliers")
+                    .help("For directories, print percentiles of ingredient and step counts, total time and calories per serving, and add a notice to files beyond --outlier-percentile")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("outlier-percentile")
+                    .long("outlier-percentile")
+                    .value_name("P")
+                    .value_parser(clap::value_parser!(f64))
+                    .default_value("99")
+                    .help("Percentile of the corpus a value must exceed to be flagged as an outlier"),
+            )
+            .arg(
+                Arg::new("profile-rules")
+                    .long("profile-rules")
+                    .help("After validating, print how long each validation phase took in total")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("journal")
+                    .long("journal")
+                    .value_name("FILE")
+                    .help("Append each validated file and its outcome to a hash-chained audit journal (needs the journal feature)"),
+            )
+            .arg(
+                Arg::new("rule-pack")
+                    .long("rule-pack")
+                    .value_name("WASM")
+                    .help("Also run the rules in a WASM rule pack (needs the wasm-rules feature)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("market")
+                    .long("market")
+                    .value_name("MARKET")
+                    .help("Apply the labeling rules of a market: EU, US or JP (repeatable)")
+                    .action(clap::ArgAction::Append),
+            )
+            .arg(
+                Arg::new("allergen-provenance")
+                    .long("allergen-provenance")
+                    .value_name("KINDS")
+                    .help("Allergen provenances diet-label and market rules act on, comma-separated (default declared,inherited)")
+                    .value_parser(clap::value_parser!(allergens::ProvenancePolicy)),
+            )
+            .arg(
+                Arg::new("lang")
+                    .long("lang")
+                    .value_name("LOCALE")
+                    .help("Language of error and warning messages, e.g. de")
+                    .default_value("en"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .value_parser(["text", "json"])
+                    .default_value("text")
+                    .help("Output: reports for people, or one JSON document with every file's result and the summary stats"),
+            )
+            .subcommand(
+                Command::new("stats")
+                    .about("Inspect usage statistics files")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("summarize")
+                            .about("Aggregate a stats file into a weekly trend table")
+                            .arg(Arg::new("file").required(true).index(1)),
+                    ),
+            )
+            .subcommand(
+                Command::new("product-db")
+                    .about("Manage offline product snapshots for external-id checks")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("build")
+                            .about("Convert an Open Food Facts JSONL export into a compact gzipped snapshot")
+                            .arg(Arg::new("export").required(true).index(1))
+                            .arg(
+                                Arg::new("output")
+                                    .short('o')
+                                    .long("output")
+                                    .value_name("SNAPSHOT")
+                                    .required(true)
+                                    .help("Where to write the snapshot, e.g. products.ndjson.gz"),
+                            ),
+                    ),
+            )
+            .subcommand(
+                Command::new("journal")
+                    .about("Inspect audit journals written with --journal")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("verify")
+                            .about("Check every entry and the hash chain; exits 1 at the first corrupted entry")
+                            .arg(Arg::new("file").required(true).index(1)),
+                    ),
+            )
+            .subcommand(
+                Command::new("verify-claims")
+                    .about("Report the allergen evidence behind each diet label of a recipe")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("open-food-facts")
+                            .long("open-food-facts")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Confirm external ids with Open Food Facts (needs the http-checks feature)"),
+                    )
+                    .arg(
+                        Arg::new("product-db")
+                            .long("product-db")
+                            .value_name("SNAPSHOT")
+                            .help("Confirm external ids with a local product snapshot, before Open Food Facts (needs the product-db feature)"),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["text", "json"])
+                            .default_value("text"),
+                    ),
+            )
+            .subcommand(
+                Command::new("compat")
+                    .about("Check a recipe against a device capability manifest")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("device")
+                            .long("device")
+                            .value_name("MANIFEST")
+                            .help("Device capability manifest (JSON)")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["text", "json"])
+                            .default_value("text"),
+                    ),
+            )
+            .subcommand(
+                Command::new("label")
+                    .about("Render a regulatory label for a recipe")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["eu-fic", "nutrition-facts"])
+                            .default_value("eu-fic"),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Print the structured label as JSON"),
+                    )
+                    .arg(
+                        Arg::new("html")
+                            .long("html")
+                            .action(clap::ArgAction::SetTrue)
+                            .conflicts_with("json")
+                            .help("Render the nutrition facts panel as HTML"),
+                    )
+                    .arg(
+                        Arg::new("market")
+                            .long("market")
+                            .value_name("MARKET")
+                            .help("Refuse formats that do not apply in this market"),
+                    )
+                    .arg(
+                        Arg::new("allergen-provenance")
+                            .long("allergen-provenance")
+                            .value_name("KINDS")
+                            .help("Allergen provenances the eu-fic label declares, comma-separated (default declared,inherited)")
+                            .value_parser(clap::value_parser!(allergens::ProvenancePolicy)),
+                    ),
+            )
+            .subcommand(
+                Command::new("allergens")
+                    .about("List a recipe's allergens and where each comes from: declared, inferred or inherited")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Print every source of every allergen as JSON"),
+                    ),
+            )
+            .subcommand(
+                Command::new("scrub")
+                    .about("Anonymize a recipe for sharing in bug reports")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("seed")
+                            .long("seed")
+                            .value_name("SEED")
+                            .value_parser(clap::value_parser!(u64))
+                            .default_value("0"),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("Write the scrubbed recipe here instead of stdout"),
+                    ),
+            )
+            .subcommand(
+                Command::new("fix")
+                    .about("Repair a recipe and write the result; what changed goes to stderr")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("infer-actions")
+                            .long("infer-actions")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Fill in missing step actions from the step text, marked x-inferred"),
+                    )
+                    .arg(
+                        Arg::new("migrate-deprecated")
+                            .long("migrate-deprecated")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Move deprecated fields that were only renamed to their replacement"),
+                    )
+                    .arg(
+                        Arg::new("normalize-terms")
+                            .long("normalize-terms")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Write allergens, diet labels and hazards in their canonical spelling, dropping repeats"),
+                    )
+                    .arg(
+                        Arg::new("normalize-numbers")
+                            .long("normalize-numbers")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Write numbers given as strings as JSON numbers, and -0 as 0"),
+                    )
+                    .arg(
+                        Arg::new("normalize-units")
+                            .long("normalize-units")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Write machine amount units given as aliases (\"grams\") as the unit they stand for"),
+                    )
+                    .arg(
+                        Arg::new("lowercase-ids")
+                            .long("lowercase-ids")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Lowercase a recipe id written in uppercase hex"),
+                    )
+                    .arg(
+                        Arg::new("empty-allergens")
+                            .long("empty-allergens")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Declare an empty allergen list on ingredients that have none; check each one"),
+                    )
+                    .arg(
+                        Arg::new("round-amounts")
+                            .long("round-amounts")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Round machine amounts to the default precision of their unit"),
+                    )
+                    .arg(
+                        Arg::new("add-prep-phase")
+                            .long("add-prep-phase")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Add parallel measure steps for unprepped ingredients before the first step"),
+                    )
+                    .arg(
+                        Arg::new("sort-ingredients")
+                            .long("sort-ingredients")
+                            .value_name("ORDER")
+                            .help("Reorder ingredients; more than one order is a conflict and none is applied")
+                            .value_parser(["amount", "name", "usage"])
+                            .action(clap::ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("renumber-ids")
+                            .long("renumber-ids")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Renumber ingredients and steps in listing order, rewriting references"),
+                    )
+                    .arg(
+                        Arg::new("min-confidence")
+                            .long("min-confidence")
+                            .value_name("CONFIDENCE")
+                            .help("Leave steps whose best guess is less certain than this, 0.0 to 1.0")
+                            .value_parser(clap::value_parser!(f64))
+                            .default_value("0.6"),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("Write the fixed recipe here instead of stdout"),
+                    ),
+            )
+            .subcommand(
+                Command::new("diff")
+                    .about("Show what changed between two versions of a recipe, matching ingredients and steps by id")
+                    .arg(Arg::new("old").required(true).index(1))
+                    .arg(Arg::new("new").required(true).index(2))
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["text", "json"])
+                            .default_value("text"),
+                    ),
+            )
+            .subcommand(
+                Command::new("merge")
+                    .about("Three-way merge of two edits of a recipe; conflicts and invalid results write nothing")
+                    .arg(Arg::new("base").required(true).index(1))
+                    .arg(Arg::new("ours").required(true).index(2))
+                    .arg(Arg::new("theirs").required(true).index(3))
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("Write the merged recipe here instead of stdout"),
+                    ),
+            )
+            .subcommand(
+                Command::new("export")
+                    .about("Export a recipe's step schedule, step cards or a printable copy")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["ics", "cards-json", "cards-md", "text", "md", "html"])
+                            .default_value("ics"),
+                    )
+                    .arg(
+                        Arg::new("start")
+                            .long("start")
+                            .value_name("TIME")
+                            .help("Schedule start, e.g. 2025-06-01T08:00 (UTC unless an offset is given); required for ics"),
+                    )
+                    .arg(
+                        Arg::new("text-budget")
+                            .long("text-budget")
+                            .value_name("CHARS")
+                            .value_parser(clap::value_parser!(usize))
+                            .help("Most characters of step text per card"),
+                    )
+                    .arg(
+                        Arg::new("lang")
+                            .long("lang")
+                            .value_name("LOCALE")
+                            .help("Language of the card or printed text, for translated steps and guidance"),
+                    )
+                    .arg(
+                        Arg::new("output")
+                            .short('o')
+                            .long("output")
+                            .value_name("PATH")
+                            .help("Write the export here instead of stdout"),
+                    ),
+            )
+            .subcommand(
+                Command::new("shots")
+                    .about("List the photos a recipe needs and check them against a folder")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("assets")
+                            .long("assets")
+                            .value_name("DIR")
+                            .help("Report shots missing from this folder and files no shot asks for"),
+                    )
+                    .arg(
+                        Arg::new("pattern")
+                            .long("pattern")
+                            .value_name("PATTERN")
+                            .help("File name pattern using {recipe}, {shot} and {index} [default: {recipe}-{shot}.jpg]"),
+                    )
+                    .arg(
+                        Arg::new("json")
+                            .long("json")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Print the shot list and asset report as JSON"),
+                    ),
+            )
+            .subcommand(
+                Command::new("import")
+                    .about("Convert recipe manager exports into RCIP documents")
+                    .arg(Arg::new("export").required(true).index(1))
+                    .arg(
+                        Arg::new("from")
+                            .long("from")
+                            .value_parser(["paprika", "mealie"])
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("out-dir")
+                            .long("out-dir")
+                            .value_name("DIR")
+                            .help("Directory to write the converted recipes to")
+                            .required(true),
+                    )
+                    .arg(
+                        Arg::new("id-namespace")
+                            .long("id-namespace")
+                            .value_name("NAMESPACE")
+                            .help("Derive all ids from the namespace and the source, so re-imports are identical"),
+                    )
+                    .arg(
+                        Arg::new("random-ids")
+                            .long("random-ids")
+                            .action(clap::ArgAction::SetTrue)
+                            .conflicts_with("id-namespace")
+                            .help("Give each import a fresh recipe id"),
+                    ),
+            )
+            .subcommand(
+                Command::new("init")
+                    .about("Create a first recipe, prompting for anything not given as a flag")
+                    .arg(Arg::new("out").required(true).index(1).help("File to write the recipe to"))
+                    .arg(Arg::new("name").long("name").value_name("NAME"))
+                    .arg(Arg::new("author").long("author").value_name("AUTHOR"))
+                    .arg(
+                        Arg::new("servings")
+                            .long("servings")
+                            .value_name("N")
+                            .value_parser(clap::value_parser!(u32)),
+                    )
+                    .arg(
+                        Arg::new("ingredient")
+                            .long("ingredient")
+                            .value_name("NAME;AMOUNT;UNIT[;ALLERGENS]")
+                            .help("Add an ingredient, e.g. 'Flour;200;g;wheat,gluten' (repeatable)")
+                            .action(clap::ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("step")
+                            .long("step")
+                            .value_name("ACTION;TEXT[;TARGETS]")
+                            .help("Add a step; targets are ingredient names or ids, or earlier step ids (repeatable)")
+                            .action(clap::ArgAction::Append),
+                    )
+                    .arg(
+                        Arg::new("no-input")
+                            .long("no-input")
+                            .help("Never prompt; fail when an answer is missing")
+                            .action(clap::ArgAction::SetTrue),
+                    )
+                    .arg(
+                        Arg::new("force")
+                            .long("force")
+                            .help("Overwrite an existing file")
+                            .action(clap::ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("search")
+                    .about("Search and rank a directory of recipes by name, allergens, diet and nutrition")
+                    .arg(Arg::new("dir").required(true).index(1))
+                    .arg(Arg::new("name").long("name").value_name("TEXT").help("Name contains TEXT"))
+                    .arg(
+                        Arg::new("exclude-allergen")
+                            .long("exclude-allergen")
+                            .value_name("ALLERGEN")
+                            .action(clap::ArgAction::Append),
+                    )
+                    .arg(Arg::new("diet").long("diet").value_name("LABEL").action(clap::ArgAction::Append))
+                    .arg(
+                        Arg::new("max-calories-per-serving")
+                            .long("max-calories-per-serving")
+                            .value_name("KCAL")
+                            .value_parser(clap::value_parser!(f64)),
+                    )
+                    .arg(
+                        Arg::new("max-sodium-per-serving")
+                            .long("max-sodium-per-serving")
+                            .value_name("MG")
+                            .value_parser(clap::value_parser!(f64)),
+                    )
+                    .arg(
+                        Arg::new("min-protein-per-serving")
+                            .long("min-protein-per-serving")
+                            .value_name("G")
+                            .value_parser(clap::value_parser!(f64)),
+                    )
+                    .arg(
+                        Arg::new("min-quality")
+                            .long("min-quality")
+                            .value_name("SCORE")
+                            .value_parser(clap::value_parser!(f64))
+                            .help("Quality score of at least SCORE out of 100"),
+                    )
+                    .arg(
+                        Arg::new("sort")
+                            .long("sort")
+                            .value_name("METRIC[:asc|desc]")
+                            .help("e.g. protein-per-serving:desc, sodium-per-serving, protein-per-calorie:desc, quality:desc"),
+                    )
+                    .arg(
+                        Arg::new("limit")
+                            .long("limit")
+                            .value_name("N")
+                            .value_parser(clap::value_parser!(usize)),
+                    ),
+            )
+            .subcommand(
+                Command::new("similar")
+                    .about("Find catalog recipes structurally similar to a recipe")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(Arg::new("in").long("in").value_name("DIR").required(true).help("Catalog directory"))
+                    .arg(
+                        Arg::new("exclude-allergen")
+                            .long("exclude-allergen")
+                            .value_name("ALLERGEN")
+                            .action(clap::ArgAction::Append),
+                    )
+                    .arg(Arg::new("diet").long("diet").value_name("LABEL").action(clap::ArgAction::Append))
+                    .arg(
+                        Arg::new("top")
+                            .long("top")
+                            .value_name("N")
+                            .default_value("10")
+                            .value_parser(clap::value_parser!(usize)),
+                    ),
+            )
+            .subcommand(
+                Command::new("simulate")
+                    .about("Dry-run a recipe and print its timeline")
+                    .arg(Arg::new("recipe").required(true).index(1)),
+            )
+            .subcommand(
+                Command::new("energy")
+                    .about("Estimate the energy a recipe's powered steps draw")
+                    .arg(Arg::new("recipe").required(true).index(1))
+                    .arg(
+                        Arg::new("profile")
+                            .long("profile")
+                            .value_name("FILE")
+                            .help("Energy profile JSON; appliances it leaves out keep their built-in power models"),
+                    )
+                    .arg(
+                        Arg::new("format")
+                            .long("format")
+                            .value_parser(["text", "json"])
+                            .default_value("text"),
+                    ),
+            )
+            .subcommand(
+                Command::new("certify")
+                    .about("Check that machines can execute recipes with no human interpretation")
+                    .arg(
+                        Arg::new("paths")
+                            .required(true)
+                            .num_args(1..)
+                            .help("Recipe files or directories; exits 1 if any recipe is not certified, 2 if any cannot be read"),
+                    ),
+            )
+            .subcommand(
+                Command::new("ingest")
+                    .about("Validate a drop folder and move recipes to accepted/, rejected/ or quarantine/")
+                    .arg(Arg::new("incoming").required(true).help("Directory the recipes are dropped into"))
+                    .arg(
+                        Arg::new("out")
+                            .long("out")
+                            .value_name("DIR")
+                            .help("Directory holding accepted/, rejected/ and quarantine/ (default: the incoming directory)"),
+                    )
+                    .arg(
+                        Arg::new("retries")
+                            .long("retries")
+                            .value_name("N")
+                            .default_value("3")
+                            .value_parser(clap::value_parser!(u32))
+                            .help("Further attempts at a read, write or move that fails before quarantining the recipe"),
+                    )
+                    .arg(
+                        Arg::new("retry-delay-ms")
+                            .long("retry-delay-ms")
+                            .value_name("MS")
+                            .default_value("100")
+                            .value_parser(clap::value_parser!(u64))
+                            .help("Wait before the first retry, doubled before each further one"),
+                    )
+                    .arg(
+                        Arg::new("manifest")
+                            .long("manifest")
+                            .value_name("FILE")
+                            .help("Write the run manifest, every recipe's disposition as JSON, to FILE"),
+                    )
+                    .arg(
+                        Arg::new("dry-run")
+                            .long("dry-run")
+                            .help("Report where each recipe would go without moving anything")
+                            .action(clap::ArgAction::SetTrue),
+                    ),
+            )
+            .subcommand(
+                Command::new("release-notes")
+                    .about("Summarize recipe changes between two git refs as Markdown")
+                    .arg(Arg::new("from").long("from").value_name("REF").required(true))
+                    .arg(Arg::new("to").long("to").value_name("REF").required(true))
+                    .arg(
+                        Arg::new("dir")
+                            .long("dir")
+                            .value_name("DIR")
+                            .default_value(".")
+                            .help("Recipe directory inside a git work tree"),
+                    ),
+            )
+            .subcommand(
+                Command::new("self-check")
+                    .about("Check the bundled schemas, vocabularies, message catalogs and examples")
+                    .arg(
+                        Arg::new("root")
+                            .long("root")
+                            .value_name("DIR")
+                            .help("Source tree holding schemas/ and examples/")
+                            .default_value("../.."),
+                    ),
+            )
+            .subcommand(
+                Command::new("conformance")
+                    .about("Check validation results against the conformance fixtures")
+                    .arg(
+                        Arg::new("dir")
+                            .help("Conformance suite directory")
+                            .default_value("../../conformance"),
+                    )
+                    .arg(
+                        Arg::new("actual")
+                            .long("actual")
+                            .value_name("FILE")
+                            .help("Check another implementation's issues, a JSON object of fixture name to [{code, path}]")
+                            .conflicts_with("update"),
+                    )
+                    .arg(
+                        Arg::new("update")
+                            .long("update")
+                            .action(clap::ArgAction::SetTrue)
+                            .help("Rewrite every expectation from this validator's results"),
+                    ),
+            )
+            .subcommand(
+                Command::new("generate")
+                    .about("Generate reproducible test data")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("corpus")
+                            .about("Write a seeded corpus of random recipes for benchmarking")
+                            .arg(
+                                Arg::new("count")
+                                    .long("count")
+                                    .value_name("N")
+                                    .value_parser(clap::value_parser!(usize))
+                                    .default_value("1000"),
+                            )
+                            .arg(
+                                Arg::new("seed")
+                                    .long("seed")
+                                    .value_name("SEED")
+                                    .value_parser(clap::value_parser!(u64))
+                                    .default_value("0"),
+                            )
+                            .arg(
+                                Arg::new("errors")
+                                    .long("errors")
+                                    .value_name("RATE")
+                                    .help("Share of files, 0.0 to 1.0, with one injected defect")
+                                    .value_parser(clap::value_parser!(f64))
+                                    .default_value("0"),
+                            )
+                            .arg(
+                                Arg::new("out")
+                                    .long("out")
+                                    .value_name("DIR")
+                                    .required(true),
+                            )
+                            .arg(
+                                Arg::new("id-namespace")
+                                    .long("id-namespace")
+                                    .value_name("NAMESPACE")
+                                    .help("Derive ids from the namespace and each recipe's content"),
+                            ),
+                    ),
+            )
+            .get_matches();
+
+        match matches.subcommand() {
+            Some(("stats", sub)) => return run_stats(sub),
+            Some(("journal", sub)) => return run_journal(sub),
+            Some(("product-db", sub)) => return run_product_db(sub),
+            Some(("verify-claims", sub)) => return run_verify_claims(sub),
+            Some(("compat", sub)) => return run_compat(sub),
+            Some(("label", sub)) => return run_label(sub),
+            Some(("allergens", sub)) => return run_allergens(sub),
+            Some(("scrub", sub)) => return run_scrub(sub),
+            Some(("fix", sub)) => return run_fix(sub),
+            Some(("diff", sub)) => return run_diff(sub),
+            Some(("merge", sub)) => return run_merge(sub),
+            Some(("export", sub)) => return run_export(sub),
+            Some(("shots", sub)) => return run_shots(sub),
+            Some(("import", sub)) => return run_import(sub),
+            Some(("init", sub)) => return run_init(sub),
+            Some(("search", sub)) => return run_search(sub),
+            Some(("similar", sub)) => return run_similar(sub),
+            Some(("simulate", sub)) => return run_simulate(sub),
+            Some(("energy", sub)) => return run_energy(sub),
+            Some(("generate", sub)) => return run_generate(sub),
+            Some(("certify", sub)) => return run_certify(sub),
+            Some(("ingest", sub)) => return run_ingest(sub),
+            Some(("release-notes", sub)) => return run_release_notes(sub),
+            Some(("self-check", sub)) => return run_self_check(sub),
+            Some(("conformance", sub)) => return run_conformance(sub),
+            _ => {}
+        }
+
+        if matches.get_flag("new-id") {
+            println!("{}", ids::generate_recipe_id());
+            return;
+        }
+        if let Some(manifest) = matches.get_one::<String>("project") {
+            return run_project(Path::new(manifest));
+        }
+        if let Some(to) = matches.get_one::<String>("migrate-to") {
+            return run_migrate(&matches, to);
+        }
+        if matches.get_flag("fix") {
+            return run_autofix(&matches);
+        }
+        if matches.get_flag("canonicalize") {
+            return run_canonicalize(&matches);
+        }
+        if let Some(factor) = matches.get_one::<f64>("scale") {
+            return run_scale(&matches, *factor);
+        }
+
+        let target = matches.get_one::<String>("target").unwrap();
+        let version = matches.get_one::<String>("version").unwrap();
+        let schema_path = matches.get_one::<String>("schema").map(Path::new);
+
+        let json = matches.get_one::<String>("format").map(|f| f.as_str()) == Some("json");
+        let mut validator = RCIPValidator::new(version);
+        if !json {
+            validator.set_reporter(Box::new(reporter::ConsoleReporter));
+        }
+        validator.set_locale(matches.get_one::<String>("lang").unwrap());
+        validator.set_strict_schema(matches.get_flag("strict-schema"));
+        validator.set_require_schema(matches.get_flag("require-schema"));
+        validator.set_memory_map(matches.get_flag("mmap"));
+        validator.set_skip_binary_payloads(matches.get_flag("skip-binary-payloads"));
+        if matches.get_flag("flag-outliers") {
+            let percentile = *matches.get_one::<f64>("outlier-percentile").unwrap();
+            validator.set_flag_outliers(Some(corpus::Thresholds::uniform(percentile)));
+        }
+        validator.set_setting_source(SettingSource::Cli);
+        if matches.get_flag("strict") {
+            validator.set_unknown_fields(true);
+        }
+
+        let jobs = matches.get_one::<usize>("jobs").copied();
+        if !cfg!(feature = "parallel") && jobs.is_some_and(|jobs| jobs > 1) {
+            eprintln!("Error: --jobs needs a build with the parallel feature");
+            process::exit(EXIT_ERROR);
+        }
+        validator.set_jobs(jobs);
+
+        if let Some(db) = product_db(&matches) {
+            let mut resolvers = Resolvers::new();
+            resolvers.push(db);
+            validator.set_resolvers(resolvers);
+        }
+
+        if let Some(policy) = matches.get_one::<allergens::ProvenancePolicy>("allergen-provenance") {
+            validator.set_allergen_provenance(policy.clone());
+        }
+
+        let mut rules = rule_config::RuleConfig::default();
+        for path in matches.get_many::<PathBuf>("rules").into_iter().flatten() {
+            match rule_config::RuleConfig::from_file(path) {
+                Ok(config) => {
+                    rules.merge(config);
+                }
+                Err(e) => {
+                    eprintln!("Error: {}: {}", path.display(), e);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        for code in matches.get_many::<String>("disable").into_iter().flatten() {
+            rules.disable(code);
+        }
+        if !rules.rules.is_empty() {
+            if let Err(e) = validator.set_rule_config(&rules) {
+                eprintln!("Error: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+
+        if let Some(patterns) = matches.get_many::<String>("deny-warnings") {
+            let patterns: Vec<&str> = patterns.map(|p| p.as_str()).collect();
+            if patterns.is_empty() {
+                validator.set_strict_warnings(true);
+            } else {
+                validator.set_denied_warnings(&patterns);
+            }
+        }
+
+        let selected: Vec<&str> = matches.get_many::<String>("market").map(|m| m.map(|s| s.as_str()).collect()).unwrap_or_default();
+        if !selected.is_empty() {
+            if let Err(e) = validator.set_markets(&selected) {
+                eprintln!("Error: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+
+        let packs: Vec<&String> = matches.get_many::<String>("rule-pack").map(|p| p.collect()).unwrap_or_default();
+        #[cfg(feature = "wasm-rules")]
+        for path in packs {
+            match rule_pack::RulePack::load(Path::new(path)) {
+                Ok(pack) => validator.add_rule_pack(pack),
+                Err(e) => {
+                    eprintln!("Error loading rule pack {}: {}", path, e);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        }
+        #[cfg(not(feature = "wasm-rules"))]
+        if !packs.is_empty() {
+            eprintln!("Error: rule packs need a build with the wasm-rules feature");
+            process::exit(EXIT_ERROR);
+        }
+
+        if matches.get_flag("explain-config") {
+            print!("{}", explain::render_text(&validator.effective_rules()));
+            return;
+        }
+
+        if let Err(e) = validator.init(schema_path) {
+            eprintln!("Error initializing validator: {}", e);
+            process::exit(EXIT_ERROR);
+        }
+        let versions: Vec<&str> = matches.get_many::<String>("versions").map(|v| v.map(|s| s.as_str()).collect()).unwrap_or_default();
+        if let Err(e) = validator.init_versions(&versions) {
+            eprintln!("Error loading schema versions: {}", e);
+            process::exit(EXIT_ERROR);
+        }
+        if let SchemaStatus::Failed(reason) = validator.schema_status() {
+            eprintln!("{}", "!".repeat(60));
+            eprintln!("⚠️  DEGRADED MODE: {}", reason);
+            eprintln!("   Schema validation is skipped; only the built-in rules run.");
+            eprintln!("   Pass --require-schema to fail instead.");
+            eprintln!("{}", "!".repeat(60));
+        }
+
+        let target_path = Path::new(target);
+        let mut profile = observer::RuleProfile::new();
+        let journal = matches.get_one::<String>("journal").map(|path| open_journal(Path::new(path)));
+        if journal.is_some() {
+            validator.set_fingerprint_files(true);
+        }
+
+        let results = if target == "-" {
+            if journal.is_some() {
+                eprintln!("Error: --journal records local files, not stdin");
+                process::exit(EXIT_ERROR);
+            }
+            match validator.validate_reader(std::io::stdin().lock()) {
+                Ok(result) => vec![("-".to_string(), result)],
+                Err(e) => {
+                    eprintln!("Error validating stdin: {}", e);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        } else if let Some(source) = open_source(target) {
+            if journal.is_some() {
+                eprintln!("Error: --journal records local files, not {}", target);
+                process::exit(EXIT_ERROR);
+            }
+            validator.validate_source_with_observer(source.as_ref(), &mut profile)
+        } else if target_path.is_dir() {
+            let globs = |name: &str| -> Vec<String> { matches.get_many::<String>(name).map(|g| g.cloned().collect()).unwrap_or_default() };
+            let options = discover::DirectoryOptions {
+                recursive: matches.get_flag("recursive"),
+                include: globs("include"),
+                exclude: globs("exclude"),
+            };
+            match validator.validate_directory_with_options(target_path, &options, &mut profile) {
+                Ok(results) => results
+                    .into_iter()
+                    .map(|(file_name, result)| (target_path.join(file_name).display().to_string(), result))
+                    .collect(),
+                Err(e) => {
+                    eprintln!("Error validating directory: {}", e);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        } else if target_path.is_file() {
+            match validator.validate_file_with_observer(target_path, &mut profile) {
+                Ok(result) => vec![(target.clone(), result)],
+                Err(e) => {
+                    eprintln!("Error validating file: {}", e);
+                    process::exit(EXIT_ERROR);
+                }
+            }
+        } else {
+            eprintln!("Error: {} is not a valid file or directory", target);
+            process::exit(EXIT_ERROR);
+        };
+
+        let all_valid = results.iter().all(|(_, result)| result.valid);
+
+        if let Some(csv) = matches.get_one::<String>("csv") {
+            if let Err(e) = fs::write(csv, render_csv(&results)) {
+                eprintln!("Error writing {}: {}", csv, e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+
+        if json {
+            println!("{}", render_json(&results, validator.get_stats()));
+        }
+
+        if let Some(journal) = &journal {
+            for (path, result) in results {
+                record_in_journal(journal, Path::new(&path), result);
+            }
+        }
+
+        // With --format json, stdout holds the JSON document alone
+        if let Some(report) = validator.corpus_report() {
+            let report = format!("\n📊 Corpus percentiles\n{}", report);
+            if json { eprintln!("{}", report) } else { println!("{}", report) }
+        }
+
+        if matches.get_flag("profile-rules") {
+            let report = format!("\n⏱️  Rule profile\n{}", profile);
+            if json { eprintln!("{}", report) } else { println!("{}", report) }
+        }
+
+        if let Some(stats_file) = matches.get_one::<String>("stats-file") {
+            let record = stats::StatsRecord::new(target, version, validator.get_stats());
+            if let Err(e) = stats::append_record(Path::new(stats_file), &record) {
+                eprintln!("Error writing stats file: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+
+        // Distinct from invalid recipes: these could not be fully validated
+        if let SchemaStatus::Failed(_) = validator.schema_status() {
+            eprintln!("⚠️  Validated in degraded mode, without the schema");
+            process::exit(EXIT_DEGRADED);
+        }
+        if !all_valid {
+            process::exit(EXIT_INVALID);
+        }
+    }
+
+    /// A zip archive or S3 prefix named as the target, if it is one
+    fn open_source(target: &str) -> Option<Box<dyn sources::RecipeSource>> {
+        if target.starts_with("s3://") {
+            #[cfg(feature = "s3")]
+            return match sources::S3Source::from_url(target) {
+                Ok(source) => Some(Box::new(source)),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(EXIT_ERROR);
+                }
+            };
+            #[cfg(not(feature = "s3"))]
+            {
+                eprintln!("Error: s3:// targets need a build with the s3 feature");
+                process::exit(EXIT_ERROR);
+            }
+        }
+        if target.ends_with(".zip") {
+            #[cfg(feature = "zip")]
+            return Some(Box::new(sources::ZipSource::new(target)));
+            #[cfg(not(feature = "zip"))]
+            {
+                eprintln!("Error: .zip targets need a build with the zip feature");
+                process::exit(EXIT_ERROR);
+            }
+        }
+        None
+    }
+
+    /// Where `--migrate-to` writes an upgraded file without `--write`:
+    /// `pizza.rcip` becomes `pizza.v0.2.rcip`
+    fn migrated_path(path: &Path, to: &str) -> PathBuf {
+        let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = match path.extension() {
+            Some(extension) => format!("{}.v{}.{}", stem, to, extension.to_string_lossy()),
+            None => format!("{}.v{}", stem, to),
+        };
+        path.with_file_name(name)
+    }
+
+    /// The target file, or the files of the target directory the discovery
+    /// flags select
+    fn target_files(matches: &clap::ArgMatches) -> Vec<PathBuf> {
+        let target = Path::new(matches.get_one::<String>("target").unwrap());
+        if !target.is_dir() {
+            return vec![target.to_path_buf()];
+        }
+        let globs = |name: &str| -> Vec<String> { matches.get_many::<String>(name).map(|g| g.cloned().collect()).unwrap_or_default() };
+        let options = discover::DirectoryOptions {
+            recursive: matches.get_flag("recursive"),
+            include: globs("include"),
+            exclude: globs("exclude"),
+        };
+        match discover::find(target, &options) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Error reading {}: {}", target.display(), e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+    }
+
+    fn run_migrate(matches: &clap::ArgMatches, to: &str) {
+        let files = target_files(matches);
+
+        // Nothing is written that the target schema has not accepted
+        let mut validator = RCIPValidator::new(to);
+        if validator.init(None).is_err() || *validator.schema_status() != SchemaStatus::Compiled {
+            eprintln!("❌ No schema for RCIP {}; upgraded files cannot be validated, so none are written", to);
+            process::exit(EXIT_ERROR);
+        }
+
+        let (mut failed, mut invalid) = (false, false);
+        for path in files {
+            let migrated = fs::read_to_string(&path)
+                .map_err(RCIPError::from)
+                .and_then(|content| Ok(serde_json::from_str::<Value>(&content)?))
+                .and_then(|recipe| match recipe.get("rcip_version").and_then(|v| v.as_str()) {
+                    Some(from) => migrate::migrate_recipe(&recipe, from, to),
+                    None => Err(RCIPError::MigrationError("the recipe has no rcip_version".to_string())),
+                });
+            let migrated = match migrated {
+                Ok(migrated) => migrated,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+
+            let output = if matches.get_flag("write") { path.clone() } else { migrated_path(&path, to) };
+            let result = validator.validate(&migrated);
+            if !result.valid {
+                eprintln!("❌ {}: the upgraded recipe is not valid RCIP {}; nothing written", path.display(), to);
+                print!("{}", render_report(&result, &path.display().to_string()));
+                invalid = true;
+                continue;
+            }
+            let text = serde_json::to_string_pretty(&migrated).unwrap() + "\n";
+            if let Err(e) = ingest::IngestIo::write_atomic(&ingest::LocalIo, &output, text.as_bytes()) {
+                eprintln!("❌ Error writing {}: {}", output.display(), e);
+                failed = true;
+                continue;
+            }
+            println!("✅ {} -> {}", path.display(), output.display());
+        }
+
+        if failed {
+            process::exit(EXIT_ERROR);
+        }
+        if invalid {
+            process::exit(EXIT_INVALID);
+        }
+    }
+
+    fn run_canonicalize(matches: &clap::ArgMatches) {
+        let (write, check) = (matches.get_flag("write"), matches.get_flag("check"));
+        let (mut failed, mut unformatted) = (false, false);
+        for path in target_files(matches) {
+            let read = fs::read_to_string(&path).map_err(RCIPError::from).and_then(|text| Ok((serde_json::from_str::<Value>(&text)?, text)));
+            let (recipe, text) = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+            let canonical = canonical::to_canonical_string(&recipe);
+
+            if check {
+                if canonical != text {
+                    println!("{}", path.display());
+                    unformatted = true;
+                }
+            } else if write {
+                if canonical != text {
+                    if let Err(e) = fs::write(&path, &canonical) {
+                        eprintln!("❌ Error writing {}: {}", path.display(), e);
+                        failed = true;
+                        continue;
+                    }
+                    println!("🔧 {}", path.display());
+                }
+            } else {
+                print!("{}", canonical);
+            }
+        }
+
+        if failed {
+            process::exit(EXIT_ERROR);
+        }
+        if unformatted {
+            process::exit(EXIT_INVALID);
+        }
+    }
+
+    fn run_scale(matches: &clap::ArgMatches, factor: f64) {
+        let counts = match matches.get_one::<String>("round-counts").map(|m| m.as_str()) {
+            Some("ceil") => scaling::CountRounding::Ceil,
+            Some("keep") => scaling::CountRounding::Keep,
+            _ => scaling::CountRounding::Round,
+        };
+        let options = scaling::ScaleOptions { counts, ..Default::default() };
+
+        let mut failed = false;
+        for path in target_files(matches) {
+            let scaled = fs::read_to_string(&path)
+                .map_err(RCIPError::from)
+                .and_then(|content| Ok(serde_json::from_str::<Value>(&content)?))
+                .and_then(|recipe| scaling::scale_recipe_with(&recipe, factor, &options));
+            let scaled = match scaled {
+                Ok(scaled) => scaled,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+            for warning in &scaled.warnings {
+                eprintln!("⚠️  {}: {}", path.display(), warning);
+            }
+
+            let text = serde_json::to_string_pretty(&scaled.recipe).unwrap() + "\n";
+            if matches.get_flag("write") {
+                if let Err(e) = fs::write(&path, text) {
+                    eprintln!("❌ Error writing {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+                println!("✅ {} scaled by {}", path.display(), factor);
+            } else {
+                print!("{}", text);
+            }
+        }
+
+        if failed {
+            process::exit(EXIT_ERROR);
+        }
+    }
+
+    fn run_autofix(matches: &clap::ArgMatches) {
+        let files = target_files(matches);
+        let mut fixes = fix::SAFE.to_vec();
+        if matches.get_flag("fix-allergens-empty") {
+            fixes.push(fix::Fix::EmptyAllergens);
+        }
+        let dry_run = matches.get_flag("dry-run");
+
+        let mut validator = RCIPValidator::new(matches.get_one::<String>("version").unwrap());
+        if let Err(e) = validator.init(matches.get_one::<String>("schema").map(Path::new)) {
+            eprintln!("Error initializing validator: {}", e);
+            process::exit(EXIT_ERROR);
+        }
+
+        let (mut failed, mut invalid) = (false, false);
+        for path in files {
+            let recipe = match fs::read_to_string(&path).map_err(RCIPError::from).and_then(|content| Ok(serde_json::from_str::<Value>(&content)?)) {
+                Ok(recipe) => recipe,
+                Err(e) => {
+                    eprintln!("❌ {}: {}", path.display(), e);
+                    failed = true;
+                    continue;
+                }
+            };
+
+            let (fixed, applied) = fix::fix_recipe_with(&recipe, &fixes);
+            if applied.is_empty() {
+                println!("{}: nothing to fix", path.display());
+            } else {
+                let verb = if dry_run { "would fix" } else { "fixed" };
+                println!("🔧 {}: {}", path.display(), verb);
+                for note in applied.iter().flat_map(|a| &a.notes) {
+                    println!("   {}", note);
+                }
+                if !dry_run {
+                    if let Err(e) = fs::write(&path, serde_json::to_string_pretty(&fixed).unwrap() + "\n") {
+                        eprintln!("❌ Error writing {}: {}", path.display(), e);
+                        failed = true;
+                        continue;
+                    }
+                }
+            }
+
+            // What is left is reported as usual
+            let result = validator.validate(&fixed);
+            if !result.valid {
+                print!("{}", render_report(&result, &path.display().to_string()));
+                invalid = true;
+            }
+        }
+
+        if failed {
+            process::exit(EXIT_ERROR);
+        }
+        if invalid {
+            process::exit(EXIT_INVALID);
+        }
+    }
+
+    fn run_project(path: &Path) {
+        let manifest = match project::Manifest::from_file(path) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+        };
+        match manifest.validate() {
+            Ok(report) => {
+                print!("\n{}", report);
+                if !report.valid() {
+                    process::exit(EXIT_INVALID);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error validating project: {}", e);
+                process::exit(EXIT_ERROR);
+            }
+        }
+    }
+
+    fn run_stats(matches: &clap::ArgMatches) {
+        if let Some(("summarize", sub)) = matches.subcommand() {
+            let file = sub.get_one::<String>("file").unwrap();
+            match stats::summarize(Path::new(file)) {
+                Ok(summary) => print!("{}", summary),
+                Err(e) => {
+                    eprintln!("Error reading stats file: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "journal")]
+    fn open_journal(path: &Path) -> journal::Journal {
+        match journal::Journal::open(path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                eprintln!("Error opening journal {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(feature = "journal")]
+    fn record_in_journal(journal: &journal::Journal, path: &Path, result: ValidationResult) {
+        let recorded = journal::FileValidation::validated(path, result).and_then(|validation| journal.record(&validation));
+        if let Err(e) = recorded {
+            eprintln!("Error writing journal {}: {}", journal.path().display(), e);
+            process::exit(1);
+        }
+    }
+
+    /// Without the journal feature there is no journal to write to
+    #[cfg(not(feature = "journal"))]
+    fn open_journal(_path: &Path) -> std::convert::Infallible {
+        eprintln!("Error: --journal needs a build with the journal feature");
+        process::exit(1);
+    }
+
+    #[cfg(not(feature = "journal"))]
+    fn record_in_journal(journal: &std::convert::Infallible, _path: &Path, _result: ValidationResult) {
+        match *journal {}
+    }
+
+    #[cfg(feature = "product-db")]
+    fn run_product_db(matches: &clap::ArgMatches) {
+        if let Some(("build", sub)) = matches.subcommand() {
+            let (export, output) = (sub.get_one::<String>("export").unwrap(), sub.get_one::<String>("output").unwrap());
+            match resolve::build_snapshot(Path::new(export), Path::new(output)) {
+                Ok(report) => print!("✅ {}", report),
+                Err(e) => {
+                    eprintln!("Error building {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+
+    #[cfg(not(feature = "product-db"))]
+    fn run_product_db(_matches: &clap::ArgMatches) {
+        eprintln!("Error: product snapshots need a build with the product-db feature");
+        process::exit(1);
+    }
+
+    /// The snapshot given with --product-db, if any
+    #[cfg(feature = "product-db")]
+    fn product_db(matches: &clap::ArgMatches) -> Option<Box<dyn resolve::Resolver>> {
+        let path = matches.get_one::<String>("product-db")?;
+        match resolve::ProductDb::open(Path::new(path)) {
+            Ok(db) => Some(Box::new(db)),
+            Err(e) => {
+                eprintln!("Error loading product snapshot {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "product-db"))]
+    fn product_db(matches: &clap::ArgMatches) -> Option<Box<dyn resolve::Resolver>> {
+        matches.get_one::<String>("product-db")?;
+        eprintln!("Error: --product-db needs a build with the product-db feature");
+        process::exit(1);
+    }
+
+    fn run_journal(matches: &clap::ArgMatches) {
+        if let Some(("verify", sub)) = matches.subcommand() {
+            verify_journal(Path::new(sub.get_one::<String>("file").unwrap()));
+        }
+    }
+
+    #[cfg(feature = "journal")]
+    fn verify_journal(path: &Path) {
+        if !path.is_file() {
+            eprintln!("Error: {} is not a file", path.display());
+            process::exit(1);
+        }
+        let journal = match journal::Journal::open(path) {
+            Ok(journal) => journal,
+            Err(e) => {
+                eprintln!("Error opening journal {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        };
+        match journal.verify() {
+            Ok(summary) => print!("✅ {}", summary),
+            Err(corruption) => {
+                println!("❌ {}", corruption);
+                process::exit(1);
+            }
+        }
+    }
+
+    #[cfg(not(feature = "journal"))]
+    fn verify_journal(_path: &Path) {
+        eprintln!("Error: journals need a build with the journal feature");
+        process::exit(1);
+    }
+
+    fn run_verify_claims(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let mut resolvers = Resolvers::new();
+        if let Some(db) = product_db(matches) {
+            resolvers.push(db);
+        }
+        if matches.get_flag("open-food-facts") {
+            resolvers.push(open_food_facts());
+        }
+        let report = claims::verify(&recipe, Some(&resolvers));
+
+        if matches.get_one::<String>("format").map(|f| f.as_str()) == Some("json") {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        } else {
+            print!("{}", report);
+        }
+
+        if !report.verified() {
+            process::exit(1);
+        }
+    }
+
+    #[cfg(feature = "http-checks")]
+    fn open_food_facts() -> Box<dyn resolve::Resolver> {
+        Box::new(resolve::OpenFoodFacts::new())
+    }
+
+    #[cfg(not(feature = "http-checks"))]
+    fn open_food_facts() -> Box<dyn resolve::Resolver> {
+        eprintln!("Error: --open-food-facts needs the http-checks feature");
+        process::exit(1);
+    }
+
+    fn run_compat(matches: &clap::ArgMatches) {
+        let recipe_path = matches.get_one::<String>("recipe").unwrap();
+        let device_path = matches.get_one::<String>("device").unwrap();
+
+        let manifest = match compat::DeviceManifest::from_file(Path::new(device_path)) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Error loading device manifest {}: {}", device_path, e);
+                process::exit(1);
+            }
+        };
+
+        let recipe = read_recipe(recipe_path);
+        let report = compat::check(&recipe, &manifest);
+
+        if matches.get_one::<String>("format").map(|f| f.as_str()) == Some("json") {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        } else {
+            print!("{}", report);
+        }
+
+        if report.verdict == compat::Verdict::Incompatible {
+            process::exit(1);
+        }
+    }
+
+    fn run_label(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let format = matches.get_one::<String>("format").unwrap();
+
+        if let Some(id) = matches.get_one::<String>("market") {
+            match markets::market(id) {
+                Ok(market) if !market.accepts_label(format) => {
+                    eprintln!("Error: {} labels do not apply in market {} ({})", format, market.id, market.name);
+                    process::exit(1);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
+
+        if format == "nutrition-facts" {
+            let panel = match labels::nutrition_facts(&recipe) {
+                Ok(panel) => panel,
+                Err(e) => {
+                    eprintln!("Error building nutrition facts: {}", e);
+                    process::exit(1);
+                }
+            };
+
+            if matches.get_flag("json") {
+                println!("{}", serde_json::to_string_pretty(&panel).unwrap());
+            } else if matches.get_flag("html") {
+                print!("{}", panel.to_html());
+            } else {
+                print!("{}", panel.to_text());
+            }
+            return;
+        }
+
+        let policy = matches.get_one::<allergens::ProvenancePolicy>("allergen-provenance").cloned().unwrap_or_default();
+        let label = labels::eu_fic_with(&recipe, &policy);
+
+        if matches.get_flag("json") {
+            println!("{}", serde_json::to_string_pretty(&label).unwrap());
+        } else {
+            println!("{}", label.text);
+        }
+    }
+
+    fn run_allergens(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let entries = allergens::provenance(&recipe);
+        if matches.get_flag("json") {
+            println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+        } else {
+            for entry in &entries {
+                println!("{}", entry);
+            }
+        }
+    }
+
+    fn run_scrub(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let seed = *matches.get_one::<u64>("seed").unwrap();
+        let scrubbed = serde_json::to_string_pretty(&fixtures::scrub(&recipe, seed)).unwrap();
+
+        match matches.get_one::<String>("output") {
+            Some(output) => {
+                if let Err(e) = fs::write(output, scrubbed + "\n") {
+                    eprintln!("Error writing {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+            None => println!("{}", scrubbed),
+        }
+    }
+
+    fn run_fix(matches: &clap::ArgMatches) {
+        let mut recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let mut fixes: Vec<fix::Fix> = [
+            ("migrate-deprecated", fix::Fix::MigrateDeprecated),
+            ("normalize-numbers", fix::Fix::NormalizeNumbers),
+            ("normalize-terms", fix::Fix::NormalizeTerms),
+            ("normalize-units", fix::Fix::NormalizeUnits),
+            ("lowercase-ids", fix::Fix::LowercaseIds),
+            ("round-amounts", fix::Fix::RoundAmounts),
+            ("infer-actions", fix::Fix::InferActions),
+            ("empty-allergens", fix::Fix::EmptyAllergens),
+            ("add-prep-phase", fix::Fix::AddPrepPhase),
+            ("renumber-ids", fix::Fix::RenumberIds),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| matches.get_flag(flag))
+        .map(|(_, fix)| fix)
+        .collect();
+        for order in matches.get_many::<String>("sort-ingredients").into_iter().flatten() {
+            fixes.push(match order.as_str() {
+                "amount" => fix::Fix::SortByAmount,
+                "name" => fix::Fix::SortByName,
+                _ => fix::Fix::SortByUsage,
+            });
+        }
+        if fixes.is_empty() {
+            eprintln!("Error: nothing to fix; pass one of the fix flags (see fix --help)");
+            process::exit(1);
+        }
+
+        let options = fix::FixOptions {
+            inference: infer::InferenceOptions {
+                threshold: *matches.get_one::<f64>("min-confidence").unwrap(),
+                ..infer::InferenceOptions::default()
             },
-            "ingredients": [],
-            "steps": []
-        });
+            ..fix::FixOptions::default()
+        };
+        eprint!("{}", fix::run(&mut recipe, &fixes, &options));
+
+        let fixed = serde_json::to_string_pretty(&recipe).unwrap();
+        match matches.get_one::<String>("output") {
+            Some(output) => {
+                if let Err(e) = fs::write(output, fixed + "\n") {
+                    eprintln!("Error writing {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+            None => println!("{}", fixed),
+        }
+    }
+
+    fn run_diff(matches: &clap::ArgMatches) {
+        let recipe = |name: &str| read_recipe(matches.get_one::<String>(name).unwrap());
+        let diff = diff::diff_recipes(&recipe("old"), &recipe("new"));
+        if matches.get_one::<String>("format").map(|f| f.as_str()) == Some("json") {
+            println!("{}", serde_json::to_string_pretty(&diff.to_json()).unwrap());
+        } else {
+            print!("{}", diff);
+        }
+    }
+
+    fn run_merge(matches: &clap::ArgMatches) {
+        let recipe = |name: &str| read_recipe(matches.get_one::<String>(name).unwrap());
+        let (merged, report) = match merge::three_way(&recipe("base"), &recipe("ours"), &recipe("theirs")) {
+            Ok(merged) => merged,
+            Err(conflicts) => {
+                eprint!("Error: {}", conflicts);
+                process::exit(1);
+            }
+        };
+        eprint!("{}", report);
+
+        let validator = match warm::global_validator("0.1") {
+            Ok(validator) => validator,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        let result = validator.validate(&merged);
+        if !result.valid {
+            eprintln!("Error: the merged recipe is not valid");
+            for error in &result.errors {
+                eprintln!("  {}", error);
+            }
+            process::exit(1);
+        }
 
-        let result = validator.validate_recipe(&recipe);
-        assert!(!result.valid, "Recipe with invalid ID should not be valid");
-        assert!(result.errors.iter().any(|e| e.contains("Invalid recipe ID format")));
+        let content = serde_json::to_string_pretty(&merged).unwrap() + "\n";
+        match matches.get_one::<String>("output") {
+            Some(output) => {
+                if let Err(e) = fs::write(output, content) {
+                    eprintln!("Error writing {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+            None => print!("{}", content),
+        }
     }
 
-    #[test]
-    fn test_missing_allergens() {
-        let mut validator = RCIPValidator::new("0.1");
-
-        let recipe = json!({
-            "rcip_version": "0.1",
-            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
-            "meta": {
-                "name": "Test Recipe",
-                "author": "Test Author",
-                "created_date": "2025-01-15T10:00:00Z"
-            },
-            "ingredients": [
-                {
-                    "id": "ing-0001",
-                    "name": "test ingredient",
-                    "human_amount": "1 piece",
-                    "machine_amount": {
-                        "value": 1,
-                        "unit": "pcs"
-                    }
-                    // Missing allergens field
+    fn run_export(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let format = matches.get_one::<String>("format").unwrap();
+        let content = if format == "ics" {
+            let start = match matches.get_one::<String>("start").map(|s| export::parse_start(s)) {
+                Some(Ok(start)) => start,
+                Some(Err(e)) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
                 }
-            ],
-            "steps": []
-        });
+                None => {
+                    eprintln!("Error: --start is required for ics exports");
+                    process::exit(1);
+                }
+            };
+            export::to_ics(&recipe, start)
+        } else if ["text", "md", "html"].contains(&format.as_str()) {
+            let locale = matches.get_one::<String>("lang").map(String::as_str).unwrap_or("en");
+            match format.as_str() {
+                "text" => export::to_text(&recipe, locale),
+                "md" => export::to_markdown(&recipe, locale),
+                _ => export::to_html(&recipe, locale),
+            }
+        } else {
+            let mut options = export::CardOptions {
+                locale: matches.get_one::<String>("lang").cloned(),
+                ..export::CardOptions::default()
+            };
+            if let Some(budget) = matches.get_one::<usize>("text-budget") {
+                options.text_budget = *budget;
+            }
+            let cards = export::to_step_cards(&recipe, &options);
+            eprint!("{}", export::CardReport::new(&cards));
+            if format == "cards-md" {
+                export::cards_to_markdown(&cards)
+            } else {
+                serde_json::to_string_pretty(&cards).unwrap() + "\n"
+            }
+        };
 
-        let result = validator.validate_recipe(&recipe);
-        assert!(!result.valid, "Recipe with missing allergens should not be valid");
-        assert!(result.errors.iter().any(|e| e.contains("Missing required allergens field")));
+        match matches.get_one::<String>("output") {
+            Some(output) => {
+                if let Err(e) = fs::write(output, content) {
+                    eprintln!("Error writing {}: {}", output, e);
+                    process::exit(1);
+                }
+            }
+            None => print!("{}", content),
+        }
     }
 
-    #[test]
-    fn test_warnings() {
-        let mut validator = RCIPValidator::new("0.1");
+    fn run_shots(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let mut options = shots::ShotOptions::default();
+        if let Some(pattern) = matches.get_one::<String>("pattern") {
+            options.pattern = pattern.clone();
+        }
+        let list = shots::shot_list(&recipe, &options);
 
-        let recipe = json!({
-            "rcip_version": "0.1",
-            "id": "rcip-123e4567-e89b-12d3-a456-426614174000",
-            "meta": {
-                "name": "Test Recipe",
-                "author": "Test Author",
-                "created_date": "2025-01-15T10:00:00Z"
-                // Missing recommended fields
-            },
-            "ingredients": [
-                {
-                    "id": "ing-0001",
-                    "name": "test ingredient",
-                    "human_amount": "1 piece",
-                    "machine_amount": {
-                        "value": 1,
-                        "unit": "pcs"
-                    },
-                    "allergens": []
+        let report = match matches.get_one::<String>("assets") {
+            Some(dir) => match shots::check_assets(&list, Path::new(dir), &options) {
+                Ok(report) => Some(report),
+                Err(e) => {
+                    eprintln!("Error reading {}: {}", dir, e);
+                    process::exit(1);
                 }
-            ],
-            "steps": [
-                {
-                    "step_id": "s-01",
-                    "human_text": "Test step",
-                    "action": "mix"
+            },
+            None => None,
+        };
+
+        if matches.get_flag("json") {
+            println!("{}", serde_json::to_string_pretty(&json!({"shots": list, "assets": report})).unwrap());
+        } else {
+            print!("{}", list);
+            if let Some(report) = &report {
+                print!("\n{}", report);
+            }
+        }
+
+        if report.map(|r| !r.is_complete()).unwrap_or(false) {
+            process::exit(1);
+        }
+    }
+
+    fn run_import(matches: &clap::ArgMatches) {
+        let export = read_recipe(matches.get_one::<String>("export").unwrap());
+        let out_dir = Path::new(matches.get_one::<String>("out-dir").unwrap());
+        let from = matches.get_one::<String>("from").unwrap();
+
+        if let Err(e) = fs::create_dir_all(out_dir) {
+            eprintln!("Error creating {}: {}", out_dir.display(), e);
+            process::exit(1);
+        }
+
+        let ids = match matches.get_one::<String>("id-namespace") {
+            Some(namespace) => Some(ids::IdStrategy::deterministic(namespace.as_str())),
+            None if matches.get_flag("random-ids") => Some(ids::IdStrategy::Random),
+            None => None,
+        };
+
+        let mut written = HashSet::new();
+        for entry in import::export_entries(&export) {
+            let (recipe, report) = match (from.as_str(), &ids) {
+                ("mealie", Some(ids)) => import::from_mealie_with(entry, ids),
+                ("mealie", None) => import::from_mealie(entry),
+                (_, Some(ids)) => import::from_paprika_with(entry, ids),
+                (_, None) => import::from_paprika(entry),
+            };
+
+            let base = import::slug(&report.name);
+            let mut file_name = format!("{}.json", base);
+            let mut n = 1;
+            while !written.insert(file_name.clone()) {
+                n += 1;
+                file_name = format!("{}-{}.json", base, n);
+            }
+
+            let path = out_dir.join(&file_name);
+            let content = serde_json::to_string_pretty(&recipe).unwrap() + "\n";
+            if let Err(e) = fs::write(&path, content) {
+                eprintln!("Error writing {}: {}", path.display(), e);
+                process::exit(1);
+            }
+            print!("{} -> {}\n{}", report.name, path.display(), report);
+        }
+    }
+
+    fn run_init(matches: &clap::ArgMatches) {
+        fn fail(message: String) -> ! {
+            eprintln!("Error: {}", message);
+            process::exit(1);
+        }
+
+        let mut answers = wizard::Answers {
+            name: matches.get_one::<String>("name").cloned(),
+            author: matches.get_one::<String>("author").cloned(),
+            servings: matches.get_one::<u32>("servings").copied(),
+            ..wizard::Answers::default()
+        };
+        for spec in matches.get_many::<String>("ingredient").into_iter().flatten() {
+            answers.ingredients.push(wizard::parse_ingredient(spec).unwrap_or_else(|e| fail(e.to_string())));
+        }
+        for spec in matches.get_many::<String>("step").into_iter().flatten() {
+            answers.steps.push(wizard::parse_step(spec).unwrap_or_else(|e| fail(e.to_string())));
+        }
+
+        #[cfg(feature = "cli-interactive")]
+        {
+            use std::io::IsTerminal;
+            if !matches.get_flag("no-input") && std::io::stdin().is_terminal() {
+                if let Err(e) = wizard::prompt(&mut answers) {
+                    fail(e.to_string());
                 }
-            ]
+            }
+        }
+
+        let recipe = wizard::build(&answers).unwrap_or_else(|e| fail(e.to_string()));
+        let out = Path::new(matches.get_one::<String>("out").unwrap());
+        if out.exists() && !matches.get_flag("force") {
+            fail(format!("{} already exists; pass --force to overwrite it", out.display()));
+        }
+        if let Err(e) = fs::write(out, serde_json::to_string_pretty(&recipe).unwrap() + "\n") {
+            fail(format!("cannot write {}: {}", out.display(), e));
+        }
+        println!("Wrote {}", out.display());
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.set_reporter(Box::new(reporter::ConsoleReporter));
+        if let Err(e) = validator.init(None) {
+            fail(format!("recipe written but not validated: {}", e));
+        }
+        match validator.validate_file(out) {
+            Ok(result) if result.valid => {}
+            Ok(_) => process::exit(1),
+            Err(e) => fail(e.to_string()),
+        }
+    }
+
+    fn run_search(matches: &clap::ArgMatches) {
+        use catalog::{CatalogIndex, NutritionMetric, Query};
+
+        let dir = Path::new(matches.get_one::<String>("dir").unwrap());
+        let index = match CatalogIndex::build(dir) {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("Error reading catalog {}: {}", dir.display(), e);
+                process::exit(1);
+            }
+        };
+        for (path, reason) in &index.unreadable {
+            eprintln!("⚠️  Skipped {}: {}", path.display(), reason);
+        }
+
+        let mut query = Query::new();
+        if let Some(name) = matches.get_one::<String>("name") {
+            query = query.name_contains(name);
+        }
+        for allergen in matches.get_many::<String>("exclude-allergen").into_iter().flatten() {
+            query = query.exclude_allergen(allergen);
+        }
+        for label in matches.get_many::<String>("diet").into_iter().flatten() {
+            query = query.require_diet(label);
+        }
+        if let Some(kcal) = matches.get_one::<f64>("max-calories-per-serving") {
+            query = query.max(NutritionMetric::CaloriesPerServing, *kcal);
+        }
+        if let Some(mg) = matches.get_one::<f64>("max-sodium-per-serving") {
+            query = query.max(NutritionMetric::SodiumPerServing, *mg);
+        }
+        if let Some(g) = matches.get_one::<f64>("min-protein-per-serving") {
+            query = query.min(NutritionMetric::ProteinPerServing, *g);
+        }
+        if let Some(score) = matches.get_one::<f64>("min-quality") {
+            query = query.min(NutritionMetric::Quality, *score);
+        }
+        let sort = matches.get_one::<String>("sort").map(|spec| {
+            catalog::parse_sort(spec).unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            })
         });
+        if let Some((metric, direction)) = sort {
+            query = query.sort_by(metric, direction);
+        }
+        if let Some(limit) = matches.get_one::<usize>("limit") {
+            query = query.limit(*limit);
+        }
 
-        let result = validator.validate_recipe(&recipe);
-        assert!(!result.warnings.is_empty(), "Should have warnings for missing recommended fields");
-        assert!(result.warnings.iter().any(|w| w.contains("meta.description")));
-        assert!(result.warnings.iter().any(|w| w.contains("meta.servings")));
+        let results = index.search(&query);
+        for (rank, entry) in results.entries.iter().enumerate() {
+            let coverage = entry.nutrition.as_ref().map(|n| n.coverage).filter(|c| *c < 1.0);
+            let value = sort
+                .and_then(|(metric, _)| {
+                    let estimate = entry.estimate(metric)?;
+                    Some(match coverage.filter(|_| entry.metric(metric) != Some(estimate)) {
+                        Some(coverage) => format!(" (~{:.1} {}, {:.0}% covered)", estimate, metric.unit(), coverage * 100.0),
+                        None => format!(" ({:.1} {})", estimate, metric.unit()),
+                    })
+                })
+                .unwrap_or_default();
+            println!("{:>3}. {}{} - {}", rank + 1, entry.name, value, entry.path.display());
+        }
+        if results.skipped_without_nutrition > 0 {
+            println!("{} recipe(s) skipped for lacking nutrition data", results.skipped_without_nutrition);
+        }
     }
-}
 
-// CLI binary implementation (src/main.rs)
-pub mod cli {
-    use super::*;
-    use clap::{Arg, Command};
-    use std::process;
+    fn run_similar(matches: &clap::ArgMatches) {
+        use catalog::{CatalogIndex, SimilarOptions};
 
-    pub fn run() {
-        let matches = Command::new("RCIP Validator")
-            .version("1.0.0")
-            .author("Alexey Kozlov")
-            .about("Validates RCIP format recipes")
-            .arg(
-                Arg::new("target")
-                    .help("Recipe file or directory to validate")
-                    .required(true)
-                    .index(1),
-            )
-            .arg(
-                Arg::new("version")
-                    .short('v')
-                    .long("version")
-                    .value_name("VERSION")
-                    .help("RCIP schema version")
-                    .default_value("0.1"),
-            )
-            .arg(
-                Arg::new("schema")
-                    .short('s')
-                    .long("schema")
-                    .value_name("PATH")
-                    .help("Path to custom schema file"),
-            )
-            .get_matches();
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let dir = Path::new(matches.get_one::<String>("in").unwrap());
+        let index = match CatalogIndex::build(dir) {
+            Ok(index) => index,
+            Err(e) => {
+                eprintln!("Error reading catalog {}: {}", dir.display(), e);
+                process::exit(1);
+            }
+        };
+        for (path, reason) in &index.unreadable {
+            eprintln!("⚠️  Skipped {}: {}", path.display(), reason);
+        }
 
-        let target = matches.get_one::<String>("target").unwrap();
-        let version = matches.get_one::<String>("version").unwrap();
-        let schema_path = matches.get_one::<String>("schema").map(|s| Path::new(s));
+        let opts = SimilarOptions {
+            exclude_allergens: matches.get_many::<String>("exclude-allergen").into_iter().flatten().cloned().collect(),
+            diet_labels: matches.get_many::<String>("diet").into_iter().flatten().cloned().collect(),
+            top: *matches.get_one::<usize>("top").unwrap(),
+        };
+        let results = catalog::similar_to(&index, &recipe, &opts);
+        if results.is_empty() {
+            println!("No similar recipes found");
+        }
+        for (rank, result) in results.iter().enumerate() {
+            let shared: Vec<String> =
+                result.shared.iter().map(|(key, share)| format!("{} {:.0}%", key, share * 100.0)).collect();
+            println!("{:>3}. {} ({:.2}) - {}", rank + 1, result.entry.name, result.score, result.entry.path.display());
+            println!("     shared: {}", shared.join(", "));
+        }
+    }
 
-        let mut validator = RCIPValidator::new(version);
+    fn run_simulate(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        match simulate::run(&recipe) {
+            Ok(trace) => print!("{}", trace),
+            Err(e) => {
+                eprintln!("Simulation failed: {}", e);
+                process::exit(1);
+            }
+        }
+    }
 
-        if let Err(e) = validator.init(schema_path) {
-            eprintln!("Error initializing validator: {}", e);
+    fn run_energy(matches: &clap::ArgMatches) {
+        let recipe = read_recipe(matches.get_one::<String>("recipe").unwrap());
+        let profile = match matches.get_one::<String>("profile") {
+            Some(path) => match analysis::EnergyProfile::from_file(Path::new(path)) {
+                Ok(profile) => profile,
+                Err(e) => {
+                    eprintln!("Error loading energy profile {}: {}", path, e);
+                    process::exit(1);
+                }
+            },
+            None => analysis::EnergyProfile::default(),
+        };
+        let report = analysis::energy_estimate(&recipe, &profile);
+
+        if matches.get_one::<String>("format").map(|f| f.as_str()) == Some("json") {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        } else {
+            print!("{}", report);
+        }
+    }
+
+    fn run_certify(matches: &clap::ArgMatches) {
+        let mut files = Vec::new();
+        for path in matches.get_many::<String>("paths").unwrap().map(PathBuf::from) {
+            match fs::read_dir(&path) {
+                Ok(entries) => {
+                    let mut recipes: Vec<PathBuf> = entries
+                        .filter_map(|e| e.ok().map(|e| e.path()))
+                        .filter(|p| p.extension().map(|e| e == "rcip" || e == "json").unwrap_or(false))
+                        .collect();
+                    recipes.sort();
+                    files.extend(recipes);
+                }
+                Err(_) => files.push(path),
+            }
+        }
+
+        let (mut failed, mut unreadable) = (0, 0);
+        for path in &files {
+            let parsed = fs::read_to_string(path)
+                .map_err(RCIPError::from)
+                .and_then(|content| serde_json::from_str::<Value>(&content).map_err(RCIPError::from));
+            let recipe = match parsed {
+                Ok(recipe) => recipe,
+                Err(e) => {
+                    eprintln!("Error reading recipe {}: {}", path.display(), e);
+                    unreadable += 1;
+                    continue;
+                }
+            };
+            let report = certify::check(&recipe);
+            println!("\n📄 {}", path.display());
+            print!("{}", report);
+            if !report.certified() {
+                failed += 1;
+            }
+        }
+
+        println!("\n{} of {} recipe(s) certified", files.len() - failed - unreadable, files.len());
+        if unreadable > 0 {
+            process::exit(2);
+        }
+        if failed > 0 {
             process::exit(1);
         }
+    }
 
-        let target_path = Path::new(target);
+    fn run_ingest(matches: &clap::ArgMatches) {
+        let validator = match warm::global_validator("0.1") {
+            Ok(validator) => validator,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        let mut config = ingest::IngestConfig::new(matches.get_one::<String>("incoming").unwrap(), validator);
+        if let Some(out) = matches.get_one::<String>("out") {
+            config.output = PathBuf::from(out);
+        }
+        config.retries = *matches.get_one::<u32>("retries").unwrap();
+        config.retry_delay = std::time::Duration::from_millis(*matches.get_one::<u64>("retry-delay-ms").unwrap());
+        config.manifest = matches.get_one::<String>("manifest").map(PathBuf::from);
+        config.dry_run = matches.get_flag("dry-run");
+
+        let report = ingest::run(config);
+        print!("{}", report);
+        if !report.clean() {
+            process::exit(2);
+        }
+    }
+
+    fn run_release_notes(matches: &clap::ArgMatches) {
+        let dir = Path::new(matches.get_one::<String>("dir").unwrap());
+        let from = matches.get_one::<String>("from").unwrap();
+        let to = matches.get_one::<String>("to").unwrap();
+        match release_notes::release_notes(dir, from, to) {
+            Ok(notes) => print!("{}", notes.to_markdown()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    fn run_self_check(matches: &clap::ArgMatches) {
+        let root = Path::new(matches.get_one::<String>("root").unwrap());
+        let problems = self_check::run(root);
+        if problems.is_empty() {
+            println!("✅ Self-check passed");
+            return;
+        }
+        for problem in &problems {
+            println!("❌ {}", problem);
+        }
+        println!("{} problem(s) found", problems.len());
+        process::exit(1);
+    }
 
-        if target_path.is_dir() {
-            match validator.validate_directory(target_path) {
-                Ok(_) => {},
+    fn run_conformance(matches: &clap::ArgMatches) {
+        let dir = Path::new(matches.get_one::<String>("dir").unwrap());
+        if matches.get_flag("update") {
+            match conformance::update(dir) {
+                Ok(changed) => {
+                    for name in &changed {
+                        println!("updated {}", name);
+                    }
+                    println!("{} expectation(s) changed", changed.len());
+                }
                 Err(e) => {
-                    eprintln!("Error validating directory: {}", e);
+                    eprintln!("Error: {}", e);
                     process::exit(1);
                 }
             }
-        } else if target_path.is_file() {
-            match validator.validate_file(target_path) {
-                Ok(_) => {},
+            return;
+        }
+
+        let report = match matches.get_one::<String>("actual") {
+            Some(path) => {
+                let actual = fs::read_to_string(path)
+                    .map_err(RCIPError::from)
+                    .and_then(|content| Ok(serde_json::from_str(&content)?));
+                match actual {
+                    Ok(actual) => conformance::compare(dir, &actual),
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", path, e);
+                        process::exit(1);
+                    }
+                }
+            }
+            None => conformance::run(dir),
+        };
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+
+        for (category, (conforming, total)) in report.by_category() {
+            let mark = if conforming == total { "✅" } else { "❌" };
+            println!("{} {:<16} {}/{}", mark, if category.is_empty() { "(root)" } else { category }, conforming, total);
+        }
+        print!("{}", report);
+        if !report.is_conformant() {
+            process::exit(1);
+        }
+    }
+
+    fn run_generate(matches: &clap::ArgMatches) {
+        if let Some(("corpus", sub)) = matches.subcommand() {
+            let out = Path::new(sub.get_one::<String>("out").unwrap());
+            let count = *sub.get_one::<usize>("count").unwrap();
+            let seed = *sub.get_one::<u64>("seed").unwrap();
+            let profile = generate::CorpusProfile {
+                error_rate: sub.get_one::<f64>("errors").unwrap().clamp(0.0, 1.0),
+                ids: match sub.get_one::<String>("id-namespace") {
+                    Some(namespace) => ids::IdStrategy::deterministic(namespace.as_str()),
+                    None => ids::IdStrategy::Random,
+                },
+                ..generate::CorpusProfile::default()
+            };
+
+            match generate::corpus(out, count, seed, profile) {
+                Ok(entries) => {
+                    let defective = entries.iter().filter(|e| e.defect.is_some()).count();
+                    println!("Wrote {} recipes ({} with defects) to {}", entries.len(), defective, out.display());
+                }
                 Err(e) => {
-                    eprintln!("Error validating file: {}", e);
+                    eprintln!("Error writing corpus: {}", e);
                     process::exit(1);
                 }
             }
-        } else {
-            eprintln!("Error: {} is not a valid file or directory", target);
-            process::exit(1);
         }
     }
-}
\ No newline at end of file
+
+    /// Read and parse a recipe file, exiting on failure
+    fn read_recipe(path: &str) -> Value {
+        let parsed = fs::read_to_string(path)
+            .map_err(RCIPError::from)
+            .and_then(|content| serde_json::from_str(&content).map_err(RCIPError::from));
+
+        match parsed {
+            Ok(recipe) => recipe,
+            Err(e) => {
+                eprintln!("Error reading recipe {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+}
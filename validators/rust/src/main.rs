@@ -1,5 +1,13 @@
+#[cfg(not(feature = "no-fs"))]
 use rcip_validator::cli;
 
+#[cfg(not(feature = "no-fs"))]
 fn main() {
     cli::run();
 }
+
+#[cfg(feature = "no-fs")]
+fn main() {
+    eprintln!("Error: the command line validator reads files and is not available with the no-fs feature");
+    std::process::exit(1);
+}
@@ -0,0 +1,243 @@
+// Market rule packs
+//
+// Labeling law differs between markets: which allergens must be declared,
+// which are only recommended, whether the specific nut, cereal, fish or
+// crustacean has to be named, and whether "may contain" statements are
+// accepted at all. Each market is a `Market` record; the rule layer below only
+// reads those records, so adding a market means adding data. The allergen
+// vocabulary stays the superset in `allergens::VOCABULARY`: an allergen a
+// market does not list is simply not regulated there. Findings name the
+// market they come from, so several markets can be checked in one run.
+// An ingredient whose name suggests a mandatory allergen it does not declare
+// is a warning, as names can mislead; advisory ones are only noted.
+// Advisory allergens count the contained allergens whose provenance the
+// validator's `allergens::ProvenancePolicy` considers.
+
+use crate::labels::{CEREAL_KEYWORDS, NUT_KEYWORDS};
+use crate::{allergens, codes, ValidationResult};
+use serde_json::Value;
+use std::fmt;
+
+/// Labeling rules of one market
+#[derive(Debug, PartialEq)]
+pub struct Market {
+    pub id: &'static str,
+    pub name: &'static str,
+    /// Allergens that must be declared
+    pub mandatory: &'static [&'static str],
+    /// Allergens whose declaration is recommended
+    pub advisory: &'static [&'static str],
+    /// Allergens whose specific source (nut, cereal, species) must be named
+    pub named_sources: &'static [&'static str],
+    /// Whether precautionary "may contain" statements are accepted
+    pub may_contain_allowed: bool,
+    /// `label --format` values applicable in this market
+    pub labels: &'static [&'static str],
+}
+
+/// Supported markets
+pub const MARKETS: &[Market] = &[
+    // Regulation (EU) 1169/2011, Annex II
+    Market {
+        id: "EU",
+        name: "European Union",
+        mandatory: &[
+            "wheat", "gluten", "shellfish", "eggs", "fish", "peanuts", "soybeans", "milk", "lactose",
+            "tree-nuts", "celery", "mustard", "sesame", "sulphites", "lupins", "molluscs",
+        ],
+        advisory: &[],
+        named_sources: &["gluten", "tree-nuts"],
+        may_contain_allowed: true,
+        labels: &["eu-fic"],
+    },
+    // FALCPA and the FASTER Act major food allergens
+    Market {
+        id: "US",
+        name: "United States",
+        mandatory: &["milk", "lactose", "eggs", "fish", "shellfish", "tree-nuts", "peanuts", "wheat", "soybeans", "sesame"],
+        advisory: &["gluten", "molluscs", "sulphites"],
+        named_sources: &["fish", "shellfish", "tree-nuts"],
+        may_contain_allowed: true,
+        labels: &["nutrition-facts"],
+    },
+    // Food Labeling Standards: specified and recommended raw materials
+    Market {
+        id: "JP",
+        name: "Japan",
+        mandatory: &["eggs", "milk", "lactose", "wheat", "peanuts", "shellfish", "tree-nuts"],
+        advisory: &["soybeans", "sesame", "fish", "molluscs"],
+        named_sources: &["shellfish"],
+        may_contain_allowed: false,
+        labels: &[],
+    },
+];
+
+const FISH_KEYWORDS: &[&str] = &[
+    "anchov", "bass", "cod", "haddock", "halibut", "herring", "mackerel", "salmon", "sardine", "tilapia",
+    "trout", "tuna",
+];
+
+const CRUSTACEAN_KEYWORDS: &[&str] = &["crab", "crayfish", "langoustine", "lobster", "prawn", "shrimp"];
+
+/// Selecting a market that does not exist
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnknownMarket(pub String);
+
+impl fmt::Display for UnknownMarket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let supported: Vec<&str> = MARKETS.iter().map(|m| m.id).collect();
+        write!(f, "unknown market '{}'; supported: {}", self.0, supported.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownMarket {}
+
+/// Look up a market by id, case-insensitively
+pub fn market(id: &str) -> Result<&'static Market, UnknownMarket> {
+    MARKETS.iter().find(|m| m.id.eq_ignore_ascii_case(id)).ok_or_else(|| UnknownMarket(id.to_string()))
+}
+
+impl Market {
+    /// Whether `label --format` output is meaningful in this market
+    pub fn accepts_label(&self, format: &str) -> bool {
+        self.labels.contains(&format)
+    }
+}
+
+fn strings(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// Whether an ingredient declaring `allergen` names its specific source
+fn names_source(allergen: &str, name: &str, declared: &[&str]) -> bool {
+    match allergen {
+        "tree-nuts" => NUT_KEYWORDS.iter().any(|(keyword, _)| name.contains(keyword)),
+        "gluten" => declared.contains(&"wheat") || CEREAL_KEYWORDS.iter().any(|(keyword, _)| name.contains(keyword)),
+        "fish" => FISH_KEYWORDS.iter().any(|keyword| name.contains(keyword)),
+        "shellfish" => CRUSTACEAN_KEYWORDS.iter().any(|keyword| name.contains(keyword)),
+        _ => true,
+    }
+}
+
+/// Apply one market's rules to a recipe
+pub fn check(recipe: &Value, market: &Market, policy: &allergens::ProvenancePolicy, result: &mut ValidationResult) {
+    let ingredients = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten();
+    for (index, ingredient) in ingredients.enumerate() {
+        let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("").to_lowercase();
+        for (keyword, allergen) in allergens::undeclared(ingredient) {
+            if market.mandatory.contains(&allergen.name()) {
+                result.warning(
+                    codes::MARKET_UNDECLARED_ALLERGEN,
+                    &[("market", &market.id), ("index", &index), ("keyword", &keyword), ("allergen", &allergen.name())],
+                );
+            }
+        }
+        let declared = strings(ingredient.get("allergens"));
+        for allergen in declared.iter().filter(|a| market.named_sources.contains(a)) {
+            if !names_source(allergen, &name, &declared) {
+                result.warning(
+                    codes::MARKET_UNNAMED_SOURCE,
+                    &[("market", &market.id), ("index", &index), ("allergen", allergen)],
+                );
+            }
+        }
+    }
+
+    let advisory: Vec<String> =
+        allergens::contained_with(recipe, policy).into_iter().filter(|a| market.advisory.contains(&a.as_str())).collect();
+    if !advisory.is_empty() {
+        result.notice(codes::MARKET_ADVISORY_ALLERGEN, &[("market", &market.id), ("allergens", &advisory.join(", "))]);
+    }
+
+    if !market.may_contain_allowed {
+        let traces: Vec<String> =
+            allergens::traces(recipe).into_iter().filter(|a| market.mandatory.contains(&a.as_str())).collect();
+        if !traces.is_empty() {
+            result.warning(codes::MARKET_MAY_CONTAIN, &[("market", &market.id), ("allergens", &traces.join(", "))]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "meta": {"facility_allergens": ["peanuts", "celery"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "Mixed nuts", "allergens": ["tree-nuts"]},
+                {"id": "ing-0002", "name": "Prawns", "allergens": ["shellfish"]},
+                {"id": "ing-0003", "name": "White fish fillet", "allergens": ["fish"]},
+                {"id": "ing-0004", "name": "Soy sauce", "allergens": ["soybeans", "gluten"]}
+            ]
+        })
+    }
+
+    fn codes_for(id: &str) -> (Vec<&'static str>, ValidationResult) {
+        let mut result = ValidationResult::new();
+        check(&recipe(), market(id).unwrap(), &allergens::ProvenancePolicy::default(), &mut result);
+        (result.issues.iter().map(|i| i.code).collect(), result)
+    }
+
+    #[test]
+    fn test_unknown_market_lists_supported() {
+        assert_eq!(market("us").unwrap().id, "US");
+        let error = market("UK").unwrap_err();
+        assert_eq!(error.to_string(), "unknown market 'UK'; supported: EU, US, JP");
+    }
+
+    #[test]
+    fn test_markets_differ() {
+        let (found, result) = codes_for("EU");
+        assert_eq!(found, vec![codes::MARKET_UNNAMED_SOURCE, codes::MARKET_UNNAMED_SOURCE]);
+        assert_eq!(result.warnings[0], "[EU] Ingredient 0: the specific source of tree-nuts must be named");
+
+        let (found, result) = codes_for("US");
+        assert_eq!(
+            found,
+            vec![codes::MARKET_UNNAMED_SOURCE, codes::MARKET_UNNAMED_SOURCE, codes::MARKET_ADVISORY_ALLERGEN]
+        );
+        assert_eq!(result.notices[0], "[US] Declaring gluten is recommended but not mandatory");
+
+        let (found, result) = codes_for("JP");
+        assert_eq!(found, vec![codes::MARKET_ADVISORY_ALLERGEN, codes::MARKET_MAY_CONTAIN]);
+        assert_eq!(result.notices[0], "[JP] Declaring fish, soybeans is recommended but not mandatory");
+        assert_eq!(result.warnings[0], "[JP] 'May contain' statements are not accepted for peanuts; declare or remove them");
+    }
+
+    #[test]
+    fn test_undeclared_mandatory_allergens() {
+        let recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "Celery stalks", "allergens": []},
+                {"id": "ing-0002", "name": "Sesame seeds", "allergens": []},
+                {"id": "ing-0003", "name": "Butter", "allergens": ["lactose"]}
+            ]
+        });
+        let undeclared = |id| {
+            let mut result = ValidationResult::new();
+            check(&recipe, market(id).unwrap(), &allergens::ProvenancePolicy::default(), &mut result);
+            result.issues.iter().filter(|i| i.code == codes::MARKET_UNDECLARED_ALLERGEN).count()
+        };
+        // EU regulates both, the US only sesame, Japan neither
+        assert_eq!(undeclared("EU"), 2);
+        assert_eq!(undeclared("US"), 1);
+        assert_eq!(undeclared("JP"), 0);
+
+        let mut result = ValidationResult::new();
+        check(&recipe, market("US").unwrap(), &allergens::ProvenancePolicy::default(), &mut result);
+        assert!(result.warnings.contains(&"[US] Ingredient 1: 'sesame' suggests sesame, which must be declared here".to_string()));
+    }
+
+    #[test]
+    fn test_label_applicability() {
+        assert!(market("EU").unwrap().accepts_label("eu-fic"));
+        assert!(!market("US").unwrap().accepts_label("eu-fic"));
+        assert!(market("JP").unwrap().labels.is_empty());
+    }
+}
@@ -0,0 +1,486 @@
+// Three-way recipe merge
+//
+// Combines two edits of the same base recipe on the semantic model rather
+// than on lines. Ingredients, steps, images, sensors and device profiles are
+// matched by id, so moving an item is not an edit of it; objects merge field
+// by field, and a field changed on one side only takes that side's value.
+// Lists of labels (allergens, diet labels, tags) merge as sets, and a list
+// only reordered on one side takes the other side's content.
+//
+// Items both sides added under the same id are kept once when equal and
+// otherwise theirs gets a fresh id, with every reference to it rewritten;
+// an item theirs added that matches one ours added under another id is
+// taken as the same item. What is left, both sides changing one field to
+// different values or one side editing what the other removed, is reported
+// with the path and all three values, and no merged recipe is returned.
+//
+// When both sides reorder the same list differently, our order wins and the
+// report says so. Paths use ids as in `diff`: `/steps/s-02/human_text`.
+
+use crate::id_sequence::rename_ids;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// (array, id field) of the lists matched by id
+const KEYED: &[(&str, &str)] = &[
+    ("ingredients", "id"),
+    ("steps", "step_id"),
+    ("images", "id"),
+    ("sensors", "id"),
+    ("device_profiles", "id"),
+];
+
+/// Lists whose order carries no meaning, merged as sets
+const SET_FIELDS: &[&str] = &["allergens", "may_contain_allergens", "diet_labels", "tags"];
+
+/// Both sides changed the same value differently
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    /// None where the value is absent on that side
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |value: &Option<Value>| value.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "(absent)".to_string());
+        write!(f, "{}: base {}, ours {}, theirs {}", self.path, show(&self.base), show(&self.ours), show(&self.theirs))
+    }
+}
+
+/// Why a merge produced no recipe
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflicts {
+    pub conflicts: Vec<Conflict>,
+}
+
+impl fmt::Display for MergeConflicts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} conflict(s):", self.conflicts.len())?;
+        for conflict in &self.conflicts {
+            writeln!(f, "  {}", conflict)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MergeConflicts {}
+
+/// What a successful merge took from each side
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeReport {
+    /// Paths changed on our side only
+    pub from_ours: Vec<String>,
+    /// Paths changed on their side only
+    pub from_theirs: Vec<String>,
+    /// Ids of their additions renamed to resolve a collision or match ours
+    pub renamed: BTreeMap<String, String>,
+    pub notes: Vec<String>,
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Merged {} change(s) from ours, {} from theirs", self.from_ours.len(), self.from_theirs.len())?;
+        for (old, new) in &self.renamed {
+            writeln!(f, "  theirs {} is now {}", old, new)?;
+        }
+        for note in &self.notes {
+            writeln!(f, "  {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+fn items<'a>(recipe: &'a Value, array: &str) -> impl Iterator<Item = &'a Value> {
+    recipe.get(array).and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+fn ids<'a>(recipe: &'a Value, array: &str, key: &str) -> BTreeSet<&'a str> {
+    items(recipe, array).filter_map(|item| item.get(key)?.as_str()).collect()
+}
+
+fn find<'a>(list: &'a [Value], key: &str, id: &str) -> Option<&'a Value> {
+    list.iter().find(|item| item.get(key).and_then(|v| v.as_str()) == Some(id))
+}
+
+/// An item without its id, to compare additions made under different ids
+fn content(item: &Value, key: &str) -> Value {
+    let mut content = item.clone();
+    if let Some(fields) = content.as_object_mut() {
+        fields.remove(key);
+    }
+    content
+}
+
+/// Next unused id in the family of `id`: `ing-0005` becomes `ing-0007` when
+/// `ing-0006` is taken, keeping the padding
+fn fresh_id(id: &str, taken: &BTreeSet<String>) -> String {
+    let prefix = id.trim_end_matches(|c: char| c.is_ascii_digit());
+    let width = id.len() - prefix.len();
+    let (prefix, width) = if width == 0 { (format!("{}-", id), 1) } else { (prefix.to_string(), width) };
+    let highest = taken
+        .iter()
+        .filter_map(|t| t.strip_prefix(prefix.as_str())?.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0);
+    format!("{}{:0width$}", prefix, highest.max(1) + 1, width = width)
+}
+
+/// Rename their additions that collide with ours or repeat one of ours
+fn resolve_ids(base: &Value, ours: &Value, theirs: &mut Value, report: &mut MergeReport) {
+    for (array, key) in KEYED {
+        let base_ids = ids(base, array, key);
+        let ours_added: Vec<&Value> = items(ours, array)
+            .filter(|item| item.get(*key).and_then(|v| v.as_str()).is_some_and(|id| !base_ids.contains(id)))
+            .collect();
+        let mut taken: BTreeSet<String> = ids(base, array, key)
+            .into_iter()
+            .chain(ids(ours, array, key))
+            .chain(ids(theirs, array, key))
+            .map(String::from)
+            .collect();
+
+        let mut mapping: HashMap<String, String> = HashMap::new();
+        for item in items(theirs, array) {
+            let id = match item.get(*key).and_then(|v| v.as_str()) {
+                Some(id) if !base_ids.contains(id) => id,
+                _ => continue,
+            };
+            let same_id = ours_added.iter().find(|o| o.get(*key).and_then(|v| v.as_str()) == Some(id));
+            if same_id.is_some_and(|o| *o == item) {
+                continue;
+            }
+            let duplicate = ours_added.iter().find(|o| content(o, key) == content(item, key));
+            let new = match duplicate {
+                Some(o) => {
+                    let new = o[*key].as_str().unwrap_or(id).to_string();
+                    report.notes.push(format!("theirs {} repeats ours {}; kept once", id, new));
+                    new
+                }
+                None if same_id.is_some() => {
+                    let new = fresh_id(id, &taken);
+                    taken.insert(new.clone());
+                    new
+                }
+                None => continue,
+            };
+            report.renamed.insert(id.to_string(), new.clone());
+            mapping.insert(id.to_string(), new);
+        }
+        if mapping.is_empty() {
+            continue;
+        }
+
+        if *array == "ingredients" || *array == "steps" {
+            rename_ids(theirs, &mapping);
+        } else {
+            for item in theirs.get_mut(*array).and_then(|v| v.as_array_mut()).into_iter().flatten() {
+                if let Some(new) = item.get(*key).and_then(|v| v.as_str()).and_then(|id| mapping.get(id)) {
+                    item[*key] = Value::String(new.clone());
+                }
+            }
+            if *array == "device_profiles" {
+                for step in theirs.get_mut("steps").and_then(|v| v.as_array_mut()).into_iter().flatten() {
+                    let renamed = step.get("device_profile_ref").and_then(|v| v.as_str()).and_then(|id| mapping.get(id));
+                    if let Some(new) = renamed {
+                        step["device_profile_ref"] = Value::String(new.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Same items in the same amounts, in any order
+fn same_content(a: &[Value], b: &[Value]) -> bool {
+    let sorted = |items: &[Value]| {
+        let mut items: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+        items.sort();
+        items
+    };
+    sorted(a) == sorted(b)
+}
+
+struct Merger {
+    report: MergeReport,
+    conflicts: Vec<Conflict>,
+}
+
+impl Merger {
+    fn conflict(&mut self, path: &str, base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> Option<Value> {
+        self.conflicts.push(Conflict {
+            path: path.to_string(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        ours.cloned()
+    }
+
+    /// Merged value at `path`; None when it ends up absent
+    fn value(&mut self, path: &str, base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>) -> Option<Value> {
+        if ours == theirs {
+            return ours.cloned();
+        }
+        if ours == base {
+            self.report.from_theirs.push(path.to_string());
+            return theirs.cloned();
+        }
+        if theirs == base {
+            self.report.from_ours.push(path.to_string());
+            return ours.cloned();
+        }
+
+        let empty = Value::Object(Map::new());
+        match (base, ours, theirs) {
+            (None | Some(Value::Object(_)), Some(Value::Object(_)), Some(Value::Object(_))) => {
+                Some(self.object(path, base.unwrap_or(&empty), ours?, theirs?))
+            }
+            (Some(Value::Array(b)), Some(Value::Array(o)), Some(Value::Array(t))) => match self.keyed(path) {
+                Some(key) => self.keyed_list(path, key, b, o, t),
+                None => self.list(path, b, o, t),
+            },
+            _ => self.conflict(path, base, ours, theirs),
+        }
+    }
+
+    fn object(&mut self, path: &str, base: &Value, ours: &Value, theirs: &Value) -> Value {
+        let keys: BTreeSet<&String> = [base, ours, theirs]
+            .iter()
+            .filter_map(|v| v.as_object())
+            .flat_map(|fields| fields.keys())
+            .collect();
+        let mut merged = Map::new();
+        for key in keys {
+            let child = format!("{}/{}", path, key);
+            if let Some(value) = self.value(&child, base.get(key), ours.get(key), theirs.get(key)) {
+                merged.insert(key.clone(), value);
+            }
+        }
+        Value::Object(merged)
+    }
+
+    /// Id field of a list matched by id
+    fn keyed(&self, path: &str) -> Option<&'static str> {
+        KEYED.iter().find(|(array, _)| path.strip_prefix('/') == Some(*array)).map(|(_, key)| *key)
+    }
+
+    /// Merge a list matched by id: each item on its own, then the order
+    fn keyed_list(&mut self, path: &str, key: &str, base: &[Value], ours: &[Value], theirs: &[Value]) -> Option<Value> {
+        let order = |list: &[Value]| -> Vec<String> {
+            list.iter().filter_map(|item| item.get(key)?.as_str()).map(String::from).collect()
+        };
+        let (base_order, ours_order, theirs_order) = (order(base), order(ours), order(theirs));
+
+        // Items without an id or listed twice cannot be matched
+        let matchable = |list: &[Value], order: &[String]| {
+            list.len() == order.len() && order.iter().collect::<BTreeSet<_>>().len() == order.len()
+        };
+        if !(matchable(base, &base_order) && matchable(ours, &ours_order) && matchable(theirs, &theirs_order)) {
+            return self.list(path, base, ours, theirs);
+        }
+
+        let mut merged: HashMap<String, Value> = HashMap::new();
+        let all: BTreeSet<&String> = base_order.iter().chain(&ours_order).chain(&theirs_order).collect();
+        for id in all {
+            let item = self.value(
+                &format!("{}/{}", path, id),
+                find(base, key, id),
+                find(ours, key, id),
+                find(theirs, key, id),
+            );
+            if let Some(item) = item {
+                merged.insert(id.clone(), item);
+            }
+        }
+
+        let kept = |order: &[String], other: &[String]| -> Vec<String> {
+            order.iter().filter(|id| other.contains(id)).cloned().collect()
+        };
+        let ours_moved = kept(&ours_order, &base_order) != kept(&base_order, &ours_order);
+        let theirs_moved = kept(&theirs_order, &base_order) != kept(&base_order, &theirs_order);
+        let (primary, secondary) = if theirs_moved && !ours_moved {
+            self.report.from_theirs.push(format!("{} (order)", path));
+            (&theirs_order, &ours_order)
+        } else {
+            if ours_moved {
+                self.report.from_ours.push(format!("{} (order)", path));
+            }
+            (&ours_order, &theirs_order)
+        };
+        if ours_moved && theirs_moved && kept(&ours_order, &theirs_order) != kept(&theirs_order, &ours_order) {
+            self.report.notes.push(format!("both sides reordered {}; kept our order", path));
+        }
+
+        // The other side's additions go after the item they follow there,
+        // and after what this side added at the same place
+        let mut sequence: Vec<String> = primary.clone();
+        for (i, id) in secondary.iter().enumerate() {
+            if sequence.contains(id) {
+                continue;
+            }
+            let mut at = secondary[..i]
+                .iter()
+                .rev()
+                .find_map(|before| sequence.iter().position(|s| s == before))
+                .map(|p| p + 1)
+                .unwrap_or(0);
+            while at < sequence.len() && !secondary.contains(&sequence[at]) && !base_order.contains(&sequence[at]) {
+                at += 1;
+            }
+            sequence.insert(at, id.clone());
+        }
+        Some(Value::Array(sequence.iter().filter_map(|id| merged.remove(id)).collect()))
+    }
+
+    /// Merge a plain list both sides changed
+    fn list(&mut self, path: &str, base: &[Value], ours: &[Value], theirs: &[Value]) -> Option<Value> {
+        let field = path.rsplit('/').next().unwrap_or("");
+        if SET_FIELDS.contains(&field) {
+            let removed: Vec<&Value> = base.iter().filter(|v| !ours.contains(v) || !theirs.contains(v)).collect();
+            let mut merged: Vec<Value> = ours.iter().filter(|v| !removed.contains(v)).cloned().collect();
+            for value in theirs {
+                if !base.contains(value) && !merged.contains(value) {
+                    merged.push(value.clone());
+                }
+            }
+            self.report.from_ours.push(path.to_string());
+            self.report.from_theirs.push(path.to_string());
+            return Some(Value::Array(merged));
+        }
+        if same_content(base, ours) {
+            self.report.from_theirs.push(path.to_string());
+            self.report.notes.push(format!("{}: took their change over our reordering", path));
+            return Some(Value::Array(theirs.to_vec()));
+        }
+        if same_content(base, theirs) {
+            self.report.from_ours.push(path.to_string());
+            self.report.notes.push(format!("{}: took our change over their reordering", path));
+            return Some(Value::Array(ours.to_vec()));
+        }
+        let (base, ours, theirs) = (Value::Array(base.to_vec()), Value::Array(ours.to_vec()), Value::Array(theirs.to_vec()));
+        self.conflict(path, Some(&base), Some(&ours), Some(&theirs))
+    }
+}
+
+/// Merge two edits of `base`
+///
+/// Returns the merged recipe and what came from each side, or every conflict
+/// when the edits cannot be combined without guessing.
+pub fn three_way(base: &Value, ours: &Value, theirs: &Value) -> Result<(Value, MergeReport), MergeConflicts> {
+    let mut report = MergeReport::default();
+    let mut theirs = theirs.clone();
+    resolve_ids(base, ours, &mut theirs, &mut report);
+
+    let mut merger = Merger { report, conflicts: Vec::new() };
+    let merged = merger.value("", Some(base), Some(ours), Some(&theirs));
+    if !merger.conflicts.is_empty() {
+        return Err(MergeConflicts { conflicts: merger.conflicts });
+    }
+    Ok((merged.unwrap_or(Value::Null), merger.report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn base() -> Value {
+        json!({
+            "meta": {"name": "Flatbread", "servings": {"amount": 2}, "diet_labels": ["vegan"]},
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "machine_amount": {"value": 200, "unit": "g"}, "allergens": ["gluten"]},
+                {"id": "ing-0002", "name": "water", "machine_amount": {"value": 120, "unit": "ml"}, "allergens": []}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "human_text": "Mix", "target": ["ing-0001", "ing-0002"]},
+                {"step_id": "s-02", "action": "knead", "human_text": "Knead", "target": ["s-01:result"]},
+                {"step_id": "s-03", "action": "bake", "human_text": "Bake", "target": ["s-02:result"]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_reordering_is_not_a_conflict() {
+        let base = base();
+        let mut ours = base.clone();
+        ours["steps"].as_array_mut().unwrap().swap(0, 1);
+        ours["ingredients"].as_array_mut().unwrap().reverse();
+        ours["meta"]["diet_labels"] = json!(["vegetarian", "vegan"]);
+        let mut theirs = base.clone();
+        theirs["steps"][0]["human_text"] = json!("Mix well");
+        theirs["ingredients"][1]["machine_amount"]["value"] = json!(130);
+        theirs["meta"]["diet_labels"] = json!(["vegan", "dairy-free"]);
+
+        let (merged, report) = three_way(&base, &ours, &theirs).unwrap();
+        let step_ids: Vec<&Value> = merged["steps"].as_array().unwrap().iter().map(|s| &s["step_id"]).collect();
+        assert_eq!(step_ids, vec!["s-02", "s-01", "s-03"]);
+        assert_eq!(merged["steps"][1]["human_text"], "Mix well");
+        assert_eq!(merged["ingredients"][0]["id"], "ing-0002");
+        assert_eq!(merged["ingredients"][0]["machine_amount"]["value"], 130);
+        assert_eq!(merged["meta"]["diet_labels"], json!(["vegetarian", "vegan", "dairy-free"]));
+        assert!(report.from_ours.contains(&"/steps (order)".to_string()));
+        assert!(report.from_theirs.contains(&"/steps/s-01".to_string()));
+
+        // Reordering alone on both sides merges too
+        let mut reordered = base.clone();
+        reordered["steps"].as_array_mut().unwrap().swap(1, 2);
+        let (merged, report) = three_way(&base, &ours, &reordered).unwrap();
+        assert_eq!(merged["steps"], ours["steps"]);
+        assert_eq!(report.notes, vec!["both sides reordered /steps; kept our order"]);
+    }
+
+    #[test]
+    fn test_conflicts_are_reported_not_guessed() {
+        let base = base();
+        let mut ours = base.clone();
+        ours["steps"][2]["human_text"] = json!("Bake hot");
+        ours["meta"]["name"] = json!("Flatbreads");
+        let mut theirs = base.clone();
+        theirs["steps"][2]["human_text"] = json!("Bake gently");
+        theirs["ingredients"].as_array_mut().unwrap().remove(1);
+        ours["ingredients"][1]["name"] = json!("warm water");
+
+        let conflicts = three_way(&base, &ours, &theirs).unwrap_err().conflicts;
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].path, "/ingredients/ing-0002");
+        assert_eq!(conflicts[0].theirs, None);
+        assert_eq!(conflicts[1].path, "/steps/s-03/human_text");
+        assert_eq!(conflicts[1].to_string(), r#"/steps/s-03/human_text: base "Bake", ours "Bake hot", theirs "Bake gently""#);
+    }
+
+    #[test]
+    fn test_additions_from_both_sides_are_kept() {
+        let base = base();
+        let salt = json!({"id": "ing-0003", "name": "salt", "machine_amount": {"value": 4, "unit": "g"}, "allergens": []});
+        let oil = json!({"id": "ing-0003", "name": "oil", "machine_amount": {"value": 10, "unit": "ml"}, "allergens": []});
+        let mut ours = base.clone();
+        ours["ingredients"].as_array_mut().unwrap().push(salt.clone());
+        ours["steps"][0]["target"] = json!(["ing-0001", "ing-0002", "ing-0003"]);
+        let mut theirs = base.clone();
+        theirs["ingredients"].as_array_mut().unwrap().push(oil);
+        theirs["steps"].as_array_mut().unwrap().push(
+            json!({"step_id": "s-04", "action": "brush", "human_text": "Brush with oil", "target": ["s-03:result", "ing-0003"]}),
+        );
+
+        let (merged, report) = three_way(&base, &ours, &theirs).unwrap();
+        assert_eq!(report.renamed, BTreeMap::from([("ing-0003".to_string(), "ing-0004".to_string())]));
+        let names: Vec<&Value> = merged["ingredients"].as_array().unwrap().iter().map(|i| &i["name"]).collect();
+        assert_eq!(names, vec!["flour", "water", "salt", "oil"]);
+        assert_eq!(merged["ingredients"][3]["id"], "ing-0004");
+        assert_eq!(merged["steps"][3]["target"], json!(["s-03:result", "ing-0004"]));
+        assert_eq!(merged["steps"][0]["target"], json!(["ing-0001", "ing-0002", "ing-0003"]));
+
+        // The same addition on both sides, even under another id, is kept once
+        let mut theirs = base.clone();
+        let mut same_salt = salt.clone();
+        same_salt["id"] = json!("ing-0009");
+        theirs["ingredients"].as_array_mut().unwrap().push(same_salt);
+        let (merged, report) = three_way(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged["ingredients"].as_array().unwrap().len(), 3);
+        assert_eq!(report.notes, vec!["theirs ing-0009 repeats ours ing-0003; kept once"]);
+    }
+}
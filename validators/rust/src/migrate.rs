@@ -0,0 +1,222 @@
+// Version migration
+//
+// `migrate_recipe` upgrades a recipe to a later RCIP version one known step
+// at a time (`STEPS`). Each step moves the fields the deprecation registry
+// marks as plain renames, converts the removed fields it knows how to (ISO
+// 8601 step durations into `params.time_minutes`) and bumps `rcip_version`.
+// A field the target version removes that no step can convert is an error
+// naming the field and its migration note, so a recipe is never labelled
+// with a version it does not conform to. Deprecated fields the target still
+// accepts are moved when they are renames and left alone otherwise.
+//
+// Downgrades, versions without a step and a `from` that disagrees with the
+// recipe's own `rcip_version` are refused. Check the output with a validator
+// that has the target schema (`RCIPValidator::init_versions`).
+//
+// v0.1 is the only version with a schema, so `STEPS` is empty and every
+// upgrade is refused for now. A step is added together with the schema of
+// the version it leads to, never ahead of it. `migrate_recipe_in` takes
+// the steps and the deprecation registry, which is how the tests exercise
+// the machinery.
+
+use crate::deprecations::{self, compare_versions, parse_version, Deprecation};
+use crate::RCIPError;
+use serde_json::{json, Value};
+
+/// Known migrations, (from, to), each to the next version
+pub const STEPS: &[(&str, &str)] = &[];
+
+/// Turns a removed field's value into its replacement's, if it can
+type Conversion = fn(&Value) -> Option<Value>;
+
+/// Removed fields a migration can convert rather than only move
+const CONVERSIONS: &[(&str, Conversion)] = &[("/steps/*/duration", duration_minutes)];
+
+/// Minutes in an ISO 8601 duration of hours, minutes and seconds, such as
+/// "PT1H30M"; a plain number is taken as minutes already
+fn duration_minutes(value: &Value) -> Option<Value> {
+    if value.is_number() {
+        return Some(value.clone());
+    }
+    let mut rest = value.as_str()?.trim().strip_prefix("PT")?;
+    if rest.is_empty() {
+        return None;
+    }
+    let mut minutes = 0.0;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+        let amount: f64 = rest[..end].parse().ok()?;
+        minutes += amount
+            * match &rest[end..end + 1] {
+                "H" => 60.0,
+                "M" => 1.0,
+                "S" => 1.0 / 60.0,
+                _ => return None,
+            };
+        rest = &rest[end + 1..];
+    }
+    Some(if minutes.fract() == 0.0 { json!(minutes as i64) } else { json!(minutes) })
+}
+
+fn error(message: String) -> RCIPError {
+    RCIPError::MigrationError(message)
+}
+
+fn known_steps(steps: &[(&str, &str)]) -> String {
+    match steps {
+        [] => "none".to_string(),
+        steps => steps.iter().map(|(from, to)| format!("{} -> {}", from, to)).collect::<Vec<_>>().join(", "),
+    }
+}
+
+/// Upgrade a recipe from version `from` to version `to`
+pub fn migrate_recipe(recipe: &Value, from: &str, to: &str) -> Result<Value, RCIPError> {
+    migrate_recipe_in(STEPS, deprecations::registry(), recipe, from, to)
+}
+
+/// `migrate_recipe` with the given steps and deprecation registry
+pub(crate) fn migrate_recipe_in<'a>(
+    steps: &[(&'a str, &'a str)],
+    registry: &'static [Deprecation],
+    recipe: &Value,
+    from: &str,
+    to: &str,
+) -> Result<Value, RCIPError> {
+    let declared = recipe.get("rcip_version").and_then(|v| v.as_str());
+    if declared.is_some_and(|declared| declared != from) {
+        return Err(error(format!("the recipe is version {}, not {}", declared.unwrap_or_default(), from)));
+    }
+
+    let mut path = Vec::new();
+    let mut at = from;
+    while at != to {
+        match steps.iter().find(|(step_from, _)| *step_from == at) {
+            Some(step) => {
+                path.push(*step);
+                at = step.1;
+            }
+            None => return Err(error(format!("no migration from {} to {} (known: {})", from, to, known_steps(steps)))),
+        }
+    }
+
+    let mut migrated = recipe.clone();
+    for (_, to) in path {
+        step(registry, &mut migrated, to)?;
+    }
+    Ok(migrated)
+}
+
+/// Apply one step, ending at version `to`
+fn step(registry: &'static [Deprecation], recipe: &mut Value, to: &str) -> Result<(), RCIPError> {
+    let target = parse_version(to).ok_or_else(|| error(format!("'{}' is not a version", to)))?;
+    let removed = |found: &deprecations::Found| {
+        parse_version(&found.deprecation.removed_in).is_some_and(|removed| compare_versions(&target, &removed).is_ge())
+    };
+
+    deprecations::migrate_in(registry, recipe);
+    let mut left = Vec::new();
+    for found in deprecations::found_in(registry, recipe).into_iter().filter(removed) {
+        let conversion = CONVERSIONS.iter().find(|(path, _)| *path == found.deprecation.path);
+        let converted = conversion.and_then(|(_, convert)| convert(recipe.pointer(&found.path)?));
+        let outcome = match (converted, recipe.pointer(&found.replacement)) {
+            (Some(value), Some(existing)) if *existing != value => Err(format!("{} already holds a different value", found.replacement)),
+            (Some(value), _) => deprecations::insert(recipe, &found.replacement, value),
+            (None, _) => Err(found.deprecation.migration_note.clone().unwrap_or_else(|| "cannot be converted".to_string())),
+        };
+        match outcome {
+            Ok(()) => {
+                deprecations::remove(recipe, &found.path);
+            }
+            Err(reason) => left.push(format!("{} ({})", found.path, reason)),
+        }
+    }
+    if !left.is_empty() {
+        return Err(error(format!("fields removed in {} remain: {}", to, left.join("; "))));
+    }
+
+    recipe["rcip_version"] = json!(to);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{warm, RCIPValidator};
+
+    /// Steps to versions that do not exist, for tests
+    const TEST_STEPS: &[(&str, &str)] = &[("0.1", "0.2"), ("0.2", "0.3")];
+
+    fn migrate_recipe(recipe: &Value, from: &str, to: &str) -> Result<Value, RCIPError> {
+        migrate_recipe_in(TEST_STEPS, deprecations::test_registry(), recipe, from, to)
+    }
+
+    fn recipe() -> Value {
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let meta = recipe["meta"].as_object_mut().unwrap();
+        let total = meta.remove("total_time_minutes").unwrap();
+        meta.insert("total_time".to_string(), total);
+        recipe["steps"][0]["duration"] = json!("PT2M");
+        recipe
+    }
+
+    /// The bundled schema with the version it requires changed
+    fn schema(version: &str) -> String {
+        let mut schema: Value = serde_json::from_str(warm::bundled_schema("0.1").unwrap()).unwrap();
+        schema["properties"]["rcip_version"]["const"] = json!(version);
+        schema.to_string()
+    }
+
+    #[test]
+    fn test_migrates_to_a_version_the_target_schema_accepts() {
+        let original = recipe();
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        validator.add_schema_version("0.3", &schema("0.3")).unwrap();
+
+        let migrated = migrate_recipe(&original, "0.1", "0.3").unwrap();
+        assert_eq!(migrated["rcip_version"], "0.3");
+        assert_eq!(migrated["meta"]["total_time_minutes"], original["meta"]["total_time"]);
+        assert!(migrated["meta"].get("total_time").is_none());
+        assert_eq!(migrated["steps"][0]["params"]["time_minutes"], 2);
+        assert!(migrated["steps"][0].get("duration").is_none());
+
+        let result = validator.validate(&migrated);
+        assert_eq!(result.info.schema_version.as_deref(), Some("0.3"));
+        assert!(result.valid && result.warnings.iter().all(|w| !w.contains("deprecated")), "{:?}", result.errors);
+
+        // 0.2 still accepts durations
+        let halfway = migrate_recipe(&original, "0.1", "0.2").unwrap();
+        assert_eq!(halfway["steps"][0]["duration"], "PT2M");
+        assert_eq!(migrate_recipe(&halfway, "0.2", "0.3").unwrap(), migrated);
+    }
+
+    #[test]
+    fn test_refuses_what_it_cannot_migrate() {
+        let original = recipe();
+        let message = |result: Result<Value, RCIPError>| result.unwrap_err().to_string();
+        assert_eq!(message(migrate_recipe(&original, "0.1", "0.9")), "Migration error: no migration from 0.1 to 0.9 (known: 0.1 -> 0.2, 0.2 -> 0.3)");
+        assert!(message(migrate_recipe(&original, "0.2", "0.1")).contains("the recipe is version 0.1, not 0.2"));
+        assert!(matches!(migrate_recipe(&json!({"rcip_version": "0.3"}), "0.3", "0.1"), Err(RCIPError::MigrationError(_))));
+
+        let mut unconvertible = original.clone();
+        unconvertible["steps"][0]["duration"] = json!("about an hour");
+        let reason = message(migrate_recipe(&unconvertible, "0.1", "0.3"));
+        assert!(reason.contains("/steps/0/duration (durations such as PT1H30M need converting to minutes)"), "{}", reason);
+        assert_eq!(migrate_recipe(&original, "0.1", "0.1").unwrap(), original);
+
+        // The bundled steps upgrade to nothing until a later schema ships
+        assert_eq!(
+            message(super::migrate_recipe(&original, "0.1", "0.2")),
+            "Migration error: no migration from 0.1 to 0.2 (known: none)"
+        );
+        assert_eq!(super::migrate_recipe(&original, "0.1", "0.1").unwrap(), original);
+    }
+
+    #[test]
+    fn test_durations() {
+        assert_eq!(duration_minutes(&json!("PT1H30M")), Some(json!(90)));
+        assert_eq!(duration_minutes(&json!("PT45S")), Some(json!(0.75)));
+        assert_eq!(duration_minutes(&json!(12)), Some(json!(12)));
+        assert_eq!(duration_minutes(&json!("PT")), None);
+        assert_eq!(duration_minutes(&json!("P1D")), None);
+    }
+}
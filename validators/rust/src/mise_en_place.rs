@@ -0,0 +1,162 @@
+// Mise-en-place checks
+//
+// Professional kitchens measure every ingredient before cooking starts. The
+// check finds ingredients whose first referencing step is not a `measure`,
+// `weigh` or `prepare` step; `add_prep_phase` fixes a recipe by inserting a
+// parallel group of measure steps at the start.
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+
+const PREP_ACTIONS: &[&str] = &["measure", "weigh", "prepare"];
+
+/// Group name given to synthesized prep steps
+pub const PREP_GROUP: &str = "mise-en-place";
+
+/// An ingredient used before it was measured or prepared
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unprepped {
+    pub ingredient_id: String,
+    pub step_id: String,
+    pub action: String,
+}
+
+/// Ingredients whose first use is not a prep step, in ingredient order
+pub fn unprepped(recipe: &Value) -> Vec<Unprepped> {
+    let mut first_use: HashMap<&str, (&str, &str)> = HashMap::new();
+
+    for step in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten() {
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?");
+        let action = step.get("action").and_then(|v| v.as_str()).unwrap_or("");
+        for target in step.get("target").and_then(|v| v.as_array()).into_iter().flatten() {
+            if let Some(id) = target.as_str().filter(|t| t.starts_with("ing-")) {
+                first_use.entry(id).or_insert((step_id, action));
+            }
+        }
+    }
+
+    recipe.get("ingredients")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|ing| ing.get("id").and_then(|v| v.as_str()))
+        .filter_map(|id| {
+            let (step_id, action) = first_use.get(id)?;
+            if PREP_ACTIONS.contains(action) {
+                return None;
+            }
+            Some(Unprepped {
+                ingredient_id: id.to_string(),
+                step_id: step_id.to_string(),
+                action: action.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Insert a measure step for every unprepped ingredient at the start of the
+/// recipe, grouped so they can run in parallel. Returns the new step ids.
+pub fn add_prep_phase(recipe: &mut Value) -> Vec<String> {
+    let missing = unprepped(recipe);
+    if missing.is_empty() {
+        return Vec::new();
+    }
+
+    let existing: HashSet<String> = recipe.get("steps")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("step_id").and_then(|v| v.as_str()).map(|s| s.to_string()))
+        .collect();
+
+    let ingredients: HashMap<String, (String, String)> = recipe.get("ingredients")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|ing| {
+            let id = ing.get("id")?.as_str()?.to_string();
+            let name = ing.get("name").and_then(|v| v.as_str()).unwrap_or(id.as_str()).to_string();
+            let amount = ing.get("human_amount").and_then(|v| v.as_str()).unwrap_or("").to_string();
+            Some((id, (name, amount)))
+        })
+        .collect();
+
+    let mut counter = 0;
+    let mut new_ids = Vec::new();
+    let mut prep_steps = Vec::new();
+
+    for item in &missing {
+        let step_id = loop {
+            counter += 1;
+            let candidate = format!("s-mep{:02}", counter);
+            if !existing.contains(&candidate) {
+                break candidate;
+            }
+        };
+
+        let (name, amount) = &ingredients[&item.ingredient_id];
+        let text = if amount.is_empty() {
+            format!("Measure the {}", name)
+        } else {
+            format!("Measure {} {}", amount, name)
+        };
+
+        prep_steps.push(json!({
+            "step_id": step_id,
+            "human_text": text,
+            "action": "measure",
+            "target": [item.ingredient_id],
+            "x-parallel-group": PREP_GROUP
+        }));
+        new_ids.push(step_id);
+    }
+
+    if let Some(steps) = recipe.get_mut("steps").and_then(|v| v.as_array_mut()) {
+        prep_steps.append(steps);
+        *steps = prep_steps;
+    }
+
+    new_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "human_amount": "500g"},
+                {"id": "ing-0002", "name": "water", "human_amount": "325ml"},
+                {"id": "ing-0003", "name": "salt"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "weigh", "target": ["ing-0001"], "human_text": "Weigh flour"},
+                {"step_id": "s-02", "action": "mix", "target": ["s-01:result", "ing-0002", "ing-0003"], "human_text": "Mix"}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_unprepped_ingredients() {
+        let missing = unprepped(&recipe());
+        let ids: Vec<&str> = missing.iter().map(|m| m.ingredient_id.as_str()).collect();
+        assert_eq!(ids, vec!["ing-0002", "ing-0003"]);
+        assert_eq!(missing[0].action, "mix");
+    }
+
+    #[test]
+    fn test_add_prep_phase() {
+        let mut fixed = recipe();
+        let new_ids = add_prep_phase(&mut fixed);
+
+        assert_eq!(new_ids, vec!["s-mep01", "s-mep02"]);
+        let steps = fixed["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 4);
+        assert_eq!(steps[0]["human_text"], "Measure 325ml water");
+        assert_eq!(steps[1]["human_text"], "Measure the salt");
+        assert_eq!(steps[1]["x-parallel-group"], PREP_GROUP);
+        assert!(unprepped(&fixed).is_empty());
+        assert!(add_prep_phase(&mut fixed).is_empty());
+    }
+}
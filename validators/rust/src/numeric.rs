@@ -0,0 +1,279 @@
+// Numeric sanity
+//
+// Every numeric field the validator knows about is listed in `FIELDS` together
+// with its documented range. `check` walks the whole document once and
+// reports non-finite values, values outside the range, negative zero and
+// numbers encoded as strings. Rules that read numbers go through `finite` or
+// `finite_at` so a malformed literal is treated the same way everywhere.
+
+use crate::codes;
+use crate::precision::number_value;
+use crate::ValidationResult;
+use serde_json::Value;
+
+/// A numeric field and its documented range. `*` in the path matches every
+/// array element or object member.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericField {
+    pub path: &'static str,
+    pub min: f64,
+    pub max: f64,
+}
+
+const fn field(path: &'static str, min: f64, max: f64) -> NumericField {
+    NumericField { path, min, max }
+}
+
+const MINUTES_PER_YEAR: f64 = 525_600.0;
+
+/// Known numeric fields with their documented ranges
+pub const FIELDS: &[NumericField] = &[
+    field("/meta/servings/amount", 0.0, 10_000.0),
+    field("/meta/prep_time_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/meta/cook_time_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/meta/active_time_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/meta/total_time_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/ingredients/*/machine_amount/value", 0.0, 1_000_000.0),
+    field("/ingredients/*/machine_amount/tolerance/min", 0.0, 1_000_000.0),
+    field("/ingredients/*/machine_amount/tolerance/max", 0.0, 1_000_000.0),
+    field("/ingredients/*/nutritional/per_100g/*", 0.0, 100_000.0),
+    field("/ingredients/*/substitutes/*/ratio", 0.0, 1_000.0),
+    field("/steps/*/params/time_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/steps/*/params/time_seconds", 0.0, MINUTES_PER_YEAR * 60.0),
+    field("/steps/*/params/time_hours", 0.0, MINUTES_PER_YEAR / 60.0),
+    field("/steps/*/params/temperature_c", -273.15, 1_000.0),
+    field("/steps/*/params/temperature_f", -459.67, 1_832.0),
+    field("/steps/*/params/speed_rpm", 0.0, 100_000.0),
+    field("/steps/*/params/pressure_bar", 0.0, 100.0),
+    field("/steps/*/done_when/temperature_c", -273.15, 1_000.0),
+    field("/steps/*/done_when/time_elapsed_minutes", 0.0, MINUTES_PER_YEAR),
+    field("/steps/*/done_when/volume_increase", 0.0, 100.0),
+];
+
+/// A JSON number as a finite f64. Strings and non-finite values give `None`.
+pub fn finite(value: &Value) -> Option<f64> {
+    value.as_f64().filter(|v| v.is_finite())
+}
+
+/// Parse a number encoded as a string, as long as it is a plain decimal literal
+fn parse_string_number(text: &str) -> Option<f64> {
+    let trimmed = text.trim();
+    let plain = !trimmed.is_empty()
+        && trimmed.chars().all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+    if plain {
+        trimmed.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn is_non_finite_word(text: &str) -> bool {
+    let lower = text.trim().trim_start_matches(['+', '-']).to_ascii_lowercase();
+    lower == "nan" || lower == "inf" || lower == "infinity"
+}
+
+/// Read the value at `pointer` as a finite number within `field`'s range,
+/// recording an issue if it is not. Missing fields and non-numeric strings
+/// (left to the schema) give `None` without an issue.
+pub fn finite_at(recipe: &Value, pointer: &str, field: &NumericField, result: &mut ValidationResult) -> Option<f64> {
+    let value = recipe.pointer(pointer)?;
+
+    let number = match value {
+        Value::Number(n) => match n.as_f64() {
+            Some(v) if v.is_finite() => v,
+            _ => {
+                result.error(codes::NON_FINITE, &[("path", &pointer), ("value", n)]);
+                return None;
+            }
+        },
+        Value::String(s) if is_non_finite_word(s) => {
+            result.error(codes::NON_FINITE, &[("path", &pointer), ("value", &format!("\"{}\"", s))]);
+            return None;
+        }
+        Value::String(s) => {
+            let parsed = parse_string_number(s)?;
+            if !parsed.is_finite() {
+                result.error(codes::NON_FINITE, &[("path", &pointer), ("value", &format!("\"{}\"", s))]);
+                return None;
+            }
+            result.warning(codes::NUMBER_AS_STRING, &[("path", &pointer), ("value", s)]);
+            parsed
+        }
+        _ => return None,
+    };
+
+    if !(field.min..=field.max).contains(&number) {
+        result.error(codes::OUT_OF_RANGE, &[
+            ("path", &pointer),
+            ("value", &number),
+            ("min", &field.min),
+            ("max", &field.max),
+        ]);
+        return None;
+    }
+
+    if number == 0.0 && number.is_sign_negative() {
+        result.warning(codes::NEGATIVE_ZERO, &[("path", &pointer)]);
+        return Some(0.0);
+    }
+
+    Some(number)
+}
+
+/// Concrete JSON pointers matching a field path
+pub fn expand(recipe: &Value, path: &str) -> Vec<String> {
+    let mut pointers = vec![(String::new(), recipe)];
+
+    for segment in path.trim_start_matches('/').split('/') {
+        let mut next = Vec::new();
+        for (pointer, value) in pointers {
+            match (segment, value) {
+                ("*", Value::Array(items)) => {
+                    for (i, item) in items.iter().enumerate() {
+                        next.push((format!("{}/{}", pointer, i), item));
+                    }
+                }
+                ("*", Value::Object(map)) => {
+                    for (key, item) in map {
+                        next.push((format!("{}/{}", pointer, escape_token(key)), item));
+                    }
+                }
+                (key, _) => {
+                    if let Some(item) = value.get(key) {
+                        next.push((format!("{}/{}", pointer, key), item));
+                    }
+                }
+            }
+        }
+        pointers = next;
+    }
+
+    pointers.into_iter().map(|(pointer, _)| pointer).collect()
+}
+
+fn escape_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Check every known numeric field of a document
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    for field in FIELDS {
+        for pointer in expand(recipe, field.path) {
+            finite_at(recipe, &pointer, field, result);
+        }
+    }
+}
+
+/// Convert numbers encoded as strings into JSON numbers and negative zero
+/// into zero. Returns the pointers that were changed.
+pub fn normalize(recipe: &mut Value) -> Vec<String> {
+    let mut changed = Vec::new();
+
+    for field in FIELDS {
+        for pointer in expand(recipe, field.path) {
+            let replacement = match recipe.pointer(&pointer) {
+                Some(Value::String(s)) if !is_non_finite_word(s) => parse_string_number(s)
+                    .filter(|v| v.is_finite())
+                    .map(number_value),
+                Some(Value::Number(n)) if n.as_f64().map(|v| v == 0.0 && v.is_sign_negative()).unwrap_or(false) => {
+                    Some(Value::from(0))
+                }
+                _ => None,
+            };
+
+            if let (Some(new_value), Some(slot)) = (replacement, recipe.pointer_mut(&pointer)) {
+                *slot = new_value;
+                changed.push(pointer);
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(recipe: &Value) -> ValidationResult {
+        let mut result = ValidationResult::new();
+        check(recipe, &mut result);
+        result
+    }
+
+    fn amount(literal: &str) -> Value {
+        serde_json::from_str(&format!(
+            r#"{{"ingredients": [{{"id": "ing-0001", "machine_amount": {{"value": {}, "unit": "g"}}}}]}}"#,
+            literal
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_weird_literals() {
+        // Accepted as-is
+        for literal in ["0", "12.5", "1E2", "1e-400", "0.000001"] {
+            let result = run(&amount(literal));
+            assert!(result.valid && result.warnings.is_empty(), "{} should pass", literal);
+        }
+
+        // Out of range
+        for literal in ["1e308", "-1", "18446744073709551616", "-1e-300"] {
+            let result = run(&amount(literal));
+            assert!(!result.valid, "{} should be rejected", literal);
+            assert!(result.errors[0].contains("outside the allowed range"), "{}", literal);
+        }
+
+        // Non-finite strings
+        for literal in [r#""NaN""#, r#""Infinity""#, r#""-inf""#, r#""1e400""#] {
+            let result = run(&amount(literal));
+            assert!(!result.valid, "{} should be rejected", literal);
+            assert!(result.errors[0].contains("not a finite number"), "{}", literal);
+        }
+
+        // Numbers as strings and negative zero are fixable warnings
+        for literal in [r#""12""#, r#"" 7.5 ""#, "-0", "-0.0"] {
+            let result = run(&amount(literal));
+            assert!(result.valid, "{} should only warn", literal);
+            assert!(result.warnings[0].contains("(fixable)"), "{}", literal);
+        }
+
+        // Non-numeric strings are left to the schema
+        let result = run(&amount(r#""0x10""#));
+        assert!(result.valid && result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_wildcard_paths() {
+        let recipe = json!({
+            "ingredients": [{"nutritional": {"per_100g": {"calories": 400, "fat": -3}}}],
+            "steps": [
+                {"params": {"temperature_c": -300}},
+                {"params": {"time_minutes": 5}, "done_when": {"temperature_c": 75}}
+            ]
+        });
+
+        let result = run(&recipe);
+        assert_eq!(result.errors.len(), 2);
+        assert!(result.errors.iter().any(|e| e.starts_with("/ingredients/0/nutritional/per_100g/fat")));
+        assert!(result.errors.iter().any(|e| e.starts_with("/steps/0/params/temperature_c")));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut recipe = json!({
+            "meta": {"servings": {"amount": "4"}},
+            "steps": [{"params": {"time_minutes": "2.5", "temperature_c": "NaN"}}]
+        });
+        recipe["ingredients"] = amount("-0")["ingredients"].clone();
+
+        let changed = normalize(&mut recipe);
+        assert_eq!(changed.len(), 3);
+        assert_eq!(recipe["meta"]["servings"]["amount"], json!(4));
+        assert_eq!(recipe["steps"][0]["params"]["time_minutes"], json!(2.5));
+        assert_eq!(recipe["steps"][0]["params"]["temperature_c"], json!("NaN"));
+        assert_eq!(recipe["ingredients"][0]["machine_amount"]["value"], json!(0));
+        assert!(run(&recipe).warnings.is_empty());
+    }
+}
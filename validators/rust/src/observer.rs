@@ -0,0 +1,357 @@
+// Validation events
+//
+// A `ValidationObserver` follows a validation while it runs: every phase
+// (the schema, each family of built-in rules, the info extraction) starts,
+// reports the issues it found and ends with its duration, and directory
+// validation adds an event before and after each file. Every method has a
+// no-op default; `NoopObserver` overrides none of them and is what the plain
+// `validate` uses. Issues carry their effective severity, after
+// `set_severity` overrides, and arrive in rule order, before the final sort.
+// Phases for opt-in rules that are switched off are not reported.
+//
+// An observer that panics is not called again for the rest of that
+// validation; the panic is reported as RCIP-E-OBSERVER-FAILED in the result
+// instead of unwinding through the validator.
+
+use crate::{codes, Issue, ValidationResult};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::time::Duration;
+
+/// A step of validation, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    Schema,
+    UnknownFields,
+    RecipeId,
+    Numeric,
+    Ingredients,
+    Allergens,
+    Claims,
+    Markets,
+    Steps,
+    IngredientOrder,
+    IdSequence,
+    Baking,
+    TextQuality,
+    Language,
+    Guidance,
+    MiseEnPlace,
+    Chilling,
+    ColdChain,
+    References,
+    DeviceLimits,
+    Simulation,
+    RulePacks,
+    Version,
+    Warnings,
+    CustomRules,
+    Info,
+}
+
+impl Phase {
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Schema => "schema",
+            Phase::UnknownFields => "unknown-fields",
+            Phase::RecipeId => "recipe-id",
+            Phase::Numeric => "numeric",
+            Phase::Ingredients => "ingredients",
+            Phase::Allergens => "allergens",
+            Phase::Claims => "claims",
+            Phase::Markets => "markets",
+            Phase::Steps => "steps",
+            Phase::IngredientOrder => "ingredient-order",
+            Phase::IdSequence => "id-sequence",
+            Phase::Baking => "baking",
+            Phase::TextQuality => "text-quality",
+            Phase::Language => "language",
+            Phase::Guidance => "guidance",
+            Phase::MiseEnPlace => "mise-en-place",
+            Phase::Chilling => "chilling",
+            Phase::ColdChain => "cold-chain",
+            Phase::References => "references",
+            Phase::DeviceLimits => "device-limits",
+            Phase::Simulation => "simulation",
+            Phase::RulePacks => "rule-packs",
+            Phase::Version => "version",
+            Phase::Warnings => "warnings",
+            Phase::CustomRules => "custom-rules",
+            Phase::Info => "info",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Receives validation events; implement only the ones you need
+pub trait ValidationObserver {
+    fn on_phase_start(&mut self, _phase: Phase) {}
+    fn on_issue(&mut self, _issue: &Issue) {}
+    fn on_phase_end(&mut self, _phase: Phase, _elapsed: Duration) {}
+    fn on_file_start(&mut self, _path: &Path) {}
+    fn on_file_end(&mut self, _path: &Path, _result: &ValidationResult) {}
+}
+
+/// Ignores every event
+pub struct NoopObserver;
+
+impl ValidationObserver for NoopObserver {}
+
+/// Buffers the events of one validation so they can be delivered later, in
+/// file order, when files are validated in parallel
+#[cfg(not(feature = "no-fs"))]
+#[derive(Default)]
+pub(crate) struct Recorder {
+    events: Vec<Event>,
+}
+
+#[cfg(not(feature = "no-fs"))]
+enum Event {
+    PhaseStart(Phase),
+    Issue(Issue),
+    PhaseEnd(Phase, Duration),
+}
+
+#[cfg(not(feature = "no-fs"))]
+impl ValidationObserver for Recorder {
+    fn on_phase_start(&mut self, phase: Phase) {
+        self.events.push(Event::PhaseStart(phase));
+    }
+
+    fn on_issue(&mut self, issue: &Issue) {
+        self.events.push(Event::Issue(issue.clone()));
+    }
+
+    fn on_phase_end(&mut self, phase: Phase, elapsed: Duration) {
+        self.events.push(Event::PhaseEnd(phase, elapsed));
+    }
+}
+
+#[cfg(not(feature = "no-fs"))]
+impl Recorder {
+    /// Deliver the buffered events, in the order they happened
+    pub(crate) fn replay(self, guard: &mut Guard) {
+        let mut stage = "";
+        for event in self.events {
+            match event {
+                Event::PhaseStart(phase) => {
+                    stage = phase.name();
+                    guard.notify(stage, |observer| observer.on_phase_start(phase));
+                }
+                Event::Issue(issue) => guard.notify(stage, |observer| observer.on_issue(&issue)),
+                Event::PhaseEnd(phase, elapsed) => guard.notify(phase.name(), |observer| observer.on_phase_end(phase, elapsed)),
+            }
+        }
+    }
+}
+
+/// Time spent in each phase, summed over every recipe validated; backs the
+/// CLI's `--profile-rules`
+#[derive(Debug, Default, Clone)]
+pub struct RuleProfile {
+    pub phases: BTreeMap<Phase, PhaseTiming>,
+    current: Option<Phase>,
+}
+
+/// How often a phase ran, what it found and how long it took in total
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PhaseTiming {
+    pub runs: u32,
+    pub issues: u32,
+    pub total: Duration,
+}
+
+impl RuleProfile {
+    pub fn new() -> Self {
+        RuleProfile::default()
+    }
+}
+
+impl ValidationObserver for RuleProfile {
+    fn on_phase_start(&mut self, phase: Phase) {
+        self.current = Some(phase);
+        self.phases.entry(phase).or_default().runs += 1;
+    }
+
+    fn on_issue(&mut self, _issue: &Issue) {
+        if let Some(phase) = self.current {
+            self.phases.entry(phase).or_default().issues += 1;
+        }
+    }
+
+    fn on_phase_end(&mut self, phase: Phase, elapsed: Duration) {
+        self.current = None;
+        self.phases.entry(phase).or_default().total += elapsed;
+    }
+}
+
+impl fmt::Display for RuleProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows: Vec<(&Phase, &PhaseTiming)> = self.phases.iter().collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        writeln!(f, "{:<18} {:>6} {:>7} {:>12}", "phase", "runs", "issues", "total ms")?;
+        for (phase, timing) in rows {
+            writeln!(
+                f,
+                "{:<18} {:>6} {:>7} {:>12.3}",
+                phase.name(),
+                timing.runs,
+                timing.issues,
+                timing.total.as_secs_f64() * 1000.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Delivers events to an observer, catching its panics. After the first
+/// panic the observer is left alone and the failure waits to be reported
+pub(crate) struct Guard<'a> {
+    observer: &'a mut dyn ValidationObserver,
+    failure: Option<(&'static str, String)>,
+    reported: bool,
+}
+
+impl<'a> Guard<'a> {
+    pub(crate) fn new(observer: &'a mut dyn ValidationObserver) -> Self {
+        Guard { observer, failure: None, reported: false }
+    }
+
+    /// Deliver one event; `stage` names where it happened for the report
+    pub(crate) fn notify(&mut self, stage: &'static str, event: impl FnOnce(&mut dyn ValidationObserver)) {
+        if self.failure.is_some() {
+            return;
+        }
+        let observer = &mut *self.observer;
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| event(observer))) {
+            self.failure = Some((stage, panic_message(payload)));
+        }
+    }
+
+    /// Add the observer's failure to a result, once; true if it was added
+    pub(crate) fn report(&mut self, result: &mut ValidationResult) -> bool {
+        match &self.failure {
+            Some((stage, message)) if !self.reported => {
+                result.error(codes::OBSERVER_FAILED, &[("stage", stage), ("message", message)]);
+                self.reported = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RCIPValidator;
+    use serde_json::json;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl ValidationObserver for Recorder {
+        fn on_phase_start(&mut self, phase: Phase) {
+            self.events.push(format!("start {}", phase));
+        }
+
+        fn on_issue(&mut self, issue: &Issue) {
+            self.events.push(format!("issue {}", issue.code));
+        }
+
+        fn on_phase_end(&mut self, phase: Phase, _elapsed: Duration) {
+            self.events.push(format!("end {}", phase));
+        }
+    }
+
+    struct Panicking;
+
+    impl ValidationObserver for Panicking {
+        fn on_phase_end(&mut self, phase: Phase, _elapsed: Duration) {
+            if phase == Phase::Ingredients {
+                panic!("observer bug");
+            }
+        }
+    }
+
+    fn validator() -> RCIPValidator {
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init_from_str(include_str!("../../../schemas/rcip-v0.1.json")).unwrap();
+        validator
+    }
+
+    fn recipe() -> serde_json::Value {
+        serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap()
+    }
+
+    #[test]
+    fn test_phases_and_issues_in_order() {
+        let mut recorder = Recorder::default();
+        let result = validator().validate_recipe_with_observer(&recipe(), &mut recorder);
+        assert_eq!(recorder.events.first().map(String::as_str), Some("start schema"));
+        assert_eq!(recorder.events.last().map(String::as_str), Some("end info"));
+        assert!(!recorder.events.contains(&"start simulation".to_string()));
+
+        let issues: Vec<&str> = recorder.events.iter().filter_map(|e| e.strip_prefix("issue ")).collect();
+        let mut codes: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        codes.sort();
+        let mut seen = issues.clone();
+        seen.sort();
+        assert_eq!(seen, codes);
+        let warnings = recorder.events.iter().position(|e| e == "start warnings").unwrap();
+        let no_images = recorder.events.iter().position(|e| e == "issue RCIP-W-NO-IMAGES").unwrap();
+        assert!(no_images > warnings);
+    }
+
+    #[test]
+    fn test_noop_observer_matches_validate() {
+        let validator = validator();
+        let mut broken = recipe();
+        broken["ingredients"][0]["allergens"] = json!("none");
+        let plain = validator.validate(&broken);
+        let observed = validator.validate_recipe_with_observer(&broken, &mut NoopObserver);
+        assert_eq!(plain.errors, observed.errors);
+        assert_eq!(plain.warnings, observed.warnings);
+        assert_eq!(plain.issues, observed.issues);
+    }
+
+    #[test]
+    fn test_panicking_observer_becomes_an_error() {
+        let validator = validator();
+        let result = validator.validate_recipe_with_observer(&recipe(), &mut Panicking);
+        assert!(!result.valid);
+        assert!(result.errors.contains(&"Validation observer failed in ingredients: observer bug".to_string()));
+        assert_eq!(result.warnings, validator.validate(&recipe()).warnings);
+    }
+
+    #[test]
+    fn test_rule_profile_counts_runs_and_issues() {
+        let validator = validator();
+        let mut profile = RuleProfile::new();
+        validator.validate_recipe_with_observer(&recipe(), &mut profile);
+        validator.validate_recipe_with_observer(&recipe(), &mut profile);
+        assert_eq!(profile.phases[&Phase::Schema].runs, 2);
+        assert_eq!(profile.phases[&Phase::Warnings].issues, 2);
+        assert!(profile.to_string().starts_with("phase"));
+    }
+}
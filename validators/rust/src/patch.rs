@@ -0,0 +1,336 @@
+// JSON Patch
+//
+// Applies RFC 6902 patches (add, remove, replace, move, copy, test) as sent
+// by collaborative editors. A patch is applied to a copy and only returned if
+// every operation succeeds, so a failing `test` or a bad path leaves the base
+// document untouched. Errors name the failing operation by index.
+//
+// The patched document is validated as a whole, except for the two
+// item-scoped phases: `Phase::Ingredients` and `Phase::Steps` check each
+// array item on its own, so an item's issues depend only on that item, its
+// index and the validator's settings. The validator keeps the issues each
+// item produced in the last patched document (`ItemCache`) and replays them
+// for an item that is unchanged at the same index, which is the common case
+// while an editor changes one field at a time. Every other phase reads the
+// whole recipe and always runs. The cache is dropped when the settings
+// fingerprint changes, so the result is always the one a full validation
+// gives.
+
+use crate::observer::Phase;
+use crate::{Issue, RCIPError, RCIPValidator, ValidationResult};
+use serde_json::Value;
+use std::collections::HashMap;
+
+fn tokens(pointer: &str) -> Result<Vec<String>, String> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    match pointer.strip_prefix('/') {
+        Some(rest) => Ok(rest.split('/').map(|t| t.replace("~1", "/").replace("~0", "~")).collect()),
+        None => Err(format!("'{}' is not a JSON pointer", pointer)),
+    }
+}
+
+fn missing(pointer: &str) -> String {
+    format!("path '{}' does not exist", pointer)
+}
+
+/// Array index for a pointer token; `-` and `len` only when inserting
+fn index(token: &str, len: usize, inserting: bool, pointer: &str) -> Result<usize, String> {
+    if inserting && token == "-" {
+        return Ok(len);
+    }
+    let valid = !token.is_empty() && token.bytes().all(|b| b.is_ascii_digit()) && (token == "0" || !token.starts_with('0'));
+    let limit = if inserting { len } else { len.saturating_sub(1) };
+    match token.parse::<usize>() {
+        Ok(i) if valid && (i <= limit) && (inserting || len > 0) => Ok(i),
+        _ => Err(missing(pointer)),
+    }
+}
+
+/// The container holding the last token of `pointer`, and that token
+fn parent<'a>(doc: &'a mut Value, pointer: &str) -> Result<(&'a mut Value, String), String> {
+    let mut tokens = tokens(pointer)?;
+    let last = tokens.pop().ok_or_else(|| "the document root has no parent".to_string())?;
+    let mut node = doc;
+    for token in &tokens {
+        node = match node {
+            Value::Object(map) => map.get_mut(token),
+            Value::Array(items) => {
+                let i = index(token, items.len(), false, pointer)?;
+                items.get_mut(i)
+            }
+            _ => None,
+        }
+        .ok_or_else(|| missing(pointer))?;
+    }
+    Ok((node, last))
+}
+
+fn add(doc: &mut Value, pointer: &str, value: Value) -> Result<(), String> {
+    if pointer.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (node, last) = parent(doc, pointer)?;
+    match node {
+        Value::Object(map) => {
+            map.insert(last, value);
+        }
+        Value::Array(items) => {
+            let i = index(&last, items.len(), true, pointer)?;
+            items.insert(i, value);
+        }
+        _ => return Err(missing(pointer)),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut Value, pointer: &str) -> Result<Value, String> {
+    let (node, last) = parent(doc, pointer)?;
+    match node {
+        Value::Object(map) => map.remove(&last).ok_or_else(|| missing(pointer)),
+        Value::Array(items) => {
+            let i = index(&last, items.len(), false, pointer)?;
+            Ok(items.remove(i))
+        }
+        _ => Err(missing(pointer)),
+    }
+}
+
+fn get<'a>(doc: &'a Value, pointer: &str) -> Result<&'a Value, String> {
+    tokens(pointer)?;
+    doc.pointer(pointer).ok_or_else(|| missing(pointer))
+}
+
+fn field<'a>(operation: &'a Value, name: &str) -> Result<&'a Value, String> {
+    operation.get(name).ok_or_else(|| format!("missing '{}'", name))
+}
+
+fn pointer_field<'a>(operation: &'a Value, name: &str) -> Result<&'a str, String> {
+    field(operation, name)?.as_str().ok_or_else(|| format!("'{}' must be a string", name))
+}
+
+fn apply_operation(doc: &mut Value, operation: &Value) -> Result<(), String> {
+    let op = pointer_field(operation, "op")?;
+    let path = pointer_field(operation, "path")?;
+
+    match op {
+        "add" => add(doc, path, field(operation, "value")?.clone()),
+        "remove" => remove(doc, path).map(|_| ()),
+        "replace" => {
+            let value = field(operation, "value")?.clone();
+            tokens(path)?;
+            *doc.pointer_mut(path).ok_or_else(|| missing(path))? = value;
+            Ok(())
+        }
+        "move" => {
+            let from = pointer_field(operation, "from")?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move '{}' into its own child '{}'", from, path));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        "copy" => {
+            let value = get(doc, pointer_field(operation, "from")?)?.clone();
+            add(doc, path, value)
+        }
+        "test" => {
+            let expected = field(operation, "value")?;
+            if get(doc, path)? == expected {
+                Ok(())
+            } else {
+                Err(format!("test failed: value at '{}' differs", path))
+            }
+        }
+        other => Err(format!("unknown operation '{}'", other)),
+    }
+}
+
+/// Apply a JSON Patch, returning the patched copy of `base`
+pub fn apply(base: &Value, patch: &Value) -> Result<Value, RCIPError> {
+    let operations = patch.as_array().ok_or_else(|| RCIPError::PatchError("patch must be an array".to_string()))?;
+    let mut doc = base.clone();
+    for (i, operation) in operations.iter().enumerate() {
+        apply_operation(&mut doc, operation).map_err(|e| {
+            let op = operation.get("op").and_then(|v| v.as_str()).unwrap_or("?");
+            RCIPError::PatchError(format!("operation {} ({}): {}", i, op, e))
+        })?;
+    }
+    Ok(doc)
+}
+
+/// Issues each item of an item-scoped phase produced, kept from one
+/// patched document to the next
+#[derive(Debug, Default)]
+pub(crate) struct ItemCache {
+    fingerprint: String,
+    /// The item and its issues, by phase and index
+    previous: HashMap<(Phase, usize), (Value, Vec<Issue>)>,
+    current: HashMap<(Phase, usize), (Value, Vec<Issue>)>,
+    /// Items whose issues were replayed in the last validation
+    reused: usize,
+}
+
+impl ItemCache {
+    /// Start a validation with settings of the given fingerprint
+    pub(crate) fn begin(&mut self, fingerprint: &str) {
+        if self.fingerprint != fingerprint {
+            self.fingerprint = fingerprint.to_string();
+            self.previous.clear();
+        }
+        self.current.clear();
+        self.reused = 0;
+    }
+
+    /// Keep this validation's items for the next one
+    pub(crate) fn finish(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Run `rules` on item `index` of an item-scoped phase, or replay the
+    /// issues they gave last time if the item has not changed
+    pub(crate) fn check(
+        cache: Option<&mut ItemCache>,
+        phase: Phase,
+        index: usize,
+        item: &Value,
+        result: &mut ValidationResult,
+        rules: impl FnOnce(&mut ValidationResult),
+    ) {
+        let Some(cache) = cache else {
+            return rules(result);
+        };
+        let entry = match cache.previous.remove(&(phase, index)) {
+            Some((cached, issues)) if cached == *item => {
+                cache.reused += 1;
+                for issue in &issues {
+                    result.push(issue.clone());
+                }
+                (cached, issues)
+            }
+            _ => {
+                let before = result.issues.len();
+                rules(result);
+                (item.clone(), result.issues[before..].to_vec())
+            }
+        };
+        cache.current.insert((phase, index), entry);
+    }
+}
+
+/// Apply a JSON Patch and validate the result, reusing the issues of the
+/// ingredients and steps left as they were in the last patched document
+pub fn apply_and_validate(
+    validator: &mut RCIPValidator,
+    base: &Value,
+    patch: &Value,
+) -> Result<(Value, ValidationResult), RCIPError> {
+    let patched = apply(base, patch)?;
+    let mut cache = std::mem::take(&mut validator.item_cache);
+    let result = validator.validate_cached(&patched, &mut cache);
+    validator.item_cache = cache;
+    validator.record_stats(&result);
+    Ok((patched, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn error(result: Result<Value, RCIPError>) -> String {
+        match result {
+            Err(RCIPError::PatchError(message)) => message,
+            other => panic!("expected a patch error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_operations() {
+        let base = json!({"a/b": 1, "list": [1, 2], "nested": {"x": "y"}});
+        let patch = json!([
+            {"op": "test", "path": "/a~1b", "value": 1},
+            {"op": "add", "path": "/list/-", "value": 3},
+            {"op": "add", "path": "/list/0", "value": 0},
+            {"op": "remove", "path": "/list/1"},
+            {"op": "replace", "path": "/nested/x", "value": "z"},
+            {"op": "copy", "from": "/nested", "path": "/copied"},
+            {"op": "move", "from": "/a~1b", "path": "/moved"}
+        ]);
+        let patched = apply(&base, &patch).unwrap();
+        assert_eq!(patched, json!({"list": [0, 2, 3], "nested": {"x": "z"}, "copied": {"x": "z"}, "moved": 1}));
+    }
+
+    #[test]
+    fn test_errors_name_the_operation() {
+        let base = json!({"list": [1], "nested": {"x": 1}});
+        assert_eq!(
+            error(apply(&base, &json!([{"op": "remove", "path": "/list/0"}, {"op": "remove", "path": "/list/0"}]))),
+            "operation 1 (remove): path '/list/0' does not exist"
+        );
+        assert_eq!(
+            error(apply(&base, &json!([{"op": "test", "path": "/nested/x", "value": 2}]))),
+            "operation 0 (test): test failed: value at '/nested/x' differs"
+        );
+        assert_eq!(
+            error(apply(&base, &json!([{"op": "add", "path": "/missing/x", "value": 2}]))),
+            "operation 0 (add): path '/missing/x' does not exist"
+        );
+        assert_eq!(
+            error(apply(&base, &json!([{"op": "move", "from": "/nested", "path": "/nested/inner"}]))),
+            "operation 0 (move): cannot move '/nested' into its own child '/nested/inner'"
+        );
+        assert_eq!(
+            error(apply(&base, &json!([{"op": "add", "path": "/list/01", "value": 2}]))),
+            "operation 0 (add): path '/list/01' does not exist"
+        );
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_matches_full_revalidation() {
+        let mut doc: Value = serde_json::from_str(include_str!("../../../examples/margherita-pizza.rcip")).unwrap();
+        let ingredients = doc["ingredients"].as_array().unwrap().len();
+        let steps = doc["steps"].as_array().unwrap().len();
+        let patches = [
+            json!([{"op": "replace", "path": "/steps/0/human_text", "value": "TODO"}]),
+            json!([
+                {"op": "add", "path": "/ingredients/0/allergens/-", "value": "sesame"},
+                {"op": "replace", "path": "/ingredients/1/id", "value": "ing-1"},
+                {"op": "remove", "path": "/meta/description"}
+            ]),
+            json!([{"op": "remove", "path": "/ingredients/0/allergens"}]),
+            json!([{"op": "move", "from": "/ingredients/2", "path": "/ingredients/0"}]),
+            json!([{"op": "replace", "path": "/steps/1/action", "value": "juggle"}]),
+        ];
+
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let mut from_scratch = RCIPValidator::new("0.1");
+        from_scratch.init(None).unwrap();
+        let mut reused = Vec::new();
+        for patch in &patches {
+            let (patched, incremental) = apply_and_validate(&mut validator, &doc, patch).unwrap();
+            let full = from_scratch.validate(&patched);
+            assert_eq!(incremental.valid, full.valid);
+            assert_eq!(incremental.issues, full.issues);
+            assert_eq!(incremental.errors, full.errors);
+            assert_eq!(incremental.warnings, full.warnings);
+            assert_eq!(incremental.notices, full.notices);
+            reused.push(validator.item_cache.reused);
+            doc = patched;
+        }
+
+        // Nothing is cached before the first patch; then every item but the
+        // changed ones is replayed, and a move changes every index it shifts
+        let items = ingredients + steps;
+        assert_eq!(reused, [0, items - 2, items - 1, items - 3, items - 1]);
+
+        // Other settings drop the cache
+        validator.set_taxonomy_warnings(true);
+        apply_and_validate(&mut validator, &doc, &json!([])).unwrap();
+        assert_eq!(validator.item_cache.reused, 0);
+    }
+}
@@ -0,0 +1,192 @@
+// Divide steps and portions
+//
+// A `divide` step splits what it works on into portions, and
+// `params.portions` says how many. Later steps take one portion as
+// `s-05:result[2]` or `@dough[2]`, counting from 1; a reference without an
+// index still means the whole result. Portion references are checked
+// against the declared count: an index past it, or a portion of a step that
+// is not a counted divide, is an error. A divide without a count is a
+// warning, and so is a divide whose portions are taken one by one but not
+// all of them.
+//
+// Content tracking gives each portion an equal share of the divided result,
+// and the simulator reports a portion used by two steps.
+
+use crate::intermediates::producer;
+use crate::{codes, ValidationResult};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+fn steps(recipe: &Value) -> impl Iterator<Item = &Value> {
+    recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten()
+}
+
+fn step_id(step: &Value) -> &str {
+    step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?")
+}
+
+fn targets(step: &Value) -> impl Iterator<Item = &str> {
+    step.get("target").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|t| t.as_str())
+}
+
+/// Split a portion reference into the result it names and the portion
+/// number. None for targets without a `[n]` suffix; a suffix that is not a
+/// number reads as portion 0, which is never in range
+pub fn split(target: &str) -> Option<(&str, usize)> {
+    let (whole, index) = target.strip_suffix(']')?.rsplit_once('[')?;
+    Some((whole, index.parse().unwrap_or(0)))
+}
+
+/// The target without its portion suffix
+pub fn whole(target: &str) -> &str {
+    split(target).map(|(whole, _)| whole).unwrap_or(target)
+}
+
+/// Portions a divide step declares; None for other steps and divides
+/// without a count
+pub fn portion_count(step: &Value) -> Option<usize> {
+    if step.get("action").and_then(|v| v.as_str()) != Some("divide") {
+        return None;
+    }
+    step.pointer("/params/portions").and_then(|v| v.as_u64()).filter(|n| *n >= 1).map(|n| n as usize)
+}
+
+/// Share of its producer's result a target takes: 1 for whole results,
+/// 1/N for one of N portions
+pub fn share(recipe: &Value, target: &str) -> f64 {
+    if split(target).is_none() {
+        return 1.0;
+    }
+    producer(recipe, target)
+        .and_then(|id| steps(recipe).find(|s| step_id(s) == id))
+        .and_then(portion_count)
+        .map(|n| 1.0 / n as f64)
+        .unwrap_or(1.0)
+}
+
+/// Report divides without a count, portion references that do not fit the
+/// producing step, and divides with portions left over
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    let counts: HashMap<&str, Option<usize>> = steps(recipe)
+        .filter(|s| s.get("action").and_then(|v| v.as_str()) == Some("divide"))
+        .map(|s| (step_id(s), portion_count(s)))
+        .collect();
+    let known: HashSet<&str> = steps(recipe).map(step_id).collect();
+
+    for step in steps(recipe) {
+        if counts.get(step_id(step)) == Some(&None) {
+            result.warning(codes::DIVIDE_WITHOUT_PORTIONS, &[("step", &step_id(step))]);
+        }
+    }
+
+    let mut taken: HashMap<&str, BTreeSet<usize>> = HashMap::new();
+    let mut taken_whole: HashSet<&str> = HashSet::new();
+    for step in steps(recipe) {
+        for target in targets(step) {
+            // Unknown steps and names are reported with the other references
+            let from = match producer(recipe, target).filter(|id| known.contains(id)) {
+                Some(from) => from,
+                None => continue,
+            };
+            let index = match split(target) {
+                Some((_, index)) => index,
+                None => {
+                    taken_whole.insert(from);
+                    continue;
+                }
+            };
+            match counts.get(from).copied().flatten() {
+                None => result.error(codes::PORTION_OF_UNDIVIDED, &[
+                    ("step", &step_id(step)),
+                    ("target", &target),
+                    ("producer", &from),
+                ]),
+                Some(portions) if index == 0 || index > portions => result.error(codes::PORTION_OUT_OF_RANGE, &[
+                    ("step", &step_id(step)),
+                    ("target", &target),
+                    ("index", &index),
+                    ("producer", &from),
+                    ("portions", &portions),
+                ]),
+                Some(_) => {
+                    taken.entry(from).or_default().insert(index);
+                }
+            }
+        }
+    }
+
+    for step in steps(recipe) {
+        let id = step_id(step);
+        let (portions, used) = match (counts.get(id).copied().flatten(), taken.get(id)) {
+            (Some(portions), Some(used)) if !taken_whole.contains(id) => (portions, used),
+            _ => continue,
+        };
+        let unused: Vec<String> = (1..=portions).filter(|i| !used.contains(i)).map(|i| i.to_string()).collect();
+        if !unused.is_empty() {
+            result.warning(codes::UNUSED_PORTIONS, &[("step", &id), ("unused", &unused.join(", ")), ("portions", &portions)]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe(uses: Value) -> Value {
+        json!({
+            "ingredients": [{"id": "ing-0001", "machine_amount": {"value": 800, "unit": "g"}}],
+            "steps": [
+                {"step_id": "s-01", "action": "knead", "target": ["ing-0001"], "result": {"name": "dough"}},
+                {"step_id": "s-02", "action": "divide", "target": ["@dough"], "params": {"portions": 4}},
+                {"step_id": "s-03", "action": "shape", "target": uses}
+            ]
+        })
+    }
+
+    fn issues(recipe: &Value) -> (Vec<String>, Vec<String>) {
+        let mut result = ValidationResult::new();
+        check(recipe, &mut result);
+        (result.errors, result.warnings)
+    }
+
+    #[test]
+    fn test_split_portion_references() {
+        assert_eq!(split("s-05:result[2]"), Some(("s-05:result", 2)));
+        assert_eq!(split("@dough[12]"), Some(("@dough", 12)));
+        assert_eq!(split("@dough[x]"), Some(("@dough", 0)));
+        assert_eq!(split("s-05:result"), None);
+        assert_eq!(whole("@dough[3]"), "@dough");
+        assert_eq!(whole("ing-0001"), "ing-0001");
+
+        let recipe = recipe(json!(["s-02:result[1]"]));
+        assert_eq!(producer(&recipe, "s-02:result[1]"), Some("s-02"));
+        assert_eq!(share(&recipe, "s-02:result[1]"), 0.25);
+        assert_eq!(share(&recipe, "s-02:result"), 1.0);
+    }
+
+    #[test]
+    fn test_portion_references_are_checked() {
+        let all = recipe(json!(["s-02:result[1]", "s-02:result[2]", "s-02:result[3]", "s-02:result[4]"]));
+        assert_eq!(issues(&all), (vec![], vec![]));
+
+        let (errors, warnings) = issues(&recipe(json!(["s-02:result[5]", "s-01:result[1]", "s-02:result[2]"])));
+        assert_eq!(errors, vec![
+            "Step s-03: s-02:result[5] refers to portion 5, but s-02 divides into 4 portions",
+            "Step s-03: s-01:result[1] refers to a portion, but s-01 is not a divide step with a portion count",
+        ]);
+        assert_eq!(warnings, vec!["Step s-02: Portions 1, 3, 4 of 4 are never used"]);
+
+        // Taking the whole result leaves nothing over
+        assert_eq!(issues(&recipe(json!(["s-02:result[1]", "s-02:result"]))), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_divide_without_count() {
+        let mut recipe = recipe(json!(["s-02:result[1]"]));
+        recipe["steps"][1]["params"] = json!({});
+        let (errors, warnings) = issues(&recipe);
+        assert_eq!(warnings, vec!["Step s-02: Divide step does not say how many portions it makes (params.portions)"]);
+        assert_eq!(errors.len(), 1);
+    }
+}
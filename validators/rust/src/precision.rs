@@ -0,0 +1,264 @@
+// Machine-amount precision policy
+//
+// Robotic dispensers work at a fixed resolution per unit, so machine amounts
+// carrying more decimal places than the device can dispense accumulate error.
+// Units a policy's `decimals` option names override the default limits; the
+// other units keep theirs.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Number, Value};
+use std::collections::HashMap;
+
+/// Per-unit precision policy for machine amounts
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PrecisionPolicy {
+    /// Maximum number of decimal places allowed per unit
+    #[serde(deserialize_with = "over_default_decimals")]
+    pub decimals: HashMap<String, u32>,
+    /// Units that can only be dispensed in whole numbers
+    pub count_units: Vec<String>,
+}
+
+impl Default for PrecisionPolicy {
+    fn default() -> Self {
+        let mut decimals = HashMap::new();
+        decimals.insert("g".to_string(), 1);
+        decimals.insert("ml".to_string(), 1);
+        decimals.insert("kg".to_string(), 2);
+        decimals.insert("l".to_string(), 2);
+        decimals.insert("pcs".to_string(), 0);
+
+        PrecisionPolicy {
+            decimals,
+            count_units: vec!["pcs".to_string()],
+        }
+    }
+}
+
+/// Decimal limits given in options, over the default ones
+fn over_default_decimals<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<String, u32>, D::Error> {
+    let mut decimals = PrecisionPolicy::default().decimals;
+    decimals.extend(HashMap::<String, u32>::deserialize(deserializer)?);
+    Ok(decimals)
+}
+
+impl PrecisionPolicy {
+    /// Load a policy from rule options, e.g. `{"decimals": {"g": 0}, "count_units": ["pcs"]}`;
+    /// units missing from `decimals` keep their default limits
+    pub fn from_value(options: &Value) -> Result<Self, serde_json::Error> {
+        PrecisionPolicy::deserialize(options)
+    }
+
+    /// Allowed decimal places for a unit, if the policy constrains it
+    pub fn decimals_for(&self, unit: &str) -> Option<u32> {
+        if self.is_count_unit(unit) {
+            return Some(0);
+        }
+        self.decimals.get(unit).copied()
+    }
+
+    /// Whether the unit only accepts whole numbers
+    pub fn is_count_unit(&self, unit: &str) -> bool {
+        self.count_units.iter().any(|u| u == unit)
+    }
+}
+
+/// Precision problem found on a single machine amount
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrecisionIssue {
+    /// Value has more decimal places than the unit allows
+    TooPrecise { decimals: u32, allowed: u32 },
+    /// Fractional value for a count unit (e.g. 2.5 pcs of egg)
+    NotWholeCount,
+}
+
+/// Check a machine amount value against the policy
+pub fn check(value: &Number, unit: &str, policy: &PrecisionPolicy) -> Option<PrecisionIssue> {
+    let decimals = decimal_places(value);
+
+    if policy.is_count_unit(unit) {
+        return if decimals > 0 { Some(PrecisionIssue::NotWholeCount) } else { None };
+    }
+
+    match policy.decimals_for(unit) {
+        Some(allowed) if decimals > allowed => Some(PrecisionIssue::TooPrecise { decimals, allowed }),
+        _ => None,
+    }
+}
+
+/// Number of significant decimal places in a JSON number as written
+pub fn decimal_places(value: &Number) -> u32 {
+    if value.is_i64() || value.is_u64() {
+        return 0;
+    }
+
+    let text = value.to_string().to_lowercase();
+    let (mantissa, exponent) = match text.split_once('e') {
+        Some((m, e)) => (m.to_string(), e.parse::<i32>().unwrap_or(0)),
+        None => (text, 0),
+    };
+
+    let fraction = mantissa
+        .split_once('.')
+        .map(|(_, f)| f.trim_end_matches('0').len() as i32)
+        .unwrap_or(0);
+
+    (fraction - exponent).max(0) as u32
+}
+
+/// Round to the given number of decimal places, ties to even
+pub fn round_half_even(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    let scaled = value * factor;
+    let floor = scaled.floor();
+
+    let rounded = if (scaled - floor - 0.5).abs() < 1e-9 {
+        if floor % 2.0 == 0.0 { floor } else { floor + 1.0 }
+    } else {
+        scaled.round()
+    };
+
+    rounded / factor
+}
+
+/// A machine amount changed by `round_amounts`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AmountAdjustment {
+    pub ingredient_id: String,
+    pub unit: String,
+    pub from: f64,
+    pub to: f64,
+}
+
+/// Round every machine amount to the precision allowed by the policy.
+///
+/// Returns one entry per value that was changed.
+pub fn round_amounts(recipe: &mut Value, policy: &PrecisionPolicy) -> Vec<AmountAdjustment> {
+    let mut adjustments = Vec::new();
+
+    let ingredients = match recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()) {
+        Some(ingredients) => ingredients,
+        None => return adjustments,
+    };
+
+    for ingredient in ingredients {
+        let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        let ma = match ingredient.get_mut("machine_amount").and_then(|v| v.as_object_mut()) {
+            Some(ma) => ma,
+            None => continue,
+        };
+
+        let unit = match ma.get("unit").and_then(|v| v.as_str()) {
+            Some(unit) => unit.to_string(),
+            None => continue,
+        };
+        let allowed = match policy.decimals_for(&unit) {
+            Some(allowed) => allowed,
+            None => continue,
+        };
+        let number = match ma.get("value") {
+            Some(Value::Number(n)) => n.clone(),
+            _ => continue,
+        };
+
+        if decimal_places(&number) <= allowed {
+            continue;
+        }
+
+        let from = number.as_f64().unwrap_or(0.0);
+        let to = round_half_even(from, allowed);
+        ma.insert("value".to_string(), number_value(to));
+        adjustments.push(AmountAdjustment { ingredient_id: id, unit, from, to });
+    }
+
+    adjustments
+}
+
+/// JSON number for a rounded value, written as an integer when whole
+pub(crate) fn number_value(value: f64) -> Value {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        Value::from(value as i64)
+    } else {
+        Value::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn number(value: Value) -> Number {
+        match value {
+            Value::Number(n) => n,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_decimal_places() {
+        assert_eq!(decimal_places(&number(json!(500))), 0);
+        assert_eq!(decimal_places(&number(json!(12.5))), 1);
+        assert_eq!(decimal_places(&number(json!(33.333333333))), 9);
+        assert_eq!(decimal_places(&number(json!(1e-7))), 7);
+    }
+
+    #[test]
+    fn test_check_against_default_policy() {
+        let policy = PrecisionPolicy::default();
+        assert_eq!(check(&number(json!(12.5)), "g", &policy), None);
+        assert_eq!(
+            check(&number(json!(33.333)), "g", &policy),
+            Some(PrecisionIssue::TooPrecise { decimals: 3, allowed: 1 })
+        );
+        assert_eq!(check(&number(json!(2.5)), "pcs", &policy), Some(PrecisionIssue::NotWholeCount));
+        assert_eq!(check(&number(json!(1.2345)), "tsp", &policy), None);
+    }
+
+    #[test]
+    fn test_round_half_even() {
+        assert_eq!(round_half_even(2.5, 0), 2.0);
+        assert_eq!(round_half_even(3.5, 0), 4.0);
+        assert_eq!(round_half_even(0.25, 1), 0.2);
+        assert_eq!(round_half_even(33.333333333, 1), 33.3);
+    }
+
+    #[test]
+    fn test_round_amounts_records_adjustments() {
+        let mut recipe = json!({
+            "ingredients": [
+                {"id": "ing-0001", "machine_amount": {"value": 33.333333333, "unit": "g"}},
+                {"id": "ing-0002", "machine_amount": {"value": 250, "unit": "ml"}},
+                {"id": "ing-0003", "machine_amount": {"value": 1.125, "unit": "kg"}}
+            ]
+        });
+
+        let adjustments = round_amounts(&mut recipe, &PrecisionPolicy::default());
+
+        assert_eq!(adjustments.len(), 2);
+        assert_eq!(adjustments[0].ingredient_id, "ing-0001");
+        assert_eq!(recipe["ingredients"][0]["machine_amount"]["value"], json!(33.3));
+        assert_eq!(recipe["ingredients"][1]["machine_amount"]["value"], json!(250));
+        assert_eq!(recipe["ingredients"][2]["machine_amount"]["value"], json!(1.12));
+    }
+
+    #[test]
+    fn test_policy_from_options() {
+        let policy = PrecisionPolicy::from_value(&json!({"decimals": {"g": 0, "oz": 2}})).unwrap();
+        assert_eq!(policy.decimals_for("g"), Some(0));
+        assert_eq!(policy.decimals_for("oz"), Some(2));
+        // Units the option leaves out keep their defaults
+        assert_eq!(policy.decimals_for("ml"), Some(1));
+        assert_eq!(policy.decimals_for("kg"), Some(2));
+        assert_eq!(policy.decimals_for("l"), Some(2));
+        assert!(policy.is_count_unit("pcs"));
+
+        let mut validator = crate::RCIPValidator::with_bundled_schema("0.1").unwrap();
+        validator.apply_setting("precision_policy", &json!({"decimals": {"g": 0}})).unwrap();
+        let applied = validator.validate_recipe(&json!({"ingredients": [
+            {"id": "ing-0001", "machine_amount": {"value": 1.25, "unit": "ml"}}
+        ]}));
+        assert!(applied.issues.iter().any(|i| i.code == crate::codes::TOO_PRECISE), "{:?}", applied.issues);
+    }
+}
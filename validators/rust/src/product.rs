@@ -0,0 +1,215 @@
+// Branded products
+//
+// An ingredient may name a specific commercial product in an optional
+// `product` object (`brand`, `product_name`, `gtin`, `required`). A product
+// that is not required is a suggestion and is rendered as "(or equivalent)"
+// wherever ingredient names are exported. A required product must be
+// machine-resolvable, i.e. carry a GTIN either on the product or in
+// `external_ids`, or at least some other external ID. Listing substitutes
+// for a required product is contradictory and is flagged as a warning.
+
+use crate::{codes, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Whether `code` is a GTIN-8, -12, -13 or -14 with a correct check digit
+pub fn gtin_valid(code: &str) -> bool {
+    if !matches!(code.len(), 8 | 12 | 13 | 14) || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+
+    let digits: Vec<u32> = code.bytes().map(|b| (b - b'0') as u32).collect();
+    let (body, check) = digits.split_at(digits.len() - 1);
+    // Weights alternate 3, 1, ... starting from the digit next to the check digit
+    let sum: u32 = body.iter().rev().enumerate().map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d }).sum();
+
+    (10 - sum % 10) % 10 == check[0]
+}
+
+/// Brand and product name joined for display, e.g. "Valrhona Guanaja 70%"
+pub fn product_label(product: &Value) -> Option<String> {
+    let parts: Vec<&str> = ["brand", "product_name"]
+        .iter()
+        .filter_map(|key| product.get(*key).and_then(|v| v.as_str()))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" "))
+    }
+}
+
+fn is_required(product: &Value) -> bool {
+    product.get("required").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// Ingredient name for exports, with the product appended. Products that are
+/// not required are suffixed "(or equivalent)".
+pub fn display_name(ingredient: &Value) -> String {
+    let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("");
+    let product = match ingredient.get("product") {
+        Some(product) => product,
+        None => return name.to_string(),
+    };
+
+    match product_label(product) {
+        Some(label) if is_required(product) => format!("{}, {}", name, label),
+        Some(label) => format!("{}, {} (or equivalent)", name, label),
+        None => name.to_string(),
+    }
+}
+
+/// Check the `product` declaration of an ingredient
+pub fn check(ingredient: &Value, index: usize, result: &mut ValidationResult) {
+    let product = match ingredient.get("product") {
+        Some(product) => product,
+        None => return,
+    };
+
+    let gtin = product.get("gtin").and_then(|v| v.as_str());
+    if let Some(gtin) = gtin {
+        if !gtin_valid(gtin) {
+            result.error(codes::BAD_GTIN, &[("index", &index), ("gtin", &gtin)]);
+        }
+    }
+
+    if !is_required(product) {
+        return;
+    }
+
+    let label = product_label(product).unwrap_or_else(|| "?".to_string());
+    let has_external_id = ingredient.get("external_ids")
+        .and_then(|v| v.as_object())
+        .map(|ids| ids.values().any(|id| id.as_str().map(|s| !s.trim().is_empty()).unwrap_or(false)))
+        .unwrap_or(false);
+    if gtin.is_none() && !has_external_id {
+        result.error(codes::UNRESOLVABLE_PRODUCT, &[("index", &index), ("product", &label)]);
+    }
+
+    let has_substitutes = ingredient.get("substitutes")
+        .and_then(|v| v.as_array())
+        .map(|a| !a.is_empty())
+        .unwrap_or(false);
+    if has_substitutes {
+        result.warning(codes::REQUIRED_PRODUCT_SUBSTITUTES, &[("index", &index), ("product", &label)]);
+    }
+}
+
+/// A branded ingredient, as listed in the `branded_ingredients` catalog facet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrandedIngredient {
+    pub recipe_id: String,
+    pub ingredient_id: String,
+    pub name: String,
+    pub product_name: Option<String>,
+    pub gtin: Option<String>,
+    pub required: bool,
+}
+
+/// Branded ingredients across a set of recipes, grouped by brand, so
+/// procurement can see which products a catalog depends on
+pub fn branded_ingredients<'a, I>(recipes: I) -> BTreeMap<String, Vec<BrandedIngredient>>
+where
+    I: IntoIterator<Item = &'a Value>,
+{
+    let mut facet: BTreeMap<String, Vec<BrandedIngredient>> = BTreeMap::new();
+
+    for recipe in recipes {
+        let recipe_id = recipe.get("id").and_then(|v| v.as_str()).unwrap_or("?");
+        for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+            let product = match ingredient.get("product") {
+                Some(product) => product,
+                None => continue,
+            };
+            let brand = match product.get("brand").and_then(|v| v.as_str()) {
+                Some(brand) if !brand.trim().is_empty() => brand.trim(),
+                _ => continue,
+            };
+            let text = |key: &str| product.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+            facet.entry(brand.to_string()).or_default().push(BrandedIngredient {
+                recipe_id: recipe_id.to_string(),
+                ingredient_id: ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string(),
+                name: ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                product_name: text("product_name"),
+                gtin: text("gtin"),
+                required: is_required(product),
+            });
+        }
+    }
+
+    facet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn chocolate(product: Value) -> Value {
+        json!({"id": "ing-0001", "name": "dark chocolate", "allergens": ["milk"], "product": product})
+    }
+
+    #[test]
+    fn test_gtin_check_digit() {
+        assert!(gtin_valid("4006381333931"));
+        assert!(gtin_valid("96385074"));
+        assert!(gtin_valid("036000291452"));
+        assert!(gtin_valid("10614141000415"));
+        assert!(!gtin_valid("4006381333932"));
+        assert!(!gtin_valid("400638133393"));
+        assert!(!gtin_valid("40063813339a1"));
+    }
+
+    #[test]
+    fn test_required_products() {
+        let mut result = ValidationResult::new();
+        check(&chocolate(json!({"brand": "Valrhona", "gtin": "4006381333932", "required": false})), 0, &mut result);
+        assert_eq!(result.issues[0].code, codes::BAD_GTIN);
+
+        let mut result = ValidationResult::new();
+        let mut ingredient = chocolate(json!({"brand": "Valrhona", "product_name": "Guanaja 70%", "required": true}));
+        ingredient["substitutes"] = json!([{"name": "any 70% dark chocolate", "ratio": 1}]);
+        check(&ingredient, 0, &mut result);
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::UNRESOLVABLE_PRODUCT, codes::REQUIRED_PRODUCT_SUBSTITUTES]);
+        assert_eq!(result.errors[0], "Ingredient 0: required product 'Valrhona Guanaja 70%' needs a GTIN or another external ID");
+
+        let mut result = ValidationResult::new();
+        ingredient["external_ids"] = json!({"OpenFoodFacts": "3395328430017"});
+        ingredient.as_object_mut().unwrap().remove("substitutes");
+        check(&ingredient, 0, &mut result);
+        assert!(result.valid && result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_display_name() {
+        let suggested = chocolate(json!({"brand": "Valrhona", "product_name": "Guanaja 70%"}));
+        assert_eq!(display_name(&suggested), "dark chocolate, Valrhona Guanaja 70% (or equivalent)");
+        let required = chocolate(json!({"brand": "Valrhona", "required": true, "gtin": "4006381333931"}));
+        assert_eq!(display_name(&required), "dark chocolate, Valrhona");
+        assert_eq!(display_name(&json!({"name": "salt"})), "salt");
+    }
+
+    #[test]
+    fn test_branded_facet() {
+        let recipes = vec![
+            json!({"id": "rcip-a", "ingredients": [
+                chocolate(json!({"brand": "Valrhona", "gtin": "4006381333931", "required": true})),
+                {"id": "ing-0002", "name": "sugar"}
+            ]}),
+            json!({"id": "rcip-b", "ingredients": [chocolate(json!({"brand": "Valrhona"}))]}),
+        ];
+
+        let facet = branded_ingredients(&recipes);
+        assert_eq!(facet.len(), 1);
+        let items = &facet["Valrhona"];
+        assert_eq!(items.len(), 2);
+        assert_eq!((items[0].recipe_id.as_str(), items[0].required), ("rcip-a", true));
+        assert_eq!(items[1].gtin, None);
+    }
+}
@@ -0,0 +1,402 @@
+// Project manifests
+//
+// A repository can declare its recipe directories in an `rcip.toml` at its
+// root. Each named root has a directory, include/exclude globs relative to
+// it, a schema version (and optionally a schema file), a profile, rule
+// settings and severity overrides:
+//
+//     [roots.bakery]
+//     path = "recipes/bakery"
+//     include = ["**/*.rcip"]
+//     exclude = ["drafts/**"]
+//     schema_version = "0.1"
+//     profile = "strict"
+//
+//     [roots.bakery.rules]
+//     chill_policy = { require_params = false }
+//
+//     [roots.bakery.severity]
+//     "RCIP-W-NO-IMAGES" = "error"
+//
+// Every root gets a fresh validator, so settings never leak between roots.
+// Settings apply in order profile, then rules, then severities; rules and
+// severities are attributed to the config file in `--explain-config`. A file
+// matched by several roots belongs to the first one declared. Problems in
+// the manifest are all reported, each with its line and column.
+
+use crate::discover::{self, DirectoryOptions};
+use crate::explain::SettingSource;
+use crate::{codes, RCIPError, RCIPValidator, Severity, ValidationResult};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+const DEFAULT_INCLUDE: &str = "**/*.rcip";
+const DEFAULT_SCHEMA_VERSION: &str = "0.1";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    #[serde(default)]
+    roots: BTreeMap<String, Spanned<RawRoot>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRoot {
+    path: Spanned<String>,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    schema_version: Option<String>,
+    schema: Option<String>,
+    profile: Option<Spanned<String>>,
+    #[serde(default)]
+    rules: BTreeMap<String, Spanned<toml::Value>>,
+    #[serde(default)]
+    severity: BTreeMap<String, Spanned<String>>,
+}
+
+/// Errors loading a project manifest
+#[derive(Debug)]
+pub enum ProjectError {
+    Io(io::Error),
+    Toml(String),
+    /// Every problem found, as `file:line:column: message`
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ProjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectError::Io(e) => write!(f, "cannot read project manifest: {}", e),
+            ProjectError::Toml(e) => write!(f, "project manifest is not valid TOML: {}", e),
+            ProjectError::Invalid(problems) => write!(f, "invalid project manifest:\n  {}", problems.join("\n  ")),
+        }
+    }
+}
+
+impl std::error::Error for ProjectError {}
+
+/// A directory of recipes with its own validation settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Root {
+    pub name: String,
+    pub dir: PathBuf,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub schema_version: String,
+    pub schema: PathBuf,
+    pub profile: Option<String>,
+    pub rules: Vec<(String, Value)>,
+    pub severities: Vec<(&'static str, Severity)>,
+}
+
+/// A parsed and checked `rcip.toml`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    pub path: PathBuf,
+    /// In declaration order
+    pub roots: Vec<Root>,
+}
+
+/// One validated file and the root it was validated under
+#[derive(Debug, Clone)]
+pub struct ProjectFile {
+    pub root: String,
+    pub path: PathBuf,
+    pub result: ValidationResult,
+}
+
+/// Results for every file of every root
+#[derive(Debug, Clone, Default)]
+pub struct ProjectReport {
+    pub files: Vec<ProjectFile>,
+}
+
+/// 1-based line and column of a byte offset
+fn location(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Match a `/`-separated relative path against a glob: `*` and `?` within a
+/// path segment, `**` for any number of segments
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    match_segments(&pattern, &path)
+}
+
+fn match_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_segments(rest, &path[skip..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let pattern: Vec<char> = segment.chars().collect();
+                let name: Vec<char> = name.chars().collect();
+                match_segment(&pattern, &name) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| match_segment(rest, &name[skip..])),
+        Some(('?', rest)) => !name.is_empty() && match_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && match_segment(rest, &name[1..]),
+    }
+}
+
+impl Manifest {
+    /// Load and check a manifest file
+    pub fn from_file(path: &Path) -> Result<Self, ProjectError> {
+        let source = fs::read_to_string(path).map_err(ProjectError::Io)?;
+        Self::parse(&source, path)
+    }
+
+    /// Check manifest text; `path` locates the roots and names the file in
+    /// problem reports
+    pub fn parse(source: &str, path: &Path) -> Result<Self, ProjectError> {
+        let raw: RawManifest = toml::from_str(source).map_err(|e| ProjectError::Toml(e.to_string()))?;
+        let base = path.parent().unwrap_or(Path::new(""));
+
+        let mut problems = Vec::new();
+        let mut problem = |offset: usize, message: String| {
+            let (line, column) = location(source, offset);
+            problems.push(format!("{}:{}:{}: {}", path.display(), line, column, message));
+        };
+        if raw.roots.is_empty() {
+            problem(0, "no roots declared; add a [roots.<name>] table".to_string());
+        }
+
+        // Declaration order, not the map's name order
+        let mut declared: Vec<(String, Spanned<RawRoot>)> = raw.roots.into_iter().collect();
+        declared.sort_by_key(|(_, root)| root.span().start);
+
+        let mut roots = Vec::new();
+        for (name, spanned) in declared {
+            let raw = spanned.into_inner();
+            let dir = base.join(raw.path.get_ref());
+            if !dir.is_dir() {
+                problem(raw.path.span().start, format!("root '{}': {} is not a directory", name, dir.display()));
+            }
+
+            let schema_version = raw.schema_version.unwrap_or_else(|| DEFAULT_SCHEMA_VERSION.to_string());
+            let schema = match &raw.schema {
+                Some(schema) => base.join(schema),
+                None => base.join(format!("schemas/rcip-v{}.json", schema_version)),
+            };
+
+            // Settings are tried on a scratch validator, which knows them all
+            let mut scratch = RCIPValidator::new(&schema_version);
+            let profile = raw.profile.map(|profile| {
+                if let Err(e) = scratch.set_profile(profile.get_ref()) {
+                    problem(profile.span().start, format!("root '{}': {}", name, e));
+                }
+                profile.into_inner()
+            });
+
+            let mut rules = Vec::new();
+            for (setting, value) in raw.rules {
+                let json = serde_json::to_value(value.get_ref()).unwrap_or(Value::Null);
+                match scratch.apply_setting(&setting, &json) {
+                    Ok(()) => rules.push((setting, json)),
+                    Err(e) => problem(value.span().start, format!("root '{}': {}", name, e)),
+                }
+            }
+
+            let mut severities = Vec::new();
+            for (code, severity) in raw.severity {
+                let known = codes::ALL.iter().find(|c| **c == code);
+                let parsed = Severity::deserialize(&Value::String(severity.get_ref().clone()));
+                match (known, parsed) {
+                    (Some(code), Ok(parsed)) => severities.push((*code, parsed)),
+                    (None, _) => problem(severity.span().start, format!("root '{}': unknown rule code '{}'", name, code)),
+                    (_, Err(_)) => problem(
+                        severity.span().start,
+                        format!("root '{}': severity of {} must be error, warning or info, not '{}'", name, code, severity.get_ref()),
+                    ),
+                }
+            }
+
+            roots.push(Root {
+                name,
+                dir,
+                include: if raw.include.is_empty() { vec![DEFAULT_INCLUDE.to_string()] } else { raw.include },
+                exclude: raw.exclude,
+                schema_version,
+                schema,
+                profile,
+                rules,
+                severities,
+            });
+        }
+
+        if !problems.is_empty() {
+            return Err(ProjectError::Invalid(problems));
+        }
+        Ok(Manifest { path: path.to_path_buf(), roots })
+    }
+
+    /// Validate every root with its own settings
+    pub fn validate(&self) -> Result<ProjectReport, RCIPError> {
+        let mut report = ProjectReport::default();
+        let mut seen: HashSet<PathBuf> = HashSet::new();
+
+        for root in &self.roots {
+            let mut validator = root.validator()?;
+            for path in root.files()? {
+                if !seen.insert(path.clone()) {
+                    continue;
+                }
+                let result = validator.validate_file(&path).unwrap_or_else(|e| {
+                    let mut result = ValidationResult::new();
+                    result.meta = Some(validator.meta());
+                    result.error(codes::READ_FAILED, &[("error", &e)]);
+                    result
+                });
+                report.files.push(ProjectFile { root: root.name.clone(), path, result });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+impl Root {
+    /// A validator configured for this root alone
+    pub fn validator(&self) -> Result<RCIPValidator, RCIPError> {
+        let invalid = |e: String| RCIPError::ValidationError(format!("root '{}': {}", self.name, e));
+        let mut validator = RCIPValidator::new(&self.schema_version);
+        if let Some(profile) = &self.profile {
+            validator.set_profile(profile).map_err(|e| invalid(e.to_string()))?;
+        }
+        validator.set_setting_source(SettingSource::ConfigFile);
+        for (setting, value) in &self.rules {
+            validator.apply_setting(setting, value).map_err(invalid)?;
+        }
+        for (code, severity) in &self.severities {
+            validator.set_severity(code, *severity).map_err(invalid)?;
+        }
+        validator.init(Some(&self.schema))?;
+        Ok(validator)
+    }
+
+    /// Files under the root matching an include glob and no exclude glob,
+    /// sorted
+    pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let options = DirectoryOptions { recursive: true, include: self.include.clone(), exclude: self.exclude.clone() };
+        discover::find(&self.dir, &options)
+    }
+}
+
+impl ProjectReport {
+    /// Whether every file in every root is valid
+    pub fn valid(&self) -> bool {
+        self.files.iter().all(|f| f.result.valid)
+    }
+}
+
+impl fmt::Display for ProjectReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for file in &self.files {
+            let mark = if file.result.valid { "✅" } else { "❌" };
+            writeln!(f, "{} [{}] {}", mark, file.root, file.path.display())?;
+            for message in file.result.errors.iter().chain(&file.result.warnings) {
+                writeln!(f, "    - {}", message)?;
+            }
+        }
+        let failed = self.files.iter().filter(|file| !file.result.valid).count();
+        writeln!(f, "{} file(s), {} failed", self.files.len(), failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("**/*.rcip", "cake.rcip"));
+        assert!(glob_match("**/*.rcip", "bakery/bread/rye.rcip"));
+        assert!(glob_match("drafts/**", "drafts/new/x.rcip"));
+        assert!(glob_match("s?up-*.rcip", "soup-1.rcip"));
+        assert!(!glob_match("*.rcip", "bakery/rye.rcip"));
+        assert!(!glob_match("**/*.rcip", "notes.json"));
+    }
+
+    #[test]
+    fn test_manifest_problems_have_locations() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("bakery")).unwrap();
+        let source = r#"[roots.bakery]
+path = "bakery"
+profile = "paranoid"
+
+[roots.bakery.rules]
+mise_en_place = "yes"
+colour = true
+
+[roots.bakery.severity]
+"RCIP-W-NO-IMAGES" = "fatal"
+"RCIP-W-NOPE" = "error"
+"#;
+        let path = dir.path().join("rcip.toml");
+        let problems = match Manifest::parse(source, &path) {
+            Err(ProjectError::Invalid(problems)) => problems,
+            other => panic!("unexpected {:?}", other.map(|m| m.roots.len())),
+        };
+        let locations: Vec<&str> = problems.iter().map(|p| p.split(": ").next().unwrap().rsplit("rcip.toml:").next().unwrap()).collect();
+        assert_eq!(locations, vec!["3:11", "7:10", "6:17", "10:22", "11:17"]);
+        assert!(problems[0].ends_with("root 'bakery': unknown profile 'paranoid'; supported: default, strict"));
+        assert!(problems[1].ends_with("unknown setting 'colour'"));
+        assert!(problems[4].ends_with("unknown rule code 'RCIP-W-NOPE'"));
+
+        assert!(matches!(Manifest::parse("[roots.a]\npath = 3\n", &path), Err(ProjectError::Toml(_))));
+    }
+
+    #[test]
+    fn test_roots_keep_their_own_severities() {
+        let dir = tempfile::tempdir().unwrap();
+        let example = fs::read_to_string("../../examples/simple-example.rcip").unwrap();
+        for root in ["strict", "relaxed"] {
+            fs::create_dir_all(dir.path().join(root).join("drafts")).unwrap();
+            fs::write(dir.path().join(root).join("simple.rcip"), &example).unwrap();
+            fs::write(dir.path().join(root).join("drafts").join("draft.rcip"), "{").unwrap();
+        }
+        let schema = fs::canonicalize("../../schemas/rcip-v0.1.json").unwrap();
+        let source = format!(
+            "[roots.strict]\npath = \"strict\"\nexclude = [\"drafts/**\"]\nschema = {schema:?}\n\
+             [roots.strict.severity]\n\"RCIP-W-NO-IMAGES\" = \"error\"\n\n\
+             [roots.relaxed]\npath = \"relaxed\"\nexclude = [\"drafts/**\"]\nschema = {schema:?}\n\
+             [roots.relaxed.severity]\n\"RCIP-W-NO-IMAGES\" = \"info\"\n",
+            schema = schema.display().to_string()
+        );
+        let manifest = Manifest::parse(&source, &dir.path().join("rcip.toml")).unwrap();
+        assert_eq!(manifest.roots.iter().map(|r| r.name.as_str()).collect::<Vec<_>>(), vec!["strict", "relaxed"]);
+
+        let report = manifest.validate().unwrap();
+        assert_eq!(report.files.len(), 2);
+        let severity = |root: &str| {
+            let file = report.files.iter().find(|f| f.root == root).unwrap();
+            file.result.issues.iter().find(|i| i.code == codes::NO_IMAGES).map(|i| i.severity)
+        };
+        assert_eq!(severity("strict"), Some(Severity::Error));
+        assert_eq!(severity("relaxed"), Some(Severity::Info));
+        assert!(!report.valid());
+        assert!(report.files.iter().any(|f| f.root == "relaxed" && f.result.valid));
+    }
+}
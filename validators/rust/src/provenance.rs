@@ -0,0 +1,95 @@
+// Result provenance
+//
+// Every result carries a `ValidationMeta` naming the validator build, the
+// schema it was checked against (by version and by a SHA-256 of the schema
+// bytes actually loaded) and a hash of the effective rule configuration. Two
+// results with the same fingerprint were produced by identical logic, which
+// is what anything caching or snapshotting results must key on.
+
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Version of this validator build
+pub const VALIDATOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Which validator, schema and configuration produced a result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationMeta {
+    pub validator_version: String,
+    pub schema_version: String,
+    /// SHA-256 of the loaded schema file, if one was loaded
+    pub schema_sha256: Option<String>,
+    pub profile: String,
+    /// SHA-256 of the effective rule configuration
+    pub rule_config_hash: String,
+    /// RFC 3339, UTC
+    pub timestamp: String,
+}
+
+impl ValidationMeta {
+    /// Stamp a result produced now
+    pub fn new(schema_version: &str, schema_sha256: Option<String>, profile: &str, rule_config: &Value) -> Self {
+        ValidationMeta {
+            validator_version: VALIDATOR_VERSION.to_string(),
+            schema_version: schema_version.to_string(),
+            schema_sha256,
+            profile: profile.to_string(),
+            rule_config_hash: sha256_hex(rule_config.to_string().as_bytes()),
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    /// Everything except the timestamp; equal fingerprints mean equal logic
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}",
+            self.validator_version,
+            self.schema_version,
+            self.schema_sha256.as_deref().unwrap_or("-"),
+            self.profile,
+            self.rule_config_hash
+        )
+    }
+}
+
+impl fmt::Display for ValidationMeta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rcip-validator {}, schema v{}", self.validator_version, self.schema_version)?;
+        if let Some(sha) = &self.schema_sha256 {
+            write!(f, " (sha256 {})", &sha[..12])?;
+        }
+        write!(f, ", profile {}, config {}, {}", self.profile, &self.rule_config_hash[..12], self.timestamp)
+    }
+}
+
+/// Lowercase hex SHA-256 of some bytes
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_sha256() {
+        assert_eq!(sha256_hex(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_timestamp() {
+        let config = json!({"mise_en_place": false});
+        let mut a = ValidationMeta::new("0.1", Some(sha256_hex(b"{}")), "default", &config);
+        let b = ValidationMeta::new("0.1", Some(sha256_hex(b"{}")), "default", &config);
+        a.timestamp = "2000-01-01T00:00:00Z".to_string();
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        let c = ValidationMeta::new("0.1", Some(sha256_hex(b"{}")), "default", &json!({"mise_en_place": true}));
+        assert_ne!(a.fingerprint(), c.fingerprint());
+        assert!(a.to_string().starts_with(&format!("rcip-validator {}, schema v0.1 (sha256 44136fa355b3)", VALIDATOR_VERSION)));
+    }
+}
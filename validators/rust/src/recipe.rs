@@ -0,0 +1,240 @@
+// Typed recipes
+//
+// `Recipe` and the structs under it give consumers a typed view of a
+// document. Only the fields the structs name are typed; every other member,
+// extensions and `x-` fields included, is kept in the struct's `extra` map, so
+// `Recipe::to_value` gives back the document it was parsed from.
+//
+// Validation still runs on the `Value` tree: `RCIPValidator::validate_typed`
+// converts the recipe back and validates that, so a document produces the
+// same issues either way. Numbers are kept as `serde_json::Number`, so `5`
+// is written back as `5` and `5.0` as `5.0`: the precision rules tell the
+// two apart, and a round trip must not change what they see.
+//
+// Parsing is stricter than validation. A document with a missing required
+// field, a wrong type or a term spelled loosely ("Milk") does not parse;
+// validate the `Value` to find out why.
+
+use crate::{Allergen, CookingAction, DietLabel, Hazard, RCIPError, Unit};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+/// A recipe document
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Recipe {
+    pub rcip_version: String,
+    pub id: String,
+    pub meta: Meta,
+    pub ingredients: Vec<Ingredient>,
+    pub steps: Vec<Step>,
+    /// Every other top-level member: device profiles, images, extensions, ...
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Recipe metadata
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Meta {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub author: Author,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diet_labels: Option<Vec<DietLabel>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prep_time_minutes: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cook_time_minutes: Option<Number>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_time_minutes: Option<Number>,
+    pub created_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// `meta.author`: a plain name or a person with contact details
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Author {
+    Name(String),
+    Person {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        email: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        organization: Option<String>,
+        #[serde(flatten)]
+        extra: Map<String, Value>,
+    },
+}
+
+impl Author {
+    /// The author's name, whichever form it is given in
+    pub fn name(&self) -> &str {
+        match self {
+            Author::Name(name) | Author::Person { name, .. } => name,
+        }
+    }
+}
+
+/// An ingredient
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Ingredient {
+    pub id: String,
+    pub name: String,
+    pub human_amount: String,
+    pub machine_amount: MachineAmount,
+    pub allergens: Vec<Allergen>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub may_contain_allergens: Option<Vec<Allergen>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optional: Option<bool>,
+    /// Nutrition, substitutes, storage and every other member
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// An ingredient amount in machine-readable form
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MachineAmount {
+    pub value: Number,
+    pub unit: Unit,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approximate: Option<bool>,
+    /// `tolerance` and any other member
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A step
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Step {
+    pub step_id: String,
+    pub human_text: String,
+    pub action: CookingAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hazards: Option<Vec<Hazard>>,
+    /// `params`, `done_when`, tips and any other member
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl MachineAmount {
+    /// The amount as a float, however it is written
+    pub fn amount(&self) -> f64 {
+        self.value.as_f64().unwrap_or(f64::NAN)
+    }
+}
+
+/// A computed amount as JSON writes it: a whole number as an integer, so
+/// `200.0` becomes `200`, anything else as a decimal; negative zero keeps its
+/// sign. None for NaN and infinities
+pub fn number(value: f64) -> Option<Number> {
+    let whole = value.fract() == 0.0 && value.abs() < 9e15 && !(value == 0.0 && value.is_sign_negative());
+    if whole {
+        Some(Number::from(value as i64))
+    } else {
+        Number::from_f64(value)
+    }
+}
+
+impl Recipe {
+    /// Parse a recipe from a JSON tree
+    pub fn from_value(recipe: &Value) -> Result<Recipe, RCIPError> {
+        Ok(Recipe::deserialize(recipe)?)
+    }
+
+    /// Read and parse a recipe file
+    #[cfg(not(feature = "no-fs"))]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Recipe, RCIPError> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The recipe as a JSON tree, as validation and the other modules take it
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("a recipe serializes to JSON")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RCIPValidator;
+    use serde_json::json;
+
+    fn examples() -> Vec<Value> {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples");
+        let mut paths: Vec<_> = std::fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).collect();
+        paths.sort();
+        paths.iter().map(|p| serde_json::from_str(&std::fs::read_to_string(p).unwrap()).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_examples_round_trip() {
+        for example in examples() {
+            let recipe = RCIPValidator::parse_recipe(&example).unwrap();
+            assert_eq!(recipe.to_value(), example, "{}", example["id"]);
+        }
+    }
+
+    #[test]
+    fn test_numbers_keep_their_form() {
+        let mut recipe = examples().remove(0);
+        recipe["ingredients"][0]["machine_amount"]["value"] = serde_json::from_str("5.0").unwrap();
+        recipe["ingredients"][1]["machine_amount"]["value"] = json!(5);
+        recipe["meta"]["total_time_minutes"] = serde_json::from_str("45.0").unwrap();
+
+        let typed = RCIPValidator::parse_recipe(&recipe).unwrap();
+        assert_eq!(typed.ingredients[0].machine_amount.amount(), 5.0);
+        let written = typed.to_value();
+        assert_eq!(written, recipe);
+        assert_eq!(written["ingredients"][0]["machine_amount"]["value"].to_string(), "5.0");
+        assert_eq!(written["ingredients"][1]["machine_amount"]["value"].to_string(), "5");
+        assert_eq!(written["meta"]["total_time_minutes"].to_string(), "45.0");
+
+        assert_eq!(number(200.0), Some(Number::from(200)));
+        assert_eq!(number(-0.0).unwrap().to_string(), "-0.0");
+        assert_eq!(number(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_typed_and_untyped_validation_agree() {
+        let validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let mut recipe = examples().remove(0);
+        recipe["ingredients"][0]["machine_amount"]["value"] = json!(-0.0);
+        recipe["ingredients"][0]["machine_amount"]["x-scale"] = json!(2.5);
+        recipe["steps"][0]["target"] = json!(["ing-missing"]);
+
+        let typed = RCIPValidator::parse_recipe(&recipe).unwrap();
+        let (untyped, typed) = (validator.validate(&recipe), validator.validate_typed(&typed));
+        assert!(!untyped.issues.is_empty());
+        assert_eq!(typed.errors, untyped.errors);
+        assert_eq!(typed.warnings, untyped.warnings);
+        assert_eq!(typed.issues, untyped.issues);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let mut recipe = examples().remove(0);
+        recipe["ingredients"][0]["allergens"] = json!(["Milk"]);
+        assert!(matches!(RCIPValidator::parse_recipe(&recipe), Err(RCIPError::JsonError(_))));
+
+        let recipe = json!({"rcip_version": "0.1", "id": "rcip-x", "meta": {"name": "X"}});
+        assert!(RCIPValidator::parse_recipe(&recipe).is_err());
+    }
+}
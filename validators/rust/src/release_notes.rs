@@ -0,0 +1,318 @@
+// Release notes
+//
+// Summarizes what changed in a recipe directory between two git refs, for a
+// tagged release: recipes added, removed and modified, each modification
+// classified by `diff::diff_recipes`, and totals (recipes at each ref,
+// allergens the collection newly covers or no longer covers). Both versions
+// of a file come from `git show <ref>:<path>`, so nothing is checked out.
+//
+// Renamed, binary and otherwise unusual entries, and recipes that do not
+// parse at either ref, are listed under "Needs attention" instead of
+// stopping the run.
+
+use crate::allergens;
+use crate::diff::{diff_recipes, ChangeLevel, RecipeDiff};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// Errors running git
+#[derive(Debug)]
+pub enum ReleaseNotesError {
+    Io(io::Error),
+    /// A git command failed, with its stderr
+    Git(String),
+}
+
+impl fmt::Display for ReleaseNotesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReleaseNotesError::Io(e) => write!(f, "cannot run git: {}", e),
+            ReleaseNotesError::Git(e) => write!(f, "git failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReleaseNotesError {}
+
+/// A recipe file added or removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipeRef {
+    pub path: String,
+    pub name: String,
+}
+
+/// A recipe present at both refs with different content
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModifiedRecipe {
+    pub path: String,
+    pub name: String,
+    pub diff: RecipeDiff,
+}
+
+impl ModifiedRecipe {
+    /// Level of the largest change; a change git sees but the diff does
+    /// not, such as reformatting, counts as a patch
+    pub fn level(&self) -> ChangeLevel {
+        self.diff.level().unwrap_or(ChangeLevel::Patch)
+    }
+}
+
+/// Everything that changed between two refs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseNotes {
+    pub from: String,
+    pub to: String,
+    pub added: Vec<RecipeRef>,
+    pub removed: Vec<RecipeRef>,
+    pub modified: Vec<ModifiedRecipe>,
+    /// Entries that could not be summarized, with the reason
+    pub attention: Vec<(String, String)>,
+    pub recipes_before: usize,
+    pub recipes_after: usize,
+    pub allergens_added: BTreeSet<String>,
+    pub allergens_removed: BTreeSet<String>,
+}
+
+fn is_recipe(path: &str) -> bool {
+    path.ends_with(".rcip") || path.ends_with(".json")
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<Vec<u8>, ReleaseNotesError> {
+    let output = Command::new("git").args(args).current_dir(dir).output().map_err(ReleaseNotesError::Io)?;
+    if !output.status.success() {
+        return Err(ReleaseNotesError::Git(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(output.stdout)
+}
+
+/// A recipe at a ref, or why it cannot be read as one
+fn recipe_at(dir: &Path, rev: &str, path: &str) -> Result<Value, String> {
+    let bytes = git(dir, &["show", &format!("{}:./{}", rev, path)]).map_err(|e| e.to_string())?;
+    if bytes.contains(&0) {
+        return Err(format!("binary file at {}", rev));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("does not parse at {}: {}", rev, e))
+}
+
+fn recipe_name(recipe: &Value, path: &str) -> String {
+    recipe.pointer("/meta/name").and_then(|v| v.as_str()).unwrap_or(path).to_string()
+}
+
+/// Recipe files under `dir` at a ref, relative to `dir`
+fn recipes_at(dir: &Path, rev: &str) -> Result<Vec<String>, ReleaseNotesError> {
+    let listing = git(dir, &["ls-tree", "-r", "-z", "--name-only", rev, "--", "."])?;
+    Ok(listing
+        .split(|b| *b == 0)
+        .map(|p| String::from_utf8_lossy(p).into_owned())
+        .filter(|p| is_recipe(p))
+        .collect())
+}
+
+/// Allergens contained by any recipe that parses at a ref, under the default
+/// provenance policy
+fn coverage(dir: &Path, rev: &str, paths: &[String]) -> BTreeSet<String> {
+    let policy = allergens::ProvenancePolicy::default();
+    paths
+        .iter()
+        .filter_map(|p| recipe_at(dir, rev, p).ok())
+        .flat_map(|r| allergens::contained_with(&r, &policy))
+        .collect()
+}
+
+/// Compare the recipes under `dir`, which must be inside a git work tree,
+/// between two refs
+pub fn release_notes(dir: &Path, from: &str, to: &str) -> Result<ReleaseNotes, ReleaseNotesError> {
+    let status = git(dir, &["diff", "-z", "--name-status", "-M", "--relative", from, to, "--", "."])?;
+    let mut fields = status.split(|b| *b == 0).map(|f| String::from_utf8_lossy(f).into_owned());
+
+    let mut notes = ReleaseNotes {
+        from: from.to_string(),
+        to: to.to_string(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        modified: Vec::new(),
+        attention: Vec::new(),
+        recipes_before: 0,
+        recipes_after: 0,
+        allergens_added: BTreeSet::new(),
+        allergens_removed: BTreeSet::new(),
+    };
+
+    while let Some(code) = fields.next().filter(|c| !c.is_empty()) {
+        let path = fields.next().unwrap_or_default();
+        if code.starts_with('R') || code.starts_with('C') {
+            let target = fields.next().unwrap_or_default();
+            if is_recipe(&path) || is_recipe(&target) {
+                let verb = if code.starts_with('R') { "renamed" } else { "copied" };
+                notes.attention.push((target, format!("{} from {}", verb, path)));
+            }
+            continue;
+        }
+        if !is_recipe(&path) {
+            continue;
+        }
+        match code.as_str() {
+            "A" => match recipe_at(dir, to, &path) {
+                Ok(recipe) => notes.added.push(RecipeRef { name: recipe_name(&recipe, &path), path }),
+                Err(reason) => notes.attention.push((path, reason)),
+            },
+            "D" => match recipe_at(dir, from, &path) {
+                Ok(recipe) => notes.removed.push(RecipeRef { name: recipe_name(&recipe, &path), path }),
+                Err(reason) => notes.attention.push((path, reason)),
+            },
+            "M" => match (recipe_at(dir, from, &path), recipe_at(dir, to, &path)) {
+                (Ok(old), Ok(new)) => notes.modified.push(ModifiedRecipe {
+                    name: recipe_name(&new, &path),
+                    diff: diff_recipes(&old, &new),
+                    path,
+                }),
+                (Err(reason), _) | (_, Err(reason)) => notes.attention.push((path, reason)),
+            },
+            other => notes.attention.push((path, format!("unexpected change type {}", other))),
+        }
+    }
+
+    let before = recipes_at(dir, from)?;
+    let after = recipes_at(dir, to)?;
+    notes.recipes_before = before.len();
+    notes.recipes_after = after.len();
+    let (covered_before, covered_after) = (coverage(dir, from, &before), coverage(dir, to, &after));
+    notes.allergens_added = covered_after.difference(&covered_before).cloned().collect();
+    notes.allergens_removed = covered_before.difference(&covered_after).cloned().collect();
+    Ok(notes)
+}
+
+fn list(items: &BTreeSet<String>) -> String {
+    if items.is_empty() {
+        "none".to_string()
+    } else {
+        items.iter().cloned().collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl ReleaseNotes {
+    /// Markdown grouped by change type, modified recipes by level
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Release notes: {} → {}\n\n", self.from, self.to);
+        out.push_str(&format!(
+            "{} recipes (was {}): {} added, {} removed, {} modified.\n",
+            self.recipes_after,
+            self.recipes_before,
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        ));
+        out.push_str(&format!("Newly covered allergens: {}.\n", list(&self.allergens_added)));
+        out.push_str(&format!("No longer covered allergens: {}.\n", list(&self.allergens_removed)));
+
+        for (title, recipes) in [("Added", &self.added), ("Removed", &self.removed)] {
+            if !recipes.is_empty() {
+                out.push_str(&format!("\n## {} ({})\n\n", title, recipes.len()));
+                for recipe in recipes {
+                    out.push_str(&format!("- **{}** (`{}`)\n", recipe.name, recipe.path));
+                }
+            }
+        }
+
+        if !self.modified.is_empty() {
+            out.push_str(&format!("\n## Modified ({})\n", self.modified.len()));
+            for level in [ChangeLevel::Major, ChangeLevel::Minor, ChangeLevel::Patch] {
+                let recipes: Vec<&ModifiedRecipe> = self.modified.iter().filter(|m| m.level() == level).collect();
+                if recipes.is_empty() {
+                    continue;
+                }
+                out.push_str(&format!("\n### {}\n\n", level));
+                for recipe in recipes {
+                    out.push_str(&format!("- **{}** (`{}`)\n", recipe.name, recipe.path));
+                    if recipe.diff.is_empty() {
+                        out.push_str("  - formatting only\n");
+                    }
+                    for change in &recipe.diff.changes {
+                        out.push_str(&format!("  - {}: {}\n", change.level, change.summary));
+                    }
+                }
+            }
+        }
+
+        if !self.attention.is_empty() {
+            out.push_str(&format!("\n## Needs attention ({})\n\n", self.attention.len()));
+            for (path, reason) in &self.attention {
+                out.push_str(&format!("- `{}`: {}\n", path, reason));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn commit(repo: &Path, tag: &str) {
+        git(repo, &["add", "-A"]).unwrap();
+        git(repo, &["-c", "user.name=Test", "-c", "user.email=test@example.com", "commit", "-q", "-m", tag]).unwrap();
+        git(repo, &["tag", tag]).unwrap();
+    }
+
+    fn recipe(name: &str, allergens: &[&str]) -> String {
+        serde_json::json!({
+            "meta": {"name": name},
+            "ingredients": [{"id": "ing-0001", "name": "flour", "machine_amount": {"value": 200, "unit": "g"}, "allergens": allergens}],
+            "steps": [{"step_id": "s-01", "action": "mix", "target": ["ing-0001"]}]
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_release_notes_between_tags() {
+        let repo = tempfile::tempdir().unwrap();
+        let dir = repo.path().join("recipes");
+        fs::create_dir(&dir).unwrap();
+        git(repo.path(), &["init", "-q"]).unwrap();
+        fs::write(dir.join("bread.rcip"), recipe("Bread", &["wheat"])).unwrap();
+        fs::write(dir.join("cake.rcip"), recipe("Cake", &["wheat"])).unwrap();
+        fs::write(dir.join("old.rcip"), recipe("Old", &[])).unwrap();
+        fs::write(dir.join("draft.rcip"), "{").unwrap();
+        fs::write(dir.join("moved.rcip"), recipe("Moved", &[])).unwrap();
+        fs::write(repo.path().join("README.md"), "outside the directory").unwrap();
+        commit(repo.path(), "v1");
+
+        fs::write(dir.join("bread.rcip"), recipe("Bread", &["wheat", "sesame"])).unwrap();
+        fs::write(dir.join("cake.rcip"), recipe("Cake", &["wheat"]).replace("200", "250")).unwrap();
+        fs::write(dir.join("draft.rcip"), recipe("Draft", &[])).unwrap();
+        fs::write(dir.join("soup.rcip"), recipe("Soup", &["celery"])).unwrap();
+        fs::remove_file(dir.join("old.rcip")).unwrap();
+        fs::rename(dir.join("moved.rcip"), dir.join("renamed.rcip")).unwrap();
+        fs::write(repo.path().join("README.md"), "changed").unwrap();
+        commit(repo.path(), "v2");
+
+        let notes = release_notes(&dir, "v1", "v2").unwrap();
+        assert_eq!(notes.added, vec![RecipeRef { path: "soup.rcip".into(), name: "Soup".into() }]);
+        assert_eq!(notes.removed, vec![RecipeRef { path: "old.rcip".into(), name: "Old".into() }]);
+        let levels: Vec<(&str, ChangeLevel)> = notes.modified.iter().map(|m| (m.path.as_str(), m.level())).collect();
+        assert_eq!(levels, vec![("bread.rcip", ChangeLevel::Major), ("cake.rcip", ChangeLevel::Minor)]);
+        assert_eq!(notes.attention.len(), 2);
+        assert!(notes.attention[0].0 == "draft.rcip" && notes.attention[0].1.starts_with("does not parse at v1"));
+        assert_eq!(notes.attention[1], ("renamed.rcip".to_string(), "renamed from moved.rcip".to_string()));
+        assert_eq!((notes.recipes_before, notes.recipes_after), (5, 5));
+        assert_eq!(notes.allergens_added, ["celery", "sesame"].iter().map(|a| a.to_string()).collect::<BTreeSet<_>>());
+
+        let markdown = notes.to_markdown();
+        assert!(markdown.starts_with("# Release notes: v1 → v2\n\n5 recipes (was 5): 1 added, 1 removed, 2 modified.\n"));
+        assert!(markdown.contains("Newly covered allergens: celery, sesame.\n"));
+        assert!(markdown.contains("### major\n\n- **Bread** (`bread.rcip`)\n  - major: now contains allergen sesame\n"));
+        assert!(markdown.contains("## Needs attention (2)\n"));
+    }
+
+    #[test]
+    fn test_unknown_ref_is_an_error() {
+        let repo = tempfile::tempdir().unwrap();
+        git(repo.path(), &["init", "-q"]).unwrap();
+        assert!(matches!(release_notes(repo.path(), "v1", "v2"), Err(ReleaseNotesError::Git(_))));
+    }
+}
@@ -0,0 +1,185 @@
+// Progress reporting
+//
+// The file-based API (`init`, `validate_file`, `validate_directory`,
+// `validate_source`) never prints. It tells the validator's `Reporter` what
+// it is doing instead: the schema it compiled, the files it found, each
+// file's result and the batch summary. Validators start with a
+// `NullReporter`, so a library user gets results and nothing on stdout or
+// stderr; the CLI installs a `ConsoleReporter`, which prints the familiar
+// reports. Every method has a no-op default.
+//
+// Unlike a `ValidationObserver`, which follows the phases of one validation,
+// a reporter only hears about whole files and batches.
+
+use crate::{lowest_quality, render_report, ValidationResult, ValidationStats, LOWEST_QUALITY_LISTED};
+
+/// Receives what the file-based API would otherwise print
+pub trait Reporter: Send + Sync {
+    /// `init` compiled the schema of this version
+    fn initialized(&mut self, _schema_version: &str) {}
+
+    /// The schema disagrees with a built-in rule; validation goes on
+    fn schema_divergence(&mut self, _divergence: &str) {}
+
+    /// A batch of `count` files is about to be validated, from `location`
+    /// when it is not a local directory
+    fn batch_start(&mut self, _count: usize, _location: Option<&str>) {}
+
+    /// A file parsed and is being validated
+    fn file_start(&mut self, _name: &str) {}
+
+    /// A file was validated
+    fn file_result(&mut self, _recipe_name: &str, _result: &ValidationResult) {}
+
+    /// A batch is done; `stats` are the validator's session statistics
+    fn batch_end(&mut self, _results: &[(String, ValidationResult)], _stats: &ValidationStats) {}
+}
+
+/// Reports nothing; what validators start with
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullReporter;
+
+impl Reporter for NullReporter {}
+
+/// Prints reports for people: progress and each file's report on stdout,
+/// schema divergences on stderr
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConsoleReporter;
+
+impl Reporter for ConsoleReporter {
+    fn initialized(&mut self, schema_version: &str) {
+        println!("✅ RCIP Validator initialized with schema v{}", schema_version);
+    }
+
+    fn schema_divergence(&mut self, divergence: &str) {
+        eprintln!("⚠️  Schema divergence: {}", divergence);
+    }
+
+    fn batch_start(&mut self, count: usize, location: Option<&str>) {
+        match location {
+            Some(location) => println!("\n🔍 Found {} recipe files to validate in {}\n", count, location),
+            None => println!("\n🔍 Found {} recipe files to validate\n", count),
+        }
+    }
+
+    fn file_start(&mut self, name: &str) {
+        println!("\n📄 Validating: {}", name);
+    }
+
+    fn file_result(&mut self, recipe_name: &str, result: &ValidationResult) {
+        print!("{}", render_report(result, recipe_name));
+    }
+
+    fn batch_end(&mut self, results: &[(String, ValidationResult)], stats: &ValidationStats) {
+        print!("{}", render_summary(results, stats));
+    }
+}
+
+/// The summary `ConsoleReporter` prints after a batch
+pub fn render_summary(results: &[(String, ValidationResult)], stats: &ValidationStats) -> String {
+    use std::fmt::Write as _;
+    let mut out = String::new();
+    writeln!(out, "\n{}", "=".repeat(60)).unwrap();
+    writeln!(out, "📈 VALIDATION SUMMARY").unwrap();
+    writeln!(out, "{}", "=".repeat(60)).unwrap();
+    writeln!(out, "Total Validated: {}", stats.validated).unwrap();
+
+    if stats.validated > 0 {
+        let pass_rate = (stats.passed as f64 / stats.validated as f64) * 100.0;
+        let fail_rate = (stats.failed as f64 / stats.validated as f64) * 100.0;
+        writeln!(out, "✅ Passed: {} ({:.0}%)", stats.passed, pass_rate).unwrap();
+        writeln!(out, "❌ Failed: {} ({:.0}%)", stats.failed, fail_rate).unwrap();
+    }
+
+    let lowest = lowest_quality(results, LOWEST_QUALITY_LISTED);
+    if !lowest.is_empty() {
+        writeln!(out, "📉 Lowest quality:").unwrap();
+        for (file, score) in lowest {
+            writeln!(out, "  {:>5.1}  {}", score, file).unwrap();
+        }
+    }
+
+    writeln!(out, "{}\n", "=".repeat(60)).unwrap();
+    out
+}
+
+#[cfg(all(test, not(feature = "no-fs")))]
+mod tests {
+    use super::*;
+    use crate::RCIPValidator;
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Recording(Arc<Mutex<Vec<String>>>);
+
+    impl Reporter for Recording {
+        fn initialized(&mut self, schema_version: &str) {
+            self.0.lock().unwrap().push(format!("initialized {}", schema_version));
+        }
+
+        fn batch_start(&mut self, count: usize, _location: Option<&str>) {
+            self.0.lock().unwrap().push(format!("found {}", count));
+        }
+
+        fn file_start(&mut self, name: &str) {
+            self.0.lock().unwrap().push(format!("file {}", name));
+        }
+
+        fn batch_end(&mut self, results: &[(String, ValidationResult)], stats: &ValidationStats) {
+            self.0.lock().unwrap().push(format!("done {} {}", results.len(), stats.validated));
+        }
+    }
+
+    fn examples() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/../../examples"))
+    }
+
+    #[test]
+    fn test_reporter_hears_about_files() {
+        let recording = Recording::default();
+        let mut validator = RCIPValidator::new("0.1");
+        validator.set_reporter(Box::new(recording.clone()));
+        validator.init(None).unwrap();
+        validator.validate_directory(examples()).unwrap();
+
+        let events = recording.0.lock().unwrap();
+        assert_eq!(events[0], "initialized 0.1");
+        assert_eq!(events[1], "found 3");
+        assert_eq!(events[2], "file margherita-pizza.rcip");
+        assert_eq!(events.last().unwrap(), "done 3 3");
+    }
+
+    /// Run by `test_library_api_prints_nothing` in a child process, where
+    /// stdout is not captured; does nothing otherwise
+    #[test]
+    fn library_api_child() {
+        if std::env::var_os("RCIP_PRINT_CHILD").is_none() {
+            return;
+        }
+        println!("<<");
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        validator.validate_file(&examples().join("simple-example.rcip")).unwrap();
+        validator.validate_directory(examples()).unwrap();
+        validator.validate_source(&crate::sources::DirectorySource::new(examples()));
+        println!(">>");
+    }
+
+    #[test]
+    fn test_library_api_prints_nothing() {
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args(["reporter::tests::library_api_child", "--exact", "--nocapture", "--test-threads=1"])
+            .env("RCIP_PRINT_CHILD", "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let start = stdout.find("<<").unwrap() + 2;
+        let end = stdout.find(">>").unwrap();
+        assert_eq!(stdout[start..end].trim(), "");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(!stderr.contains("Schema divergence") && !stderr.contains("Validating"), "{}", stderr);
+    }
+}
@@ -0,0 +1,473 @@
+// External-ID resolvers
+//
+// Validation can only check the format of `external_ids`; a `Resolver` looks
+// the ids up in a product database. `Resolvers` chains several resolvers and
+// caches every answer for the lifetime of the set, so a run asks each
+// database at most once per id. Lookup failures (no network, bad responses)
+// are reported, never fatal: enrichment skips the ingredient and the
+// allergen cross-check stays silent.
+//
+// The Open Food Facts resolver needs the `http-checks` feature; its response
+// mapping is available without it. Offline sites use a `ProductDb` instead, a
+// local snapshot of the same data (feature `product-db`, see `product_db`).
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+#[cfg(feature = "product-db")]
+mod product_db;
+
+#[cfg(feature = "product-db")]
+pub use product_db::{build_snapshot, BuildReport, ProductDb, ProductDbError};
+
+/// Product data returned by a resolver
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ResolvedProduct {
+    /// Resolver that produced the data, e.g. `open-food-facts`
+    pub source: String,
+    pub name: Option<String>,
+    /// Nutrition per 100 g, keyed like `nutritional.per_100g`
+    pub per_100g: Map<String, Value>,
+    /// RCIP allergen names
+    pub allergens: Vec<String>,
+    /// Product categories, e.g. `spreads`, without a language prefix
+    pub categories: Vec<String>,
+}
+
+/// Errors looking up an external id
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// The service could not be reached
+    Unavailable(String),
+    /// The service answered with something unusable
+    InvalidResponse(String),
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::Unavailable(e) => write!(f, "service unavailable: {}", e),
+            ResolveError::InvalidResponse(e) => write!(f, "invalid response: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+/// Looks up products by external id
+pub trait Resolver: Send + Sync {
+    /// Resolve an id of the given type (`GTIN`, `USDA`, `OpenFoodFacts`, ...).
+    /// `Ok(None)` means the resolver does not know the id or the id type.
+    fn resolve(&self, id_type: &str, id: &str) -> Result<Option<ResolvedProduct>, ResolveError>;
+}
+
+type Lookup = Result<Option<ResolvedProduct>, ResolveError>;
+
+/// An ordered set of resolvers with a per-run cache
+#[derive(Default)]
+pub struct Resolvers {
+    resolvers: Vec<Box<dyn Resolver>>,
+    cache: Mutex<HashMap<(String, String), Lookup>>,
+}
+
+impl Resolvers {
+    pub fn new() -> Self {
+        Resolvers::default()
+    }
+
+    /// Add a resolver; earlier resolvers take precedence
+    pub fn push(&mut self, resolver: Box<dyn Resolver>) {
+        self.resolvers.push(resolver);
+    }
+
+    /// First product any resolver knows for this id. Errors are returned only
+    /// if no resolver produced a product.
+    pub fn resolve(&self, id_type: &str, id: &str) -> Lookup {
+        let key = (id_type.to_string(), id.to_string());
+        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+            return cached.clone();
+        }
+
+        let mut outcome = Ok(None);
+        for resolver in &self.resolvers {
+            match resolver.resolve(id_type, id) {
+                Ok(Some(product)) => {
+                    outcome = Ok(Some(product));
+                    break;
+                }
+                Ok(None) => {}
+                Err(e) => outcome = Err(e),
+            }
+        }
+
+        self.cache.lock().unwrap().insert(key, outcome.clone());
+        outcome
+    }
+
+    /// Resolve the first known product for an ingredient's `external_ids`
+    pub fn resolve_ingredient(&self, ingredient: &Value) -> Lookup {
+        let ids = match ingredient.get("external_ids").and_then(|v| v.as_object()) {
+            Some(ids) => ids,
+            None => return Ok(None),
+        };
+
+        let mut outcome = Ok(None);
+        for (id_type, id) in ids {
+            let id = match id.as_str() {
+                Some(id) => id,
+                None => continue,
+            };
+            match self.resolve(id_type, id) {
+                Ok(Some(product)) => return Ok(Some(product)),
+                Ok(None) => {}
+                Err(e) => outcome = Err(e),
+            }
+        }
+        outcome
+    }
+}
+
+/// In-memory resolver for tests and offline fixtures
+#[derive(Debug, Default)]
+pub struct MockResolver {
+    products: HashMap<(String, String), ResolvedProduct>,
+    calls: Mutex<usize>,
+}
+
+impl MockResolver {
+    pub fn new() -> Self {
+        MockResolver::default()
+    }
+
+    /// Register a product for an id
+    pub fn with(mut self, id_type: &str, id: &str, product: ResolvedProduct) -> Self {
+        self.products.insert((id_type.to_string(), id.to_string()), product);
+        self
+    }
+
+    /// Number of lookups made so far
+    pub fn calls(&self) -> usize {
+        *self.calls.lock().unwrap()
+    }
+}
+
+impl Resolver for MockResolver {
+    fn resolve(&self, id_type: &str, id: &str) -> Result<Option<ResolvedProduct>, ResolveError> {
+        *self.calls.lock().unwrap() += 1;
+        Ok(self.products.get(&(id_type.to_string(), id.to_string())).cloned())
+    }
+}
+
+impl<R: Resolver + ?Sized> Resolver for std::sync::Arc<R> {
+    fn resolve(&self, id_type: &str, id: &str) -> Result<Option<ResolvedProduct>, ResolveError> {
+        self.as_ref().resolve(id_type, id)
+    }
+}
+
+/// Open Food Facts nutriment keys (per 100 g) and their RCIP names and factors
+const OFF_NUTRIMENTS: &[(&str, &str, f64)] = &[
+    ("energy-kcal_100g", "calories", 1.0),
+    ("proteins_100g", "protein", 1.0),
+    ("carbohydrates_100g", "carbs", 1.0),
+    ("fat_100g", "fat", 1.0),
+    ("saturated-fat_100g", "saturated_fat", 1.0),
+    ("fiber_100g", "fiber", 1.0),
+    ("sugars_100g", "sugar", 1.0),
+    ("sodium_100g", "sodium", 1000.0),
+    ("calcium_100g", "calcium", 1000.0),
+];
+
+/// Open Food Facts allergen tags and the RCIP allergens they map to
+const OFF_ALLERGENS: &[(&str, &str)] = &[
+    ("en:milk", "milk"),
+    ("en:eggs", "eggs"),
+    ("en:fish", "fish"),
+    ("en:crustaceans", "shellfish"),
+    ("en:molluscs", "molluscs"),
+    ("en:nuts", "tree-nuts"),
+    ("en:peanuts", "peanuts"),
+    ("en:gluten", "gluten"),
+    ("en:soybeans", "soybeans"),
+    ("en:sesame-seeds", "sesame"),
+    ("en:celery", "celery"),
+    ("en:mustard", "mustard"),
+    ("en:lupin", "lupins"),
+    ("en:sulphur-dioxide-and-sulphites", "sulphites"),
+];
+
+/// Map an Open Food Facts product API response to a product
+pub fn parse_off_product(response: &Value) -> Option<ResolvedProduct> {
+    if response.get("status").and_then(|v| v.as_i64()) != Some(1) {
+        return None;
+    }
+    Some(map_off_product(response.get("product")?, "open-food-facts"))
+}
+
+/// Map an Open Food Facts product object, from the API or an export
+pub(crate) fn map_off_product(product: &Value, source: &str) -> ResolvedProduct {
+    let mut per_100g = Map::new();
+    if let Some(nutriments) = product.get("nutriments") {
+        for (off_key, key, factor) in OFF_NUTRIMENTS {
+            if let Some(value) = nutriments.get(*off_key).and_then(|v| v.as_f64()) {
+                let scaled = (value * factor * 1000.0).round() / 1000.0;
+                per_100g.insert(key.to_string(), json!(scaled));
+            }
+        }
+    }
+
+    let mut allergens: Vec<String> = product.get("allergens_tags")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str())
+        .filter_map(|tag| OFF_ALLERGENS.iter().find(|(t, _)| *t == tag).map(|(_, a)| a.to_string()))
+        .collect();
+    allergens.sort();
+    allergens.dedup();
+
+    let categories = product.get("categories_tags")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|tag| tag.as_str())
+        .map(|tag| tag.split_once(':').map(|(_, name)| name).unwrap_or(tag).to_string())
+        .collect();
+
+    ResolvedProduct {
+        source: source.to_string(),
+        name: product.get("product_name").and_then(|v| v.as_str()).map(|s| s.to_string()),
+        per_100g,
+        allergens,
+        categories,
+    }
+}
+
+/// Open Food Facts lookup by barcode
+#[cfg(feature = "http-checks")]
+pub struct OpenFoodFacts {
+    base_url: String,
+    agent: ureq::Agent,
+}
+
+#[cfg(feature = "http-checks")]
+impl OpenFoodFacts {
+    pub fn new() -> Self {
+        OpenFoodFacts::with_base_url("https://world.openfoodfacts.org")
+    }
+
+    pub fn with_base_url(base_url: &str) -> Self {
+        OpenFoodFacts {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout(std::time::Duration::from_secs(10))
+                .user_agent(concat!("rcip-validator/", env!("CARGO_PKG_VERSION")))
+                .build(),
+        }
+    }
+}
+
+#[cfg(feature = "http-checks")]
+impl Default for OpenFoodFacts {
+    fn default() -> Self {
+        OpenFoodFacts::new()
+    }
+}
+
+#[cfg(feature = "http-checks")]
+impl Resolver for OpenFoodFacts {
+    fn resolve(&self, id_type: &str, id: &str) -> Result<Option<ResolvedProduct>, ResolveError> {
+        if id_type != "GTIN" && id_type != "OpenFoodFacts" {
+            return Ok(None);
+        }
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+            return Ok(None);
+        }
+
+        let url = format!(
+            "{}/api/v2/product/{}.json?fields=product_name,nutriments,allergens_tags,categories_tags",
+            self.base_url, id
+        );
+        let response = match self.agent.get(&url).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(ResolveError::Unavailable(e.to_string())),
+        };
+        let body: Value = response
+            .into_string()
+            .map_err(|e| ResolveError::Unavailable(e.to_string()))
+            .and_then(|text| serde_json::from_str(&text).map_err(|e| ResolveError::InvalidResponse(e.to_string())))?;
+
+        Ok(parse_off_product(&body))
+    }
+}
+
+/// What `enrich` changed and what it could not look up
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EnrichReport {
+    /// Ingredient id and the fields filled in
+    pub enriched: Vec<(String, Vec<String>)>,
+    /// Lookup failures, one per ingredient
+    pub failures: Vec<String>,
+}
+
+/// Fill missing nutrition and allergen data from resolved products. Filled
+/// ingredients record where the data came from in `x-enriched-from`.
+pub fn enrich(recipe: &mut Value, resolvers: &Resolvers) -> EnrichReport {
+    let mut report = EnrichReport::default();
+
+    let ingredients = match recipe.get_mut("ingredients").and_then(|v| v.as_array_mut()) {
+        Some(ingredients) => ingredients,
+        None => return report,
+    };
+
+    for ingredient in ingredients {
+        let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        let product = match resolvers.resolve_ingredient(ingredient) {
+            Ok(Some(product)) => product,
+            Ok(None) => continue,
+            Err(e) => {
+                report.failures.push(format!("{}: {}", id, e));
+                continue;
+            }
+        };
+
+        let mut filled = Vec::new();
+
+        if ingredient.pointer("/nutritional/per_100g").is_none() && !product.per_100g.is_empty() {
+            if !ingredient.get("nutritional").map(|n| n.is_object()).unwrap_or(false) {
+                ingredient["nutritional"] = json!({});
+            }
+            ingredient["nutritional"]["per_100g"] = Value::Object(product.per_100g.clone());
+            filled.push("nutritional.per_100g".to_string());
+        }
+
+        let declared_empty = ingredient.get("allergens")
+            .and_then(|v| v.as_array())
+            .map(|a| a.is_empty())
+            .unwrap_or(true);
+        if declared_empty && !product.allergens.is_empty() {
+            ingredient["allergens"] = json!(product.allergens);
+            filled.push("allergens".to_string());
+        }
+
+        if !filled.is_empty() {
+            ingredient["x-enriched-from"] = json!({
+                "source": product.source,
+                "fields": filled
+            });
+            report.enriched.push((id, filled));
+        }
+    }
+
+    report
+}
+
+/// Whether two allergens name the same hazard (wheat implies gluten,
+/// lactose implies milk)
+fn related(a: &str, b: &str) -> bool {
+    a == b
+        || matches!((a, b), ("gluten", "wheat") | ("wheat", "gluten") | ("milk", "lactose") | ("lactose", "milk"))
+}
+
+/// Allergens the product lists but the ingredient does not declare, and
+/// declared allergens the product does not list
+pub fn allergen_discrepancies(declared: &[&str], product: &ResolvedProduct) -> (Vec<String>, Vec<String>) {
+    let missing = product.allergens.iter()
+        .filter(|a| !declared.iter().any(|d| related(d, a)))
+        .cloned()
+        .collect();
+    let extra = declared.iter()
+        .filter(|d| !product.allergens.iter().any(|a| related(d, a)))
+        .map(|d| d.to_string())
+        .collect();
+    (missing, extra)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn butter() -> ResolvedProduct {
+        ResolvedProduct {
+            source: "mock".to_string(),
+            name: Some("Butter".to_string()),
+            per_100g: json!({"calories": 717, "fat": 81}).as_object().unwrap().clone(),
+            allergens: vec!["milk".to_string()],
+            categories: vec!["butters".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_parse_off_product() {
+        let response = json!({
+            "status": 1,
+            "product": {
+                "product_name": "Whole milk",
+                "nutriments": {"energy-kcal_100g": 64, "fat_100g": 3.6, "sodium_100g": 0.044},
+                "allergens_tags": ["en:milk", "en:unknown"],
+                "categories_tags": ["en:dairies", "en:milks"]
+            }
+        });
+
+        let product = parse_off_product(&response).unwrap();
+        assert_eq!(product.per_100g["sodium"], json!(44.0));
+        assert_eq!(product.allergens, vec!["milk"]);
+        assert_eq!(product.categories, vec!["dairies", "milks"]);
+        assert!(parse_off_product(&json!({"status": 0})).is_none());
+    }
+
+    #[test]
+    fn test_enrich_fills_missing_data_and_caches() {
+        let mock = Arc::new(MockResolver::new().with("GTIN", "5000000000001", butter()));
+        let mut resolvers = Resolvers::new();
+        resolvers.push(Box::new(mock.clone()));
+
+        let mut recipe = json!({"ingredients": [
+            {"id": "ing-0001", "allergens": [], "external_ids": {"GTIN": "5000000000001"}},
+            {"id": "ing-0002", "allergens": ["milk"], "external_ids": {"GTIN": "5000000000001"},
+             "nutritional": {"per_100g": {"calories": 700}}},
+            {"id": "ing-0003", "allergens": []}
+        ]});
+
+        let report = enrich(&mut recipe, &resolvers);
+        assert_eq!(report.enriched, vec![(
+            "ing-0001".to_string(),
+            vec!["nutritional.per_100g".to_string(), "allergens".to_string()]
+        )]);
+        assert_eq!(recipe["ingredients"][0]["allergens"], json!(["milk"]));
+        assert_eq!(recipe["ingredients"][0]["x-enriched-from"]["source"], "mock");
+        assert_eq!(recipe["ingredients"][1]["nutritional"]["per_100g"]["calories"], json!(700));
+        assert_eq!(mock.calls(), 1);
+    }
+
+    #[test]
+    fn test_offline_resolver_degrades() {
+        struct Offline;
+        impl Resolver for Offline {
+            fn resolve(&self, _: &str, _: &str) -> Result<Option<ResolvedProduct>, ResolveError> {
+                Err(ResolveError::Unavailable("no network".to_string()))
+            }
+        }
+
+        let mut resolvers = Resolvers::new();
+        resolvers.push(Box::new(Offline));
+        let mut recipe = json!({"ingredients": [{"id": "ing-0001", "allergens": [], "external_ids": {"GTIN": "1"}}]});
+        let before = recipe.clone();
+
+        let report = enrich(&mut recipe, &resolvers);
+        assert_eq!(recipe, before);
+        assert_eq!(report.failures, vec!["ing-0001: service unavailable: no network"]);
+    }
+
+    #[test]
+    fn test_allergen_discrepancies() {
+        let product = ResolvedProduct { allergens: vec!["gluten".to_string(), "milk".to_string()], ..butter() };
+        let (missing, extra) = allergen_discrepancies(&["wheat", "eggs"], &product);
+        assert_eq!(missing, vec!["milk"]);
+        assert_eq!(extra, vec!["eggs"]);
+    }
+}
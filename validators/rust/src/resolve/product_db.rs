@@ -0,0 +1,346 @@
+// Offline product snapshots
+//
+// A factory without network access can still check external ids against a
+// local copy of Open Food Facts. `build_snapshot` turns a raw OFF JSONL export
+// into a snapshot that keeps only what the rules read: the barcode, the
+// product name, its allergens in RCIP names and its categories. A snapshot is
+// gzipped NDJSON, one header line and then one product per line:
+//
+//   {"format":"rcip-product-db","version":1,"source":"open-food-facts"}
+//   {"gtin":"3017620422003","name":"Nutella","allergens":["milk","tree-nuts"],"categories":["spreads"]}
+//
+// `ProductDb` loads a snapshot into memory and is a `Resolver` like the HTTP
+// client, so enrichment, the allergen cross-check and claim verification work
+// the same offline. Barcodes are indexed by their numeric value, which also
+// makes "03017620422003" and "3017620422003" the same product; names and
+// categories share one string arena and allergens are a bit set over
+// `allergens::VOCABULARY`, so a million products take tens of megabytes and a
+// lookup is a single hash probe (`cargo bench --bench product_db`).
+
+use super::{map_off_product, ResolveError, ResolvedProduct, Resolver};
+use crate::allergens::VOCABULARY;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+const FORMAT: &str = "rcip-product-db";
+const VERSION: u64 = 1;
+
+/// Bit set over `VOCABULARY`
+type AllergenBits = u32;
+
+// Adding allergens past the width of the bit set must not compile, rather
+// than shift bits out of it
+const _: () = assert!(VOCABULARY.len() <= AllergenBits::BITS as usize);
+
+/// Errors reading or writing a snapshot
+#[derive(Debug)]
+pub enum ProductDbError {
+    Io(io::Error),
+    /// A line that is not what the format expects, numbered from 1
+    Format { line: usize, message: String },
+}
+
+impl fmt::Display for ProductDbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProductDbError::Io(e) => write!(f, "{}", e),
+            ProductDbError::Format { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ProductDbError {}
+
+impl From<io::Error> for ProductDbError {
+    fn from(e: io::Error) -> Self {
+        ProductDbError::Io(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u64,
+    #[serde(default)]
+    source: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    gtin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    allergens: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    categories: Vec<String>,
+}
+
+/// Numeric value of a barcode, the index key
+fn key(gtin: &str) -> Option<u64> {
+    if gtin.is_empty() || gtin.len() > 18 || !gtin.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    gtin.parse().ok()
+}
+
+/// A file's contents, decompressed when it is gzipped
+fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let gzipped = file.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    Ok(if gzipped { Box::new(BufReader::new(GzDecoder::new(file))) } else { Box::new(file) })
+}
+
+/// Where a product's strings sit in the arena
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    name: (u32, u32),
+    /// Categories, separated by newlines
+    categories: (u32, u32),
+    /// Bit i set when the product contains `VOCABULARY[i]`
+    allergens: AllergenBits,
+}
+
+/// A product snapshot loaded for lookups
+#[derive(Debug, Default)]
+pub struct ProductDb {
+    index: HashMap<u64, u32>,
+    records: Vec<Record>,
+    arena: String,
+    source: String,
+}
+
+impl ProductDb {
+    /// Load a snapshot file, gzipped or not
+    pub fn open(path: &Path) -> Result<Self, ProductDbError> {
+        ProductDb::from_reader(open(path)?)
+    }
+
+    /// Load a snapshot from NDJSON lines
+    pub fn from_reader(reader: impl BufRead) -> Result<Self, ProductDbError> {
+        let mut db = ProductDb::default();
+        let format = |line: usize, message: String| ProductDbError::Format { line, message };
+        for (i, text) in reader.lines().enumerate() {
+            let (number, text) = (i + 1, text?);
+            if number == 1 {
+                let header: Header = serde_json::from_str(&text).map_err(|e| format(1, format!("bad header: {}", e)))?;
+                if header.format != FORMAT || header.version != VERSION {
+                    let found = format!("{} v{}", header.format, header.version);
+                    return Err(format(1, format!("expected {} v{}, found {}", FORMAT, VERSION, found)));
+                }
+                db.source = header.source;
+                continue;
+            }
+            if text.trim().is_empty() {
+                continue;
+            }
+            let line: Line = serde_json::from_str(&text).map_err(|e| format(number, e.to_string()))?;
+            let gtin = key(&line.gtin).ok_or_else(|| format(number, format!("'{}' is not a barcode", line.gtin)))?;
+            let mut allergens: AllergenBits = 0;
+            for allergen in &line.allergens {
+                let bit = VOCABULARY
+                    .iter()
+                    .position(|a| a == allergen)
+                    .ok_or_else(|| format(number, format!("'{}' is not an RCIP allergen", allergen)))?;
+                allergens |= 1 << bit;
+            }
+            let name = db.intern(line.name.as_deref().unwrap_or(""));
+            let categories = db.intern(&line.categories.join("\n"));
+            db.index.insert(gtin, db.records.len() as u32);
+            db.records.push(Record { name, categories, allergens });
+        }
+        Ok(db)
+    }
+
+    fn intern(&mut self, text: &str) -> (u32, u32) {
+        let start = self.arena.len() as u32;
+        self.arena.push_str(text);
+        (start, text.len() as u32)
+    }
+
+    fn text(&self, (start, len): (u32, u32)) -> &str {
+        &self.arena[start as usize..(start + len) as usize]
+    }
+
+    /// Where the snapshot's data came from, as its header says
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Number of products
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// The product with this barcode
+    pub fn get(&self, gtin: &str) -> Option<ResolvedProduct> {
+        let record = self.records[*self.index.get(&key(gtin)?)? as usize];
+        let name = self.text(record.name);
+        let categories = self.text(record.categories);
+        // Sorted, as the online resolver returns them
+        let mut allergens: Vec<String> = (0..VOCABULARY.len())
+            .filter(|bit| record.allergens & (1 << bit) != 0)
+            .map(|bit| VOCABULARY[bit].to_string())
+            .collect();
+        allergens.sort();
+        Some(ResolvedProduct {
+            source: "product-db".to_string(),
+            name: (!name.is_empty()).then(|| name.to_string()),
+            per_100g: Map::new(),
+            allergens,
+            categories: categories.split('\n').filter(|c| !c.is_empty()).map(String::from).collect(),
+        })
+    }
+}
+
+impl Resolver for ProductDb {
+    fn resolve(&self, id_type: &str, id: &str) -> Result<Option<ResolvedProduct>, ResolveError> {
+        if id_type != "GTIN" && id_type != "OpenFoodFacts" {
+            return Ok(None);
+        }
+        Ok(self.get(id))
+    }
+}
+
+/// What `build_snapshot` kept and left out
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BuildReport {
+    pub products: usize,
+    /// Lines that were not JSON or had no usable barcode
+    pub skipped: usize,
+    /// Products whose barcode was already taken; the first is kept
+    pub duplicates: usize,
+}
+
+impl fmt::Display for BuildReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} products, {} lines skipped, {} duplicate barcodes", self.products, self.skipped, self.duplicates)
+    }
+}
+
+/// Convert a raw Open Food Facts JSONL export (gzipped or not) into a
+/// gzipped snapshot, keeping only barcodes, names, allergens and categories
+pub fn build_snapshot(export: &Path, snapshot: &Path) -> Result<BuildReport, ProductDbError> {
+    let mut output = GzEncoder::new(io::BufWriter::new(File::create(snapshot)?), Compression::best());
+    let report = build_from(open(export)?, &mut output)?;
+    output.finish()?.flush()?;
+    Ok(report)
+}
+
+fn build_from(export: impl Read, output: &mut impl Write) -> Result<BuildReport, ProductDbError> {
+    let mut report = BuildReport::default();
+    let header = Header { format: FORMAT.to_string(), version: VERSION, source: "open-food-facts".to_string() };
+    writeln!(output, "{}", serde_json::to_string(&header).unwrap())?;
+
+    let mut seen = HashSet::new();
+    for text in BufReader::new(export).lines() {
+        let text = text?;
+        let product: Value = match serde_json::from_str(&text) {
+            Ok(product) => product,
+            Err(_) => {
+                report.skipped += 1;
+                continue;
+            }
+        };
+        let code = product.get("code").and_then(|v| v.as_str()).unwrap_or("");
+        let Some(gtin) = key(code) else {
+            report.skipped += 1;
+            continue;
+        };
+        if !seen.insert(gtin) {
+            report.duplicates += 1;
+            continue;
+        }
+        let mapped = map_off_product(&product, "open-food-facts");
+        let line = Line { gtin: code.to_string(), name: mapped.name, allergens: mapped.allergens, categories: mapped.categories };
+        writeln!(output, "{}", serde_json::to_string(&line).unwrap())?;
+        report.products += 1;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::{enrich, Resolvers};
+    use serde_json::json;
+
+    const EXPORT: &str = r#"{"code": "3017620422003", "product_name": "Nutella", "allergens_tags": ["en:milk", "en:nuts", "en:soybeans"], "categories_tags": ["en:spreads", "fr:pâtes-à-tartiner"], "nutriments": {"energy-kcal_100g": 539}, "ingredients_text": "Sugar, palm oil, hazelnuts"}
+not json
+{"code": "", "product_name": "No barcode"}
+{"code": "5000000000001", "product_name": "Butter", "allergens_tags": ["en:milk"]}
+{"code": "5000000000001", "product_name": "Butter again"}
+{"code": "0000000000017", "allergens_tags": []}"#;
+
+    fn snapshot() -> (ProductDb, BuildReport) {
+        let mut compact = Vec::new();
+        let report = build_from(EXPORT.as_bytes(), &mut compact).unwrap();
+        (ProductDb::from_reader(&compact[..]).unwrap(), report)
+    }
+
+    #[test]
+    fn test_builds_a_compact_snapshot() {
+        let mut compact = Vec::new();
+        build_from(EXPORT.as_bytes(), &mut compact).unwrap();
+        let text = String::from_utf8(compact).unwrap();
+        assert!(!text.contains("nutriments") && !text.contains("palm oil"));
+
+        let (db, report) = snapshot();
+        assert_eq!(report, BuildReport { products: 3, skipped: 2, duplicates: 1 });
+        assert_eq!(db.len(), 3);
+        let nutella = db.get("03017620422003").unwrap();
+        assert_eq!(nutella.name.as_deref(), Some("Nutella"));
+        assert_eq!(nutella.allergens, ["milk", "soybeans", "tree-nuts"]);
+        assert_eq!(nutella.categories, ["spreads", "pâtes-à-tartiner"]);
+        assert_eq!(db.get("17").unwrap().name, None);
+        assert!(db.get("123").is_none());
+        assert!(db.get("not-a-barcode").is_none());
+    }
+
+    #[test]
+    fn test_resolves_like_the_online_database() {
+        let (db, _) = snapshot();
+        let mut resolvers = Resolvers::new();
+        resolvers.push(Box::new(db));
+        let mut recipe = json!({"ingredients": [
+            {"id": "ing-0001", "allergens": [], "external_ids": {"GTIN": "5000000000001"}},
+            {"id": "ing-0002", "allergens": [], "external_ids": {"USDA": "5000000000001"}}
+        ]});
+        let report = enrich(&mut recipe, &resolvers);
+        assert_eq!(report.enriched, vec![("ing-0001".to_string(), vec!["allergens".to_string()])]);
+        assert_eq!(recipe["ingredients"][0]["x-enriched-from"]["source"], "product-db");
+        assert_eq!(recipe["ingredients"][1]["allergens"], json!([]));
+    }
+
+    #[test]
+    fn test_keeps_every_allergen() {
+        let line = json!({"gtin": "5000000000001", "allergens": VOCABULARY});
+        let snapshot = format!("{}\n{}", r#"{"format":"rcip-product-db","version":1,"source":"test"}"#, line);
+        let db = ProductDb::from_reader(snapshot.as_bytes()).unwrap();
+        let mut all: Vec<&str> = VOCABULARY.to_vec();
+        all.sort();
+        assert_eq!(db.get("5000000000001").unwrap().allergens, all);
+    }
+
+    #[test]
+    fn test_rejects_malformed_snapshots() {
+        let header = r#"{"format":"rcip-product-db","version":1,"source":"test"}"#;
+        let bad = |body: &str| ProductDb::from_reader(format!("{}\n{}", header, body).as_bytes()).unwrap_err().to_string();
+        assert_eq!(bad(r#"{"gtin":"12","allergens":["nuts"]}"#), "line 2: 'nuts' is not an RCIP allergen");
+        assert_eq!(bad(r#"{"gtin":"12a"}"#), "line 2: '12a' is not a barcode");
+        let old = ProductDb::from_reader(r#"{"format":"rcip-product-db","version":0}"#.as_bytes()).unwrap_err();
+        assert_eq!(old.to_string(), "line 1: expected rcip-product-db v1, found rcip-product-db v0");
+    }
+}
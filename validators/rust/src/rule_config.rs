@@ -0,0 +1,175 @@
+// Rule sets
+//
+// A `RuleConfig` turns individual rules on or off. Rules are named by the
+// code of the issues they report, e.g. `RCIP-W-NO-EXTERNAL-IDS`, the same
+// stable identifiers severity overrides use. A disabled rule's findings are
+// dropped before severities are applied, so they neither appear in the
+// result nor make it invalid. Listing a rule as `true` only undoes an
+// earlier `false`; rules that are off by default (mise en place, markets,
+// ...) are switched on with their settings.
+//
+// Configs are small JSON or TOML files:
+//
+//     [rules]
+//     "RCIP-W-NO-EXTERNAL-IDS" = false
+//     "RCIP-W-NO-IMAGES" = true
+//
+// Unknown codes are refused when the file is loaded and when a config is
+// applied, never ignored.
+
+use crate::codes;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+
+/// Rules switched on or off
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Rule code → whether it runs; rules not listed keep their default
+    #[serde(default)]
+    pub rules: BTreeMap<String, bool>,
+}
+
+/// Errors loading or applying a rule config
+#[derive(Debug)]
+pub enum RuleConfigError {
+    Io(io::Error),
+    /// The file is not valid JSON or TOML, or not shaped like a config
+    Parse(String),
+    /// Codes that name no rule
+    UnknownRules(Vec<String>),
+}
+
+impl fmt::Display for RuleConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleConfigError::Io(e) => write!(f, "cannot read rule config: {}", e),
+            RuleConfigError::Parse(e) => write!(f, "invalid rule config: {}", e),
+            RuleConfigError::UnknownRules(unknown) => {
+                let quoted: Vec<String> = unknown.iter().map(|code| format!("'{}'", code)).collect();
+                write!(f, "unknown rule ID {} (rule IDs are issue codes such as {})", quoted.join(", "), codes::NO_IMAGES)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuleConfigError {}
+
+/// The code constant for a rule ID, if it names a rule
+pub fn rule_id(code: &str) -> Option<&'static str> {
+    codes::ALL.iter().find(|c| **c == code).copied()
+}
+
+impl RuleConfig {
+    /// Turn a rule off
+    pub fn disable(&mut self, code: &str) -> &mut Self {
+        self.rules.insert(code.to_string(), false);
+        self
+    }
+
+    /// Turn a rule back on
+    pub fn enable(&mut self, code: &str) -> &mut Self {
+        self.rules.insert(code.to_string(), true);
+        self
+    }
+
+    /// Apply another config on top of this one: its rules win
+    pub fn merge(&mut self, other: RuleConfig) -> &mut Self {
+        self.rules.extend(other.rules);
+        self
+    }
+
+    /// Refuse codes that name no rule, listing them all
+    pub fn check(&self) -> Result<(), RuleConfigError> {
+        let unknown: Vec<String> = self.rules.keys().filter(|code| rule_id(code).is_none()).cloned().collect();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(RuleConfigError::UnknownRules(unknown))
+        }
+    }
+
+    /// Parse and check a JSON config
+    pub fn from_json(source: &str) -> Result<Self, RuleConfigError> {
+        let config: RuleConfig = serde_json::from_str(source).map_err(|e| RuleConfigError::Parse(e.to_string()))?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Parse and check a TOML config
+    pub fn from_toml(source: &str) -> Result<Self, RuleConfigError> {
+        let config: RuleConfig = toml::from_str(source).map_err(|e| RuleConfigError::Parse(e.to_string()))?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Load a config file: TOML when its name ends in `.toml`, JSON otherwise
+    #[cfg(not(feature = "no-fs"))]
+    pub fn from_file(path: &std::path::Path) -> Result<Self, RuleConfigError> {
+        let source = std::fs::read_to_string(path).map_err(RuleConfigError::Io)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Self::from_toml(&source)
+        } else {
+            Self::from_json(&source)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RCIPValidator, Severity};
+    use serde_json::Value;
+
+    fn example() -> Value {
+        serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap()
+    }
+
+    #[test]
+    fn test_configs_parse_and_refuse_unknown_rules() {
+        let toml = RuleConfig::from_toml("[rules]\n\"RCIP-W-NO-IMAGES\" = false\n").unwrap();
+        let json = RuleConfig::from_json(r#"{"rules": {"RCIP-W-NO-IMAGES": false}}"#).unwrap();
+        assert_eq!(toml, json);
+        assert_eq!(toml, RuleConfig::default().disable(codes::NO_IMAGES).clone());
+
+        let mut merged = json.clone();
+        merged.merge(RuleConfig::from_json(r#"{"rules": {"RCIP-W-NO-IMAGES": true, "RCIP-W-NO-NUTRITION": false}}"#).unwrap());
+        assert_eq!(merged, RuleConfig::default().enable(codes::NO_IMAGES).disable(codes::NO_NUTRITION).clone());
+
+        let error = RuleConfig::from_json(r#"{"rules": {"RCIP-W-NO-PICTURES": false, "RCIP-W-NO-IMAGES": true}}"#).unwrap_err();
+        assert!(matches!(&error, RuleConfigError::UnknownRules(unknown) if unknown == &["RCIP-W-NO-PICTURES"]));
+        assert!(error.to_string().contains("'RCIP-W-NO-PICTURES'"));
+        assert!(matches!(RuleConfig::from_json(r#"{"disable": []}"#), Err(RuleConfigError::Parse(_))));
+
+        let mut validator = RCIPValidator::new("0.1");
+        assert!(validator.set_rule_config(RuleConfig::default().disable("RCIP-E-NOPE")).is_err());
+    }
+
+    #[test]
+    fn test_disabled_rules_are_silent() {
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let mut recipe = example();
+        recipe["id"] = "not-an-id".into();
+        let before = validator.validate(&recipe);
+        assert!(!before.valid);
+        assert!(before.issues.iter().any(|i| i.code == codes::NO_IMAGES));
+
+        let invalid_id: Vec<&str> = before.issues.iter().filter(|i| i.severity == Severity::Error).map(|i| i.code).collect();
+        let mut config = RuleConfig::default();
+        config.disable(codes::NO_IMAGES);
+        for code in &invalid_id {
+            config.disable(code);
+        }
+        validator.set_rule_config(&config).unwrap();
+        let after = validator.validate(&recipe);
+        assert!(after.valid, "{:?}", after.errors);
+        assert!(after.warnings.is_empty() && after.errors.is_empty());
+
+        validator.set_rule_config(RuleConfig::default().enable(codes::NO_IMAGES)).unwrap();
+        assert_eq!(validator.validate(&recipe).warnings.len(), 1);
+        let rule = validator.effective_rules().into_iter().find(|r| r.code == codes::NO_IMAGES).unwrap();
+        assert!(rule.enabled);
+    }
+}
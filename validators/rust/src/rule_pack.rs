@@ -0,0 +1,337 @@
+// WASM rule packs
+//
+// A rule pack is a WebAssembly module exporting `memory`,
+// `rcip_alloc(len) -> ptr` and `rcip_rule_run(ptr, len) -> i64`. The host
+// allocates a buffer, writes the recipe JSON into it and calls the rule,
+// which returns its output buffer packed as `ptr << 32 | len`. The output is
+// a JSON array of issues:
+//
+//     [{"code": "NO-PORK", "severity": "error", "message": "...", "path": "/ingredients/2"}]
+//
+// Codes are namespaced by the pack name (`acme/NO-PORK`). Every run gets a
+// fresh instance, so packs cannot carry state between recipes, and runs past
+// the time or memory cap are aborted and reported as RCIP-E-RULE-PACK-FAILED.
+//
+// The time cap uses epoch interruption. The epoch belongs to the engine,
+// which every run of a pack shares, so a timer bumping it reaches all runs
+// in flight; each run's store therefore checks its own deadline when the
+// epoch moves and carries on if it still has time. One recipe timing out
+// never cuts short another validated at the same moment.
+
+use crate::{Issue, Severity, ValidationResult};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmtime::{Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, Trap, UpdateDeadline};
+
+/// Resource caps for one rule run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RulePackLimits {
+    pub timeout: Duration,
+    pub max_memory_bytes: usize,
+}
+
+impl Default for RulePackLimits {
+    fn default() -> Self {
+        RulePackLimits {
+            timeout: Duration::from_millis(250),
+            max_memory_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Errors loading or running a rule pack
+#[derive(Debug, Clone, PartialEq)]
+pub enum RulePackError {
+    /// The module could not be read or compiled
+    Load(String),
+    /// A required export is missing or has the wrong signature
+    Abi(String),
+    /// The run exceeded its time cap
+    Timeout,
+    /// The module trapped, including on exceeding the memory cap
+    Trap(String),
+    /// The output was not a JSON issue list
+    BadOutput(String),
+}
+
+impl fmt::Display for RulePackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RulePackError::Load(e) => write!(f, "cannot load module: {}", e),
+            RulePackError::Abi(e) => write!(f, "ABI mismatch: {}", e),
+            RulePackError::Timeout => write!(f, "exceeded its time limit"),
+            RulePackError::Trap(e) => write!(f, "trapped: {}", e),
+            RulePackError::BadOutput(e) => write!(f, "invalid output: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RulePackError {}
+
+#[derive(Deserialize)]
+struct PackIssue {
+    code: String,
+    severity: Severity,
+    message: String,
+    #[serde(default)]
+    path: String,
+}
+
+lazy_static! {
+    static ref CODES: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
+/// Issue codes are `&'static str`; pack codes are interned once and kept
+fn intern(code: String) -> &'static str {
+    let mut codes = CODES.lock().unwrap();
+    match codes.get(code.as_str()) {
+        Some(code) => *code,
+        None => {
+            let code: &'static str = Box::leak(code.into_boxed_str());
+            codes.insert(code);
+            code
+        }
+    }
+}
+
+/// A compiled rule pack
+pub struct RulePack {
+    name: String,
+    sha256: String,
+    engine: Engine,
+    module: Module,
+    limits: RulePackLimits,
+}
+
+impl RulePack {
+    /// Load a pack from a `.wasm` file; its file stem becomes the code namespace
+    pub fn load(path: &Path) -> Result<Self, RulePackError> {
+        let bytes = std::fs::read(path).map_err(|e| RulePackError::Load(e.to_string()))?;
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("pack");
+        RulePack::from_bytes(name, &bytes)
+    }
+
+    /// Compile a pack from WASM bytes (or WAT text)
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, RulePackError> {
+        let mut config = Config::new();
+        config.epoch_interruption(true);
+        let engine = Engine::new(&config).map_err(|e| RulePackError::Load(e.to_string()))?;
+        let module = Module::new(&engine, bytes).map_err(|e| RulePackError::Load(e.to_string()))?;
+
+        for (export, what) in [("memory", "memory"), ("rcip_alloc", "function"), ("rcip_rule_run", "function")] {
+            if module.get_export(export).is_none() {
+                return Err(RulePackError::Abi(format!("missing {} export `{}`", what, export)));
+            }
+        }
+
+        Ok(RulePack {
+            name: name.to_string(),
+            sha256: crate::provenance::sha256_hex(bytes),
+            engine,
+            module,
+            limits: RulePackLimits::default(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// SHA-256 of the module bytes, for result provenance
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    pub fn set_limits(&mut self, limits: RulePackLimits) {
+        self.limits = limits;
+    }
+
+    /// Run the pack against a recipe; codes come back namespaced
+    pub fn run(&self, recipe: &serde_json::Value) -> Result<Vec<Issue>, RulePackError> {
+        let input = recipe.to_string();
+        let output = self.call(input.as_bytes())?;
+        let issues: Vec<PackIssue> =
+            serde_json::from_slice(&output).map_err(|e| RulePackError::BadOutput(e.to_string()))?;
+
+        Ok(issues
+            .into_iter()
+            .map(|issue| Issue {
+                code: intern(format!("{}/{}", self.name, issue.code)),
+                severity: issue.severity,
+                params: vec![("message", issue.message)],
+                path: issue.path,
+                pointer: String::new(),
+            })
+            .collect())
+    }
+
+    /// Run and record the issues, or the failure, into a result
+    pub fn check(&self, recipe: &serde_json::Value, result: &mut ValidationResult) {
+        match self.run(recipe) {
+            Ok(issues) => {
+                for issue in issues {
+                    result.push(issue);
+                }
+            }
+            Err(e) => result.error(crate::codes::RULE_PACK_FAILED, &[("pack", &self.name), ("reason", &e)]),
+        }
+    }
+
+    fn call(&self, input: &[u8]) -> Result<Vec<u8>, RulePackError> {
+        let limits: StoreLimits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory_bytes)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+
+        // Any run's timer may bump the shared epoch; only this run's own
+        // deadline stops it
+        let timeout = self.limits.timeout;
+        let deadline = Instant::now() + timeout;
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(move |_| {
+            if Instant::now() >= deadline {
+                Err(Trap::Interrupt.into())
+            } else {
+                Ok(UpdateDeadline::Continue(1))
+            }
+        });
+
+        // Bump the epoch once the time cap passes; dropping `done` cancels it
+        let (done, cancelled) = mpsc::channel::<()>();
+        let engine = self.engine.clone();
+        let timer = thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                engine.increment_epoch();
+            }
+        });
+
+        let output = self.call_in(&mut store, input);
+        drop(done);
+        let _ = timer.join();
+        output
+    }
+
+    fn call_in(&self, store: &mut Store<StoreLimits>, input: &[u8]) -> Result<Vec<u8>, RulePackError> {
+        let trap = |e: wasmtime::Error| match e.downcast_ref::<Trap>() {
+            Some(Trap::Interrupt) => RulePackError::Timeout,
+            _ => RulePackError::Trap(e.to_string()),
+        };
+        let abi = |e: wasmtime::Error| RulePackError::Abi(e.to_string());
+
+        let instance = Linker::new(&self.engine).instantiate(&mut *store, &self.module).map_err(trap)?;
+        let memory = instance
+            .get_memory(&mut *store, "memory")
+            .ok_or_else(|| RulePackError::Abi("`memory` is not a memory".to_string()))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut *store, "rcip_alloc").map_err(abi)?;
+        let run = instance.get_typed_func::<(i32, i32), i64>(&mut *store, "rcip_rule_run").map_err(abi)?;
+
+        let len = i32::try_from(input.len()).map_err(|_| RulePackError::Trap("recipe too large".to_string()))?;
+        let ptr = alloc.call(&mut *store, len).map_err(trap)?;
+        memory
+            .write(&mut *store, ptr as u32 as usize, input)
+            .map_err(|e| RulePackError::Abi(format!("rcip_alloc returned an unusable buffer: {}", e)))?;
+
+        let packed = run.call(&mut *store, (ptr, len)).map_err(trap)? as u64;
+        let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+        let mut output = vec![0; out_len];
+        memory
+            .read(&*store, out_ptr, &mut output)
+            .map_err(|e| RulePackError::BadOutput(format!("output buffer out of bounds: {}", e)))?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A pack whose rule body is `run` and whose output is fixed at offset 16
+    fn pack(output: &str, run: &str) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 4096))
+                (data (i32.const 16) "{}")
+                (func (export "rcip_alloc") (param $len i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $next))
+                    (global.set $next (i32.add (global.get $next) (local.get $len)))
+                    (local.get $ptr))
+                (func (export "rcip_rule_run") (param i32 i32) (result i64)
+                    {}
+                    (i64.or (i64.shl (i64.const 16) (i64.const 32)) (i64.const {}))))"#,
+            output.replace('"', "\\\""),
+            run,
+            output.len()
+        )
+    }
+
+    #[test]
+    fn test_issues_are_namespaced() {
+        let output = r#"[{"code":"NO-PORK","severity":"error","message":"bacon is pork","path":"/ingredients/0"}]"#;
+        let pack = RulePack::from_bytes("acme", pack(output, "").as_bytes()).unwrap();
+
+        let mut result = ValidationResult::new();
+        pack.check(&json!({"ingredients": [{"name": "bacon"}]}), &mut result);
+        assert!(!result.valid);
+        assert_eq!(result.issues[0].code, "acme/NO-PORK");
+        assert_eq!(result.issues[0].path, "/ingredients/0");
+        assert_eq!(result.errors, vec!["/ingredients/0: acme/NO-PORK: bacon is pork"]);
+    }
+
+    #[test]
+    fn test_caps_stop_misbehaving_packs() {
+        let mut spinner = RulePack::from_bytes("spin", pack("[]", "(loop $l (br $l))").as_bytes()).unwrap();
+        spinner.set_limits(RulePackLimits { timeout: Duration::from_millis(20), ..RulePackLimits::default() });
+        assert_eq!(spinner.run(&json!({})), Err(RulePackError::Timeout));
+
+        let hog = pack("[]", "(drop (memory.grow (i32.const 2048)))");
+        let mut hog = RulePack::from_bytes("hog", hog.as_bytes()).unwrap();
+        hog.set_limits(RulePackLimits { max_memory_bytes: 1024 * 1024, ..RulePackLimits::default() });
+        assert!(matches!(hog.run(&json!({})), Err(RulePackError::Trap(_))));
+
+        let mut result = ValidationResult::new();
+        spinner.check(&json!({}), &mut result);
+        assert_eq!(result.errors, vec!["Rule pack spin failed: exceeded its time limit"]);
+    }
+
+    #[test]
+    fn test_other_runs_timing_out_do_not_interrupt() {
+        let busy = "(local $i i32)
+            (loop $l
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $l (i32.lt_u (local.get $i) (i32.const 200000000))))";
+        let mut pack = RulePack::from_bytes("busy", pack("[]", busy).as_bytes()).unwrap();
+        pack.set_limits(RulePackLimits { timeout: Duration::from_secs(60), ..RulePackLimits::default() });
+
+        // Stand in for the timers of other runs expiring while this one works
+        let running = std::sync::atomic::AtomicBool::new(true);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                while running.load(std::sync::atomic::Ordering::Relaxed) {
+                    pack.engine.increment_epoch();
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+            let outcome = pack.run(&json!({}));
+            running.store(false, std::sync::atomic::Ordering::Relaxed);
+            assert_eq!(outcome, Ok(Vec::new()));
+        });
+    }
+
+    #[test]
+    fn test_abi_is_checked_on_load() {
+        let result = RulePack::from_bytes("empty", b"(module (memory (export \"memory\") 1))");
+        assert!(matches!(result, Err(RulePackError::Abi(_))));
+        assert!(matches!(RulePack::from_bytes("junk", b"not wasm"), Err(RulePackError::Load(_))));
+    }
+}
@@ -0,0 +1,93 @@
+// Custom rules
+//
+// Requirements of one organization, such as an internal SKU on every
+// ingredient, do not belong in the built-in rules. A `ValidationRule` added
+// with `RCIPValidator::add_rule` runs after every built-in rule, in the order
+// rules were added, and records its findings like they do. Its codes are its
+// own: a code without a message template is shown with the `message`
+// parameter, so
+//
+//     result.error("ACME-E-NO-SKU", &[("message", &"ingredient has no SKU")]);
+//
+// reads "ACME-E-NO-SKU: ingredient has no SKU". Findings recorded inside
+// `result.within(pointer, ...)` point at the offending value. Severity
+// overrides and `strict_warnings` apply to them; `RuleConfig` only knows the
+// built-in codes.
+
+use crate::ValidationResult;
+use serde_json::Value;
+
+/// A check run on every recipe after the built-in rules
+pub trait ValidationRule: Send + Sync {
+    /// Shown in `--explain-config` and hashed into result provenance
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Record findings about `recipe` into `result`
+    fn check(&self, recipe: &Value, result: &mut ValidationResult);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RCIPValidator, Severity};
+
+    /// Every ingredient must carry an internal SKU in `external_ids`
+    struct RequireSku;
+
+    impl ValidationRule for RequireSku {
+        fn name(&self) -> &str {
+            "require-sku"
+        }
+
+        fn check(&self, recipe: &Value, result: &mut ValidationResult) {
+            let ingredients = recipe["ingredients"].as_array().into_iter().flatten();
+            for (i, ingredient) in ingredients.enumerate() {
+                if ingredient.pointer("/external_ids/acme_sku").is_none() {
+                    result.within(&format!("/ingredients/{}", i), |result| {
+                        result.error("ACME-E-NO-SKU", &[("message", &format!("{} has no ACME SKU", ingredient["id"]))])
+                    });
+                }
+            }
+        }
+    }
+
+    /// Step texts must be short enough for the kitchen display
+    struct ShortSteps(usize);
+
+    impl ValidationRule for ShortSteps {
+        fn check(&self, recipe: &Value, result: &mut ValidationResult) {
+            let steps = recipe["steps"].as_array().into_iter().flatten();
+            for (i, step) in steps.enumerate() {
+                let length = step["human_text"].as_str().map_or(0, |text| text.chars().count());
+                if length > self.0 {
+                    result.within(&format!("/steps/{}/human_text", i), |result| {
+                        result.warning("ACME-W-LONG-STEP", &[("message", &format!("{} characters", length))])
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_rules_report_after_built_in_rules() {
+        let recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        assert!(validator.validate(&recipe).valid);
+
+        validator.add_rule(Box::new(RequireSku));
+        validator.add_rule(Box::new(ShortSteps(20)));
+        let result = validator.validate(&recipe);
+        assert!(!result.valid);
+        let sku = result.issues.iter().find(|i| i.code == "ACME-E-NO-SKU").unwrap();
+        assert_eq!(sku.severity, Severity::Error);
+        assert_eq!(sku.pointer, "/ingredients/0");
+        assert!(result.errors.iter().any(|e| e.starts_with("ACME-E-NO-SKU: \"ing-0001\" has no ACME SKU")), "{:?}", result.errors);
+        assert!(result.issues.iter().any(|i| i.code == "ACME-W-LONG-STEP" && i.pointer.starts_with("/steps/")));
+
+        let names = &validator.rule_config()["custom_rules"];
+        assert_eq!(names[0], "require-sku");
+        assert!(names[1].as_str().unwrap().ends_with("ShortSteps"));
+    }
+}
@@ -0,0 +1,627 @@
+// Recipe scaling
+//
+// Scaling with equipment constraints tracks what is "in the vessel" after
+// each step: a step's contents are the machine amounts of the ingredients it
+// targets plus the contents of any result (`s-xx:result` or `@name`) it
+// targets, or an equal share of them for one portion of a divided result
+// (`s-xx:result[2]`). A step with no targets continues working on the previous step's
+// contents. Mass and volume are accumulated separately; count and special
+// units are not measured.
+//
+// When a step's vessel would overflow, the recipe is split into N parallel
+// batches. Every ingredient and step is duplicated once per batch with a
+// `b<k>` suffix (`s-03` becomes `s-03b1`, `s-03b2`; the id patterns do not
+// allow a second dash). Each ingredient copy holds 1/N of the scaled amount,
+// rounded like any other scaling, so the batches together use what the whole
+// recipe does and no vessel holds more than one batch. Ingredient ids, result names and references are
+// rewritten within each batch, and each copy records its batch in an
+// `x-batch` extension object.
+//
+// `scale_recipe_with` is the one place amounts are multiplied, for plain
+// scaling, for scaling under constraints and for batch shares alike. It keeps
+// the result valid: machine amounts are rounded to the precision the unit
+// allows (`PrecisionPolicy`), counts to whole pieces as `CountRounding`
+// says, and `pinch`, `dash`, `handful` and `to-taste` amounts are left as
+// they are. The leading quantity of a `human_amount` ("1 1/2 cups, sifted"),
+// as `amounts` reads it, is rewritten too; one without a plain quantity
+// ("enough to cover", "2-3", "2 or 3") is kept and reported in
+// `Scaled::warnings`.
+
+use crate::amounts::leading_amount;
+use crate::intermediates::producer;
+use crate::portions;
+use crate::precision::{number_value, round_half_even, PrecisionPolicy};
+use crate::units::Dimension;
+use crate::{RCIPError, Unit};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Units whose amounts do not scale
+const UNSCALED_UNITS: &[&str] = &["pinch", "dash", "handful", "to-taste"];
+
+/// Capacity of one piece of equipment
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Capacity {
+    #[serde(default)]
+    pub max_g: Option<f64>,
+    #[serde(default)]
+    pub max_ml: Option<f64>,
+}
+
+/// Declared equipment capacities, keyed by vessel or device profile id
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct EquipmentCapacities {
+    pub equipment: HashMap<String, Capacity>,
+}
+
+impl EquipmentCapacities {
+    /// Load a capacity file: `{"equipment": {"bowl": {"max_g": 5000, "max_ml": 4000}}}`
+    pub fn from_file(path: &Path) -> Result<Self, RCIPError> {
+        let content = fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&content)?;
+        Self::from_value(&value)
+    }
+
+    /// Parse and check a capacity document
+    pub fn from_value(value: &Value) -> Result<Self, RCIPError> {
+        let capacities = EquipmentCapacities::deserialize(value)?;
+
+        for (name, capacity) in &capacities.equipment {
+            let limits = [capacity.max_g, capacity.max_ml];
+            if limits.iter().all(|l| l.is_none()) {
+                return Err(RCIPError::ValidationError(format!(
+                    "equipment '{}' declares neither max_g nor max_ml", name
+                )));
+            }
+            if limits.iter().flatten().any(|l| *l <= 0.0) {
+                return Err(RCIPError::ValidationError(format!(
+                    "equipment '{}' capacities must be positive", name
+                )));
+            }
+        }
+
+        Ok(capacities)
+    }
+}
+
+/// Measured contents of a vessel
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Contents {
+    pub grams: f64,
+    pub ml: f64,
+}
+
+impl Contents {
+    fn add(&mut self, other: Contents) {
+        self.grams += other.grams;
+        self.ml += other.ml;
+    }
+
+    fn times(self, factor: f64) -> Contents {
+        Contents { grams: self.grams * factor, ml: self.ml * factor }
+    }
+}
+
+/// A step whose contents exceed its equipment's capacity
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CapacityViolation {
+    pub step_id: String,
+    pub equipment: String,
+    pub required: f64,
+    pub capacity: f64,
+    pub unit: String,
+}
+
+/// Outcome of scaling under equipment constraints
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstrainedScaling {
+    pub recipe: Value,
+    pub batches: usize,
+    pub violations: Vec<CapacityViolation>,
+    /// Human amounts left as they were
+    pub warnings: Vec<ScaleWarning>,
+}
+
+/// Mass in grams or volume in millilitres of a machine amount
+pub(crate) fn measured(value: f64, unit: &str) -> Option<Contents> {
+    let unit = Unit::parse(unit)?;
+    match unit.dimension() {
+        Dimension::Mass => Some(Contents { grams: unit.to_base(value), ml: 0.0 }),
+        Dimension::Volume => Some(Contents { grams: 0.0, ml: unit.to_base(value) }),
+        Dimension::Count | Dimension::Special => None,
+    }
+}
+
+/// How scaled counts (`pcs`) are rounded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CountRounding {
+    /// To the nearest whole piece, never below one
+    #[default]
+    Round,
+    /// Up to the next whole piece
+    Ceil,
+    /// Keep the fraction; the validator reports it as `NOT_WHOLE_COUNT`
+    Keep,
+}
+
+impl CountRounding {
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            CountRounding::Round if value > 0.0 => value.round().max(1.0),
+            CountRounding::Round => value.round(),
+            CountRounding::Ceil => (value - 1e-9).ceil(),
+            CountRounding::Keep => round_half_even(value, 2),
+        }
+    }
+}
+
+/// Options for `scale_recipe_with` and `scale_with_constraints`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScaleOptions {
+    pub counts: CountRounding,
+    pub precision: PrecisionPolicy,
+}
+
+/// A human amount `scale_recipe_with` could not rewrite
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScaleWarning {
+    pub ingredient_id: String,
+    pub human_amount: String,
+}
+
+impl fmt::Display for ScaleWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: human amount '{}' has no quantity to scale and was kept", self.ingredient_id, self.human_amount)
+    }
+}
+
+/// A scaled recipe and the human amounts left as they were
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scaled {
+    pub recipe: Value,
+    pub warnings: Vec<ScaleWarning>,
+}
+
+/// Scale a recipe by `factor` with the default options
+pub fn scale_recipe(recipe: &Value, factor: f64) -> Result<Value, RCIPError> {
+    scale_recipe_with(recipe, factor, &ScaleOptions::default()).map(|scaled| scaled.recipe)
+}
+
+/// Scale a recipe so that it makes `servings` servings
+pub fn scale_to_servings(recipe: &Value, servings: f64, options: &ScaleOptions) -> Result<Scaled, RCIPError> {
+    let current = recipe.pointer("/meta/servings/amount").and_then(|v| v.as_f64()).filter(|amount| *amount > 0.0);
+    match current {
+        Some(current) => scale_recipe_with(recipe, servings / current, options),
+        None => Err(RCIPError::ValidationError("the recipe declares no servings to scale from".to_string())),
+    }
+}
+
+/// Scale every machine amount, human amount and the serving count by `factor`
+pub fn scale_recipe_with(recipe: &Value, factor: f64, options: &ScaleOptions) -> Result<Scaled, RCIPError> {
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err(RCIPError::ValidationError(format!("scale factor must be positive, got {}", factor)));
+    }
+
+    let mut scaled = recipe.clone();
+    let mut warnings = Vec::new();
+
+    for ingredient in scaled.get_mut("ingredients").and_then(|v| v.as_array_mut()).into_iter().flatten() {
+        let id = ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        let unit = ingredient.pointer("/machine_amount/unit").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        if UNSCALED_UNITS.contains(&unit.as_str()) {
+            continue;
+        }
+        let count = options.precision.is_count_unit(&unit);
+        let round = |value: f64| match options.precision.decimals_for(&unit) {
+            _ if count => options.counts.apply(value),
+            Some(decimals) => round_half_even(value, decimals),
+            None => value,
+        };
+
+        if let Some(ma) = ingredient.get_mut("machine_amount").and_then(|v| v.as_object_mut()) {
+            if let Some(value) = ma.get("value").and_then(|v| v.as_f64()) {
+                ma.insert("value".to_string(), number_value(round(value * factor)));
+            }
+            if let Some(tolerance) = ma.get_mut("tolerance").and_then(|v| v.as_object_mut()) {
+                for bound in tolerance.values_mut() {
+                    if let Some(value) = bound.as_f64() {
+                        *bound = number_value(round(value * factor));
+                    }
+                }
+            }
+        }
+
+        if let Some(human) = ingredient.get("human_amount").and_then(|v| v.as_str()).map(str::to_string) {
+            let rewritten = scale_human_amount(&human, factor, |value| if count { options.counts.apply(value) } else { value });
+            match rewritten {
+                Some(rewritten) => ingredient["human_amount"] = json!(rewritten),
+                None => warnings.push(ScaleWarning { ingredient_id: id, human_amount: human }),
+            }
+        }
+    }
+
+    if let Some(servings) = scaled.pointer_mut("/meta/servings").and_then(|v| v.as_object_mut()) {
+        if let Some(amount) = servings.get("amount").and_then(|v| v.as_f64()) {
+            servings.insert("amount".to_string(), number_value(round_half_even(amount * factor, 2)));
+        }
+    }
+
+    Ok(Scaled { recipe: scaled, warnings })
+}
+
+/// `human` with its leading quantity multiplied by `factor` and passed
+/// through `round`, or `None` when it has no single quantity to scale
+fn scale_human_amount(human: &str, factor: f64, round: impl Fn(f64) -> f64) -> Option<String> {
+    let trimmed = human.trim();
+    let leading = leading_amount(trimmed)?;
+    if leading.quantity_end == 0 || leading.amount.max.is_some() {
+        return None;
+    }
+    let value = round(leading.amount.value * factor);
+    Some(format!("{}{}", format_quantity(value), &trimmed[leading.quantity_end..]))
+}
+
+/// A quantity as a cook writes it: "3", "1 1/2", "2/3" or "0.15"
+fn format_quantity(value: f64) -> String {
+    const FRACTIONS: &[(f64, &str)] = &[(0.25, "1/4"), (1.0 / 3.0, "1/3"), (0.5, "1/2"), (2.0 / 3.0, "2/3"), (0.75, "3/4")];
+
+    let whole = value.floor();
+    if value - whole < 0.01 || whole + 1.0 - value < 0.01 {
+        return format!("{}", value.round());
+    }
+    match FRACTIONS.iter().find(|(fraction, _)| (value - whole - fraction).abs() < 0.01) {
+        Some((_, fraction)) if whole == 0.0 => fraction.to_string(),
+        Some((_, fraction)) => format!("{} {}", whole, fraction),
+        None => number_value(round_half_even(value, 2)).to_string(),
+    }
+}
+
+/// Contents of each step's vessel, in step order
+pub fn step_contents(recipe: &Value) -> Vec<(String, Contents)> {
+    let mut ingredients: HashMap<&str, Contents> = HashMap::new();
+    for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+        let id = match ingredient.get("id").and_then(|v| v.as_str()) {
+            Some(id) => id,
+            None => continue,
+        };
+        let ma = ingredient.get("machine_amount");
+        let value = ma.and_then(|m| m.get("value")).and_then(|v| v.as_f64());
+        let unit = ma.and_then(|m| m.get("unit")).and_then(|v| v.as_str());
+        if let (Some(value), Some(unit)) = (value, unit) {
+            if let Some(contents) = measured(value, unit) {
+                ingredients.insert(id, contents);
+            }
+        }
+    }
+
+    let mut results: HashMap<String, Contents> = HashMap::new();
+    let mut contents_by_step = Vec::new();
+    let mut previous = Contents::default();
+
+    for step in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten() {
+        let step_id = step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?").to_string();
+        let targets: Vec<&str> = step.get("target")
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|t| t.as_str()).collect())
+            .unwrap_or_default();
+
+        let mut contents = Contents::default();
+        if targets.is_empty() {
+            contents = previous;
+        }
+        for target in targets {
+            if let Some(step_ref) = producer(recipe, target) {
+                if let Some(result) = results.get(step_ref) {
+                    contents.add(result.times(portions::share(recipe, target)));
+                }
+            } else if let Some(ingredient) = ingredients.get(target) {
+                contents.add(*ingredient);
+            }
+        }
+
+        results.insert(step_id.clone(), contents);
+        contents_by_step.push((step_id, contents));
+        previous = contents;
+    }
+
+    contents_by_step
+}
+
+pub(crate) fn step_equipment(step: &Value) -> Option<&str> {
+    step.get("params")
+        .and_then(|p| p.get("vessel"))
+        .and_then(|v| v.as_str())
+        .or_else(|| step.get("device_profile_ref").and_then(|v| v.as_str()))
+}
+
+/// Scale a recipe, splitting it into parallel batches when any step would
+/// exceed its equipment's declared capacity
+pub fn scale_with_constraints(
+    recipe: &Value,
+    factor: f64,
+    constraints: &EquipmentCapacities,
+    options: &ScaleOptions,
+) -> Result<ConstrainedScaling, RCIPError> {
+    let Scaled { recipe: mut scaled, warnings } = scale_recipe_with(recipe, factor, options)?;
+
+    let steps = scaled.get("steps").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let mut violations = Vec::new();
+    let mut batches = 1usize;
+
+    for ((step_id, contents), step) in step_contents(&scaled).into_iter().zip(steps.iter()) {
+        let equipment = match step_equipment(step) {
+            Some(equipment) => equipment,
+            None => continue,
+        };
+        let capacity = match constraints.equipment.get(equipment) {
+            Some(capacity) => capacity,
+            None => continue,
+        };
+
+        let checks = [(contents.grams, capacity.max_g, "g"), (contents.ml, capacity.max_ml, "ml")];
+        for (required, limit, unit) in checks {
+            if let Some(limit) = limit {
+                if required > limit {
+                    // Tolerate float noise so 3000.0000000000005 ml over 1500 ml is 2 batches
+                    batches = batches.max((required / limit - 1e-9).ceil() as usize);
+                    violations.push(CapacityViolation {
+                        step_id: step_id.clone(),
+                        equipment: equipment.to_string(),
+                        required,
+                        capacity: limit,
+                        unit: unit.to_string(),
+                    });
+                }
+            }
+        }
+    }
+
+    if batches > 1 {
+        split_batches(&mut scaled, batches, options);
+    }
+
+    Ok(ConstrainedScaling { recipe: scaled, batches, violations, warnings })
+}
+
+/// Duplicate the ingredient and step lists once per batch, each ingredient
+/// copy holding an equal share, and rewrite ids, result names and references
+/// within each copy
+fn split_batches(recipe: &mut Value, batches: usize, options: &ScaleOptions) {
+    let steps = match recipe.get("steps").and_then(|v| v.as_array()) {
+        Some(steps) => steps.clone(),
+        None => return,
+    };
+    // A human amount the share cannot rewrite was already kept, and reported,
+    // when the whole recipe was scaled
+    let whole = json!({"ingredients": recipe.get("ingredients").cloned().unwrap_or_else(|| json!([]))});
+    let share = match scale_recipe_with(&whole, 1.0 / batches as f64, options) {
+        Ok(share) => share,
+        Err(_) => return,
+    };
+    let ingredients = share.recipe["ingredients"].as_array().cloned().unwrap_or_default();
+    let ingredient_ids: HashSet<&str> = ingredients.iter().filter_map(|i| i.get("id").and_then(|v| v.as_str())).collect();
+
+    let mut portioned = Vec::with_capacity(ingredients.len() * batches);
+    let mut split = Vec::with_capacity(steps.len() * batches);
+    for batch in 1..=batches {
+        let suffix = format!("b{}", batch);
+        for ingredient in &ingredients {
+            let mut copy = ingredient.clone();
+            if let Some(id) = copy.get("id").and_then(|v| v.as_str()).map(|id| format!("{}{}", id, suffix)) {
+                copy["id"] = json!(id);
+            }
+            copy["x-batch"] = json!({"index": batch, "of": batches});
+            portioned.push(copy);
+        }
+        for step in &steps {
+            let mut copy = step.clone();
+            let batch_id = copy.get("step_id").and_then(|v| v.as_str()).map(|id| format!("{}{}", id, suffix));
+            if let Some(batch_id) = batch_id {
+                copy["step_id"] = json!(batch_id);
+            }
+            if let Some(targets) = copy.get_mut("target").and_then(|v| v.as_array_mut()) {
+                for target in targets {
+                    let text = target.as_str().unwrap_or("");
+                    let portion = portions::split(text).map(|(_, index)| format!("[{}]", index)).unwrap_or_default();
+                    let whole = portions::whole(text);
+                    if let Some(step_ref) = whole.strip_suffix(":result") {
+                        *target = json!(format!("{}{}:result{}", step_ref, suffix, portion));
+                    } else if let Some(name) = whole.strip_prefix('@') {
+                        *target = json!(format!("@{} {}{}", name, suffix, portion));
+                    } else if ingredient_ids.contains(whole) {
+                        *target = json!(format!("{}{}", whole, suffix));
+                    }
+                }
+            }
+            if let Some(name) = copy.pointer("/result/name").and_then(|v| v.as_str()).map(|n| format!("{} {}", n, suffix)) {
+                copy["result"]["name"] = json!(name);
+            }
+            copy["x-batch"] = json!({"index": batch, "of": batches});
+            split.push(copy);
+        }
+    }
+
+    recipe["ingredients"] = Value::Array(portioned);
+    recipe["steps"] = Value::Array(split);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe() -> Value {
+        json!({
+            "meta": {"servings": {"amount": 4}},
+            "ingredients": [
+                {"id": "ing-0001", "machine_amount": {"value": 500, "unit": "g"}},
+                {"id": "ing-0002", "machine_amount": {"value": 0.3, "unit": "l"}},
+                {"id": "ing-0003", "human_amount": "2 eggs", "machine_amount": {"value": 2, "unit": "pcs"}},
+                {"id": "ing-0004", "human_amount": "a pinch", "machine_amount": {"value": 1, "unit": "pinch"}}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "target": ["ing-0001", "ing-0002", "ing-0003"],
+                 "params": {"vessel": "bowl"}},
+                {"step_id": "s-02", "action": "knead"},
+                {"step_id": "s-03", "action": "bake", "target": ["s-02:result"], "device_profile_ref": "oven"}
+            ]
+        })
+    }
+
+    fn capacities() -> EquipmentCapacities {
+        EquipmentCapacities::from_value(&json!({"equipment": {
+            "bowl": {"max_g": 2000, "max_ml": 1500},
+            "oven": {"max_g": 6000}
+        }}))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_step_contents_accumulation() {
+        let contents = step_contents(&recipe());
+        assert_eq!(contents[0].1, Contents { grams: 500.0, ml: 300.0 });
+        // s-02 has no targets and continues with the previous contents
+        assert_eq!(contents[1].1, contents[0].1);
+        assert_eq!(contents[2].1, contents[0].1);
+
+        // One of four portions holds a quarter of the divided dough
+        let mut divided = recipe();
+        divided["steps"][1] = json!({"step_id": "s-02", "action": "divide", "params": {"portions": 4}});
+        divided["steps"][2]["target"] = json!(["s-02:result[3]"]);
+        assert_eq!(step_contents(&divided)[2].1, Contents { grams: 125.0, ml: 75.0 });
+    }
+
+    #[test]
+    fn test_scaling_within_capacity() {
+        let scaled = scale_with_constraints(&recipe(), 2.0, &capacities(), &ScaleOptions::default()).unwrap();
+        assert_eq!(scaled.batches, 1);
+        assert!(scaled.violations.is_empty());
+        assert_eq!(scaled.recipe["ingredients"][0]["machine_amount"]["value"], json!(1000));
+        assert_eq!(scaled.recipe["meta"]["servings"]["amount"], json!(8));
+
+        // Constrained scaling rounds counts and keeps pinches as plain scaling does
+        let scaled = scale_with_constraints(&recipe(), 1.3, &capacities(), &ScaleOptions::default()).unwrap();
+        assert_eq!(scaled.recipe["ingredients"][2]["machine_amount"]["value"], json!(3));
+        assert_eq!(scaled.recipe["ingredients"][2]["human_amount"], "3 eggs");
+        assert_eq!(scaled.recipe["ingredients"][3], recipe()["ingredients"][3]);
+        assert_eq!(scaled.recipe, scale_recipe(&recipe(), 1.3).unwrap());
+    }
+
+    #[test]
+    fn test_split_into_batches_rewrites_ids() {
+        let scaled = scale_with_constraints(&recipe(), 10.0, &capacities(), &ScaleOptions::default()).unwrap();
+
+        // 5000 g in a 2000 g bowl needs 3 batches, 3000 ml in a 1500 ml bowl needs 2
+        assert_eq!(scaled.batches, 3);
+        assert_eq!(scaled.violations.len(), 2);
+        assert_eq!(scaled.violations[0].equipment, "bowl");
+
+        let steps = scaled.recipe["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 9);
+        assert_eq!(steps[0]["step_id"], "s-01b1");
+        assert_eq!(steps[5]["step_id"], "s-03b2");
+        assert_eq!(steps[5]["target"], json!(["s-02b2:result"]));
+        assert_eq!(steps[8]["x-batch"], json!({"index": 3, "of": 3}));
+        assert_eq!(steps[3]["target"], json!(["ing-0001b2", "ing-0002b2", "ing-0003b2"]));
+
+        // Each batch gets a third of every scaled amount, rounded, so no bowl
+        // overflows; a pinch stays a pinch in every batch
+        let ingredients = scaled.recipe["ingredients"].as_array().unwrap();
+        assert_eq!(ingredients.len(), 12);
+        assert_eq!(ingredients[4]["id"], "ing-0001b2");
+        assert_eq!(ingredients[4]["x-batch"], json!({"index": 2, "of": 3}));
+        for batch in ingredients.chunks(4) {
+            assert_eq!(batch[0]["machine_amount"]["value"], json!(1666.7));
+            assert_eq!(batch[1]["machine_amount"]["value"], json!(1));
+            assert_eq!(batch[2]["machine_amount"]["value"], json!(7));
+            assert_eq!(batch[2]["human_amount"], "7 eggs");
+            assert_eq!(batch[3]["machine_amount"]["value"], json!(1));
+        }
+        for (step_id, contents) in step_contents(&scaled.recipe).iter().filter(|(id, _)| id.starts_with("s-01")) {
+            assert!(contents.grams <= 2000.0 && contents.ml <= 1500.0, "{}: {:?}", step_id, contents);
+        }
+
+        let mut named = recipe();
+        named["steps"][1]["result"] = json!({"name": "dough"});
+        named["steps"][2]["target"] = json!(["@dough"]);
+        let scaled = scale_with_constraints(&named, 10.0, &capacities(), &ScaleOptions::default()).unwrap();
+        assert_eq!(scaled.recipe["steps"][4]["result"]["name"], "dough b2");
+        assert_eq!(scaled.recipe["steps"][5]["target"], json!(["@dough b2"]));
+
+        named["steps"][2]["target"] = json!(["@dough[1]", "s-02:result[2]"]);
+        let scaled = scale_with_constraints(&named, 10.0, &capacities(), &ScaleOptions::default()).unwrap();
+        assert_eq!(scaled.recipe["steps"][5]["target"], json!(["@dough b2[1]", "s-02b2:result[2]"]));
+    }
+
+    #[test]
+    fn test_scaled_examples_still_validate() {
+        let validator = crate::warm::global_validator("0.1").unwrap();
+        for text in [
+            include_str!("../../../examples/simple-example.rcip"),
+            include_str!("../../../examples/margherita-pizza.rcip"),
+            include_str!("../../../examples/ukrainian-borscht.rcip"),
+        ] {
+            let recipe: Value = serde_json::from_str(text).unwrap();
+            for factor in [0.3, 2.5, 7.0] {
+                let scaled = scale_recipe(&recipe, factor).unwrap();
+                let result = validator.validate(&scaled);
+                assert!(result.valid, "{} x{}: {:?}", recipe["id"], factor, result.errors);
+                assert!(!result.issues.iter().any(|i| i.code == crate::codes::TOO_PRECISE), "{:?}", result.warnings);
+            }
+        }
+
+        let egg: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let scaled = scale_to_servings(&egg, 2.5, &ScaleOptions::default()).unwrap();
+        assert_eq!(scaled.recipe["meta"]["servings"]["amount"], json!(2.5));
+        assert_eq!(scaled.recipe["ingredients"][0]["machine_amount"]["value"], json!(3));
+        assert_eq!(scaled.recipe["ingredients"][0]["human_amount"], "3 egg");
+        assert_eq!(scaled.recipe["ingredients"][1]["machine_amount"]["value"], json!(1250));
+        assert_eq!(scaled.warnings[0].to_string(), "ing-0002: human amount 'enough to cover egg' has no quantity to scale and was kept");
+    }
+
+    #[test]
+    fn test_counts_units_and_human_amounts() {
+        let recipe = json!({"ingredients": [
+            {"id": "ing-0001", "human_amount": "1½ cups, sifted", "machine_amount": {"value": 2, "unit": "pcs"}},
+            {"id": "ing-0002", "human_amount": "a pinch", "machine_amount": {"value": 1, "unit": "pinch"}},
+            {"id": "ing-0003", "human_amount": "1 1/2 tbsp", "machine_amount": {"value": 22.5, "unit": "ml"}},
+            {"id": "ing-0004", "human_amount": "2-3 cloves", "machine_amount": {"value": 1, "unit": "pcs"}},
+            {"id": "ing-0005", "human_amount": "2 or 3 sprigs", "machine_amount": {"value": 2, "unit": "pcs"}},
+            {"id": "ing-0006", "human_amount": "300g flour", "machine_amount": {"value": 300, "unit": "g"}}
+        ]});
+        let scaled = |factor: f64, counts: CountRounding| {
+            scale_recipe_with(&recipe, factor, &ScaleOptions { counts, ..ScaleOptions::default() }).unwrap()
+        };
+
+        let third = scaled(1.0 / 3.0, CountRounding::Round);
+        assert_eq!(third.recipe["ingredients"][0]["machine_amount"]["value"], json!(1));
+        assert_eq!(third.recipe["ingredients"][0]["human_amount"], "1 cups, sifted");
+        assert_eq!(third.recipe["ingredients"][1], recipe["ingredients"][1]);
+        assert_eq!(third.recipe["ingredients"][2]["machine_amount"]["value"], json!(7.5));
+        assert_eq!(third.recipe["ingredients"][2]["human_amount"], "1/2 tbsp");
+        assert_eq!(third.recipe["ingredients"][5]["human_amount"], "100g flour");
+        let kept: Vec<&str> = third.warnings.iter().map(|w| w.human_amount.as_str()).collect();
+        assert_eq!(kept, vec!["2-3 cloves", "2 or 3 sprigs"]);
+
+        let ceil = scaled(1.25, CountRounding::Ceil);
+        assert_eq!(ceil.recipe["ingredients"][0]["machine_amount"]["value"], json!(3));
+        assert_eq!(ceil.recipe["ingredients"][3]["machine_amount"]["value"], json!(2));
+        let keep = scaled(1.25, CountRounding::Keep);
+        assert_eq!(keep.recipe["ingredients"][0]["machine_amount"]["value"], json!(2.5));
+        assert_eq!(keep.recipe["ingredients"][2]["human_amount"], "1.88 tbsp");
+
+        assert!(scale_recipe(&recipe, 0.0).is_err());
+        assert!(scale_recipe(&recipe, -2.0).is_err());
+        assert!(scale_to_servings(&recipe, 4.0, &ScaleOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        assert!(scale_with_constraints(&recipe(), f64::INFINITY, &capacities(), &ScaleOptions::default()).is_err());
+        assert!(scale_with_constraints(&recipe(), 0.0, &capacities(), &ScaleOptions::default()).is_err());
+        assert!(EquipmentCapacities::from_value(&json!({"equipment": {"bowl": {}}})).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+// Release self-check
+//
+// Verifies at runtime what the validator ships with: every bundled JSON
+// schema compiles and agrees with the built-in rules, each vocabulary the
+// rules use (actions, allergens, units, diet labels, hazards) matches both
+// its enum in code and the schema's enum, every rule code is well formed and
+// has a template in every bundled locale, and every example recipe passes
+// every schema. Each problem is one line naming the file, vocabulary or code
+// at fault, so a packager can gate a release on an empty report. The
+// deprecation registry is checked for entries validation and migration
+// could not both follow, and against what each schema declares.
+
+use crate::deprecations::{self, Deprecation};
+use crate::{allergens, codes, explain, i18n, introspect, schema_divergences};
+use crate::{Allergen, CookingAction, DietLabel, Hazard, Unit};
+use crate::{COOKING_ACTIONS, DIET_LABELS, HAZARDS, UNITS};
+use jsonschema::JSONSchema;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+lazy_static! {
+    static ref CODE_REGEX: Regex = Regex::new(r"^RCIP-[EWI]-[A-Z0-9]+(-[A-Z0-9]+)*$").unwrap();
+    static ref PLACEHOLDER_REGEX: Regex = Regex::new(r"\{([a-z_]+)\}").unwrap();
+}
+
+fn parses<T: DeserializeOwned>(name: &str) -> bool {
+    serde_json::from_value::<T>(Value::from(name)).is_ok()
+}
+
+/// (vocabulary, schema path, names the rules accept, whether a name is a
+/// variant of the enum in code). Actions are compared with the schema by
+/// `schema_divergences`
+type Vocabulary = (&'static str, Option<&'static str>, &'static [&'static str], fn(&str) -> bool);
+
+const VOCABULARIES: &[Vocabulary] = &[
+    ("actions", None, COOKING_ACTIONS, parses::<CookingAction>),
+    ("allergens", Some("/ingredients/*/allergens"), allergens::VOCABULARY, parses::<Allergen>),
+    ("units", Some("/ingredients/*/machine_amount/unit"), UNITS, parses::<Unit>),
+    ("diet labels", Some("/meta/diet_labels"), DIET_LABELS, parses::<DietLabel>),
+    ("hazards", Some("/steps/*/hazards"), HAZARDS, parses::<Hazard>),
+];
+
+/// Files in `dir` with the given extension, sorted
+fn files(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut found: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().map(|e| e == extension).unwrap_or(false))
+        .collect();
+    found.sort();
+    found
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn difference<'a>(left: &[&'a str], right: &[&str]) -> Vec<&'a str> {
+    left.iter().copied().filter(|v| !right.contains(v)).collect()
+}
+
+/// Vocabularies against their enums in code
+pub fn check_vocabularies() -> Vec<String> {
+    let mut problems = Vec::new();
+    for (vocabulary, _, values, in_enum) in VOCABULARIES {
+        let unknown: Vec<&str> = values.iter().copied().filter(|v| !in_enum(v)).collect();
+        if !unknown.is_empty() {
+            problems.push(format!("{} are not variants of the enum in code: {}", vocabulary, unknown.join(", ")));
+        }
+    }
+
+    let diets: Vec<&str> = allergens::DIET_EXCLUSIONS.iter().map(|(diet, _)| *diet).collect();
+    let unknown = difference(&diets, DIET_LABELS);
+    if !unknown.is_empty() {
+        problems.push(format!("diet exclusions name unknown diet labels: {}", unknown.join(", ")));
+    }
+    problems
+}
+
+/// Vocabularies against the enums of one schema
+fn check_schema_enums(name: &str, schema: &Value, problems: &mut Vec<String>) {
+    for (vocabulary, path, values, _) in VOCABULARIES {
+        let path = match path {
+            Some(path) => path,
+            None => continue,
+        };
+        match introspect::enum_values(schema, path) {
+            Some(allowed) => {
+                let missing = difference(values, &allowed);
+                let extra = difference(&allowed, values);
+                if !missing.is_empty() {
+                    problems.push(format!("{}: {} enum lacks {}", name, vocabulary, missing.join(", ")));
+                }
+                if !extra.is_empty() {
+                    problems.push(format!("{}: {} enum allows {}, unknown to the rules", name, vocabulary, extra.join(", ")));
+                }
+            }
+            None => problems.push(format!("{}: no {} enum at {}", name, vocabulary, path)),
+        }
+    }
+}
+
+/// Rule codes against the catalogs and the rule settings table
+pub fn check_codes() -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut seen = HashSet::new();
+    for code in codes::ALL {
+        if !CODE_REGEX.is_match(code) {
+            problems.push(format!("rule code {} is not of the form RCIP-<E|W|I>-NAME", code));
+        }
+        if !seen.insert(*code) {
+            problems.push(format!("rule code {} is listed twice", code));
+        }
+    }
+
+    for (code, _, _) in explain::RULE_SETTINGS {
+        if !codes::ALL.contains(code) {
+            problems.push(format!("rule settings describe {}, which is not a rule code", code));
+        }
+    }
+
+    for locale in i18n::LOCALES {
+        let catalog = i18n::catalog_codes(locale);
+        for code in codes::ALL.iter().filter(|c| !catalog.contains(c)) {
+            problems.push(format!("{}: no message template for {}", locale, code));
+        }
+        for code in catalog.iter().filter(|c| !codes::ALL.contains(c)) {
+            problems.push(format!("{}: template for unknown code {}", locale, code));
+        }
+
+        for code in codes::ALL.iter().filter(|c| catalog.contains(c)) {
+            let placeholders = |locale: &str| -> BTreeSet<String> {
+                let template = i18n::template(locale, code).unwrap_or("");
+                PLACEHOLDER_REGEX.captures_iter(template).map(|c| c[1].to_string()).collect()
+            };
+            let (english, localized) = (placeholders("en"), placeholders(locale));
+            if localized != english {
+                let names = |set: &BTreeSet<String>| set.iter().cloned().collect::<Vec<_>>().join(", ");
+                problems.push(format!(
+                    "{}: template for {} uses {{{}}}, English uses {{{}}}",
+                    locale,
+                    code,
+                    names(&localized),
+                    names(&english)
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+/// A deprecation registry on its own
+pub fn check_deprecations(registry: &[Deprecation]) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut seen = HashSet::new();
+    let stars = |path: &str| path.split('/').filter(|s| *s == "*").count();
+    for entry in registry {
+        let path = &entry.path;
+        if !seen.insert(path.as_str()) {
+            problems.push(format!("deprecations: {} is listed twice", path));
+        }
+        if !path.starts_with('/') || !entry.replacement.starts_with('/') {
+            problems.push(format!("deprecations: {} and its replacement must be JSON pointers", path));
+        }
+        if stars(path) != stars(&entry.replacement) {
+            problems.push(format!("deprecations: {} and {} differ in array items", path, entry.replacement));
+        }
+        if registry.iter().any(|other| other.path == entry.replacement) {
+            problems.push(format!("deprecations: {} is replaced by {}, which is deprecated too", path, entry.replacement));
+        }
+        if entry.source.trim().is_empty() {
+            problems.push(format!("deprecations: {} cites no specification change", path));
+        }
+        if !entry.rename && entry.migration_note.is_none() {
+            problems.push(format!("deprecations: {} is not a rename and has no migration note", path));
+        }
+        match (deprecations::parse_version(&entry.deprecated_in), deprecations::parse_version(&entry.removed_in)) {
+            (Some(deprecated), Some(removed)) => {
+                if deprecations::compare_versions(&deprecated, &removed).is_ge() {
+                    problems.push(format!(
+                        "deprecations: {} is removed in {}, not after its deprecation in {}",
+                        path, entry.removed_in, entry.deprecated_in
+                    ));
+                }
+            }
+            _ => problems.push(format!("deprecations: {} has an unreadable version", path)),
+        }
+    }
+    problems
+}
+
+/// The deprecation registry against the fields one schema declares: from
+/// the deprecation on the replacement must exist, from the removal on the
+/// old field must not
+fn check_schema_deprecations(registry: &[Deprecation], name: &str, schema: &Value, problems: &mut Vec<String>) {
+    let version = introspect::node_at(schema, "/rcip_version")
+        .and_then(|node| node.get("const"))
+        .and_then(|v| v.as_str())
+        .and_then(deprecations::parse_version);
+    let version = match version {
+        Some(version) => version,
+        None => return,
+    };
+    let reached = |point: &str| {
+        deprecations::parse_version(point).map(|point| deprecations::compare_versions(&version, &point).is_ge())
+    };
+    for entry in registry {
+        if reached(&entry.deprecated_in) == Some(true) && introspect::node_at(schema, &entry.replacement).is_none() {
+            problems.push(format!("{}: replacement {} for {} is not declared", name, entry.replacement, entry.path));
+        }
+        if reached(&entry.removed_in) == Some(true) && introspect::node_at(schema, &entry.path).is_some() {
+            problems.push(format!("{}: still declares {}, removed in {}", name, entry.path, entry.removed_in));
+        }
+    }
+}
+
+/// Run every check against a source tree holding `schemas/` and `examples/`
+pub fn run(root: &Path) -> Vec<String> {
+    let mut problems = check_codes();
+    problems.extend(check_vocabularies());
+    problems.extend(check_deprecations(deprecations::registry()));
+
+    let mut examples = Vec::new();
+    for path in files(&root.join("examples"), "rcip") {
+        match read_json(&path) {
+            Ok(recipe) => examples.push((path, recipe)),
+            Err(e) => problems.push(e),
+        }
+    }
+
+    let schemas = files(&root.join("schemas"), "json");
+    if schemas.is_empty() {
+        problems.push(format!("no schemas found in {}", root.join("schemas").display()));
+    }
+    for path in schemas {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let schema = match read_json(&path) {
+            Ok(schema) => schema,
+            Err(e) => {
+                problems.push(e);
+                continue;
+            }
+        };
+
+        problems.extend(schema_divergences(&schema).into_iter().map(|d| format!("{}: {}", name, d)));
+        check_schema_enums(&name, &schema, &mut problems);
+        check_schema_deprecations(deprecations::registry(), &name, &schema, &mut problems);
+
+        let compiled = match JSONSchema::compile(&schema) {
+            Ok(compiled) => compiled,
+            Err(e) => {
+                problems.push(format!("{}: does not compile: {}", name, e));
+                continue;
+            }
+        };
+        for (example, recipe) in &examples {
+            if let Err(errors) = compiled.validate(recipe) {
+                for error in errors {
+                    problems.push(format!("{}: {} fails at '{}': {}", name, example.display(), error.instance_path, error));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bundled_tree_passes() {
+        assert_eq!(run(Path::new("../..")), Vec::<String>::new());
+        assert!(check_vocabularies().is_empty());
+    }
+
+    #[test]
+    fn test_reports_drift_and_failing_examples() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("schemas")).unwrap();
+        fs::create_dir(dir.path().join("examples")).unwrap();
+
+        let mut schema = read_json(Path::new("../../schemas/rcip-v0.1.json")).unwrap();
+        let allergens = schema.pointer_mut("/$defs/ingredient/properties/allergens/items/enum").unwrap();
+        allergens.as_array_mut().unwrap().retain(|a| a != "sesame");
+        let hazards = schema.pointer_mut("/$defs/step/properties/hazards/items/enum").unwrap();
+        hazards.as_array_mut().unwrap().push(json!("steam"));
+        fs::write(dir.path().join("schemas/rcip-v9.json"), schema.to_string()).unwrap();
+        fs::write(dir.path().join("schemas/broken.json"), r#"{"type": 7}"#).unwrap();
+        fs::write(dir.path().join("examples/empty.rcip"), "{}").unwrap();
+
+        let problems = run(dir.path());
+        assert!(problems.contains(&"rcip-v9.json: allergens enum lacks sesame".to_string()));
+        assert!(problems.contains(&"rcip-v9.json: hazards enum allows steam, unknown to the rules".to_string()));
+        assert!(problems.iter().any(|p| p.starts_with("broken.json: does not compile")));
+        assert!(problems.iter().any(|p| p.starts_with("rcip-v9.json: ") && p.contains("empty.rcip fails at")));
+    }
+
+    #[test]
+    fn test_reports_inconsistent_deprecations() {
+        let entry = |path: &str, replacement: &str, deprecated_in: &str, removed_in: &str| Deprecation {
+            path: path.to_string(),
+            replacement: replacement.to_string(),
+            deprecated_in: deprecated_in.to_string(),
+            removed_in: removed_in.to_string(),
+            rename: true,
+            migration_note: None,
+            source: "SPECIFICATION.md, Field Versioning".to_string(),
+        };
+        let registry = [
+            entry("/meta/cook_time", "/meta/cooking_time", "0.2", "0.2"),
+            entry("/meta/cooking_time", "/meta/cook_time_minutes", "0.3", "1.x"),
+            entry("/steps/*/temp", "/meta/temperature_c", "0.1", "0.2"),
+            Deprecation { rename: false, ..entry("/meta/cook_time", "/meta/cook_time_minutes", "0.1", "0.2") },
+            Deprecation { source: String::new(), ..entry("/meta/yield", "/meta/servings/amount", "0.1", "0.2") },
+        ];
+        assert_eq!(
+            check_deprecations(&registry),
+            [
+                "deprecations: /meta/cook_time is replaced by /meta/cooking_time, which is deprecated too",
+                "deprecations: /meta/cook_time is removed in 0.2, not after its deprecation in 0.2",
+                "deprecations: /meta/cooking_time has an unreadable version",
+                "deprecations: /steps/*/temp and /meta/temperature_c differ in array items",
+                "deprecations: /meta/cook_time is listed twice",
+                "deprecations: /meta/cook_time is not a rename and has no migration note",
+                "deprecations: /meta/yield cites no specification change",
+            ]
+        );
+        assert!(check_deprecations(deprecations::registry()).is_empty());
+
+        let mut problems = Vec::new();
+        let mut schema = read_json(Path::new("../../schemas/rcip-v0.1.json")).unwrap();
+        schema.pointer_mut("/$defs/meta/properties").unwrap().as_object_mut().unwrap().remove("cook_time_minutes");
+        let registry = deprecations::test_registry();
+        check_schema_deprecations(registry, "rcip-v0.1.json", &schema, &mut problems);
+        schema["properties"]["rcip_version"]["const"] = json!("0.2");
+        schema.pointer_mut("/$defs/meta/properties").unwrap()["prep_time"] = json!({"type": "number"});
+        check_schema_deprecations(registry, "rcip-v0.2.json", &schema, &mut problems);
+        assert_eq!(
+            problems,
+            [
+                "rcip-v0.1.json: replacement /meta/cook_time_minutes for /meta/cook_time is not declared",
+                "rcip-v0.2.json: still declares /meta/prep_time, removed in 0.2",
+                "rcip-v0.2.json: replacement /meta/cook_time_minutes for /meta/cook_time is not declared",
+            ]
+        );
+    }
+}
@@ -0,0 +1,309 @@
+// Photo shot lists
+//
+// Content teams photograph a recipe once it validates. `shot_list` derives
+// the photos still needed: a hero shot, the final plating, every step that no
+// image points at through `step_ref` (or a `stage` naming the step), and one
+// shot per ingredient group, or a single ingredients shot when the recipe has
+// no groups, naming each ingredient with its declared product so the right
+// package is on set. Each shot gets a file name from a configurable pattern, so a
+// folder of photos can be compared with the list: shots without a file are
+// missing, files without a shot are orphaned. A step that is already pictured
+// keeps its shot, set aside in `pictured`, so its photo is matched to the step
+// by id rather than reported as orphaned, and `{index}` counts it too, so the
+// numbers of the other shots do not shift as photos come in. File names are
+// compared case-insensitively where the file system is, on Windows and macOS.
+
+use crate::import::slug;
+use crate::product;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+#[cfg(not(feature = "no-fs"))]
+use std::fs;
+#[cfg(not(feature = "no-fs"))]
+use std::path::Path;
+
+/// Naming convention for shot files
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShotOptions {
+    /// File name pattern; `{recipe}` is the recipe id, `{shot}` the shot key
+    /// (`hero`, `final`, `s-04`, `group-sauce`) and `{index}` its two-digit
+    /// position among all the recipe's shots, pictured steps included
+    pub pattern: String,
+    /// Compare file names ignoring case
+    pub case_insensitive: bool,
+}
+
+impl Default for ShotOptions {
+    fn default() -> Self {
+        ShotOptions {
+            pattern: "{recipe}-{shot}.jpg".to_string(),
+            case_insensitive: cfg!(any(windows, target_os = "macos")),
+        }
+    }
+}
+
+/// What a shot shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShotKind {
+    Hero,
+    Final,
+    Step,
+    Ingredients,
+}
+
+/// One photo to take
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Shot {
+    pub kind: ShotKind,
+    pub key: String,
+    /// What to photograph
+    pub subject: String,
+    pub file_name: String,
+}
+
+/// The photos a recipe needs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShotList {
+    pub recipe_id: String,
+    pub shots: Vec<Shot>,
+    /// Shots of steps an image already points at
+    pub pictured: Vec<Shot>,
+}
+
+impl fmt::Display for ShotList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Shot list for {}", self.recipe_id)?;
+        for shot in &self.shots {
+            writeln!(f, "[ ] {}  {}", shot.file_name, shot.subject)?;
+        }
+        for shot in &self.pictured {
+            writeln!(f, "[x] {}  {}", shot.file_name, shot.subject)?;
+        }
+        Ok(())
+    }
+}
+
+/// Shot files missing from a folder, and files no shot asks for
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AssetReport {
+    pub missing: Vec<String>,
+    pub orphaned: Vec<String>,
+}
+
+impl AssetReport {
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for AssetReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for name in &self.missing {
+            writeln!(f, "missing: {}", name)?;
+        }
+        for name in &self.orphaned {
+            writeln!(f, "orphaned: {}", name)?;
+        }
+        if self.missing.is_empty() && self.orphaned.is_empty() {
+            writeln!(f, "All shots present")?;
+        }
+        Ok(())
+    }
+}
+
+fn text<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(|v| v.as_str())
+}
+
+/// List the photos a recipe still needs
+pub fn shot_list(recipe: &Value, options: &ShotOptions) -> ShotList {
+    let recipe_id = text(recipe, "id").unwrap_or("recipe").to_string();
+    let name = recipe.pointer("/meta/name").and_then(|v| v.as_str()).unwrap_or("the dish");
+    let images: Vec<&Value> = recipe.get("images").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let pictured = |step_id: &str| {
+        images.iter().any(|image| text(image, "step_ref") == Some(step_id) || text(image, "stage") == Some(step_id))
+    };
+
+    let mut shots = vec![
+        (ShotKind::Hero, "hero".to_string(), format!("Hero shot of {}", name), false),
+        (ShotKind::Final, "final".to_string(), format!("{}, plated", name), false),
+    ];
+
+    for step in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten() {
+        let step_id = match text(step, "step_id") {
+            Some(step_id) => step_id,
+            None => continue,
+        };
+        let subject = text(step, "human_text").unwrap_or(step_id).to_string();
+        shots.push((ShotKind::Step, step_id.to_string(), subject, pictured(step_id)));
+    }
+
+    let ingredients: Vec<&Value> = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let mut groups: Vec<&str> = Vec::new();
+    for group in ingredients.iter().filter_map(|ing| text(ing, "group")) {
+        if !groups.contains(&group) {
+            groups.push(group);
+        }
+    }
+    let names = |group: Option<&str>| {
+        ingredients
+            .iter()
+            .filter(|ing| group.is_none() || text(ing, "group") == group)
+            .filter(|ing| text(ing, "name").is_some())
+            .map(|ing| product::display_name(ing))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if groups.is_empty() {
+        shots.push((ShotKind::Ingredients, "ingredients".to_string(), format!("Ingredients: {}", names(None)), false));
+    }
+    for group in groups {
+        shots.push((ShotKind::Ingredients, format!("group-{}", slug(group)), format!("{}: {}", group, names(Some(group))), false));
+    }
+
+    let (mut list, mut done) = (Vec::new(), Vec::new());
+    for (i, (kind, key, subject, pictured)) in shots.into_iter().enumerate() {
+        let file_name = options
+            .pattern
+            .replace("{recipe}", &recipe_id)
+            .replace("{shot}", &key)
+            .replace("{index}", &format!("{:02}", i + 1));
+        let shot = Shot { kind, key, subject, file_name };
+        if pictured { done.push(shot) } else { list.push(shot) }
+    }
+
+    ShotList { recipe_id, shots: list, pictured: done }
+}
+
+/// Compare a shot list with the names of the files at hand; a file of a
+/// pictured step is neither missing nor orphaned
+pub fn compare(list: &ShotList, files: &[String], options: &ShotOptions) -> AssetReport {
+    let fold = |name: &str| if options.case_insensitive { name.to_lowercase() } else { name.to_string() };
+    let present: BTreeSet<String> = files.iter().map(|f| fold(f)).collect();
+    let wanted: BTreeSet<String> = list.shots.iter().chain(&list.pictured).map(|s| fold(&s.file_name)).collect();
+
+    let mut orphaned: Vec<String> = files.iter().filter(|f| !wanted.contains(&fold(f))).cloned().collect();
+    orphaned.sort();
+    AssetReport {
+        missing: list.shots.iter().filter(|s| !present.contains(&fold(&s.file_name))).map(|s| s.file_name.clone()).collect(),
+        orphaned,
+    }
+}
+
+/// Compare a shot list with the files in a folder, ignoring hidden files
+#[cfg(not(feature = "no-fs"))]
+pub fn check_assets(list: &ShotList, dir: &Path, options: &ShotOptions) -> std::io::Result<AssetReport> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') {
+            files.push(name);
+        }
+    }
+    Ok(compare(list, &files, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "id": "rcip-pizza",
+            "meta": {"name": "Margherita"},
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "group": "Dough", "product": {"brand": "Caputo", "required": true}},
+                {"id": "ing-0002", "name": "water", "group": "Dough"},
+                {"id": "ing-0003", "name": "tomatoes", "group": "Tomato sauce"}
+            ],
+            "steps": [
+                {"step_id": "s-01", "human_text": "Knead the dough"},
+                {"step_id": "s-02", "human_text": "Stretch the base"},
+                {"step_id": "s-03", "human_text": "Bake"}
+            ],
+            "images": [{"id": "img-01", "step_ref": "s-02"}]
+        })
+    }
+
+    fn options(case_insensitive: bool) -> ShotOptions {
+        ShotOptions { case_insensitive, ..ShotOptions::default() }
+    }
+
+    #[test]
+    fn test_shot_list() {
+        let list = shot_list(&recipe(), &options(false));
+        let files: Vec<&str> = list.shots.iter().map(|s| s.file_name.as_str()).collect();
+        assert_eq!(files, vec![
+            "rcip-pizza-hero.jpg",
+            "rcip-pizza-final.jpg",
+            "rcip-pizza-s-01.jpg",
+            "rcip-pizza-s-03.jpg",
+            "rcip-pizza-group-dough.jpg",
+            "rcip-pizza-group-tomato-sauce.jpg",
+        ]);
+        assert_eq!(list.shots[4].subject, "Dough: flour, Caputo, water");
+        assert_eq!(list.shots[2].kind, ShotKind::Step);
+        assert_eq!(list.pictured.len(), 1);
+        assert_eq!(list.pictured[0].file_name, "rcip-pizza-s-02.jpg");
+
+        let studio = ShotOptions { pattern: "{index}_{shot}.png".to_string(), ..options(false) };
+        let list = shot_list(&recipe(), &studio);
+        assert_eq!(list.shots[3].file_name, "05_s-03.png");
+        assert_eq!(list.pictured[0].file_name, "04_s-02.png");
+    }
+
+    #[test]
+    fn test_compare_reports_missing_and_orphaned() {
+        let list = shot_list(&recipe(), &options(false));
+        let files: Vec<String> =
+            ["rcip-pizza-hero.jpg", "RCIP-PIZZA-FINAL.JPG", "rcip-pizza-s-01.jpg", "rcip-pizza-s-02.jpg", "outtake.jpg"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect();
+
+        let report = compare(&list, &files, &options(false));
+        assert_eq!(report.orphaned, vec!["RCIP-PIZZA-FINAL.JPG", "outtake.jpg"]);
+        assert_eq!(report.missing.len(), 4);
+
+        let report = compare(&list, &files, &options(true));
+        assert_eq!(report.orphaned, vec!["outtake.jpg"]);
+        assert_eq!(report.missing, vec![
+            "rcip-pizza-s-03.jpg",
+            "rcip-pizza-group-dough.jpg",
+            "rcip-pizza-group-tomato-sauce.jpg",
+        ]);
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn test_renumbering_keeps_steps_pictured() {
+        let mut recipe = recipe();
+        recipe["steps"][1]["step_id"] = json!("s-5");
+        recipe["images"][0]["step_ref"] = json!("s-5");
+        crate::id_sequence::renumber_ids(&mut recipe).unwrap();
+        let list = shot_list(&recipe, &options(false));
+        assert_eq!(list.pictured.iter().map(|s| s.key.as_str()).collect::<Vec<_>>(), vec!["s-02"]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "no-fs"))]
+    fn test_check_assets_reads_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = shot_list(&json!({"id": "r", "steps": []}), &options(false));
+        for shot in &list.shots {
+            fs::write(dir.path().join(&shot.file_name), b"").unwrap();
+        }
+        fs::write(dir.path().join(".DS_Store"), b"").unwrap();
+        let report = check_assets(&list, dir.path(), &options(false)).unwrap();
+        assert!(report.is_complete());
+        assert!(report.orphaned.is_empty());
+    }
+}
@@ -0,0 +1,394 @@
+// Dry-run simulation
+//
+// Steps run in document order on a shared clock. A step starts once every
+// step whose result it targets has finished, or after the previous step when
+// it targets no results. Consecutive steps sharing an `x-parallel-group` all
+// start together, and the step after the group waits for the whole group.
+// Durations come from the step params; a step without one takes no time.
+//
+// `run` replays that schedule and fails on what static rules cannot see: an
+// ingredient used up more than once (steps using part of an ingredient say
+// so in an `x-fractions` object, e.g. `{"ing-0001": 0.5}`), a result used
+// before the step producing it has finished, one portion of a divided result
+// (`s-05:result[2]`) used by two steps, and a vessel or device used by two
+// steps at the same time.
+
+use crate::intermediates::producer;
+use crate::portions;
+use crate::scaling::step_equipment;
+use crate::{codes, i18n, step_duration_minutes, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+
+const EPSILON: f64 = 1e-6;
+
+/// A step placed on the recipe timeline, in minutes from the start
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledStep {
+    pub step_id: String,
+    pub start_minutes: f64,
+    pub end_minutes: f64,
+}
+
+/// What happened at a point of the simulation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum EventKind {
+    StepStarted,
+    IngredientConsumed { ingredient_id: String, fraction: f64 },
+    ResultUsed {
+        result_of: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        portion: Option<usize>,
+    },
+    EquipmentAcquired { equipment: String },
+    EquipmentReleased { equipment: String },
+    StepFinished,
+}
+
+/// A timestamped simulation event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationEvent {
+    pub at_minutes: f64,
+    pub step_id: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// Outcome of a successful dry run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimulationTrace {
+    pub steps: Vec<ScheduledStep>,
+    pub events: Vec<SimulationEvent>,
+    pub total_minutes: f64,
+}
+
+/// A logical impossibility found by the simulation
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulationError {
+    IngredientOverused { ingredient_id: String, step_id: String, total: f64 },
+    ResultNotReady { step_id: String, result_of: String },
+    PortionOverused { step_id: String, result_of: String, index: usize, other_step_id: String },
+    EquipmentConflict { equipment: String, step_id: String, other_step_id: String, at_minutes: f64 },
+}
+
+impl SimulationError {
+    /// Rule code and message parameters
+    fn issue(&self) -> (&'static str, Vec<(&'static str, String)>) {
+        match self {
+            SimulationError::IngredientOverused { ingredient_id, step_id, total } => (codes::INGREDIENT_OVERUSED, vec![
+                ("step", step_id.clone()),
+                ("ingredient", ingredient_id.clone()),
+                ("percent", format!("{:.0}", total * 100.0)),
+            ]),
+            SimulationError::ResultNotReady { step_id, result_of } => (codes::RESULT_NOT_READY, vec![
+                ("step", step_id.clone()),
+                ("result_of", result_of.clone()),
+            ]),
+            SimulationError::PortionOverused { step_id, result_of, index, other_step_id } => (codes::PORTION_OVERUSED, vec![
+                ("step", step_id.clone()),
+                ("index", index.to_string()),
+                ("result_of", result_of.clone()),
+                ("other", other_step_id.clone()),
+            ]),
+            SimulationError::EquipmentConflict { equipment, step_id, other_step_id, at_minutes } => (codes::EQUIPMENT_CONFLICT, vec![
+                ("step", step_id.clone()),
+                ("equipment", equipment.clone()),
+                ("other_step", other_step_id.clone()),
+                ("minute", at_minutes.to_string()),
+            ]),
+        }
+    }
+}
+
+impl fmt::Display for SimulationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (code, params) = self.issue();
+        write!(f, "{}", i18n::render("en", code, &params))
+    }
+}
+
+impl std::error::Error for SimulationError {}
+
+impl fmt::Display for SimulationTrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            let what = match &event.kind {
+                EventKind::StepStarted => "start".to_string(),
+                EventKind::IngredientConsumed { ingredient_id, fraction } if *fraction < 1.0 => {
+                    format!("use {:.0}% of {}", fraction * 100.0, ingredient_id)
+                }
+                EventKind::IngredientConsumed { ingredient_id, .. } => format!("use {}", ingredient_id),
+                EventKind::ResultUsed { result_of, portion: Some(index) } => format!("use {}:result[{}]", result_of, index),
+                EventKind::ResultUsed { result_of, .. } => format!("use {}:result", result_of),
+                EventKind::EquipmentAcquired { equipment } => format!("take {}", equipment),
+                EventKind::EquipmentReleased { equipment } => format!("free {}", equipment),
+                EventKind::StepFinished => "finish".to_string(),
+            };
+            writeln!(f, "{:>8.1} min  {:<6} {}", event.at_minutes, event.step_id, what)?;
+        }
+        writeln!(f, "Total: {:.1} min", self.total_minutes)
+    }
+}
+
+fn step_id(step: &Value) -> &str {
+    step.get("step_id").and_then(|v| v.as_str()).unwrap_or("?")
+}
+
+fn parallel_group(step: &Value) -> Option<&str> {
+    step.get("x-parallel-group").and_then(|v| v.as_str())
+}
+
+fn targets(step: &Value) -> impl Iterator<Item = &str> {
+    step.get("target").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|t| t.as_str())
+}
+
+/// Lay the steps of a recipe onto a timeline
+pub fn schedule(recipe: &Value) -> Vec<ScheduledStep> {
+    let mut finished: HashMap<&str, f64> = HashMap::new();
+    let mut previous_end = 0.0;
+    let mut group: Option<(&str, f64)> = None;
+    let mut scheduled = Vec::new();
+
+    for step in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten() {
+        let joins = match (group, parallel_group(step)) {
+            (Some((current, _)), Some(name)) => current == name,
+            _ => false,
+        };
+
+        let start = match group {
+            Some((_, group_start)) if joins => group_start,
+            _ => {
+                let dependencies: Vec<f64> = targets(step)
+                    .filter_map(|t| producer(recipe, t))
+                    .filter_map(|id| finished.get(id).copied())
+                    .collect();
+                if dependencies.is_empty() {
+                    previous_end
+                } else {
+                    dependencies.into_iter().fold(0.0, f64::max)
+                }
+            }
+        };
+        let end = start + step_duration_minutes(step).unwrap_or(0.0);
+
+        if joins {
+            previous_end = f64::max(previous_end, end);
+        } else {
+            previous_end = end;
+            group = parallel_group(step).map(|name| (name, start));
+        }
+
+        finished.insert(step_id(step), end);
+        scheduled.push(ScheduledStep { step_id: step_id(step).to_string(), start_minutes: start, end_minutes: end });
+    }
+
+    scheduled
+}
+
+/// Dry-run a recipe, returning the event trace or the first impossibility
+pub fn run(recipe: &Value) -> Result<SimulationTrace, SimulationError> {
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let slots = schedule(recipe);
+
+    let ingredient_ids: Vec<&str> = recipe.get("ingredients")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|ing| ing.get("id").and_then(|v| v.as_str()))
+        .collect();
+    let slot_by_id: HashMap<&str, &ScheduledStep> = slots.iter().map(|s| (s.step_id.as_str(), s)).collect();
+
+    let mut consumed: HashMap<&str, f64> = HashMap::new();
+    let mut portions_taken: HashMap<(&str, usize), &str> = HashMap::new();
+    let mut booked: Vec<(&str, &ScheduledStep)> = Vec::new();
+    let mut events = Vec::new();
+
+    for (position, (step, slot)) in steps.iter().zip(&slots).enumerate() {
+        let id = slot.step_id.as_str();
+        let event = |at_minutes: f64, kind: EventKind| SimulationEvent { at_minutes, step_id: id.to_string(), kind };
+        events.push(event(slot.start_minutes, EventKind::StepStarted));
+
+        for target in targets(step) {
+            if let Some(result_of) = producer(recipe, target) {
+                // A result must come from an earlier step that has finished by now
+                let producer = slot_by_id.get(result_of);
+                let produced_earlier = slots[..position].iter().any(|s| s.step_id == result_of);
+                let late = producer.map(|p| p.end_minutes > slot.start_minutes + EPSILON).unwrap_or(false);
+                if producer.is_some() && (!produced_earlier || late) {
+                    return Err(SimulationError::ResultNotReady {
+                        step_id: id.to_string(),
+                        result_of: result_of.to_string(),
+                    });
+                }
+                if producer.is_none() {
+                    continue;
+                }
+                let portion = portions::split(target).map(|(_, index)| index);
+                if let Some(index) = portion {
+                    if let Some(other) = portions_taken.insert((result_of, index), id) {
+                        return Err(SimulationError::PortionOverused {
+                            step_id: id.to_string(),
+                            result_of: result_of.to_string(),
+                            index,
+                            other_step_id: other.to_string(),
+                        });
+                    }
+                }
+                events.push(event(slot.start_minutes, EventKind::ResultUsed { result_of: result_of.to_string(), portion }));
+            } else if ingredient_ids.contains(&target) {
+                let fraction = step.get("x-fractions")
+                    .and_then(|f| f.get(target))
+                    .and_then(|v| v.as_f64())
+                    .unwrap_or(1.0);
+                let total = consumed.entry(target).or_insert(0.0);
+                *total += fraction;
+                if *total > 1.0 + EPSILON {
+                    return Err(SimulationError::IngredientOverused {
+                        ingredient_id: target.to_string(),
+                        step_id: id.to_string(),
+                        total: *total,
+                    });
+                }
+                events.push(event(slot.start_minutes, EventKind::IngredientConsumed {
+                    ingredient_id: target.to_string(),
+                    fraction,
+                }));
+            }
+        }
+
+        if let Some(equipment) = step_equipment(step) {
+            let clash = booked.iter().find(|(other, other_slot)| {
+                *other == equipment
+                    && other_slot.start_minutes < slot.end_minutes - EPSILON
+                    && slot.start_minutes < other_slot.end_minutes - EPSILON
+            });
+            if let Some((_, other_slot)) = clash {
+                return Err(SimulationError::EquipmentConflict {
+                    equipment: equipment.to_string(),
+                    step_id: id.to_string(),
+                    other_step_id: other_slot.step_id.clone(),
+                    at_minutes: slot.start_minutes.max(other_slot.start_minutes),
+                });
+            }
+            booked.push((equipment, slot));
+            events.push(event(slot.start_minutes, EventKind::EquipmentAcquired { equipment: equipment.to_string() }));
+            events.push(event(slot.end_minutes, EventKind::EquipmentReleased { equipment: equipment.to_string() }));
+        }
+
+        events.push(event(slot.end_minutes, EventKind::StepFinished));
+    }
+
+    // Stable, so events at the same minute keep their step order
+    events.sort_by(|a, b| a.at_minutes.total_cmp(&b.at_minutes));
+    let total_minutes = slots.iter().map(|s| s.end_minutes).fold(0.0, f64::max);
+
+    Ok(SimulationTrace { steps: slots, events, total_minutes })
+}
+
+/// Report the first impossibility found by a dry run
+pub fn check(recipe: &Value, result: &mut ValidationResult) {
+    if let Err(error) = run(recipe) {
+        let (code, params) = error.issue();
+        let params: Vec<(&'static str, &dyn fmt::Display)> = params.iter().map(|(name, value)| (*name, value as &dyn fmt::Display)).collect();
+        result.error(code, &params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe(steps: Value) -> Value {
+        json!({
+            "ingredients": [{"id": "ing-0001"}, {"id": "ing-0002"}],
+            "steps": steps
+        })
+    }
+
+    #[test]
+    fn test_parallel_groups_share_a_start() {
+        let recipe = recipe(json!([
+            {"step_id": "s-01", "action": "measure", "target": ["ing-0001"], "x-parallel-group": "prep", "params": {"time_minutes": 2}},
+            {"step_id": "s-02", "action": "measure", "target": ["ing-0002"], "x-parallel-group": "prep", "params": {"time_minutes": 5}},
+            {"step_id": "s-03", "action": "mix", "params": {"time_minutes": 3}}
+        ]));
+
+        let slots = schedule(&recipe);
+        assert_eq!((slots[1].start_minutes, slots[1].end_minutes), (0.0, 5.0));
+        assert_eq!((slots[2].start_minutes, slots[2].end_minutes), (5.0, 8.0));
+
+        let trace = run(&recipe).unwrap();
+        assert_eq!(trace.total_minutes, 8.0);
+        assert_eq!(trace.events.first().map(|e| &e.kind), Some(&EventKind::StepStarted));
+        assert!(trace.to_string().contains("use ing-0002"));
+    }
+
+    #[test]
+    fn test_fractions_and_overuse() {
+        let split = recipe(json!([
+            {"step_id": "s-01", "action": "add", "target": ["ing-0001"], "x-fractions": {"ing-0001": 0.5}},
+            {"step_id": "s-02", "action": "add", "target": ["ing-0001"], "x-fractions": {"ing-0001": 0.5}}
+        ]));
+        assert!(run(&split).is_ok());
+
+        let twice = recipe(json!([
+            {"step_id": "s-01", "action": "add", "target": ["ing-0001"]},
+            {"step_id": "s-02", "action": "add", "target": ["ing-0001"], "x-fractions": {"ing-0001": 0.25}}
+        ]));
+        match run(&twice) {
+            Err(SimulationError::IngredientOverused { ingredient_id, step_id, .. }) => {
+                assert_eq!((ingredient_id.as_str(), step_id.as_str()), ("ing-0001", "s-02"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_result_not_ready() {
+        let forward = recipe(json!([
+            {"step_id": "s-01", "action": "mix", "target": ["s-02:result"]},
+            {"step_id": "s-02", "action": "mix", "target": ["ing-0001"]}
+        ]));
+        assert!(matches!(run(&forward), Err(SimulationError::ResultNotReady { .. })));
+
+        let same_group = recipe(json!([
+            {"step_id": "s-01", "action": "boil", "target": ["ing-0001"], "x-parallel-group": "g", "params": {"time_minutes": 10}},
+            {"step_id": "s-02", "action": "mix", "target": ["s-01:result"], "x-parallel-group": "g"}
+        ]));
+        assert_eq!(
+            run(&same_group),
+            Err(SimulationError::ResultNotReady { step_id: "s-02".to_string(), result_of: "s-01".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_portions_are_used_once() {
+        let mut steps = json!([
+            {"step_id": "s-01", "action": "divide", "target": ["ing-0001"], "params": {"portions": 2}},
+            {"step_id": "s-02", "action": "shape", "target": ["s-01:result[1]"]},
+            {"step_id": "s-03", "action": "shape", "target": ["s-01:result[2]"]}
+        ]);
+        let trace = run(&recipe(steps.clone())).unwrap();
+        assert!(trace.to_string().contains("use s-01:result[2]"));
+
+        steps[2]["target"] = json!(["s-01:result[1]"]);
+        let err = run(&recipe(steps)).unwrap_err();
+        assert_eq!(err.to_string(), "Step s-03: Portion 1 of the result of s-01 was already used by s-02");
+    }
+
+    #[test]
+    fn test_equipment_double_booking() {
+        let recipe = recipe(json!([
+            {"step_id": "s-01", "action": "boil", "target": ["ing-0001"], "params": {"time_minutes": 10, "vessel": "saucepan"}},
+            {"step_id": "s-02", "action": "simmer", "target": ["ing-0002"], "x-parallel-group": "g", "params": {"time_minutes": 20, "vessel": "saucepan"}},
+            {"step_id": "s-03", "action": "fry", "target": ["s-01:result"], "x-parallel-group": "g", "params": {"time_minutes": 5, "vessel": "saucepan"}}
+        ]));
+
+        let err = run(&recipe).unwrap_err();
+        assert_eq!(err.to_string(), "Step s-03: saucepan is still in use by s-02 at minute 10");
+    }
+}
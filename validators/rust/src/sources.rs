@@ -0,0 +1,184 @@
+// Recipe sources
+//
+// Directory validation reads recipes from local disk, but collections also
+// live in release zip bundles and S3 buckets. A `RecipeSource` lists the
+// recipes it holds and reads one at a time; `RCIPValidator::validate_source`
+// validates whatever a source lists, the way `validate_directory` does for
+// a directory. Sources answer for each entry on its own: a recipe that
+// cannot be read, a listing page that fails, or an archive entry with an
+// unsafe name is one failed outcome, and the rest of the run goes on.
+//
+// `DirectorySource` reads `.rcip` and `.json` files in a directory.
+// `ZipSource` (feature `zip`) reads them from a zip archive and refuses entry
+// names that would escape the archive when extracted. `S3Source` (feature
+// `s3`) lists a bucket prefix and reads objects with credentials from the
+// environment.
+
+use crate::RCIPError;
+#[cfg(not(feature = "no-fs"))]
+use std::fs;
+#[cfg(not(feature = "no-fs"))]
+use std::path::PathBuf;
+
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(all(feature = "zip", not(feature = "no-fs")))]
+mod zip;
+
+#[cfg(feature = "s3")]
+pub use s3::{S3Config, S3Source};
+#[cfg(all(feature = "zip", not(feature = "no-fs")))]
+pub use self::zip::ZipSource;
+
+/// A recipe a source holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceEntry {
+    /// Path of the recipe within the source
+    pub name: String,
+    /// Size in bytes, when the listing says
+    pub size: Option<u64>,
+}
+
+/// One outcome of listing a source
+#[derive(Debug)]
+pub enum Listed {
+    Entry(SourceEntry),
+    /// An entry that could not be listed, or with an empty `name`, a
+    /// listing of the source that failed
+    Failed { name: String, error: RCIPError },
+}
+
+impl Listed {
+    pub fn name(&self) -> &str {
+        match self {
+            Listed::Entry(entry) => &entry.name,
+            Listed::Failed { name, .. } => name,
+        }
+    }
+}
+
+/// Somewhere recipes can be listed and read from
+pub trait RecipeSource {
+    /// Where the recipes are, as given by the user
+    fn location(&self) -> String;
+
+    /// Recipes in the source, sorted by name
+    fn list(&self) -> Vec<Listed>;
+
+    /// The bytes of one listed recipe
+    fn read(&self, entry: &SourceEntry) -> Result<Vec<u8>, RCIPError>;
+
+    /// How results name an entry, or the source itself for an empty name
+    fn display_name(&self, name: &str) -> String {
+        match name {
+            "" => self.location(),
+            name => format!("{}/{}", self.location().trim_end_matches('/'), name),
+        }
+    }
+}
+
+/// Whether a name has a recipe file extension
+pub fn is_recipe_name(name: &str) -> bool {
+    name.ends_with(".rcip") || name.ends_with(".json")
+}
+
+/// Recipe files directly inside a local directory
+#[cfg(not(feature = "no-fs"))]
+pub struct DirectorySource {
+    dir: PathBuf,
+}
+
+#[cfg(not(feature = "no-fs"))]
+impl DirectorySource {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DirectorySource { dir: dir.into() }
+    }
+}
+
+#[cfg(not(feature = "no-fs"))]
+impl RecipeSource for DirectorySource {
+    fn location(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    fn list(&self) -> Vec<Listed> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => return vec![Listed::Failed { name: String::new(), error: e.into() }],
+        };
+        let mut listed = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    listed.push(Listed::Failed { name: String::new(), error: e.into() });
+                    continue;
+                }
+            };
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_recipe_name(&name) && entry.path().is_file() {
+                let size = entry.metadata().ok().map(|m| m.len());
+                listed.push(Listed::Entry(SourceEntry { name, size }));
+            }
+        }
+        sort(&mut listed);
+        listed
+    }
+
+    fn read(&self, entry: &SourceEntry) -> Result<Vec<u8>, RCIPError> {
+        Ok(fs::read(self.dir.join(&entry.name))?)
+    }
+}
+
+/// Sort listing outcomes by name
+#[cfg(any(not(feature = "no-fs"), feature = "s3"))]
+pub(crate) fn sort(listed: &mut [Listed]) {
+    listed.sort_by(|a, b| a.name().cmp(b.name()));
+}
+
+#[cfg(all(test, not(feature = "no-fs")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_lists_recipes_and_reports_failures() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.rcip"), "{}").unwrap();
+        fs::write(dir.path().join("a.json"), "[]").unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::create_dir(dir.path().join("nested.rcip")).unwrap();
+
+        let source = DirectorySource::new(dir.path());
+        let names: Vec<String> = source
+            .list()
+            .into_iter()
+            .map(|listed| match listed {
+                Listed::Entry(entry) => entry.name,
+                Listed::Failed { name, .. } => panic!("{} failed", name),
+            })
+            .collect();
+        assert_eq!(names, ["a.json", "b.rcip"]);
+        let entry = SourceEntry { name: "b.rcip".to_string(), size: None };
+        assert_eq!(source.read(&entry).unwrap(), b"{}");
+
+        let missing = DirectorySource::new(dir.path().join("missing"));
+        assert!(matches!(&missing.list()[..], [Listed::Failed { .. }]));
+    }
+
+    #[test]
+    fn test_unreadable_entries_become_results() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("broken.rcip"), "{").unwrap();
+
+        let mut validator = crate::RCIPValidator::new("0.1");
+        let results = validator.validate_source(&DirectorySource::new(dir.path()));
+        assert_eq!(results.len(), 1);
+        assert!(results[0].0.ends_with("/broken.rcip"));
+        assert_eq!(results[0].1.issues[0].code, crate::codes::READ_FAILED);
+
+        let missing = dir.path().join("missing");
+        let results = validator.validate_source(&DirectorySource::new(&missing));
+        assert_eq!(results[0].0, missing.display().to_string());
+        assert!(!results[0].1.valid);
+    }
+}
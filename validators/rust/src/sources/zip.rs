@@ -0,0 +1,240 @@
+// Zip archives
+//
+// The archive is read with the `zip` crate, which handles the central
+// directory, stored and deflated entries and zip64. Encrypted entries and
+// other compression methods fail for that entry only. An entry is inflated a
+// chunk at a time and never past `MAX_ENTRY_BYTES`, whatever size its header
+// claims, so a crafted archive cannot make the validator allocate more than
+// that; the CRC is checked as the entry is read, so a corrupt entry is an
+// error, not a surprise.
+//
+// Entry names come from whoever built the archive. A name that is absolute,
+// has a drive letter, climbs with `..`, uses backslashes or contains NUL
+// would escape an extraction directory, so it is listed as a failure and
+// never read.
+
+use super::{is_recipe_name, sort, Listed, RecipeSource, SourceEntry};
+use crate::RCIPError;
+use ::zip::result::ZipError;
+use ::zip::ZipArchive;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// The largest entry read; recipes are JSON text and far smaller
+pub const MAX_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+
+fn error(message: impl Into<String>) -> RCIPError {
+    RCIPError::ValidationError(message.into())
+}
+
+fn zip_error(e: ZipError) -> RCIPError {
+    match e {
+        ZipError::Io(e) => RCIPError::IoError(e),
+        ZipError::InvalidArchive(reason) => error(format!("not a valid zip archive: {}", reason)),
+        ZipError::UnsupportedArchive(reason) => error(format!("unsupported entry: {}", reason)),
+        ZipError::FileNotFound => error("no such entry"),
+    }
+}
+
+/// Why an archive entry name is unsafe to extract, if it is
+pub fn unsafe_name(name: &str) -> Option<&'static str> {
+    if name.contains('\0') {
+        Some("contains NUL")
+    } else if name.contains('\\') {
+        Some("uses backslashes")
+    } else if name.starts_with('/') {
+        Some("is absolute")
+    } else if name.as_bytes().get(1) == Some(&b':') {
+        Some("has a drive letter")
+    } else if name.split('/').any(|part| part == "..") {
+        Some("climbs out of the archive")
+    } else {
+        None
+    }
+}
+
+/// Recipe files inside a zip archive, at any depth
+pub struct ZipSource {
+    path: PathBuf,
+    archive: OnceLock<Result<Mutex<ZipArchive<File>>, String>>,
+}
+
+impl ZipSource {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        ZipSource { path: path.as_ref().to_path_buf(), archive: OnceLock::new() }
+    }
+
+    fn archive(&self) -> Result<&Mutex<ZipArchive<File>>, RCIPError> {
+        let archive = self.archive.get_or_init(|| {
+            let file = File::open(&self.path).map_err(|e| e.to_string())?;
+            ZipArchive::new(file).map(Mutex::new).map_err(|e| zip_error(e).to_string())
+        });
+        archive.as_ref().map_err(|e| error(format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+impl RecipeSource for ZipSource {
+    fn location(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn list(&self) -> Vec<Listed> {
+        let mut archive = match self.archive() {
+            Ok(archive) => archive.lock().unwrap(),
+            Err(error) => return vec![Listed::Failed { name: String::new(), error }],
+        };
+
+        let mut listed = Vec::new();
+        for index in 0..archive.len() {
+            let (name, size) = match archive.by_index_raw(index) {
+                Ok(file) => (String::from_utf8_lossy(file.name_raw()).to_string(), file.size()),
+                Err(e) => {
+                    listed.push(Listed::Failed { name: format!("#{}", index), error: zip_error(e) });
+                    continue;
+                }
+            };
+            if name.ends_with('/') || !is_recipe_name(&name) {
+                continue;
+            }
+            match unsafe_name(&name) {
+                Some(reason) => {
+                    let error = error(format!("unsafe entry name: {}", reason));
+                    listed.push(Listed::Failed { name, error });
+                }
+                None => listed.push(Listed::Entry(SourceEntry { name, size: Some(size) })),
+            }
+        }
+        sort(&mut listed);
+        listed
+    }
+
+    fn read(&self, entry: &SourceEntry) -> Result<Vec<u8>, RCIPError> {
+        if let Some(reason) = unsafe_name(&entry.name) {
+            return Err(error(format!("unsafe entry name: {}", reason)));
+        }
+        let mut archive = self.archive()?.lock().unwrap();
+        let file = archive.by_name(&entry.name).map_err(|e| match e {
+            ZipError::FileNotFound => error(format!("no entry {}", entry.name)),
+            e => zip_error(e),
+        })?;
+        if file.size() > MAX_ENTRY_BYTES {
+            return Err(error(format!("entry is {} bytes, more than the {} read", file.size(), MAX_ENTRY_BYTES)));
+        }
+
+        // The header's size is only a hint: reading stops one byte past the
+        // limit whatever it says
+        let mut unpacked = Vec::new();
+        file.take(MAX_ENTRY_BYTES + 1).read_to_end(&mut unpacked).map_err(|e| error(format!("entry is corrupt: {}", e)))?;
+        if unpacked.len() as u64 > MAX_ENTRY_BYTES {
+            return Err(error(format!("entry unpacks to more than {} bytes", MAX_ENTRY_BYTES)));
+        }
+        Ok(unpacked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::zip::write::FileOptions;
+    use ::zip::{CompressionMethod, ZipWriter};
+    use std::fs;
+    use std::io::{Cursor, Write};
+
+    /// A zip archive of (name, data, deflate) entries
+    fn archive(entries: &[(&str, &[u8], bool)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data, deflate) in entries {
+            let method = if *deflate { CompressionMethod::Deflated } else { CompressionMethod::Stored };
+            writer.start_file(*name, FileOptions::default().compression_method(method)).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn names(listed: &[Listed]) -> Vec<String> {
+        listed
+            .iter()
+            .map(|l| match l {
+                Listed::Entry(entry) => entry.name.clone(),
+                Listed::Failed { name, error } => format!("{} ({})", name, error),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reads_stored_and_deflated_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bundle.zip");
+        let recipe = br#"{"meta": {"name": "Soup"}, "ingredients": [], "steps": []}"#;
+        fs::write(&path, archive(&[
+            ("recipes/", b"", false),
+            ("recipes/soup.rcip", recipe, true),
+            ("recipes/bread.json", b"{}", false),
+            ("README.md", b"read me", false),
+        ]))
+        .unwrap();
+
+        let source = ZipSource::new(&path);
+        let listed = source.list();
+        assert_eq!(names(&listed), ["recipes/bread.json", "recipes/soup.rcip"]);
+        let entry = SourceEntry { name: "recipes/soup.rcip".to_string(), size: None };
+        assert_eq!(source.read(&entry).unwrap(), recipe);
+        assert_eq!(ZipSource::new(&path).read(&entry).unwrap(), recipe);
+    }
+
+    #[test]
+    fn test_hostile_names_and_corrupt_entries_fail_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hostile.zip");
+        let mut bytes = archive(&[
+            ("../../etc/evil.rcip", b"{}", false),
+            ("/abs.rcip", b"{}", false),
+            ("C:/win.rcip", b"{}", false),
+            ("ok/../../up.json", b"{}", false),
+            ("bad.rcip", b"{\"x\": 1}", false),
+            ("good.rcip", b"{}", false),
+        ]);
+        // Corrupt the data of bad.rcip so its CRC no longer matches
+        let at = bytes.windows(8).position(|w| w == b"{\"x\": 1}").unwrap();
+        bytes[at + 6] = b'2';
+        fs::write(&path, bytes).unwrap();
+
+        let source = ZipSource::new(&path);
+        let listed = source.list();
+        assert_eq!(
+            names(&listed),
+            [
+                "../../etc/evil.rcip (Validation error: unsafe entry name: climbs out of the archive)",
+                "/abs.rcip (Validation error: unsafe entry name: is absolute)",
+                "C:/win.rcip (Validation error: unsafe entry name: has a drive letter)",
+                "bad.rcip",
+                "good.rcip",
+                "ok/../../up.json (Validation error: unsafe entry name: climbs out of the archive)",
+            ]
+        );
+        let read = |name: &str| source.read(&SourceEntry { name: name.to_string(), size: None });
+        assert!(read("bad.rcip").unwrap_err().to_string().starts_with("Validation error: entry is corrupt"));
+        assert_eq!(read("good.rcip").unwrap(), b"{}");
+        assert!(read("../../etc/evil.rcip").is_err());
+
+        fs::write(&path, b"not a zip").unwrap();
+        assert_eq!(names(&ZipSource::new(&path).list()).len(), 1);
+    }
+
+    #[test]
+    fn test_claimed_size_does_not_drive_allocation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge.zip");
+        let mut bytes = archive(&[("huge.rcip", b"{}", false)]);
+        // Claim 4 GiB in the central directory entry's uncompressed size
+        let directory = bytes.windows(4).rposition(|w| w == [0x50, 0x4b, 0x01, 0x02]).unwrap();
+        bytes[directory + 24..directory + 28].copy_from_slice(&[0xfe, 0xff, 0xff, 0xff]);
+        fs::write(&path, bytes).unwrap();
+
+        let source = ZipSource::new(&path);
+        let error = source.read(&SourceEntry { name: "huge.rcip".to_string(), size: None }).unwrap_err();
+        assert!(error.to_string().contains("more than the 67108864 read"), "{}", error);
+    }
+}
@@ -0,0 +1,272 @@
+// Opt-in local usage statistics for batch runs
+//
+// Nothing here touches the network: records are appended to a JSON-lines
+// file chosen by the user and only ever read back by `summarize`. Runs may
+// share a stats file, so an append holds an exclusive lock on a `.lock` file
+// beside it from reading the old content to renaming the new one into place;
+// without it two runs read the same lines and the later rename drops the
+// other's record.
+
+use crate::{RCIPError, ValidationStats};
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// One line of a stats file, describing a single validation run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsRecord {
+    pub timestamp: String,
+    pub target: String,
+    pub files: u32,
+    pub passed: u32,
+    pub failed: u32,
+    pub errors: u32,
+    pub warnings: u32,
+    #[serde(default)]
+    pub rule_counts: BTreeMap<String, u32>,
+    pub schema_version: String,
+    pub cli_version: String,
+}
+
+impl StatsRecord {
+    /// Build a record from the validator statistics of a finished run
+    pub fn new(target: &str, schema_version: &str, stats: &ValidationStats) -> Self {
+        StatsRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            target: target.to_string(),
+            files: stats.validated,
+            passed: stats.passed,
+            failed: stats.failed,
+            errors: stats.errors,
+            warnings: stats.warnings,
+            rule_counts: stats.rule_counts.clone(),
+            schema_version: schema_version.to_string(),
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// Append a record to a stats file.
+///
+/// The new content is written to a temporary file next to the target and
+/// renamed over it, so readers never observe a half-written line. Concurrent
+/// appends wait for each other.
+pub fn append_record(path: &Path, record: &StatsRecord) -> Result<(), RCIPError> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("stats");
+    // The target itself cannot be locked: the rename replaces it
+    let lock = fs::OpenOptions::new().create(true).truncate(false).write(true).open(path.with_file_name(format!(".{}.lock", file_name)))?;
+    lock.lock()?;
+
+    let mut content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&serde_json::to_string(record)?);
+    content.push('\n');
+
+    let tmp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    {
+        let mut tmp = fs::File::create(&tmp_path)?;
+        tmp.write_all(content.as_bytes())?;
+        tmp.sync_all()?;
+    }
+
+    if let Err(e) = fs::rename(&tmp_path, path) {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Aggregated numbers for one ISO week
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeeklyTrend {
+    pub week: String,
+    pub runs: u32,
+    pub validated: u32,
+    pub passed: u32,
+    pub failed: u32,
+}
+
+impl WeeklyTrend {
+    /// Percentage of validated files that passed
+    pub fn pass_rate(&self) -> f64 {
+        if self.validated == 0 {
+            0.0
+        } else {
+            (self.passed as f64 / self.validated as f64) * 100.0
+        }
+    }
+}
+
+/// Trend summary of a stats file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsSummary {
+    pub runs: u32,
+    pub skipped_lines: u32,
+    pub weeks: Vec<WeeklyTrend>,
+    pub top_rules: Vec<(String, u32)>,
+}
+
+/// Summarize a stats file into a weekly trend table
+pub fn summarize(path: &Path) -> Result<StatsSummary, RCIPError> {
+    let content = fs::read_to_string(path)?;
+    Ok(summarize_str(&content))
+}
+
+/// Summarize stats-file content. Malformed lines are counted and skipped.
+pub fn summarize_str(content: &str) -> StatsSummary {
+    let mut summary = StatsSummary::default();
+    let mut weeks: BTreeMap<String, WeeklyTrend> = BTreeMap::new();
+    let mut rules: HashMap<String, u32> = HashMap::new();
+
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let record: StatsRecord = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(_) => {
+                summary.skipped_lines += 1;
+                continue;
+            }
+        };
+
+        let week = match DateTime::parse_from_rfc3339(&record.timestamp) {
+            Ok(ts) => {
+                let iso = ts.with_timezone(&Utc).iso_week();
+                format!("{}-W{:02}", iso.year(), iso.week())
+            }
+            Err(_) => {
+                summary.skipped_lines += 1;
+                continue;
+            }
+        };
+
+        summary.runs += 1;
+        let trend = weeks.entry(week.clone()).or_insert_with(|| WeeklyTrend {
+            week,
+            ..WeeklyTrend::default()
+        });
+        trend.runs += 1;
+        trend.validated += record.files;
+        trend.passed += record.passed;
+        trend.failed += record.failed;
+
+        for (rule, count) in record.rule_counts {
+            *rules.entry(rule).or_insert(0) += count;
+        }
+    }
+
+    let mut top_rules: Vec<(String, u32)> = rules.into_iter().collect();
+    top_rules.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top_rules.truncate(10);
+
+    summary.weeks = weeks.into_values().collect();
+    summary.top_rules = top_rules;
+    summary
+}
+
+impl fmt::Display for StatsSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<10} {:>6} {:>10} {:>8} {:>8} {:>10}", "Week", "Runs", "Validated", "Passed", "Failed", "Pass rate")?;
+        for week in &self.weeks {
+            writeln!(
+                f,
+                "{:<10} {:>6} {:>10} {:>8} {:>8} {:>9.1}%",
+                week.week, week.runs, week.validated, week.passed, week.failed, week.pass_rate()
+            )?;
+        }
+
+        if !self.top_rules.is_empty() {
+            writeln!(f, "\nTop recurring rules:")?;
+            for (rule, count) in &self.top_rules {
+                writeln!(f, "  {:>6}  {}", count, rule)?;
+            }
+        }
+
+        if self.skipped_lines > 0 {
+            writeln!(f, "\n{} malformed line(s) skipped", self.skipped_lines)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: &str, files: u32, passed: u32) -> StatsRecord {
+        let mut rule_counts = BTreeMap::new();
+        rule_counts.insert("missing-images".to_string(), files);
+        StatsRecord {
+            timestamp: timestamp.to_string(),
+            target: "recipes/".to_string(),
+            files,
+            passed,
+            failed: files - passed,
+            errors: files - passed,
+            warnings: files,
+            rule_counts,
+            schema_version: "0.1".to_string(),
+            cli_version: "1.0.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_append_and_summarize() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.jsonl");
+
+        append_record(&path, &record("2025-06-02T08:00:00Z", 10, 8)).unwrap();
+        append_record(&path, &record("2025-06-03T08:00:00Z", 10, 10)).unwrap();
+        append_record(&path, &record("2025-06-10T08:00:00Z", 4, 1)).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 3);
+
+        let summary = summarize(&path).unwrap();
+        assert_eq!(summary.runs, 3);
+        assert_eq!(summary.weeks.len(), 2);
+        assert_eq!(summary.weeks[0].week, "2025-W23");
+        assert_eq!(summary.weeks[0].validated, 20);
+        assert_eq!(summary.weeks[0].pass_rate(), 90.0);
+        assert_eq!(summary.top_rules[0], ("missing-images".to_string(), 24));
+    }
+
+    #[test]
+    fn test_concurrent_appends_keep_every_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.jsonl");
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        append_record(&path, &record("2025-06-02T08:00:00Z", 1, 1)).unwrap();
+                    }
+                });
+            }
+        });
+
+        let summary = summarize(&path).unwrap();
+        assert_eq!((summary.runs, summary.skipped_lines), (80, 0));
+    }
+
+    #[test]
+    fn test_malformed_lines_are_skipped() {
+        let line = serde_json::to_string(&record("2025-06-02T08:00:00Z", 1, 1)).unwrap();
+        let summary = summarize_str(&format!("{}\nnot json\n", line));
+        assert_eq!(summary.runs, 1);
+        assert_eq!(summary.skipped_lines, 1);
+    }
+}
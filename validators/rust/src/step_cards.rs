@@ -0,0 +1,316 @@
+// Step cards for small displays
+//
+// Appliance displays show one step at a time. A card carries the step's
+// position, its text cut to a character budget, the amounts of the
+// ingredients it targets, its duration and temperature, and its hazards as
+// identifiers for the device to draw as icons. Text over budget is cut after
+// the last whole sentence that fits, then the continuation marker; if not
+// even the first sentence fits it is cut at a word boundary and the step is
+// flagged as unfit, since the device would show half an instruction. Steps
+// with more ingredients than a card has lines are flagged too. Result
+// references read "the dough" as in the calendar export. Ingredients are
+// named with their declared product, as `product::display_name` writes them.
+
+use crate::{guidance, intermediates, product, step_duration_minutes, step_temperature_c};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// Budgets and language for step cards
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardOptions {
+    /// Most characters of step text on a card, continuation marker included
+    pub text_budget: usize,
+    /// Most ingredient lines on a card
+    pub ingredient_lines: usize,
+    /// Appended to text that was cut
+    pub continuation: String,
+    /// Language of the step text when `human_text` carries translations;
+    /// `None` uses the plain or English text
+    pub locale: Option<String>,
+}
+
+impl Default for CardOptions {
+    fn default() -> Self {
+        CardOptions {
+            text_budget: 160,
+            ingredient_lines: 4,
+            continuation: "…".to_string(),
+            locale: None,
+        }
+    }
+}
+
+/// An ingredient line on a card
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CardIngredient {
+    pub name: String,
+    pub amount: Option<String>,
+}
+
+/// One step, laid out for a small display
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepCard {
+    pub step_id: String,
+    pub number: usize,
+    pub total: usize,
+    pub text: String,
+    pub truncated: bool,
+    pub ingredients: Vec<CardIngredient>,
+    pub duration_minutes: Option<f64>,
+    pub temperature_c: Option<f64>,
+    pub hazards: Vec<String>,
+    /// Why the step does not fit a card; empty when it does
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unfit: Vec<String>,
+}
+
+/// Steps an author should shorten
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CardReport {
+    /// Steps whose text was cut
+    pub truncated: Vec<String>,
+    /// Steps that do not fit a card, with the reason
+    pub unfit: Vec<(String, String)>,
+}
+
+impl CardReport {
+    pub fn new(cards: &[StepCard]) -> Self {
+        CardReport {
+            truncated: cards.iter().filter(|c| c.truncated).map(|c| c.step_id.clone()).collect(),
+            unfit: cards
+                .iter()
+                .flat_map(|c| c.unfit.iter().map(move |reason| (c.step_id.clone(), reason.clone())))
+                .collect(),
+        }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.unfit.is_empty()
+    }
+}
+
+impl fmt::Display for CardReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.truncated.is_empty() {
+            writeln!(f, "Truncated: {}", self.truncated.join(", "))?;
+        }
+        for (step_id, reason) in &self.unfit {
+            writeln!(f, "Does not fit: {}: {}", step_id, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Cut text to `budget` characters; returns the text, whether it was cut,
+/// and whether a whole sentence survived
+fn fit_text(text: &str, budget: usize, marker: &str) -> (String, bool, bool) {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= budget {
+        return (text.to_string(), false, true);
+    }
+    let room = budget.saturating_sub(marker.chars().count() + 1);
+    let sentence_end = (0..room)
+        .rev()
+        .find(|&i| matches!(chars[i], '.' | '!' | '?') && chars[i + 1].is_whitespace());
+    if let Some(end) = sentence_end {
+        let head: String = chars[..=end].iter().collect();
+        return (format!("{} {}", head, marker), true, true);
+    }
+    let head: String = chars[..room].iter().collect();
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => head[..space].trim_end().to_string(),
+        _ => head,
+    };
+    (format!("{} {}", head, marker), true, false)
+}
+
+pub(crate) fn step_text(recipe: &Value, step: &Value, locale: Option<&str>) -> String {
+    let text = match step.get("human_text") {
+        Some(Value::String(text)) => text.clone(),
+        Some(entry @ Value::Object(_)) => guidance::localized(entry, locale.unwrap_or("en")).unwrap_or_default(),
+        _ => String::new(),
+    };
+    intermediates::friendly_text(recipe, text.trim())
+}
+
+pub(crate) fn amount(ingredient: &Value) -> Option<String> {
+    if let Some(human) = ingredient.get("human_amount").and_then(|v| v.as_str()) {
+        return Some(human.to_string());
+    }
+    let machine = ingredient.get("machine_amount")?;
+    let value = machine.get("value").and_then(|v| v.as_f64())?;
+    let unit = machine.get("unit").and_then(|v| v.as_str()).unwrap_or("");
+    Some(format!("{} {}", value, unit).trim_end().to_string())
+}
+
+/// Lay out every step of a recipe as a card
+pub fn to_step_cards(recipe: &Value, options: &CardOptions) -> Vec<StepCard> {
+    let steps: Vec<&Value> = recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let ingredients: Vec<&Value> = recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten().collect();
+    let total = steps.len();
+
+    steps
+        .iter()
+        .enumerate()
+        .map(|(i, step)| {
+            let step_id = step.get("step_id").and_then(|v| v.as_str()).map(|s| s.to_string()).unwrap_or_else(|| format!("#{}", i));
+            let text = step_text(recipe, step, options.locale.as_deref());
+            let (text, truncated, whole_sentence) = fit_text(&text, options.text_budget, &options.continuation);
+
+            let mut card_ingredients: Vec<CardIngredient> = step
+                .get("target")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|t| t.as_str())
+                .filter_map(|t| ingredients.iter().find(|ing| ing.get("id").and_then(|v| v.as_str()) == Some(t)))
+                .map(|ing| CardIngredient {
+                    name: product::display_name(ing),
+                    amount: amount(ing),
+                })
+                .collect();
+
+            let mut unfit = Vec::new();
+            if !whole_sentence {
+                unfit.push(format!("the first sentence is longer than {} characters", options.text_budget));
+            }
+            if card_ingredients.len() > options.ingredient_lines {
+                unfit.push(format!(
+                    "{} ingredients, the card has {} lines",
+                    card_ingredients.len(),
+                    options.ingredient_lines
+                ));
+                card_ingredients.truncate(options.ingredient_lines);
+            }
+
+            StepCard {
+                step_id,
+                number: i + 1,
+                total,
+                text,
+                truncated,
+                ingredients: card_ingredients,
+                duration_minutes: step_duration_minutes(step),
+                temperature_c: step_temperature_c(step),
+                hazards: step
+                    .get("hazards")
+                    .and_then(|v| v.as_array())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|h| h.as_str())
+                    .map(|h| h.to_string())
+                    .collect(),
+                unfit,
+            }
+        })
+        .collect()
+}
+
+/// Printable Markdown deck, one card per section
+pub fn cards_to_markdown(cards: &[StepCard]) -> String {
+    let mut out = String::new();
+    for (i, card) in cards.iter().enumerate() {
+        if i > 0 {
+            out.push_str("\n---\n\n");
+        }
+        out.push_str(&format!("## Step {} of {}\n\n{}\n", card.number, card.total, card.text));
+        if !card.ingredients.is_empty() {
+            out.push('\n');
+            for ingredient in &card.ingredients {
+                match &ingredient.amount {
+                    Some(amount) => out.push_str(&format!("- {} {}\n", amount, ingredient.name)),
+                    None => out.push_str(&format!("- {}\n", ingredient.name)),
+                }
+            }
+        }
+        let mut facts = Vec::new();
+        if let Some(minutes) = card.duration_minutes {
+            facts.push(format!("{} min", minutes));
+        }
+        if let Some(celsius) = card.temperature_c {
+            facts.push(format!("{}°C", celsius));
+        }
+        if !card.hazards.is_empty() {
+            facts.push(format!("hazards: {}", card.hazards.join(", ")));
+        }
+        if !facts.is_empty() {
+            out.push_str(&format!("\n_{}_\n", facts.join(" · ")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn recipe() -> Value {
+        json!({
+            "ingredients": [
+                {"id": "ing-0001", "name": "flour", "human_amount": "500 g", "product": {"brand": "King Arthur"}},
+                {"id": "ing-0002", "name": "water", "machine_amount": {"value": 350, "unit": "ml"}}
+            ],
+            "steps": [
+                {"step_id": "s-01", "action": "mix", "target": ["ing-0001", "ing-0002"],
+                 "human_text": "Mix flour and water. Rest the dough until smooth and elastic, about ten minutes.",
+                 "params": {"time_minutes": 10}, "result": {"name": "dough"}},
+                {"step_id": "s-02", "action": "bake", "target": ["@dough"], "hazards": ["hot-surface"],
+                 "human_text": {"en": "Bake @dough", "de": "Den Teig backen"},
+                 "params": {"time_minutes": 40, "temperature_c": 230}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_cards_carry_step_facts() {
+        let cards = to_step_cards(&recipe(), &CardOptions::default());
+        assert_eq!(cards.len(), 2);
+        assert_eq!((cards[1].number, cards[1].total), (2, 2));
+        assert_eq!(cards[0].ingredients, vec![
+            CardIngredient { name: "flour, King Arthur (or equivalent)".to_string(), amount: Some("500 g".to_string()) },
+            CardIngredient { name: "water".to_string(), amount: Some("350 ml".to_string()) },
+        ]);
+        assert_eq!(cards[1].text, "Bake the dough");
+        assert_eq!(cards[1].temperature_c, Some(230.0));
+        assert_eq!(cards[1].hazards, vec!["hot-surface"]);
+
+        let german = CardOptions { locale: Some("de".to_string()), ..CardOptions::default() };
+        assert_eq!(to_step_cards(&recipe(), &german)[1].text, "Den Teig backen");
+    }
+
+    #[test]
+    fn test_truncation_and_report() {
+        let options = CardOptions { text_budget: 40, ingredient_lines: 1, ..CardOptions::default() };
+        let cards = to_step_cards(&recipe(), &options);
+        assert_eq!(cards[0].text, "Mix flour and water. …");
+        assert!(cards[0].truncated);
+
+        let tight = CardOptions { text_budget: 12, ..options };
+        let cards = to_step_cards(&recipe(), &tight);
+        assert_eq!(cards[0].text, "Mix flour …");
+        assert!(cards[0].text.chars().count() <= 12);
+        let report = CardReport::new(&cards);
+        assert_eq!(report.truncated, vec!["s-01", "s-02"]);
+        assert_eq!(report.unfit, vec![
+            ("s-01".to_string(), "the first sentence is longer than 12 characters".to_string()),
+            ("s-01".to_string(), "2 ingredients, the card has 1 lines".to_string()),
+            ("s-02".to_string(), "the first sentence is longer than 12 characters".to_string()),
+        ]);
+        assert_eq!(cards[0].ingredients.len(), 1);
+    }
+
+    #[test]
+    fn test_markdown_and_json() {
+        let cards = to_step_cards(&recipe(), &CardOptions::default());
+        let markdown = cards_to_markdown(&cards);
+        assert!(markdown.starts_with("## Step 1 of 2\n\nMix flour and water."));
+        assert!(markdown.contains("- 500 g flour, King Arthur (or equivalent)\n"));
+        assert!(markdown.contains("\n---\n\n## Step 2 of 2\n\nBake the dough\n\n_40 min · 230°C · hazards: hot-surface_\n"));
+
+        let json = serde_json::to_value(&cards).unwrap();
+        assert_eq!(json[1]["hazards"], json!(["hot-surface"]));
+        assert!(json[0].get("unfit").is_none());
+    }
+}
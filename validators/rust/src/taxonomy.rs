@@ -0,0 +1,206 @@
+// Ingredient taxonomy
+//
+// Keyword-based classification of ingredient names into store categories.
+// The built-in keyword table lives in `assets/taxonomy.json` so it can grow
+// without touching this module. Matching is on whole words (with simple
+// plurals), and the longest matching keyword wins, so "olive oil" is a fat
+// while "olives" are produce.
+
+use crate::{is_optional, product};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const BUILTIN_KEYWORDS: &str = include_str!("../assets/taxonomy.json");
+
+/// Store category of an ingredient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Produce,
+    Dairy,
+    Meat,
+    Seafood,
+    Grains,
+    Spices,
+    Fats,
+    Sweeteners,
+    Beverages,
+}
+
+impl Category {
+    /// Store aisle name for shopping lists
+    pub fn aisle(&self) -> &'static str {
+        match self {
+            Category::Produce => "Produce",
+            Category::Dairy => "Dairy & Eggs",
+            Category::Meat => "Meat",
+            Category::Seafood => "Seafood",
+            Category::Grains => "Bakery & Grains",
+            Category::Spices => "Spices & Seasonings",
+            Category::Fats => "Oils & Fats",
+            Category::Sweeteners => "Baking & Sweeteners",
+            Category::Beverages => "Beverages",
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.aisle())
+    }
+}
+
+/// Keyword classifier, extensible with custom keywords
+#[derive(Debug, Clone, Default)]
+pub struct Classifier {
+    keywords: HashMap<String, Category>,
+    /// Keywords by their first word, so a name is only checked against
+    /// keywords starting with one of its words
+    by_first_word: HashMap<String, Vec<String>>,
+}
+
+impl Classifier {
+    /// Classifier loaded with the built-in keyword table
+    pub fn builtin() -> Self {
+        let table: HashMap<Category, Vec<String>> =
+            serde_json::from_str(BUILTIN_KEYWORDS).expect("built-in taxonomy asset is valid JSON");
+
+        let mut classifier = Classifier::default();
+        for (category, keywords) in table {
+            for keyword in keywords {
+                classifier.add_keyword(category, &keyword);
+            }
+        }
+        classifier
+    }
+
+    /// Add or override a keyword
+    pub fn add_keyword(&mut self, category: Category, keyword: &str) {
+        let keyword = normalize(keyword);
+        if self.keywords.insert(keyword.clone(), category).is_none() {
+            let first = keyword.split(' ').next().unwrap_or("").to_string();
+            self.by_first_word.entry(first).or_default().push(keyword);
+        }
+    }
+
+    /// Classify an ingredient name
+    pub fn classify(&self, name: &str) -> Option<Category> {
+        let padded = format!(" {} ", normalize(name));
+
+        // A word is a keyword's first word, or its last in plural
+        let candidates = padded.split_whitespace().flat_map(|word| {
+            [Some(word), word.strip_suffix('s'), word.strip_suffix("es")]
+                .into_iter()
+                .flatten()
+                .filter_map(|stem| self.by_first_word.get(stem))
+                .flatten()
+        });
+        candidates
+            .filter(|keyword| mentions(&padded, keyword))
+            .filter_map(|keyword| self.keywords.get_key_value(keyword))
+            .max_by(|a, b| a.0.len().cmp(&b.0.len()).then_with(|| b.0.cmp(a.0)))
+            .map(|(_, category)| *category)
+    }
+}
+
+lazy_static! {
+    static ref BUILTIN: Classifier = Classifier::builtin();
+}
+
+/// Classify an ingredient name with the built-in keyword table
+pub fn classify(name: &str) -> Option<Category> {
+    BUILTIN.classify(name)
+}
+
+/// Whether the space-padded text has the keyword as whole words, or its
+/// plural with -s or -es
+fn mentions(padded: &str, keyword: &str) -> bool {
+    padded.match_indices(keyword).any(|(at, _)| {
+        let after = &padded[at + keyword.len()..];
+        padded[..at].ends_with(' ') && (after.starts_with(' ') || after.starts_with("s ") || after.starts_with("es "))
+    })
+}
+
+fn normalize(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut normalized = String::with_capacity(lower.len());
+    for word in lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        normalized.push_str(word);
+    }
+    normalized
+}
+
+/// A shopping-list item
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShoppingItem {
+    pub id: String,
+    pub name: String,
+    pub human_amount: Option<String>,
+    /// Garnish or serving suggestion; buy only if wanted
+    pub optional: bool,
+}
+
+/// Group a recipe's ingredients by store aisle. Unclassified ingredients are
+/// collected under `None`, which sorts first.
+pub fn group_by_category(recipe: &Value, classifier: &Classifier) -> BTreeMap<Option<Category>, Vec<ShoppingItem>> {
+    let mut groups: BTreeMap<Option<Category>, Vec<ShoppingItem>> = BTreeMap::new();
+
+    for ingredient in recipe.get("ingredients").and_then(|v| v.as_array()).into_iter().flatten() {
+        let name = ingredient.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        groups.entry(classifier.classify(name)).or_default().push(ShoppingItem {
+            id: ingredient.get("id").and_then(|v| v.as_str()).unwrap_or("?").to_string(),
+            name: product::display_name(ingredient),
+            human_amount: ingredient.get("human_amount").and_then(|v| v.as_str()).map(|s| s.to_string()),
+            optional: is_optional(ingredient),
+        });
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builtin_classification() {
+        assert_eq!(classify("San Marzano tomatoes"), Some(Category::Produce));
+        assert_eq!(classify("extra virgin olive oil"), Some(Category::Fats));
+        assert_eq!(classify("Kalamata olives"), Some(Category::Produce));
+        assert_eq!(classify("fresh mozzarella"), Some(Category::Dairy));
+        assert_eq!(classify("freshly ground black pepper"), Some(Category::Spices));
+        assert_eq!(classify("00 flour"), Some(Category::Grains));
+        assert_eq!(classify("xanthan gum"), None);
+    }
+
+    #[test]
+    fn test_custom_keywords() {
+        let mut classifier = Classifier::builtin();
+        assert_eq!(classifier.classify("xanthan gum"), None);
+        classifier.add_keyword(Category::Spices, "xanthan gum");
+        assert_eq!(classifier.classify("Xanthan Gum"), Some(Category::Spices));
+    }
+
+    #[test]
+    fn test_group_by_category() {
+        let recipe = json!({"ingredients": [
+            {"id": "ing-0001", "name": "flour", "human_amount": "500g"},
+            {"id": "ing-0002", "name": "unobtainium"},
+            {"id": "ing-0003", "name": "butter", "optional": true}
+        ]});
+
+        let groups = group_by_category(&recipe, &Classifier::builtin());
+        let keys: Vec<Option<Category>> = groups.keys().copied().collect();
+        assert_eq!(keys, vec![None, Some(Category::Dairy), Some(Category::Grains)]);
+        assert_eq!(groups[&Some(Category::Grains)][0].human_amount.as_deref(), Some("500g"));
+        assert!(groups[&Some(Category::Dairy)][0].optional);
+        assert!(!groups[&Some(Category::Grains)][0].optional);
+    }
+}
@@ -0,0 +1,365 @@
+// Controlled vocabularies
+//
+// Allergens, diet labels and hazards are closed lists of kebab-case names,
+// but real files spell them loosely: "Milk", "MILK ", "tree nuts",
+// "TreeNuts". A value is matched after trimming, splitting camel case,
+// lowercasing and joining its words with hyphens; one that only differs from
+// a name in that way is a variant, reported with the canonical spelling and
+// rewritten by `normalize`. A list naming the same
+// term twice, in any spelling, gets a warning for each repeat. Only values
+// no spelling explains are unknown, and those within an edit or two of a
+// name come with it as a suggestion.
+//
+// The allergen, diet label and hazard rules all report through
+// `Vocabulary::check`, which keeps each field's own code for values that
+// are simply unknown. Aggregates such as `RecipeInfo::allergens` go through
+// `Vocabulary::canonical`, so "Milk" and "milk" count as one allergen.
+
+use crate::{allergens, codes, Severity, ValidationResult};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// A closed list of names, and how values that look misspelled are reported
+pub struct Vocabulary {
+    pub terms: &'static [&'static str],
+    pub misspelled: (&'static str, Severity),
+}
+
+pub const ALLERGENS: Vocabulary = Vocabulary {
+    terms: allergens::VOCABULARY,
+    misspelled: (codes::MISSPELLED_TERM, Severity::Error),
+};
+pub const DIET_LABELS: Vocabulary = Vocabulary {
+    terms: crate::DIET_LABELS,
+    misspelled: (codes::MISSPELLED_TERM, Severity::Error),
+};
+pub const HAZARDS: Vocabulary = Vocabulary {
+    terms: crate::HAZARDS,
+    misspelled: (codes::MISSPELLED_HAZARD, Severity::Warning),
+};
+
+/// Recipe fields holding a list of names, `*` for array items
+pub const LISTS: &[(&str, &Vocabulary)] = &[
+    ("/ingredients/*/allergens", &ALLERGENS),
+    ("/ingredients/*/may_contain_allergens", &ALLERGENS),
+    ("/meta/facility_allergens", &ALLERGENS),
+    ("/meta/diet_labels", &DIET_LABELS),
+    ("/steps/*/hazards", &HAZARDS),
+];
+
+/// A value trimmed, lowercased, and with its words, camel case ones
+/// included, joined by hyphens
+pub fn normalized(value: &str) -> String {
+    let mut words = String::with_capacity(value.len() + 4);
+    let mut previous = ' ';
+    for c in value.chars() {
+        if c.is_uppercase() && (previous.is_lowercase() || previous.is_ascii_digit()) {
+            words.push(' ');
+        }
+        words.push(c);
+        previous = c;
+    }
+    words
+        .to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Edit distance between two names
+pub(crate) fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// How a value relates to a vocabulary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Term {
+    Canonical(&'static str),
+    /// The name, spelled differently
+    Variant(&'static str),
+    /// No name, with the only one close enough to be meant, if any
+    Unknown(Option<&'static str>),
+}
+
+/// One value of a list, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    Term(Term),
+    /// The name an earlier entry, at index `first`, already gave
+    Repeat { canonical: &'static str, first: usize },
+}
+
+impl Vocabulary {
+    pub fn resolve(&self, value: &str) -> Term {
+        if let Some(term) = self.terms.iter().find(|t| **t == value) {
+            return Term::Canonical(term);
+        }
+        let normalized = normalized(value);
+        if let Some(term) = self.terms.iter().find(|t| **t == normalized) {
+            return Term::Variant(term);
+        }
+
+        let allowed = if normalized.chars().count() < 5 { 1 } else { 2 };
+        let close: Vec<(usize, &'static str)> =
+            self.terms.iter().map(|t| (distance(&normalized, t), *t)).filter(|(d, _)| *d <= allowed).collect();
+        let best = close.iter().map(|(d, _)| *d).min();
+        let mut nearest = close.iter().filter(|(d, _)| Some(*d) == best);
+        match (nearest.next(), nearest.next()) {
+            (Some((_, term)), None) => Term::Unknown(Some(term)),
+            _ => Term::Unknown(None),
+        }
+    }
+
+    /// The name a value stands for, or the value itself when it is unknown
+    pub fn canonical<'a>(&self, value: &'a str) -> &'a str {
+        match self.resolve(value) {
+            Term::Canonical(term) | Term::Variant(term) => term,
+            Term::Unknown(_) => value,
+        }
+    }
+
+    /// Classify the string values of a list, by their index in it
+    pub fn review<'v>(&self, list: &'v Value) -> Vec<(usize, &'v str, Entry)> {
+        let mut seen: Vec<(&'static str, usize)> = Vec::new();
+        let values = list.as_array().into_iter().flatten().enumerate();
+        let values = values.filter_map(|(i, v)| v.as_str().map(|s| (i, s)));
+        values
+            .map(|(index, value)| {
+                let term = self.resolve(value);
+                let entry = match term {
+                    Term::Canonical(name) | Term::Variant(name) => match seen.iter().find(|(n, _)| *n == name) {
+                        Some((_, first)) => Entry::Repeat { canonical: name, first: *first },
+                        None => {
+                            seen.push((name, index));
+                            Entry::Term(term)
+                        }
+                    },
+                    Term::Unknown(_) => Entry::Term(term),
+                };
+                (index, value, entry)
+            })
+            .collect()
+    }
+
+    /// Report variants, repeats and misspellings in the list at `pointer`;
+    /// `unknown` reports a value with nothing close, under the field's code
+    pub fn check(
+        &self,
+        list: &Value,
+        pointer: &str,
+        result: &mut ValidationResult,
+        mut unknown: impl FnMut(&mut ValidationResult, &str),
+    ) {
+        for (index, value, entry) in self.review(list) {
+            let path = format!("{}/{}", pointer, index);
+            match entry {
+                Entry::Term(Term::Canonical(_)) => {}
+                Entry::Term(Term::Variant(canonical)) => {
+                    result.warning(codes::NONCANONICAL_TERM, &[("path", &path), ("value", &value), ("canonical", &canonical)]);
+                }
+                Entry::Repeat { canonical, first } => {
+                    let first = format!("{}/{}", pointer, first);
+                    result.warning(codes::DUPLICATE_TERM, &[
+                        ("path", &path),
+                        ("value", &value),
+                        ("canonical", &canonical),
+                        ("first", &first),
+                    ]);
+                }
+                Entry::Term(Term::Unknown(Some(suggestion))) => {
+                    let params: [(&'static str, &dyn fmt::Display); 3] =
+                        [("path", &path), ("value", &value), ("suggestion", &suggestion)];
+                    match self.misspelled {
+                        (code, Severity::Error) => result.error(code, &params),
+                        (code, _) => result.warning(code, &params),
+                    }
+                }
+                Entry::Term(Term::Unknown(None)) => unknown(result, value),
+            }
+        }
+    }
+}
+
+/// Pointers to the lists at a path with at most one `*`
+fn pointers(recipe: &Value, path: &str) -> Vec<String> {
+    match path.split_once("/*") {
+        Some((array, rest)) => {
+            let items = recipe.pointer(array).and_then(|v| v.as_array()).map(|a| a.len()).unwrap_or(0);
+            (0..items).map(|i| format!("{}/{}{}", array, i, rest)).collect()
+        }
+        None => vec![path.to_string()],
+    }
+}
+
+/// Whether the value at `pointer` is a vocabulary name spelled differently,
+/// which the schema rejects and the rules report as a variant instead
+pub fn is_variant(recipe: &Value, pointer: &str) -> bool {
+    let (list, _) = match pointer.rsplit_once('/') {
+        Some(split) => split,
+        None => return false,
+    };
+    let value = match recipe.pointer(pointer).and_then(|v| v.as_str()) {
+        Some(value) => value,
+        None => return false,
+    };
+    LISTS.iter().any(|(path, vocabulary)| {
+        matches_path(path, list) && matches!(vocabulary.resolve(value), Term::Variant(_))
+    })
+}
+
+/// Whether a pointer is one of the paths a pattern with `*`s describes
+fn matches_path(pattern: &str, pointer: &str) -> bool {
+    let (pattern, pointer): (Vec<&str>, Vec<&str>) = (pattern.split('/').collect(), pointer.split('/').collect());
+    pattern.len() == pointer.len()
+        && pattern.iter().zip(&pointer).all(|(p, s)| p == s || (*p == "*" && s.parse::<usize>().is_ok()))
+}
+
+/// One value `normalize` rewrote or dropped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub path: String,
+    pub value: String,
+    pub canonical: &'static str,
+    /// Dropped as a repeat of an earlier entry rather than rewritten
+    pub dropped: bool,
+}
+
+/// What `normalize` did
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NormalizationReport {
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for NormalizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            if change.dropped {
+                writeln!(f, "{}: dropped '{}', a repeat of {}", change.path, change.value, change.canonical)?;
+            } else {
+                writeln!(f, "{}: '{}' written as {}", change.path, change.value, change.canonical)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rewrite every variant in the recipe's vocabulary lists to its canonical
+/// name and drop repeats. Unknown values are left for a person
+pub fn normalize(recipe: &mut Value) -> NormalizationReport {
+    let mut report = NormalizationReport::default();
+    for (path, vocabulary) in LISTS {
+        for pointer in pointers(recipe, path) {
+            let list = match recipe.pointer(&pointer) {
+                Some(list) => list,
+                None => continue,
+            };
+            let mut dropped = Vec::new();
+            let mut rewritten = Vec::new();
+            for (index, value, entry) in vocabulary.review(list) {
+                let path = format!("{}/{}", pointer, index);
+                let (canonical, drop) = match entry {
+                    Entry::Term(Term::Variant(canonical)) => (canonical, false),
+                    Entry::Repeat { canonical, .. } => (canonical, true),
+                    _ => continue,
+                };
+                if drop {
+                    dropped.push(index);
+                } else {
+                    rewritten.push((index, canonical));
+                }
+                report.changes.push(Change { path, value: value.to_string(), canonical, dropped: drop });
+            }
+
+            if let Some(items) = recipe.pointer_mut(&pointer).and_then(|v| v.as_array_mut()) {
+                for (index, canonical) in rewritten {
+                    items[index] = Value::from(canonical);
+                }
+                for index in dropped.into_iter().rev() {
+                    items.remove(index);
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolves_spellings_and_suggestions() {
+        assert_eq!(ALLERGENS.resolve("milk"), Term::Canonical("milk"));
+        assert_eq!(ALLERGENS.resolve(" MILK "), Term::Variant("milk"));
+        assert_eq!(ALLERGENS.resolve("Tree Nuts"), Term::Variant("tree-nuts"));
+        assert_eq!(ALLERGENS.resolve("TreeNuts"), Term::Variant("tree-nuts"));
+        assert_eq!(DIET_LABELS.resolve("GlutenFree"), Term::Variant("gluten-free"));
+        assert_eq!(DIET_LABELS.resolve("gluten_free"), Term::Variant("gluten-free"));
+        assert_eq!(HAZARDS.resolve("Hot  Surface"), Term::Variant("hot-surface"));
+        assert_eq!(ALLERGENS.resolve("sesmae"), Term::Unknown(Some("sesame")));
+        assert_eq!(ALLERGENS.resolve("egg"), Term::Unknown(Some("eggs")));
+        assert_eq!(DIET_LABELS.resolve("vegn"), Term::Unknown(Some("vegan")));
+        assert_eq!(HAZARDS.resolve("steam"), Term::Unknown(None));
+        assert_eq!(ALLERGENS.canonical("Soybeans "), "soybeans");
+        assert_eq!(ALLERGENS.canonical("nuts"), "nuts");
+    }
+
+    #[test]
+    fn test_check_reports_each_entry_once() {
+        let list = json!(["Milk", "milk", "MILK ", "sesmae", "nuts", 7, "wheat"]);
+        let mut result = ValidationResult::new();
+        let mut unknown = Vec::new();
+        ALLERGENS.check(&list, "/ingredients/0/allergens", &mut result, |_, value| unknown.push(value.to_string()));
+
+        assert_eq!(unknown, ["nuts"]);
+        assert!(!result.valid);
+        assert_eq!(
+            result.warnings,
+            [
+                "/ingredients/0/allergens/0: 'Milk' stands for 'milk'; write it that way",
+                "/ingredients/0/allergens/1: 'milk' repeats 'milk' from /ingredients/0/allergens/0",
+                "/ingredients/0/allergens/2: 'MILK ' repeats 'milk' from /ingredients/0/allergens/0",
+            ]
+        );
+        assert_eq!(result.errors, ["/ingredients/0/allergens/3: unknown value 'sesmae'; did you mean 'sesame'?"]);
+
+        let mut result = ValidationResult::new();
+        HAZARDS.check(&json!(["sharp tools"]), "/steps/0/hazards", &mut result, |_, _| {});
+        assert!(result.valid);
+        assert_eq!(result.issues[0].code, codes::MISSPELLED_HAZARD);
+    }
+
+    #[test]
+    fn test_normalize_rewrites_and_drops_repeats() {
+        let mut recipe = json!({
+            "meta": {"diet_labels": ["Vegetarian", "vegetarian"], "facility_allergens": ["peanuts"]},
+            "ingredients": [{"allergens": ["Milk", "milk", "MILK ", "nuts"]}, {"allergens": ["Eggs"]}],
+            "steps": [{"hazards": ["Hot Surface"]}]
+        });
+        let report = normalize(&mut recipe);
+        assert_eq!(recipe["ingredients"][0]["allergens"], json!(["milk", "nuts"]));
+        assert_eq!(recipe["ingredients"][1]["allergens"], json!(["eggs"]));
+        assert_eq!(recipe["meta"]["diet_labels"], json!(["vegetarian"]));
+        assert_eq!(recipe["steps"][0]["hazards"], json!(["hot-surface"]));
+        assert_eq!(report.changes.len(), 7);
+        assert!(report.to_string().contains("/ingredients/0/allergens/2: dropped 'MILK ', a repeat of milk\n"));
+
+        assert!(is_variant(&json!({"steps": [{"hazards": ["Pressure"]}]}), "/steps/0/hazards/0"));
+        assert!(!is_variant(&json!({"steps": [{"hazards": ["pressure"]}]}), "/steps/0/hazards/0"));
+        assert!(!is_variant(&json!({"meta": {"name": "Milk"}}), "/meta/name"));
+    }
+}
@@ -0,0 +1,163 @@
+// Step text quality
+//
+// Catches `human_text` that was never really written: empty or whitespace
+// only (an error), placeholders like "TODO" or a lone character, and pasted
+// prose too long to follow at the stove (warnings). Optionally, a step's text
+// must mention its declared action, a synonym or a context word for it, so a
+// `bake` step that never talks about baking or the oven stands out. Long
+// conditional sentences ("if the dough is sticky, ...") are flagged as advice
+// that belongs in the step's `tips`.
+
+use crate::{codes, vocabulary, ValidationResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Thresholds for the text-quality rules
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TextPolicy {
+    /// Texts shorter than this many characters are treated as placeholders
+    pub min_length: usize,
+    /// Texts longer than this many characters should be split into steps
+    pub max_length: usize,
+    /// Words that mark unfinished text, matched case-insensitively
+    pub placeholders: Vec<String>,
+    /// Warn when the text never mentions the step's action
+    pub require_action_mention: bool,
+    /// Conditional sentences longer than this many characters belong in tips
+    pub max_conditional_length: usize,
+}
+
+impl Default for TextPolicy {
+    fn default() -> Self {
+        TextPolicy {
+            min_length: 3,
+            max_length: 500,
+            placeholders: ["todo", "tbd", "tba", "xxx", "fixme", "lorem", "asdf"].iter().map(|s| s.to_string()).collect(),
+            require_action_mention: false,
+            max_conditional_length: 60,
+        }
+    }
+}
+
+/// Placeholder word in a text, if any
+fn placeholder<'a>(text: &str, policy: &'a TextPolicy) -> Option<&'a str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .find_map(|word| policy.placeholders.iter().find(|p| p.eq_ignore_ascii_case(word)))
+        .map(|p| p.as_str())
+}
+
+/// Phrases that introduce optional or situational advice
+const CONDITIONAL_PHRASES: &[&str] = &["if", "in case", "optionally", "unless", "if desired", "if needed"];
+
+/// First conditional phrase opening a sentence longer than the policy allows
+fn conditional(text: &str, policy: &TextPolicy) -> Option<&'static str> {
+    text.split(['.', '!', '?', ';', '\n'])
+        .filter(|sentence| sentence.trim().chars().count() > policy.max_conditional_length)
+        .find_map(|sentence| {
+            let words: Vec<String> = sentence
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|w| !w.is_empty())
+                .map(|w| w.to_lowercase())
+                .collect();
+            let padded = format!(" {} ", words.join(" "));
+            CONDITIONAL_PHRASES.iter().find(|p| padded.contains(&format!(" {} ", p))).copied()
+        })
+}
+
+/// Apply the text-quality rules to every step of a recipe
+pub fn check(recipe: &Value, policy: &TextPolicy, result: &mut ValidationResult) {
+    for (index, step) in recipe.get("steps").and_then(|v| v.as_array()).into_iter().flatten().enumerate() {
+        let text = match step.get("human_text").and_then(|v| v.as_str()) {
+            Some(text) => text.trim(),
+            None => continue,
+        };
+        let length = text.chars().count();
+
+        if text.is_empty() {
+            result.error(codes::EMPTY_TEXT, &[("index", &index)]);
+            continue;
+        }
+
+        if let Some(word) = placeholder(text, policy) {
+            result.warning(codes::PLACEHOLDER_TEXT, &[("index", &index), ("text", &word)]);
+        } else if length < policy.min_length {
+            result.warning(codes::PLACEHOLDER_TEXT, &[("index", &index), ("text", &text)]);
+        }
+
+        if length > policy.max_length {
+            result.warning(codes::LONG_TEXT, &[("index", &index), ("length", &length), ("max", &policy.max_length)]);
+        }
+
+        if let Some(phrase) = conditional(text, policy) {
+            result.warning(codes::CONDITIONAL_TEXT, &[("index", &index), ("phrase", &phrase)]);
+        }
+
+        if policy.require_action_mention {
+            if let Some(action) = step.get("action").and_then(|v| v.as_str()) {
+                if !vocabulary::mentions(text, action) {
+                    result.warning(codes::ACTION_NOT_MENTIONED, &[("index", &index), ("action", &action)]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn steps(texts: &[(&str, &str)]) -> Value {
+        let steps: Vec<Value> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, (action, text))| json!({"step_id": format!("s-{:02}", i + 1), "action": action, "human_text": text}))
+            .collect();
+        json!({"steps": steps})
+    }
+
+    #[test]
+    fn test_empty_placeholder_and_long_text() {
+        let long = "Stir. ".repeat(100);
+        let recipe = steps(&[("mix", "   "), ("mix", "TODO: write this"), ("mix", "x"), ("mix", &long), ("mix", "Stir well")]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &TextPolicy::default(), &mut result);
+
+        assert!(!result.valid);
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::EMPTY_TEXT, codes::PLACEHOLDER_TEXT, codes::PLACEHOLDER_TEXT, codes::LONG_TEXT]);
+        assert_eq!(result.warnings[0], "Step 1: human_text looks like a placeholder ('todo')");
+    }
+
+    #[test]
+    fn test_action_mention_is_opt_in() {
+        let recipe = steps(&[("bake", "Put the tray in the oven"), ("bake", "Stretch the dough")]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &TextPolicy::default(), &mut result);
+        assert!(result.issues.is_empty());
+
+        let policy = TextPolicy { require_action_mention: true, max_length: 10, ..TextPolicy::default() };
+        let mut result = ValidationResult::new();
+        check(&recipe, &policy, &mut result);
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::LONG_TEXT, codes::LONG_TEXT, codes::ACTION_NOT_MENTIONED]);
+        assert_eq!(result.warnings[2], "Step 1: human_text never mentions its action 'bake'");
+    }
+
+    #[test]
+    fn test_long_conditional_sentences() {
+        let recipe = steps(&[
+            ("mix", "Add salt if needed."),
+            ("knead", "Knead for ten minutes. If the dough still sticks to your hands after kneading, dust it with a little flour."),
+            ("mix", "Stir gently and fold in the herbs, chopped finely beforehand."),
+        ]);
+        let mut result = ValidationResult::new();
+        check(&recipe, &TextPolicy::default(), &mut result);
+
+        let found: Vec<&str> = result.issues.iter().map(|i| i.code).collect();
+        assert_eq!(found, vec![codes::CONDITIONAL_TEXT]);
+        assert_eq!(result.warnings[0], "Step 1: human_text contains conditional advice ('if'); consider moving it into tips");
+    }
+}
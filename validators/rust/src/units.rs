@@ -0,0 +1,228 @@
+// Unit conversion
+//
+// Every `Unit` belongs to a `Dimension`. Mass units convert through grams,
+// volume units through millilitres and counts through pieces (a dozen is 12).
+// The US customary factors are the exact legal definitions: 1 lb is
+// 453.59237 g and 1 oz a sixteenth of it (28.349523125 g); 1 US gallon is
+// 3785.411784 ml, a quart a quarter, a pint an eighth and a fluid ounce a
+// 128th of it (29.5735295625 ml), a cup 8 fl oz, a tablespoon half a fluid
+// ounce and a teaspoon a third of a tablespoon (4.92892159375 ml). Nothing
+// converts between dimensions; mass to volume needs a density the unit
+// does not carry.
+//
+// `pinch`, `dash`, `handful` and `to-taste` are Special: each is its own
+// base, so a value converts only to the same unit.
+
+use crate::Unit;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+/// What a unit measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dimension {
+    /// Converts through grams
+    Mass,
+    /// Converts through millilitres
+    Volume,
+    /// Converts through pieces
+    Count,
+    /// Converts only to itself
+    Special,
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Dimension::Mass => "mass",
+            Dimension::Volume => "volume",
+            Dimension::Count => "count",
+            Dimension::Special => "special",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A conversion between units that cannot be made
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    /// The units measure different things, e.g. `cup` to `g`
+    CrossDimension { from: Unit, to: Unit },
+    /// Two different special units, e.g. `pinch` to `dash`
+    Unmeasured { from: Unit, to: Unit },
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionError::CrossDimension { from, to } => write!(
+                f,
+                "cannot convert {} ({}) to {} ({})",
+                from.name(),
+                from.dimension(),
+                to.name(),
+                to.dimension()
+            ),
+            ConversionError::Unmeasured { from, to } => {
+                write!(f, "cannot convert {} to {}: neither is a measured amount", from.name(), to.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl Unit {
+    /// The unit a document spells `name`, such as "fl-oz"
+    pub fn parse(name: &str) -> Option<Unit> {
+        serde_json::from_value(Value::from(name)).ok()
+    }
+
+    /// The unit as documents spell it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Unit::Mg => "mg",
+            Unit::G => "g",
+            Unit::Kg => "kg",
+            Unit::Oz => "oz",
+            Unit::Lb => "lb",
+            Unit::Ml => "ml",
+            Unit::L => "l",
+            Unit::Tsp => "tsp",
+            Unit::Tbsp => "tbsp",
+            Unit::Cup => "cup",
+            Unit::FlOz => "fl-oz",
+            Unit::Pt => "pt",
+            Unit::Qt => "qt",
+            Unit::Gal => "gal",
+            Unit::Pcs => "pcs",
+            Unit::Dozen => "dozen",
+            Unit::Pinch => "pinch",
+            Unit::Dash => "dash",
+            Unit::Handful => "handful",
+            Unit::ToTaste => "to-taste",
+        }
+    }
+
+    /// What the unit measures
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Mg | Unit::G | Unit::Kg | Unit::Oz | Unit::Lb => Dimension::Mass,
+            Unit::Ml | Unit::L | Unit::Tsp | Unit::Tbsp | Unit::Cup | Unit::FlOz | Unit::Pt | Unit::Qt | Unit::Gal => {
+                Dimension::Volume
+            }
+            Unit::Pcs | Unit::Dozen => Dimension::Count,
+            Unit::Pinch | Unit::Dash | Unit::Handful | Unit::ToTaste => Dimension::Special,
+        }
+    }
+
+    /// Grams, millilitres or pieces in one of this unit; 1 for special units
+    pub fn base_factor(&self) -> f64 {
+        match self {
+            Unit::Mg => 0.001,
+            Unit::G => 1.0,
+            Unit::Kg => 1000.0,
+            Unit::Oz => 28.349523125,
+            Unit::Lb => 453.59237,
+            Unit::Ml => 1.0,
+            Unit::L => 1000.0,
+            Unit::Tsp => 4.92892159375,
+            Unit::Tbsp => 14.78676478125,
+            Unit::Cup => 236.5882365,
+            Unit::FlOz => 29.5735295625,
+            Unit::Pt => 473.176473,
+            Unit::Qt => 946.352946,
+            Unit::Gal => 3785.411784,
+            Unit::Pcs => 1.0,
+            Unit::Dozen => 12.0,
+            Unit::Pinch | Unit::Dash | Unit::Handful | Unit::ToTaste => 1.0,
+        }
+    }
+
+    /// `value` of this unit in grams, millilitres or pieces
+    pub fn to_base(&self, value: f64) -> f64 {
+        value * self.base_factor()
+    }
+
+    /// `value` grams, millilitres or pieces in this unit
+    pub fn from_base(&self, value: f64) -> f64 {
+        value / self.base_factor()
+    }
+}
+
+/// `value` in unit `from` expressed in unit `to`
+pub fn convert(value: f64, from: Unit, to: Unit) -> Result<f64, ConversionError> {
+    if from == to {
+        return Ok(value);
+    }
+    match (from.dimension(), to.dimension()) {
+        (Dimension::Special, Dimension::Special) => Err(ConversionError::Unmeasured { from, to }),
+        (a, b) if a != b => Err(ConversionError::CrossDimension { from, to }),
+        _ => Ok(to.from_base(from.to_base(value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::UNITS;
+
+    #[test]
+    fn test_every_unit_converts_to_its_base() {
+        let table = [
+            (Unit::Mg, Dimension::Mass, 0.001),
+            (Unit::G, Dimension::Mass, 1.0),
+            (Unit::Kg, Dimension::Mass, 1000.0),
+            (Unit::Oz, Dimension::Mass, 28.349523125),
+            (Unit::Lb, Dimension::Mass, 453.59237),
+            (Unit::Ml, Dimension::Volume, 1.0),
+            (Unit::L, Dimension::Volume, 1000.0),
+            (Unit::Tsp, Dimension::Volume, 4.92892159375),
+            (Unit::Tbsp, Dimension::Volume, 14.78676478125),
+            (Unit::Cup, Dimension::Volume, 236.5882365),
+            (Unit::FlOz, Dimension::Volume, 29.5735295625),
+            (Unit::Pt, Dimension::Volume, 473.176473),
+            (Unit::Qt, Dimension::Volume, 946.352946),
+            (Unit::Gal, Dimension::Volume, 3785.411784),
+            (Unit::Pcs, Dimension::Count, 1.0),
+            (Unit::Dozen, Dimension::Count, 12.0),
+            (Unit::Pinch, Dimension::Special, 1.0),
+            (Unit::Dash, Dimension::Special, 1.0),
+            (Unit::Handful, Dimension::Special, 1.0),
+            (Unit::ToTaste, Dimension::Special, 1.0),
+        ];
+        assert_eq!(table.len(), UNITS.len());
+
+        for (unit, dimension, factor) in table {
+            assert_eq!(Unit::parse(unit.name()), Some(unit), "{}", unit.name());
+            assert!(UNITS.contains(&unit.name()), "{}", unit.name());
+            assert_eq!(unit.dimension(), dimension, "{}", unit.name());
+            assert_eq!(unit.to_base(2.0), 2.0 * factor, "{}", unit.name());
+            assert!((unit.from_base(unit.to_base(3.5)) - 3.5).abs() < 1e-12, "{}", unit.name());
+            assert_eq!(convert(3.5, unit, unit), Ok(3.5));
+        }
+    }
+
+    #[test]
+    fn test_conversions_within_and_across_dimensions() {
+        let close = |from: f64, to: f64| (from - to).abs() < 1e-9;
+        assert!(close(convert(2.0, Unit::Cup, Unit::Ml).unwrap(), 473.176473));
+        assert!(close(convert(1.0, Unit::Tbsp, Unit::Tsp).unwrap(), 3.0));
+        assert!(close(convert(1.0, Unit::Gal, Unit::FlOz).unwrap(), 128.0));
+        assert!(close(convert(1.0, Unit::Lb, Unit::Oz).unwrap(), 16.0));
+        assert!(close(convert(1500.0, Unit::G, Unit::Kg).unwrap(), 1.5));
+        assert!(close(convert(2.0, Unit::Dozen, Unit::Pcs).unwrap(), 24.0));
+
+        let err = convert(2.0, Unit::Cup, Unit::G).unwrap_err();
+        assert_eq!(err, ConversionError::CrossDimension { from: Unit::Cup, to: Unit::G });
+        assert_eq!(err.to_string(), "cannot convert cup (volume) to g (mass)");
+        assert!(convert(1.0, Unit::Pcs, Unit::G).is_err());
+        assert!(convert(1.0, Unit::Pinch, Unit::G).is_err());
+        assert_eq!(
+            convert(1.0, Unit::Pinch, Unit::Dash).unwrap_err().to_string(),
+            "cannot convert pinch to dash: neither is a measured amount"
+        );
+        assert_eq!(Unit::parse("grams"), None);
+    }
+}
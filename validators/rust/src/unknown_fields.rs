@@ -0,0 +1,145 @@
+// Unknown fields
+//
+// The schema leaves objects open, so a misspelled key such as "alergens" is
+// accepted and the field it was meant to be is reported missing instead.
+// With `set_unknown_fields` (the CLI's `--strict`) every object the schema
+// declares properties for is checked: a key it does not declare is an
+// error, with the declared key it most likely meant when one is within an
+// edit or two. Keys starting with `x-` and everything under `extensions`
+// are vendor data and always allowed. Objects the schema does not describe
+// member by member (`oneOf` authors) are not checked, and neither are step
+// parameters, done-when conditions and nutrient tables, whose declared
+// members are only the common ones.
+
+use crate::terms::distance;
+use crate::{codes, introspect, ValidationResult};
+use serde_json::Value;
+
+/// Schema paths of objects open to members the schema does not list
+const OPEN: &[&str] = &[
+    "/steps/*/params",
+    "/steps/*/done_when",
+    "/ingredients/*/nutritional/per_100g",
+    "/ingredients/*/nutritional/per_serving",
+];
+
+/// Escape a key for use in a JSON pointer
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// The declared key `key` most likely stands for, if only one is close
+fn suggestion<'a>(key: &str, declared: &[&'a str]) -> Option<&'a str> {
+    let key = key.to_lowercase();
+    let allowed = if key.chars().count() < 5 { 1 } else { 2 };
+    let close: Vec<(usize, &str)> = declared.iter().map(|d| (distance(&key, d), *d)).filter(|(d, _)| *d <= allowed).collect();
+    let best = close.iter().map(|(d, _)| *d).min()?;
+    let mut nearest = close.iter().filter(|(d, _)| *d == best);
+    match (nearest.next(), nearest.next()) {
+        (Some((_, declared)), None) => Some(declared),
+        _ => None,
+    }
+}
+
+/// Report keys the schema does not declare for the object they appear in
+pub fn check(schema: &Value, recipe: &Value, result: &mut ValidationResult) {
+    walk(schema, recipe, "", "", result);
+}
+
+fn walk(schema: &Value, value: &Value, schema_path: &str, pointer: &str, result: &mut ValidationResult) {
+    match value {
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                walk(schema, item, &format!("{}/*", schema_path), &format!("{}/{}", pointer, i), result);
+            }
+        }
+        Value::Object(members) => {
+            if OPEN.contains(&schema_path) {
+                return;
+            }
+            let Some(declared) = introspect::properties(schema, schema_path) else {
+                return;
+            };
+            for (key, member) in members {
+                let path = format!("{}/{}", pointer, escape(key));
+                if declared.contains(&key.as_str()) {
+                    if key != "extensions" {
+                        walk(schema, member, &format!("{}/{}", schema_path, key), &path, result);
+                    }
+                } else if !key.starts_with("x-") {
+                    match suggestion(key, &declared) {
+                        Some(meant) => result.error(codes::MISSPELLED_FIELD, &[("path", &path), ("field", key), ("suggestion", &meant)]),
+                        None => result.error(codes::UNKNOWN_FIELD, &[("path", &path), ("field", key)]),
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{codes, RCIPValidator};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_unknown_fields_are_reported_in_strict_mode() {
+        let mut recipe: Value = serde_json::from_str(include_str!("../../../examples/simple-example.rcip")).unwrap();
+        let allergens = recipe["ingredients"][0].as_object_mut().unwrap().remove("allergens").unwrap();
+        recipe["ingredients"][0]["alergens"] = allergens;
+        recipe["meta"]["total_time_minuts"] = json!(8);
+        recipe["meta"]["flavour"] = json!("sweet");
+        recipe["meta"]["x-internal"] = json!(true);
+        recipe["extensions"]["acme"] = json!({"sku": "123"});
+        recipe["steps"][1]["depends_on"] = json!([recipe["steps"][0]["step_id"]]);
+
+        let mut validator = RCIPValidator::with_bundled_schema("0.1").unwrap();
+        let lenient = validator.validate(&recipe);
+        assert!(lenient.issues.iter().any(|i| i.code == codes::MISSING_ALLERGENS));
+        assert!(!lenient.issues.iter().any(|i| i.code == codes::UNKNOWN_FIELD || i.code == codes::MISSPELLED_FIELD));
+
+        validator.set_unknown_fields(true);
+        let strict = validator.validate(&recipe);
+        let found: Vec<(&str, &str)> = strict
+            .issues
+            .iter()
+            .filter(|i| i.code == codes::UNKNOWN_FIELD || i.code == codes::MISSPELLED_FIELD)
+            .map(|i| (i.code, i.pointer.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (codes::MISSPELLED_FIELD, "/ingredients/0/alergens"),
+                (codes::UNKNOWN_FIELD, "/meta/flavour"),
+                (codes::MISSPELLED_FIELD, "/meta/total_time_minuts"),
+            ]
+        );
+        assert!(strict.errors.contains(&"/ingredients/0/alergens: unknown field 'alergens'; did you mean 'allergens'?".to_string()));
+
+        let mut constructed = RCIPValidator::new_strict("0.1");
+        constructed.init_bundled().unwrap();
+        assert_eq!(constructed.validate(&recipe).issues, strict.issues);
+    }
+
+    #[test]
+    fn test_examples_have_no_unknown_fields() {
+        let mut validator = RCIPValidator::new_strict("0.1");
+        validator.init_bundled().unwrap();
+        for example in [
+            include_str!("../../../examples/simple-example.rcip"),
+            include_str!("../../../examples/margherita-pizza.rcip"),
+            include_str!("../../../examples/ukrainian-borscht.rcip"),
+        ] {
+            let recipe: Value = serde_json::from_str(example).unwrap();
+            let result = validator.validate(&recipe);
+            let unknown: Vec<&str> = result
+                .issues
+                .iter()
+                .filter(|i| i.code == codes::UNKNOWN_FIELD || i.code == codes::MISSPELLED_FIELD)
+                .map(|i| i.pointer.as_str())
+                .collect();
+            assert!(unknown.is_empty(), "{}: {:?}", recipe["meta"]["name"], unknown);
+        }
+    }
+}
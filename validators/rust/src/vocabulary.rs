@@ -0,0 +1,112 @@
+// Cooking vocabulary
+//
+// Maps words in free text to the cooking actions they name. A word matches
+// an action directly ("bake"), through an inflection ("chopped", "bakes"),
+// or through a synonym ("whisk" is `mix`). The importer uses it to guess the
+// action of a direction paragraph, and the text lint to check that a step's
+// text talks about its declared action; the lint also accepts context words
+// such as "oven" for `bake`.
+
+use crate::COOKING_ACTIONS;
+
+/// Words that name an action without being its canonical name
+pub const ACTION_SYNONYMS: &[(&str, &str)] = &[
+    ("stir", "mix"), ("whisk", "mix"), ("beat", "mix"), ("toss", "mix"),
+    ("preheat", "heat"), ("cook", "heat"), ("warm", "heat"), ("melt", "heat"),
+    ("pour", "add"), ("place", "add"), ("put", "add"), ("transfer", "add"), ("season", "add"),
+    ("sprinkle", "garnish"), ("serve", "garnish"),
+    ("refrigerate", "chill"), ("drain", "strain"), ("sear", "fry"), ("brown", "fry"),
+    ("toast", "roast"), ("puree", "blend"), ("marinate", "rest"), ("let", "rest"),
+];
+
+/// Context words that show a text is about an action without naming it
+const ACTION_HINTS: &[(&str, &[&str])] = &[
+    ("bake", &["oven", "baking"]),
+    ("roast", &["oven"]),
+    ("heat", &["stove", "pan", "oven", "hot"]),
+    ("boil", &["boiling"]),
+    ("chill", &["fridge", "refrigerator", "cold"]),
+    ("freeze", &["freezer", "frozen"]),
+    ("cool", &["cold", "room"]),
+    ("ferment", &["rise", "proof"]),
+    ("proof", &["rise", "ferment"]),
+    ("rest", &["wait", "sit", "stand"]),
+    ("wait", &["rest", "sit", "stand"]),
+    ("combine", &["mix", "stir", "add"]),
+    ("mix", &["combine", "stir"]),
+    ("garnish", &["top", "drizzle", "sprinkle"]),
+    ("measure", &["weigh"]),
+    ("weigh", &["measure"]),
+];
+
+/// Lowercase forms a word may be an inflection of, the word itself first
+pub fn stems(word: &str) -> Vec<String> {
+    let word = word.to_lowercase();
+    let mut candidates = vec![word.clone(), word.trim_end_matches('s').to_string()];
+    for suffix in ["ing", "ed"] {
+        if let Some(stem) = word.strip_suffix(suffix) {
+            candidates.push(stem.to_string());
+            candidates.push(format!("{}e", stem));
+            // "chopped" -> "chop"
+            let mut last = stem.chars().rev();
+            if let (Some(a), Some(b)) = (last.next(), last.next()) {
+                if a == b {
+                    candidates.push(stem[..stem.len() - a.len_utf8()].to_string());
+                }
+            }
+        }
+    }
+    candidates
+}
+
+/// The action a single word names, if any
+pub fn action_of(word: &str) -> Option<&'static str> {
+    for candidate in stems(word) {
+        if let Some(action) = COOKING_ACTIONS.iter().copied().find(|a| *a == candidate) {
+            return Some(action);
+        }
+        if let Some((_, action)) = ACTION_SYNONYMS.iter().find(|(w, _)| *w == candidate) {
+            return Some(action);
+        }
+    }
+    None
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphabetic()).filter(|w| !w.is_empty())
+}
+
+/// Actions named in a text, in order of appearance
+pub fn actions_in(text: &str) -> impl Iterator<Item = &'static str> + '_ {
+    words(text).filter_map(action_of)
+}
+
+/// Whether a text names `action`, one of its synonyms or a context word for it
+pub fn mentions(text: &str, action: &str) -> bool {
+    let hints = ACTION_HINTS.iter().find(|(a, _)| *a == action).map(|(_, h)| *h).unwrap_or(&[]);
+    words(text).any(|word| {
+        action_of(word) == Some(action) || stems(word).iter().any(|stem| hints.contains(&stem.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_action_of() {
+        assert_eq!(action_of("Chopped"), Some("chop"));
+        assert_eq!(action_of("whisking"), Some("mix"));
+        assert_eq!(action_of("bakes"), Some("bake"));
+        assert_eq!(action_of("enjoy"), None);
+        assert_eq!(actions_in("Preheat the oven, then bake").collect::<Vec<_>>(), vec!["heat", "bake"]);
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("Put it in the oven for 20 minutes", "bake"));
+        assert!(mentions("Let the dough rise overnight", "ferment"));
+        assert!(mentions("Stir in the sugar", "mix"));
+        assert!(!mentions("Stir in the sugar", "bake"));
+    }
+}
@@ -0,0 +1,121 @@
+// Warm start for serverless handlers
+//
+// A function-as-a-service handler pays for every cold start and may find the
+// filesystem read-only. `global_validator` hands out one process-wide
+// validator per bundled schema version, compiled on first use from the
+// schema embedded in the binary; every later call, from any thread, gets the
+// same instance. Validation through it goes via `&self` (`validate`,
+// `validate_bytes`) and never updates statistics, so concurrent invocations
+// share it without locking. Building with the `no-fs` feature compiles the
+// file-based API (`init`, `validate_file`, `validate_directory`, projects and
+// the CLI) out of the library altogether.
+
+use crate::{RCIPError, RCIPValidator};
+use lazy_static::lazy_static;
+
+/// Schema versions embedded in the binary, with their JSON source
+pub const BUNDLED_SCHEMAS: &[(&str, &str)] = &[
+    ("0.1", include_str!("../../../schemas/rcip-v0.1.json")),
+];
+
+lazy_static! {
+    static ref V0_1: Result<RCIPValidator, String> = build("0.1");
+}
+
+/// Embedded JSON source of a schema version
+pub fn bundled_schema(version: &str) -> Option<&'static str> {
+    BUNDLED_SCHEMAS.iter().find(|(v, _)| *v == version).map(|(_, schema)| *schema)
+}
+
+fn build(version: &str) -> Result<RCIPValidator, String> {
+    let mut validator = RCIPValidator::new(version);
+    // A bundled schema that does not compile is a build error, not a reason
+    // to run degraded
+    validator.set_require_schema(true);
+    validator.init_bundled().map_err(|e| e.to_string())?;
+    Ok(validator)
+}
+
+/// Shared validator for a bundled schema version, with default settings.
+/// Safe to call from concurrent invocations; the first call compiles the
+/// schema and the rest return the same instance
+pub fn global_validator(version: &str) -> Result<&'static RCIPValidator, RCIPError> {
+    let validator: &'static Result<RCIPValidator, String> = match version {
+        "0.1" => &V0_1,
+        _ => return Err(RCIPError::UnknownSchemaVersion(version.to_string())),
+    };
+    validator.as_ref().map_err(|e| RCIPError::SchemaError(e.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codes;
+    use std::thread;
+
+    fn example() -> Vec<u8> {
+        include_bytes!("../../../examples/simple-example.rcip").to_vec()
+    }
+
+    #[test]
+    fn test_global_validator_is_shared() {
+        let address = || global_validator("0.1").unwrap() as *const RCIPValidator as usize;
+        assert_eq!(address(), thread::spawn(address).join().unwrap());
+        assert!(global_validator("9.9").is_err());
+        assert_eq!(BUNDLED_SCHEMAS.iter().map(|(v, _)| *v).collect::<Vec<_>>(), vec!["0.1"]);
+    }
+
+    #[test]
+    fn test_validate_bytes_concurrently() {
+        let bytes = example();
+        let mut validator = RCIPValidator::new("0.1");
+        validator.init_from_str(bundled_schema("0.1").unwrap()).unwrap();
+        let expected = validator.validate_recipe(&serde_json::from_slice(&bytes).unwrap());
+
+        let shared = global_validator("0.1").unwrap();
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..4).map(|_| s.spawn(|| shared.validate_bytes(&bytes))).collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for result in results {
+            assert!(result.valid);
+            assert_eq!(result.issues, expected.issues);
+        }
+
+        let broken = shared.validate_bytes(b"{\"meta\": ");
+        assert!(!broken.valid);
+        assert_eq!(broken.issues[0].code, codes::READ_FAILED);
+    }
+
+    /// Read syscalls made by this thread so far, from a single read of
+    /// `/proc/thread-self/io`
+    #[cfg(target_os = "linux")]
+    fn read_syscalls() -> u64 {
+        use std::io::Read;
+        let mut buf = [0u8; 4096];
+        let mut file = std::fs::File::open("/proc/thread-self/io").unwrap();
+        let len = file.read(&mut buf).unwrap();
+        let io = std::str::from_utf8(&buf[..len]).unwrap();
+        io.lines().find_map(|l| l.strip_prefix("syscr: ")).unwrap().trim().parse().unwrap()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_no_reads_while_validating_in_memory() {
+        let bytes = example();
+        let baseline = read_syscalls();
+        let probe = read_syscalls() - baseline;
+
+        // Runs on a fresh thread, so the validator and every lazily built
+        // table it uses may be initialised here
+        let reads = thread::spawn(move || {
+            let before = read_syscalls();
+            let result = global_validator("0.1").unwrap().validate_bytes(&bytes);
+            assert!(result.valid);
+            read_syscalls() - before
+        })
+        .join()
+        .unwrap();
+        assert_eq!(reads, probe);
+    }
+}
@@ -0,0 +1,315 @@
+// Authoring wizard
+//
+// `rcip init` builds a first recipe from a few answers: name, author,
+// servings, then ingredients (name, amount, unit, allergens) and steps
+// (action, text, targets). Ids are generated in listing order and step
+// targets are picked from the ingredients and earlier steps, so the result
+// passes the reference checks without the author knowing the id formats.
+//
+// Every answer can be given as a flag, which makes the command scriptable;
+// with the `cli-interactive` feature the wizard prompts for whatever the
+// flags left open. Ingredients are written `NAME;AMOUNT;UNIT[;ALLERGEN,...]`
+// and steps `ACTION;TEXT[;TARGET,...]`, where a target is an ingredient
+// name, an ingredient id or an earlier step id.
+
+use crate::precision::number_value;
+use crate::{allergens, ids, COOKING_ACTIONS, UNITS};
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// An ingredient as entered
+#[derive(Debug, Clone, PartialEq)]
+pub struct IngredientInput {
+    pub name: String,
+    pub value: f64,
+    pub unit: String,
+    pub allergens: Vec<String>,
+}
+
+/// A step as entered; targets as typed, resolved when building
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepInput {
+    pub action: String,
+    pub text: String,
+    pub targets: Vec<String>,
+}
+
+/// Everything the wizard asks for
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Answers {
+    pub name: Option<String>,
+    pub author: Option<String>,
+    pub servings: Option<u32>,
+    pub ingredients: Vec<IngredientInput>,
+    pub steps: Vec<StepInput>,
+}
+
+/// An answer the wizard cannot use
+#[derive(Debug, Clone, PartialEq)]
+pub enum WizardError {
+    Missing(&'static str),
+    BadIngredient { spec: String, problem: String },
+    BadStep { spec: String, problem: String },
+    UnknownTarget { step: usize, target: String },
+}
+
+impl fmt::Display for WizardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WizardError::Missing(what) => write!(f, "missing {}; pass --{} or run interactively", what, what),
+            WizardError::BadIngredient { spec, problem } => write!(f, "ingredient '{}': {}", spec, problem),
+            WizardError::BadStep { spec, problem } => write!(f, "step '{}': {}", spec, problem),
+            WizardError::UnknownTarget { step, target } => write!(
+                f,
+                "step {}: target '{}' is neither an ingredient nor an earlier step",
+                step + 1,
+                target
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WizardError {}
+
+fn list(text: Option<&str>) -> Vec<String> {
+    text.into_iter().flat_map(|t| t.split(',')).map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()
+}
+
+/// Parse `NAME;AMOUNT;UNIT[;ALLERGEN,...]`
+pub fn parse_ingredient(spec: &str) -> Result<IngredientInput, WizardError> {
+    let bad = |problem: String| WizardError::BadIngredient { spec: spec.to_string(), problem };
+    let fields: Vec<&str> = spec.split(';').map(str::trim).collect();
+    if fields.len() < 3 || fields.len() > 4 || fields[0].is_empty() {
+        return Err(bad("expected NAME;AMOUNT;UNIT[;ALLERGEN,...]".to_string()));
+    }
+
+    let value: f64 = fields[1].parse().ok().filter(|v: &f64| v.is_finite() && *v >= 0.0)
+        .ok_or_else(|| bad(format!("amount '{}' is not a non-negative number", fields[1])))?;
+    if !UNITS.contains(&fields[2]) {
+        return Err(bad(format!("unknown unit '{}'", fields[2])));
+    }
+    let allergens = list(fields.get(3).copied());
+    if let Some(unknown) = allergens.iter().find(|a| !allergens::VOCABULARY.contains(&a.as_str())) {
+        return Err(bad(format!("unknown allergen '{}'", unknown)));
+    }
+
+    Ok(IngredientInput { name: fields[0].to_string(), value, unit: fields[2].to_string(), allergens })
+}
+
+/// Parse `ACTION;TEXT[;TARGET,...]`
+pub fn parse_step(spec: &str) -> Result<StepInput, WizardError> {
+    let bad = |problem: String| WizardError::BadStep { spec: spec.to_string(), problem };
+    let fields: Vec<&str> = spec.splitn(3, ';').map(str::trim).collect();
+    if fields.len() < 2 || fields[1].is_empty() {
+        return Err(bad("expected ACTION;TEXT[;TARGET,...]".to_string()));
+    }
+    if !COOKING_ACTIONS.contains(&fields[0]) {
+        return Err(bad(format!("unknown action '{}'", fields[0])));
+    }
+    Ok(StepInput { action: fields[0].to_string(), text: fields[1].to_string(), targets: list(fields.get(2).copied()) })
+}
+
+/// Id of the ingredient at a listing position
+pub fn ingredient_id(index: usize) -> String {
+    ids::generate_ingredient_id(index)
+}
+
+/// Id of the step at a listing position
+pub fn step_id(index: usize) -> String {
+    ids::generate_step_id(index)
+}
+
+/// Target reference for what the author typed, looking only at earlier steps
+fn resolve_target(target: &str, ingredients: &[IngredientInput], step: usize) -> Option<String> {
+    if let Some(i) = ingredients.iter().position(|ing| ing.name.eq_ignore_ascii_case(target)) {
+        return Some(ingredient_id(i));
+    }
+    if (0..ingredients.len()).any(|i| ingredient_id(i) == target) {
+        return Some(target.to_string());
+    }
+    let id = target.strip_suffix(":result").unwrap_or(target);
+    (0..step).find(|i| step_id(*i) == id).map(|i| format!("{}:result", step_id(i)))
+}
+
+/// Build the recipe document from complete answers
+pub fn build(answers: &Answers) -> Result<Value, WizardError> {
+    let name = answers.name.as_deref().ok_or(WizardError::Missing("name"))?;
+    let author = answers.author.as_deref().ok_or(WizardError::Missing("author"))?;
+    if answers.ingredients.is_empty() {
+        return Err(WizardError::Missing("ingredient"));
+    }
+    if answers.steps.is_empty() {
+        return Err(WizardError::Missing("step"));
+    }
+
+    let ingredients: Vec<Value> = answers
+        .ingredients
+        .iter()
+        .enumerate()
+        .map(|(i, ing)| {
+            json!({
+                "id": ingredient_id(i),
+                "name": ing.name,
+                "human_amount": format!("{} {}", ing.value, ing.unit),
+                "machine_amount": {"value": number_value(ing.value), "unit": ing.unit},
+                "allergens": ing.allergens
+            })
+        })
+        .collect();
+
+    let mut steps = Vec::new();
+    for (i, step) in answers.steps.iter().enumerate() {
+        let mut value = json!({"step_id": step_id(i), "human_text": step.text, "action": step.action});
+        if !step.targets.is_empty() {
+            let targets = step
+                .targets
+                .iter()
+                .map(|t| {
+                    resolve_target(t, &answers.ingredients, i)
+                        .ok_or_else(|| WizardError::UnknownTarget { step: i, target: t.clone() })
+                })
+                .collect::<Result<Vec<String>, WizardError>>()?;
+            value["target"] = json!(targets);
+        }
+        steps.push(value);
+    }
+
+    let now = Utc::now();
+    let mut meta = json!({
+        "name": name,
+        "author": author,
+        "created_date": now.to_rfc3339_opts(SecondsFormat::Secs, true)
+    });
+    if let Some(servings) = answers.servings {
+        meta["servings"] = json!({"amount": servings});
+    }
+
+    Ok(json!({
+        "rcip_version": "0.1",
+        "id": ids::generate_recipe_id(),
+        "meta": meta,
+        "ingredients": ingredients,
+        "steps": steps
+    }))
+}
+
+/// Prompt for everything the flags left open
+#[cfg(feature = "cli-interactive")]
+pub fn prompt(answers: &mut Answers) -> dialoguer::Result<()> {
+    use dialoguer::{Confirm, Input, MultiSelect, Select};
+
+    if answers.name.is_none() {
+        answers.name = Some(Input::new().with_prompt("Recipe name").interact_text()?);
+    }
+    if answers.author.is_none() {
+        answers.author = Some(Input::new().with_prompt("Author").interact_text()?);
+    }
+    if answers.servings.is_none() {
+        answers.servings = Some(Input::new().with_prompt("Servings").default(4).interact_text()?);
+    }
+
+    let mut more = answers.ingredients.is_empty();
+    while more {
+        let name: String = Input::new().with_prompt("Ingredient name").interact_text()?;
+        let value: f64 = Input::new()
+            .with_prompt("Amount")
+            .validate_with(|v: &f64| if v.is_finite() && *v >= 0.0 { Ok(()) } else { Err("must be 0 or more") })
+            .interact_text()?;
+        let unit = Select::new().with_prompt("Unit").items(UNITS).default(1).interact()?;
+        let picked = MultiSelect::new().with_prompt("Allergens (space to select)").items(allergens::VOCABULARY).interact()?;
+        answers.ingredients.push(IngredientInput {
+            name,
+            value,
+            unit: UNITS[unit].to_string(),
+            allergens: picked.into_iter().map(|i| allergens::VOCABULARY[i].to_string()).collect(),
+        });
+        more = Confirm::new().with_prompt("Add another ingredient?").default(true).interact()?;
+    }
+
+    let mut more = answers.steps.is_empty();
+    while more {
+        let action = Select::new().with_prompt("Action").items(COOKING_ACTIONS).default(0).interact()?;
+        let text: String = Input::new().with_prompt("Instruction").interact_text()?;
+
+        let mut choices: Vec<(String, String)> = answers
+            .ingredients
+            .iter()
+            .enumerate()
+            .map(|(i, ing)| (ingredient_id(i), format!("{} ({})", ing.name, ingredient_id(i))))
+            .collect();
+        choices.extend((0..answers.steps.len()).map(|i| (step_id(i), format!("Result of step {}", i + 1))));
+        let labels: Vec<&str> = choices.iter().map(|(_, label)| label.as_str()).collect();
+        let picked = MultiSelect::new().with_prompt("Targets (space to select)").items(&labels[..]).interact()?;
+
+        answers.steps.push(StepInput {
+            action: COOKING_ACTIONS[action].to_string(),
+            text,
+            targets: picked.into_iter().map(|i| choices[i].0.clone()).collect(),
+        });
+        more = Confirm::new().with_prompt("Add another step?").default(true).interact()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answers() -> Answers {
+        Answers {
+            name: Some("Pancakes".to_string()),
+            author: Some("Test Cook".to_string()),
+            servings: Some(2),
+            ingredients: vec![
+                parse_ingredient("Flour; 200; g; wheat, gluten").unwrap(),
+                parse_ingredient("Milk;300;ml;milk").unwrap(),
+                parse_ingredient("Salt;1;pinch").unwrap(),
+            ],
+            steps: vec![
+                parse_step("mix;Whisk flour, milk and salt;flour,ing-0002,Salt").unwrap(),
+                parse_step("fry;Fry thin pancakes; s-01").unwrap(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_parse_specs() {
+        assert_eq!(answers().ingredients[0].allergens, vec!["wheat", "gluten"]);
+        assert_eq!(answers().steps[0].text, "Whisk flour, milk and salt");
+
+        let error = parse_ingredient("Flour;200;grams").unwrap_err();
+        assert_eq!(error.to_string(), "ingredient 'Flour;200;grams': unknown unit 'grams'");
+        assert!(parse_ingredient("Flour;-1;g").is_err());
+        assert!(parse_ingredient("Nuts;1;g;almonds").is_err());
+        assert_eq!(parse_step("whisk;Whisk").unwrap_err().to_string(), "step 'whisk;Whisk': unknown action 'whisk'");
+    }
+
+    #[cfg(not(feature = "no-fs"))]
+    #[test]
+    fn test_built_recipe_validates() {
+        let recipe = build(&answers()).unwrap();
+        assert_eq!(recipe["steps"][0]["target"], json!(["ing-0001", "ing-0002", "ing-0003"]));
+        assert_eq!(recipe["steps"][1]["target"], json!(["s-01:result"]));
+        assert_eq!(recipe["ingredients"][0]["human_amount"], "200 g");
+
+        let mut validator = crate::RCIPValidator::new("0.1");
+        validator.init(None).unwrap();
+        let result = validator.validate_recipe(&recipe);
+        assert!(result.valid, "{:?}", result.errors);
+    }
+
+    #[test]
+    fn test_missing_and_unknown_answers() {
+        let mut incomplete = answers();
+        incomplete.author = None;
+        assert_eq!(build(&incomplete).unwrap_err().to_string(), "missing author; pass --author or run interactively");
+
+        let mut forward = answers();
+        forward.steps[0].targets = vec!["s-02".to_string()];
+        assert_eq!(
+            build(&forward).unwrap_err().to_string(),
+            "step 1: target 's-02' is neither an ingredient nor an earlier step"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+// Exit codes of the rcip-validator binary; with no-fs the binary only reports
+// that it is unavailable, so there is nothing to test
+#![cfg(not(feature = "no-fs"))]
+
+use assert_cmd::Command;
+use std::path::{Path, PathBuf};
+
+fn examples() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples")
+}
+
+fn validator() -> Command {
+    Command::cargo_bin("rcip-validator").unwrap()
+}
+
+#[test]
+fn valid_file_exits_zero() {
+    validator().arg(examples().join("simple-example.rcip")).assert().code(0);
+}
+
+#[test]
+fn invalid_file_exits_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.rcip");
+    let mut recipe: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(examples().join("simple-example.rcip")).unwrap()).unwrap();
+    recipe["id"] = serde_json::json!("not-an-rcip-id");
+    std::fs::write(&path, recipe.to_string()).unwrap();
+
+    validator().arg(&path).assert().code(1);
+}
+
+#[test]
+fn missing_file_exits_two() {
+    let dir = tempfile::tempdir().unwrap();
+    validator().arg(dir.path().join("missing.rcip")).assert().code(2);
+}
+
+#[test]
+fn migrate_to_writes_only_what_the_target_schema_accepts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simple.rcip");
+    std::fs::copy(examples().join("simple-example.rcip"), &path).unwrap();
+    let original = std::fs::read_to_string(&path).unwrap();
+
+    // No schema for 0.2 is bundled, so nothing can be validated or written
+    validator().args(["--migrate-to", "0.2"]).arg(&path).assert().code(2);
+    assert!(!dir.path().join("simple.v0.2.rcip").exists());
+
+    validator().args(["--migrate-to", "0.1"]).arg(&path).assert().code(0);
+    let migrated: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(dir.path().join("simple.v0.1.rcip")).unwrap()).unwrap();
+    assert_eq!(migrated["rcip_version"], "0.1");
+
+    // An invalid result leaves the original alone, even with --write
+    let broken = dir.path().join("broken.rcip");
+    let mut recipe: serde_json::Value = serde_json::from_str(&original).unwrap();
+    recipe["id"] = serde_json::json!("not-an-rcip-id");
+    std::fs::write(&broken, recipe.to_string()).unwrap();
+    validator().args(["--migrate-to", "0.1", "--write"]).arg(&broken).assert().code(1);
+    assert_eq!(std::fs::read_to_string(&broken).unwrap(), recipe.to_string());
+}
+
+#[test]
+fn new_id_prints_a_valid_recipe_id() {
+    let output = validator().arg("--new-id").assert().code(0).get_output().stdout.clone();
+    let id = String::from_utf8(output).unwrap();
+    assert!(rcip_validator::ids::is_valid_recipe_id(id.trim_end()), "{}", id);
+}
+
+#[test]
+fn fix_rewrites_what_it_can_and_reports_the_rest() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("broken.rcip");
+    let mut recipe: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(examples().join("simple-example.rcip")).unwrap()).unwrap();
+    recipe["id"] = serde_json::json!("rcip-123E4567-E89B-12D3-A456-426614174000");
+    recipe["ingredients"][2]["machine_amount"]["unit"] = serde_json::json!("grams");
+    let broken = recipe.to_string();
+    std::fs::write(&path, &broken).unwrap();
+
+    validator().arg(&path).assert().code(1);
+    validator().args(["--fix", "--dry-run"]).arg(&path).assert().code(0);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), broken);
+
+    validator().arg("--fix").arg(&path).assert().code(0);
+    let fixed: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(fixed["ingredients"][2]["machine_amount"]["unit"], "g");
+    validator().arg(&path).assert().code(0);
+
+    recipe["ingredients"][2]["machine_amount"]["unit"] = serde_json::json!("splash");
+    std::fs::write(&path, recipe.to_string()).unwrap();
+    validator().arg("--fix").arg(&path).assert().code(1);
+}
+
+#[test]
+fn canonicalize_check_and_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simple.rcip");
+    let recipe: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(examples().join("simple-example.rcip")).unwrap()).unwrap();
+    std::fs::write(&path, serde_json::to_string(&recipe).unwrap()).unwrap();
+
+    validator().args(["--canonicalize", "--check"]).arg(&path).assert().code(1);
+    validator().args(["--canonicalize", "--write"]).arg(&path).assert().code(0);
+    validator().args(["--canonicalize", "--check"]).arg(&path).assert().code(0);
+    assert!(std::fs::read_to_string(&path).unwrap().starts_with("{\n  \"rcip_version\": \"0.1\",\n  \"id\": "));
+    validator().arg(&path).assert().code(0);
+    validator().args(["--write"]).arg(&path).assert().code(2);
+}
+
+#[test]
+fn scale_prints_or_writes_the_scaled_recipe() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("simple.rcip");
+    std::fs::copy(examples().join("simple-example.rcip"), &path).unwrap();
+
+    let output = validator().args(["--scale", "2.5"]).arg(&path).assert().code(0).get_output().stdout.clone();
+    let scaled: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(scaled["meta"]["servings"]["amount"], 2.5);
+    assert_eq!(scaled["ingredients"][0]["machine_amount"]["value"], 3);
+
+    validator().args(["--scale", "0"]).arg(&path).assert().code(2);
+    validator().args(["--scale", "0.5", "--round-counts", "ceil", "--write"]).arg(&path).assert().code(0);
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written["ingredients"][0]["machine_amount"]["value"], 1);
+    validator().arg(&path).assert().code(0);
+}